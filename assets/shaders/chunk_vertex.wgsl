@@ -0,0 +1,44 @@
+// Unpacks chunk vertices, see `ATTRIBUTE_PACKED_VERTEX` in src/chunks/vertex.rs for the layout.
+
+const POSITION_STEPS: f32 = 16.0;
+const EMISSIVE_STEPS: f32 = 8.0;
+const MAX_LIGHT: f32 = 15.0;
+
+var<private> FACE_NORMALS: array<vec3<f32>, 6> = array<vec3<f32>, 6>(
+    vec3(0.0, 0.0, 1.0),
+    vec3(1.0, 0.0, 0.0),
+    vec3(-1.0, 0.0, 0.0),
+    vec3(0.0, 0.0, -1.0),
+    vec3(0.0, 1.0, 0.0),
+    vec3(0.0, -1.0, 0.0),
+);
+
+struct ChunkVertex {
+  position: vec3<f32>,
+  normal: vec3<f32>,
+  uv: vec2<f32>,
+  texture: u32,
+  emissive: f32,
+  // light reaching the vertex from `0.0` to `1.0`
+  light: f32,
+  // number of blocks crowding the vertex's corner, from `0` to `3`
+  occlusion: u32,
+}
+
+fn unpack_vertex(packed: vec2<u32>) -> ChunkVertex {
+    let corner = vec3(
+        f32(packed.x & 0x3ffu),
+        f32((packed.x >> 10u) & 0x3ffu),
+        f32((packed.x >> 20u) & 0x3ffu),
+    ) / POSITION_STEPS;
+
+    var vertex: ChunkVertex;
+    vertex.position = corner - 0.5;
+    vertex.normal = FACE_NORMALS[packed.y & 0x7u];
+    vertex.uv = vec2(f32((packed.y >> 3u) & 1u), f32((packed.y >> 4u) & 1u));
+    vertex.texture = (packed.y >> 5u) & 0xffu;
+    vertex.emissive = f32((packed.y >> 13u) & 0xffu) / EMISSIVE_STEPS;
+    vertex.light = f32((packed.y >> 21u) & 0xfu) / MAX_LIGHT;
+    vertex.occlusion = (packed.y >> 25u) & 0x3u;
+    return vertex;
+}
@@ -1,13 +1,84 @@
-#import bevy_pbr::{forward_io::VertexOutput, mesh_view_bindings as view_bindings}
+#import bevy_pbr::{
+    mesh_functions,
+    mesh_view_bindings as view_bindings,
+    mesh_view_types::DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT,
+    shadows,
+    view_transformations::position_world_to_clip,
+}
+#import "shaders/chunk_vertex.wgsl"::unpack_vertex
 
 @group(2) @binding(0) var<uniform> material_color: vec4<f32>;
-@group(2) @binding(1) var material_color_texture: texture_2d<f32>;
+@group(2) @binding(1) var material_color_texture: texture_2d_array<f32>;
 @group(2) @binding(2) var material_color_sampler: sampler;
 
+struct ChunkLighting {
+  daylight: f32,
+  debug_cascades: u32,
+  sun_direction: vec3<f32>,
+}
+
+@group(2) @binding(3) var<uniform> lighting: ChunkLighting;
+
+struct FlatColors {
+  colors: array<vec4<f32>, 256>,
+}
+
+@group(2) @binding(4) var<uniform> flat_colors: FlatColors;
+
+struct ChunkFog {
+  color: vec4<f32>,
+  start: f32,
+  end: f32,
+}
+
+@group(2) @binding(5) var<uniform> fog: ChunkFog;
+
+struct Vertex {
+  @builtin(instance_index) instance_index: u32,
+  @location(0) packed: vec2<u32>,
+}
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) world_position: vec4<f32>,
+  @location(1) world_normal: vec3<f32>,
+  @location(2) uv: vec2<f32>,
+  @location(3) @interpolate(flat) texture: u32,
+  @location(4) emissive: f32,
+  @location(5) light: f32,
+#ifdef FLAT_COLOR
+  @location(6) color: vec4<f32>,
+#endif
+}
+
 struct FragmentOutput {
   @location(0) color: vec4<f32>
 }
 
+@vertex
+fn vertex(in: Vertex) -> VertexOutput {
+    let vertex = unpack_vertex(in.packed);
+    let world_from_local = mesh_functions::get_world_from_local(in.instance_index);
+
+    var out: VertexOutput;
+    out.world_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4(vertex.position, 1.0));
+    out.position = position_world_to_clip(out.world_position.xyz);
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(vertex.normal, in.instance_index);
+    out.uv = vertex.uv;
+    out.texture = vertex.texture;
+    out.emissive = vertex.emissive;
+    // each occluded corner darkens the vertex, interpolating into soft shadows in creases
+    out.light = vertex.light * (1.0 - 0.2 * f32(vertex.occlusion));
+#ifdef FLAT_COLOR
+    // low spec terrain is lit once per vertex by the sun, without shadows
+    let ambient = mix(0.25, 0.7, lighting.daylight);
+    let brightness = max(dot(out.world_normal, lighting.sun_direction), 0.0);
+    out.light = out.light * (ambient + (1.0 - ambient) * brightness * lighting.daylight);
+    out.color = flat_colors.colors[vertex.texture];
+#endif
+    return out;
+}
+
 @fragment
 fn fragment(
     in: VertexOutput,
@@ -22,12 +93,52 @@ fn fragment(
       discard;
     }
 
-    let brightness = dot(normalize(in.world_normal), normalize(vec3(-0.2, 0.7, 0.2)));
+#ifdef FLAT_COLOR
+    let color_lit = material_color * in.color;
+    var color = vec4(color_lit.rgb * in.light, color_lit.a);
+#else
+    let normal = normalize(in.world_normal);
+    var light_direction = normalize(vec3(-0.2, 0.7, 0.2));
+    var shadow = 1.0;
+    let view_z = dot(vec4<f32>(
+        view_bindings::view.view_from_world[0].z,
+        view_bindings::view.view_from_world[1].z,
+        view_bindings::view.view_from_world[2].z,
+        view_bindings::view.view_from_world[3].z
+    ), in.world_position);
+
+    if view_bindings::lights.n_directional_lights > 0u {
+      let sun = view_bindings::lights.directional_lights[0];
+      light_direction = sun.direction_to_light;
+      if (sun.flags & DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u {
+        shadow = shadows::fetch_directional_shadow(0u, in.world_position, normal, view_z);
+      }
+    }
+
+    let brightness = max(dot(normal, light_direction), 0.0) * shadow;
+
+    let color_lit = material_color * textureSample(material_color_texture, material_color_sampler, in.uv, in.texture);
+    // cutout blocks such as leaves have holes in their texture
+    if color_lit.a < 0.05 {
+      discard;
+    }
+
+    // ambient falls off at night so caves and the night side of hills read darker
+    let ambient = mix(0.25, 0.7, lighting.daylight);
+    var color = vec4(color_lit.rgb * (ambient + (1.0 - ambient) * brightness * lighting.daylight) * in.light, color_lit.a);
+
+    if lighting.debug_cascades != 0u {
+      color = vec4(shadows::cascade_debug_visualization(color.rgb, 0u, view_z), color.a);
+    }
+#endif
 
-    let color_lit = material_color * textureSample(material_color_texture, material_color_sampler, in.uv);
+    // the block's emissive multiplier pushes glowing blocks into HDR for bloom
+    color = color + vec4(color_lit.rgb * in.emissive, 0.0);
 
-    let dark = color_lit * 0.7;
-    let color = mix(dark, color_lit, brightness);
+    // distant terrain fades into the biome's fog, which darkens at night along with the terrain
+    let fog_amount = clamp((dist - fog.start) / max(fog.end - fog.start, 0.001), 0.0, 1.0);
+    let fog_color = fog.color.rgb * mix(0.1, 1.0, lighting.daylight);
+    color = vec4(mix(color.rgb, fog_color, fog_amount), color.a);
 
     var output: FragmentOutput;
     output.color = color;
@@ -0,0 +1,44 @@
+#import bevy_pbr::{
+    mesh_functions,
+    prepass_io::VertexOutput,
+    view_transformations::position_world_to_clip,
+}
+#import "shaders/chunk_vertex.wgsl"::unpack_vertex
+
+// Chunk meshes only carry packed vertices, so shadow maps and other prepasses draw them with
+// this instead of Bevy's prepass vertex shader.
+
+struct Vertex {
+  @builtin(instance_index) instance_index: u32,
+  @location(0) packed: vec2<u32>,
+}
+
+@vertex
+fn vertex(in: Vertex) -> VertexOutput {
+    let vertex = unpack_vertex(in.packed);
+    let world_from_local = mesh_functions::get_world_from_local(in.instance_index);
+
+    var out: VertexOutput;
+    out.world_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4(vertex.position, 1.0));
+    out.position = position_world_to_clip(out.world_position.xyz);
+
+#ifdef DEPTH_CLAMP_ORTHO
+    out.clip_position_unclamped = out.position;
+    out.position.z = min(out.position.z, 1.0);
+#endif
+
+#ifdef NORMAL_PREPASS_OR_DEFERRED_PREPASS
+    out.world_normal = mesh_functions::mesh_normal_local_to_world(vertex.normal, in.instance_index);
+#endif
+
+#ifdef MOTION_VECTOR_PREPASS
+    let previous_world_from_local = mesh_functions::get_previous_world_from_local(in.instance_index);
+    out.previous_world_position = mesh_functions::mesh_position_local_to_world(previous_world_from_local, vec4(vertex.position, 1.0));
+#endif
+
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+    out.instance_index = in.instance_index;
+#endif
+
+    return out;
+}
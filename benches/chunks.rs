@@ -0,0 +1,306 @@
+//! Benchmarks of chunk generation, meshing and saving, run with `cargo bench`.
+//!
+//! Meshing has a single mesher, emitting a quad per visible face, so there is no greedy mesher to
+//! compare it with, and lighting is ambient occlusion worked out per vertex while meshing rather
+//! than light propagated between blocks. The `generate_chunk_mesh` group's smooth and flat
+//! lighting cases are the closest to either.
+
+use std::{hint::black_box, sync::Arc, time::Instant};
+
+use bevy::math::{I64Vec3, IVec3, U16Vec3};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rustcraft::{
+    block::{BlockShape, BlockType},
+    chunks::{
+        chunk::{ChunkCoordinate, ChunkData},
+        codec::{decode_chunk, encode_chunk},
+        generate::{
+            generator::{generate_chunk, generate_chunk_mesh, Lighting},
+            noise::SharedNoise,
+            terrain::{GeneratorRegistry, GENERATOR_KINDS},
+            visibility::{face_masks, FACE_COUNT},
+        },
+        layout::{BlockLayout, BlockStorage, LinearLayout, MortonLayout},
+    },
+    ids::IdMap,
+};
+
+const SEED: u32 = 0;
+const WORLD_HEIGHT: u64 = 256;
+/// A chunk through the surface near the origin, with terrain, caves and air.
+const SURFACE_CHUNK: I64Vec3 = I64Vec3::new(0, 3, 0);
+
+/// A chunk and its neighbours, as the chunk loader meshes it.
+type MeshInput = (Arc<ChunkData>, Vec<Option<Arc<ChunkData>>>);
+
+/// The surface chunk of `chunk_size` and its neighbours.
+fn surface_chunk(chunk_size: u16) -> MeshInput {
+    let noise = SharedNoise::new(SEED);
+    let coord = ChunkCoordinate(SURFACE_CHUNK * 16 / chunk_size as i64);
+    let generate = |coord| {
+        Arc::new(generate_chunk(
+            noise.clone(),
+            coord,
+            WORLD_HEIGHT,
+            chunk_size,
+        ))
+    };
+    let adjacent = coord
+        .adjacent()
+        .into_iter()
+        .map(|coord| Some(generate(coord)))
+        .collect();
+    (generate(coord), adjacent)
+}
+
+/// Every chunk of a 64³ region of surface terrain as chunks of `chunk_size`, with neighbours.
+fn surface_region(chunk_size: u16) -> Vec<MeshInput> {
+    let noise = SharedNoise::new(SEED);
+    let count = 64 / chunk_size as i64;
+    let base_y = 32 / chunk_size as i64;
+    let generate = |coord| {
+        Arc::new(generate_chunk(
+            noise.clone(),
+            coord,
+            WORLD_HEIGHT,
+            chunk_size,
+        ))
+    };
+
+    let mut chunks = vec![];
+    for x in 0..count {
+        for y in base_y..base_y + count {
+            for z in 0..count {
+                let coord = ChunkCoordinate(I64Vec3::new(x, y, z));
+                let adjacent = coord
+                    .adjacent()
+                    .into_iter()
+                    .map(|coord| Some(generate(coord)))
+                    .collect();
+                chunks.push((generate(coord), adjacent));
+            }
+        }
+    }
+    chunks
+}
+
+/// Looks up each neighbour of a block individually, as the mesher did before face masks.
+fn visible_faces_scalar(
+    chunk: &ChunkData,
+    adjacent: &[Option<Arc<ChunkData>>],
+    position: U16Vec3,
+) -> [bool; FACE_COUNT] {
+    let block = chunk.get_block_at(position);
+    if block.shape() != BlockShape::Cube {
+        return [false; FACE_COUNT];
+    }
+    let size = chunk.size as i32;
+    let offsets = [
+        (1, [0, 0, -1]),
+        (2, [1, 0, 0]),
+        (3, [-1, 0, 0]),
+        (0, [0, 0, 1]),
+        (4, [0, 1, 0]),
+        (5, [0, -1, 0]),
+    ];
+
+    offsets.map(|(adjacent_index, offset)| {
+        let neighbour = position.as_ivec3() + IVec3::from_array(offset);
+        let neighbour_block =
+            if neighbour.cmpge(IVec3::ZERO).all() && neighbour.cmplt(IVec3::splat(size)).all() {
+                chunk.get_block_at(neighbour.as_u16vec3())
+            } else {
+                adjacent[adjacent_index]
+                    .as_ref()
+                    .map(|adjacent| {
+                        adjacent.get_block_at(neighbour.rem_euclid(IVec3::splat(size)).as_u16vec3())
+                    })
+                    .unwrap_or_default()
+            };
+        match neighbour_block {
+            BlockType::Air => true,
+            neighbour if neighbour.shape() != BlockShape::Cube => true,
+            neighbour if neighbour.is_transparent() => block != neighbour,
+            _ => false,
+        }
+    })
+}
+
+/// Sums the solid neighbours of every block, the access pattern of meshing.
+fn count_solid_neighbours<L: BlockLayout>(storage: &BlockStorage<L>, size: u16) -> usize {
+    let mut count = 0;
+    for z in 1..size - 1 {
+        for y in 1..size - 1 {
+            for x in 1..size - 1 {
+                let position = U16Vec3::new(x, y, z);
+                for offset in [U16Vec3::X, U16Vec3::Y, U16Vec3::Z] {
+                    count += storage.get(position + offset).is_solid() as usize;
+                    count += storage.get(position - offset).is_solid() as usize;
+                }
+            }
+        }
+    }
+    count
+}
+
+/// Storage of `size` with its bottom half stone.
+fn half_full_storage<L: BlockLayout>(size: u16) -> BlockStorage<L> {
+    let mut storage = BlockStorage::<L>::new(size);
+    for x in 0..size {
+        for y in 0..size / 2 {
+            for z in 0..size {
+                storage.set(U16Vec3::new(x, y, z), BlockType::Stone);
+            }
+        }
+    }
+    storage
+}
+
+fn generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generate_chunk");
+    for chunk_size in [16, 32] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(chunk_size),
+            &chunk_size,
+            |b, &chunk_size| {
+                // fresh noise every time, so no heightmap is cached yet
+                let mut seed = SEED;
+                b.iter(|| {
+                    seed += 1;
+                    let coord = ChunkCoordinate(SURFACE_CHUNK);
+                    generate_chunk(SharedNoise::new(seed), coord, WORLD_HEIGHT, chunk_size)
+                })
+            },
+        );
+    }
+    group.finish();
+
+    // as chunks above and below one another are, with the column's heightmap already cached
+    let mut group = c.benchmark_group("generator");
+    let noise = SharedNoise::new(SEED);
+    for kind in GENERATOR_KINDS {
+        let generator = kind
+            .build(
+                noise.clone(),
+                WORLD_HEIGHT,
+                16,
+                &GeneratorRegistry::default(),
+            )
+            .unwrap();
+        group.bench_function(kind.name(), |b| {
+            b.iter(|| generator.generate(ChunkCoordinate(SURFACE_CHUNK)))
+        });
+    }
+    group.finish();
+
+    // a stack of chunks sharing a column, sampling the heightmap for each or once for them all
+    let mut group = c.benchmark_group("stacked_chunks");
+    let noise = SharedNoise::new(SEED);
+    let generate_stack = |resample: bool| {
+        for y in 0..8 {
+            if resample {
+                noise.heightmaps().clear();
+            }
+            let coord = ChunkCoordinate(I64Vec3::new(0, y, 0));
+            black_box(generate_chunk(noise.clone(), coord, WORLD_HEIGHT, 16));
+        }
+    };
+    group.bench_function("resampled", |b| b.iter(|| generate_stack(true)));
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            noise.heightmaps().clear();
+            generate_stack(false)
+        })
+    });
+    group.finish();
+}
+
+fn meshing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generate_chunk_mesh");
+    for chunk_size in [16, 32] {
+        let (chunk, adjacent) = surface_chunk(chunk_size);
+        for lighting in [Lighting::Smooth, Lighting::Flat] {
+            group.bench_function(BenchmarkId::new(lighting.name(), chunk_size), |b| {
+                b.iter(|| generate_chunk_mesh(chunk.clone(), adjacent.clone(), lighting))
+            });
+        }
+    }
+    group.finish();
+
+    // a whole region against the chunk in it slowest to remesh, the worst case cost of an edit
+    let mut group = c.benchmark_group("mesh_region");
+    group.sample_size(10);
+    for chunk_size in [16, 32] {
+        let region = surface_region(chunk_size);
+        group.bench_function(BenchmarkId::new("region", chunk_size), |b| {
+            b.iter(|| {
+                for (chunk, adjacent) in &region {
+                    black_box(generate_chunk_mesh(
+                        chunk.clone(),
+                        adjacent.clone(),
+                        Lighting::Smooth,
+                    ));
+                }
+            })
+        });
+
+        let Some((chunk, adjacent)) = region.iter().max_by_key(|(chunk, adjacent)| {
+            let start = Instant::now();
+            black_box(generate_chunk_mesh(
+                chunk.clone(),
+                adjacent.clone(),
+                Lighting::Smooth,
+            ));
+            start.elapsed()
+        }) else {
+            continue;
+        };
+        group.bench_function(BenchmarkId::new("slowest_chunk", chunk_size), |b| {
+            b.iter(|| generate_chunk_mesh(chunk.clone(), adjacent.clone(), Lighting::Smooth))
+        });
+    }
+    group.finish();
+
+    let (chunk, adjacent) = surface_chunk(16);
+    let mut group = c.benchmark_group("face_visibility");
+    group.bench_function("face_masks", |b| {
+        b.iter(|| face_masks(black_box(&chunk), black_box(&adjacent)))
+    });
+    group.bench_function("scalar", |b| {
+        b.iter(|| {
+            for (position, _) in chunk.blocks() {
+                black_box(visible_faces_scalar(&chunk, &adjacent, position));
+            }
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("neighbour_lookups");
+    for size in [16, 32] {
+        let linear = half_full_storage::<LinearLayout>(size);
+        let morton = half_full_storage::<MortonLayout>(size);
+        group.bench_function(BenchmarkId::new("linear", size), |b| {
+            b.iter(|| count_solid_neighbours(black_box(&linear), size))
+        });
+        group.bench_function(BenchmarkId::new("morton", size), |b| {
+            b.iter(|| count_solid_neighbours(black_box(&morton), size))
+        });
+    }
+    group.finish();
+}
+
+fn serialization(c: &mut Criterion) {
+    let ids = IdMap::default();
+    let (chunk, _) = surface_chunk(16);
+    let bytes = encode_chunk(&chunk, &ids);
+
+    c.bench_function("encode_chunk", |b| {
+        b.iter(|| encode_chunk(black_box(&chunk), &ids))
+    });
+    c.bench_function("decode_chunk", |b| {
+        b.iter(|| decode_chunk(black_box(&bytes), &ids).unwrap())
+    });
+}
+
+criterion_group!(benches, generation, meshing, serialization);
+criterion_main!(benches);
@@ -0,0 +1,627 @@
+use std::collections::HashMap;
+
+use bevy::math::{I64Vec3, Vec3, Vec3Swizzles};
+use rand::{Rng, RngCore};
+use serde::Deserialize;
+
+use super::path::{cell_position, feet_cell, find_path};
+use crate::{block::BlockType, world::World};
+
+/// Horizontal distance at which a mob has reached where it was walking to.
+const ARRIVE_DISTANCE: f32 = 0.5;
+/// Distance from a block's centre within which a mob can use it.
+const USE_REACH: f32 = 1.5;
+/// Speed relative to walking when running away.
+const FLEE_SPEED: f32 = 1.5;
+/// Blocks a followed target can move before the path to it is found again.
+const REPATH_DISTANCE: i64 = 2;
+
+/// Result of ticking a node.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Failure,
+    /// Still working, tick again next time.
+    Running,
+}
+
+/// Who a node acts on.
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Target {
+    /// The nearest player.
+    #[default]
+    Player,
+    /// The player that tamed the mob.
+    Owner,
+    /// Whatever last hurt the mob's owner.
+    Enemy,
+}
+
+/// A node of a behavior tree. Trees are re-evaluated from the root every tick, so a higher
+/// priority branch such as fleeing interrupts whatever the mob was doing.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub enum BehaviorNode {
+    /// Ticks children in order until one doesn't succeed. A running sequence resumes from the
+    /// child it was on, rechecking the conditions before it.
+    Sequence(Vec<BehaviorNode>),
+    /// Ticks children in order until one doesn't fail.
+    Selector(Vec<BehaviorNode>),
+    /// Succeeds if the player is within this distance.
+    PlayerWithin(f32),
+    /// Succeeds if the mob's health is below this fraction of its maximum.
+    HealthBelow(f32),
+    IsDay,
+    IsNight,
+    /// Succeeds if the mob's owner has told it to stay.
+    Staying,
+    /// Succeeds with this probability each tick.
+    Chance(f32),
+    /// Stands still for this many seconds.
+    Idle(f32),
+    /// Walks to a random spot within `radius` blocks.
+    Wander {
+        radius: f32,
+    },
+    /// Runs directly away from the target while it's within `distance`.
+    Flee {
+        #[serde(default)]
+        target: Target,
+        distance: f32,
+    },
+    /// Walks towards the target while it's within `range`, succeeding once within `reach`.
+    Chase {
+        #[serde(default)]
+        target: Target,
+        range: f32,
+        reach: f32,
+    },
+    /// Turns to face the target while it's within `range`.
+    LookAt {
+        #[serde(default)]
+        target: Target,
+        range: f32,
+    },
+    /// Hits the target if it's within `reach`, as often as the mob's attack allows.
+    Attack {
+        #[serde(default)]
+        target: Target,
+        reach: f32,
+    },
+    /// Finds a way to the target, succeeding once within `distance`. Teleports to the target
+    /// when more than `teleport` away.
+    Follow {
+        #[serde(default)]
+        target: Target,
+        distance: f32,
+        teleport: f32,
+    },
+    /// Walks to the nearest `block` within `range` and turns it into `into`, like a sheep eating
+    /// grass.
+    UseBlock {
+        block: BlockType,
+        into: BlockType,
+        range: i64,
+    },
+}
+
+/// What a mob knows about its surroundings this tick.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Senses {
+    pub position: Vec3,
+    pub player: Option<Vec3>,
+    pub owner: Option<Vec3>,
+    pub enemy: Option<Vec3>,
+    /// Told to stay put by its owner.
+    pub staying: bool,
+    /// Where a lead ties it, and how long the lead is.
+    pub tether: Option<(Vec3, f32)>,
+    pub health_fraction: f32,
+    pub is_day: bool,
+    /// Seconds since the last tick.
+    pub delta: f32,
+}
+
+impl Senses {
+    fn target(&self, target: Target) -> Option<Vec3> {
+        match target {
+            Target::Player => self.player,
+            Target::Owner => self.owner,
+            Target::Enemy => self.enemy,
+        }
+    }
+
+    fn target_within(&self, target: Target, distance: f32) -> Option<Vec3> {
+        self.target(target)
+            .filter(|position| position.distance(self.position) <= distance)
+    }
+}
+
+/// What the tree decided the mob should do this tick.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Intent {
+    /// Horizontal direction to move in, scaled relative to walking speed.
+    pub movement: Vec3,
+    /// Point to turn and face, otherwise the mob faces where it moves.
+    pub look_at: Option<Vec3>,
+    /// A block to replace, and what with.
+    pub use_block: Option<(I64Vec3, BlockType)>,
+    /// Who to hit this tick.
+    pub attack: Option<Target>,
+    /// Where to move to instantly.
+    pub teleport: Option<Vec3>,
+}
+
+/// State kept between ticks by nodes that take several ticks to finish.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Memory {
+    /// Child each running sequence is on, keyed by the sequence's address in its shared tree.
+    sequences: HashMap<usize, usize>,
+    wander_target: Option<Vec3>,
+    idle_left: Option<f32>,
+    block_target: Option<I64Vec3>,
+    /// Cells left to walk through to `path_goal`, the next one last.
+    path: Vec<I64Vec3>,
+    path_goal: Option<I64Vec3>,
+}
+
+pub struct BehaviorContext<'a> {
+    pub senses: Senses,
+    pub memory: &'a mut Memory,
+    pub intent: Intent,
+    pub world: &'a mut World,
+    pub rng: &'a mut dyn RngCore,
+}
+
+impl BehaviorContext<'_> {
+    /// Moves towards `target`, returning whether it is already within `distance`.
+    fn walk_to(&mut self, target: Vec3, distance: f32, speed: f32) -> bool {
+        let offset = (target - self.senses.position).xz();
+        if offset.length() <= distance {
+            return true;
+        }
+        let direction = offset.normalize_or_zero();
+        self.intent.movement = Vec3::new(direction.x, 0.0, direction.y) * speed;
+        false
+    }
+
+    /// Walks along a path to `goal`, finding a new one when `goal` has moved too far.
+    fn follow_path(&mut self, goal: Vec3, distance: f32) {
+        let position = self.senses.position;
+        let goal_cell = feet_cell(goal);
+        let moved = self
+            .memory
+            .path_goal
+            .is_none_or(|old| (old - goal_cell).abs().max_element() > REPATH_DISTANCE);
+        if moved {
+            let mut path = find_path(self.world, feet_cell(position), goal_cell);
+            path.reverse();
+            self.memory.path = path;
+            self.memory.path_goal = Some(goal_cell);
+        }
+
+        while let Some(next) = self.memory.path.last() {
+            if (cell_position(*next) - position).xz().length() > ARRIVE_DISTANCE {
+                break;
+            }
+            self.memory.path.pop();
+        }
+        match self.memory.path.last() {
+            Some(next) => self.walk_to(cell_position(*next), 0.0, 1.0),
+            None => self.walk_to(goal, distance, 1.0),
+        };
+    }
+
+    fn forget_path(&mut self) {
+        self.memory.path.clear();
+        self.memory.path_goal = None;
+    }
+
+    /// Nearest block of `block` within `range` of the mob.
+    fn find_block(&mut self, block: BlockType, range: i64) -> Option<I64Vec3> {
+        let centre = self.senses.position.round().as_i64vec3();
+        let mut nearest: Option<(i64, I64Vec3)> = None;
+        for x in -range..=range {
+            for y in -range..=range {
+                for z in -range..=range {
+                    let offset = I64Vec3::new(x, y, z);
+                    let distance = offset.length_squared();
+                    if nearest.is_some_and(|(nearest, _)| nearest <= distance) {
+                        continue;
+                    }
+                    if self.world.get_block(centre + offset) == block {
+                        nearest = Some((distance, centre + offset));
+                    }
+                }
+            }
+        }
+        nearest.map(|(_, position)| position)
+    }
+}
+
+impl BehaviorNode {
+    pub fn tick(&self, context: &mut BehaviorContext) -> Status {
+        let senses = context.senses;
+        match self {
+            Self::Sequence(children) => {
+                let key = std::ptr::from_ref(self) as usize;
+                let resume = context.memory.sequences.remove(&key).unwrap_or(0);
+                for (i, child) in children.iter().enumerate() {
+                    if i < resume && !child.is_condition() {
+                        continue;
+                    }
+                    match child.tick(context) {
+                        Status::Success => continue,
+                        Status::Running => {
+                            context.memory.sequences.insert(key, i);
+                            return Status::Running;
+                        }
+                        Status::Failure => return Status::Failure,
+                    }
+                }
+                Status::Success
+            }
+            Self::Selector(children) => children
+                .iter()
+                .map(|child| child.tick(context))
+                .find(|status| *status != Status::Failure)
+                .unwrap_or(Status::Failure),
+            Self::PlayerWithin(distance) => {
+                succeed_if(senses.target_within(Target::Player, *distance).is_some())
+            }
+            Self::HealthBelow(fraction) => succeed_if(senses.health_fraction < *fraction),
+            Self::IsDay => succeed_if(senses.is_day),
+            Self::IsNight => succeed_if(!senses.is_day),
+            Self::Staying => succeed_if(senses.staying),
+            Self::Chance(chance) => succeed_if(context.rng.gen::<f32>() < *chance),
+            Self::Idle(seconds) => {
+                let left = context.memory.idle_left.unwrap_or(*seconds) - senses.delta;
+                if left <= 0.0 {
+                    context.memory.idle_left = None;
+                    Status::Success
+                } else {
+                    context.memory.idle_left = Some(left);
+                    Status::Running
+                }
+            }
+            Self::Wander { radius } => {
+                // a leashed mob wanders around what it's tied to instead of where it stands
+                let (centre, radius) = match senses.tether {
+                    Some((anchor, length)) => (anchor, radius.min(length)),
+                    None => (senses.position, *radius),
+                };
+                let target = *context.memory.wander_target.get_or_insert_with(|| {
+                    let angle = context.rng.gen_range(0.0..std::f32::consts::TAU);
+                    let distance = context.rng.gen_range(0.0..radius);
+                    centre + Vec3::new(angle.cos(), 0.0, angle.sin()) * distance
+                });
+                if context.walk_to(target, ARRIVE_DISTANCE, 1.0) {
+                    context.memory.wander_target = None;
+                    Status::Success
+                } else {
+                    Status::Running
+                }
+            }
+            Self::Flee { target, distance } => match senses.target_within(*target, *distance) {
+                Some(from) => {
+                    let away = senses.position * 2.0 - from;
+                    context.walk_to(away, 0.0, FLEE_SPEED);
+                    Status::Running
+                }
+                None => Status::Failure,
+            },
+            Self::Chase {
+                target,
+                range,
+                reach,
+            } => match senses.target_within(*target, *range) {
+                Some(position) => {
+                    context.intent.look_at = Some(position);
+                    if context.walk_to(position, *reach, 1.0) {
+                        Status::Success
+                    } else {
+                        Status::Running
+                    }
+                }
+                None => Status::Failure,
+            },
+            Self::LookAt { target, range } => match senses.target_within(*target, *range) {
+                Some(position) => {
+                    context.intent.look_at = Some(position);
+                    Status::Success
+                }
+                None => Status::Failure,
+            },
+            Self::Attack { target, reach } => match senses.target_within(*target, *reach) {
+                Some(position) => {
+                    context.intent.look_at = Some(position);
+                    context.intent.attack = Some(*target);
+                    Status::Success
+                }
+                None => Status::Failure,
+            },
+            Self::Follow {
+                target,
+                distance,
+                teleport,
+            } => {
+                let Some(goal) = senses.target(*target) else {
+                    context.forget_path();
+                    return Status::Failure;
+                };
+                let away = goal.distance(senses.position);
+                if away > *teleport {
+                    context.intent.teleport = Some(goal);
+                } else if away > *distance {
+                    context.follow_path(goal, *distance);
+                    return Status::Running;
+                }
+                context.forget_path();
+                Status::Success
+            }
+            Self::UseBlock { block, into, range } => {
+                let target = match context.memory.block_target {
+                    Some(target) if context.world.get_block(target) == *block => Some(target),
+                    _ => context.find_block(*block, *range),
+                };
+                context.memory.block_target = target;
+                let Some(target) = target else {
+                    return Status::Failure;
+                };
+
+                if context.walk_to(target.as_vec3(), USE_REACH, 1.0) {
+                    context.intent.use_block = Some((target, *into));
+                    context.memory.block_target = None;
+                    Status::Success
+                } else {
+                    Status::Running
+                }
+            }
+        }
+    }
+}
+
+impl BehaviorNode {
+    /// Whether the node only checks something, so is worth rechecking every tick.
+    fn is_condition(&self) -> bool {
+        matches!(
+            self,
+            Self::PlayerWithin(_)
+                | Self::HealthBelow(_)
+                | Self::IsDay
+                | Self::IsNight
+                | Self::Staying
+        )
+    }
+}
+
+fn succeed_if(condition: bool) -> Status {
+    if condition {
+        Status::Success
+    } else {
+        Status::Failure
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec3, U16Vec3, Vec3};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{BehaviorContext, BehaviorNode, Intent, Memory, Senses, Status, Target};
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        world::World,
+    };
+
+    fn senses(player: Option<Vec3>) -> Senses {
+        Senses {
+            position: Vec3::ZERO,
+            player,
+            owner: None,
+            enemy: None,
+            staying: false,
+            tether: None,
+            health_fraction: 1.0,
+            is_day: true,
+            delta: 0.05,
+        }
+    }
+
+    fn tick(node: &BehaviorNode, senses: Senses, memory: &mut Memory) -> (Status, Intent) {
+        let mut world = World::new(1);
+        let mut rng = StdRng::seed_from_u64(1);
+        tick_in(node, senses, memory, &mut world, &mut rng)
+    }
+
+    fn tick_in(
+        node: &BehaviorNode,
+        senses: Senses,
+        memory: &mut Memory,
+        world: &mut World,
+        rng: &mut StdRng,
+    ) -> (Status, Intent) {
+        let mut context = BehaviorContext {
+            senses,
+            memory,
+            intent: Intent::default(),
+            world,
+            rng,
+        };
+        let status = node.tick(&mut context);
+        (status, context.intent)
+    }
+
+    #[test]
+    fn test_selector_prefers_earlier_branches() {
+        let tree = BehaviorNode::Selector(vec![
+            BehaviorNode::Flee {
+                target: Target::Player,
+                distance: 5.0,
+            },
+            BehaviorNode::Idle(1.0),
+        ]);
+        let mut memory = Memory::default();
+
+        let (status, intent) = tick(&tree, senses(Some(Vec3::new(2.0, 0.0, 0.0))), &mut memory);
+        assert_eq!(Status::Running, status);
+        assert!(intent.movement.x < 0.0);
+
+        let (status, intent) = tick(&tree, senses(Some(Vec3::new(9.0, 0.0, 0.0))), &mut memory);
+        assert_eq!(Status::Running, status);
+        assert_eq!(Vec3::ZERO, intent.movement);
+    }
+
+    #[test]
+    fn test_sequence_stops_at_failed_condition() {
+        let tree = BehaviorNode::Sequence(vec![
+            BehaviorNode::IsNight,
+            BehaviorNode::Chase {
+                target: Target::Player,
+                range: 16.0,
+                reach: 1.0,
+            },
+        ]);
+        let player = Some(Vec3::new(0.0, 0.0, 8.0));
+        let mut memory = Memory::default();
+        assert_eq!(Status::Failure, tick(&tree, senses(player), &mut memory).0);
+
+        let night = Senses {
+            is_day: false,
+            ..senses(player)
+        };
+        let (status, intent) = tick(&tree, night, &mut memory);
+        assert_eq!(Status::Running, status);
+        assert_eq!(Vec3::Z, intent.movement);
+        assert_eq!(player, intent.look_at);
+    }
+
+    #[test]
+    fn test_idle_and_wander_finish() {
+        let mut memory = Memory::default();
+        let idle = BehaviorNode::Idle(0.08);
+        assert_eq!(Status::Running, tick(&idle, senses(None), &mut memory).0);
+        assert_eq!(Status::Success, tick(&idle, senses(None), &mut memory).0);
+
+        let wander = BehaviorNode::Wander { radius: 4.0 };
+        let (status, intent) = tick(&wander, senses(None), &mut memory);
+        let target = memory.wander_target.unwrap();
+        assert!(status == Status::Success || intent.movement.length() > 0.99);
+        assert!(target.length() < 4.0);
+
+        let arrived = Senses {
+            position: target,
+            ..senses(None)
+        };
+        assert_eq!(Status::Success, tick(&wander, arrived, &mut memory).0);
+        assert_eq!(None, memory.wander_target);
+
+        let anchor = Vec3::new(20.0, 0.0, 0.0);
+        let leashed = Senses {
+            tether: Some((anchor, 2.0)),
+            ..senses(None)
+        };
+        tick(&wander, leashed, &mut memory);
+        assert!(memory.wander_target.unwrap().distance(anchor) < 2.0);
+    }
+
+    #[test]
+    fn test_sequence_resumes_running_child() {
+        let tree = BehaviorNode::Sequence(vec![
+            BehaviorNode::IsDay,
+            BehaviorNode::Idle(0.08),
+            BehaviorNode::Wander { radius: 4.0 },
+        ]);
+        let mut memory = Memory::default();
+        assert_eq!(Status::Running, tick(&tree, senses(None), &mut memory).0);
+        // Idle finishes, so the wander starts rather than the idle starting over.
+        let (status, _) = tick(&tree, senses(None), &mut memory);
+        assert!(memory.wander_target.is_some() || status == Status::Success);
+
+        let night = Senses {
+            is_day: false,
+            ..senses(None)
+        };
+        assert_eq!(Status::Failure, tick(&tree, night, &mut memory).0);
+        assert!(memory.sequences.is_empty());
+    }
+
+    #[test]
+    fn test_follow_owner_and_attack_enemy() {
+        let tree = BehaviorNode::Selector(vec![
+            BehaviorNode::Sequence(vec![
+                BehaviorNode::Chase {
+                    target: Target::Enemy,
+                    range: 16.0,
+                    reach: 1.5,
+                },
+                BehaviorNode::Attack {
+                    target: Target::Enemy,
+                    reach: 1.5,
+                },
+            ]),
+            BehaviorNode::Follow {
+                target: Target::Owner,
+                distance: 3.0,
+                teleport: 12.0,
+            },
+        ]);
+        let mut memory = Memory::default();
+        let following = |owner: Vec3| Senses {
+            owner: Some(owner),
+            ..senses(None)
+        };
+
+        let (status, intent) = tick(&tree, following(Vec3::new(-6.0, 0.0, 0.0)), &mut memory);
+        assert_eq!(Status::Running, status);
+        assert_eq!(Vec3::NEG_X, intent.movement);
+
+        let owner = Vec3::new(0.0, 0.0, 20.0);
+        let (status, intent) = tick(&tree, following(owner), &mut memory);
+        assert_eq!(Status::Success, status);
+        assert_eq!(Some(owner), intent.teleport);
+
+        let (_, intent) = tick(&tree, following(Vec3::new(2.0, 0.0, 0.0)), &mut memory);
+        assert_eq!(Vec3::ZERO, intent.movement);
+        assert_eq!(None, intent.attack);
+
+        let defending = Senses {
+            enemy: Some(Vec3::new(1.0, 0.0, 0.0)),
+            ..following(Vec3::new(2.0, 0.0, 0.0))
+        };
+        let (status, intent) = tick(&tree, defending, &mut memory);
+        assert_eq!(Status::Success, status);
+        assert_eq!(Some(Target::Enemy), intent.attack);
+    }
+
+    #[test]
+    fn test_use_block_walks_to_nearest() {
+        let mut world = World::new(1);
+        let mut chunk = ChunkData::default();
+        chunk.set_block_at(U16Vec3::new(3, 0, 0), BlockType::Grass);
+        chunk.set_block_at(U16Vec3::new(8, 0, 0), BlockType::Grass);
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), chunk);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let tree = BehaviorNode::UseBlock {
+            block: BlockType::Grass,
+            into: BlockType::Sand,
+            range: 4,
+        };
+        let mut memory = Memory::default();
+        let (status, intent) = tick_in(&tree, senses(None), &mut memory, &mut world, &mut rng);
+        assert_eq!(Status::Running, status);
+        assert_eq!(Vec3::X, intent.movement);
+
+        let close = Senses {
+            position: Vec3::new(2.0, 0.0, 0.0),
+            ..senses(None)
+        };
+        let (status, intent) = tick_in(&tree, close, &mut memory, &mut world, &mut rng);
+        assert_eq!(Status::Success, status);
+        assert_eq!(
+            Some((I64Vec3::new(3, 0, 0), BlockType::Sand)),
+            intent.use_block
+        );
+    }
+}
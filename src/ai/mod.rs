@@ -0,0 +1,353 @@
+use std::{collections::HashMap, sync::Arc};
+
+use bevy::{
+    app::{App, FixedUpdate, Plugin, Update},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::EventWriter,
+        query::{Added, With, Without},
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res, ResMut, Resource},
+    },
+    math::{I64Vec3, Quat, Vec3},
+    state::condition::in_state,
+    time::Time,
+    transform::components::Transform,
+};
+
+use crate::{
+    chunks::chunk_loader::ChunkLoader,
+    daylight::TimeOfDay,
+    interaction::{BlockEdited, EditSource},
+    mob::{
+        combat::{Damage, LastAttacker},
+        lead::{anchor_position, pull_leashed_mobs, Leashed, LEAD_LENGTH},
+        pet::Pet,
+        registry::MobRegistry,
+        ride::Ridden,
+        Health, Mob,
+    },
+    player::Player,
+    state::GameState,
+    tick::TickPosition,
+    world::World,
+};
+
+pub mod behavior;
+pub mod path;
+
+use behavior::{BehaviorContext, BehaviorNode, Intent, Memory, Senses, Target};
+
+/// Behavior of mobs whose definition doesn't name one.
+pub const DEFAULT_BEHAVIOR: &str = "passive";
+/// Speed mobs jump at to climb a block in their way.
+const MOB_JUMP_SPEED: f32 = 7.0;
+/// Daylight above which it counts as day for `IsDay` and `IsNight`.
+const DAY_THRESHOLD: f32 = 0.5;
+
+/// Runs each mob's behavior tree and turns its decisions into movement.
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BehaviorLibrary>()
+            .add_systems(Update, give_mobs_brains.run_if(in_state(GameState::InGame)))
+            .add_systems(
+                FixedUpdate,
+                think
+                    .before(pull_leashed_mobs)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Behavior trees by name, which mob definitions pick from with `behavior`.
+#[derive(Resource, Debug)]
+pub struct BehaviorLibrary {
+    trees: HashMap<String, Arc<BehaviorNode>>,
+}
+
+impl Default for BehaviorLibrary {
+    fn default() -> Self {
+        let mut library = Self {
+            trees: HashMap::new(),
+        };
+        library.register(DEFAULT_BEHAVIOR, passive());
+        library.register("hostile", hostile());
+        library.register("pet", pet());
+        library
+    }
+}
+
+impl BehaviorLibrary {
+    /// Adds a tree, replacing any with the same name.
+    pub fn register(&mut self, name: &str, tree: BehaviorNode) {
+        self.trees.insert(name.to_lowercase(), Arc::new(tree));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<BehaviorNode>> {
+        self.trees.get(&name.to_lowercase()).cloned()
+    }
+}
+
+/// Runs from the player once hurt, otherwise watches them when they're close and wanders about.
+fn passive() -> BehaviorNode {
+    use BehaviorNode::*;
+    Selector(vec![
+        Sequence(vec![
+            HealthBelow(1.0),
+            Flee {
+                target: Target::Player,
+                distance: 10.0,
+            },
+        ]),
+        Sequence(vec![
+            LookAt {
+                target: Target::Player,
+                range: 4.0,
+            },
+            Idle(2.0),
+        ]),
+        Sequence(vec![Chance(0.02), Wander { radius: 8.0 }, Idle(3.0)]),
+    ])
+}
+
+/// Chases and attacks the player at night, otherwise wanders like a passive mob.
+fn hostile() -> BehaviorNode {
+    use BehaviorNode::*;
+    Selector(vec![
+        Sequence(vec![
+            IsNight,
+            Chase {
+                target: Target::Player,
+                range: 16.0,
+                reach: 1.2,
+            },
+            Attack {
+                target: Target::Player,
+                reach: 1.5,
+            },
+        ]),
+        LookAt {
+            target: Target::Player,
+            range: 8.0,
+        },
+        Sequence(vec![Chance(0.02), Wander { radius: 8.0 }, Idle(3.0)]),
+    ])
+}
+
+/// Untamed, wanders like a passive mob. Once tamed it follows its owner and fights whatever last
+/// hurt them, unless told to stay.
+fn pet() -> BehaviorNode {
+    use BehaviorNode::*;
+    Selector(vec![
+        Sequence(vec![
+            Staying,
+            Selector(vec![
+                LookAt {
+                    target: Target::Owner,
+                    range: 16.0,
+                },
+                Idle(1.0),
+            ]),
+        ]),
+        Sequence(vec![
+            Chase {
+                target: Target::Enemy,
+                range: 16.0,
+                reach: 1.2,
+            },
+            Attack {
+                target: Target::Enemy,
+                reach: 1.5,
+            },
+        ]),
+        Follow {
+            target: Target::Owner,
+            distance: 3.0,
+            teleport: 12.0,
+        },
+        Sequence(vec![Chance(0.02), Wander { radius: 8.0 }, Idle(3.0)]),
+    ])
+}
+
+/// A mob's behavior tree and what it remembers between ticks.
+#[derive(Component, Debug)]
+pub struct Brain {
+    pub tree: Arc<BehaviorNode>,
+    pub memory: Memory,
+    /// Seconds until the mob can attack again.
+    pub cooldown: f32,
+}
+
+fn give_mobs_brains(
+    mut commands: Commands,
+    registry: Res<MobRegistry>,
+    library: Res<BehaviorLibrary>,
+    mob_query: Query<(Entity, &Mob), Added<Mob>>,
+) {
+    for (entity, mob) in mob_query.iter() {
+        let name = registry
+            .get(mob.id)
+            .and_then(|definition| definition.behavior.as_deref())
+            .unwrap_or(DEFAULT_BEHAVIOR);
+        let Some(tree) = library.get(name).or_else(|| library.get(DEFAULT_BEHAVIOR)) else {
+            continue;
+        };
+        commands.entity(entity).insert(Brain {
+            tree,
+            memory: Memory::default(),
+            cooldown: 0.0,
+        });
+    }
+}
+
+/// Ticks every mob's behavior tree, then steers it, turns it, attacks and uses blocks as it
+/// decided.
+#[allow(clippy::too_many_arguments)]
+fn think(
+    mut commands: Commands,
+    time: Res<Time>,
+    time_of_day: Res<TimeOfDay>,
+    registry: Res<MobRegistry>,
+    mut world: ResMut<World>,
+    chunk_loader: Res<ChunkLoader>,
+    mut edited: EventWriter<BlockEdited>,
+    mut damage: EventWriter<Damage>,
+    player_query: Query<(Entity, &Transform), (With<Player>, Without<Mob>)>,
+    position_query: Query<(&Transform, Option<&TickPosition>), Without<Mob>>,
+    attacker_query: Query<&LastAttacker>,
+    mut mob_query: Query<
+        (
+            Entity,
+            &mut Mob,
+            &mut Brain,
+            &mut Transform,
+            &mut TickPosition,
+            &Health,
+            Option<&Pet>,
+            Option<&Leashed>,
+        ),
+        Without<Ridden>,
+    >,
+) {
+    let player = player_query.get_single().ok();
+    let is_day = time_of_day.daylight() > DAY_THRESHOLD;
+    let delta_secs = time.delta_secs();
+    let mut rng = rand::thread_rng();
+
+    // mobs' positions come from their tick position, anything else from its transform
+    let mob_positions: HashMap<Entity, Vec3> = mob_query
+        .iter()
+        .map(|(entity, _, _, _, tick_position, ..)| (entity, tick_position.current))
+        .collect();
+    let position_of = |entity: Entity| {
+        mob_positions.get(&entity).copied().or_else(|| {
+            position_query
+                .get(entity)
+                .ok()
+                .map(|(transform, tick_position)| {
+                    tick_position.map_or(transform.translation, |position| position.current)
+                })
+        })
+    };
+
+    for (entity, mut mob, mut brain, mut transform, mut tick_position, health, pet, leashed) in
+        mob_query.iter_mut()
+    {
+        let Some(definition) = registry.get(mob.id) else {
+            continue;
+        };
+
+        let owner = pet.map(|pet| pet.owner);
+        let enemy = owner
+            .and_then(|owner| attacker_query.get(owner).ok())
+            .map(|attacker| attacker.0)
+            .filter(|enemy| *enemy != entity);
+        let target_entity = |target: Target| match target {
+            Target::Player => player.map(|(player, _)| player),
+            Target::Owner => owner,
+            Target::Enemy => enemy,
+        };
+
+        let brain = &mut *brain;
+        brain.cooldown = (brain.cooldown - delta_secs).max(0.0);
+        let mut context = BehaviorContext {
+            senses: Senses {
+                position: tick_position.current,
+                player: player.map(|(_, transform)| transform.translation),
+                owner: owner.and_then(position_of),
+                enemy: enemy.and_then(position_of),
+                staying: pet.is_some_and(|pet| pet.staying),
+                tether: leashed
+                    .and_then(|leashed| anchor_position(leashed.anchor, position_of))
+                    .map(|anchor| (anchor, LEAD_LENGTH)),
+                health_fraction: health.current / health.max,
+                is_day,
+                delta: delta_secs,
+            },
+            memory: &mut brain.memory,
+            intent: Intent::default(),
+            world: &mut world,
+            rng: &mut rng,
+        };
+        brain.tree.tick(&mut context);
+        let intent = context.intent;
+
+        let velocity = intent.movement * definition.speed;
+        mob.velocity.x = velocity.x;
+        mob.velocity.z = velocity.z;
+        if mob.grounded && is_blocked(&mut world, tick_position.current, intent.movement) {
+            mob.velocity.y = MOB_JUMP_SPEED;
+        }
+        if let Some(position) = intent.teleport {
+            *tick_position = TickPosition::new(position);
+            mob.velocity = Vec3::ZERO;
+        }
+
+        let facing = intent
+            .look_at
+            .map(|target| target - tick_position.current)
+            .unwrap_or(intent.movement);
+        if facing.x != 0.0 || facing.z != 0.0 {
+            transform.rotation = Quat::from_rotation_y(f32::atan2(-facing.x, -facing.z));
+        }
+
+        if let (Some(attack), Some(target)) =
+            (definition.attack, intent.attack.and_then(target_entity))
+        {
+            if brain.cooldown <= 0.0 {
+                brain.cooldown = attack.cooldown;
+                damage.send(Damage {
+                    target,
+                    attacker: Some(entity),
+                    amount: attack.damage,
+                });
+            }
+        }
+
+        if let Some((position, block)) = intent.use_block {
+            if world.set_block(position, block) {
+                chunk_loader.remesh_block(&mut commands, &world, position);
+                edited.send(BlockEdited {
+                    position,
+                    block,
+                    source: EditSource::Mob,
+                });
+            }
+        }
+    }
+}
+
+/// Whether a solid block at foot height is directly in the way of moving in `direction`.
+fn is_blocked(world: &mut World, position: Vec3, direction: Vec3) -> bool {
+    if direction.length_squared() == 0.0 {
+        return false;
+    }
+    let ahead = position + direction.normalize() * 0.8 + Vec3::Y * 0.5;
+    world.get_block(ahead.round().as_i64vec3()).is_solid()
+        && !world
+            .get_block(ahead.round().as_i64vec3() + I64Vec3::Y)
+            .is_solid()
+}
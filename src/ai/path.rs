@@ -0,0 +1,165 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use bevy::math::{I64Vec3, Vec3};
+
+use crate::world::World;
+
+/// Most cells a search visits before settling for the closest cell it found.
+const MAX_VISITED: usize = 1024;
+/// Furthest a path may step down in one go.
+const MAX_DROP: i64 = 3;
+const STEP_COST: u32 = 10;
+/// Extra cost of climbing a block, so flat detours of a block or two are preferred.
+const CLIMB_COST: u32 = 5;
+
+const DIRECTIONS: [I64Vec3; 4] = [I64Vec3::X, I64Vec3::NEG_X, I64Vec3::Z, I64Vec3::NEG_Z];
+
+/// The cell of air a mob standing at `position` has its feet in.
+pub fn feet_cell(position: Vec3) -> I64Vec3 {
+    (position + Vec3::Y * 0.5).round().as_i64vec3()
+}
+
+/// Where a mob's feet are when standing in `cell`.
+pub fn cell_position(cell: I64Vec3) -> Vec3 {
+    cell.as_vec3() - Vec3::Y * 0.5
+}
+
+/// Whether a mob two blocks tall can stand in `cell`.
+pub fn is_standable(world: &mut World, cell: I64Vec3) -> bool {
+    !world.get_block(cell).is_solid()
+        && !world.get_block(cell + I64Vec3::Y).is_solid()
+        && world.get_block(cell - I64Vec3::Y).is_solid()
+}
+
+/// Cells reachable in one step from `cell` by walking, jumping up a block or dropping down,
+/// with the cost of each.
+fn neighbours(world: &mut World, cell: I64Vec3) -> Vec<(I64Vec3, u32)> {
+    let mut neighbours = Vec::new();
+    let head_room = !world.get_block(cell + I64Vec3::Y * 2).is_solid();
+    for direction in DIRECTIONS {
+        let next = cell + direction;
+        if is_standable(world, next) {
+            neighbours.push((next, STEP_COST));
+        } else if world.get_block(next).is_solid() {
+            if head_room && is_standable(world, next + I64Vec3::Y) {
+                neighbours.push((next + I64Vec3::Y, STEP_COST + CLIMB_COST));
+            }
+        } else if !world.get_block(next + I64Vec3::Y).is_solid() {
+            if let Some(drop) = (1..=MAX_DROP)
+                .find(|drop| world.get_block(next - I64Vec3::Y * (drop + 1)).is_solid())
+            {
+                neighbours.push((next - I64Vec3::Y * drop, STEP_COST));
+            }
+        }
+    }
+    neighbours
+}
+
+fn estimate(cell: I64Vec3, goal: I64Vec3) -> u32 {
+    (goal - cell).abs().element_sum() as u32 * STEP_COST
+}
+
+/// Finds the cells to walk through from `start` to `goal` with A*, not including `start`. When
+/// `goal` can't be reached within the search limit the path leads to the closest cell found
+/// instead, which is empty if no cell is closer than `start`.
+pub fn find_path(world: &mut World, start: I64Vec3, goal: I64Vec3) -> Vec<I64Vec3> {
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<I64Vec3, I64Vec3> = HashMap::new();
+    let mut costs: HashMap<I64Vec3, u32> = HashMap::from([(start, 0)]);
+    let mut closest = (estimate(start, goal), start.to_array());
+    open.push((Reverse(estimate(start, goal)), start.to_array()));
+
+    let mut visited = 0;
+    while let Some((_, cell)) = open.pop() {
+        let cell = I64Vec3::from_array(cell);
+        if cell == goal {
+            closest = (0, cell.to_array());
+            break;
+        }
+        visited += 1;
+        if visited > MAX_VISITED {
+            break;
+        }
+
+        let cost = costs[&cell];
+        for (next, step) in neighbours(world, cell) {
+            let next_cost = cost + step;
+            if costs.get(&next).is_some_and(|known| *known <= next_cost) {
+                continue;
+            }
+            costs.insert(next, next_cost);
+            came_from.insert(next, cell);
+            let remaining = estimate(next, goal);
+            closest = closest.min((remaining, next.to_array()));
+            open.push((Reverse(next_cost + remaining), next.to_array()));
+        }
+    }
+
+    let mut path = vec![];
+    let mut cell = I64Vec3::from_array(closest.1);
+    while cell != start {
+        path.push(cell);
+        cell = came_from[&cell];
+    }
+    path.reverse();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::I64Vec3;
+
+    use super::{find_path, is_standable};
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        world::World,
+    };
+
+    /// A stone floor at y = 0 across chunk (0, 0, 0), with a wall along x = 4 from z = 0 to 10.
+    fn walled_world() -> World {
+        let mut world = World::new(1);
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), ChunkData::default());
+        let floor =
+            (0..16).flat_map(|x| (0..16).map(move |z| (I64Vec3::new(x, 0, z), BlockType::Stone)));
+        let wall =
+            (0..=10).flat_map(|z| (1..=3).map(move |y| (I64Vec3::new(4, y, z), BlockType::Stone)));
+        world.set_blocks(floor.chain(wall));
+        world
+    }
+
+    #[test]
+    fn test_path_goes_around_walls() {
+        let mut world = walled_world();
+        let (start, goal) = (I64Vec3::new(2, 1, 2), I64Vec3::new(7, 1, 2));
+        let path = find_path(&mut world, start, goal);
+
+        assert_eq!(Some(&goal), path.last());
+        assert!(path.iter().any(|cell| cell.z > 10));
+        let mut previous = start;
+        for cell in path {
+            assert!(is_standable(&mut world, cell));
+            assert_eq!(1, (cell - previous).abs().element_sum());
+            previous = cell;
+        }
+    }
+
+    #[test]
+    fn test_path_climbs_single_blocks() {
+        let mut world = walled_world();
+        world.set_block(I64Vec3::new(3, 1, 12), BlockType::Stone);
+        let path = find_path(&mut world, I64Vec3::new(2, 1, 12), I64Vec3::new(3, 2, 12));
+        assert_eq!(vec![I64Vec3::new(3, 2, 12)], path);
+    }
+
+    #[test]
+    fn test_unreachable_goal_leads_closer() {
+        let mut world = walled_world();
+        // the goal is inside the wall, so the path stops beside it
+        let path = find_path(&mut world, I64Vec3::new(0, 1, 5), I64Vec3::new(4, 2, 5));
+        assert_eq!(Some(&I64Vec3::new(3, 1, 5)), path.last());
+    }
+}
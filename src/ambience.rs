@@ -0,0 +1,108 @@
+use bevy::{
+    ecs::{
+        component::Component,
+        query::With,
+        system::{Query, Res},
+    },
+    math::I64Vec2,
+    render::{camera::Camera, view::ColorGrading},
+    time::Time,
+    transform::components::GlobalTransform,
+};
+
+use crate::{chunks::generate::biome::Biome, settings::Settings, world::World};
+
+/// How far below the terrain surface the camera must be before cave grading starts to apply.
+pub const CAVE_DEPTH: f32 = 8.0;
+const TRANSITION_RATE: f32 = 1.5;
+
+/// Offsets applied on top of a neutral `ColorGrading`.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+struct Grade {
+    temperature: f32,
+    exposure: f32,
+    saturation: f32,
+}
+
+const CAVE_GRADE: Grade = Grade {
+    temperature: -0.25,
+    exposure: -0.3,
+    saturation: -0.2,
+};
+
+impl Grade {
+    fn for_biome(biome: Biome) -> Self {
+        match biome {
+            Biome::Desert => Self {
+                temperature: 0.2,
+                exposure: 0.05,
+                saturation: 0.1,
+            },
+            Biome::Snow => Self {
+                temperature: -0.15,
+                exposure: 0.1,
+                saturation: -0.1,
+            },
+            Biome::Ocean => Self {
+                temperature: -0.05,
+                ..Self::default()
+            },
+            Biome::Plains | Biome::Mountains => Self::default(),
+        }
+    }
+
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Self {
+            temperature: self.temperature + (other.temperature - self.temperature) * t,
+            exposure: self.exposure + (other.exposure - self.exposure) * t,
+            saturation: self.saturation + (other.saturation - self.saturation) * t,
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct AmbienceGrading {
+    current: Grade,
+}
+
+pub fn update_color_grading(
+    time: Res<Time>,
+    world: Res<World>,
+    settings_query: Query<&Settings>,
+    mut camera_query: Query<
+        (&GlobalTransform, &mut ColorGrading, &mut AmbienceGrading),
+        With<Camera>,
+    >,
+) {
+    let Ok(settings) = settings_query.get_single() else {
+        return;
+    };
+    let Ok((camera, mut grading, mut ambience)) = camera_query.get_single_mut() else {
+        return;
+    };
+
+    let position = camera.translation();
+    let column = I64Vec2::new(position.x.floor() as i64, position.z.floor() as i64);
+    let surface = (settings.graphics.color_grading)
+        .then(|| world.surface(column))
+        .flatten();
+    // columns without ground, such as in the void, stay neutral
+    let target = if let Some(surface) = surface {
+        let depth = surface.height as f32 - position.y;
+        let cave_factor = ((depth - CAVE_DEPTH) / CAVE_DEPTH).clamp(0.0, 1.0);
+
+        Grade::default().lerp(
+            Grade::for_biome(Biome::from_surface(surface)).lerp(CAVE_GRADE, cave_factor),
+            settings.graphics.color_grading_strength,
+        )
+    } else {
+        Grade::default()
+    };
+
+    let t = 1.0 - (-TRANSITION_RATE * time.delta_secs()).exp();
+    ambience.current = ambience.current.lerp(target, t);
+
+    grading.global.temperature = ambience.current.temperature;
+    grading.global.exposure = ambience.current.exposure;
+    grading.global.post_saturation = 1.0 + ambience.current.saturation;
+}
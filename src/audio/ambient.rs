@@ -0,0 +1,163 @@
+//! Background loops that follow where the camera is: wind up in the mountains and drips in dark
+//! caves. Both loops always play, faded between silence and full volume as the camera moves.
+
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    asset::AssetServer,
+    audio::{AudioPlayer, AudioSink, AudioSinkPlayback, PlaybackSettings, Volume},
+    core_pipeline::core_3d::Camera3d,
+    ecs::{
+        component::Component,
+        query::With,
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    math::{I64Vec2, I64Vec3},
+    time::Time,
+    transform::components::GlobalTransform,
+};
+
+use crate::{
+    ambience::CAVE_DEPTH,
+    chunks::generate::biome::{Biome, ColumnSurface},
+    settings::Settings,
+    state::in_world,
+    world::World,
+};
+
+/// Height above which the wind picks up anywhere, reaching full strength `WIND_RANGE` higher.
+const WIND_HEIGHT: f32 = 80.0;
+const WIND_RANGE: f32 = 60.0;
+/// Wind strength over mountainous biomes whatever the height.
+const HIGHLAND_WIND: f32 = 0.6;
+/// Blocks above the camera checked for a roof, which a cave needs to be dark.
+const ROOF_SCAN_HEIGHT: i64 = 24;
+/// How quickly loops fade towards their volume, per second.
+const FADE_RATE: f32 = 0.5;
+
+pub struct AmbientSoundPlugin;
+
+impl Plugin for AmbientSoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_ambient_loops)
+            .add_systems(Update, update_ambient_loops.run_if(in_world));
+    }
+}
+
+/// A looping background sound, and how loud it currently is from `0.0` to `1.0`.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub enum AmbientLoop {
+    Wind(f32),
+    Cave(f32),
+}
+
+impl AmbientLoop {
+    fn level(&mut self) -> &mut f32 {
+        match self {
+            Self::Wind(level) | Self::Cave(level) => level,
+        }
+    }
+}
+
+/// How loud the wind and the cave should be for a camera at `height` over a column with
+/// `surface`, where `roofed` is whether there's a block anywhere above it.
+pub fn ambient_levels(surface: Option<ColumnSurface>, height: f32, roofed: bool) -> (f32, f32) {
+    // the void has nothing to echo off or blow over
+    let Some(surface) = surface else {
+        return (0.0, 0.0);
+    };
+    let depth = surface.height as f32 - height;
+    let cave = if roofed {
+        ((depth - CAVE_DEPTH) / CAVE_DEPTH).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    let highland = match Biome::from_surface(surface) {
+        Biome::Mountains | Biome::Snow => HIGHLAND_WIND,
+        _ => 0.0,
+    };
+    let altitude = ((height - WIND_HEIGHT) / WIND_RANGE).clamp(0.0, 1.0);
+    let outside = (1.0 - depth.max(0.0) / CAVE_DEPTH).clamp(0.0, 1.0);
+    (highland.max(altitude) * outside, cave)
+}
+
+fn spawn_ambient_loops(mut commands: Commands, asset_server: Res<AssetServer>) {
+    for (path, ambient) in [
+        ("sounds/ambient/wind.wav", AmbientLoop::Wind(0.0)),
+        ("sounds/ambient/cave.wav", AmbientLoop::Cave(0.0)),
+    ] {
+        commands.spawn((
+            AudioPlayer::new(asset_server.load(path)),
+            PlaybackSettings::LOOP.with_volume(Volume::new(0.0)),
+            ambient,
+        ));
+    }
+}
+
+/// Fades the loops towards how loud they should be where the camera is, at the volume in
+/// `audio.ambient`.
+fn update_ambient_loops(
+    time: Res<Time>,
+    mut world: ResMut<World>,
+    settings_query: Query<&Settings>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    mut loop_query: Query<(&mut AmbientLoop, &AudioSink)>,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let audio = settings_query
+        .get_single()
+        .map(|settings| settings.audio)
+        .unwrap_or_default();
+
+    let position = camera.translation();
+    let block = position.round().as_i64vec3();
+    let roofed =
+        (1..=ROOF_SCAN_HEIGHT).any(|dy| world.get_block(block + I64Vec3::Y * dy).is_solid());
+    let surface = world.surface(I64Vec2::new(block.x, block.z));
+    let (wind, cave) = ambient_levels(surface, position.y, roofed);
+
+    let step = FADE_RATE * time.delta_secs();
+    for (mut ambient, sink) in loop_query.iter_mut() {
+        let target = match *ambient {
+            AmbientLoop::Wind(_) => wind,
+            AmbientLoop::Cave(_) => cave,
+        };
+        let level = ambient.level();
+        *level += (target - *level).clamp(-step, step);
+        sink.set_volume(*level * audio.master * audio.ambient);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ambient_levels, ColumnSurface, HIGHLAND_WIND};
+
+    #[test]
+    fn test_wind_blows_high_and_caves_drip_deep() {
+        let plains = ColumnSurface {
+            height: 50,
+            gradient: 1.0,
+        };
+        let mountains = ColumnSurface {
+            height: 80,
+            gradient: 4.0,
+        };
+
+        assert_eq!((0.0, 0.0), ambient_levels(Some(plains), 51.0, false));
+        assert_eq!(
+            (HIGHLAND_WIND, 0.0),
+            ambient_levels(Some(mountains), 81.0, false)
+        );
+        // far above anything the wind is at its strongest
+        assert_eq!((1.0, 0.0), ambient_levels(Some(plains), 300.0, false));
+
+        // deep under a roof the wind is gone and the cave is loud
+        assert_eq!((0.0, 1.0), ambient_levels(Some(mountains), 40.0, true));
+        // as deep in an open ravine there are no echoes
+        assert_eq!((0.0, 0.0), ambient_levels(Some(mountains), 40.0, false));
+        assert_eq!((0.0, 0.0), ambient_levels(None, 40.0, true));
+    }
+}
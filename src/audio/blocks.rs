@@ -0,0 +1,245 @@
+//! Sounds of blocks: footsteps on whatever the player is walking over, and a sound as blocks are
+//! broken or placed. Each block sounds like its `BlockSound` material, played where it happened so
+//! it's heard from the right direction.
+
+use bevy::{
+    asset::{AssetServer, Handle},
+    audio::{AudioPlayer, AudioSource, PlaybackSettings, Volume},
+    ecs::{
+        event::EventReader,
+        query::With,
+        system::{Commands, Local, Query, Res, ResMut, Resource},
+        world::FromWorld,
+    },
+    math::{I64Vec3, Vec3},
+    transform::components::Transform,
+    utils::HashMap,
+};
+use rand::Rng;
+
+use crate::{
+    block::{BlockSound, BlockType},
+    chunks::position::BlockPos,
+    interaction::{BlockEdited, EditSource},
+    item::BlockBroken,
+    player::{Player, PlayerMovement},
+    world::World,
+};
+
+/// Blocks walked between footsteps.
+const STEP_DISTANCE: f32 = 1.7;
+const STEP_VOLUME: f32 = 0.35;
+/// Sneaking steps are this much quieter.
+const SNEAK_VOLUME: f32 = 0.4;
+const BREAK_VOLUME: f32 = 0.9;
+const PLACE_VOLUME: f32 = 0.7;
+/// Playback speed of each kind of sound, so one recording per material covers them all.
+const STEP_SPEED: f32 = 1.0;
+const BREAK_SPEED: f32 = 0.8;
+const PLACE_SPEED: f32 = 1.2;
+/// Most a sound's speed is varied by at random, so repeated sounds aren't identical.
+const SPEED_JITTER: f32 = 0.1;
+
+const ALL_SOUNDS: [BlockSound; 7] = [
+    BlockSound::Stone,
+    BlockSound::Grass,
+    BlockSound::Sand,
+    BlockSound::Snow,
+    BlockSound::Wood,
+    BlockSound::Glass,
+    BlockSound::Metal,
+];
+
+/// Recording played for `sound`, relative to the assets folder.
+fn sound_path(sound: BlockSound) -> &'static str {
+    match sound {
+        BlockSound::Stone => "sounds/blocks/stone.wav",
+        BlockSound::Grass => "sounds/blocks/grass.wav",
+        BlockSound::Sand => "sounds/blocks/sand.wav",
+        BlockSound::Snow => "sounds/blocks/snow.wav",
+        BlockSound::Wood => "sounds/blocks/wood.wav",
+        BlockSound::Glass => "sounds/blocks/glass.wav",
+        BlockSound::Metal => "sounds/blocks/metal.wav",
+    }
+}
+
+#[derive(Resource)]
+pub struct BlockSounds(HashMap<BlockSound, Handle<AudioSource>>);
+
+impl FromWorld for BlockSounds {
+    fn from_world(world: &mut bevy::ecs::world::World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        Self(
+            ALL_SOUNDS
+                .into_iter()
+                .map(|sound| (sound, asset_server.load(sound_path(sound))))
+                .collect(),
+        )
+    }
+}
+
+impl BlockSounds {
+    /// Plays `block`'s sound once at `position`.
+    fn play(
+        &self,
+        commands: &mut Commands,
+        block: BlockType,
+        position: Vec3,
+        volume: f32,
+        speed: f32,
+    ) {
+        let Some(source) = self.0.get(&block.sound()) else {
+            return;
+        };
+        let jitter = rand::thread_rng().gen_range(-SPEED_JITTER..=SPEED_JITTER);
+        commands.spawn((
+            AudioPlayer::new(source.clone()),
+            PlaybackSettings::DESPAWN
+                .with_spatial(true)
+                .with_volume(Volume::new(volume))
+                .with_speed(speed + jitter),
+            Transform::from_translation(position),
+        ));
+    }
+}
+
+/// Distance walked since the last footstep.
+#[derive(Debug, Default)]
+pub struct Footsteps {
+    last: Option<Vec3>,
+    walked: f32,
+    grounded: bool,
+}
+
+impl Footsteps {
+    /// Follows the player to `position`, returning whether a footstep is due. Steps fall every
+    /// `STEP_DISTANCE` walked along the ground, and on landing.
+    pub fn advance(&mut self, position: Vec3, grounded: bool) -> bool {
+        let moved = self.last.map_or(0.0, |last| {
+            Vec3::new(position.x - last.x, 0.0, position.z - last.z).length()
+        });
+        let landed = grounded && !self.grounded && self.last.is_some();
+        self.last = Some(position);
+        self.grounded = grounded;
+        if !grounded {
+            self.walked = 0.0;
+            return false;
+        }
+
+        self.walked += moved;
+        if landed || self.walked >= STEP_DISTANCE {
+            self.walked = 0.0;
+            return true;
+        }
+        false
+    }
+}
+
+/// The block the player standing at `feet` is walking on. Thin blocks like snow layers are stood
+/// in rather than on, and sound in place of the block below them.
+pub fn ground_block(world: &mut World, feet: Vec3) -> BlockType {
+    world.get_block(BlockPos::from_world(feet - Vec3::Y * 0.05).0)
+}
+
+pub fn play_footsteps(
+    mut commands: Commands,
+    sounds: Res<BlockSounds>,
+    mut world: ResMut<World>,
+    mut footsteps: Local<Footsteps>,
+    player_query: Query<(&Transform, &PlayerMovement), With<Player>>,
+) {
+    let Ok((transform, movement)) = player_query.get_single() else {
+        return;
+    };
+    let feet = transform.translation;
+    let grounded = movement.is_grounded() && !movement.is_swimming();
+    if !footsteps.advance(feet, grounded) {
+        return;
+    }
+
+    let block = ground_block(&mut world, feet);
+    if block.is_solid() {
+        let volume = if movement.is_sneaking() {
+            STEP_VOLUME * SNEAK_VOLUME
+        } else {
+            STEP_VOLUME
+        };
+        sounds.play(&mut commands, block, feet, volume, STEP_SPEED);
+    }
+}
+
+/// Plays the sound of each block broken, and of each block a player places.
+pub fn play_block_sounds(
+    mut commands: Commands,
+    sounds: Res<BlockSounds>,
+    mut broken: EventReader<BlockBroken>,
+    mut edited: EventReader<BlockEdited>,
+) {
+    let placed = edited
+        .read()
+        .filter(|edit| edit.source == EditSource::Player)
+        .map(|edit| (edit.position, edit.block, PLACE_VOLUME, PLACE_SPEED));
+    let played: Vec<(I64Vec3, BlockType, f32, f32)> = broken
+        .read()
+        .map(|broken| (broken.position, broken.block, BREAK_VOLUME, BREAK_SPEED))
+        .chain(placed)
+        .filter(|(_, block, _, _)| *block != BlockType::Air)
+        .collect();
+    for (position, block, volume, speed) in played {
+        sounds.play(&mut commands, block, position.as_vec3(), volume, speed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bevy::math::{I64Vec3, Vec3};
+
+    use super::{ground_block, sound_path, Footsteps, ALL_SOUNDS, STEP_DISTANCE};
+    use crate::{
+        block::{BlockSound, BlockType},
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        world::World,
+    };
+
+    #[test]
+    fn test_footsteps_follow_distance_walked() {
+        let mut footsteps = Footsteps::default();
+        assert!(!footsteps.advance(Vec3::ZERO, true));
+        assert!(!footsteps.advance(Vec3::X * STEP_DISTANCE * 0.6, true));
+        assert!(footsteps.advance(Vec3::X * STEP_DISTANCE * 1.2, true));
+
+        // moving up or down alone isn't walking, and the air doesn't count
+        assert!(!footsteps.advance(Vec3::new(STEP_DISTANCE * 1.2, 5.0, 0.0), true));
+        assert!(!footsteps.advance(Vec3::new(10.0, 5.0, 0.0), false));
+        // landing is a step of its own
+        assert!(footsteps.advance(Vec3::new(10.0, 0.0, 0.0), true));
+    }
+
+    #[test]
+    fn test_ground_block_includes_thin_blocks() {
+        let mut world = World::new(0);
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), ChunkData::default());
+        world.set_block(I64Vec3::new(2, 2, 2), BlockType::Grass);
+        world.set_block(I64Vec3::new(3, 2, 3), BlockType::Stone);
+        world.set_block(I64Vec3::new(3, 3, 3), BlockType::SnowLayer);
+
+        assert_eq!(
+            BlockType::Grass,
+            ground_block(&mut world, Vec3::new(2.0, 2.5, 2.0))
+        );
+        assert_eq!(
+            BlockType::SnowLayer,
+            ground_block(&mut world, Vec3::new(3.0, 2.625, 3.0))
+        );
+        assert_eq!(BlockSound::Snow, BlockType::SnowLayer.sound());
+    }
+
+    #[test]
+    fn test_every_block_sound_has_a_recording() {
+        for sound in ALL_SOUNDS {
+            assert!(Path::new("assets").join(sound_path(sound)).exists());
+        }
+    }
+}
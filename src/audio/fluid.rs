@@ -0,0 +1,202 @@
+use bevy::{
+    asset::{AssetServer, Handle},
+    audio::{AudioPlayer, AudioSource, PlaybackSettings, Volume},
+    ecs::{
+        change_detection::DetectChanges,
+        component::Component,
+        entity::Entity,
+        query::With,
+        system::{Commands, Query, Res, ResMut, Resource},
+        world::{FromWorld, Ref},
+    },
+    hierarchy::{BuildChildren, DespawnRecursiveExt},
+    math::{I64Vec3, U16Vec3, Vec3},
+    prelude::Mesh3d,
+    render::camera::Camera,
+    transform::components::{GlobalTransform, Transform},
+    utils::HashMap,
+};
+
+use crate::{
+    block::BlockType,
+    chunks::{
+        chunk::{ChunkCoordinate, ChunkData},
+        chunk_loader::{chunk_distance, Chunk},
+    },
+    world::World,
+};
+
+/// Upper bound on positional emitters spawned for a single chunk.
+const MAX_EMITTERS_PER_CHUNK: usize = 4;
+/// Side length of the cubic regions that exposed fluid cells are grouped into.
+const EMITTER_REGION_SIZE: u16 = 8;
+/// Only chunks this close to the camera carry emitters at all.
+const AUDIBLE_CHUNK_DISTANCE: u32 = 2;
+
+#[derive(Resource)]
+pub struct FluidSounds {
+    flow: Handle<AudioSource>,
+}
+
+impl FromWorld for FluidSounds {
+    fn from_world(world: &mut bevy::ecs::world::World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        Self {
+            flow: asset_server.load("sounds/water_flow.wav"),
+        }
+    }
+}
+
+/// A group of exposed fluid cells that is played back as a single positional sound.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FluidEmitter {
+    /// Centroid of the grouped cells relative to the chunk origin.
+    pub position: Vec3,
+    pub cells: u32,
+}
+
+impl FluidEmitter {
+    fn volume(&self) -> f32 {
+        let full_surface = (EMITTER_REGION_SIZE * EMITTER_REGION_SIZE) as f32;
+        (self.cells as f32 / full_surface).sqrt().min(1.0)
+    }
+}
+
+/// Groups fluid cells with air above them into at most `max_emitters` emitters, loudest first.
+pub fn fluid_emitters(
+    chunk: &ChunkData,
+    chunk_above: Option<&ChunkData>,
+    max_emitters: usize,
+) -> Vec<FluidEmitter> {
+    let mut regions: HashMap<U16Vec3, (Vec3, u32)> = HashMap::new();
+
+    for (coord, block) in chunk.blocks() {
+        if block != BlockType::Water {
+            continue;
+        }
+
+        let above = chunk.get_block_above(coord, chunk_above);
+        if above != BlockType::Air {
+            continue;
+        }
+
+        let region = regions
+            .entry(coord / EMITTER_REGION_SIZE)
+            .or_insert((Vec3::ZERO, 0));
+        region.0 += coord.as_vec3();
+        region.1 += 1;
+    }
+
+    let mut emitters: Vec<FluidEmitter> = regions
+        .into_values()
+        .map(|(sum, cells)| FluidEmitter {
+            position: sum / cells as f32,
+            cells,
+        })
+        .collect();
+    emitters.sort_by(|a, b| b.cells.cmp(&a.cells));
+    emitters.truncate(max_emitters);
+    emitters
+}
+
+#[derive(Component)]
+pub struct FluidSound;
+
+/// Emitter entities currently attached to a chunk.
+#[derive(Component)]
+pub struct FluidEmitters(Vec<Entity>);
+
+pub fn update_fluid_emitters(
+    mut commands: Commands,
+    mut world: ResMut<World>,
+    sounds: Res<FluidSounds>,
+    camera_query: Query<&GlobalTransform, With<Camera>>,
+    chunks_query: Query<(Entity, &Chunk, Ref<Mesh3d>, Option<&FluidEmitters>)>,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let camera_pos = camera.translation();
+    let camera_chunk = world.chunk_at(camera_pos);
+
+    for (entity, chunk, mesh, existing) in chunks_query.iter() {
+        let in_range = chunk_distance(chunk.coord(), camera_chunk) <= AUDIBLE_CHUNK_DISTANCE;
+        if in_range && existing.is_some() && !mesh.is_changed() {
+            continue;
+        }
+
+        if let Some(existing) = existing {
+            for emitter in existing.0.iter() {
+                commands.entity(*emitter).despawn_recursive();
+            }
+            commands.entity(entity).remove::<FluidEmitters>();
+        }
+
+        if !in_range {
+            continue;
+        }
+
+        let Some(chunk_data) = world.get_chunk_data(chunk.coord()) else {
+            continue;
+        };
+        let chunk_above = world.get_chunk_data(ChunkCoordinate(chunk.coord().0 + I64Vec3::Y));
+
+        let emitters: Vec<Entity> =
+            fluid_emitters(&chunk_data, chunk_above.as_deref(), MAX_EMITTERS_PER_CHUNK)
+                .into_iter()
+                .map(|emitter| {
+                    commands
+                        .spawn((
+                            FluidSound,
+                            AudioPlayer::new(sounds.flow.clone()),
+                            PlaybackSettings::LOOP
+                                .with_spatial(true)
+                                .with_volume(Volume::new(emitter.volume())),
+                            Transform::from_translation(emitter.position),
+                        ))
+                        .id()
+                })
+                .collect();
+
+        commands
+            .entity(entity)
+            .add_children(&emitters)
+            .insert(FluidEmitters(emitters));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{U16Vec3, Vec3};
+
+    use crate::{block::BlockType, chunks::chunk::ChunkData};
+
+    use super::fluid_emitters;
+
+    #[test]
+    fn test_fluid_emitters_ignores_covered_fluid() {
+        let mut chunk_data = ChunkData::default();
+        chunk_data.set_block_at(U16Vec3::new(2, 3, 2), BlockType::Water);
+        chunk_data.set_block_at(U16Vec3::new(2, 4, 2), BlockType::Water);
+
+        let emitters = fluid_emitters(&chunk_data, None, 4);
+        assert_eq!(1, emitters.len());
+        assert_eq!(1, emitters[0].cells);
+        assert_eq!(Vec3::new(2.0, 4.0, 2.0), emitters[0].position);
+    }
+
+    #[test]
+    fn test_fluid_emitters_aggregates_and_limits() {
+        let mut chunk_data = ChunkData::default();
+        for x in 0..chunk_data.size {
+            for z in 0..chunk_data.size {
+                chunk_data.set_block_at(U16Vec3::new(x, 5, z), BlockType::Water);
+            }
+        }
+        chunk_data.set_block_at(U16Vec3::new(0, 12, 0), BlockType::Water);
+
+        let emitters = fluid_emitters(&chunk_data, None, 4);
+        assert_eq!(4, emitters.len());
+        assert!(emitters.iter().all(|emitter| emitter.cells == 64));
+    }
+}
@@ -0,0 +1,25 @@
+pub mod ambient;
+pub mod blocks;
+pub mod fluid;
+pub mod music;
+
+use bevy::{
+    audio::{GlobalVolume, Volume},
+    ecs::{
+        query::Changed,
+        system::{Query, ResMut},
+    },
+};
+
+use crate::settings::Settings;
+
+/// Sets the volume every sound starts at from `audio.master`. Loops that are already playing,
+/// like music, follow it themselves.
+pub fn apply_master_volume(
+    settings_query: Query<&Settings, Changed<Settings>>,
+    mut global: ResMut<GlobalVolume>,
+) {
+    if let Ok(settings) = settings_query.get_single() {
+        global.volume = Volume::new(settings.audio.master);
+    }
+}
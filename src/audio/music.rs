@@ -0,0 +1,201 @@
+//! Background music. Tracks found in `MUSIC_DIR` play in a shuffled order, each looping for a
+//! while before the next one crossfades in over it.
+
+use std::{fs, path::Path};
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::{AssetServer, Handle},
+    audio::{AudioPlayer, AudioSink, AudioSinkPlayback, AudioSource, PlaybackSettings, Volume},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        system::{Commands, Query, Res, ResMut, Resource},
+        world::FromWorld,
+    },
+    log::warn,
+    time::Time,
+};
+use rand::Rng;
+
+use crate::settings::Settings;
+
+const MUSIC_DIR: &str = "assets/sounds/music";
+/// Seconds each track plays for before the next fades in.
+const TRACK_TIME: f32 = 150.0;
+/// Seconds a track takes to fade in or out.
+const CROSSFADE_TIME: f32 = 6.0;
+
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Playlist>()
+            .add_systems(Update, play_music);
+    }
+}
+
+/// Every track in `MUSIC_DIR`, and which is playing.
+#[derive(Resource)]
+pub struct Playlist {
+    tracks: Vec<Handle<AudioSource>>,
+    current: Option<usize>,
+    /// Seconds until the next track.
+    remaining: f32,
+}
+
+impl FromWorld for Playlist {
+    fn from_world(world: &mut bevy::ecs::world::World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        let mut paths: Vec<String> = match fs::read_dir(MUSIC_DIR) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| is_track(path))
+                .filter_map(|path| Some(path.file_name()?.to_str()?.to_string()))
+                .collect(),
+            Err(e) => {
+                warn!("failed to read {}, no music will play: {}", MUSIC_DIR, e);
+                vec![]
+            }
+        };
+        paths.sort();
+
+        Self {
+            tracks: paths
+                .into_iter()
+                .map(|name| asset_server.load(format!("sounds/music/{name}")))
+                .collect(),
+            current: None,
+            remaining: 0.0,
+        }
+    }
+}
+
+/// Files in `MUSIC_DIR` that can be played.
+fn is_track(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| matches!(extension, "ogg" | "wav"))
+}
+
+/// The track to play after `current` out of `count`, which is never the same one twice running.
+pub fn next_track(count: usize, current: Option<usize>, rng: &mut impl Rng) -> Option<usize> {
+    match (count, current) {
+        (0, _) => None,
+        (1, _) | (_, None) => Some(rng.gen_range(0..count)),
+        (_, Some(current)) => Some((current + rng.gen_range(1..count)) % count),
+    }
+}
+
+/// A track that's playing, fading in until the next one starts and then out.
+#[derive(Component, Debug, Default)]
+pub struct MusicTrack {
+    level: f32,
+    fading_out: bool,
+}
+
+impl MusicTrack {
+    /// Fades the track by `delta_secs`, returning how loud it now is from `0.0` to `1.0`.
+    pub fn fade(&mut self, delta_secs: f32) -> f32 {
+        let change = delta_secs / CROSSFADE_TIME;
+        self.level = if self.fading_out {
+            self.level - change
+        } else {
+            self.level + change
+        }
+        .clamp(0.0, 1.0);
+        self.level
+    }
+
+    /// Whether the track has faded out, and can be stopped.
+    pub fn is_silent(&self) -> bool {
+        self.fading_out && self.level <= 0.0
+    }
+}
+
+/// Starts the next track when it's time, crossfading it with the last one, and keeps the tracks
+/// playing at the volume in `audio.music`.
+fn play_music(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut playlist: ResMut<Playlist>,
+    settings_query: Query<&Settings>,
+    mut track_query: Query<(Entity, &mut MusicTrack, Option<&AudioSink>)>,
+) {
+    let audio = settings_query
+        .get_single()
+        .map(|settings| settings.audio)
+        .unwrap_or_default();
+
+    playlist.remaining -= time.delta_secs();
+    if playlist.remaining <= 0.0 {
+        playlist.remaining = TRACK_TIME;
+        let next = next_track(
+            playlist.tracks.len(),
+            playlist.current,
+            &mut rand::thread_rng(),
+        );
+        if next != playlist.current {
+            for (_, mut track, _) in track_query.iter_mut() {
+                track.fading_out = true;
+            }
+            if let Some(next) = next {
+                commands.spawn((
+                    AudioPlayer::new(playlist.tracks[next].clone()),
+                    PlaybackSettings::LOOP.with_volume(Volume::new(0.0)),
+                    MusicTrack::default(),
+                ));
+            }
+            playlist.current = next;
+        }
+    }
+
+    let volume = audio.master * audio.music;
+    for (entity, mut track, sink) in track_query.iter_mut() {
+        let level = track.fade(time.delta_secs());
+        if track.is_silent() {
+            commands.entity(entity).despawn();
+        } else if let Some(sink) = sink {
+            sink.set_volume(level * volume);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{next_track, MusicTrack, CROSSFADE_TIME};
+
+    #[test]
+    fn test_next_track_never_repeats() {
+        let mut rng = StdRng::seed_from_u64(3);
+        assert_eq!(None, next_track(0, None, &mut rng));
+        assert_eq!(Some(0), next_track(1, Some(0), &mut rng));
+
+        let mut current = next_track(3, None, &mut rng);
+        for _ in 0..50 {
+            let next = next_track(3, current, &mut rng);
+            assert_ne!(current, next);
+            assert!(next.is_some_and(|next| next < 3));
+            current = next;
+        }
+    }
+
+    #[test]
+    fn test_tracks_crossfade() {
+        let (mut old, mut new) = (MusicTrack::default(), MusicTrack::default());
+        old.fade(CROSSFADE_TIME);
+        old.fading_out = true;
+
+        let half = CROSSFADE_TIME / 2.0;
+        assert_eq!(0.5, old.fade(half));
+        assert_eq!(0.5, new.fade(half));
+        assert!(!old.is_silent());
+
+        assert_eq!(0.0, old.fade(half));
+        assert_eq!(1.0, new.fade(half));
+        assert!(old.is_silent() && !new.is_silent());
+    }
+}
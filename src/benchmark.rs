@@ -0,0 +1,453 @@
+use std::{
+    error::Error,
+    f32::consts::TAU,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    ecs::{
+        query::With,
+        system::{Commands, In, Query, Res, ResMut, Resource},
+    },
+    log::{info, warn},
+    math::{I64Vec2, Quat, Vec3},
+    prelude::Mesh3d,
+    time::Time,
+    transform::components::Transform,
+};
+use serde::Serialize;
+
+use crate::{
+    chunks::{
+        chunk_loader::{Chunk, GenerateChunkData, GenerateChunkMesh},
+        generate::{
+            biome::{column_surface, Biome, ColumnSurface},
+            noise::NoiseGenerator,
+        },
+    },
+    command::{CommandArgs, CommandResult},
+    player::{MovementMode, Player, PlayerMovement},
+    settings::Settings,
+    world::World,
+};
+
+const REPORT_DIR: &str = "benchmarks";
+/// Spacing in blocks between columns sampled while searching for a stop's biome.
+const SEARCH_STEP: i64 = 64;
+const SEARCH_RADIUS: i64 = 32;
+/// Height of the camera above the terrain surface for open-air stops.
+const FLY_HEIGHT: f32 = 16.0;
+const CAVE_DEPTH: f32 = 24.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BenchmarkPreset {
+    Quick,
+    Standard,
+    Long,
+}
+
+impl BenchmarkPreset {
+    pub const ALL: [BenchmarkPreset; 3] = [Self::Quick, Self::Standard, Self::Long];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Quick => "quick",
+            Self::Standard => "standard",
+            Self::Long => "long",
+        }
+    }
+
+    /// Seconds spent at each stop before recording, letting nearby chunks stream in.
+    fn warmup(&self) -> f32 {
+        match self {
+            Self::Quick => 2.0,
+            Self::Standard => 5.0,
+            Self::Long => 10.0,
+        }
+    }
+
+    /// Seconds of frame times recorded at each stop.
+    fn duration(&self) -> f32 {
+        match self {
+            Self::Quick => 5.0,
+            Self::Standard => 15.0,
+            Self::Long => 30.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stop {
+    Plains,
+    Mountains,
+    Cave,
+    Ocean,
+}
+
+const ROUTE: [Stop; 4] = [Stop::Plains, Stop::Mountains, Stop::Cave, Stop::Ocean];
+
+impl Stop {
+    fn biome(&self) -> Biome {
+        match self {
+            Self::Plains => Biome::Plains,
+            Self::Mountains | Self::Cave => Biome::Mountains,
+            Self::Ocean => Biome::Ocean,
+        }
+    }
+
+    fn height(&self, surface: ColumnSurface) -> f32 {
+        match self {
+            Self::Cave => (surface.height as f32 - CAVE_DEPTH).max(4.0),
+            _ => surface.height as f32 + FLY_HEIGHT,
+        }
+    }
+}
+
+/// Finds the nearest sampled column of the given biome, searching outwards in square rings.
+fn find_biome(
+    noise: &mut NoiseGenerator,
+    world_height: u64,
+    biome: Biome,
+) -> Option<(I64Vec2, ColumnSurface)> {
+    for ring in 0..=SEARCH_RADIUS {
+        for x in -ring..=ring {
+            for z in -ring..=ring {
+                if x.abs() != ring && z.abs() != ring {
+                    continue;
+                }
+
+                let column = I64Vec2::new(x, z) * SEARCH_STEP;
+                let surface = column_surface(noise, column, world_height);
+                if Biome::from_surface(surface) == biome {
+                    return Some((column, surface));
+                }
+            }
+        }
+    }
+    None
+}
+
+fn plan_route(world: &World) -> Vec<(Stop, Vec3)> {
+    let mut noise = world.noise_generator();
+    ROUTE
+        .into_iter()
+        .filter_map(|stop| {
+            let Some((column, surface)) = find_biome(&mut noise, world.height, stop.biome()) else {
+                warn!("benchmark skipping {:?}, no matching terrain nearby", stop);
+                return None;
+            };
+            Some((
+                stop,
+                Vec3::new(column.x as f32, stop.height(surface), column.y as f32),
+            ))
+        })
+        .collect()
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize)]
+pub struct FrameStats {
+    pub frames: usize,
+    pub mean_fps: f32,
+    pub mean_ms: f32,
+    pub p99_ms: f32,
+    pub max_ms: f32,
+}
+
+impl FrameStats {
+    pub fn from_frame_times(frame_times: &[f32]) -> Self {
+        if frame_times.is_empty() {
+            return Self::default();
+        }
+
+        let mut sorted = frame_times.to_vec();
+        sorted.sort_by(f32::total_cmp);
+        let total: f32 = sorted.iter().sum();
+        let mean = total / sorted.len() as f32;
+        let p99 = sorted[((sorted.len() - 1) as f32 * 0.99).round() as usize];
+
+        Self {
+            frames: sorted.len(),
+            mean_fps: 1.0 / mean,
+            mean_ms: mean * 1000.0,
+            p99_ms: p99 * 1000.0,
+            max_ms: sorted[sorted.len() - 1] * 1000.0,
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize)]
+pub struct ChunkStats {
+    /// Most chunks waiting on terrain generation in any recorded frame.
+    pub max_generating: usize,
+    /// Most chunks waiting on meshing in any recorded frame.
+    pub max_meshing: usize,
+    /// Chunks with a mesh when recording finished.
+    pub loaded: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StopReport {
+    pub stop: Stop,
+    pub position: [f32; 3],
+    pub frames: FrameStats,
+    pub chunks: ChunkStats,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkReport {
+    pub preset: BenchmarkPreset,
+    pub version: &'static str,
+    pub timestamp: u64,
+    pub seed: u32,
+    pub render_distance: u32,
+    pub bloom: bool,
+    pub color_grading: bool,
+    pub foliage_density: f32,
+    pub stops: Vec<StopReport>,
+}
+
+impl BenchmarkReport {
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::new();
+        let _ = writeln!(md, "# Benchmark report: {}\n", self.preset.name());
+        let _ = writeln!(md, "- version: {}", self.version);
+        let _ = writeln!(md, "- timestamp: {}", self.timestamp);
+        let _ = writeln!(md, "- seed: {}", self.seed);
+        let _ = writeln!(md, "- render distance: {}", self.render_distance);
+        let _ = writeln!(md, "- bloom: {}", self.bloom);
+        let _ = writeln!(md, "- color grading: {}", self.color_grading);
+        let _ = writeln!(md, "- foliage density: {}\n", self.foliage_density);
+
+        let _ = writeln!(
+            md,
+            "| stop | frames | mean fps | mean ms | p99 ms | max ms | max generating | max meshing | loaded |"
+        );
+        let _ = writeln!(md, "|---|---|---|---|---|---|---|---|---|");
+        for stop in &self.stops {
+            let _ = writeln!(
+                md,
+                "| {:?} | {} | {:.1} | {:.2} | {:.2} | {:.2} | {} | {} | {} |",
+                stop.stop,
+                stop.frames.frames,
+                stop.frames.mean_fps,
+                stop.frames.mean_ms,
+                stop.frames.p99_ms,
+                stop.frames.max_ms,
+                stop.chunks.max_generating,
+                stop.chunks.max_meshing,
+                stop.chunks.loaded,
+            );
+        }
+        md
+    }
+
+    /// Writes the report as markdown and JSON, returning the path of the markdown file.
+    pub fn write(&self, dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
+        fs::create_dir_all(dir)?;
+        let name = format!("{}-{}", self.preset.name(), self.timestamp);
+        let md_path = dir.join(format!("{name}.md"));
+        fs::write(&md_path, self.to_markdown())?;
+        fs::write(
+            dir.join(format!("{name}.json")),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(md_path)
+    }
+}
+
+#[derive(Resource)]
+pub struct Benchmark {
+    preset: BenchmarkPreset,
+    route: Vec<(Stop, Vec3)>,
+    current: usize,
+    elapsed: f32,
+    frame_times: Vec<f32>,
+    chunk_stats: ChunkStats,
+    stops: Vec<StopReport>,
+}
+
+/// `/benchmark [preset]` flies the player along the benchmark route and writes a report.
+pub fn benchmark_command(
+    In(mut args): In<CommandArgs>,
+    mut commands: Commands,
+    world: Res<World>,
+    benchmark: Option<Res<Benchmark>>,
+) -> CommandResult {
+    if benchmark.is_some() {
+        return Err("a benchmark is already running".to_string());
+    }
+
+    let preset_name = args.optional_word().unwrap_or_else(|| "quick".to_string());
+    args.finish()?;
+    let Some(preset) = BenchmarkPreset::parse(&preset_name) else {
+        let presets: Vec<&str> = BenchmarkPreset::ALL.iter().map(|p| p.name()).collect();
+        return Err(format!(
+            "unknown benchmark preset '{}', expected one of {}",
+            preset_name,
+            presets.join(", ")
+        ));
+    };
+
+    commands.insert_resource(Benchmark {
+        preset,
+        route: plan_route(&world),
+        current: 0,
+        elapsed: 0.0,
+        frame_times: Vec::new(),
+        chunk_stats: ChunkStats::default(),
+        stops: Vec::new(),
+    });
+    Ok(format!("starting {} benchmark", preset.name()))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn run_benchmark(
+    mut commands: Commands,
+    time: Res<Time>,
+    world: Res<World>,
+    benchmark: Option<ResMut<Benchmark>>,
+    mut player_query: Query<(&mut Transform, &mut PlayerMovement), With<Player>>,
+    generating_query: Query<(), With<GenerateChunkData>>,
+    meshing_query: Query<(), With<GenerateChunkMesh>>,
+    loaded_query: Query<(), (With<Chunk>, With<Mesh3d>)>,
+    settings_query: Query<&Settings>,
+) {
+    let Some(mut benchmark) = benchmark else {
+        return;
+    };
+    let Ok((mut transform, mut movement)) = player_query.get_single_mut() else {
+        return;
+    };
+
+    if let Some(&(_, position)) = benchmark.route.get(benchmark.current) {
+        let warmup = benchmark.preset.warmup();
+        let total = warmup + benchmark.preset.duration();
+
+        if benchmark.elapsed == 0.0 {
+            transform.translation = position;
+            transform.rotation = Quat::IDENTITY;
+            movement.mode = MovementMode::Noclip;
+            movement.stop();
+        }
+
+        // a full turn per stop so the loader has to stream chunks in every direction
+        let delta = time.delta_secs();
+        transform.rotate_y(TAU * delta / total);
+        benchmark.elapsed += delta;
+
+        if benchmark.elapsed > warmup {
+            benchmark.frame_times.push(delta);
+            let chunk_stats = &mut benchmark.chunk_stats;
+            chunk_stats.max_generating = chunk_stats
+                .max_generating
+                .max(generating_query.iter().count());
+            chunk_stats.max_meshing = chunk_stats.max_meshing.max(meshing_query.iter().count());
+        }
+
+        if benchmark.elapsed >= total {
+            let stop = benchmark.route[benchmark.current].0;
+            let chunks = ChunkStats {
+                loaded: loaded_query.iter().count(),
+                ..benchmark.chunk_stats
+            };
+            let frames = FrameStats::from_frame_times(&benchmark.frame_times);
+            info!("benchmark {:?}: {:.1} fps", stop, frames.mean_fps);
+
+            benchmark.stops.push(StopReport {
+                stop,
+                position: position.to_array(),
+                frames,
+                chunks,
+            });
+            benchmark.current += 1;
+            benchmark.elapsed = 0.0;
+            benchmark.frame_times.clear();
+            benchmark.chunk_stats = ChunkStats::default();
+        }
+        return;
+    }
+
+    let settings = settings_query.get_single().cloned().unwrap_or_default();
+    let report = BenchmarkReport {
+        preset: benchmark.preset,
+        version: env!("CARGO_PKG_VERSION"),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+        seed: world.seed(),
+        render_distance: settings.renderer.render_distance,
+        bloom: settings.graphics.bloom,
+        color_grading: settings.graphics.color_grading,
+        foliage_density: settings.graphics.foliage_density,
+        stops: std::mem::take(&mut benchmark.stops),
+    };
+    match report.write(Path::new(REPORT_DIR)) {
+        Ok(path) => info!("benchmark report written to {}", path.display()),
+        Err(e) => warn!("failed to write benchmark report: {}", e),
+    }
+    commands.remove_resource::<Benchmark>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BenchmarkPreset, BenchmarkReport, ChunkStats, FrameStats, Stop, StopReport};
+
+    #[test]
+    fn test_parse_preset() {
+        assert_eq!(
+            Some(BenchmarkPreset::Quick),
+            BenchmarkPreset::parse("quick")
+        );
+        assert_eq!(Some(BenchmarkPreset::Long), BenchmarkPreset::parse("LONG"));
+        assert_eq!(None, BenchmarkPreset::parse("forever"));
+    }
+
+    #[test]
+    fn test_frame_stats() {
+        let mut frame_times = vec![0.01; 98];
+        frame_times.extend([0.1, 0.1]);
+        let stats = FrameStats::from_frame_times(&frame_times);
+
+        assert_eq!(100, stats.frames);
+        assert!((stats.mean_ms - 11.8).abs() < 0.01);
+        assert!((stats.p99_ms - 100.0).abs() < 0.01);
+        assert!((stats.max_ms - 100.0).abs() < 0.01);
+        assert_eq!(FrameStats::default(), FrameStats::from_frame_times(&[]));
+    }
+
+    #[test]
+    fn test_report_markdown_lists_stops() {
+        let report = BenchmarkReport {
+            preset: BenchmarkPreset::Quick,
+            version: "0.1.0",
+            timestamp: 0,
+            seed: 1,
+            render_distance: 8,
+            bloom: true,
+            color_grading: true,
+            foliage_density: 0.4,
+            stops: vec![StopReport {
+                stop: Stop::Cave,
+                position: [0.0; 3],
+                frames: FrameStats::from_frame_times(&[0.02]),
+                chunks: ChunkStats::default(),
+            }],
+        };
+
+        let markdown = report.to_markdown();
+        assert!(markdown.contains("# Benchmark report: quick"));
+        assert!(markdown.contains("| Cave | 1 | 50.0 | 20.00 |"));
+    }
+}
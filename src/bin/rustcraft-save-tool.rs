@@ -0,0 +1,126 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process,
+};
+
+use rustcraft::{
+    mob::registry::{MobRegistry, MOBS_DIR},
+    save::{
+        inspect::{inspect_world, repair_world, REGION_SIZE},
+        map::render_map,
+        WorldInfo,
+    },
+};
+
+const USAGE: &str =
+    "usage: rustcraft-save-tool <stats|verify|repair> <world name>\n       rustcraft-save-tool map <world name> [output dir]";
+
+/// Inspects a world's save without running the game.
+///
+/// Usage: `rustcraft-save-tool <stats|verify|repair> <world name>`. `stats` prints how many
+/// chunks each region holds, how many of each block they contain, the largest chunk files and
+/// how many problems were found. `verify` lists the problems, exiting with 1 if there are any,
+/// and `repair` fixes them: corrupt chunks and stray files are moved to the world's quarantine
+/// and pets or gravestones that no longer exist are removed.
+///
+/// `rustcraft-save-tool map <world name> [output dir]` renders the world's explored columns to
+/// map tiles with a page to view them in a browser, in `<world name>-map` by default.
+fn main() {
+    let mut args = env::args().skip(1);
+    let (Some(mode), Some(name)) = (args.next(), args.next()) else {
+        eprintln!("{USAGE}");
+        process::exit(2);
+    };
+    if !["stats", "verify", "repair", "map"].contains(&mode.as_str()) {
+        eprintln!("{USAGE}");
+        process::exit(2);
+    }
+
+    let world_info = WorldInfo::load(&WorldInfo::new(&name, 0).dir()).unwrap_or_else(|e| {
+        eprintln!("failed to load {name}: {e}");
+        process::exit(1);
+    });
+    if mode == "map" {
+        let out = args
+            .next()
+            .map_or_else(|| PathBuf::from(format!("{name}-map")), PathBuf::from);
+        match render_map(&world_info, &out) {
+            Ok(summary) => println!(
+                "drew {} columns into {} tiles, open {} to view them",
+                summary.columns,
+                summary.tiles,
+                out.join("index.html").display()
+            ),
+            Err(e) => {
+                eprintln!("failed to draw the map of {name}: {e}");
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let mobs = MobRegistry::load(Path::new(MOBS_DIR)).unwrap_or_else(|e| {
+        eprintln!("failed to load mobs, every pet will be unknown: {e}");
+        MobRegistry::default()
+    });
+    let report = inspect_world(&world_info, &mobs).unwrap_or_else(|e| {
+        eprintln!("failed to inspect {name}: {e}");
+        process::exit(1);
+    });
+
+    match mode.as_str() {
+        "stats" => {
+            let chunks: usize = report.regions.values().sum();
+            println!(
+                "{name}: {chunks} chunks in {} regions of {REGION_SIZE}^3 chunks",
+                report.regions.len()
+            );
+            for (region, chunks) in &report.regions {
+                println!(
+                    "  region {} {} {}: {chunks}",
+                    region[0], region[1], region[2]
+                );
+            }
+
+            let mut blocks: Vec<_> = report.blocks.iter().collect();
+            blocks.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+            println!("blocks:");
+            for (block, count) in blocks {
+                println!("  {block}: {count}");
+            }
+
+            println!("largest chunks:");
+            for (coord, size) in &report.largest {
+                let coord = coord.0;
+                println!("  {} {} {}: {size} bytes", coord.x, coord.y, coord.z);
+            }
+            println!(
+                "{} problems, run verify to list them",
+                report.problems.len()
+            );
+        }
+        "verify" => {
+            for problem in &report.problems {
+                println!("{problem}");
+            }
+            if !report.problems.is_empty() {
+                println!("{} problems, run repair to fix them", report.problems.len());
+                process::exit(1);
+            }
+            println!("no problems found");
+        }
+        _ => {
+            let fixes = repair_world(&world_info, &report.problems).unwrap_or_else(|e| {
+                eprintln!("failed to repair {name}: {e}");
+                process::exit(1);
+            });
+            for fix in &fixes {
+                println!("{fix}");
+            }
+            if fixes.is_empty() {
+                println!("no problems found");
+            }
+        }
+    }
+}
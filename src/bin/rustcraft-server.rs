@@ -0,0 +1,184 @@
+use std::{env, error::Error, io, process, time::Duration};
+
+use bevy::{app::ScheduleRunnerPlugin, log::LogPlugin, prelude::*};
+use rustcraft::{
+    block::BlockType,
+    chunks::generate::terrain::GeneratorRegistry,
+    net::{
+        dedicated::{DedicatedServerPlugin, RESTART_EXIT_CODE},
+        metrics::MetricsEndpoint,
+        schedule::{RestartCountdown, Schedule},
+        server::Server,
+    },
+    save::{parse_seed, upgrade::upgrade_world, WorldInfo},
+    settings::{read_server_settings, read_settings, SETTINGS_FILE},
+    world::World,
+};
+
+const TICKS_PER_SECOND: f64 = 30.0;
+
+/// Hosts a world for remote players without opening a window.
+///
+/// Usage: `rustcraft-server [world name] [seed]`. An existing save with the same name is
+/// loaded, otherwise a new world is created from the seed. A save that exists but can't be read,
+/// or whose world was made with a mod's generator, stops the server instead, leaving it
+/// untouched. The port is read from the `[network]` section of `settings.toml`, along with
+/// `metrics_port`, which serves Prometheus metrics when set. Scheduled commands and automatic
+/// restarts are read from the `[server]` section; a restarting server saves its metadata and
+/// edited chunks, then runs itself again with the same arguments. If the world can't be saved
+/// the restart is put off and retried.
+///
+/// `rustcraft-server --upgrade-world <world name> [fallback block]` instead rewrites the world's
+/// saved chunks with this version's block ids, replacing blocks it no longer has with the
+/// fallback (air by default), and exits.
+fn main() {
+    let mut args = env::args().skip(1);
+    let name = args.next().unwrap_or_else(|| "world".to_string());
+    if name == "--upgrade-world" {
+        upgrade(args.next(), args.next());
+        return;
+    }
+    let seed = args.next().unwrap_or_default();
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+            1.0 / TICKS_PER_SECOND,
+        ))),
+        LogPlugin::default(),
+    ));
+
+    let settings = read_settings(SETTINGS_FILE).unwrap_or_else(|e| {
+        warn!("failed to read {}, using defaults: {}", SETTINGS_FILE, e);
+        Default::default()
+    });
+    let server_settings = read_server_settings(SETTINGS_FILE).unwrap_or_else(|e| {
+        warn!("failed to read the server's schedule, running none: {}", e);
+        Default::default()
+    });
+    let server = Server::bind(settings.network.port)
+        .unwrap_or_else(|e| {
+            error!("failed to listen on port {}: {}", settings.network.port, e);
+            process::exit(1);
+        })
+        .with_reach(settings.player.reach)
+        .with_max_players(settings.network.max_players);
+
+    let new_world = WorldInfo::new(&name, 0);
+    let world_info = match WorldInfo::load(&new_world.dir()) {
+        Ok(world_info) => world_info,
+        Err(e) if is_not_found(&*e) => WorldInfo::new(&name, parse_seed(&seed))
+            .with_chunk_size(settings.world.chunk_size())
+            .with_generator(settings.world.generator.clone()),
+        // anything else is left alone rather than replaced by a new world in the same directory
+        Err(e) => {
+            error!("failed to load {}: {}", new_world.dir().display(), e);
+            process::exit(1);
+        }
+    };
+    // dedicated servers don't load mods, so can't generate worlds made with one
+    let world = World::with_chunk_size(world_info.seed, world_info.chunk_size)
+        .with_ores(settings.world.ores)
+        .with_generator(world_info.generator.clone(), &GeneratorRegistry::default())
+        .unwrap_or_else(|e| {
+            error!("failed to load {}: {}", world_info.name, e);
+            process::exit(1);
+        })
+        .with_history(settings.world.edit_history as usize);
+    // saved even when loaded, to keep any ids given to new blocks
+    if let Err(e) = world_info.save() {
+        warn!("failed to save {}: {}", world_info.name, e);
+    }
+    info!(
+        "hosting {} ({} terrain, seed {}) on port {}, type 'help' for commands",
+        world_info.name,
+        world_info.generator.name(),
+        world_info.seed,
+        settings.network.port
+    );
+
+    app.add_plugins(DedicatedServerPlugin)
+        .insert_resource(world)
+        .insert_resource(world_info)
+        .insert_resource(server)
+        .insert_resource(Schedule::new(&server_settings));
+    if let Some(countdown) = RestartCountdown::new(&server_settings) {
+        info!("restarting in {:.0} minutes", countdown.remaining() / 60.0);
+        app.insert_resource(countdown);
+    }
+    if let Some(port) = settings.network.metrics_port {
+        match MetricsEndpoint::bind(port) {
+            Ok(endpoint) => {
+                info!("serving metrics on port {}", port);
+                app.insert_resource(endpoint);
+            }
+            Err(e) => warn!("failed to serve metrics on port {}: {}", port, e),
+        }
+    }
+    if app.run() == AppExit::from_code(RESTART_EXIT_CODE) {
+        restart();
+    }
+}
+
+/// Replaces this process with a fresh copy of the server, given the same arguments.
+fn restart() {
+    let exe = env::current_exe().unwrap_or_else(|e| {
+        error!("failed to find the server executable to restart: {}", e);
+        process::exit(1);
+    });
+    let mut command = process::Command::new(exe);
+    command.args(env::args_os().skip(1));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let e = command.exec();
+        error!("failed to restart: {}", e);
+        process::exit(1);
+    }
+
+    #[cfg(not(unix))]
+    match command.status() {
+        Ok(status) => process::exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            error!("failed to restart: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Whether loading a world failed only because it hasn't been saved yet.
+fn is_not_found(e: &(dyn Error + 'static)) -> bool {
+    e.downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::NotFound)
+}
+
+fn upgrade(name: Option<String>, fallback: Option<String>) {
+    let Some(name) = name else {
+        eprintln!("usage: rustcraft-server --upgrade-world <world name> [fallback block]");
+        process::exit(2);
+    };
+    let fallback = match fallback {
+        Some(fallback) => BlockType::from_name(&fallback).unwrap_or_else(|| {
+            eprintln!("unknown block {fallback}");
+            process::exit(2);
+        }),
+        None => BlockType::Air,
+    };
+
+    let mut world_info = WorldInfo::load(&WorldInfo::new(&name, 0).dir()).unwrap_or_else(|e| {
+        eprintln!("failed to load {name}: {e}");
+        process::exit(1);
+    });
+    let report = upgrade_world(&mut world_info, fallback).unwrap_or_else(|e| {
+        eprintln!("failed to upgrade {name}: {e}");
+        process::exit(1);
+    });
+    println!("upgraded {} chunks of {}", report.chunks, name);
+    for (key, chunks) in &report.unknown {
+        println!(
+            "  {key} is no longer a block, replaced with {} in {chunks} chunks",
+            fallback.name()
+        );
+    }
+}
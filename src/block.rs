@@ -1,4 +1,9 @@
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+use bevy::{color::Color, math::Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::block_registry::definition;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
 pub enum BlockType {
     Air,
     Stone,
@@ -6,6 +11,24 @@ pub enum BlockType {
     Sand,
     Water,
     Snow,
+    Lava,
+    Glowstone,
+    Coal,
+    Iron,
+    Gold,
+    Fence,
+    /// Left where a player died, holding what they carried. See `death::Gravestones`.
+    Gravestone,
+    Glass,
+    Leaves,
+    StoneSlab,
+    StoneStairs,
+    TallGrass,
+    Flower,
+    /// An iron cage that spawns mobs in dungeons. See `mob::spawner`.
+    Spawner,
+    /// A thin covering of snow left by snowfall. See `snow`.
+    SnowLayer,
 }
 
 impl Default for BlockType {
@@ -14,4 +37,250 @@ impl Default for BlockType {
     }
 }
 
-pub const BLOCK_COUNT: usize = 6;
+pub const BLOCK_COUNT: usize = 21;
+
+/// Every block type, indexed by its numeric id.
+pub const ALL_BLOCKS: [BlockType; BLOCK_COUNT] = [
+    BlockType::Air,
+    BlockType::Stone,
+    BlockType::Grass,
+    BlockType::Sand,
+    BlockType::Water,
+    BlockType::Snow,
+    BlockType::Lava,
+    BlockType::Glowstone,
+    BlockType::Coal,
+    BlockType::Iron,
+    BlockType::Gold,
+    BlockType::Fence,
+    BlockType::Gravestone,
+    BlockType::Glass,
+    BlockType::Leaves,
+    BlockType::StoneSlab,
+    BlockType::StoneStairs,
+    BlockType::TallGrass,
+    BlockType::Flower,
+    BlockType::Spawner,
+    BlockType::SnowLayer,
+];
+
+/// Blocks the player can select for placement, in selection order.
+pub const PLACEABLE_BLOCKS: [BlockType; 17] = [
+    BlockType::Stone,
+    BlockType::Grass,
+    BlockType::Sand,
+    BlockType::Water,
+    BlockType::Snow,
+    BlockType::Lava,
+    BlockType::Glowstone,
+    BlockType::Coal,
+    BlockType::Iron,
+    BlockType::Gold,
+    BlockType::Fence,
+    BlockType::Glass,
+    BlockType::Leaves,
+    BlockType::StoneSlab,
+    BlockType::StoneStairs,
+    BlockType::TallGrass,
+    BlockType::Flower,
+];
+
+/// How a block's faces are drawn, which decides the chunk mesh they go in.
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone, Deserialize)]
+pub enum BlockLayer {
+    #[default]
+    Opaque,
+    /// Drawn with the opaque blocks, with fully transparent texels cut out.
+    Cutout,
+    /// Blended over whatever is behind it, in a chunk's translucent mesh.
+    Translucent,
+}
+
+/// The material a block sounds like when it's walked on, broken or placed. See
+/// `audio::blocks`.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Copy, Clone, Deserialize)]
+pub enum BlockSound {
+    #[default]
+    Stone,
+    Grass,
+    Sand,
+    Snow,
+    Wood,
+    Glass,
+    Metal,
+}
+
+/// Geometry of a block within its cell.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum BlockShape {
+    Cube,
+    /// The bottom half of the cell.
+    Slab,
+    /// A slab with a step on its +z half.
+    Stairs,
+    /// Two crossed planes along the cell's diagonals, for plants. Has nothing to collide with.
+    Cross,
+    /// A thin layer over the bottom of the cell.
+    Layer,
+}
+
+const CUBE_BOXES: [(Vec3, Vec3); 1] = [(Vec3::splat(-0.5), Vec3::splat(0.5))];
+const SLAB_BOXES: [(Vec3, Vec3); 1] = [(Vec3::splat(-0.5), Vec3::new(0.5, 0.0, 0.5))];
+const LAYER_BOXES: [(Vec3, Vec3); 1] = [(Vec3::splat(-0.5), Vec3::new(0.5, -0.375, 0.5))];
+const STAIRS_BOXES: [(Vec3, Vec3); 2] = [
+    (Vec3::splat(-0.5), Vec3::new(0.5, 0.0, 0.5)),
+    (Vec3::new(-0.5, 0.0, 0.0), Vec3::splat(0.5)),
+];
+
+impl BlockShape {
+    /// Boxes the shape is built from as `(min, max)` relative to the block's centre, used both to
+    /// mesh it and to collide with it.
+    pub fn boxes(&self) -> &'static [(Vec3, Vec3)] {
+        match self {
+            Self::Cube => &CUBE_BOXES,
+            Self::Slab => &SLAB_BOXES,
+            Self::Stairs => &STAIRS_BOXES,
+            Self::Layer => &LAYER_BOXES,
+            Self::Cross => &[],
+        }
+    }
+}
+
+impl BlockType {
+    /// Position in `ALL_BLOCKS`. Saves number blocks through their world's `IdMap` instead.
+    pub fn id(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        ALL_BLOCKS.get(id as usize).copied()
+    }
+
+    /// Name shown to players and typed in commands, from the block's definition.
+    pub fn name(&self) -> &'static str {
+        &definition(*self).name
+    }
+
+    /// Namespaced id the block is saved under. Unlike `id`, which follows the order of
+    /// `ALL_BLOCKS`, keys never change between versions.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::Air => "rustcraft:air",
+            Self::Stone => "rustcraft:stone",
+            Self::Grass => "rustcraft:grass",
+            Self::Sand => "rustcraft:sand",
+            Self::Water => "rustcraft:water",
+            Self::Snow => "rustcraft:snow",
+            Self::Lava => "rustcraft:lava",
+            Self::Glowstone => "rustcraft:glowstone",
+            Self::Coal => "rustcraft:coal",
+            Self::Iron => "rustcraft:iron",
+            Self::Gold => "rustcraft:gold",
+            Self::Fence => "rustcraft:fence",
+            Self::Gravestone => "rustcraft:gravestone",
+            Self::Glass => "rustcraft:glass",
+            Self::Leaves => "rustcraft:leaves",
+            Self::StoneSlab => "rustcraft:stone_slab",
+            Self::StoneStairs => "rustcraft:stone_stairs",
+            Self::TallGrass => "rustcraft:tall_grass",
+            Self::Flower => "rustcraft:flower",
+            Self::Spawner => "rustcraft:spawner",
+            Self::SnowLayer => "rustcraft:snow_layer",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        ALL_BLOCKS.into_iter().find(|block| block.key() == key)
+    }
+
+    /// Looks a block up by name or key, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        ALL_BLOCKS.into_iter().find(|block| {
+            block.name().eq_ignore_ascii_case(name) || block.key().eq_ignore_ascii_case(name)
+        })
+    }
+
+    /// The colour the block looks from a distance, roughly the average of its texture with ores
+    /// shifted towards their specks so they can be told apart. Low spec rendering draws blocks in
+    /// this colour instead of their textures.
+    pub fn color(&self) -> Color {
+        match self {
+            Self::Air => Color::NONE,
+            _ => {
+                let [r, g, b] = definition(*self).color;
+                Color::srgb_u8(r, g, b)
+            }
+        }
+    }
+
+    /// Multiplier applied to the block's texture to make it glow. Values above `1.0` push the
+    /// colour into HDR range so it is picked up by bloom.
+    pub fn emissive(&self) -> f32 {
+        definition(*self).emissive
+    }
+
+    /// Layer of the block texture array its faces are drawn with.
+    pub fn texture(&self) -> u8 {
+        definition(*self).texture
+    }
+
+    /// How long the block takes to break, `0.0` meaning instantly.
+    pub fn hardness(&self) -> f32 {
+        definition(*self).hardness
+    }
+
+    /// Blocks that fall when the block below them is removed.
+    pub fn has_gravity(&self) -> bool {
+        matches!(self, Self::Sand)
+    }
+
+    /// Fluids that spread into neighbouring air, see `fluid::flow_step`.
+    pub fn flows(&self) -> bool {
+        matches!(self, Self::Water)
+    }
+
+    pub fn is_fluid(&self) -> bool {
+        matches!(self, Self::Water | Self::Lava)
+    }
+
+    /// Blocks mobs can be tied to with a lead.
+    pub fn is_fence(&self) -> bool {
+        matches!(self, Self::Fence)
+    }
+
+    pub fn layer(&self) -> BlockLayer {
+        definition(*self).layer
+    }
+
+    pub fn sound(&self) -> BlockSound {
+        definition(*self).sound
+    }
+
+    /// Blocks that can be seen through, so the faces of other blocks touching them are drawn.
+    /// Faces between two of the same transparent block are not.
+    pub fn is_transparent(&self) -> bool {
+        definition(*self).transparent
+    }
+
+    pub fn shape(&self) -> BlockShape {
+        match self {
+            Self::StoneSlab => BlockShape::Slab,
+            Self::StoneStairs => BlockShape::Stairs,
+            Self::SnowLayer => BlockShape::Layer,
+            Self::TallGrass | Self::Flower => BlockShape::Cross,
+            _ => BlockShape::Cube,
+        }
+    }
+
+    pub fn is_solid(&self) -> bool {
+        !matches!(
+            self,
+            Self::Air | Self::Water | Self::Lava | Self::TallGrass | Self::Flower
+        )
+    }
+
+    /// Blocks the player can aim at to break, which includes plants that are not solid.
+    pub fn is_selectable(&self) -> bool {
+        self.is_solid() || self.shape() == BlockShape::Cross
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fs,
+    path::Path,
+    sync::OnceLock,
+};
+
+use bevy::{
+    app::{App, Plugin},
+    ecs::system::Resource,
+    log::warn,
+};
+use serde::Deserialize;
+
+use crate::block::{BlockLayer, BlockSound, BlockType, ALL_BLOCKS, BLOCK_COUNT};
+
+pub const BLOCKS_DIR: &str = "assets/blocks";
+const DEFINITIONS_FILE: &str = "blocks.ron";
+const TAGS_FILE: &str = "tags.ron";
+/// The definitions built into the game, used when `BLOCKS_DIR` can't be read and by anything
+/// running without `BlockRegistryPlugin`, such as tests.
+const BUNDLED_DEFINITIONS: &str = include_str!("../assets/blocks/blocks.ron");
+
+/// Global rather than a resource, as `BlockType`'s methods read it without access to the app.
+static DEFINITIONS: OnceLock<[BlockDefinition; BLOCK_COUNT]> = OnceLock::new();
+
+/// Loads block definitions and the `BlockRegistry` from `BLOCKS_DIR`. Definitions change the
+/// properties of the blocks built into `BlockType`, they can't add blocks: that needs blocks to
+/// be given runtime ids, through the save's `IdMap`, rather than being compiled in.
+pub struct BlockRegistryPlugin;
+
+impl Plugin for BlockRegistryPlugin {
+    fn build(&self, app: &mut App) {
+        let path = Path::new(BLOCKS_DIR).join(DEFINITIONS_FILE);
+        match fs::read_to_string(&path)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|source| parse_definitions(&source))
+        {
+            Ok(definitions) => {
+                if DEFINITIONS.set(definitions).is_err() {
+                    warn!("blocks were used before {} was loaded", path.display());
+                }
+            }
+            Err(e) => warn!(
+                "failed to load {}, using built in blocks: {}",
+                path.display(),
+                e
+            ),
+        }
+
+        let registry = BlockRegistry::load(Path::new(BLOCKS_DIR)).unwrap_or_else(|e| {
+            warn!("failed to load blocks from {}: {}", BLOCKS_DIR, e);
+            BlockRegistry::default()
+        });
+        app.insert_resource(registry);
+    }
+}
+
+/// A block's properties, read from `DEFINITIONS_FILE` so they can be changed without rebuilding.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct BlockDefinition {
+    /// Shown to players and typed in commands.
+    pub name: String,
+    /// Layer of the block texture array, the column of the atlas, its faces are drawn with.
+    pub texture: u8,
+    #[serde(default)]
+    pub hardness: f32,
+    #[serde(default)]
+    pub transparent: bool,
+    #[serde(default)]
+    pub layer: BlockLayer,
+    #[serde(default)]
+    pub emissive: f32,
+    #[serde(default)]
+    pub sound: BlockSound,
+    /// sRGB colour the block is drawn with where its texture isn't, see `BlockType::color`.
+    #[serde(default)]
+    pub color: [u8; 3],
+}
+
+/// The definition of `block`, from `BLOCKS_DIR` once `BlockRegistryPlugin` has loaded it.
+pub fn definition(block: BlockType) -> &'static BlockDefinition {
+    let definitions = DEFINITIONS.get_or_init(|| {
+        parse_definitions(BUNDLED_DEFINITIONS).expect("built in block definitions are valid")
+    });
+    &definitions[block.id() as usize]
+}
+
+/// Reads a map from each block's key to its definition, which must cover every block in
+/// `ALL_BLOCKS` and nothing else.
+pub fn parse_definitions(source: &str) -> Result<[BlockDefinition; BLOCK_COUNT], Box<dyn Error>> {
+    let mut by_key: HashMap<String, BlockDefinition> = ron::from_str(source)?;
+    let definitions = ALL_BLOCKS.map(|block| by_key.remove(block.key()));
+    if let Some(key) = by_key.keys().next() {
+        return Err(format!("unknown block '{key}', blocks can't be added by definitions").into());
+    }
+    if let Some(block) = ALL_BLOCKS
+        .iter()
+        .find(|block| definitions[block.id() as usize].is_none())
+    {
+        return Err(format!("'{}' is not defined", block.key()).into());
+    }
+
+    let definitions = definitions.map(Option::unwrap);
+    for (i, definition) in definitions.iter().enumerate() {
+        if definition.name.is_empty() || definition.name.contains(char::is_whitespace) {
+            return Err(format!("'{}' is not a valid block name", definition.name).into());
+        }
+        if definitions[..i]
+            .iter()
+            .any(|other| other.name.eq_ignore_ascii_case(&definition.name))
+        {
+            return Err(format!("more than one block is named '{}'", definition.name).into());
+        }
+    }
+    Ok(definitions)
+}
+
+/// What is known about blocks beyond their type, loaded from `BLOCKS_DIR`. Tags name groups of
+/// blocks, e.g. `stone_like`, for rules that apply to any block in the group.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct BlockRegistry {
+    /// Members of each tag as written, kept so tags can be added to after loading.
+    definitions: BTreeMap<String, Vec<String>>,
+    /// Every block in each tag, with tags inside tags already expanded.
+    tags: HashMap<String, [bool; BLOCK_COUNT]>,
+}
+
+impl BlockRegistry {
+    pub fn load(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let path = dir.join(TAGS_FILE);
+        Self::parse_tags(&fs::read_to_string(&path)?)
+            .map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    /// Reads tags written as a map from each tag's name to its members, which are block names or
+    /// other tags starting with `#`:
+    ///
+    /// ```ron
+    /// {
+    ///     "ores": ["Coal", "Iron", "Gold"],
+    ///     "stone_like": ["Stone", "#ores"],
+    /// }
+    /// ```
+    pub fn parse_tags(source: &str) -> Result<Self, Box<dyn Error>> {
+        let definitions: BTreeMap<String, Vec<String>> = ron::from_str(source)?;
+        let tags = expand_tags(&definitions)?;
+        Ok(Self { definitions, tags })
+    }
+
+    /// Adds block names or `#`-prefixed tags to `tag`, creating it if needed. Tags containing
+    /// `tag` gain the new members too. Nothing changes if a member is unknown.
+    pub fn add_to_tag(&mut self, tag: &str, members: &[&str]) -> Result<(), String> {
+        let mut definitions = self.definitions.clone();
+        definitions
+            .entry(tag.strip_prefix('#').unwrap_or(tag).to_string())
+            .or_default()
+            .extend(members.iter().map(|member| member.to_string()));
+        self.tags = expand_tags(&definitions)?;
+        self.definitions = definitions;
+        Ok(())
+    }
+
+    /// Whether `block` is in `tag`, given with or without its leading `#`. Unknown tags hold no
+    /// blocks.
+    pub fn is(&self, block: BlockType, tag: &str) -> bool {
+        self.members(tag)
+            .is_some_and(|members| members[block.id() as usize])
+    }
+
+    /// Every block in `tag`.
+    pub fn blocks(&self, tag: &str) -> impl Iterator<Item = BlockType> + '_ {
+        let members = self.members(tag);
+        ALL_BLOCKS
+            .into_iter()
+            .filter(move |block| members.is_some_and(|members| members[block.id() as usize]))
+    }
+
+    fn members(&self, tag: &str) -> Option<&[bool; BLOCK_COUNT]> {
+        self.tags
+            .get(&tag.strip_prefix('#').unwrap_or(tag).to_lowercase())
+    }
+
+    /// Names of every tag, in no particular order.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.tags.keys().map(String::as_str)
+    }
+}
+
+fn expand_tags(
+    definitions: &BTreeMap<String, Vec<String>>,
+) -> Result<HashMap<String, [bool; BLOCK_COUNT]>, String> {
+    definitions
+        .keys()
+        .map(|name| {
+            Ok((
+                name.to_lowercase(),
+                expand_tag(name, definitions, &mut vec![])?,
+            ))
+        })
+        .collect()
+}
+
+/// The blocks in tag `name`, following nested tags. `visiting` holds the tags being expanded, to
+/// catch tags that contain themselves.
+fn expand_tag(
+    name: &str,
+    definitions: &BTreeMap<String, Vec<String>>,
+    visiting: &mut Vec<String>,
+) -> Result<[bool; BLOCK_COUNT], String> {
+    if visiting.iter().any(|tag| tag == name) {
+        return Err(format!("tag '{name}' contains itself"));
+    }
+    let members = definitions
+        .get(name)
+        .ok_or_else(|| format!("unknown tag '{name}'"))?;
+
+    visiting.push(name.to_string());
+    let mut blocks = [false; BLOCK_COUNT];
+    for member in members {
+        if let Some(tag) = member.strip_prefix('#') {
+            let nested = expand_tag(tag, definitions, visiting)?;
+            for (block, nested) in blocks.iter_mut().zip(nested) {
+                *block |= nested;
+            }
+        } else {
+            let block = BlockType::from_name(member)
+                .ok_or_else(|| format!("tag '{name}' has unknown block '{member}'"))?;
+            blocks[block.id() as usize] = true;
+        }
+    }
+    visiting.pop();
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{
+        parse_definitions, BlockRegistry, BLOCKS_DIR, BUNDLED_DEFINITIONS, DEFINITIONS_FILE,
+    };
+    use crate::block::{BlockLayer, BlockType};
+
+    #[test]
+    fn test_bundled_definitions() {
+        let definitions = parse_definitions(BUNDLED_DEFINITIONS).unwrap();
+        let glass = &definitions[BlockType::Glass.id() as usize];
+        assert_eq!("Glass", glass.name);
+        assert_eq!(BlockLayer::Translucent, glass.layer);
+        assert!(glass.transparent);
+        assert_eq!(6.0, BlockType::Glowstone.emissive());
+        assert_eq!(BlockType::Flower.id() - 1, BlockType::Flower.texture());
+
+        let source =
+            std::fs::read_to_string(std::path::Path::new(BLOCKS_DIR).join(DEFINITIONS_FILE))
+                .unwrap();
+        assert_eq!(definitions, parse_definitions(&source).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_definitions() {
+        let stone = r#""rustcraft:stone": (name: "Stone", texture: 0)"#;
+        assert!(parse_definitions(&format!("{{{stone}}}")).is_err());
+        let all = BUNDLED_DEFINITIONS.trim_end().trim_end_matches('}');
+        assert!(parse_definitions(&format!(
+            "{all} \"rustcraft:marble\": (name: \"Marble\", texture: 0), }}"
+        ))
+        .is_err());
+        assert!(parse_definitions(&BUNDLED_DEFINITIONS.replace("\"Sand\"", "\"Stone\"")).is_err());
+        assert!(
+            parse_definitions(&BUNDLED_DEFINITIONS.replace("\"Sand\"", "\"Red Sand\"")).is_err()
+        );
+    }
+
+    #[test]
+    fn test_nested_tags() {
+        let registry = BlockRegistry::parse_tags(
+            r##"{
+                "ores": ["Coal", "Iron"],
+                "stone_like": ["Stone", "#ores"],
+            }"##,
+        )
+        .unwrap();
+
+        assert!(registry.is(BlockType::Iron, "stone_like"));
+        assert!(registry.is(BlockType::Stone, "#stone_like"));
+        assert!(!registry.is(BlockType::Stone, "ores"));
+        assert!(!registry.is(BlockType::Stone, "logs"));
+        assert_eq!(
+            vec![BlockType::Coal, BlockType::Iron],
+            registry.blocks("ores").collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_add_to_tag() {
+        let mut registry = BlockRegistry::parse_tags(
+            r##"{
+                "ores": ["Coal"],
+                "stone_like": ["Stone", "#ores"],
+            }"##,
+        )
+        .unwrap();
+
+        registry.add_to_tag("#ores", &["Gold"]).unwrap();
+        registry.add_to_tag("shiny", &["Glass", "#ores"]).unwrap();
+        assert!(registry.is(BlockType::Gold, "stone_like"));
+        assert!(registry.is(BlockType::Gold, "shiny"));
+        assert!(registry.is(BlockType::Glass, "shiny"));
+
+        assert!(registry.add_to_tag("ores", &["Stne"]).is_err());
+        assert!(registry.add_to_tag("ores", &["#stone_like"]).is_err());
+        assert!(!registry.is(BlockType::Stone, "ores"));
+    }
+
+    #[test]
+    fn test_invalid_tags() {
+        assert!(BlockRegistry::parse_tags(r#"{"a": ["Stne"]}"#).is_err());
+        assert!(BlockRegistry::parse_tags(r##"{"a": ["#b"]}"##).is_err());
+        assert!(BlockRegistry::parse_tags(r##"{"a": ["#b"], "b": ["Stone", "#a"]}"##).is_err());
+    }
+
+    #[test]
+    fn test_bundled_tags_load() {
+        let registry = BlockRegistry::load(Path::new(BLOCKS_DIR)).unwrap();
+        assert!(registry.is(BlockType::Grass, "foliage_soil"));
+    }
+}
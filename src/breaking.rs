@@ -0,0 +1,271 @@
+//! Breaking blocks over time. Holding break on a block cracks it for as long as its hardness,
+//! drawn as a cracked overlay over the block, before it's removed.
+
+use bevy::{
+    asset::{Assets, Handle},
+    ecs::{
+        change_detection::DetectChangesMut,
+        component::Component,
+        query::With,
+        system::{Commands, Query, Res, ResMut, Resource},
+    },
+    image::{Image, ImageSampler},
+    math::{primitives::Cuboid, I64Vec3, IVec2},
+    pbr::{MeshMaterial3d, NotShadowCaster, StandardMaterial},
+    prelude::{AlphaMode, Mesh3d},
+    render::{
+        mesh::Mesh,
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        view::Visibility,
+    },
+    transform::components::Transform,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{interaction::TargetBlock, world::World};
+
+/// Seconds between breaking one block and starting on the next while break is held.
+const BREAK_COOLDOWN: f32 = 0.25;
+/// Number of crack textures drawn over a block as it breaks.
+const CRACK_STAGES: usize = 8;
+const CRACK_SIZE: u32 = 16;
+/// Lines the cracks spread along from the middle of each face.
+const CRACK_LINES: usize = 6;
+
+/// How far the player has got breaking the block they're holding break on.
+#[derive(Resource, Default, Debug)]
+pub struct BreakProgress {
+    /// The block being broken.
+    pub block: Option<I64Vec3>,
+    /// Seconds spent breaking it.
+    elapsed: f32,
+    /// Seconds before the next block can be started on.
+    cooldown: f32,
+}
+
+impl BreakProgress {
+    /// Spends `delta_secs` breaking `target` of `hardness` while break is `held`, returning
+    /// whether it's broken. Progress starts over when the target changes or break is let go.
+    pub fn advance(
+        &mut self,
+        target: Option<I64Vec3>,
+        hardness: f32,
+        held: bool,
+        delta_secs: f32,
+    ) -> bool {
+        self.cooldown = (self.cooldown - delta_secs).max(0.0);
+        let target = target.filter(|_| held);
+        if target != self.block {
+            self.block = None;
+            self.elapsed = 0.0;
+        }
+        let Some(target) = target else {
+            return false;
+        };
+        if self.cooldown > 0.0 {
+            return false;
+        }
+
+        self.block = Some(target);
+        self.elapsed += delta_secs;
+        if self.elapsed < hardness {
+            return false;
+        }
+        *self = Self {
+            cooldown: BREAK_COOLDOWN,
+            ..Default::default()
+        };
+        true
+    }
+
+    /// How far through breaking a block of `hardness` the player is, from `0.0` to `1.0`.
+    pub fn fraction(&self, hardness: f32) -> f32 {
+        if hardness <= 0.0 {
+            return 1.0;
+        }
+        (self.elapsed / hardness).min(1.0)
+    }
+}
+
+/// Pixels of the crack texture in the order they appear, spreading out from the middle along a
+/// few jagged lines.
+fn crack_pixels() -> Vec<IVec2> {
+    let mut rng = StdRng::seed_from_u64(0xc4ac);
+    let size = CRACK_SIZE as i32;
+    let mut lines: Vec<Vec<IVec2>> = (0..CRACK_LINES)
+        .map(|line| {
+            let angle =
+                line as f32 / CRACK_LINES as f32 * std::f32::consts::TAU + rng.gen_range(-0.3..0.3);
+            let mut position = IVec2::splat(size / 2);
+            let mut pixels = vec![];
+            while (0..size).contains(&position.x) && (0..size).contains(&position.y) {
+                pixels.push(position);
+                let wobble = angle + rng.gen_range(-0.8..0.8);
+                position += IVec2::new(wobble.cos().round() as i32, wobble.sin().round() as i32);
+            }
+            pixels
+        })
+        .collect();
+
+    // interleave the lines so they all grow together
+    let mut pixels = vec![];
+    while lines.iter().any(|line| !line.is_empty()) {
+        for line in &mut lines {
+            if !line.is_empty() {
+                let pixel = line.remove(0);
+                if !pixels.contains(&pixel) {
+                    pixels.push(pixel);
+                }
+            }
+        }
+    }
+    pixels
+}
+
+/// RGBA texels of the crack texture for `stage`, with more of the crack showing each stage.
+pub fn crack_texels(stage: usize) -> Vec<u8> {
+    let pixels = crack_pixels();
+    let shown = pixels.len() * (stage + 1) / CRACK_STAGES;
+    let mut texels = vec![0; (CRACK_SIZE * CRACK_SIZE * 4) as usize];
+    for pixel in &pixels[..shown] {
+        let index = (pixel.y as u32 * CRACK_SIZE + pixel.x as u32) as usize * 4;
+        texels[index..index + 4].copy_from_slice(&[20, 20, 20, 200]);
+    }
+    texels
+}
+
+/// The overlay drawn over the block being broken.
+#[derive(Component)]
+pub struct CrackOverlay;
+
+/// Materials for each crack stage.
+#[derive(Resource)]
+pub struct CrackMaterials(Vec<Handle<StandardMaterial>>);
+
+pub fn spawn_crack_overlay(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let stages: Vec<Handle<StandardMaterial>> = (0..CRACK_STAGES)
+        .map(|stage| {
+            let mut image = Image::new(
+                Extent3d {
+                    width: CRACK_SIZE,
+                    height: CRACK_SIZE,
+                    depth_or_array_layers: 1,
+                },
+                TextureDimension::D2,
+                crack_texels(stage),
+                TextureFormat::Rgba8UnormSrgb,
+                RenderAssetUsages::RENDER_WORLD,
+            );
+            image.sampler = ImageSampler::nearest();
+            materials.add(StandardMaterial {
+                base_color_texture: Some(images.add(image)),
+                unlit: true,
+                alpha_mode: AlphaMode::Blend,
+                ..Default::default()
+            })
+        })
+        .collect();
+
+    commands.spawn((
+        // a little larger than a block so it isn't hidden by the block's own faces
+        Mesh3d(meshes.add(Cuboid::from_length(1.01))),
+        MeshMaterial3d(stages[0].clone()),
+        Transform::default(),
+        Visibility::Hidden,
+        NotShadowCaster,
+        CrackOverlay,
+    ));
+    commands.insert_resource(CrackMaterials(stages));
+}
+
+/// Shows the crack stage of the block being broken over it.
+pub fn show_break_progress(
+    mut world: ResMut<World>,
+    progress: Res<BreakProgress>,
+    target: Res<TargetBlock>,
+    stages: Res<CrackMaterials>,
+    mut overlay_query: Query<
+        (
+            &mut Transform,
+            &mut Visibility,
+            &mut MeshMaterial3d<StandardMaterial>,
+        ),
+        With<CrackOverlay>,
+    >,
+) {
+    let Ok((mut transform, mut visibility, mut material)) = overlay_query.get_single_mut() else {
+        return;
+    };
+    let Some(block) = progress
+        .block
+        .filter(|block| target.0.is_some_and(|hit| hit.block == *block))
+    else {
+        visibility.set_if_neq(Visibility::Hidden);
+        return;
+    };
+
+    let fraction = progress.fraction(world.get_block(block).hardness());
+    let stage = ((fraction * CRACK_STAGES as f32) as usize).min(CRACK_STAGES - 1);
+    visibility.set_if_neq(Visibility::Inherited);
+    transform.translation = block.as_vec3();
+    if material.0 != stages.0[stage] {
+        material.0 = stages.0[stage].clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::I64Vec3;
+
+    use super::{crack_texels, BreakProgress, BREAK_COOLDOWN, CRACK_STAGES};
+
+    #[test]
+    fn test_breaking_takes_hardness_seconds() {
+        let mut progress = BreakProgress::default();
+        let block = Some(I64Vec3::new(1, 2, 3));
+        assert!(!progress.advance(block, 1.5, true, 1.0));
+        assert_eq!(2.0 / 3.0, progress.fraction(1.5));
+        assert!(progress.advance(block, 1.5, true, 0.5));
+
+        // the next block waits for the cooldown, and instant blocks break at once after it
+        assert!(!progress.advance(block, 0.0, true, BREAK_COOLDOWN / 2.0));
+        assert!(progress.advance(block, 0.0, true, BREAK_COOLDOWN));
+    }
+
+    #[test]
+    fn test_breaking_starts_over_on_a_new_target() {
+        let mut progress = BreakProgress::default();
+        let (a, b) = (Some(I64Vec3::ZERO), Some(I64Vec3::X));
+        progress.advance(a, 2.0, true, 1.5);
+        assert!(!progress.advance(b, 2.0, true, 1.0));
+        assert_eq!(b, progress.block);
+        assert_eq!(0.5, progress.fraction(2.0));
+
+        // letting go loses the progress too
+        progress.advance(b, 2.0, false, 0.1);
+        assert_eq!(None, progress.block);
+        assert!(!progress.advance(b, 2.0, true, 1.5));
+    }
+
+    #[test]
+    fn test_cracks_grow_each_stage() {
+        let shown = |stage| {
+            crack_texels(stage)
+                .chunks(4)
+                .map(|texel| texel[3] > 0)
+                .collect::<Vec<_>>()
+        };
+        let (first, last) = (shown(0), shown(CRACK_STAGES - 1));
+        assert!(first.iter().any(|&cracked| cracked));
+        assert!(first.iter().zip(&last).all(|(&a, &b)| !a || b));
+        assert!(
+            last.iter().filter(|&&cracked| cracked).count() > first.iter().filter(|&&c| c).count()
+        );
+    }
+}
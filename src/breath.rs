@@ -0,0 +1,156 @@
+//! Breath, which runs out while the player's head is under water and then drowns them.
+
+use bevy::{
+    app::{App, Plugin, Update},
+    core_pipeline::core_3d::Camera3d,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::EventWriter,
+        query::{With, Without},
+        schedule::IntoSystemConfigs,
+        system::{Query, Res, ResMut},
+    },
+    hierarchy::Parent,
+    state::condition::in_state,
+    time::Time,
+    transform::components::GlobalTransform,
+};
+
+use crate::{
+    block::BlockType,
+    mob::combat::Damage,
+    player::{Player, Spectator},
+    state::GameState,
+    third_person::CameraView,
+    ui::underwater::fluid_surface,
+    world::World,
+};
+
+/// Seconds the player can stay under water before drowning.
+pub const PLAYER_MAX_BREATH: f32 = 10.0;
+/// Seconds of breath regained per second above water.
+const RECOVERY_RATE: f32 = 5.0;
+/// Damage dealt every `DROWNING_INTERVAL` seconds once breath has run out.
+const DROWNING_DAMAGE: f32 = 2.0;
+const DROWNING_INTERVAL: f32 = 1.0;
+
+pub struct BreathPlugin;
+
+impl Plugin for BreathPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_breath.run_if(in_state(GameState::InGame)));
+    }
+}
+
+/// Seconds of air left before drowning.
+#[derive(Component, Debug, Copy, Clone, PartialEq)]
+pub struct Breath {
+    pub current: f32,
+    pub max: f32,
+    /// Seconds spent drowning since damage was last dealt.
+    drowning: f32,
+}
+
+impl Breath {
+    pub fn new(max: f32) -> Self {
+        Self {
+            current: max,
+            max,
+            drowning: 0.0,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.current >= self.max
+    }
+
+    /// Spends or regains breath over `delta_secs` with the head in or out of water, returning the
+    /// damage dealt by drowning.
+    pub fn tick(&mut self, submerged: bool, delta_secs: f32) -> f32 {
+        if !submerged {
+            self.current = (self.current + RECOVERY_RATE * delta_secs).min(self.max);
+            self.drowning = 0.0;
+            return 0.0;
+        }
+
+        let left = self.current - delta_secs;
+        self.current = left.max(0.0);
+        if left >= 0.0 {
+            return 0.0;
+        }
+        // only the time spent without breath counts towards drowning
+        self.drowning += (-left).min(delta_secs);
+        let hits = (self.drowning / DROWNING_INTERVAL).floor();
+        self.drowning -= hits * DROWNING_INTERVAL;
+        hits * DROWNING_DAMAGE
+    }
+}
+
+impl Default for Breath {
+    fn default() -> Self {
+        Self::new(PLAYER_MAX_BREATH)
+    }
+}
+
+/// Spends the player's breath while their eye is under water, hurting them once it's gone.
+fn update_breath(
+    time: Res<Time>,
+    mut world: ResMut<World>,
+    mut damage: EventWriter<Damage>,
+    view: Res<CameraView>,
+    camera_query: Query<(&Parent, &GlobalTransform), With<Camera3d>>,
+    mut player_query: Query<(Entity, &mut Breath), (With<Player>, Without<Spectator>)>,
+) {
+    for (parent, camera) in camera_query.iter() {
+        let Ok((player, mut breath)) = player_query.get_mut(parent.get()) else {
+            continue;
+        };
+        let eye = view.eye(camera);
+        let submerged = fluid_surface(&mut world, eye)
+            .is_some_and(|(fluid, surface)| fluid == BlockType::Water && surface > eye.y);
+        if !submerged && breath.is_full() {
+            continue;
+        }
+
+        let amount = breath.tick(submerged, time.delta_secs());
+        if amount > 0.0 {
+            damage.send(Damage {
+                target: player,
+                attacker: None,
+                amount,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Breath, DROWNING_DAMAGE};
+
+    #[test]
+    fn test_breath_runs_out_then_drowns() {
+        let mut breath = Breath::new(2.0);
+        assert_eq!(0.0, breath.tick(true, 1.5));
+        assert_eq!(0.5, breath.current);
+        // half a second of this is spent holding the last of the breath
+        assert_eq!(0.0, breath.tick(true, 1.0));
+        assert_eq!(0.0, breath.current);
+        assert_eq!(DROWNING_DAMAGE, breath.tick(true, 0.5));
+        assert_eq!(2.0 * DROWNING_DAMAGE, breath.tick(true, 2.0));
+    }
+
+    #[test]
+    fn test_breath_recovers_above_water() {
+        let mut breath = Breath::new(10.0);
+        breath.tick(true, 20.0);
+        assert_eq!(0.0, breath.tick(false, 1.0));
+        assert!(breath.current > 0.0 && !breath.is_full());
+        breath.tick(false, 10.0);
+        assert!(breath.is_full());
+
+        // drowning starts over after coming up for air
+        breath.tick(true, 10.5);
+        assert_eq!(0.0, breath.tick(true, 0.4));
+    }
+}
@@ -0,0 +1,378 @@
+//! Items on the ground, mobs and falling blocks kept with the chunk they're in. When a chunk
+//! unloads they're saved with the world and despawned, and they come back once the chunk has
+//! loaded again. Pets aren't tied to a chunk and are saved with the world as a whole, see
+//! `mob::pet`.
+//!
+//! Pausing or closing the game also saves the entities of every loaded chunk, right after the
+//! chunks' blocks, while leaving them in the world. A chunk's file is only ever replaced by a
+//! newer one, never removed while its entities are out, so none are lost if the game stops
+//! before they're saved again. Each file has a generation, and the generation of each file whose
+//! entities are in the world is remembered so they aren't brought back a second time. Once one of
+//! those entities despawns or moves to another chunk, the file it was saved in is rewritten.
+
+use std::error::Error;
+
+use bevy::{
+    app::{App, AppExit, Last, Plugin, Update},
+    asset::Assets,
+    ecs::{
+        entity::Entity,
+        query::{Added, Has, Without},
+        removal_detection::RemovedComponents,
+        schedule::{
+            common_conditions::{on_event, resource_exists},
+            Condition, IntoSystemConfigs,
+        },
+        system::{Commands, Local, Query, Res, ResMut, Resource, SystemParam},
+    },
+    hierarchy::DespawnRecursiveExt,
+    log::warn,
+    math::Vec3,
+    render::mesh::Mesh,
+    state::{condition::in_state, state::OnEnter},
+    transform::components::Transform,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    chunks::{
+        chunk::ChunkCoordinate,
+        chunk_loader::{
+            evict_chunk_data, receive_chunk_results, save_resident_chunks, Chunk, ChunkLoader,
+            GenerateChunkData,
+        },
+    },
+    falling_block::{falling_block_bundle, FallingBlock},
+    item::{BlockMeshes, ItemDrop, ITEM_SCALE},
+    mob::{
+        mob_bundle, natural::NaturalSpawn, pet::Pet, registry::MobRegistry, ride::Saddled, Health,
+        Mob,
+    },
+    save::{SavedChunkEntities, SavedFallingBlock, SavedItem, SavedMob, WorldInfo},
+    state::GameState,
+    tick::TickPosition,
+    world::World,
+};
+
+/// Saves and restores the entities of chunks as they unload and load. Clients have no save, so
+/// only the host or a single player world keeps them.
+pub struct ChunkEntityPlugin;
+
+impl Plugin for ChunkEntityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SavedInWorld>()
+            .add_systems(
+                Update,
+                (
+                    restore_chunk_entities.after(receive_chunk_results),
+                    store_chunk_entities.after(evict_chunk_data),
+                    save_stale_chunk_entities.after(store_chunk_entities),
+                )
+                    .run_if(in_state(GameState::InGame).or(in_state(GameState::Loading)))
+                    .run_if(resource_exists::<WorldInfo>),
+            )
+            .add_systems(
+                OnEnter(GameState::Paused),
+                save_chunk_entities
+                    .after(save_resident_chunks)
+                    .run_if(resource_exists::<WorldInfo>),
+            )
+            .add_systems(
+                Last,
+                save_chunk_entities
+                    .after(save_resident_chunks)
+                    .run_if(on_event::<AppExit>)
+                    .run_if(resource_exists::<WorldInfo>),
+            );
+    }
+}
+
+/// The chunk files whose entities are in the world, and those entities.
+#[derive(Resource, Default)]
+struct SavedInWorld {
+    /// The generation of each file, kept until its chunk unloads. Only these chunks are saved, as
+    /// saving any other would replace entities that haven't been brought back yet.
+    chunks: HashMap<ChunkCoordinate, u64>,
+    /// Entities still in the world that one of those files has, with its chunk.
+    entities: HashMap<Entity, ChunkCoordinate>,
+}
+
+impl SavedInWorld {
+    /// Saves the entities gathered in `coord` as the next generation of its file. Returns the
+    /// chunks any of them were saved in before, whose files are now stale.
+    fn save(
+        &mut self,
+        world_info: &WorldInfo,
+        coord: ChunkCoordinate,
+        mut saved: SavedChunkEntities,
+        stored: Vec<Entity>,
+    ) -> Result<HashSet<ChunkCoordinate>, Box<dyn Error>> {
+        saved.generation = self
+            .chunks
+            .get(&coord)
+            .map_or(1, |generation| generation + 1);
+        world_info.save_chunk_entities(coord, &saved)?;
+        self.chunks.insert(coord, saved.generation);
+        self.entities.retain(|_, saved_in| *saved_in != coord);
+        Ok(stored
+            .into_iter()
+            .filter_map(|entity| self.entities.insert(entity, coord))
+            .collect())
+    }
+
+    /// Saves `stale` chunks again, along with any chunk that an entity in one of them was saved
+    /// in before.
+    fn save_stale(
+        &mut self,
+        world_info: &WorldInfo,
+        entities: &ChunkEntities,
+        mut stale: HashSet<ChunkCoordinate>,
+    ) {
+        while !stale.is_empty() {
+            stale.retain(|coord| self.chunks.contains_key(coord));
+            let mut next = HashSet::new();
+            for (coord, (saved, stored)) in entities.gather(&stale) {
+                match self.save(world_info, coord, saved, stored) {
+                    Ok(moved_from) => next.extend(moved_from),
+                    Err(e) => warn!("failed to save entities of chunk {:?}: {}", coord, e),
+                }
+            }
+            stale = next;
+        }
+    }
+}
+
+/// The entities that are saved with chunks.
+#[derive(SystemParam)]
+pub struct ChunkEntities<'w, 's> {
+    world: Res<'w, World>,
+    registry: Res<'w, MobRegistry>,
+    items: Query<'w, 's, (Entity, &'static ItemDrop, &'static Transform)>,
+    #[allow(clippy::type_complexity)]
+    mobs: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static Mob,
+            &'static TickPosition,
+            &'static Health,
+            Has<Saddled>,
+            Has<NaturalSpawn>,
+        ),
+        Without<Pet>,
+    >,
+    falling_blocks: Query<'w, 's, (Entity, &'static FallingBlock, &'static TickPosition)>,
+}
+
+impl ChunkEntities<'_, '_> {
+    /// The entities in each of `chunks` as they'll be saved, along with the entities themselves.
+    /// Chunks with nothing in them are included, so their saved entities can be cleared.
+    fn gather(
+        &self,
+        chunks: &HashSet<ChunkCoordinate>,
+    ) -> HashMap<ChunkCoordinate, (SavedChunkEntities, Vec<Entity>)> {
+        let mut gathered: HashMap<ChunkCoordinate, (SavedChunkEntities, Vec<Entity>)> = chunks
+            .iter()
+            .map(|&coord| (coord, Default::default()))
+            .collect();
+
+        for (entity, item, transform) in self.items.iter() {
+            let position = transform.translation;
+            if let Some((saved, entities)) = gathered.get_mut(&self.world.chunk_at(position)) {
+                saved.items.push(SavedItem {
+                    item: item.item,
+                    count: item.count,
+                    position: position.to_array(),
+                    age: item.age(),
+                });
+                entities.push(entity);
+            }
+        }
+        for (entity, mob, position, health, saddled, natural) in self.mobs.iter() {
+            let Some(definition) = self.registry.get(mob.id) else {
+                continue;
+            };
+            if let Some((saved, entities)) =
+                gathered.get_mut(&self.world.chunk_at(position.current))
+            {
+                saved.mobs.push(SavedMob {
+                    mob: definition.name.clone(),
+                    position: position.current.to_array(),
+                    health: health.current,
+                    saddled,
+                    natural,
+                });
+                entities.push(entity);
+            }
+        }
+        for (entity, falling, position) in self.falling_blocks.iter() {
+            if let Some((saved, entities)) =
+                gathered.get_mut(&self.world.chunk_at(position.current))
+            {
+                saved.falling_blocks.push(SavedFallingBlock {
+                    block: falling.block,
+                    position: position.current.to_array(),
+                });
+                entities.push(entity);
+            }
+        }
+        gathered
+    }
+}
+
+/// Brings back the entities saved in each chunk as it finishes loading.
+#[allow(clippy::too_many_arguments)]
+fn restore_chunk_entities(
+    mut commands: Commands,
+    world_info: Res<WorldInfo>,
+    registry: Res<MobRegistry>,
+    chunk_loader: Res<ChunkLoader>,
+    mut block_meshes: ResMut<BlockMeshes>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut saved_in_world: ResMut<SavedInWorld>,
+    mut generated: RemovedComponents<GenerateChunkData>,
+    chunk_query: Query<&Chunk>,
+) {
+    for entity in generated.read() {
+        // the chunk was unloaded before it finished
+        let Ok(chunk) = chunk_query.get(entity) else {
+            continue;
+        };
+        let coord = chunk.coord();
+        let saved = match world_info.load_chunk_entities(coord) {
+            Ok(saved) => saved,
+            Err(e) => {
+                warn!("failed to load entities of chunk {:?}: {}", coord, e);
+                continue;
+            }
+        };
+        // saving them when unloading failed, so they were never despawned
+        if saved_in_world.chunks.get(&coord) == Some(&saved.generation) {
+            continue;
+        }
+        saved_in_world.chunks.insert(coord, saved.generation);
+
+        for item in saved.items {
+            let spawned = commands.spawn((
+                ItemDrop::new(item.item, Vec3::ZERO)
+                    .with_count(item.count)
+                    .with_age(item.age),
+                Transform::from_translation(Vec3::from_array(item.position))
+                    .with_scale(Vec3::splat(ITEM_SCALE)),
+            ));
+            saved_in_world.entities.insert(spawned.id(), coord);
+        }
+        for mob in saved.mobs {
+            let Some((id, definition)) = registry.by_name(&mob.mob) else {
+                warn!("skipping saved entity of unknown mob '{}'", mob.mob);
+                continue;
+            };
+            let health = Health {
+                current: mob.health.min(definition.health),
+                max: definition.health,
+            };
+            let mut spawned =
+                commands.spawn(mob_bundle(id, &registry, Vec3::from_array(mob.position)));
+            spawned.insert(health);
+            if mob.saddled {
+                spawned.insert(Saddled);
+            }
+            if mob.natural {
+                spawned.insert(NaturalSpawn);
+            }
+            saved_in_world.entities.insert(spawned.id(), coord);
+        }
+        for falling in saved.falling_blocks {
+            let spawned = commands.spawn(falling_block_bundle(
+                falling.block,
+                Vec3::from_array(falling.position),
+                block_meshes.get(&mut meshes, falling.block),
+                chunk_loader.material_for(falling.block),
+            ));
+            saved_in_world.entities.insert(spawned.id(), coord);
+        }
+    }
+}
+
+/// Saves the entities in each chunk that unloads and despawns them. Chunk entities are gone by
+/// the time they're seen to unload, so where each was is remembered as it's spawned.
+fn store_chunk_entities(
+    mut commands: Commands,
+    world_info: Res<WorldInfo>,
+    entities: ChunkEntities,
+    mut saved_in_world: ResMut<SavedInWorld>,
+    mut loaded: Local<HashMap<Entity, ChunkCoordinate>>,
+    added_query: Query<(Entity, &Chunk), Added<Chunk>>,
+    mut removed: RemovedComponents<Chunk>,
+) {
+    loaded.extend(
+        added_query
+            .iter()
+            .map(|(entity, chunk)| (entity, chunk.coord())),
+    );
+    let unloaded: HashSet<ChunkCoordinate> = removed
+        .read()
+        .filter_map(|entity| loaded.remove(&entity))
+        .filter(|coord| saved_in_world.chunks.contains_key(coord))
+        .collect();
+    if unloaded.is_empty() {
+        return;
+    }
+
+    let mut stale = HashSet::new();
+    for (coord, (saved, stored)) in entities.gather(&unloaded) {
+        let despawned = stored.clone();
+        match saved_in_world.save(&world_info, coord, saved, stored) {
+            Ok(moved_from) => stale.extend(moved_from),
+            Err(e) => {
+                // better left in the world than lost
+                warn!("failed to save entities of chunk {:?}: {}", coord, e);
+                continue;
+            }
+        }
+        saved_in_world.chunks.remove(&coord);
+        saved_in_world
+            .entities
+            .retain(|_, saved_in| *saved_in != coord);
+        for entity in despawned {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    saved_in_world.save_stale(&world_info, &entities, stale);
+}
+
+/// Saves the entities of every loaded chunk just after `save_resident_chunks` saves their blocks,
+/// so the two are read back together, leaving the entities where they are.
+fn save_chunk_entities(
+    world_info: Res<WorldInfo>,
+    entities: ChunkEntities,
+    mut saved_in_world: ResMut<SavedInWorld>,
+) {
+    // every chunk is saved, so none are left stale
+    let loaded = saved_in_world.chunks.keys().copied().collect();
+    for (coord, (saved, stored)) in entities.gather(&loaded) {
+        if let Err(e) = saved_in_world.save(&world_info, coord, saved, stored) {
+            warn!("failed to save entities of chunk {:?}: {}", coord, e);
+        }
+    }
+}
+
+/// Saves again the chunks that a saved entity has since despawned from, such as an item being
+/// picked up, which would otherwise be loaded again alongside the picked up copy.
+fn save_stale_chunk_entities(
+    world_info: Res<WorldInfo>,
+    entities: ChunkEntities,
+    mut saved_in_world: ResMut<SavedInWorld>,
+    mut items: RemovedComponents<ItemDrop>,
+    mut mobs: RemovedComponents<Mob>,
+    mut falling_blocks: RemovedComponents<FallingBlock>,
+) {
+    let stale: HashSet<ChunkCoordinate> = items
+        .read()
+        .chain(mobs.read())
+        .chain(falling_blocks.read())
+        .filter_map(|entity| saved_in_world.entities.remove(&entity))
+        .collect();
+    saved_in_world.save_stale(&world_info, &entities, stale);
+}
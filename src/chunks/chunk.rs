@@ -1,12 +1,16 @@
-use std::sync::Arc;
+use std::{fmt::Debug, sync::Arc};
 
 use bevy::{
     math::{I64Vec3, U16Vec3, Vec3},
     utils::HashMap,
 };
 
+use super::{
+    generate::visibility::MAX_MASK_SIZE,
+    layout::{BlockStorage, ChunkLayout},
+};
 use crate::block::BlockType;
-use crate::util::octree::Octree;
+use crate::util::octree::{Octree, OctreeCounts};
 
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub struct ChunkCoordinate(pub I64Vec3);
@@ -36,37 +40,81 @@ impl ChunkCoordinate {
     }
 }
 
-type BlockPalette = HashMap<U16Vec3, BlockType>;
-
+#[derive(Clone)]
 pub struct ChunkData {
-    blocks: BlockPalette,
+    blocks: BlockStorage<ChunkLayout>,
+    /// Levels of fluid blocks that aren't full. Most fluid is still, so this stays small.
+    fluid_levels: HashMap<U16Vec3, u8>,
     pub size: u16,
     pub dirty: bool,
 }
 
+/// Default width of a chunk in blocks. Each world picks its own size, see `is_valid_chunk_size`.
 pub const CHUNK_SIZE: u16 = 16;
+/// Smallest chunk the octree's leaves can tell apart.
+pub const MIN_CHUNK_SIZE: u16 = 8;
+/// Level of a fluid block that fills its whole cell, such as generated or placed water.
+pub const FULL_FLUID_LEVEL: u8 = 8;
+
+/// Chunk sizes must be a power of two so Morton storage isn't padded, and fit the mesher's face
+/// masks.
+pub fn is_valid_chunk_size(size: u16) -> bool {
+    size.is_power_of_two() && (MIN_CHUNK_SIZE..=MAX_MASK_SIZE).contains(&size)
+}
 
 impl Default for ChunkData {
     fn default() -> Self {
         Self {
-            blocks: HashMap::new(),
+            blocks: BlockStorage::new(CHUNK_SIZE),
+            fluid_levels: HashMap::new(),
             size: CHUNK_SIZE,
             dirty: false,
         }
     }
 }
 
+impl Debug for ChunkData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChunkData")
+            .field("size", &self.size)
+            .field("dirty", &self.dirty)
+            .finish_non_exhaustive()
+    }
+}
+
 impl ChunkData {
+    pub fn with_size(size: u16) -> Self {
+        Self {
+            blocks: BlockStorage::new(size),
+            fluid_levels: HashMap::new(),
+            size,
+            dirty: false,
+        }
+    }
+
     fn is_block_in_chunk(&self, block_coord: U16Vec3) -> bool {
         return block_coord.x < self.size && block_coord.y < self.size && block_coord.z < self.size;
     }
 
     pub fn empty(&self) -> bool {
-        self.blocks.is_empty()
+        self.blocks.count() == 0
+    }
+
+    /// Approximate bytes of memory the chunk takes up.
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + (self.size as usize).pow(3) * std::mem::size_of::<BlockType>()
+            + self.fluid_levels.len() * (std::mem::size_of::<U16Vec3>() + 1)
     }
 
-    pub fn blocks(&self) -> &BlockPalette {
-        &self.blocks
+    /// Number of blocks in the chunk that aren't air.
+    pub fn block_count(&self) -> usize {
+        self.blocks.count()
+    }
+
+    /// Every block in the chunk that isn't air, in storage order.
+    pub fn blocks(&self) -> impl Iterator<Item = (U16Vec3, BlockType)> + '_ {
+        self.blocks.iter()
     }
 
     pub fn get_block_at(&self, block_coord: U16Vec3) -> BlockType {
@@ -74,7 +122,23 @@ impl ChunkData {
             panic!("get block {:?} not in chunk", block_coord);
         }
 
-        return *self.blocks.get(&block_coord).unwrap_or(&BlockType::Air);
+        self.blocks.get(block_coord)
+    }
+
+    /// Block directly above `block_coord`, looking into `chunk_above` at the top of the chunk.
+    /// Unknown neighbours are treated as air.
+    pub fn get_block_above(
+        &self,
+        block_coord: U16Vec3,
+        chunk_above: Option<&ChunkData>,
+    ) -> BlockType {
+        if block_coord.y < self.size - 1 {
+            self.get_block_at(block_coord + U16Vec3::Y)
+        } else {
+            chunk_above
+                .map(|above| above.get_block_at(U16Vec3::new(block_coord.x, 0, block_coord.z)))
+                .unwrap_or_default()
+        }
     }
 
     pub fn set_block_at(&mut self, block_coord: U16Vec3, block_type: BlockType) {
@@ -82,29 +146,85 @@ impl ChunkData {
             panic!("set block {:?} not in chunk", block_coord);
         }
 
-        self.blocks.insert(block_coord, block_type);
+        self.blocks.set(block_coord, block_type);
+        self.fluid_levels.remove(&block_coord);
         self.dirty = true;
     }
+
+    /// How full the block's cell is, from 1 to `FULL_FLUID_LEVEL`. Blocks that aren't a partly
+    /// drained fluid are full.
+    pub fn fluid_level_at(&self, block_coord: U16Vec3) -> u8 {
+        self.fluid_levels
+            .get(&block_coord)
+            .copied()
+            .unwrap_or(FULL_FLUID_LEVEL)
+    }
+
+    /// Places `block_type` as a fluid filled to `level`.
+    pub fn set_fluid_at(&mut self, block_coord: U16Vec3, block_type: BlockType, level: u8) {
+        self.set_block_at(block_coord, block_type);
+        if level < FULL_FLUID_LEVEL {
+            self.fluid_levels.insert(block_coord, level.max(1));
+        }
+    }
+
+    /// Every fluid block that isn't full, with its level.
+    pub fn fluid_levels(&self) -> impl Iterator<Item = (U16Vec3, u8)> + '_ {
+        self.fluid_levels
+            .iter()
+            .map(|(position, level)| (*position, *level))
+    }
 }
 
 pub struct ChunkOctree {
     octree: Octree<ChunkData>,
     cache: HashMap<ChunkCoordinate, usize>,
+    residency: HashMap<ChunkCoordinate, Residency>,
+    resident_bytes: usize,
+    /// Counts up on every access so residents can be ordered by when they were last used.
+    clock: u64,
     pub chunk_size: u16,
 }
 
+/// Bookkeeping for a chunk whose data is held in memory.
+#[derive(Debug, Copy, Clone)]
+struct Residency {
+    last_used: u64,
+    bytes: usize,
+    /// Changed since it was generated, so it must be saved before being evicted.
+    edited: bool,
+}
+
+/// Chunk data dropped from memory to stay within a budget.
+#[derive(Debug, Clone)]
+pub struct EvictedChunk {
+    pub coord: ChunkCoordinate,
+    pub data: Arc<ChunkData>,
+    pub edited: bool,
+}
+
 impl Default for ChunkOctree {
     fn default() -> Self {
-        let chunk_size = 16;
+        Self::with_chunk_size(CHUNK_SIZE)
+    }
+}
+
+impl ChunkOctree {
+    pub fn with_chunk_size(chunk_size: u16) -> Self {
+        assert!(
+            is_valid_chunk_size(chunk_size),
+            "invalid chunk size {chunk_size}"
+        );
         Self {
             octree: Octree::new(4096.0, 9),
             cache: HashMap::new(),
+            residency: HashMap::new(),
+            resident_bytes: 0,
+            clock: 0,
             chunk_size,
         }
     }
-}
 
-impl ChunkOctree {
     pub fn get_chunk_data(&mut self, coord: ChunkCoordinate) -> Option<Arc<ChunkData>> {
         let octant = if self.cache.contains_key(&coord) {
             self.octree.get_node_by_id(*self.cache.get(&coord).unwrap())
@@ -114,7 +234,19 @@ impl ChunkOctree {
 
         let read = octant.read().unwrap();
         self.cache.insert(coord, read.id());
-        read.get_data()
+        let data = read.get_data();
+        drop(read);
+        if data.is_some() {
+            self.touch(coord);
+        }
+        data
+    }
+
+    fn touch(&mut self, coord: ChunkCoordinate) {
+        self.clock += 1;
+        if let Some(residency) = self.residency.get_mut(&coord) {
+            residency.last_used = self.clock;
+        }
     }
 
     pub fn set_chunk_data(
@@ -122,20 +254,129 @@ impl ChunkOctree {
         coord: ChunkCoordinate,
         chunk_data: ChunkData,
     ) -> Arc<ChunkData> {
-        let chunk_octant = self.octree.query_octant(self.chunk_centre(coord));
-
         let chunk_data = Arc::new(chunk_data);
-        let mut write = chunk_octant.write().unwrap();
-        write.set_data(chunk_data.clone());
+        let occupied = !chunk_data.empty();
+        let id = self
+            .octree
+            .insert(self.chunk_centre(coord), chunk_data.clone(), occupied);
+        self.cache.insert(coord, id);
+
+        self.clock += 1;
+        let bytes = chunk_data.memory_size();
+        let residency = self.residency.entry(coord).or_insert(Residency {
+            last_used: 0,
+            bytes: 0,
+            edited: false,
+        });
+        self.resident_bytes = self.resident_bytes - residency.bytes + bytes;
+        residency.bytes = bytes;
+        residency.last_used = self.clock;
         chunk_data
     }
 
     pub fn clear_chunk(&mut self, coord: ChunkCoordinate) {
-        let chunk_octant = self.octree.query_octant(self.chunk_centre(coord));
-
-        let mut write = chunk_octant.write().unwrap();
-        write.clear_data();
+        self.octree.remove(self.chunk_centre(coord));
         self.cache.remove(&coord);
+        if let Some(residency) = self.residency.remove(&coord) {
+            self.resident_bytes -= residency.bytes;
+        }
+    }
+
+    /// Records that a resident chunk no longer matches what the generator would make.
+    pub fn mark_edited(&mut self, coord: ChunkCoordinate) {
+        if let Some(residency) = self.residency.get_mut(&coord) {
+            residency.edited = true;
+        }
+    }
+
+    pub fn is_edited(&self, coord: ChunkCoordinate) -> bool {
+        self.residency
+            .get(&coord)
+            .is_some_and(|residency| residency.edited)
+    }
+
+    /// Every resident chunk that no longer matches what the generator would make.
+    pub fn edited_chunks(&self) -> impl Iterator<Item = ChunkCoordinate> + '_ {
+        self.residency
+            .iter()
+            .filter(|(_, residency)| residency.edited)
+            .map(|(coord, _)| *coord)
+    }
+
+    /// Number of chunks whose data is held in memory.
+    pub fn resident_chunks(&self) -> usize {
+        self.residency.len()
+    }
+
+    /// Approximate memory taken by every resident chunk, see `ChunkData::memory_size`.
+    pub fn resident_bytes(&self) -> usize {
+        self.resident_bytes
+    }
+
+    /// Drops the least recently used chunks until no more than `budget` bytes are resident,
+    /// never evicting chunks `keep` returns true for. Evicted chunks are returned so edited ones
+    /// can be saved.
+    pub fn evict(
+        &mut self,
+        budget: usize,
+        keep: impl Fn(ChunkCoordinate) -> bool,
+    ) -> Vec<EvictedChunk> {
+        if self.resident_bytes <= budget {
+            return Vec::new();
+        }
+
+        let mut candidates: Vec<(u64, ChunkCoordinate)> = self
+            .residency
+            .iter()
+            .filter(|(coord, _)| !keep(**coord))
+            .map(|(coord, residency)| (residency.last_used, *coord))
+            .collect();
+        candidates.sort_unstable_by_key(|(last_used, _)| *last_used);
+
+        let mut evicted = Vec::new();
+        for (_, coord) in candidates {
+            if self.resident_bytes <= budget {
+                break;
+            }
+            let edited = self.is_edited(coord);
+            if let Some(data) = self.get_chunk_data(coord) {
+                evicted.push(EvictedChunk {
+                    coord,
+                    data,
+                    edited,
+                });
+            }
+            self.clear_chunk(coord);
+        }
+        evicted
+    }
+
+    /// Whether the chunk has been generated without any blocks, answered from the octree without
+    /// reading the chunk's data.
+    pub fn is_chunk_empty(&mut self, coord: ChunkCoordinate) -> bool {
+        let octant = match self.cache.get(&coord) {
+            Some(id) => self.octree.get_node_by_id(*id),
+            None => self.octree.query_octant(self.chunk_centre(coord)),
+        };
+
+        let read = octant.read().unwrap();
+        self.cache.insert(coord, read.id());
+        read.has_data() && !read.is_occupied()
+    }
+
+    /// Generated chunks, and those of them with blocks, from `min` to `max` inclusive.
+    pub fn count_chunks(&self, min: ChunkCoordinate, max: ChunkCoordinate) -> OctreeCounts {
+        let chunk_size = self.chunk_size as f32;
+        self.octree.count_in(
+            Vec3::from(min) * chunk_size,
+            (Vec3::from(max) + 1.0) * chunk_size,
+        )
+    }
+
+    /// Whether any chunk from `min` to `max` inclusive has blocks, so regions of only air or
+    /// ungenerated chunks can be skipped as a whole.
+    pub fn any_solid_chunks(&self, min: ChunkCoordinate, max: ChunkCoordinate) -> bool {
+        self.count_chunks(min, max).occupied > 0
     }
 
     pub fn chunk_centre(&self, chunk_coord: ChunkCoordinate) -> Vec3 {
@@ -154,7 +395,8 @@ mod tests {
 
     use crate::block::BlockType;
 
-    use super::{ChunkCoordinate, ChunkData, ChunkOctree};
+    use super::{is_valid_chunk_size, ChunkCoordinate, ChunkData, ChunkOctree};
+    use crate::util::octree::OctreeCounts;
 
     #[test]
     #[should_panic]
@@ -175,13 +417,22 @@ mod tests {
         let mut chunk_data = ChunkData::default();
         chunk_data.set_block_at(U16Vec3::new(4, 12, 5), BlockType::Grass);
 
-        assert_eq!(1, chunk_data.blocks.len());
+        assert_eq!(1, chunk_data.block_count());
         assert_eq!(
-            BlockType::Grass,
-            *chunk_data.blocks.get(&U16Vec3::new(4, 12, 5)).unwrap()
+            vec![(U16Vec3::new(4, 12, 5), BlockType::Grass)],
+            chunk_data.blocks().collect::<Vec<_>>()
         )
     }
 
+    #[test]
+    fn test_set_block_at_air_removes_block() {
+        let mut chunk_data = ChunkData::default();
+        chunk_data.set_block_at(U16Vec3::new(4, 12, 5), BlockType::Grass);
+        chunk_data.set_block_at(U16Vec3::new(4, 12, 5), BlockType::Air);
+
+        assert!(chunk_data.empty());
+    }
+
     #[test]
     fn test_set_block_at_makes_chunk_dirty() {
         let mut chunk_data = ChunkData::default();
@@ -213,6 +464,65 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_octree_counts_empty_and_solid_chunks() {
+        let mut octree = ChunkOctree::default();
+        let mut solid = ChunkData::default();
+        solid.set_block_at(U16Vec3::ZERO, BlockType::Stone);
+
+        let coord = |x, y, z| ChunkCoordinate(I64Vec3::new(x, y, z));
+        octree.set_chunk_data(coord(0, 0, 0), solid.clone());
+        octree.set_chunk_data(coord(0, 1, 0), ChunkData::default());
+        octree.set_chunk_data(coord(-3, 5, 2), ChunkData::default());
+
+        assert!(!octree.is_chunk_empty(coord(0, 0, 0)));
+        assert!(octree.is_chunk_empty(coord(0, 1, 0)));
+        // ungenerated chunks aren't known to be empty
+        assert!(!octree.is_chunk_empty(coord(1, 1, 0)));
+
+        assert_eq!(
+            OctreeCounts {
+                loaded: 3,
+                occupied: 1
+            },
+            octree.count_chunks(coord(-4, -4, -4), coord(4, 8, 4))
+        );
+        assert!(!octree.any_solid_chunks(coord(-3, 1, -3), coord(3, 8, 3)));
+        assert!(octree.any_solid_chunks(coord(-1, -1, -1), coord(0, 0, 0)));
+
+        octree.clear_chunk(coord(0, 0, 0));
+        assert!(!octree.any_solid_chunks(coord(-4, -4, -4), coord(4, 8, 4)));
+        octree.set_chunk_data(coord(-3, 5, 2), solid);
+        assert!(octree.any_solid_chunks(coord(-3, 5, 2), coord(-3, 5, 2)));
+    }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let mut octree = ChunkOctree::default();
+        let coord = |x| ChunkCoordinate(I64Vec3::new(x, 0, 0));
+        for x in 0..4 {
+            octree.set_chunk_data(coord(x), ChunkData::default());
+        }
+        let chunk_bytes = ChunkData::default().memory_size();
+        assert_eq!(4, octree.resident_chunks());
+        assert_eq!(4 * chunk_bytes, octree.resident_bytes());
+
+        octree.get_chunk_data(coord(0));
+        octree.mark_edited(coord(2));
+        assert!(octree.evict(4 * chunk_bytes, |_| false).is_empty());
+
+        // chunk 1 is the least recently used but kept, so 2 and 3 go before the recently read 0
+        let evicted = octree.evict(2 * chunk_bytes, |chunk| chunk == coord(1));
+        let evicted: Vec<_> = evicted
+            .iter()
+            .map(|chunk| (chunk.coord, chunk.edited))
+            .collect();
+        assert_eq!(vec![(coord(2), true), (coord(3), false)], evicted);
+        assert_eq!(2, octree.resident_chunks());
+        assert!(octree.get_chunk_data(coord(2)).is_none());
+        assert!(octree.get_chunk_data(coord(1)).is_some());
+    }
+
     #[test]
     fn test_chunk_centre() {
         let octree = ChunkOctree::default();
@@ -228,6 +538,21 @@ mod tests {
         assert_eq!(
             Vec3::new(-680.0, 360.0, -1592.0),
             octree.chunk_centre(ChunkCoordinate(I64Vec3::new(-43, 22, -100)))
-        )
+        );
+
+        let octree = ChunkOctree::with_chunk_size(32);
+        assert_eq!(
+            Vec3::new(-16.0, 48.0, 16.0),
+            octree.chunk_centre(ChunkCoordinate(I64Vec3::new(-1, 1, 0)))
+        );
+    }
+
+    #[test]
+    fn test_valid_chunk_sizes() {
+        assert!(is_valid_chunk_size(16));
+        assert!(is_valid_chunk_size(32));
+        assert!(!is_valid_chunk_size(24));
+        assert!(!is_valid_chunk_size(4));
+        assert!(!is_valid_chunk_size(64));
     }
 }
@@ -1,78 +1,391 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
     vec::IntoIter,
 };
 
 use bevy::{
     asset::{Assets, Handle},
+    diagnostic::{DiagnosticPath, Diagnostics},
     ecs::{
         component::Component,
         entity::Entity,
+        event::EventWriter,
         query::{With, Without},
-        system::{Commands, Query, ResMut, Resource},
+        system::{Commands, In, Query, Res, ResMut, Resource},
     },
-    hierarchy::Parent,
+    hierarchy::{BuildChildren, DespawnRecursiveExt, Parent},
+    log::{error, warn},
     math::{Dir3, I64Vec3, Vec3},
     pbr::MeshMaterial3d,
     prelude::Mesh3d,
     render::{camera::Camera, mesh::Mesh, primitives::Aabb},
-    tasks::{AsyncComputeTaskPool, Task},
+    tasks::AsyncComputeTaskPool,
+    time::Time,
     transform::components::{GlobalTransform, Transform},
-    utils::futures,
 };
 use priority_queue::PriorityQueue;
 
 use super::{
     chunk::{ChunkCoordinate, ChunkData},
-    generate::generator::{generate_chunk, generate_chunk_mesh},
+    codec::ChunkCodecError,
+    generate::{
+        generator::{generate_chunk_mesh, ChunkMeshes, Lighting},
+        terrain::ChunkGenerator,
+    },
     material::ChunkMaterial,
 };
-use crate::{player::PlayerLook, world::World};
+use crate::{
+    block::{BlockLayer, BlockType},
+    command::{CommandArgs, CommandResult},
+    player::PlayerLook,
+    save::WorldInfo,
+    settings::{MemorySettings, UnloadSettings},
+    ui::toast::Toast,
+    util::octree::OctreeCounts,
+    world::World,
+};
 
 #[derive(Component)]
 pub struct Chunk {
     coord: ChunkCoordinate,
 }
 
+impl Chunk {
+    pub fn coord(&self) -> ChunkCoordinate {
+        self.coord
+    }
+}
+
 #[derive(Component)]
 pub struct DirtyChunk {}
 
+/// The child entity drawing a chunk's translucent blocks, present while it has any.
+#[derive(Component)]
+pub struct TranslucentPart(Entity);
+
+impl TranslucentPart {
+    pub fn entity(&self) -> Entity {
+        self.0
+    }
+}
+
+/// A chunk whose blocks are being generated by the task with id `task`.
 #[derive(Component)]
 pub struct GenerateChunkData {
-    task: Task<ChunkData>,
+    task: u64,
 }
 
+/// A chunk whose mesh is being built by the task with id `task`.
 #[derive(Component)]
 pub struct GenerateChunkMesh {
+    task: u64,
+}
+
+enum ChunkTaskOutput {
+    Data(LoadedChunk),
+    Mesh(ChunkMeshes),
+}
+
+/// A chunk's blocks, read back from the save or generated when it has none.
+pub enum LoadedChunk {
+    Generated(ChunkData),
+    /// Data read back from an edited chunk saved before it was evicted or unloaded.
+    Saved(ChunkData),
+    /// Data generated in place of a corrupt saved chunk, with a warning for the player.
+    Recovered(ChunkData, String),
+}
+
+/// Sent back from a worker when a generation or meshing task completes.
+struct ChunkTaskResult {
     coord: ChunkCoordinate,
-    task: Option<Task<Mesh>>,
+    task: u64,
+    output: ChunkTaskOutput,
+}
+
+/// A finished chunk mesh waiting for its turn to be uploaded.
+struct PendingMesh {
+    coord: ChunkCoordinate,
+    task: u64,
+    meshes: ChunkMeshes,
+    bytes: usize,
 }
 
 #[derive(Resource)]
 pub struct ChunkLoader {
     render_distance: u32,
+    unloading: UnloadSettings,
+    memory: MemorySettings,
+    /// Seconds each loaded chunk has been beyond the unload distance for.
+    out_of_range: HashMap<ChunkCoordinate, f32>,
     chunk_to_entity: HashMap<ChunkCoordinate, Entity>,
     chunk_iterator: ChunkIterator,
     material: Handle<ChunkMaterial>,
+    translucent_material: Handle<ChunkMaterial>,
+    lighting: Lighting,
+    next_task: u64,
+    sender: Sender<ChunkTaskResult>,
+    results: Mutex<Receiver<ChunkTaskResult>>,
+    /// Meshes left over once a frame's upload budget was spent, oldest first.
+    pending_meshes: VecDeque<PendingMesh>,
 }
 
 const MAX_CHUNKS_PER_FRAME: usize = 32;
+/// Completed tasks applied per frame, the rest wait in the channel for later frames.
+const MAX_RESULTS_PER_FRAME: usize = 32;
+/// Chunk meshes added to `Assets<Mesh>` per frame, as each is uploaded to the GPU.
+const MAX_MESH_UPLOADS_PER_FRAME: usize = 8;
+/// Estimated bytes of chunk meshes uploaded per frame.
+const MESH_UPLOAD_BYTES_PER_FRAME: usize = 4 * 1024 * 1024;
+/// Half angle of the cone in front of the camera that chunks are loaded in, in radians. Wider than
+/// the camera's field of view so chunks just off screen are ready when the player turns.
+const INTEREST_HALF_ANGLE: f32 = std::f32::consts::FRAC_PI_3;
+/// Distance in chunks within which chunks load whichever way the camera faces, so turning around
+/// never shows holes right next to the player.
+const NEARBY_DISTANCE: u32 = 2;
+/// Distance from a chunk's centre to its corners, in chunks.
+const CHUNK_RADIUS: f32 = 0.87;
+/// Chunks touching a chunk, diagonals included.
+const SURROUNDING_CHUNKS: u32 = 26;
+
+/// Number of chunks whose data is held in memory.
+pub const RESIDENT_CHUNKS: DiagnosticPath = DiagnosticPath::const_new("chunks/resident");
+/// Approximate memory taken by the data of resident chunks.
+pub const RESIDENT_CHUNK_MEGABYTES: DiagnosticPath =
+    DiagnosticPath::const_new("chunks/resident_megabytes");
+
+/// World queries the chunk loader's scheduling depends on, so it can be tested without a world.
+pub trait ChunkQuery {
+    fn is_chunk_empty(&mut self, chunk_coord: ChunkCoordinate) -> bool;
+
+    fn count_chunks(&mut self, min: ChunkCoordinate, max: ChunkCoordinate) -> OctreeCounts;
+}
+
+impl ChunkQuery for World {
+    fn is_chunk_empty(&mut self, chunk_coord: ChunkCoordinate) -> bool {
+        World::is_chunk_empty(self, chunk_coord)
+    }
+
+    fn count_chunks(&mut self, min: ChunkCoordinate, max: ChunkCoordinate) -> OctreeCounts {
+        World::count_chunks(self, min, max)
+    }
+}
 
 impl ChunkLoader {
     pub fn new(render_distance: u32, material: Handle<ChunkMaterial>) -> Self {
+        let (sender, results) = mpsc::channel();
         Self {
             render_distance,
+            unloading: UnloadSettings::default(),
+            memory: MemorySettings::default(),
+            out_of_range: HashMap::new(),
             chunk_to_entity: HashMap::new(),
-            chunk_iterator: ChunkIterator::new(),
+            chunk_iterator: ChunkIterator::new(render_distance),
+            translucent_material: material.clone(),
             material,
+            lighting: Lighting::default(),
+            next_task: 0,
+            sender,
+            results: Mutex::new(results),
+            pending_meshes: VecDeque::new(),
+        }
+    }
+
+    pub fn with_unloading(mut self, unloading: UnloadSettings) -> Self {
+        self.unloading = unloading;
+        self
+    }
+
+    pub fn with_memory(mut self, memory: MemorySettings) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    pub fn with_lighting(mut self, lighting: Lighting) -> Self {
+        self.lighting = lighting;
+        self
+    }
+
+    /// Material for translucent blocks, which should blend. Defaults to the chunk material.
+    pub fn with_translucent_material(mut self, material: Handle<ChunkMaterial>) -> Self {
+        self.translucent_material = material;
+        self
+    }
+
+    /// Despawns a chunk and drops its data, saving it first if it was edited.
+    fn unload_chunk(
+        &mut self,
+        commands: &mut Commands,
+        world: &mut World,
+        world_info: Option<&WorldInfo>,
+        coord: ChunkCoordinate,
+    ) {
+        if let Some(entity) = self.chunk_to_entity.remove(&coord) {
+            commands.entity(entity).despawn_recursive();
+        }
+        self.out_of_range.remove(&coord);
+        if world.is_edited(coord) {
+            if let Some(data) = world.get_chunk_data(coord) {
+                save_edited_chunk(world_info, coord, &data);
+            }
+        }
+        world.clear_chunk(coord);
+    }
+
+    /// Runs `work` on the compute pool, sending its output back to `receive_chunk_results`.
+    /// Returns the task's id.
+    fn spawn_task(
+        &mut self,
+        coord: ChunkCoordinate,
+        work: impl FnOnce() -> ChunkTaskOutput + Send + 'static,
+    ) -> u64 {
+        let task = self.next_task;
+        self.next_task += 1;
+
+        let sender = self.sender.clone();
+        AsyncComputeTaskPool::get()
+            .spawn(async move {
+                // the loader is gone if the receiver is, so there is nobody left to tell
+                let _ = sender.send(ChunkTaskResult {
+                    coord,
+                    task,
+                    output: work(),
+                });
+            })
+            .detach();
+        task
+    }
+
+    /// Advances the time `chunk` has been out of range by `delta_secs`, returning whether it has
+    /// now been out of range for the whole grace period. Coming back in range resets the timer.
+    fn should_unload(&mut self, chunk: ChunkCoordinate, delta_secs: f32) -> bool {
+        let unload_distance = self.render_distance + self.unloading.extra_distance;
+        if chunk_distance(chunk, self.chunk_iterator.camera_chunk) <= unload_distance {
+            self.out_of_range.remove(&chunk);
+            return false;
+        }
+        let waited = self.out_of_range.entry(chunk).or_default();
+        *waited += delta_secs;
+        *waited >= self.unloading.grace_period
+    }
+
+    pub fn material(&self) -> Handle<ChunkMaterial> {
+        self.material.clone()
+    }
+
+    pub fn translucent_material(&self) -> Handle<ChunkMaterial> {
+        self.translucent_material.clone()
+    }
+
+    /// Material to draw a lone block with, as in dropped items.
+    pub fn material_for(&self, block: BlockType) -> Handle<ChunkMaterial> {
+        match block.layer() {
+            BlockLayer::Opaque | BlockLayer::Cutout => self.material(),
+            BlockLayer::Translucent => self.translucent_material(),
+        }
+    }
+
+    /// Queues a remesh of every loaded chunk whose mesh depends on `block_coord`,
+    /// including neighbours when the block lies on a chunk border.
+    pub fn remesh_block(&self, commands: &mut Commands, world: &World, block_coord: I64Vec3) {
+        let (ChunkCoordinate(chunk), local) = world.split_block_coordinate(block_coord);
+
+        let mut coords = vec![chunk];
+        for axis in 0..3 {
+            let mut neighbour = chunk;
+            if local[axis] == 0 {
+                neighbour[axis] -= 1;
+            } else if local[axis] == world.chunk_size() - 1 {
+                neighbour[axis] += 1;
+            } else {
+                continue;
+            }
+            coords.push(neighbour);
+        }
+
+        for coord in coords {
+            self.remesh_chunk(commands, ChunkCoordinate(coord));
+        }
+    }
+
+    /// Queues a remesh of every loaded chunk whose mesh depends on blocks between `min` and
+    /// `max` inclusive.
+    pub fn remesh_region(
+        &self,
+        commands: &mut Commands,
+        world: &World,
+        min: I64Vec3,
+        max: I64Vec3,
+    ) {
+        let chunk_size = I64Vec3::splat(world.chunk_size() as i64);
+        let min_chunk = (min - 1).div_euclid(chunk_size);
+        let max_chunk = (max + 1).div_euclid(chunk_size);
+        for x in min_chunk.x..=max_chunk.x {
+            for y in min_chunk.y..=max_chunk.y {
+                for z in min_chunk.z..=max_chunk.z {
+                    self.remesh_chunk(commands, ChunkCoordinate(I64Vec3::new(x, y, z)));
+                }
+            }
         }
     }
+
+    pub fn remesh_chunk(&self, commands: &mut Commands, chunk_coord: ChunkCoordinate) {
+        if let Some(entity) = self.chunk_to_entity.get(&chunk_coord) {
+            commands.entity(*entity).insert(DirtyChunk {});
+        }
+    }
+
+    /// Every loaded chunk and the entity drawing it, in no particular order.
+    pub fn loaded_chunks(&self) -> impl Iterator<Item = (ChunkCoordinate, Entity)> + '_ {
+        self.chunk_to_entity
+            .iter()
+            .map(|(coord, entity)| (*coord, *entity))
+    }
+
+    /// Distance in chunks around the camera that chunks are loaded within.
+    pub fn render_distance(&self) -> u32 {
+        self.render_distance
+    }
+
+    pub fn lighting(&self) -> Lighting {
+        self.lighting
+    }
+
+    /// Changes how chunks are lit, remeshing every loaded chunk.
+    pub fn set_lighting(&mut self, commands: &mut Commands, lighting: Lighting) {
+        self.lighting = lighting;
+        for entity in self.chunk_to_entity.values() {
+            commands.entity(*entity).insert(DirtyChunk {});
+        }
+    }
+}
+
+pub fn lighting_command(
+    In(mut args): In<CommandArgs>,
+    mut commands: Commands,
+    mut chunk_loader: ResMut<ChunkLoader>,
+) -> CommandResult {
+    let Some(mode) = args.optional_word() else {
+        return Ok(format!("lighting is {}", chunk_loader.lighting().name()));
+    };
+    args.finish()?;
+    let lighting = match mode.as_str() {
+        "smooth" => Lighting::Smooth,
+        "flat" => Lighting::Flat,
+        other => return Err(format!("unknown lighting '{other}', try smooth or flat")),
+    };
+    chunk_loader.set_lighting(&mut commands, lighting);
+    Ok(format!("lighting is now {}", lighting.name()))
 }
 
 pub fn gather_chunks(
     mut commands: Commands,
     mut chunk_loader: ResMut<ChunkLoader>,
     mut world: ResMut<World>,
+    world_info: Option<Res<WorldInfo>>,
     camera_query: Query<(&Parent, &GlobalTransform), (With<Camera>, Without<PlayerLook>)>,
     generating_chunks_query: Query<&Chunk, With<GenerateChunkData>>,
 ) {
@@ -83,25 +396,19 @@ pub fn gather_chunks(
     let (_, camera) = camera_query.get_single().expect("could not find camera");
 
     let camera_pos = camera.translation();
-    let camera_chunk = world.block_to_chunk_coordinate(I64Vec3::new(
-        camera_pos.x as i64,
-        camera_pos.y as i64,
-        camera_pos.z as i64,
-    ));
+    let camera_chunk = world.chunk_at(camera_pos);
 
     let camera_forward = camera.forward();
+    let distance = chunk_loader.render_distance;
     chunk_loader
         .chunk_iterator
-        .update(camera_chunk, camera_forward);
-
-    let distance = chunk_loader.render_distance;
+        .update(camera_chunk, camera_forward, distance, &mut *world);
 
     let mut next_chunks: Vec<ChunkCoordinate> = vec![];
     while next_chunks.len() < MAX_CHUNKS_PER_FRAME {
-        if let Some(next) =
-            chunk_loader
-                .chunk_iterator
-                .next_chunks(MAX_CHUNKS_PER_FRAME, distance, &mut world)
+        if let Some(next) = chunk_loader
+            .chunk_iterator
+            .next_chunks(MAX_CHUNKS_PER_FRAME, &mut *world)
         {
             next_chunks
                 .extend(next.filter(|chunk| !chunk_loader.chunk_to_entity.contains_key(chunk)));
@@ -110,13 +417,12 @@ pub fn gather_chunks(
         }
     }
 
-    let task_pool = AsyncComputeTaskPool::get();
     for chunk in next_chunks {
         generate_single_chunk(
             &mut commands,
-            &mut world,
+            &world,
+            world_info.as_deref().cloned(),
             chunk,
-            task_pool,
             &mut chunk_loader,
         );
     }
@@ -124,165 +430,425 @@ pub fn gather_chunks(
 
 fn generate_single_chunk(
     commands: &mut Commands,
-    world: &mut ResMut<World>,
+    world: &World,
+    world_info: Option<WorldInfo>,
     coord: ChunkCoordinate,
-    task_pool: &AsyncComputeTaskPool,
-    chunk_loader: &mut ResMut<ChunkLoader>,
+    chunk_loader: &mut ChunkLoader,
 ) {
-    let noise_generator = world.noise_generator.clone();
-    let height = world.height;
+    let generator = world.generator();
+    let task = chunk_loader.spawn_task(coord, move || {
+        ChunkTaskOutput::Data(load_chunk_data(world_info.as_ref(), &*generator, coord))
+    });
     let entity = commands
-        .spawn((
-            Chunk { coord },
-            GenerateChunkData {
-                task: task_pool
-                    .spawn(async move { generate_chunk(noise_generator, coord, height) }),
-            },
-        ))
+        .spawn((Chunk { coord }, GenerateChunkData { task }))
         .id();
     chunk_loader.chunk_to_entity.insert(coord, entity);
 }
 
-pub fn generate_chunks(
-    mut commands: Commands,
-    mut world: ResMut<World>,
-    mut chunks_query: Query<(Entity, &mut Chunk, &mut GenerateChunkData)>,
-) {
-    for (entity, chunk, mut gen_chunk) in chunks_query.iter_mut() {
-        if let Some(chunk_data) = futures::check_ready(&mut gen_chunk.task) {
-            let data = world.insert_chunk(chunk.coord, chunk_data);
-            if !data.empty() {
-                commands.entity(entity).insert(DirtyChunk {});
-            }
-            commands.entity(entity).remove::<GenerateChunkData>();
+/// Reads a chunk back from the save if it has been edited, otherwise generates it. Corrupt saves
+/// are quarantined and regenerated. Blocks on file IO, so should be run in a task.
+pub fn load_chunk_data(
+    world_info: Option<&WorldInfo>,
+    generator: &dyn ChunkGenerator,
+    coord: ChunkCoordinate,
+) -> LoadedChunk {
+    let generate = || generator.generate(coord);
+    let Some(world_info) = world_info else {
+        return LoadedChunk::Generated(generate());
+    };
+    match world_info.load_chunk(coord) {
+        Ok(Some(chunk_data)) => LoadedChunk::Saved(chunk_data),
+        Ok(None) => LoadedChunk::Generated(generate()),
+        Err(e) if e.is::<ChunkCodecError>() => {
+            LoadedChunk::Recovered(generate(), quarantine_chunk(world_info, coord, &*e))
+        }
+        Err(e) => {
+            warn!("failed to load chunk {:?}, regenerating it: {}", coord, e);
+            LoadedChunk::Generated(generate())
         }
     }
 }
 
-pub fn mark_chunks(
-    mut commands: Commands,
-    mut world: ResMut<World>,
-    mut chunks_query: Query<
-        (Entity, &mut Chunk),
-        (
-            With<DirtyChunk>,
-            Without<GenerateChunkData>,
-            Without<GenerateChunkMesh>,
-        ),
-    >,
-) {
-    chunks_query.iter_mut().for_each(|(entity, chunk)| {
-        if chunk
-            .coord
-            .adjacent()
-            .into_iter()
-            .all(|adj| world.is_chunk_generated(adj))
-        {
-            commands.entity(entity).insert(GenerateChunkMesh {
-                coord: chunk.coord,
-                task: None,
-            });
-            commands.entity(entity).remove::<DirtyChunk>();
+/// Moves a corrupt saved chunk aside so it is regenerated rather than loaded, returning a warning
+/// for the player.
+fn quarantine_chunk(
+    world_info: &WorldInfo,
+    coord: ChunkCoordinate,
+    e: &dyn std::error::Error,
+) -> String {
+    let ChunkCoordinate(position) = coord;
+    match world_info.quarantine_chunk(coord) {
+        Ok(path) => {
+            error!(
+                "saved chunk {:?} is corrupt ({}), moved it to {} and regenerated it",
+                coord,
+                e,
+                path.display()
+            );
+            format!(
+                "Chunk {} {} {} was corrupt and has been regenerated, the damaged save was kept",
+                position.x, position.y, position.z
+            )
         }
-    });
+        Err(quarantine_error) => {
+            error!(
+                "saved chunk {:?} is corrupt ({}) and couldn't be moved aside: {}",
+                coord, e, quarantine_error
+            );
+            format!(
+                "Chunk {} {} {} is corrupt and has been regenerated",
+                position.x, position.y, position.z
+            )
+        }
+    }
 }
 
-pub fn load_chunks(
+/// Applies the results of finished generation and meshing tasks, a bounded number per frame.
+/// Finished meshes are queued and uploaded within a per-frame budget, so a burst of them doesn't
+/// stall a frame. Results for chunks that have since been unloaded, or superseded by a newer
+/// task, are dropped.
+pub fn receive_chunk_results(
     mut commands: Commands,
     mut world: ResMut<World>,
-    mut chunks_query: Query<(Entity, &Chunk, &mut GenerateChunkMesh)>,
     mut meshes: ResMut<Assets<Mesh>>,
-    chunk_loader: ResMut<ChunkLoader>,
+    mut chunk_loader: ResMut<ChunkLoader>,
+    mut toasts: EventWriter<Toast>,
+    chunks_query: Query<(
+        Option<&GenerateChunkData>,
+        Option<&GenerateChunkMesh>,
+        Option<&TranslucentPart>,
+    )>,
 ) {
-    let mut ready = vec![];
-    let task_pool = AsyncComputeTaskPool::get();
-
-    for (entity, chunk, mut gen_chunk_mesh) in chunks_query.iter_mut() {
-        match &mut gen_chunk_mesh.task {
-            Some(task) => {
-                if let Some(mesh) = futures::check_ready(task) {
-                    ready.push((entity, chunk, mesh));
-                }
+    let results: Vec<ChunkTaskResult> = chunk_loader
+        .results
+        .lock()
+        .unwrap()
+        .try_iter()
+        .take(MAX_RESULTS_PER_FRAME)
+        .collect();
+
+    for ChunkTaskResult {
+        coord,
+        task,
+        output,
+    } in results
+    {
+        let Some(&entity) = chunk_loader.chunk_to_entity.get(&coord) else {
+            continue;
+        };
+        let Ok((generating, meshing, _)) = chunks_query.get(entity) else {
+            continue;
+        };
+
+        let saved = matches!(output, ChunkTaskOutput::Data(LoadedChunk::Saved(_)));
+        let output = match output {
+            ChunkTaskOutput::Data(LoadedChunk::Recovered(chunk_data, warning)) => {
+                toasts.send(Toast(warning));
+                ChunkTaskOutput::Data(LoadedChunk::Generated(chunk_data))
             }
-            None => {
-                if let Some(data) = world.get_chunk_data(gen_chunk_mesh.coord) {
-                    let adjacent = world.adjacent_chunk_data(chunk.coord);
-                    gen_chunk_mesh.task =
-                        Some(task_pool.spawn(async move { generate_chunk_mesh(data, adjacent) }));
+            output => output,
+        };
+        match output {
+            ChunkTaskOutput::Data(
+                LoadedChunk::Generated(chunk_data) | LoadedChunk::Saved(chunk_data),
+            ) if generating.is_some_and(|g| g.task == task) => {
+                let data = world.insert_chunk(coord, chunk_data);
+                if saved {
+                    world.mark_edited(coord);
                 }
+                if !data.empty() {
+                    commands.entity(entity).insert(DirtyChunk {});
+                }
+                commands.entity(entity).remove::<GenerateChunkData>();
             }
-        }
-
-        if ready.len() > MAX_CHUNKS_PER_FRAME {
-            break;
+            ChunkTaskOutput::Mesh(chunk_meshes) if meshing.is_some_and(|m| m.task == task) => {
+                chunk_loader.pending_meshes.push_back(PendingMesh {
+                    coord,
+                    task,
+                    bytes: mesh_bytes(&chunk_meshes.solid)
+                        + chunk_meshes.translucent.as_ref().map_or(0, mesh_bytes),
+                    meshes: chunk_meshes,
+                });
+            }
+            _ => (),
         }
     }
 
-    for (entity, chunk, mesh) in ready {
-        let (t, aabb) = chunk_components(chunk.coord);
+    // meshes may have been superseded or unloaded while they waited
+    let chunk_loader = &mut *chunk_loader;
+    chunk_loader.pending_meshes.retain(|pending| {
+        chunk_loader
+            .chunk_to_entity
+            .get(&pending.coord)
+            .and_then(|entity| chunks_query.get(*entity).ok())
+            .is_some_and(|(_, meshing, _)| meshing.is_some_and(|m| m.task == pending.task))
+    });
 
+    let uploads = take_within_budget(
+        &mut chunk_loader.pending_meshes,
+        |pending| pending.bytes,
+        MAX_MESH_UPLOADS_PER_FRAME,
+        MESH_UPLOAD_BYTES_PER_FRAME,
+    );
+    for PendingMesh {
+        coord,
+        meshes: chunk_meshes,
+        ..
+    } in uploads
+    {
+        let entity = chunk_loader.chunk_to_entity[&coord];
+        let (t, aabb) = chunk_components(coord, world.chunk_size());
         commands.entity(entity).insert((
-            Mesh3d(meshes.add(mesh)),
+            Mesh3d(meshes.add(chunk_meshes.solid)),
             MeshMaterial3d(chunk_loader.material.clone_weak()),
             t,
             aabb,
         ));
         commands.entity(entity).remove::<GenerateChunkMesh>();
+
+        let part = chunks_query
+            .get(entity)
+            .ok()
+            .and_then(|(.., part)| part.map(|part| part.0));
+        match (chunk_meshes.translucent, part) {
+            (Some(mesh), Some(part)) => {
+                commands.entity(part).insert(Mesh3d(meshes.add(mesh)));
+            }
+            (Some(mesh), None) => {
+                let part = commands
+                    .spawn((
+                        Mesh3d(meshes.add(mesh)),
+                        MeshMaterial3d(chunk_loader.translucent_material.clone_weak()),
+                        Transform::default(),
+                        aabb,
+                    ))
+                    .set_parent(entity)
+                    .id();
+                commands.entity(entity).insert(TranslucentPart(part));
+            }
+            (None, Some(part)) => {
+                commands.entity(part).despawn_recursive();
+                commands.entity(entity).remove::<TranslucentPart>();
+            }
+            (None, None) => (),
+        }
     }
 }
 
+/// Estimated size of a mesh's vertex and index buffers.
+fn mesh_bytes(mesh: &Mesh) -> usize {
+    let vertices = mesh.count_vertices() * mesh.get_vertex_size() as usize;
+    let indices = mesh.indices().map_or(0, |indices| indices.len() * 4);
+    vertices + indices
+}
+
+/// Pops items off the front of `queue` until `max_count` items or `max_bytes` bytes have been
+/// taken. The first item is always taken, so one larger than the budget can't block the queue.
+fn take_within_budget<T>(
+    queue: &mut VecDeque<T>,
+    bytes: impl Fn(&T) -> usize,
+    max_count: usize,
+    max_bytes: usize,
+) -> Vec<T> {
+    let mut taken = Vec::new();
+    let mut total = 0;
+    while let Some(next) = queue.front() {
+        let size = bytes(next);
+        if taken.len() >= max_count || (!taken.is_empty() && total + size > max_bytes) {
+            break;
+        }
+        total += size;
+        taken.extend(queue.pop_front());
+    }
+    taken
+}
+
+/// Starts meshing dirty chunks once all their neighbours have been generated. The neighbours are
+/// read by the meshing task itself, so only a shared borrow of the world is needed.
+pub fn mark_chunks(
+    mut commands: Commands,
+    world: Res<World>,
+    mut chunk_loader: ResMut<ChunkLoader>,
+    chunks_query: Query<
+        (Entity, &Chunk),
+        (
+            With<DirtyChunk>,
+            Without<GenerateChunkData>,
+            Without<GenerateChunkMesh>,
+        ),
+    >,
+) {
+    let chunks = world.shared_chunks();
+    for (entity, chunk) in chunks_query.iter() {
+        let coord = chunk.coord;
+        if !coord.adjacent().into_iter().all(|adj| chunks.contains(adj)) {
+            continue;
+        }
+        let Some(data) = chunks.get(coord) else {
+            continue;
+        };
+
+        let lighting = chunk_loader.lighting;
+        let chunks = chunks.clone();
+        let task = chunk_loader.spawn_task(coord, move || {
+            ChunkTaskOutput::Mesh(generate_chunk_mesh(data, chunks.adjacent(coord), lighting))
+        });
+        commands
+            .entity(entity)
+            .insert(GenerateChunkMesh { task })
+            .remove::<DirtyChunk>();
+    }
+}
+
+/// Unloads chunks that have stayed beyond the unload distance for the grace period.
 pub fn unload_chunks(
     mut commands: Commands,
+    time: Res<Time>,
     mut world: ResMut<World>,
+    world_info: Option<Res<WorldInfo>>,
     mut chunk_loader: ResMut<ChunkLoader>,
-    chunks_query: Query<(Entity, &Chunk), (Without<GenerateChunkData>, Without<GenerateChunkMesh>)>,
+    chunks_query: Query<&Chunk, (Without<GenerateChunkData>, Without<GenerateChunkMesh>)>,
 ) {
-    for (entity, chunk) in chunks_query.iter() {
-        if chunk_distance(chunk.coord, chunk_loader.chunk_iterator.camera_chunk)
-            > chunk_loader.render_distance
-        {
-            commands.entity(entity).despawn();
-            chunk_loader.chunk_to_entity.remove(&chunk.coord);
-            world.clear_chunk(chunk.coord);
+    for chunk in chunks_query.iter() {
+        if chunk_loader.should_unload(chunk.coord, time.delta_secs()) {
+            chunk_loader.unload_chunk(
+                &mut commands,
+                &mut world,
+                world_info.as_deref(),
+                chunk.coord,
+            );
         }
     }
 }
 
-fn chunk_world_pos(chunk: ChunkCoordinate) -> Vec3 {
-    Vec3::new(
-        (chunk.0.x * 16) as f32,
-        (chunk.0.y * 16) as f32,
-        (chunk.0.z * 16) as f32,
-    )
+/// Evicts the least recently used chunks outside the keep distance while chunk data takes more
+/// memory than the budget allows, unloading them like chunks that went out of range.
+pub fn evict_chunk_data(
+    mut commands: Commands,
+    mut world: ResMut<World>,
+    world_info: Option<Res<WorldInfo>>,
+    mut chunk_loader: ResMut<ChunkLoader>,
+) {
+    let memory = chunk_loader.memory;
+    let evicted = world.evict_chunks(
+        memory.chunk_budget_bytes(),
+        chunk_loader.chunk_iterator.camera_chunk,
+        memory.keep_distance,
+    );
+    for chunk in evicted {
+        if chunk.edited {
+            save_edited_chunk(world_info.as_deref(), chunk.coord, &chunk.data);
+        }
+        chunk_loader.unload_chunk(
+            &mut commands,
+            &mut world,
+            world_info.as_deref(),
+            chunk.coord,
+        );
+    }
+}
+
+/// Saves every edited chunk still in memory, so pausing to quit, or closing the game, doesn't
+/// lose edits to chunks that were never unloaded.
+pub fn save_resident_chunks(mut world: ResMut<World>, world_info: Res<WorldInfo>) {
+    if let Err(e) = world.save_edited_chunks(&world_info) {
+        warn!("failed to save edited chunks: {}", e);
+    }
 }
 
-fn chunk_distance(chunk: ChunkCoordinate, other: ChunkCoordinate) -> u32 {
+/// Reports how many chunks are held in memory, and how much memory they take, as diagnostics.
+pub fn measure_resident_chunks(mut diagnostics: Diagnostics, world: Res<World>) {
+    diagnostics.add_measurement(&RESIDENT_CHUNKS, || world.resident_chunks() as f64);
+    diagnostics.add_measurement(&RESIDENT_CHUNK_MEGABYTES, || {
+        world.resident_bytes() as f64 / (1024.0 * 1024.0)
+    });
+}
+
+fn save_edited_chunk(world_info: Option<&WorldInfo>, coord: ChunkCoordinate, data: &ChunkData) {
+    let Some(world_info) = world_info else {
+        warn!(
+            "edits to chunk {:?} are lost, the world has nowhere to save them",
+            coord
+        );
+        return;
+    };
+    if let Err(e) = world_info.save_chunk(coord, data) {
+        warn!("failed to save chunk {:?}: {}", coord, e);
+    }
+}
+
+fn chunk_world_pos(chunk: ChunkCoordinate, chunk_size: u16) -> Vec3 {
+    (chunk.0 * chunk_size as i64).as_vec3()
+}
+
+pub fn chunk_distance(chunk: ChunkCoordinate, other: ChunkCoordinate) -> u32 {
     (chunk.0 - other.0).abs().max_element() as u32
 }
 
-fn chunk_components(chunk: ChunkCoordinate) -> (Transform, Aabb) {
-    let pos = chunk_world_pos(chunk);
+/// Whether a chunk is worth loading for a camera: within render distance and in view.
+fn is_in_interest(
+    chunk: ChunkCoordinate,
+    camera_chunk: ChunkCoordinate,
+    camera_forward: Dir3,
+    max_distance: u32,
+) -> bool {
+    chunk_distance(chunk, camera_chunk) <= max_distance
+        && is_in_view(chunk, camera_chunk, camera_forward)
+}
+
+/// Whether a chunk is close to the camera or inside a cone around its view direction, however
+/// far away. Any part of the chunk in the cone is enough.
+pub fn is_in_view(
+    chunk: ChunkCoordinate,
+    camera_chunk: ChunkCoordinate,
+    camera_forward: Dir3,
+) -> bool {
+    if chunk_distance(chunk, camera_chunk) <= NEARBY_DISTANCE {
+        return true;
+    }
+
+    let offset = Vec3::from(chunk) - Vec3::from(camera_chunk);
+    let chunk_angle = (CHUNK_RADIUS / offset.length()).min(1.0).asin();
+    camera_forward.angle_between(offset) <= INTEREST_HALF_ANGLE + chunk_angle
+}
+
+/// Whether every chunk around `chunk`, diagonals included, has been generated without blocks. Such
+/// chunks, like the sky above the terrain, are almost always air themselves.
+fn is_surrounded_by_air(chunk: ChunkCoordinate, world: &mut impl ChunkQuery) -> bool {
+    let counts = world.count_chunks(ChunkCoordinate(chunk.0 - 1), ChunkCoordinate(chunk.0 + 1));
+    counts.occupied == 0 && counts.loaded >= SURROUNDING_CHUNKS
+}
+
+fn chunk_direction(chunk: ChunkCoordinate, camera_chunk: ChunkCoordinate) -> Vec3 {
+    (Vec3::from(chunk) - Vec3::from(camera_chunk)).normalize_or_zero()
+}
+
+fn chunk_components(chunk: ChunkCoordinate, chunk_size: u16) -> (Transform, Aabb) {
+    let pos = chunk_world_pos(chunk, chunk_size);
     let t = Transform::from_translation(Vec3::new(pos.x, pos.y, pos.z));
-    let aabb = Aabb::from_min_max(Vec3::new(0.0, 0.0, 0.0), Vec3::new(16.0, 16.0, 16.0));
+    let aabb = Aabb::from_min_max(Vec3::ZERO, Vec3::splat(chunk_size as f32));
     (t, aabb)
 }
 
 /// `ChunkIterator` enables iteration of nearby chunks over multiple frames
-/// by storing BFS state in memory and dynamically recalculating when the camera chunk or direction changes
+/// by storing BFS state in memory and dynamically recalculating when the camera chunk, direction
+/// or render distance changes
 #[derive(Debug)]
 struct ChunkIterator {
     seen: HashSet<ChunkCoordinate>,
     camera_chunk: ChunkCoordinate,
     camera_forward: Dir3,
+    max_distance: u32,
     queue: PriorityQueue<ChunkCoordinate, u32>,
 }
 
 impl ChunkIterator {
-    fn new() -> Self {
+    fn new(max_distance: u32) -> Self {
         Self {
             seen: HashSet::new(),
             camera_chunk: ChunkCoordinate(I64Vec3::ZERO),
             camera_forward: Dir3::X,
+            max_distance,
             queue: PriorityQueue::new(),
         }
     }
@@ -290,8 +856,7 @@ impl ChunkIterator {
     fn next_chunks(
         &mut self,
         count: usize,
-        max_distance: u32,
-        world: &mut World,
+        world: &mut impl ChunkQuery,
     ) -> Option<IntoIter<ChunkCoordinate>> {
         if self.queue.is_empty() {
             return None;
@@ -303,7 +868,7 @@ impl ChunkIterator {
             next_chunks.push(next);
             self.seen.insert(next);
 
-            if chunk_distance(next, self.camera_chunk) >= max_distance {
+            if chunk_distance(next, self.camera_chunk) >= self.max_distance {
                 continue;
             }
 
@@ -315,13 +880,17 @@ impl ChunkIterator {
         Some(next_chunks.into_iter())
     }
 
-    fn queue_chunk(&mut self, chunk: ChunkCoordinate, world: &mut World) {
+    fn queue_chunk(&mut self, chunk: ChunkCoordinate, world: &mut impl ChunkQuery) {
         if self.seen.contains(&chunk) {
             return;
         }
 
-        let dot = self.dot(chunk, world);
-        if dot < 0.0 {
+        if !is_in_interest(
+            chunk,
+            self.camera_chunk,
+            self.camera_forward,
+            self.max_distance,
+        ) {
             return;
         }
 
@@ -330,36 +899,319 @@ impl ChunkIterator {
         self.seen.insert(chunk);
     }
 
-    fn dot(&self, chunk: ChunkCoordinate, world: &World) -> f32 {
-        let direction: Vec3 =
-            (world.chunk_to_world(chunk) - world.chunk_to_world(self.camera_chunk)).normalize();
-        self.camera_forward.dot(direction)
-    }
-
-    fn calculate_priority(&self, chunk: ChunkCoordinate, world: &mut World) -> u32 {
-        let mut score = self.dot(chunk, world) / chunk_distance(chunk, self.camera_chunk) as f32;
+    fn calculate_priority(&self, chunk: ChunkCoordinate, world: &mut impl ChunkQuery) -> u32 {
+        let dot = self
+            .camera_forward
+            .dot(chunk_direction(chunk, self.camera_chunk));
+        let mut score = dot / chunk_distance(chunk, self.camera_chunk) as f32;
 
-        if let Some(true) = world.get_chunk_data(chunk).map(|data| data.empty()) {
+        if world.is_chunk_empty(chunk) || is_surrounded_by_air(chunk, world) {
             score = 0.0;
         }
 
         (score * 100.0).round() as u32
     }
 
-    fn update(&mut self, camera_chunk: ChunkCoordinate, camera_forward: Dir3) {
+    fn update(
+        &mut self,
+        camera_chunk: ChunkCoordinate,
+        camera_forward: Dir3,
+        max_distance: u32,
+        world: &mut impl ChunkQuery,
+    ) {
         // reset if camera turns too far from original direction
         if camera_forward.dot(self.camera_forward.as_vec3()) < 0.9 {
+            self.max_distance = max_distance;
             self.reset(camera_chunk, camera_forward);
-            return;
+        } else if camera_chunk != self.camera_chunk || max_distance != self.max_distance {
+            self.recentre(camera_chunk, max_distance, world);
         }
     }
 
+    /// Carries on the search from a new camera chunk or render distance rather than starting over.
+    /// Chunks already yielded that are still of interest aren't yielded again, and the search
+    /// grows out from their unseen neighbours into the ground that has come into range.
+    fn recentre(
+        &mut self,
+        camera_chunk: ChunkCoordinate,
+        max_distance: u32,
+        world: &mut impl ChunkQuery,
+    ) {
+        self.camera_chunk = camera_chunk;
+        self.max_distance = max_distance;
+        let (camera_forward, queued) = (self.camera_forward, std::mem::take(&mut self.queue));
+        self.seen
+            .retain(|chunk| is_in_interest(*chunk, camera_chunk, camera_forward, max_distance));
+
+        // queued chunks are rescored from the new camera chunk, the rest have been yielded
+        for (chunk, _) in queued {
+            if self.seen.contains(&chunk) {
+                let score = self.calculate_priority(chunk, world);
+                self.queue.push(chunk, score);
+            }
+        }
+        let yielded: Vec<ChunkCoordinate> = self
+            .seen
+            .iter()
+            .filter(|chunk| {
+                self.queue.get(chunk).is_none()
+                    && chunk_distance(**chunk, camera_chunk) < max_distance
+            })
+            .copied()
+            .collect();
+        for chunk in yielded {
+            for neighbour in chunk.adjacent() {
+                self.queue_chunk(neighbour, world);
+            }
+        }
+        if self.seen.insert(camera_chunk) {
+            self.queue.push(camera_chunk, 99999);
+        }
+    }
+
+    /// Drops queued chunks that are no longer of interest to the camera.
+    fn prune(&mut self) {
+        let (camera_chunk, camera_forward, max_distance) =
+            (self.camera_chunk, self.camera_forward, self.max_distance);
+        self.queue = std::mem::take(&mut self.queue)
+            .into_iter()
+            .filter(|(chunk, _)| is_in_interest(*chunk, camera_chunk, camera_forward, max_distance))
+            .collect();
+    }
+
     fn reset(&mut self, camera_chunk: ChunkCoordinate, camera_forward: Dir3) {
         self.seen.clear();
 
         self.camera_chunk = camera_chunk;
         self.camera_forward = camera_forward;
+        self.prune();
 
         self.queue.push(camera_chunk, 99999);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashSet, VecDeque};
+
+    use bevy::{
+        asset::Handle,
+        math::{Dir3, I64Vec3},
+    };
+
+    use super::{
+        chunk_distance, is_in_interest, take_within_budget, ChunkIterator, ChunkLoader, ChunkQuery,
+    };
+    use crate::{
+        chunks::chunk::ChunkCoordinate, settings::UnloadSettings, util::octree::OctreeCounts,
+    };
+
+    #[derive(Default)]
+    struct MockWorld {
+        empty: HashSet<ChunkCoordinate>,
+    }
+
+    impl ChunkQuery for MockWorld {
+        fn is_chunk_empty(&mut self, chunk_coord: ChunkCoordinate) -> bool {
+            self.empty.contains(&chunk_coord)
+        }
+
+        fn count_chunks(&mut self, min: ChunkCoordinate, max: ChunkCoordinate) -> OctreeCounts {
+            let loaded = self
+                .empty
+                .iter()
+                .filter(|chunk| chunk.0.cmpge(min.0).all() && chunk.0.cmple(max.0).all())
+                .count();
+            OctreeCounts {
+                loaded: loaded as u32,
+                occupied: 0,
+            }
+        }
+    }
+
+    fn coord(x: i64, y: i64, z: i64) -> ChunkCoordinate {
+        ChunkCoordinate(I64Vec3::new(x, y, z))
+    }
+
+    fn drain(iterator: &mut ChunkIterator, world: &mut MockWorld) -> Vec<ChunkCoordinate> {
+        let mut chunks = Vec::new();
+        while let Some(next) = iterator.next_chunks(64, world) {
+            chunks.extend(next);
+        }
+        chunks
+    }
+
+    #[test]
+    fn test_iterator_yields_chunks_in_front_within_distance() {
+        let mut world = MockWorld::default();
+        let mut iterator = ChunkIterator::new(6);
+        iterator.update(coord(0, 0, 0), Dir3::Z, 6, &mut world);
+
+        let chunks = drain(&mut iterator, &mut world);
+        assert_eq!(coord(0, 0, 0), chunks[0]);
+        assert!(chunks.contains(&coord(0, 0, 6)));
+        assert!(chunks.contains(&coord(4, 2, 5)));
+        // nearby chunks load for quick turns, but not those further to the side or behind
+        assert!(chunks.contains(&coord(0, 0, -2)));
+        assert!(chunks.contains(&coord(2, 0, 0)));
+        assert!(!chunks.contains(&coord(0, 0, -3)));
+        assert!(!chunks.contains(&coord(6, 0, 1)));
+        assert!(chunks
+            .iter()
+            .all(|chunk| chunk.0.z >= -2 && chunk_distance(*chunk, coord(0, 0, 0)) <= 6));
+        assert_eq!(chunks.len(), chunks.iter().collect::<HashSet<_>>().len());
+    }
+
+    #[test]
+    fn test_iterator_deprioritises_empty_chunks() {
+        let mut world = MockWorld::default();
+        world.empty.insert(coord(0, 0, 1));
+        let mut iterator = ChunkIterator::new(2);
+        iterator.update(coord(0, 0, 0), Dir3::Z, 2, &mut world);
+
+        assert_eq!(0, iterator.calculate_priority(coord(0, 0, 1), &mut world));
+        assert_eq!(50, iterator.calculate_priority(coord(0, 0, 2), &mut world));
+    }
+
+    #[test]
+    fn test_iterator_deprioritises_chunks_surrounded_by_air() {
+        let mut world = MockWorld::default();
+        for x in -1..=1 {
+            for y in 1..=3 {
+                for z in 1..=3 {
+                    if (x, y, z) != (0, 2, 2) {
+                        world.empty.insert(coord(x, y, z));
+                    }
+                }
+            }
+        }
+        let mut iterator = ChunkIterator::new(4);
+        iterator.update(coord(0, 0, 0), Dir3::Z, 4, &mut world);
+
+        assert_eq!(0, iterator.calculate_priority(coord(0, 2, 2), &mut world));
+        world.empty.remove(&coord(1, 3, 3));
+        assert_ne!(0, iterator.calculate_priority(coord(0, 2, 2), &mut world));
+    }
+
+    #[test]
+    fn test_iterator_crossing_border_reaches_new_chunks() {
+        let mut world = MockWorld::default();
+        let mut iterator = ChunkIterator::new(2);
+        iterator.update(coord(0, 0, 0), Dir3::Z, 2, &mut world);
+        let before = drain(&mut iterator, &mut world);
+
+        iterator.update(coord(0, 0, 1), Dir3::Z, 2, &mut world);
+        let after = drain(&mut iterator, &mut world);
+        assert!(after.contains(&coord(0, 0, 3)));
+        // only chunks that came into range are yielded again
+        assert!(after.iter().all(|chunk| !before.contains(chunk)));
+
+        let mut fresh = ChunkIterator::new(2);
+        fresh.update(coord(0, 0, 1), Dir3::Z, 2, &mut world);
+        let expected: HashSet<_> = drain(&mut fresh, &mut world).into_iter().collect();
+        let recentred: HashSet<_> = before
+            .into_iter()
+            .chain(after)
+            .filter(|chunk| expected.contains(chunk))
+            .collect();
+        assert_eq!(expected, recentred);
+    }
+
+    #[test]
+    fn test_iterator_crossing_border_keeps_queued_chunks() {
+        let mut world = MockWorld::default();
+        let mut iterator = ChunkIterator::new(4);
+        iterator.update(coord(0, 0, 0), Dir3::Z, 4, &mut world);
+        iterator.next_chunks(8, &mut world);
+        let queued: Vec<ChunkCoordinate> = iterator.queue.iter().map(|(chunk, _)| *chunk).collect();
+
+        iterator.update(coord(0, 0, 1), Dir3::Z, 4, &mut world);
+        assert!(queued
+            .iter()
+            .filter(|chunk| is_in_interest(**chunk, coord(0, 0, 1), Dir3::Z, 4))
+            .all(|chunk| iterator.queue.get(chunk).is_some()));
+    }
+
+    #[test]
+    fn test_iterator_teleport_prunes_stale_queue() {
+        let mut world = MockWorld::default();
+        let mut iterator = ChunkIterator::new(4);
+        iterator.update(coord(0, 0, 0), Dir3::Z, 4, &mut world);
+        iterator.next_chunks(8, &mut world);
+        assert!(!iterator.queue.is_empty());
+
+        iterator.update(coord(100, 0, 100), Dir3::Z, 4, &mut world);
+        assert_eq!(1, iterator.queue.len());
+
+        let chunks = drain(&mut iterator, &mut world);
+        assert_eq!(coord(100, 0, 100), chunks[0]);
+        assert!(chunks
+            .iter()
+            .all(|chunk| chunk_distance(*chunk, coord(100, 0, 100)) <= 4));
+    }
+
+    #[test]
+    fn test_iterator_render_distance_changes() {
+        let mut world = MockWorld::default();
+        let mut iterator = ChunkIterator::new(4);
+        iterator.update(coord(0, 0, 0), Dir3::Z, 4, &mut world);
+        iterator.next_chunks(64, &mut world);
+
+        iterator.update(coord(0, 0, 0), Dir3::Z, 1, &mut world);
+        assert!(iterator
+            .queue
+            .iter()
+            .all(|(chunk, _)| chunk_distance(*chunk, coord(0, 0, 0)) <= 1));
+        let chunks = drain(&mut iterator, &mut world);
+        assert!(!chunks.contains(&coord(0, 0, 2)));
+
+        iterator.update(coord(0, 0, 0), Dir3::Z, 3, &mut world);
+        let chunks = drain(&mut iterator, &mut world);
+        assert!(chunks.contains(&coord(0, 0, 3)));
+    }
+
+    #[test]
+    fn test_should_unload_with_hysteresis() {
+        let mut loader = ChunkLoader::new(8, Handle::default()).with_unloading(UnloadSettings {
+            extra_distance: 1,
+            grace_period: 0.0,
+        });
+        assert!(!loader.should_unload(coord(0, 0, 9), 0.1));
+        assert!(loader.should_unload(coord(0, 0, 10), 0.1));
+        assert!(loader.should_unload(coord(-10, 0, 0), 0.1));
+    }
+
+    #[test]
+    fn test_should_unload_after_grace_period() {
+        let mut loader = ChunkLoader::new(8, Handle::default()).with_unloading(UnloadSettings {
+            extra_distance: 2,
+            grace_period: 1.0,
+        });
+        let chunk = coord(0, 0, 11);
+        assert!(!loader.should_unload(chunk, 0.6));
+
+        // stepping back in range restarts the grace period
+        loader.chunk_iterator.camera_chunk = coord(0, 0, 1);
+        assert!(!loader.should_unload(chunk, 0.6));
+        loader.chunk_iterator.camera_chunk = coord(0, 0, 0);
+        assert!(!loader.should_unload(chunk, 0.6));
+        assert!(loader.should_unload(chunk, 0.6));
+    }
+
+    #[test]
+    fn test_mesh_uploads_stay_within_budget() {
+        let mut queue: VecDeque<usize> = VecDeque::from([40, 30, 20, 10, 100, 5]);
+        let take = |queue: &mut VecDeque<usize>| take_within_budget(queue, |bytes| *bytes, 3, 80);
+
+        assert_eq!(vec![40, 30], take(&mut queue));
+        assert_eq!(vec![20, 10], take(&mut queue));
+        // a mesh over the budget still goes through on its own
+        assert_eq!(vec![100], take(&mut queue));
+        assert_eq!(vec![5], take(&mut queue));
+        assert!(take(&mut queue).is_empty());
+
+        let mut small = VecDeque::from([1, 1, 1, 1]);
+        assert_eq!(3, take(&mut small).len());
+        assert_eq!(1, small.len());
+    }
+}
@@ -0,0 +1,267 @@
+use std::{fmt::Display, io};
+
+use bevy::math::U16Vec3;
+
+use super::chunk::ChunkData;
+use crate::{block::BlockType, ids::IdMap};
+
+/// Bumped whenever the encoded layout changes.
+const FORMAT_VERSION: u8 = 2;
+/// Version 1 chunks have no fluid levels, so all their fluid is full.
+const FIRST_VERSION: u8 = 1;
+const COMPRESSION_LEVEL: i32 = 3;
+const HEADER_SIZE: usize = 4;
+
+#[derive(Debug)]
+pub enum ChunkCodecError {
+    Io(io::Error),
+    UnsupportedVersion(u8),
+    Malformed(&'static str),
+}
+
+impl Display for ChunkCodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to decompress chunk: {e}"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported chunk format version {version}")
+            }
+            Self::Malformed(reason) => write!(f, "malformed chunk: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ChunkCodecError {}
+
+impl From<io::Error> for ChunkCodecError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Encodes a chunk as a palette of the block types it contains followed by one palette index
+/// per block, compressed with zstd. Runs of the same block compress to almost nothing, so a
+/// typical surface chunk is a few hundred bytes.
+///
+/// Layout before compression: format version, chunk size (`u16` little-endian), palette
+/// length, palette block ids as numbered by `ids`, then `size³` indices in x, y, z order with x varying fastest.
+/// After the indices comes the number of partly filled fluid blocks (`u16` little-endian)
+/// followed by each one's block index (`u16` little-endian) and level.
+pub fn encode_chunk(chunk: &ChunkData, ids: &IdMap) -> Vec<u8> {
+    let size = chunk.size as usize;
+    let mut palette = vec![BlockType::Air];
+    let mut indices = vec![0u8; size * size * size];
+
+    for (position, block) in chunk.blocks() {
+        let index = match palette.iter().position(|entry| *entry == block) {
+            Some(index) => index,
+            None => {
+                palette.push(block);
+                palette.len() - 1
+            }
+        };
+        indices[block_index(position, size)] = index as u8;
+    }
+
+    let mut raw = Vec::with_capacity(HEADER_SIZE + palette.len() + indices.len());
+    raw.push(FORMAT_VERSION);
+    raw.extend_from_slice(&chunk.size.to_le_bytes());
+    raw.push(palette.len() as u8);
+    raw.extend(palette.iter().map(|block| {
+        ids.block_id(*block)
+            .expect("every block is registered when a world is loaded")
+    }));
+    raw.extend(indices);
+
+    let fluid_levels: Vec<(U16Vec3, u8)> = chunk.fluid_levels().collect();
+    raw.extend_from_slice(&(fluid_levels.len() as u16).to_le_bytes());
+    for (position, level) in fluid_levels {
+        raw.extend_from_slice(&(block_index(position, size) as u16).to_le_bytes());
+        raw.push(level);
+    }
+
+    zstd::encode_all(raw.as_slice(), COMPRESSION_LEVEL).expect("compressing to memory can't fail")
+}
+
+pub fn decode_chunk(bytes: &[u8], ids: &IdMap) -> Result<ChunkData, ChunkCodecError> {
+    decode_chunk_mapped(bytes, |id| ids.block(id))
+}
+
+/// Decodes a chunk, turning each saved block id into a block with `to_block`. Ids it returns
+/// `None` for make the chunk malformed.
+pub fn decode_chunk_mapped(
+    bytes: &[u8],
+    mut to_block: impl FnMut(u8) -> Option<BlockType>,
+) -> Result<ChunkData, ChunkCodecError> {
+    let raw = zstd::decode_all(bytes)?;
+    if raw.len() < HEADER_SIZE {
+        return Err(ChunkCodecError::Malformed("missing header"));
+    }
+    let version = raw[0];
+    if !(FIRST_VERSION..=FORMAT_VERSION).contains(&version) {
+        return Err(ChunkCodecError::UnsupportedVersion(version));
+    }
+
+    let size = u16::from_le_bytes([raw[1], raw[2]]);
+    let palette_len = raw[3] as usize;
+    let palette_end = HEADER_SIZE + palette_len;
+    let block_count = (size as usize).pow(3);
+    let blocks_end = palette_end + block_count;
+    let fluid_levels = if version == FIRST_VERSION {
+        &[][..]
+    } else {
+        let Some(count) = raw.get(blocks_end..blocks_end + 2) else {
+            return Err(ChunkCodecError::Malformed("wrong length for chunk size"));
+        };
+        let count = u16::from_le_bytes([count[0], count[1]]) as usize;
+        raw.get(blocks_end + 2..)
+            .filter(|levels| levels.len() == count * 3)
+            .ok_or(ChunkCodecError::Malformed("wrong length for fluid levels"))?
+    };
+    if version == FIRST_VERSION && raw.len() != blocks_end {
+        return Err(ChunkCodecError::Malformed("wrong length for chunk size"));
+    }
+
+    let palette = raw[HEADER_SIZE..palette_end]
+        .iter()
+        .map(|id| to_block(*id))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(ChunkCodecError::Malformed("unknown block id"))?;
+
+    let mut chunk = ChunkData::with_size(size);
+    for (i, palette_index) in raw[palette_end..blocks_end].iter().enumerate() {
+        let block = *palette
+            .get(*palette_index as usize)
+            .ok_or(ChunkCodecError::Malformed("palette index out of range"))?;
+        if block != BlockType::Air {
+            chunk.set_block_at(block_position(i, size), block);
+        }
+    }
+    for entry in fluid_levels.chunks_exact(3) {
+        let index = u16::from_le_bytes([entry[0], entry[1]]) as usize;
+        if index >= block_count {
+            return Err(ChunkCodecError::Malformed("fluid level out of range"));
+        }
+        let position = block_position(index, size);
+        let block = chunk.get_block_at(position);
+        chunk.set_fluid_at(position, block, entry[2]);
+    }
+    chunk.dirty = false;
+    Ok(chunk)
+}
+
+fn block_index(position: U16Vec3, size: usize) -> usize {
+    position.x as usize + size * (position.y as usize + size * position.z as usize)
+}
+
+fn block_position(index: usize, size: u16) -> U16Vec3 {
+    let size = size as usize;
+    U16Vec3::new(
+        (index % size) as u16,
+        (index / size % size) as u16,
+        (index / (size * size)) as u16,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::U16Vec3;
+
+    use super::{decode_chunk, encode_chunk, ChunkCodecError};
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkData, FULL_FLUID_LEVEL},
+        ids::IdMap,
+    };
+
+    fn layered_chunk() -> ChunkData {
+        let mut chunk = ChunkData::default();
+        for x in 0..chunk.size {
+            for z in 0..chunk.size {
+                for y in 0..4 {
+                    chunk.set_block_at(U16Vec3::new(x, y, z), BlockType::Stone);
+                }
+                chunk.set_block_at(U16Vec3::new(x, 4, z), BlockType::Grass);
+            }
+        }
+        chunk.set_block_at(U16Vec3::new(3, 9, 15), BlockType::Glowstone);
+        chunk
+    }
+
+    #[test]
+    fn test_chunk_round_trip() {
+        let chunk = layered_chunk();
+        let ids = IdMap::default();
+        let decoded = decode_chunk(&encode_chunk(&chunk, &ids), &ids).unwrap();
+
+        assert_eq!(
+            chunk.blocks().collect::<Vec<_>>(),
+            decoded.blocks().collect::<Vec<_>>()
+        );
+        assert_eq!(chunk.size, decoded.size);
+    }
+
+    #[test]
+    fn test_empty_chunk_round_trip() {
+        let decoded = decode_chunk(
+            &encode_chunk(&ChunkData::default(), &IdMap::default()),
+            &IdMap::default(),
+        )
+        .unwrap();
+        assert!(decoded.empty());
+    }
+
+    #[test]
+    fn test_fluid_levels_round_trip() {
+        let mut chunk = layered_chunk();
+        chunk.set_fluid_at(U16Vec3::new(2, 5, 2), BlockType::Water, 3);
+        chunk.set_fluid_at(U16Vec3::new(3, 5, 2), BlockType::Water, FULL_FLUID_LEVEL);
+        let ids = IdMap::default();
+        let decoded = decode_chunk(&encode_chunk(&chunk, &ids), &ids).unwrap();
+
+        assert_eq!(
+            BlockType::Water,
+            decoded.get_block_at(U16Vec3::new(2, 5, 2))
+        );
+        assert_eq!(3, decoded.fluid_level_at(U16Vec3::new(2, 5, 2)));
+        assert_eq!(
+            FULL_FLUID_LEVEL,
+            decoded.fluid_level_at(U16Vec3::new(3, 5, 2))
+        );
+    }
+
+    #[test]
+    fn test_decode_version_one_chunk() {
+        let mut raw = vec![1u8, 8, 0, 2, BlockType::Air.id(), BlockType::Stone.id()];
+        raw.extend([1u8].into_iter().chain([0; 511]));
+        let decoded = decode_chunk(
+            &zstd::encode_all(raw.as_slice(), 3).unwrap(),
+            &IdMap::legacy(),
+        )
+        .unwrap();
+
+        assert_eq!(8, decoded.size);
+        assert_eq!(1, decoded.block_count());
+        assert_eq!(BlockType::Stone, decoded.get_block_at(U16Vec3::ZERO));
+    }
+
+    #[test]
+    fn test_encoded_chunk_is_compressed() {
+        let encoded = encode_chunk(&layered_chunk(), &IdMap::default());
+        assert!(encoded.len() < 256, "encoded to {} bytes", encoded.len());
+    }
+
+    #[test]
+    fn test_decode_rejects_garbage() {
+        assert!(matches!(
+            decode_chunk(&[1, 2, 3], &IdMap::default()),
+            Err(ChunkCodecError::Io(_))
+        ));
+
+        let wrong_version = zstd::encode_all([9u8, 16, 0, 0].as_slice(), 3).unwrap();
+        assert!(matches!(
+            decode_chunk(&wrong_version, &IdMap::default()),
+            Err(ChunkCodecError::UnsupportedVersion(9))
+        ));
+    }
+}
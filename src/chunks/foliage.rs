@@ -0,0 +1,348 @@
+//! Decorative grass and flowers scattered on top of chunk surfaces.
+//!
+//! Every tuft shares one mesh and one of a handful of materials, so the renderer batches each
+//! chunk's foliage into a few instanced draws instead of baking it into the voxel mesh.
+
+use std::f32::consts::PI;
+
+use bevy::{
+    asset::{Assets, Handle},
+    color::Color,
+    ecs::{
+        change_detection::DetectChanges,
+        component::Component,
+        entity::Entity,
+        query::With,
+        system::{Commands, Query, Res, ResMut, Resource},
+        world::{FromWorld, Ref},
+    },
+    hierarchy::{BuildChildren, ChildBuild, DespawnRecursiveExt},
+    math::{I64Vec2, I64Vec3, Quat, Vec3},
+    pbr::{MeshMaterial3d, StandardMaterial},
+    prelude::Mesh3d,
+    render::{
+        camera::Camera,
+        mesh::{Indices, Mesh, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+        view::Visibility,
+    },
+    transform::components::{GlobalTransform, Transform},
+};
+
+use super::{
+    chunk::{ChunkCoordinate, ChunkData},
+    chunk_loader::{chunk_distance, Chunk},
+    generate::biome::Biome,
+};
+use crate::{block::BlockType, block_registry::BlockRegistry, settings::Settings, world::World};
+
+/// Limits how many chunk layers are rebuilt per frame so newly meshed areas don't hitch.
+const MAX_LAYER_UPDATES_PER_FRAME: usize = 4;
+const FLOWER_CHANCE: f32 = 0.06;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FoliageKind {
+    Grass,
+    YellowFlower,
+    RedFlower,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FoliageInstance {
+    /// Base of the tuft relative to the chunk origin.
+    pub position: Vec3,
+    pub rotation: f32,
+    pub scale: f32,
+    pub kind: FoliageKind,
+}
+
+/// Cheap deterministic hash of a block position into `0.0..1.0`.
+fn hash_unit(coord: I64Vec3, salt: u64) -> f32 {
+    let mut h = (coord.x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (coord.y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (coord.z as u64).wrapping_mul(0x1656_67B1_9E37_79F9)
+        ^ salt;
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    h ^= h >> 33;
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Places foliage on exposed blocks tagged `foliage_soil`. `density` maps a local column to the
+/// probability of a tuft growing there.
+pub fn foliage_instances(
+    chunk_coord: ChunkCoordinate,
+    chunk: &ChunkData,
+    chunk_above: Option<&ChunkData>,
+    registry: &BlockRegistry,
+    mut density: impl FnMut(u16, u16) -> f32,
+) -> Vec<FoliageInstance> {
+    let mut instances = vec![];
+
+    for (coord, block) in chunk.blocks() {
+        if !registry.is(block, "foliage_soil")
+            || chunk.get_block_above(coord, chunk_above) != BlockType::Air
+        {
+            continue;
+        }
+
+        let world_coord = chunk_coord.0 * chunk.size as i64 + coord.as_i64vec3();
+        if hash_unit(world_coord, 0) >= density(coord.x, coord.z) {
+            continue;
+        }
+
+        let variant = hash_unit(world_coord, 1);
+        let kind = if variant < FLOWER_CHANCE / 2.0 {
+            FoliageKind::YellowFlower
+        } else if variant < FLOWER_CHANCE {
+            FoliageKind::RedFlower
+        } else {
+            FoliageKind::Grass
+        };
+
+        instances.push(FoliageInstance {
+            position: coord.as_vec3() + Vec3::new(0.0, 0.5, 0.0),
+            rotation: hash_unit(world_coord, 2) * PI,
+            scale: 0.7 + hash_unit(world_coord, 3) * 0.5,
+            kind,
+        });
+    }
+
+    instances
+}
+
+/// Two vertical quads crossed in an X, with the origin at the base.
+fn tuft_mesh() -> Mesh {
+    let (w, h) = (0.4, 0.6);
+    let positions = vec![
+        [-w, 0.0, -w],
+        [w, 0.0, w],
+        [-w, h, -w],
+        [w, h, w],
+        [-w, 0.0, w],
+        [w, 0.0, -w],
+        [-w, h, w],
+        [w, h, -w],
+    ];
+    let normals = vec![[0.0, 1.0, 0.0]; 8];
+    let uvs = vec![
+        [0.0, 1.0],
+        [1.0, 1.0],
+        [0.0, 0.0],
+        [1.0, 0.0],
+        [0.0, 1.0],
+        [1.0, 1.0],
+        [0.0, 0.0],
+        [1.0, 0.0],
+    ];
+
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    mesh.insert_indices(Indices::U32(vec![0, 1, 2, 2, 1, 3, 4, 5, 6, 6, 5, 7]));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh
+}
+
+#[derive(Resource)]
+pub struct FoliageAssets {
+    mesh: Handle<Mesh>,
+    grass: Handle<StandardMaterial>,
+    yellow_flower: Handle<StandardMaterial>,
+    red_flower: Handle<StandardMaterial>,
+}
+
+impl FoliageAssets {
+    fn material(&self, kind: FoliageKind) -> Handle<StandardMaterial> {
+        match kind {
+            FoliageKind::Grass => self.grass.clone(),
+            FoliageKind::YellowFlower => self.yellow_flower.clone(),
+            FoliageKind::RedFlower => self.red_flower.clone(),
+        }
+    }
+}
+
+impl FromWorld for FoliageAssets {
+    fn from_world(world: &mut bevy::ecs::world::World) -> Self {
+        let mesh = world.resource_mut::<Assets<Mesh>>().add(tuft_mesh());
+
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        let mut material = |color: Color| {
+            materials.add(StandardMaterial {
+                base_color: color,
+                unlit: true,
+                double_sided: true,
+                cull_mode: None,
+                ..Default::default()
+            })
+        };
+
+        Self {
+            mesh,
+            grass: material(Color::srgb_u8(86, 140, 50)),
+            yellow_flower: material(Color::srgb_u8(232, 210, 60)),
+            red_flower: material(Color::srgb_u8(200, 50, 45)),
+        }
+    }
+}
+
+/// The entity holding all foliage tufts of a chunk.
+#[derive(Component)]
+pub struct FoliageLayer(Entity);
+
+pub fn update_foliage(
+    mut commands: Commands,
+    mut world: ResMut<World>,
+    foliage_assets: Res<FoliageAssets>,
+    registry: Res<BlockRegistry>,
+    settings_query: Query<&Settings>,
+    camera_query: Query<&GlobalTransform, With<Camera>>,
+    chunks_query: Query<(Entity, &Chunk, Ref<Mesh3d>, Option<&FoliageLayer>)>,
+) {
+    let (Ok(settings), Ok(camera)) = (settings_query.get_single(), camera_query.get_single())
+    else {
+        return;
+    };
+    let camera_pos = camera.translation();
+    let camera_chunk = world.chunk_at(camera_pos);
+
+    let density_setting = settings.graphics.foliage_density;
+    let draw_distance = settings.graphics.foliage_distance;
+
+    let mut updates = 0;
+    for (entity, chunk, mesh, existing) in chunks_query.iter() {
+        let in_range =
+            density_setting > 0.0 && chunk_distance(chunk.coord(), camera_chunk) <= draw_distance;
+        if in_range && existing.is_some() && !mesh.is_changed() {
+            continue;
+        }
+        if !in_range && existing.is_none() {
+            continue;
+        }
+        if let Some(FoliageLayer(layer)) = existing {
+            commands.entity(*layer).despawn_recursive();
+            commands.entity(entity).remove::<FoliageLayer>();
+        }
+
+        if !in_range || updates >= MAX_LAYER_UPDATES_PER_FRAME {
+            continue;
+        }
+        updates += 1;
+
+        let Some(chunk_data) = world.get_chunk_data(chunk.coord()) else {
+            continue;
+        };
+        let chunk_above = world.get_chunk_data(ChunkCoordinate(chunk.coord().0 + I64Vec3::Y));
+
+        let chunk_size = chunk_data.size as i64;
+        let chunk_origin = chunk.coord().0 * chunk_size;
+        let generator = world.generator();
+        let instances = foliage_instances(
+            chunk.coord(),
+            &chunk_data,
+            chunk_above.as_deref(),
+            &registry,
+            |x, z| {
+                let column = I64Vec2::new(chunk_origin.x + x as i64, chunk_origin.z + z as i64);
+                let density = generator.surface(column).map_or(0.0, |surface| {
+                    Biome::from_surface(surface).foliage_density()
+                });
+                density * density_setting
+            },
+        );
+
+        let layer = commands
+            .spawn((Transform::default(), Visibility::default()))
+            .with_children(|parent| {
+                for instance in instances {
+                    parent.spawn((
+                        Mesh3d(foliage_assets.mesh.clone()),
+                        MeshMaterial3d(foliage_assets.material(instance.kind)),
+                        Transform::from_translation(instance.position)
+                            .with_rotation(Quat::from_rotation_y(instance.rotation))
+                            .with_scale(Vec3::splat(instance.scale)),
+                    ));
+                }
+            })
+            .id();
+
+        commands
+            .entity(entity)
+            .add_child(layer)
+            .insert(FoliageLayer(layer));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec3, U16Vec3};
+
+    use crate::{
+        block::BlockType,
+        block_registry::BlockRegistry,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+    };
+
+    use super::foliage_instances;
+
+    fn registry() -> BlockRegistry {
+        BlockRegistry::parse_tags(r#"{"foliage_soil": ["Grass"]}"#).unwrap()
+    }
+
+    fn grass_chunk() -> ChunkData {
+        let mut chunk_data = ChunkData::default();
+        for x in 0..chunk_data.size {
+            for z in 0..chunk_data.size {
+                chunk_data.set_block_at(U16Vec3::new(x, 3, z), BlockType::Grass);
+            }
+        }
+        chunk_data.set_block_at(U16Vec3::new(0, 4, 0), BlockType::Stone);
+        chunk_data
+    }
+
+    #[test]
+    fn test_foliage_instances_full_density_covers_exposed_grass() {
+        let chunk_data = grass_chunk();
+        let instances = foliage_instances(
+            ChunkCoordinate(I64Vec3::ZERO),
+            &chunk_data,
+            None,
+            &registry(),
+            |_, _| 1.0,
+        );
+
+        assert_eq!(255, instances.len());
+        assert!(instances.iter().all(|instance| instance.position.y == 3.5));
+    }
+
+    #[test]
+    fn test_foliage_instances_zero_density() {
+        let chunk_data = grass_chunk();
+        let instances = foliage_instances(
+            ChunkCoordinate(I64Vec3::ZERO),
+            &chunk_data,
+            None,
+            &registry(),
+            |_, _| 0.0,
+        );
+
+        assert!(instances.is_empty());
+    }
+
+    #[test]
+    fn test_foliage_instances_deterministic() {
+        let chunk_data = grass_chunk();
+        let coord = ChunkCoordinate(I64Vec3::new(3, 0, -2));
+        let registry = registry();
+        let mut first = foliage_instances(coord, &chunk_data, None, &registry, |_, _| 0.5);
+        let mut second = foliage_instances(coord, &chunk_data, None, &registry, |_, _| 0.5);
+
+        let key = |a: &super::FoliageInstance| (a.position.x as i32, a.position.z as i32);
+        first.sort_by_key(key);
+        second.sort_by_key(key);
+        assert_eq!(first, second);
+    }
+}
@@ -0,0 +1,84 @@
+use bevy::math::I64Vec2;
+use serde::Deserialize;
+
+use super::noise::NoiseGenerator;
+
+/// Height and steepness of the terrain surface at a single block column.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ColumnSurface {
+    pub height: u64,
+    pub gradient: f64,
+}
+
+pub fn column_surface(
+    noise: &mut NoiseGenerator,
+    column: I64Vec2,
+    world_height: u64,
+) -> ColumnSurface {
+    let height = (noise.get(column) * world_height as f64).round() as u64;
+
+    let gradient_x =
+        (height as f64 * (noise.get(column + I64Vec2::X) - noise.get(column - I64Vec2::X))).abs();
+    let gradient_z =
+        (height as f64 * (noise.get(column + I64Vec2::Y) - noise.get(column - I64Vec2::Y))).abs();
+
+    ColumnSurface {
+        height,
+        gradient: gradient_x + gradient_z,
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize)]
+pub enum Biome {
+    Ocean,
+    Desert,
+    Plains,
+    Mountains,
+    Snow,
+}
+
+impl Biome {
+    pub fn from_surface(surface: ColumnSurface) -> Self {
+        if surface.height <= 16 {
+            Self::Ocean
+        } else if surface.height < 36 {
+            Self::Desert
+        } else if surface.height >= 90 && surface.gradient <= 2.0 {
+            Self::Snow
+        } else if surface.height >= 70 && surface.gradient >= 2.0 || surface.gradient >= 3.5 {
+            Self::Mountains
+        } else {
+            Self::Plains
+        }
+    }
+
+    pub fn foliage_density(&self) -> f32 {
+        match self {
+            Self::Plains => 1.0,
+            Self::Mountains => 0.3,
+            Self::Desert => 0.05,
+            Self::Ocean | Self::Snow => 0.0,
+        }
+    }
+
+    pub fn at(noise: &mut NoiseGenerator, column: I64Vec2, world_height: u64) -> Self {
+        Self::from_surface(column_surface(noise, column, world_height))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Biome, ColumnSurface};
+
+    #[test]
+    fn test_biome_from_surface() {
+        let surface = |height, gradient| ColumnSurface { height, gradient };
+
+        assert_eq!(Biome::Ocean, Biome::from_surface(surface(10, 0.0)));
+        assert_eq!(Biome::Desert, Biome::from_surface(surface(20, 5.0)));
+        assert_eq!(Biome::Plains, Biome::from_surface(surface(50, 1.0)));
+        assert_eq!(Biome::Mountains, Biome::from_surface(surface(50, 4.0)));
+        assert_eq!(Biome::Mountains, Biome::from_surface(surface(75, 2.5)));
+        assert_eq!(Biome::Snow, Biome::from_surface(surface(100, 1.0)));
+    }
+}
@@ -0,0 +1,150 @@
+//! Hashes of generated chunks, so changes to generation can be checked against what worlds
+//! generated before.
+
+use bevy::math::U16Vec3;
+
+use crate::chunks::chunk::ChunkData;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// FNV-1a hash of every block and fluid level in `chunk`, in a fixed order. Unlike
+/// `DefaultHasher` it's the same across Rust versions, so hashes can be kept in files.
+pub fn chunk_hash(chunk: &ChunkData) -> u64 {
+    let mut hash = FNV_OFFSET;
+    let mut write = |byte: u8| {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    };
+    chunk.size.to_le_bytes().into_iter().for_each(&mut write);
+    for y in 0..chunk.size {
+        for z in 0..chunk.size {
+            for x in 0..chunk.size {
+                let position = U16Vec3::new(x, y, z);
+                write(chunk.get_block_at(position).id());
+                write(chunk.fluid_level_at(position));
+            }
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, HashSet},
+        env, fs,
+        ops::RangeInclusive,
+        path::Path,
+    };
+
+    use bevy::math::{I64Vec3, U16Vec3};
+
+    use super::chunk_hash;
+    use crate::{
+        block::BlockType,
+        chunks::{
+            chunk::{ChunkCoordinate, ChunkData},
+            generate::terrain::{GeneratorKind, GeneratorRegistry, GENERATOR_KINDS},
+        },
+        world::World,
+    };
+
+    /// Hashes the generation test expects, kept in the repository and only rewritten with
+    /// `BLESS_GENERATION=1` when a change to the world's output is intended.
+    const EXPECTED_HASHES: &str = "tests/generation_hashes.ron";
+    const SEED: u32 = 1288;
+    /// The void platform at the origin, and columns that are land rather than sea with this seed.
+    const COLUMNS: [(i64, i64); 4] = [(0, 0), (-40, -32), (-32, -16), (-40, 16)];
+    /// Chunk heights from below the sea floor to the peaks of amplified terrain, which takes in
+    /// superflat's surface at 2 and the void platform at 3.
+    const HEIGHTS: RangeInclusive<i64> = 0..=4;
+
+    /// Whether every block in `chunk` is the same.
+    fn is_uniform(chunk: &ChunkData) -> bool {
+        match chunk.get_block_at(U16Vec3::ZERO) {
+            BlockType::Air => chunk.empty(),
+            first => {
+                chunk.block_count() == (chunk.size as usize).pow(3)
+                    && chunk.blocks().all(|(_, block)| block == first)
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunk_hash_changes_with_blocks() {
+        let mut chunk = ChunkData::default();
+        let empty = chunk_hash(&chunk);
+        assert_eq!(empty, chunk_hash(&ChunkData::default()));
+        assert_ne!(empty, chunk_hash(&ChunkData::with_size(32)));
+
+        chunk.set_block_at(U16Vec3::new(1, 2, 3), BlockType::Stone);
+        let stone = chunk_hash(&chunk);
+        assert_ne!(empty, stone);
+        chunk.set_block_at(U16Vec3::new(1, 2, 3), BlockType::Grass);
+        assert_ne!(stone, chunk_hash(&chunk));
+    }
+
+    #[test]
+    fn test_generation_is_unchanged() {
+        let mut hashes = BTreeMap::new();
+        for kind in GENERATOR_KINDS {
+            let world = World::new(SEED)
+                .with_generator(kind.clone(), &GeneratorRegistry::default())
+                .unwrap();
+            let mut any_varied = false;
+            let mut columns = HashSet::new();
+            for (x, z) in COLUMNS {
+                let mut column = Vec::new();
+                for y in HEIGHTS {
+                    let chunk = world
+                        .generator()
+                        .generate(ChunkCoordinate(I64Vec3::new(x, y, z)));
+                    any_varied |= !is_uniform(&chunk);
+                    let key = format!("{} {} {} {}", kind.name(), x, y, z);
+                    hashes.insert(key, format!("{:016x}", chunk_hash(&chunk)));
+                    column.push(chunk_hash(&chunk));
+                }
+                columns.insert(column);
+            }
+            // otherwise the hashes would only check that the sky is still empty
+            assert!(any_varied, "{} only sampled uniform chunks", kind.name());
+            if matches!(kind, GeneratorKind::Noise | GeneratorKind::Amplified) {
+                assert_eq!(
+                    COLUMNS.len(),
+                    columns.len(),
+                    "{} generated the same chunks in different columns",
+                    kind.name()
+                );
+            }
+        }
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(EXPECTED_HASHES);
+        if env::var_os("BLESS_GENERATION").is_some() {
+            let source = ron::ser::to_string_pretty(&hashes, Default::default()).unwrap();
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, source).unwrap();
+            eprintln!("wrote generation hashes to {}", path.display());
+            return;
+        }
+        let expected = fs::read_to_string(&path).unwrap_or_else(|e| {
+            panic!(
+                "failed to read {} ({}), run with BLESS_GENERATION=1 to write it",
+                path.display(),
+                e
+            )
+        });
+
+        let expected: BTreeMap<String, String> = ron::from_str(&expected).unwrap();
+        let changed: Vec<_> = hashes
+            .iter()
+            .filter(|(key, hash)| expected.get(*key) != Some(hash))
+            .map(|(key, _)| key.as_str())
+            .collect();
+        assert!(
+            changed.is_empty(),
+            "generation changed for {:?}, rerun with BLESS_GENERATION=1 if that was intended",
+            changed
+        );
+    }
+}
@@ -1,25 +1,80 @@
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
 
 use bevy::{
-    math::{I64Vec2, U16Vec3, Vec3},
+    math::{I64Vec2, I64Vec3, IVec3, U16Vec3, Vec3},
     render::{
         mesh::{Indices, Mesh, VertexAttributeValues},
         render_asset::RenderAssetUsages,
     },
 };
 
-use super::noise::NoiseGenerator;
-use crate::block::{BlockType, BLOCK_COUNT};
-use crate::chunks::chunk::{ChunkCoordinate, ChunkData};
+use super::{
+    noise::{NoiseGenerator, SharedNoise},
+    structure::place_structures,
+    visibility::face_masks,
+};
+use serde::Deserialize;
+
+use crate::block::{BlockLayer, BlockShape, BlockType};
+use crate::chunks::chunk::{ChunkCoordinate, ChunkData, FULL_FLUID_LEVEL};
+use crate::chunks::vertex::{ChunkVertex, ATTRIBUTE_PACKED_VERTEX, MAX_LIGHT, MAX_OCCLUSION};
 use crate::util::primitives::Vertex;
 
+/// Index of the top face in `FACE_NORMALS`, given to plants so they are lit from above.
+const TOP_FACE: usize = 4;
+/// Direction each face of a cube looks out of its block, in the mesher's face order.
+const FACE_DIRECTIONS: [IVec3; 6] = [
+    IVec3::NEG_Z,
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Z,
+    IVec3::Y,
+    IVec3::NEG_Y,
+];
+/// Offset of each chunk in `ChunkCoordinate::adjacent` order.
+const ADJACENT_DIRECTIONS: [IVec3; 6] = [
+    IVec3::Z,
+    IVec3::NEG_Z,
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+];
+
+/// How light is baked into chunk meshes.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Lighting {
+    /// Each vertex is darkened by the blocks crowding its corner, blending into soft shadows
+    /// across faces.
+    #[default]
+    Smooth,
+    /// Every face is lit evenly, for a crisp look. Cheaper to mesh, as no neighbours are read.
+    Flat,
+}
+
+impl Lighting {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Smooth => "smooth",
+            Self::Flat => "flat",
+        }
+    }
+}
+
 pub fn generate_chunk(
-    noise_generator: Arc<RwLock<NoiseGenerator>>,
+    noise: SharedNoise,
     chunk_pos: ChunkCoordinate,
     world_height: u64,
+    chunk_size: u16,
 ) -> ChunkData {
-    let mut chunk_data = ChunkData::default();
-    let mut noise = noise_generator.write().unwrap();
+    let mut chunk_data = ChunkData::with_size(chunk_size);
+    let mut noise = NoiseGenerator::from_shared(noise);
+    let heightmap = noise.heightmap(
+        I64Vec2::new(chunk_pos.0.x, chunk_pos.0.z),
+        chunk_size,
+        world_height,
+    );
 
     for x in 0..chunk_data.size {
         for z in 0..chunk_data.size {
@@ -28,9 +83,9 @@ pub fn generate_chunk(
                 chunk_pos.0.y * chunk_data.size as i64,
                 chunk_pos.0.z * chunk_data.size as i64 + z as i64,
             );
-            let noise_val = noise.get(I64Vec2::new(world_x, world_z));
+            let surface = heightmap.get(x, z);
 
-            let world_height = (noise_val * world_height as f64).round() as u64;
+            let world_height = surface.height;
             let chunk_height = if world_y > 0 {
                 let positive_y = world_y as u64;
                 (world_height - positive_y.min(world_height)).min(chunk_data.size as u64)
@@ -38,16 +93,7 @@ pub fn generate_chunk(
                 chunk_data.size as u64
             };
 
-            let gradient_x = (world_height as f64
-                * (noise.get(I64Vec2::new(world_x + 1, world_z))
-                    - noise.get(I64Vec2::new(world_x - 1, world_z))))
-            .abs();
-            let gradient_z = (world_height as f64
-                * (noise.get(I64Vec2::new(world_x, world_z + 1))
-                    - noise.get(I64Vec2::new(world_x, world_z - 1))))
-            .abs();
-
-            let combined_gradient = gradient_x + gradient_z;
+            let combined_gradient = surface.gradient;
 
             for y in 0..chunk_height {
                 let world_y = world_y + y as i64;
@@ -63,6 +109,9 @@ pub fn generate_chunk(
                 } else {
                     BlockType::Sand
                 };
+                let depth = surface.height.saturating_sub(world_y.max(0) as u64 + 1);
+                let position = I64Vec3::new(world_x, world_y, world_z);
+                let block = noise.ores().ore_at(position, depth).unwrap_or(block);
                 chunk_data.set_block_at(U16Vec3::new(x, y as u16, z), block);
             }
 
@@ -74,37 +123,147 @@ pub fn generate_chunk(
         }
     }
 
+    let chunk_origin = chunk_pos.0 * chunk_data.size as i64;
+    let seed = noise.seed();
+    place_structures(
+        &mut chunk_data,
+        chunk_origin,
+        seed,
+        &mut noise,
+        world_height,
+    );
     chunk_data
 }
 
+/// A chunk's meshes: opaque and cut out blocks together, and blended blocks on their own so they
+/// can be drawn after everything behind them.
+pub struct ChunkMeshes {
+    pub solid: Mesh,
+    /// `None` when the chunk has no translucent blocks.
+    pub translucent: Option<Mesh>,
+}
+
+/// Packed vertices and indices of a mesh being built.
+#[derive(Default)]
+struct MeshBuilder {
+    vertices: Vec<[u32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    /// Adds one face of a block. `height` is how far up its cell the block reaches, lower for
+    /// partly filled fluid. `occlusion` is given for each of the face's four vertices.
+    fn add_face(
+        &mut self,
+        vs: &[Vertex],
+        face: usize,
+        position: Vec3,
+        block_type: BlockType,
+        height: f32,
+        occlusion: [u8; 4],
+    ) {
+        let triangle_start: u32 = self.vertices.len() as u32;
+        self.vertices
+            .extend(vs.iter().zip(occlusion).map(|(v, occlusion)| {
+                ChunkVertex {
+                    position: Vec3::new(
+                        v.position[0],
+                        v.position[1].min(height - 0.5),
+                        v.position[2],
+                    ) + position,
+                    face: face as u8,
+                    uv: v.uv,
+                    texture: block_type.texture(),
+                    emissive: block_type.emissive(),
+                    light: MAX_LIGHT,
+                    occlusion,
+                }
+                .pack()
+            }));
+        // split the quad along the diagonal whose ends are equally occluded, so shading doesn't
+        // bend along the wrong one
+        let corners = if occlusion[0] + occlusion[3] > occlusion[1] + occlusion[2] {
+            [0, 1, 3, 0, 3, 2]
+        } else {
+            [0, 1, 2, 2, 1, 3]
+        };
+        self.indices
+            .extend(corners.map(|corner| triangle_start + corner));
+    }
+
+    fn build(self) -> Mesh {
+        let mut mesh = Mesh::new(
+            bevy::render::mesh::PrimitiveTopology::TriangleList,
+            RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD,
+        );
+        mesh.insert_indices(Indices::U32(self.indices));
+        mesh.insert_attribute(
+            ATTRIBUTE_PACKED_VERTEX,
+            VertexAttributeValues::Uint32x2(self.vertices),
+        );
+        mesh
+    }
+}
+
+/// Whether the block at `position`, which may be just outside `chunk`, darkens the corners of
+/// faces next to it. Blocks past an edge or corner of the chunk aren't known, so don't.
+fn occludes(chunk: &ChunkData, adjacent: &[Option<Arc<ChunkData>>], position: IVec3) -> bool {
+    let size = chunk.size as i32;
+    let outside = position.cmplt(IVec3::ZERO) | position.cmpge(IVec3::splat(size));
+    let block = match outside.bitmask().count_ones() {
+        0 => chunk.get_block_at(position.as_u16vec3()),
+        1 => {
+            let direction = position.div_euclid(IVec3::splat(size));
+            let Some(neighbour) = ADJACENT_DIRECTIONS
+                .iter()
+                .position(|d| *d == direction)
+                .and_then(|i| adjacent[i].as_deref())
+            else {
+                return false;
+            };
+            neighbour.get_block_at(position.rem_euclid(IVec3::splat(size)).as_u16vec3())
+        }
+        _ => return false,
+    };
+    block != BlockType::Air && block.shape() == BlockShape::Cube && !block.is_transparent()
+}
+
+/// Occlusion of each vertex of a cube's face, from the blocks beside, and diagonally across
+/// from, each corner on the side the face looks out of.
+fn face_occlusion(
+    chunk: &ChunkData,
+    adjacent: &[Option<Arc<ChunkData>>],
+    position: U16Vec3,
+    face: usize,
+    vertices: &[Vertex],
+) -> [u8; 4] {
+    let direction = FACE_DIRECTIONS[face];
+    let outside = position.as_ivec3() + direction;
+    std::array::from_fn(|i| {
+        // the corner's offset along the two axes the face lies in
+        let corner =
+            Vec3::from(vertices[i].position).signum().as_ivec3() * (IVec3::ONE - direction.abs());
+        let (side_a, side_b) = match direction.abs() {
+            IVec3::X => (IVec3::new(0, corner.y, 0), IVec3::new(0, 0, corner.z)),
+            IVec3::Y => (IVec3::new(corner.x, 0, 0), IVec3::new(0, 0, corner.z)),
+            _ => (IVec3::new(corner.x, 0, 0), IVec3::new(0, corner.y, 0)),
+        };
+        let side_a = occludes(chunk, adjacent, outside + side_a);
+        let side_b = occludes(chunk, adjacent, outside + side_b);
+        if side_a && side_b {
+            return MAX_OCCLUSION;
+        }
+        side_a as u8 + side_b as u8 + occludes(chunk, adjacent, outside + corner) as u8
+    })
+}
+
 pub fn generate_chunk_mesh(
     chunk: Arc<ChunkData>,
     adjacent_chunks: Vec<Option<Arc<ChunkData>>>,
-) -> Mesh {
-    let mut vertices: Vec<Vertex> = vec![];
-    let mut indices: Vec<u32> = vec![];
-
-    let mut add_vertices = |vs: &[Vertex], position: Vec3, block_type: BlockType| {
-        let uv_scale = 1.0 / (BLOCK_COUNT - 1) as f32;
-
-        let triangle_start: u32 = vertices.len() as u32;
-        vertices.extend(&mut vs.iter().map(|v| Vertex {
-            position: (Vec3::from(v.position) + position).into(),
-            normal: v.normal,
-            uv: [
-                uv_scale * (v.uv[0] + (block_type as usize - 1) as f32),
-                v.uv[1],
-            ],
-        }));
-        indices.extend(vec![
-            triangle_start,
-            triangle_start + 1,
-            triangle_start + 2,
-            triangle_start + 2,
-            triangle_start + 1,
-            triangle_start + 3,
-        ]);
-    };
+    lighting: Lighting,
+) -> ChunkMeshes {
+    let mut solid = MeshBuilder::default();
+    let mut translucent = MeshBuilder::default();
 
     let cube_vertices = crate::util::primitives::cube();
     let face_vertices = [
@@ -116,94 +275,210 @@ pub fn generate_chunk_mesh(
         &cube_vertices[20..24], // bottom
     ];
 
-    for (coord, block) in chunk.blocks().iter() {
-        let (x, y, z) = (coord.x, coord.y, coord.z);
-        let world_position = Vec3::new(x as f32, y as f32, z as f32);
+    let cross_vertices = crate::util::primitives::cross();
 
-        let front = if z > 0 {
-            chunk.get_block_at(U16Vec3::new(x, y, z - 1))
-        } else {
-            let adjacent = &adjacent_chunks[1].as_ref();
-            adjacent
-                .map(|adjacent| adjacent.get_block_at(U16Vec3::new(x, y, adjacent.size - 1)))
-                .unwrap_or_default()
+    let masks = face_masks(&chunk, &adjacent_chunks);
+    for (coord, block) in chunk.blocks() {
+        let world_position = coord.as_vec3();
+        let height = chunk.fluid_level_at(coord) as f32 / FULL_FLUID_LEVEL as f32;
+        let builder = match block.layer() {
+            BlockLayer::Opaque | BlockLayer::Cutout => &mut solid,
+            BlockLayer::Translucent => &mut translucent,
         };
+        match block.shape() {
+            BlockShape::Cube => {
+                for (face, vertices) in face_vertices.iter().enumerate() {
+                    if !masks.is_visible(face, coord) {
+                        continue;
+                    }
+                    let occlusion = match lighting {
+                        Lighting::Smooth => {
+                            face_occlusion(&chunk, &adjacent_chunks, coord, face, vertices)
+                        }
+                        Lighting::Flat => [0; 4],
+                    };
+                    builder.add_face(vertices, face, world_position, block, height, occlusion);
+                }
+            }
+            BlockShape::Cross => {
+                for quad in cross_vertices.chunks(4) {
+                    builder.add_face(quad, TOP_FACE, world_position, block, 1.0, [0; 4]);
+                }
+            }
+            // other shapes are left out of the face masks, so every face of their boxes is drawn
+            shape => {
+                for &(min, max) in shape.boxes() {
+                    for (face, vertices) in face_vertices.iter().enumerate() {
+                        let vertices: Vec<Vertex> = vertices
+                            .iter()
+                            .map(|v| Vertex {
+                                position: (min + (Vec3::from(v.position) + 0.5) * (max - min))
+                                    .to_array(),
+                                ..*v
+                            })
+                            .collect();
+                        builder.add_face(&vertices, face, world_position, block, 1.0, [0; 4]);
+                    }
+                }
+            }
+        }
+    }
 
-        let back = if z < chunk.size - 1 {
-            chunk.get_block_at(U16Vec3::new(x, y, z + 1))
-        } else {
-            let adjacent = &adjacent_chunks[0].as_ref();
-            adjacent
-                .map(|adjacent| adjacent.get_block_at(U16Vec3::new(x, y, 0)))
-                .unwrap_or_default()
-        };
+    ChunkMeshes {
+        solid: solid.build(),
+        translucent: (!translucent.indices.is_empty()).then(|| translucent.build()),
+    }
+}
 
-        let left = if x > 0 {
-            chunk.get_block_at(U16Vec3::new(x - 1, y, z))
-        } else {
-            let adjacent = &adjacent_chunks[3].as_ref();
-            adjacent
-                .map(|adjacent| adjacent.get_block_at(U16Vec3::new(adjacent.size - 1, y, z)))
-                .unwrap_or_default()
-        };
+/// Mesh of a lone block, for entities drawn as blocks.
+pub fn generate_block_mesh(block: BlockType) -> Mesh {
+    let mut chunk_data = ChunkData::default();
+    chunk_data.set_block_at(U16Vec3::ZERO, block);
+    let meshes = generate_chunk_mesh(Arc::new(chunk_data), vec![None; 6], Lighting::Flat);
+    meshes.translucent.unwrap_or(meshes.solid)
+}
 
-        let right = if x < chunk.size - 1 {
-            chunk.get_block_at(U16Vec3::new(x + 1, y, z))
-        } else {
-            let adjacent = &adjacent_chunks[2].as_ref();
-            adjacent
-                .map(|adjacent| adjacent.get_block_at(U16Vec3::new(0, y, z)))
-                .unwrap_or_default()
-        };
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
 
-        let top = if y < chunk.size - 1 {
-            chunk.get_block_at(U16Vec3::new(x, y + 1, z))
-        } else {
-            let adjacent = &adjacent_chunks[4].as_ref();
-            adjacent
-                .map(|adjacent| adjacent.get_block_at(U16Vec3::new(x, 0, z)))
-                .unwrap_or_default()
-        };
+    use bevy::math::{I64Vec3, U16Vec3};
 
-        let bottom = if y > 0 {
-            chunk.get_block_at(U16Vec3::new(x, y - 1, z))
-        } else {
-            let adjacent = &adjacent_chunks[5].as_ref();
-            adjacent
-                .map(|adjacent| adjacent.get_block_at(U16Vec3::new(x, adjacent.size - 1, z)))
-                .unwrap_or_default()
-        };
+    use bevy::render::mesh::VertexAttributeValues;
 
-        let sides = [front, right, left, back, top, bottom];
-        for (i, side) in sides.iter().enumerate() {
-            match side {
-                BlockType::Water => {
-                    if *block != BlockType::Water {
-                        add_vertices(&face_vertices[i], world_position, *block)
+    use super::{generate_chunk, generate_chunk_mesh, Lighting};
+    use crate::{
+        block::BlockType,
+        chunks::{
+            chunk::{ChunkCoordinate, ChunkData},
+            generate::noise::SharedNoise,
+            vertex::{ChunkVertex, ATTRIBUTE_PACKED_VERTEX},
+        },
+    };
+
+    const WORLD_HEIGHT: u64 = 256;
+
+    #[test]
+    fn test_chunk_sizes_generate_the_same_terrain() {
+        let noise = SharedNoise::new(7);
+        let large = generate_chunk(
+            noise.clone(),
+            ChunkCoordinate(I64Vec3::new(0, 1, -1)),
+            WORLD_HEIGHT,
+            32,
+        );
+
+        for x in 0..2 {
+            for y in 2..4 {
+                for z in -2..0 {
+                    let coord = ChunkCoordinate(I64Vec3::new(x, y, z));
+                    let small = generate_chunk(noise.clone(), coord, WORLD_HEIGHT, 16);
+                    let offset = ((coord.0 * 16).rem_euclid(I64Vec3::splat(32))).as_u16vec3();
+                    for (position, block) in small.blocks() {
+                        assert_eq!(block, large.get_block_at(position + offset));
                     }
+                    let in_small = |position: U16Vec3| {
+                        position.cmpge(offset).all() && position.cmplt(offset + 16).all()
+                    };
+                    assert_eq!(
+                        small.block_count(),
+                        large
+                            .blocks()
+                            .filter(|(position, _)| in_small(*position))
+                            .count()
+                    );
                 }
-                BlockType::Air => add_vertices(&face_vertices[i], world_position, *block),
-                _ => (),
-            };
+            }
         }
     }
 
-    let mut mesh = Mesh::new(
-        bevy::render::mesh::PrimitiveTopology::TriangleList,
-        RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD,
-    );
-    mesh.insert_indices(Indices::U32(indices));
-    mesh.insert_attribute(
-        Mesh::ATTRIBUTE_POSITION,
-        VertexAttributeValues::Float32x3(vertices.iter().map(|v| v.position).collect()),
-    );
-    mesh.insert_attribute(
-        Mesh::ATTRIBUTE_NORMAL,
-        VertexAttributeValues::Float32x3(vertices.iter().map(|v| v.normal).collect()),
-    );
-    mesh.insert_attribute(
-        Mesh::ATTRIBUTE_UV_0,
-        VertexAttributeValues::Float32x2(vertices.iter().map(|v| v.uv).collect()),
-    );
-    mesh
+    #[test]
+    fn test_translucent_blocks_meshed_separately() {
+        let mut chunk = ChunkData::default();
+        chunk.set_block_at(U16Vec3::new(1, 1, 1), BlockType::Stone);
+        chunk.set_block_at(U16Vec3::new(2, 1, 1), BlockType::Glass);
+        chunk.set_block_at(U16Vec3::new(4, 1, 1), BlockType::Leaves);
+
+        let meshes = generate_chunk_mesh(Arc::new(chunk), vec![None; 6], Lighting::Smooth);
+        // every face of the stone and leaves, four vertices each
+        assert_eq!(2 * 6 * 4, meshes.solid.count_vertices());
+        // the glass face against the stone is hidden
+        assert_eq!(5 * 4, meshes.translucent.unwrap().count_vertices());
+
+        let meshes = generate_chunk_mesh(
+            Arc::new(ChunkData::default()),
+            vec![None; 6],
+            Lighting::Smooth,
+        );
+        assert!(meshes.translucent.is_none());
+    }
+
+    #[test]
+    fn test_block_shapes_meshed() {
+        let mesh = |block| {
+            let mut chunk = ChunkData::default();
+            chunk.set_block_at(U16Vec3::new(1, 1, 1), block);
+            chunk.set_block_at(U16Vec3::new(1, 0, 1), BlockType::Stone);
+            let mesh = generate_chunk_mesh(Arc::new(chunk), vec![None; 6], Lighting::Smooth).solid;
+            let Some(VertexAttributeValues::Uint32x2(vertices)) =
+                mesh.attribute(ATTRIBUTE_PACKED_VERTEX)
+            else {
+                panic!("chunk mesh has no packed vertices");
+            };
+            vertices
+                .iter()
+                .map(|v| ChunkVertex::unpack(*v).position)
+                .collect::<Vec<_>>()
+        };
+        // shapes don't hide faces of the blocks around them, so all six faces of the stone below
+        // are drawn
+        let stone = 6 * 4;
+
+        let slab = mesh(BlockType::StoneSlab);
+        assert_eq!(stone + 6 * 4, slab.len());
+        assert!(slab.iter().all(|position| position.y <= 1.0));
+
+        let stairs = mesh(BlockType::StoneStairs);
+        assert_eq!(stone + 2 * 6 * 4, stairs.len());
+        assert!(stairs
+            .iter()
+            .filter(|position| position.y > 1.0)
+            .all(|position| position.z >= 1.0));
+
+        let plant = mesh(BlockType::TallGrass);
+        assert_eq!(stone + 4 * 4, plant.len());
+    }
+
+    #[test]
+    fn test_smooth_lighting_occludes_corners() {
+        let mut chunk = ChunkData::default();
+        chunk.set_block_at(U16Vec3::new(1, 1, 1), BlockType::Stone);
+        chunk.set_block_at(U16Vec3::new(2, 2, 1), BlockType::Stone);
+        let chunk = Arc::new(chunk);
+
+        // the top face of the lower stone, whose +x edge runs under the upper stone
+        let top_face = |lighting| {
+            let mesh = generate_chunk_mesh(chunk.clone(), vec![None; 6], lighting).solid;
+            let Some(VertexAttributeValues::Uint32x2(vertices)) =
+                mesh.attribute(ATTRIBUTE_PACKED_VERTEX)
+            else {
+                panic!("chunk mesh has no packed vertices");
+            };
+            vertices
+                .iter()
+                .map(|v| ChunkVertex::unpack(*v))
+                .filter(|v| v.face == 4 && v.position.y == 1.5)
+                .map(|v| (v.position.x, v.occlusion))
+                .collect::<Vec<_>>()
+        };
+
+        let smooth = top_face(Lighting::Smooth);
+        assert_eq!(4, smooth.len());
+        for (x, occlusion) in smooth {
+            assert_eq!(if x > 1.0 { 1 } else { 0 }, occlusion);
+        }
+        assert!(top_face(Lighting::Flat)
+            .iter()
+            .all(|(_, occlusion)| *occlusion == 0));
+    }
 }
@@ -0,0 +1,137 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use bevy::math::I64Vec2;
+
+use super::{
+    biome::{column_surface, ColumnSurface},
+    noise::NoiseGenerator,
+};
+
+/// Heightmaps kept before the oldest are dropped, enough for every column within a large
+/// render distance.
+const MAX_CACHED_HEIGHTMAPS: usize = 4096;
+
+/// The surface of every block column in a chunk column.
+pub struct Heightmap {
+    size: u16,
+    columns: Vec<ColumnSurface>,
+}
+
+impl Heightmap {
+    /// Samples the surface of the chunk column at `chunk_column` for chunks of `size`.
+    pub fn generate(
+        noise: &mut NoiseGenerator,
+        chunk_column: I64Vec2,
+        size: u16,
+        world_height: u64,
+    ) -> Self {
+        let origin = chunk_column * size as i64;
+        let mut columns = Vec::with_capacity(size as usize * size as usize);
+        for z in 0..size {
+            for x in 0..size {
+                let column = origin + I64Vec2::new(x as i64, z as i64);
+                columns.push(column_surface(noise, column, world_height));
+            }
+        }
+        Self { size, columns }
+    }
+
+    pub fn get(&self, x: u16, z: u16) -> ColumnSurface {
+        self.columns[x as usize + z as usize * self.size as usize]
+    }
+}
+
+type HeightmapKey = (I64Vec2, u16, u64);
+
+/// Heightmaps shared between generation tasks, so stacked chunks sample each column's noise
+/// once rather than once per chunk.
+#[derive(Default)]
+pub struct HeightmapCache {
+    heightmaps: Mutex<CachedHeightmaps>,
+}
+
+#[derive(Default)]
+struct CachedHeightmaps {
+    by_column: HashMap<HeightmapKey, Arc<Heightmap>>,
+    /// Oldest first, for eviction.
+    order: VecDeque<HeightmapKey>,
+}
+
+impl HeightmapCache {
+    /// The heightmap of `chunk_column`, generated with `noise` if it isn't cached. The lock
+    /// isn't held while generating, so two tasks may occasionally both generate the same one.
+    pub fn get_or_generate(
+        &self,
+        noise: &mut NoiseGenerator,
+        chunk_column: I64Vec2,
+        size: u16,
+        world_height: u64,
+    ) -> Arc<Heightmap> {
+        let key = (chunk_column, size, world_height);
+        if let Some(heightmap) = self.heightmaps.lock().unwrap().by_column.get(&key) {
+            return heightmap.clone();
+        }
+
+        let heightmap = Arc::new(Heightmap::generate(noise, chunk_column, size, world_height));
+        let mut cached = self.heightmaps.lock().unwrap();
+        if cached.by_column.insert(key, heightmap.clone()).is_none() {
+            cached.order.push_back(key);
+        }
+        while cached.order.len() > MAX_CACHED_HEIGHTMAPS {
+            if let Some(oldest) = cached.order.pop_front() {
+                cached.by_column.remove(&oldest);
+            }
+        }
+        heightmap
+    }
+
+    pub fn len(&self) -> usize {
+        self.heightmaps.lock().unwrap().by_column.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        let mut cached = self.heightmaps.lock().unwrap();
+        cached.by_column.clear();
+        cached.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::I64Vec2;
+
+    use super::{Heightmap, HeightmapCache};
+    use crate::chunks::generate::{biome::column_surface, noise::NoiseGenerator};
+
+    const WORLD_HEIGHT: u64 = 256;
+
+    #[test]
+    fn test_heightmap_matches_column_surface() {
+        let mut noise = NoiseGenerator::new(3);
+        let heightmap = Heightmap::generate(&mut noise, I64Vec2::new(-1, 2), 16, WORLD_HEIGHT);
+
+        assert_eq!(
+            column_surface(&mut noise, I64Vec2::new(-16 + 5, 32 + 9), WORLD_HEIGHT),
+            heightmap.get(5, 9)
+        );
+    }
+
+    #[test]
+    fn test_cache_reuses_heightmaps() {
+        let cache = HeightmapCache::default();
+        let mut noise = NoiseGenerator::new(3);
+        let first = cache.get_or_generate(&mut noise, I64Vec2::ZERO, 16, WORLD_HEIGHT);
+        let second = cache.get_or_generate(&mut noise, I64Vec2::ZERO, 16, WORLD_HEIGHT);
+
+        assert!(std::sync::Arc::ptr_eq(&first, &second));
+        cache.get_or_generate(&mut noise, I64Vec2::ZERO, 32, WORLD_HEIGHT);
+        assert_eq!(2, cache.len());
+    }
+}
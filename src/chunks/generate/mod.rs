@@ -1,2 +1,10 @@
+pub mod biome;
+pub mod fingerprint;
 pub mod generator;
+pub mod heightmap;
 pub mod noise;
+pub mod ore;
+pub mod structure;
+pub mod terrain;
+pub mod visibility;
+pub mod vox;
@@ -1,11 +1,14 @@
-use std::cell::RefCell;
+use std::sync::Arc;
 
 use bevy::{math::I64Vec2, utils::HashMap};
-use noise::{
-    Cache, Clamp, Fbm, MultiFractal, NoiseFn, Perlin, ScalePoint, Seedable, Select, Turbulence,
+use noise::{Clamp, Fbm, MultiFractal, NoiseFn, Perlin, ScalePoint, Seedable, Select, Turbulence};
+
+use super::{
+    heightmap::{Heightmap, HeightmapCache},
+    ore::{OreSettings, OreVeins},
 };
 
-pub fn world_noise(seed: u32) -> impl NoiseFn<f64, 2> {
+pub fn world_noise(seed: u32) -> impl NoiseFn<f64, 2> + Send + Sync {
     let scale: f64 = 1.0 / 1024.0;
 
     let freq = 0.2;
@@ -31,39 +34,134 @@ pub fn world_noise(seed: u32) -> impl NoiseFn<f64, 2> {
         .set_bounds(0.2, 1.0)
         .set_falloff(0.1);
 
-    let generator = Clamp::new(ScalePoint::new(combined).set_scale(scale))
+    Clamp::new(ScalePoint::new(combined).set_scale(scale))
         .set_lower_bound(0.0)
-        .set_upper_bound(10.0);
+        .set_upper_bound(10.0)
+}
 
-    Cache::new(generator)
+/// A world's terrain and ore noise, built once from its seed and shared read-only between the
+/// main thread and every generation task, along with the column heightmaps sampled from it.
+#[derive(Clone)]
+pub struct SharedNoise {
+    seed: u32,
+    terrain: Arc<dyn NoiseFn<f64, 2> + Send + Sync>,
+    ores: Arc<OreVeins>,
+    heightmaps: Arc<HeightmapCache>,
 }
 
-pub struct NoiseGenerator {
-    cache: RefCell<HashMap<I64Vec2, f64>>,
-    source: Box<dyn NoiseFn<f64, 2>>,
+impl SharedNoise {
+    pub fn new(seed: u32) -> Self {
+        Self::with_ores(seed, OreSettings::default())
+    }
+
+    pub fn with_ores(seed: u32, ores: OreSettings) -> Self {
+        Self {
+            seed,
+            terrain: Arc::new(world_noise(seed)),
+            ores: Arc::new(OreVeins::new(seed, ores)),
+            heightmaps: Arc::new(HeightmapCache::default()),
+        }
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    pub fn get(&self, pos: I64Vec2) -> f64 {
+        self.terrain.get([pos.x as f64, pos.y as f64])
+    }
+
+    pub fn ores(&self) -> &OreVeins {
+        &self.ores
+    }
+
+    pub fn heightmaps(&self) -> &HeightmapCache {
+        &self.heightmaps
+    }
 }
 
-unsafe impl Send for NoiseGenerator {}
-unsafe impl Sync for NoiseGenerator {}
+/// Memoises lookups of a `SharedNoise`, which neighbouring columns repeat when computing
+/// gradients. Cheap to create, so each task or system makes its own rather than sharing one.
+pub struct NoiseGenerator {
+    cache: HashMap<I64Vec2, f64>,
+    source: SharedNoise,
+}
 
 impl NoiseGenerator {
     pub fn new(seed: u32) -> Self {
+        Self::from_shared(SharedNoise::new(seed))
+    }
+
+    pub fn from_shared(source: SharedNoise) -> Self {
         Self {
-            cache: RefCell::new(HashMap::new()),
-            source: Box::new(world_noise(seed)),
+            cache: HashMap::new(),
+            source,
         }
     }
-}
 
-impl NoiseGenerator {
+    pub fn ores(&self) -> &OreVeins {
+        self.source.ores()
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.source.seed()
+    }
+
+    /// The surface of every block column in `chunk_column`, shared with other tasks generating
+    /// chunks in the same column.
+    pub fn heightmap(
+        &mut self,
+        chunk_column: I64Vec2,
+        size: u16,
+        world_height: u64,
+    ) -> Arc<Heightmap> {
+        let heightmaps = self.source.heightmaps.clone();
+        heightmaps.get_or_generate(self, chunk_column, size, world_height)
+    }
+
     pub fn get(&mut self, pos: I64Vec2) -> f64 {
-        if self.cache.borrow().contains_key(&pos) {
-            return *self.cache.borrow().get(&pos).unwrap();
-        }
+        *self
+            .cache
+            .entry(pos)
+            .or_insert_with(|| self.source.get(pos))
+    }
+}
 
-        let value = self.source.get([pos.x as f64, pos.y as f64]);
-        self.cache.borrow_mut().insert(pos, value);
+#[cfg(test)]
+mod tests {
+    use std::thread;
 
-        value
+    use bevy::math::I64Vec2;
+
+    use super::{NoiseGenerator, SharedNoise};
+
+    #[test]
+    fn test_shared_noise_is_identical_across_threads() {
+        let noise = SharedNoise::new(42);
+        let columns: Vec<I64Vec2> = (0..64).map(|i| I64Vec2::new(i * 37, -i * 11)).collect();
+        let expected: Vec<f64> = columns.iter().map(|column| noise.get(*column)).collect();
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let mut generator = NoiseGenerator::from_shared(noise.clone());
+                let columns = columns.clone();
+                thread::spawn(move || {
+                    columns
+                        .iter()
+                        .map(|column| generator.get(*column))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(expected, handle.join().unwrap());
+        }
+        assert_eq!(expected, {
+            let mut fresh = NoiseGenerator::new(42);
+            columns
+                .iter()
+                .map(|column| fresh.get(*column))
+                .collect::<Vec<_>>()
+        });
     }
 }
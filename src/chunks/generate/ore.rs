@@ -0,0 +1,174 @@
+use bevy::math::I64Vec3;
+use noise::{NoiseFn, Perlin};
+use serde::Deserialize;
+
+use crate::block::BlockType;
+
+/// Ores are only placed this far below the surface, so they don't speckle exposed terrain.
+const MIN_DEPTH: u64 = 3;
+/// Blocks below an ore's ceiling over which it becomes as common as it gets.
+const DEPTH_RAMP: f64 = 48.0;
+
+/// An ore generated in veins below the surface, more often the deeper it is.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ore {
+    pub block: BlockType,
+    /// Highest world y the ore is found at.
+    pub max_y: i64,
+    /// Noise value veins must exceed at the ore's ceiling, closer to `1.0` is rarer.
+    threshold: f64,
+    /// How much the threshold drops at full depth.
+    depth_bonus: f64,
+    /// Frequency of the vein noise, higher gives smaller veins.
+    scale: f64,
+}
+
+pub const ORES: [Ore; 3] = [
+    Ore {
+        block: BlockType::Coal,
+        max_y: 128,
+        threshold: 0.58,
+        depth_bonus: 0.04,
+        scale: 0.16,
+    },
+    Ore {
+        block: BlockType::Iron,
+        max_y: 64,
+        threshold: 0.66,
+        depth_bonus: 0.08,
+        scale: 0.2,
+    },
+    Ore {
+        block: BlockType::Gold,
+        max_y: 32,
+        threshold: 0.72,
+        depth_bonus: 0.1,
+        scale: 0.24,
+    },
+];
+
+/// How common each ore is relative to the default, `0.0` disables an ore. Read from the
+/// `[world.ores]` table of the settings file.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct OreSettings {
+    pub coal: f32,
+    pub iron: f32,
+    pub gold: f32,
+}
+
+impl Default for OreSettings {
+    fn default() -> Self {
+        Self {
+            coal: 1.0,
+            iron: 1.0,
+            gold: 1.0,
+        }
+    }
+}
+
+impl OreSettings {
+    fn abundance(&self, block: BlockType) -> f32 {
+        match block {
+            BlockType::Coal => self.coal,
+            BlockType::Iron => self.iron,
+            BlockType::Gold => self.gold,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Decides where ore veins are, from 3D noise seeded per ore so veins of different ores don't
+/// line up.
+pub struct OreVeins {
+    veins: Vec<(Ore, Perlin, f64)>,
+}
+
+impl OreVeins {
+    pub fn new(seed: u32, settings: OreSettings) -> Self {
+        Self {
+            veins: ORES
+                .iter()
+                .enumerate()
+                .map(|(i, ore)| {
+                    let abundance = settings.abundance(ore.block).max(0.0) as f64;
+                    (
+                        *ore,
+                        Perlin::new(seed.wrapping_add(i as u32 + 1)),
+                        abundance,
+                    )
+                })
+                .filter(|(_, _, abundance)| *abundance > 0.0)
+                .collect(),
+        }
+    }
+
+    /// The ore at `position`, `depth` blocks below the surface of its column, if any. Earlier
+    /// ores in `ORES` win where veins overlap.
+    pub fn ore_at(&self, position: I64Vec3, depth: u64) -> Option<BlockType> {
+        if depth < MIN_DEPTH {
+            return None;
+        }
+
+        self.veins
+            .iter()
+            .filter(|(ore, _, _)| position.y <= ore.max_y)
+            .find(|(ore, noise, abundance)| {
+                let ramp = ((ore.max_y - position.y) as f64 / DEPTH_RAMP).min(1.0);
+                let threshold = ore.threshold - ore.depth_bonus * ramp;
+                // scaling the gap to 1.0 makes veins both more frequent and larger
+                let threshold = 1.0 - (1.0 - threshold) * abundance;
+                let point = position.as_dvec3() * ore.scale;
+                noise.get(point.to_array()) > threshold
+            })
+            .map(|(ore, _, _)| ore.block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::I64Vec3;
+
+    use super::{OreSettings, OreVeins, ORES};
+    use crate::block::BlockType;
+
+    fn count_ores(veins: &OreVeins, y: i64) -> [usize; 3] {
+        let mut counts = [0; 3];
+        for x in 0..64 {
+            for z in 0..64 {
+                if let Some(block) = veins.ore_at(I64Vec3::new(x, y, z), 100) {
+                    counts[ORES.iter().position(|ore| ore.block == block).unwrap()] += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn test_ores_are_deeper_and_rarer() {
+        let veins = OreVeins::new(7, OreSettings::default());
+
+        let [coal, iron, gold] = count_ores(&veins, 10);
+        assert!(coal > 0 && iron > 0 && gold > 0, "{coal} {iron} {gold}");
+        assert!(coal > gold);
+
+        // gold and iron stop at their ceilings
+        assert_eq!([0, 0], count_ores(&veins, 100)[1..]);
+        assert!(veins.ore_at(I64Vec3::new(0, 10, 0), 1).is_none());
+    }
+
+    #[test]
+    fn test_ore_settings_disable_ores() {
+        let settings = OreSettings {
+            coal: 0.0,
+            ..Default::default()
+        };
+        let veins = OreVeins::new(7, settings);
+
+        assert_eq!(0, count_ores(&veins, 10)[0]);
+        assert_ne!(
+            Some(BlockType::Coal),
+            veins.ore_at(I64Vec3::new(5, 10, 5), 100)
+        );
+    }
+}
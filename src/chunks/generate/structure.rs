@@ -0,0 +1,552 @@
+use std::{collections::HashMap, error::Error, fs, sync::OnceLock};
+
+use bevy::{
+    app::{App, Plugin},
+    ecs::system::{In, Res},
+    log::warn,
+    math::{I64Vec2, I64Vec3},
+};
+use serde::Deserialize;
+
+use super::{
+    biome::{column_surface, Biome},
+    noise::NoiseGenerator,
+};
+use crate::{
+    block::BlockType,
+    chunks::chunk::ChunkData,
+    command::{CommandArgs, CommandResult},
+    world::World,
+};
+
+pub const STRUCTURES_FILE: &str = "assets/structures.ron";
+/// The placement built into the game, used when `STRUCTURES_FILE` can't be read and by anything
+/// running without `StructurePlugin`, such as tests.
+const BUNDLED_PLACEMENTS: &str = include_str!("../../../assets/structures.ron");
+
+static PLACEMENTS: OnceLock<HashMap<StructureKind, StructurePlacement>> = OnceLock::new();
+
+/// Loads where structures are generated from `STRUCTURES_FILE`. Generation reads the placement
+/// from then on, so it must be added before any chunk is generated.
+pub struct StructurePlugin;
+
+impl Plugin for StructurePlugin {
+    fn build(&self, _app: &mut App) {
+        match fs::read_to_string(STRUCTURES_FILE)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|source| parse_placements(&source))
+        {
+            Ok(placements) => {
+                if PLACEMENTS.set(placements).is_err() {
+                    warn!(
+                        "structures were generated before {} was loaded",
+                        STRUCTURES_FILE
+                    );
+                }
+            }
+            Err(e) => warn!(
+                "failed to load {}, using built in structures: {}",
+                STRUCTURES_FILE, e
+            ),
+        }
+    }
+}
+
+/// Where a kind of structure is generated, read from `STRUCTURES_FILE`. The world is split into
+/// square cells `spacing` blocks wide, each holding at most one of the structure.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct StructurePlacement {
+    pub spacing: i64,
+    /// Chance out of 256 that a cell holds one, by the biome at the structure's centre. Biomes
+    /// left out never have one.
+    pub weights: HashMap<Biome, u16>,
+    /// Solid blocks kept between the structure's top and the surface.
+    pub cover: i64,
+    /// Lowest the structure's bottom is placed.
+    pub min_y: i64,
+}
+
+/// Reads a map from each kind of structure to its placement, which must cover every kind.
+pub fn parse_placements(
+    source: &str,
+) -> Result<HashMap<StructureKind, StructurePlacement>, Box<dyn Error>> {
+    let placements: HashMap<StructureKind, StructurePlacement> = ron::from_str(source)?;
+    for kind in STRUCTURES {
+        let Some(placement) = placements.get(&kind) else {
+            return Err(format!("no placement for {:?}", kind).into());
+        };
+        let size = kind.blueprint().size;
+        if placement.spacing < size.x.max(size.z) {
+            return Err(format!("{:?} is wider than its spacing", kind).into());
+        }
+        if let Some((biome, _)) = placement.weights.iter().find(|(_, weight)| **weight > 256) {
+            return Err(format!("{:?} in {:?} has a weight above 256", kind, biome).into());
+        }
+    }
+    Ok(placements)
+}
+
+fn placement(kind: StructureKind) -> &'static StructurePlacement {
+    let placements = PLACEMENTS.get_or_init(|| {
+        parse_placements(BUNDLED_PLACEMENTS).expect("built in structures are valid")
+    });
+    &placements[&kind]
+}
+
+/// A prefab stored voxel by voxel. Cells left as `None` keep whatever terrain is there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blueprint {
+    pub size: I64Vec3,
+    blocks: Vec<Option<BlockType>>,
+}
+
+impl Blueprint {
+    pub fn new(size: I64Vec3) -> Self {
+        Self {
+            size,
+            blocks: vec![None; size.element_product() as usize],
+        }
+    }
+
+    fn index(&self, local: I64Vec3) -> Option<usize> {
+        (local.cmpge(I64Vec3::ZERO).all() && local.cmplt(self.size).all())
+            .then(|| (local.x + self.size.x * (local.y + self.size.y * local.z)) as usize)
+    }
+
+    pub fn get(&self, local: I64Vec3) -> Option<BlockType> {
+        self.index(local).and_then(|index| self.blocks[index])
+    }
+
+    pub fn set(&mut self, local: I64Vec3, block: BlockType) {
+        let index = self.index(local).expect("block outside blueprint");
+        self.blocks[index] = Some(block);
+    }
+
+    /// Every cell that has a block, with its position in the blueprint.
+    pub fn blocks(&self) -> impl Iterator<Item = (I64Vec3, BlockType)> + '_ {
+        let size = self.size;
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, block)| {
+                let index = index as i64;
+                let local = I64Vec3::new(
+                    index % size.x,
+                    index / size.x % size.y,
+                    index / (size.x * size.y),
+                );
+                block.map(|block| (local, block))
+            })
+    }
+
+    /// Sets every cell in the box from `min` to `max` inclusive.
+    pub fn fill(&mut self, min: I64Vec3, max: I64Vec3, block: BlockType) {
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    self.set(I64Vec3::new(x, y, z), block);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize)]
+pub enum StructureKind {
+    Dungeon,
+}
+
+impl StructureKind {
+    pub fn blueprint(&self) -> &'static Blueprint {
+        static DUNGEON: OnceLock<Blueprint> = OnceLock::new();
+        match self {
+            Self::Dungeon => DUNGEON.get_or_init(dungeon_room),
+        }
+    }
+
+    /// Where in the blueprint the structure's mob spawner is, if it has one.
+    pub fn spawner(&self) -> Option<I64Vec3> {
+        match self {
+            Self::Dungeon => Some(DUNGEON_SPAWNER),
+        }
+    }
+
+    fn salt(&self) -> u64 {
+        match self {
+            Self::Dungeon => 0x64756e67,
+        }
+    }
+}
+
+const STRUCTURES: [StructureKind; 1] = [StructureKind::Dungeon];
+/// In the middle of the dungeon's floor.
+const DUNGEON_SPAWNER: I64Vec3 = I64Vec3::new(4, 1, 4);
+
+/// A stone room lit from the ceiling, with a floor of sand for contrast and a spawner in the
+/// middle.
+fn dungeon_room() -> Blueprint {
+    let size = I64Vec3::new(9, 6, 9);
+    let mut blueprint = Blueprint::new(size);
+    blueprint.fill(I64Vec3::ZERO, size - 1, BlockType::Stone);
+    blueprint.fill(I64Vec3::ONE, size - 2, BlockType::Air);
+    blueprint.fill(
+        I64Vec3::new(1, 0, 1),
+        I64Vec3::new(size.x - 2, 0, size.z - 2),
+        BlockType::Sand,
+    );
+    blueprint.set(
+        I64Vec3::new(size.x / 2, size.y - 1, size.z / 2),
+        BlockType::Glowstone,
+    );
+    blueprint.set(DUNGEON_SPAWNER, BlockType::Spawner);
+    blueprint
+}
+
+/// A structure placed in the world, its blueprint's minimum corner at `origin`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Structure {
+    pub kind: StructureKind,
+    pub origin: I64Vec3,
+}
+
+impl Structure {
+    pub fn max(&self) -> I64Vec3 {
+        self.origin + self.kind.blueprint().size - 1
+    }
+}
+
+/// Mixes the seed and a cell into a well spread random number, the same every time.
+fn cell_hash(seed: u32, cell: I64Vec2, salt: u64) -> u64 {
+    let mut x = (seed as u64) ^ salt;
+    for value in [cell.x as u64, cell.y as u64] {
+        // splitmix64
+        x = x.wrapping_add(value).wrapping_add(0x9e3779b97f4a7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+        x ^= x >> 31;
+    }
+    x
+}
+
+/// Why a cell does or doesn't hold a structure.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Decision {
+    Placed(Structure),
+    /// The cell's roll out of 256 wasn't below the weight of its biome.
+    Rolled {
+        roll: u64,
+        weight: u16,
+    },
+    /// The ground is too low to bury the structure under its cover.
+    TooShallow,
+}
+
+/// The decision made for a cell of the world about one kind of structure.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Placement {
+    pub kind: StructureKind,
+    pub cell: I64Vec2,
+    pub biome: Biome,
+    pub decision: Decision,
+}
+
+/// Decides whether `cell` holds a structure of `kind`. Structures lie entirely within their cell
+/// so a chunk only needs to look at the cells it overlaps.
+fn place_in_cell(
+    kind: StructureKind,
+    cell: I64Vec2,
+    seed: u32,
+    noise: &mut NoiseGenerator,
+    world_height: u64,
+) -> Placement {
+    let placement = placement(kind);
+    let hash = cell_hash(seed, cell, kind.salt());
+    let size = kind.blueprint().size;
+    let spacing = placement.spacing;
+    let x = cell.x * spacing + ((hash >> 8) % (spacing - size.x + 1) as u64) as i64;
+    let z = cell.y * spacing + ((hash >> 24) % (spacing - size.z + 1) as u64) as i64;
+
+    let centre = I64Vec2::new(x + size.x / 2, z + size.z / 2);
+    let surface = column_surface(noise, centre, world_height);
+    let biome = Biome::from_surface(surface);
+    let roll = hash & 0xff;
+    let weight = placement.weights.get(&biome).copied().unwrap_or(0);
+    let max_y = surface.height as i64 - placement.cover - size.y;
+    let decision = if roll >= weight as u64 {
+        Decision::Rolled { roll, weight }
+    } else if max_y < placement.min_y {
+        Decision::TooShallow
+    } else {
+        let y = placement.min_y + ((hash >> 40) % (max_y - placement.min_y + 1) as u64) as i64;
+        Decision::Placed(Structure {
+            kind,
+            origin: I64Vec3::new(x, y, z),
+        })
+    };
+    Placement {
+        kind,
+        cell,
+        biome,
+        decision,
+    }
+}
+
+/// The decision for every cell overlapping the columns from `min` to `max` inclusive.
+pub fn placements_in(
+    min: I64Vec2,
+    max: I64Vec2,
+    seed: u32,
+    noise: &mut NoiseGenerator,
+    world_height: u64,
+) -> Vec<Placement> {
+    let mut placements = vec![];
+    for kind in STRUCTURES {
+        let spacing = placement(kind).spacing;
+        for cell_x in min.x.div_euclid(spacing)..=max.x.div_euclid(spacing) {
+            for cell_z in min.y.div_euclid(spacing)..=max.y.div_euclid(spacing) {
+                let cell = I64Vec2::new(cell_x, cell_z);
+                placements.push(place_in_cell(kind, cell, seed, noise, world_height));
+            }
+        }
+    }
+    placements
+}
+
+/// Every structure overlapping the box from `min` to `max` inclusive.
+pub fn structures_in(
+    min: I64Vec3,
+    max: I64Vec3,
+    seed: u32,
+    noise: &mut NoiseGenerator,
+    world_height: u64,
+) -> Vec<Structure> {
+    placements_in(
+        I64Vec2::new(min.x, min.z),
+        I64Vec2::new(max.x, max.z),
+        seed,
+        noise,
+        world_height,
+    )
+    .into_iter()
+    .filter_map(|placement| match placement.decision {
+        Decision::Placed(structure) => Some(structure),
+        _ => None,
+    })
+    .filter(|structure| structure.origin.cmple(max).all() && structure.max().cmpge(min).all())
+    .collect()
+}
+
+/// Where the spawners of the structures overlapping the box from `min` to `max` inclusive are
+/// generated. They may since have been broken.
+pub fn spawners_in(
+    min: I64Vec3,
+    max: I64Vec3,
+    seed: u32,
+    noise: &mut NoiseGenerator,
+    world_height: u64,
+) -> Vec<I64Vec3> {
+    structures_in(min, max, seed, noise, world_height)
+        .into_iter()
+        .filter_map(|structure| Some(structure.origin + structure.kind.spawner()?))
+        .filter(|spawner| spawner.cmpge(min).all() && spawner.cmple(max).all())
+        .collect()
+}
+
+/// `/structures [radius]` lists the structure placement decisions for the cells within `radius`
+/// blocks of the player, 64 by default.
+pub fn structures_command(In(mut args): In<CommandArgs>, world: Res<World>) -> CommandResult {
+    let radius: i64 = match args.optional_word() {
+        Some(word) => word
+            .parse()
+            .map_err(|_| format!("'{word}' is not a radius in blocks"))?,
+        None => 64,
+    };
+    let origin = args
+        .origin()
+        .ok_or_else(|| "there is no player to look around".to_string())?
+        .translation
+        .round()
+        .as_i64vec3();
+    args.finish()?;
+
+    let centre = I64Vec2::new(origin.x, origin.z);
+    let mut noise = NoiseGenerator::from_shared(world.noise());
+    let placements = placements_in(
+        centre - radius,
+        centre + radius,
+        world.seed(),
+        &mut noise,
+        world.height,
+    );
+    let lines: Vec<String> = placements
+        .iter()
+        .map(|placement| {
+            let decision = match placement.decision {
+                Decision::Placed(structure) => format!("placed at {}", structure.origin),
+                Decision::Rolled { roll, weight } => {
+                    format!("not placed, rolled {roll} against weight {weight}")
+                }
+                Decision::TooShallow => "not placed, ground too low".to_string(),
+            };
+            format!(
+                "{:?} in cell {}, {} ({:?}): {}",
+                placement.kind, placement.cell.x, placement.cell.y, placement.biome, decision
+            )
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+/// Stamps the parts of any structures overlapping the chunk at `chunk_origin` into it.
+pub fn place_structures(
+    chunk_data: &mut ChunkData,
+    chunk_origin: I64Vec3,
+    seed: u32,
+    noise: &mut NoiseGenerator,
+    world_height: u64,
+) {
+    let chunk_max = chunk_origin + chunk_data.size as i64 - 1;
+    for structure in structures_in(chunk_origin, chunk_max, seed, noise, world_height) {
+        let blueprint = structure.kind.blueprint();
+        let min = structure.origin.max(chunk_origin);
+        let max = structure.max().min(chunk_max);
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let position = I64Vec3::new(x, y, z);
+                    if let Some(block) = blueprint.get(position - structure.origin) {
+                        let local = position - chunk_origin;
+                        chunk_data.set_block_at(local.as_u16vec3(), block);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::I64Vec3;
+
+    use bevy::math::I64Vec2;
+
+    use super::{
+        parse_placements, placements_in, structures_in, Decision, StructureKind,
+        BUNDLED_PLACEMENTS, STRUCTURES_FILE,
+    };
+    use crate::{
+        block::BlockType,
+        chunks::{
+            chunk::ChunkCoordinate,
+            generate::{
+                generator::generate_chunk,
+                noise::{NoiseGenerator, SharedNoise},
+            },
+        },
+        world::World,
+    };
+
+    const SEED: u32 = 5;
+    const WORLD_HEIGHT: u64 = 256;
+
+    #[test]
+    fn test_dungeon_blueprint_is_hollow() {
+        let blueprint = StructureKind::Dungeon.blueprint();
+        assert_eq!(Some(BlockType::Stone), blueprint.get(I64Vec3::ZERO));
+        assert_eq!(Some(BlockType::Air), blueprint.get(I64Vec3::new(2, 2, 2)));
+        assert_eq!(None, blueprint.get(blueprint.size));
+        let spawner = StructureKind::Dungeon.spawner().unwrap();
+        assert_eq!(Some(BlockType::Spawner), blueprint.get(spawner));
+        assert_eq!(Some(BlockType::Sand), blueprint.get(spawner - I64Vec3::Y));
+    }
+
+    #[test]
+    fn test_bundled_placements() {
+        let placements = parse_placements(BUNDLED_PLACEMENTS).unwrap();
+        assert_eq!(48, placements[&StructureKind::Dungeon].spacing);
+        let source = std::fs::read_to_string(STRUCTURES_FILE).unwrap();
+        assert_eq!(placements, parse_placements(&source).unwrap());
+
+        assert!(parse_placements("{}").is_err());
+        let narrow = BUNDLED_PLACEMENTS.replace("spacing: 48", "spacing: 4");
+        assert!(parse_placements(&narrow).is_err());
+        let certain = BUNDLED_PLACEMENTS.replace("Ocean: 96", "Ocean: 300");
+        assert!(parse_placements(&certain).is_err());
+    }
+
+    #[test]
+    fn test_placements_explain_every_cell() {
+        let mut generator = NoiseGenerator::from_shared(SharedNoise::new(SEED));
+        let placements = placements_in(
+            I64Vec2::splat(-480),
+            I64Vec2::splat(479),
+            SEED,
+            &mut generator,
+            WORLD_HEIGHT,
+        );
+        // 20 cells of 48 blocks across each way
+        assert_eq!(400, placements.len());
+        for placement in &placements {
+            if let Decision::Rolled { roll, weight } = placement.decision {
+                assert!(roll >= weight as u64);
+            }
+        }
+        let placed = placements
+            .iter()
+            .filter(|placement| matches!(placement.decision, Decision::Placed(_)))
+            .count();
+        assert!(placed > 0 && placed < placements.len());
+    }
+
+    #[test]
+    fn test_structures_are_placed_across_chunks() {
+        let noise = SharedNoise::new(SEED);
+        let mut generator = NoiseGenerator::from_shared(noise.clone());
+        let structures = structures_in(
+            I64Vec3::new(-512, 0, -512),
+            I64Vec3::new(512, 256, 512),
+            SEED,
+            &mut generator,
+            WORLD_HEIGHT,
+        );
+        assert!(!structures.is_empty());
+        assert_eq!(
+            structures,
+            structures_in(
+                I64Vec3::new(-512, 0, -512),
+                I64Vec3::new(512, 256, 512),
+                SEED,
+                &mut generator,
+                WORLD_HEIGHT,
+            )
+        );
+
+        // every block of the first dungeon is in the world, whichever chunk it fell in
+        let structure = structures[0];
+        let mut world = World::new(SEED);
+        let (min_chunk, _) = world.split_block_coordinate(structure.origin);
+        let (max_chunk, _) = world.split_block_coordinate(structure.max());
+        for x in min_chunk.0.x..=max_chunk.0.x {
+            for y in min_chunk.0.y..=max_chunk.0.y {
+                for z in min_chunk.0.z..=max_chunk.0.z {
+                    let coord = ChunkCoordinate(I64Vec3::new(x, y, z));
+                    let chunk = generate_chunk(noise.clone(), coord, WORLD_HEIGHT, 16);
+                    world.insert_chunk(coord, chunk);
+                }
+            }
+        }
+
+        let blueprint = structure.kind.blueprint();
+        for x in 0..blueprint.size.x {
+            for y in 0..blueprint.size.y {
+                for z in 0..blueprint.size.z {
+                    let local = I64Vec3::new(x, y, z);
+                    assert_eq!(
+                        blueprint.get(local).unwrap(),
+                        world.get_block(structure.origin + local)
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,435 @@
+//! The generators a world can be made with, chosen when it's created and kept in its save.
+
+use std::sync::Arc;
+
+use bevy::{
+    ecs::system::Resource,
+    math::{I64Vec2, U16Vec3},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    biome::ColumnSurface,
+    generator::generate_chunk,
+    noise::{NoiseGenerator, SharedNoise},
+};
+use crate::{
+    block::BlockType,
+    chunks::chunk::{ChunkCoordinate, ChunkData},
+};
+
+/// How many times taller amplified terrain is than the default.
+const AMPLIFIED_SCALE: u64 = 2;
+/// Surface of superflat worlds, the grass being one below it. High enough to count as plains.
+const SUPERFLAT_HEIGHT: i64 = 40;
+/// Surface of the platform void worlds are started on.
+const VOID_PLATFORM_HEIGHT: i64 = 64;
+/// Blocks the void platform reaches from the origin in each direction.
+const VOID_PLATFORM_RADIUS: i64 = 2;
+
+/// Makes the blocks of a world's chunks. Shared between every generation task.
+pub trait ChunkGenerator: Send + Sync {
+    fn generate(&self, coord: ChunkCoordinate) -> ChunkData;
+
+    /// The terrain's surface at a block column, `None` where there is no ground.
+    fn surface(&self, column: I64Vec2) -> Option<ColumnSurface>;
+}
+
+/// The generator a world is made with. Only the kind is saved, by name, the generator itself is
+/// rebuilt from it and the world's seed.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum GeneratorKind {
+    /// Continents, mountains and oceans from the seed's noise.
+    #[default]
+    Noise,
+    /// Noise terrain stretched to twice the height.
+    Amplified,
+    /// Grass over stone at a single height, for building.
+    Superflat,
+    /// Nothing but a small platform to start on.
+    Void,
+    /// Added by a mod to the `GeneratorRegistry`, under this name.
+    Mod(String),
+}
+
+pub const GENERATOR_KINDS: [GeneratorKind; 4] = [
+    GeneratorKind::Noise,
+    GeneratorKind::Amplified,
+    GeneratorKind::Superflat,
+    GeneratorKind::Void,
+];
+
+impl GeneratorKind {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Noise => "noise",
+            Self::Amplified => "amplified",
+            Self::Superflat => "superflat",
+            Self::Void => "void",
+            Self::Mod(name) => name,
+        }
+    }
+
+    /// The built in kind called `name`, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        GENERATOR_KINDS
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Builds the generator, looking mod generators up in `registry`. One that isn't registered,
+    /// such as in a world made with a mod that has since been removed, is an error rather than
+    /// different terrain next to the world's saved chunks.
+    pub fn build(
+        &self,
+        noise: SharedNoise,
+        world_height: u64,
+        chunk_size: u16,
+        registry: &GeneratorRegistry,
+    ) -> Result<Arc<dyn ChunkGenerator>, String> {
+        if let Some(generator) = self.build_builtin(noise.clone(), world_height, chunk_size) {
+            return Ok(generator);
+        }
+        let factory = registry
+            .get(self.name())
+            .ok_or_else(|| format!("no loaded mod adds the {} generator", self.name()))?;
+        Ok(factory(noise, world_height, chunk_size))
+    }
+
+    /// Builds a built in generator, or `None` for one added by a mod, which needs the
+    /// `GeneratorRegistry`.
+    pub fn build_builtin(
+        &self,
+        noise: SharedNoise,
+        world_height: u64,
+        chunk_size: u16,
+    ) -> Option<Arc<dyn ChunkGenerator>> {
+        Some(match self {
+            Self::Mod(_) => return None,
+            Self::Noise => Arc::new(NoiseTerrain {
+                noise,
+                terrain_height: world_height,
+                chunk_size,
+            }),
+            Self::Amplified => Arc::new(NoiseTerrain {
+                noise,
+                terrain_height: world_height * AMPLIFIED_SCALE,
+                chunk_size,
+            }),
+            Self::Superflat => Arc::new(Superflat { chunk_size }),
+            Self::Void => Arc::new(Void { chunk_size }),
+        })
+    }
+}
+
+impl From<String> for GeneratorKind {
+    fn from(name: String) -> Self {
+        Self::from_name(&name).unwrap_or(Self::Mod(name))
+    }
+}
+
+impl From<GeneratorKind> for String {
+    fn from(kind: GeneratorKind) -> Self {
+        kind.name().to_string()
+    }
+}
+
+/// Builds a mod's generator for a world from the world's noise, height and chunk size, as
+/// `GeneratorKind::build` does the built in ones.
+pub type GeneratorFactory = dyn Fn(SharedNoise, u64, u16) -> Arc<dyn ChunkGenerator> + Send + Sync;
+
+/// Generators added by mods, which worlds refer to by name with `GeneratorKind::Mod`.
+#[derive(Resource, Default, Clone)]
+pub struct GeneratorRegistry {
+    generators: Vec<(String, Arc<GeneratorFactory>)>,
+}
+
+impl GeneratorRegistry {
+    /// Adds a generator, replacing any added before with the same name. The built in generators'
+    /// names are taken.
+    pub fn register(
+        &mut self,
+        name: &str,
+        factory: impl Fn(SharedNoise, u64, u16) -> Arc<dyn ChunkGenerator> + Send + Sync + 'static,
+    ) -> Result<(), String> {
+        if GeneratorKind::from_name(name).is_some() {
+            return Err(format!("{} is a built in generator", name));
+        }
+        let factory: Arc<GeneratorFactory> = Arc::new(factory);
+        match self.generators.iter_mut().find(|(added, _)| added == name) {
+            Some((_, added)) => *added = factory,
+            None => self.generators.push((name.to_string(), factory)),
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<GeneratorFactory>> {
+        self.generators
+            .iter()
+            .find(|(added, _)| added == name)
+            .map(|(_, factory)| factory.clone())
+    }
+
+    /// Every kind a world can be created with, the built in ones first.
+    pub fn kinds(&self) -> impl Iterator<Item = GeneratorKind> + '_ {
+        GENERATOR_KINDS.into_iter().chain(
+            self.generators
+                .iter()
+                .map(|(name, _)| GeneratorKind::Mod(name.clone())),
+        )
+    }
+
+    /// The kind after `kind`, wrapping around, for cycling through them in menus.
+    pub fn next(&self, kind: &GeneratorKind) -> GeneratorKind {
+        let kinds: Vec<GeneratorKind> = self.kinds().collect();
+        let index = kinds.iter().position(|k| k == kind).unwrap_or(0);
+        kinds[(index + 1) % kinds.len()].clone()
+    }
+}
+
+/// Terrain shaped by the seed's noise, see `generate_chunk`.
+pub struct NoiseTerrain {
+    noise: SharedNoise,
+    /// Height the noise is scaled to, the tallest the terrain can be.
+    terrain_height: u64,
+    chunk_size: u16,
+}
+
+impl ChunkGenerator for NoiseTerrain {
+    fn generate(&self, coord: ChunkCoordinate) -> ChunkData {
+        generate_chunk(
+            self.noise.clone(),
+            coord,
+            self.terrain_height,
+            self.chunk_size,
+        )
+    }
+
+    /// Read from the heightmap chunks are generated with, usually cached already.
+    fn surface(&self, column: I64Vec2) -> Option<ColumnSurface> {
+        let size = I64Vec2::splat(self.chunk_size as i64);
+        let local = column.rem_euclid(size);
+        let heightmap = NoiseGenerator::from_shared(self.noise.clone()).heightmap(
+            column.div_euclid(size),
+            self.chunk_size,
+            self.terrain_height,
+        );
+        Some(heightmap.get(local.x as u16, local.y as u16))
+    }
+}
+
+/// Fills every block of `chunk` whose world height and column `block_at` gives a block for.
+fn fill_chunk(
+    coord: ChunkCoordinate,
+    chunk_size: u16,
+    block_at: impl Fn(i64, I64Vec2) -> Option<BlockType>,
+) -> ChunkData {
+    let mut chunk = ChunkData::with_size(chunk_size);
+    let origin = coord.0 * chunk_size as i64;
+    for y in 0..chunk_size {
+        for z in 0..chunk_size {
+            for x in 0..chunk_size {
+                let column = I64Vec2::new(origin.x + x as i64, origin.z + z as i64);
+                if let Some(block) = block_at(origin.y + y as i64, column) {
+                    chunk.set_block_at(U16Vec3::new(x, y, z), block);
+                }
+            }
+        }
+    }
+    chunk
+}
+
+pub struct Superflat {
+    chunk_size: u16,
+}
+
+impl ChunkGenerator for Superflat {
+    fn generate(&self, coord: ChunkCoordinate) -> ChunkData {
+        fill_chunk(coord, self.chunk_size, |y, _| match y {
+            y if y == SUPERFLAT_HEIGHT - 1 => Some(BlockType::Grass),
+            y if y < SUPERFLAT_HEIGHT - 1 => Some(BlockType::Stone),
+            _ => None,
+        })
+    }
+
+    fn surface(&self, _column: I64Vec2) -> Option<ColumnSurface> {
+        Some(ColumnSurface {
+            height: SUPERFLAT_HEIGHT as u64,
+            gradient: 0.0,
+        })
+    }
+}
+
+pub struct Void {
+    chunk_size: u16,
+}
+
+impl Void {
+    fn on_platform(column: I64Vec2) -> bool {
+        column.abs().max_element() <= VOID_PLATFORM_RADIUS
+    }
+}
+
+impl ChunkGenerator for Void {
+    fn generate(&self, coord: ChunkCoordinate) -> ChunkData {
+        fill_chunk(coord, self.chunk_size, |y, column| {
+            (y == VOID_PLATFORM_HEIGHT - 1 && Self::on_platform(column)).then_some(BlockType::Stone)
+        })
+    }
+
+    fn surface(&self, column: I64Vec2) -> Option<ColumnSurface> {
+        Self::on_platform(column).then_some(ColumnSurface {
+            height: VOID_PLATFORM_HEIGHT as u64,
+            gradient: 0.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec2, I64Vec3};
+
+    use std::sync::Arc;
+
+    use super::{GeneratorKind, GeneratorRegistry, Superflat, GENERATOR_KINDS};
+    use crate::{
+        block::BlockType,
+        chunks::{chunk::ChunkCoordinate, generate::noise::SharedNoise, position::BlockPos},
+    };
+
+    const WORLD_HEIGHT: u64 = 256;
+
+    #[test]
+    fn test_generator_names() {
+        for kind in GENERATOR_KINDS {
+            assert_eq!(Some(&kind), GeneratorKind::from_name(kind.name()).as_ref());
+        }
+        assert_eq!(Some(GeneratorKind::Void), GeneratorKind::from_name("VOID"));
+        assert_eq!(None, GeneratorKind::from_name("islands"));
+        assert_eq!(
+            GeneratorKind::Mod("islands".to_string()),
+            GeneratorKind::from("islands".to_string())
+        );
+    }
+
+    #[test]
+    fn test_generator_next_cycles_every_kind() {
+        let mut registry = GeneratorRegistry::default();
+        registry
+            .register("islands", |_, _, chunk_size| {
+                Arc::new(Superflat { chunk_size })
+            })
+            .unwrap();
+        assert!(registry
+            .register("Void", |_, _, chunk_size| Arc::new(Superflat {
+                chunk_size
+            }))
+            .is_err());
+
+        let kinds: Vec<GeneratorKind> = registry.kinds().collect();
+        let mut kind = GeneratorKind::default();
+        for expected in kinds.iter().cycle().skip(1).take(kinds.len()) {
+            kind = registry.next(&kind);
+            assert_eq!(expected, &kind);
+        }
+        assert_eq!(GeneratorKind::default(), kind);
+        assert_eq!(
+            Some(GeneratorKind::Mod("islands".to_string())),
+            registry.kinds().last()
+        );
+    }
+
+    #[test]
+    fn test_mod_generators_are_built_for_the_world() {
+        let mut registry = GeneratorRegistry::default();
+        let islands = GeneratorKind::Mod("islands".to_string());
+        assert!(islands
+            .build(SharedNoise::new(3), WORLD_HEIGHT, 16, &registry)
+            .is_err());
+
+        registry
+            .register("islands", |_, _, chunk_size| {
+                Arc::new(Superflat { chunk_size })
+            })
+            .unwrap();
+        let generator = islands
+            .build(SharedNoise::new(3), WORLD_HEIGHT, 32, &registry)
+            .unwrap();
+        let chunk = generator.generate(ChunkCoordinate(I64Vec3::ZERO));
+        assert_eq!(32, chunk.size);
+    }
+
+    #[test]
+    fn test_generated_surface_matches_chunks() {
+        for kind in GENERATOR_KINDS {
+            let generator = kind
+                .build(
+                    SharedNoise::new(3),
+                    WORLD_HEIGHT,
+                    16,
+                    &GeneratorRegistry::default(),
+                )
+                .unwrap();
+            for column in [I64Vec2::ZERO, I64Vec2::new(-20, 37)] {
+                let Some(surface) = generator.surface(column) else {
+                    assert_eq!(GeneratorKind::Void, kind);
+                    continue;
+                };
+                // the top block is one below the surface
+                let top = I64Vec3::new(column.x, surface.height as i64 - 1, column.y);
+                let (coord, local) = BlockPos(top).split(16);
+                let chunk = generator.generate(coord);
+                assert!(chunk.get_block_at(local.get()).is_solid(), "{:?}", kind);
+            }
+        }
+    }
+
+    #[test]
+    fn test_amplified_terrain_is_taller() {
+        let noise = SharedNoise::new(5);
+        let registry = GeneratorRegistry::default();
+        let noise_terrain = GeneratorKind::Noise
+            .build(noise.clone(), WORLD_HEIGHT, 16, &registry)
+            .unwrap();
+        let amplified = GeneratorKind::Amplified
+            .build(noise, WORLD_HEIGHT, 16, &registry)
+            .unwrap();
+        // the first column on land, as the sea floor can be at the bottom of the world
+        let (column, height) = (0..1000)
+            .map(|x| I64Vec2::new(x * 16, 100))
+            .map(|column| (column, noise_terrain.surface(column).unwrap().height))
+            .find(|(_, height)| *height > 0)
+            .expect("some land near the origin");
+        let amplified_height = amplified.surface(column).unwrap().height;
+        assert!(amplified_height.abs_diff(height * 2) <= 1);
+    }
+
+    #[test]
+    fn test_void_is_empty_beyond_platform() {
+        let void = GeneratorKind::Void
+            .build(
+                SharedNoise::new(0),
+                WORLD_HEIGHT,
+                16,
+                &GeneratorRegistry::default(),
+            )
+            .unwrap();
+        // the platform is 5x5 blocks around the origin, across the corners of four chunks
+        let platform: usize = [(0, 0), (-1, 0), (0, -1), (-1, -1)]
+            .into_iter()
+            .map(|(x, z)| {
+                let chunk = void.generate(ChunkCoordinate(I64Vec3::new(x, 3, z)));
+                assert!(chunk.blocks().all(|(_, block)| block == BlockType::Stone));
+                chunk.block_count()
+            })
+            .sum();
+        assert_eq!(25, platform);
+        assert_eq!(
+            0,
+            void.generate(ChunkCoordinate(I64Vec3::new(0, 0, 0)))
+                .block_count()
+        );
+        assert!(void.surface(I64Vec2::new(3, 0)).is_none());
+    }
+}
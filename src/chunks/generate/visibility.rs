@@ -0,0 +1,314 @@
+use std::sync::Arc;
+
+use bevy::math::U16Vec3;
+
+use crate::{
+    block::{BlockShape, BlockType},
+    chunks::chunk::ChunkData,
+};
+
+/// Widest chunk the masks can represent, one bit per block along x.
+pub const MAX_MASK_SIZE: u16 = u32::BITS as u16;
+pub const FACE_COUNT: usize = 6;
+
+/// Which faces of each block in a chunk are visible, in the mesher's face order: front (-z),
+/// right (+x), left (-x), back (+z), top (+y), bottom (-y). Each face has one mask per row of
+/// blocks along x, indexed by `y * size + z`, with bit `x` set if that block's face is visible.
+pub struct FaceMasks {
+    size: usize,
+    rows: [Vec<u32>; FACE_COUNT],
+}
+
+impl FaceMasks {
+    pub fn is_visible(&self, face: usize, position: U16Vec3) -> bool {
+        let row = self.rows[face][position.y as usize * self.size + position.z as usize];
+        (row >> position.x) & 1 == 1
+    }
+}
+
+/// Every block that `BlockType::is_transparent`, each given its own mask in `Row`.
+const TRANSPARENT_BLOCKS: [BlockType; 4] = [
+    BlockType::Water,
+    BlockType::Glass,
+    BlockType::Leaves,
+    BlockType::Spawner,
+];
+
+/// Occupancy of a row of blocks along x.
+#[derive(Debug, Default, Copy, Clone)]
+struct Row {
+    filled: u32,
+    /// Blocks of each of `TRANSPARENT_BLOCKS`.
+    transparent: [u32; TRANSPARENT_BLOCKS.len()],
+}
+
+impl Row {
+    fn set(&mut self, x: u16, block: BlockType) {
+        // other shapes don't fill their cell and are meshed whole
+        if block != BlockType::Air && block.shape() == BlockShape::Cube {
+            self.filled |= 1 << x;
+        }
+        if let Some(i) = TRANSPARENT_BLOCKS.iter().position(|t| *t == block) {
+            self.transparent[i] |= 1 << x;
+        }
+    }
+
+    fn map(self, f: impl Fn(u32) -> u32) -> Self {
+        Self {
+            filled: f(self.filled),
+            transparent: self.transparent.map(f),
+        }
+    }
+
+    fn shift_down(self, n: u32) -> Self {
+        self.map(|mask| mask >> n)
+    }
+
+    fn shift_up(self, n: u32) -> Self {
+        self.map(|mask| mask << n)
+    }
+
+    fn or(self, other: Self) -> Self {
+        Self {
+            filled: self.filled | other.filled,
+            transparent: std::array::from_fn(|i| self.transparent[i] | other.transparent[i]),
+        }
+    }
+
+    /// Blocks whose face towards `neighbour` is visible, where bit `x` of `neighbour` is the
+    /// block that face of block `x` touches. Faces are hidden by anything but air, except
+    /// transparent blocks which only hide the faces of the same block.
+    fn visible_against(self, neighbour: Self) -> u32 {
+        let (see_through, same) = self
+            .transparent
+            .iter()
+            .zip(neighbour.transparent)
+            .fold((0, 0), |(see_through, same), (own, theirs)| {
+                (see_through | theirs, same | (own & theirs))
+            });
+        self.filled & (!neighbour.filled | (see_through & !same))
+    }
+}
+
+/// Computes face visibility a whole row of blocks at a time, so culling is a handful of bitwise
+/// operations per row rather than six neighbour lookups per block.
+///
+/// `adjacent` is ordered as `ChunkCoordinate::adjacent`. Missing neighbours are treated as air.
+pub fn face_masks(chunk: &ChunkData, adjacent: &[Option<Arc<ChunkData>>]) -> FaceMasks {
+    let size = chunk.size;
+    assert!(
+        size <= MAX_MASK_SIZE,
+        "chunk size {size} is too large for face masks"
+    );
+    let n = size as usize;
+
+    let mut rows = vec![Row::default(); n * n];
+    for (position, block) in chunk.blocks() {
+        rows[position.y as usize * n + position.z as usize].set(position.x, block);
+    }
+
+    let adjacent = |i: usize| adjacent[i].as_deref();
+    let back = neighbour_rows(adjacent(0), size, |y| U16Vec3::new(0, y, 0));
+    let front = neighbour_rows(adjacent(1), size, |y| U16Vec3::new(0, y, size - 1));
+    let right = neighbour_column(adjacent(2), size, 0);
+    let left = neighbour_column(adjacent(3), size, size - 1);
+    let top = neighbour_rows(adjacent(4), size, |z| U16Vec3::new(0, 0, z));
+    let bottom = neighbour_rows(adjacent(5), size, |z| U16Vec3::new(0, size - 1, z));
+
+    let mut masks = FaceMasks {
+        size: n,
+        rows: std::array::from_fn(|_| vec![0; n * n]),
+    };
+    for y in 0..n {
+        for z in 0..n {
+            let i = y * n + z;
+            let row = rows[i];
+            if row.filled == 0 {
+                continue;
+            }
+
+            let neighbours = [
+                if z > 0 { rows[i - 1] } else { front[y] },
+                row.shift_down(1).or(right[i].shift_up(size as u32 - 1)),
+                row.shift_up(1).or(left[i]),
+                if z < n - 1 { rows[i + 1] } else { back[y] },
+                if y < n - 1 { rows[i + n] } else { top[z] },
+                if y > 0 { rows[i - n] } else { bottom[z] },
+            ];
+            for (face, neighbour) in neighbours.into_iter().enumerate() {
+                masks.rows[face][i] = row.visible_against(neighbour);
+            }
+        }
+    }
+    masks
+}
+
+/// Rows along x of a neighbouring chunk, one per value passed to `row_start`.
+fn neighbour_rows(
+    chunk: Option<&ChunkData>,
+    size: u16,
+    row_start: impl Fn(u16) -> U16Vec3,
+) -> Vec<Row> {
+    (0..size)
+        .map(|i| {
+            let mut row = Row::default();
+            if let Some(chunk) = chunk {
+                let start = row_start(i);
+                for x in 0..size {
+                    row.set(x, chunk.get_block_at(start + U16Vec3::new(x, 0, 0)));
+                }
+            }
+            row
+        })
+        .collect()
+}
+
+/// The blocks of a neighbouring chunk at a fixed `x`, one per row in bit 0.
+fn neighbour_column(chunk: Option<&ChunkData>, size: u16, x: u16) -> Vec<Row> {
+    let mut column = vec![Row::default(); size as usize * size as usize];
+    if let Some(chunk) = chunk {
+        for y in 0..size {
+            for z in 0..size {
+                column[y as usize * size as usize + z as usize]
+                    .set(0, chunk.get_block_at(U16Vec3::new(x, y, z)));
+            }
+        }
+    }
+    column
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bevy::math::{IVec3, U16Vec3};
+
+    use super::{face_masks, FACE_COUNT, TRANSPARENT_BLOCKS};
+    use crate::{
+        block::{BlockShape, BlockType, ALL_BLOCKS},
+        chunks::chunk::ChunkData,
+    };
+
+    /// Looks up each neighbour individually, as the mesher did before face masks.
+    fn visible_faces_scalar(
+        chunk: &ChunkData,
+        adjacent: &[Option<Arc<ChunkData>>],
+        position: U16Vec3,
+    ) -> [bool; FACE_COUNT] {
+        let block = chunk.get_block_at(position);
+        if block.shape() != BlockShape::Cube {
+            return [false; FACE_COUNT];
+        }
+        let size = chunk.size as i32;
+        let offsets = [
+            (1, [0, 0, -1]),
+            (2, [1, 0, 0]),
+            (3, [-1, 0, 0]),
+            (0, [0, 0, 1]),
+            (4, [0, 1, 0]),
+            (5, [0, -1, 0]),
+        ];
+
+        offsets.map(|(adjacent_index, offset)| {
+            let neighbour = position.as_ivec3() + IVec3::from_array(offset);
+            let neighbour_block = if neighbour.cmpge(IVec3::ZERO).all()
+                && neighbour.cmplt(IVec3::splat(size)).all()
+            {
+                chunk.get_block_at(neighbour.as_u16vec3())
+            } else {
+                adjacent[adjacent_index]
+                    .as_ref()
+                    .map(|adjacent| {
+                        adjacent.get_block_at(neighbour.rem_euclid(IVec3::splat(size)).as_u16vec3())
+                    })
+                    .unwrap_or_default()
+            };
+            match neighbour_block {
+                BlockType::Air => true,
+                neighbour if neighbour.shape() != BlockShape::Cube => true,
+                neighbour if neighbour.is_transparent() => block != neighbour,
+                _ => false,
+            }
+        })
+    }
+
+    fn patterned_chunk(seed: u16) -> ChunkData {
+        let mut chunk = ChunkData::default();
+        for x in 0..chunk.size {
+            for y in 0..chunk.size {
+                for z in 0..chunk.size {
+                    let block = match (x * 7 + y * 13 + z * 5 + seed) % 8 {
+                        0 | 1 => BlockType::Stone,
+                        2 => BlockType::Water,
+                        3 => BlockType::Lava,
+                        4 => BlockType::Glass,
+                        5 => BlockType::Leaves,
+                        6 => BlockType::StoneSlab,
+                        _ => BlockType::Air,
+                    };
+                    chunk.set_block_at(U16Vec3::new(x, y, z), block);
+                }
+            }
+        }
+        chunk
+    }
+
+    #[test]
+    fn test_face_masks_match_scalar() {
+        let chunk = patterned_chunk(0);
+        let adjacent: Vec<_> = (1..=6)
+            .map(|i| (i % 3 != 0).then(|| Arc::new(patterned_chunk(i))))
+            .collect();
+
+        let masks = face_masks(&chunk, &adjacent);
+        for (position, _) in chunk.blocks() {
+            let expected = visible_faces_scalar(&chunk, &adjacent, position);
+            for (face, visible) in expected.into_iter().enumerate() {
+                assert_eq!(
+                    visible,
+                    masks.is_visible(face, position),
+                    "face {face} of {position:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_lone_block_shows_every_face() {
+        let mut chunk = ChunkData::default();
+        chunk.set_block_at(U16Vec3::new(0, 15, 7), BlockType::Stone);
+
+        let masks = face_masks(&chunk, &vec![None; FACE_COUNT]);
+        for face in 0..FACE_COUNT {
+            assert!(masks.is_visible(face, U16Vec3::new(0, 15, 7)));
+        }
+    }
+
+    #[test]
+    fn test_transparent_faces() {
+        assert!(ALL_BLOCKS
+            .iter()
+            .all(|block| block.is_transparent() == TRANSPARENT_BLOCKS.contains(block)));
+
+        // glass, glass, stone and leaves in a row along x
+        let mut chunk = ChunkData::default();
+        let blocks = [
+            BlockType::Glass,
+            BlockType::Glass,
+            BlockType::Stone,
+            BlockType::Leaves,
+        ];
+        for (x, block) in blocks.into_iter().enumerate() {
+            chunk.set_block_at(U16Vec3::new(x as u16 + 1, 1, 1), block);
+        }
+        let masks = face_masks(&chunk, &vec![None; FACE_COUNT]);
+        let (right, left) = (1, 2);
+        let at = |x: u16| U16Vec3::new(x, 1, 1);
+
+        assert!(!masks.is_visible(right, at(1)), "glass against glass");
+        assert!(!masks.is_visible(right, at(2)), "glass against stone");
+        assert!(masks.is_visible(left, at(3)), "stone against glass");
+        assert!(masks.is_visible(right, at(3)), "stone against leaves");
+        assert!(!masks.is_visible(left, at(4)), "leaves against stone");
+    }
+}
@@ -0,0 +1,225 @@
+//! Reads MagicaVoxel `.vox` models into `Blueprint`s, picking the block closest in colour to each
+//! voxel.
+
+use std::{fs, path::Path};
+
+use bevy::{
+    color::{ColorToComponents, Srgba},
+    math::{I64Vec3, Vec3},
+};
+
+use super::structure::Blueprint;
+use crate::block::{BlockShape, BlockType, PLACEABLE_BLOCKS};
+
+const MAGIC: &[u8; 4] = b"VOX ";
+/// Largest model MagicaVoxel saves along each axis.
+const MAX_SIZE: i64 = 256;
+
+/// Loads the first model in the `.vox` file at `path`.
+pub fn load_vox(path: &Path) -> Result<Blueprint, String> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse_vox(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Reads the first model of a `.vox` file. Scene transforms and any other models are ignored.
+/// MagicaVoxel's z is up, so it becomes y, with its y running away from the viewer along -z.
+pub fn parse_vox(bytes: &[u8]) -> Result<Blueprint, String> {
+    let mut reader = Reader { bytes, offset: 0 };
+    if reader.take(4)? != MAGIC {
+        return Err("not a .vox file".to_string());
+    }
+    let _version = reader.u32()?;
+
+    let (id, content, children) = reader.chunk()?;
+    if id != *b"MAIN" {
+        return Err("missing MAIN chunk".to_string());
+    }
+    if !content.is_empty() {
+        return Err("MAIN chunk has content".to_string());
+    }
+
+    let mut size = None;
+    let mut voxels = None;
+    let mut palette = default_palette();
+    let mut reader = Reader {
+        bytes: children,
+        offset: 0,
+    };
+    while !reader.is_empty() {
+        let (id, content, _) = reader.chunk()?;
+        let mut content = Reader {
+            bytes: content,
+            offset: 0,
+        };
+        match &id {
+            b"SIZE" if size.is_none() => {
+                let [x, y, z] = [content.u32()?, content.u32()?, content.u32()?].map(i64::from);
+                size = Some(I64Vec3::new(x, y, z));
+            }
+            b"XYZI" if voxels.is_none() => {
+                let count = content.u32()? as usize;
+                voxels = Some(content.take(count.checked_mul(4).ok_or("too many voxels")?)?);
+            }
+            b"RGBA" => {
+                for color in palette.iter_mut().skip(1) {
+                    let rgba = content.take(4)?;
+                    *color = [rgba[0], rgba[1], rgba[2]];
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let size = size.ok_or("missing SIZE chunk")?;
+    let voxels = voxels.ok_or("missing XYZI chunk")?;
+    if size.cmplt(I64Vec3::ONE).any() || size.cmpgt(I64Vec3::splat(MAX_SIZE)).any() {
+        return Err(format!("model size {size} is out of range"));
+    }
+
+    let blocks = palette.map(nearest_block);
+    let mut blueprint = Blueprint::new(I64Vec3::new(size.x, size.z, size.y));
+    for voxel in voxels.chunks_exact(4) {
+        let [x, y, z, index] = [voxel[0], voxel[1], voxel[2], voxel[3]].map(i64::from);
+        if x >= size.x || y >= size.y || z >= size.z {
+            return Err(format!("voxel {x} {y} {z} is outside the model"));
+        }
+        blueprint.set(I64Vec3::new(x, z, size.y - 1 - y), blocks[index as usize]);
+    }
+    Ok(blueprint)
+}
+
+/// The full cube block whose colour is closest to `rgb`. Fluids are left out, since they would
+/// run out of the structure.
+fn nearest_block(rgb: [u8; 3]) -> BlockType {
+    let target = Vec3::from(rgb.map(|channel| channel as f32 / 255.0));
+    PLACEABLE_BLOCKS
+        .into_iter()
+        .filter(|block| block.shape() == BlockShape::Cube && !block.is_fence())
+        .filter(|block| !matches!(block, BlockType::Water | BlockType::Lava))
+        .min_by(|a, b| {
+            let distance = |block: &BlockType| {
+                Vec3::from_array(Srgba::from(block.color()).to_f32_array_no_alpha())
+                    .distance_squared(target)
+            };
+            distance(a).total_cmp(&distance(b))
+        })
+        .unwrap()
+}
+
+/// The palette used by models saved without their own, indexed by colour. MagicaVoxel builds it
+/// from a 6×6×6 cube of colours, then ramps of red, green, blue and grey.
+fn default_palette() -> [[u8; 3]; 256] {
+    const CUBE: [u8; 6] = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    const RAMP: [u8; 10] = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+
+    let mut palette = [[0; 3]; 256];
+    let cube = CUBE
+        .iter()
+        .flat_map(|r| {
+            CUBE.iter()
+                .flat_map(move |g| CUBE.iter().map(move |b| [*r, *g, *b]))
+        })
+        .take(215);
+    let ramps = [[1, 0, 0], [0, 1, 0], [0, 0, 1], [1, 1, 1]]
+        .into_iter()
+        .flat_map(|axes: [u8; 3]| RAMP.map(|value| axes.map(|axis| axis * value)));
+    for (color, value) in palette.iter_mut().skip(1).zip(cube.chain(ramps)) {
+        *color = value;
+    }
+    palette
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.offset >= self.bytes.len()
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset.saturating_add(count))
+            .ok_or("unexpected end of file")?;
+        self.offset += count;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Reads a chunk's id, its own content and the bytes of its children.
+    fn chunk(&mut self) -> Result<([u8; 4], &'a [u8], &'a [u8]), String> {
+        let id = self.take(4)?;
+        let content_size = self.u32()? as usize;
+        let children_size = self.u32()? as usize;
+        let content = self.take(content_size)?;
+        let children = self.take(children_size)?;
+        Ok(([id[0], id[1], id[2], id[3]], content, children))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::I64Vec3;
+
+    use super::{default_palette, nearest_block, parse_vox};
+    use crate::block::BlockType;
+
+    fn chunk(id: &[u8; 4], content: &[u8], children: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend((content.len() as u32).to_le_bytes());
+        bytes.extend((children.len() as u32).to_le_bytes());
+        bytes.extend(content);
+        bytes.extend(children);
+        bytes
+    }
+
+    /// A 2×3×4 model with voxels at its origin and far corner.
+    fn model(palette: Option<&[u8]>) -> Vec<u8> {
+        let mut children = chunk(b"SIZE", &[2, 0, 0, 0, 3, 0, 0, 0, 4, 0, 0, 0], &[]);
+        children.extend(chunk(b"XYZI", &[2, 0, 0, 0, 0, 0, 0, 1, 1, 2, 3, 2], &[]));
+        if let Some(palette) = palette {
+            children.extend(chunk(b"RGBA", palette, &[]));
+        }
+        let mut bytes = b"VOX ".to_vec();
+        bytes.extend(150u32.to_le_bytes());
+        bytes.extend(chunk(b"MAIN", &[], &children));
+        bytes
+    }
+
+    #[test]
+    fn test_parse_vox() {
+        let blueprint = parse_vox(&model(None)).unwrap();
+        assert_eq!(I64Vec3::new(2, 4, 3), blueprint.size);
+        // white, then the palette's second colour, a light yellow
+        assert_eq!(Some(BlockType::Snow), blueprint.get(I64Vec3::new(0, 0, 2)));
+        assert_eq!(Some(BlockType::Snow), blueprint.get(I64Vec3::new(1, 3, 0)));
+        assert_eq!(None, blueprint.get(I64Vec3::ZERO));
+
+        let mut palette = [0; 1024];
+        palette[..8].copy_from_slice(&[96, 96, 96, 255, 45, 102, 3, 255]);
+        let blueprint = parse_vox(&model(Some(&palette))).unwrap();
+        assert_eq!(Some(BlockType::Stone), blueprint.get(I64Vec3::new(0, 0, 2)));
+        assert_eq!(Some(BlockType::Grass), blueprint.get(I64Vec3::new(1, 3, 0)));
+
+        assert!(parse_vox(b"VOX ").is_err());
+        assert!(parse_vox(&model(None)[..40]).is_err());
+    }
+
+    #[test]
+    fn test_default_palette() {
+        let palette = default_palette();
+        assert_eq!([0xff, 0xff, 0xff], palette[1]);
+        assert_eq!([0xff, 0xff, 0xcc], palette[2]);
+        assert_eq!([0x00, 0x00, 0x33], palette[215]);
+        assert_eq!([0xee, 0x00, 0x00], palette[216]);
+        assert_eq!([0x11, 0x11, 0x11], palette[255]);
+        assert_eq!(BlockType::Gold, nearest_block([230, 200, 50]));
+    }
+}
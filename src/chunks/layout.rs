@@ -0,0 +1,195 @@
+use std::marker::PhantomData;
+
+use bevy::math::U16Vec3;
+
+use crate::block::BlockType;
+
+/// Layout used for chunk storage. Morton order keeps a block's neighbours in all three axes
+/// close together in memory, where a linear layout puts neighbours along z `size²` apart.
+pub type ChunkLayout = MortonLayout;
+
+/// Maps a block's position within a chunk to its slot in the chunk's block array.
+pub trait BlockLayout {
+    /// Number of slots needed for a chunk `size` blocks wide.
+    fn capacity(size: u16) -> usize;
+    fn index(position: U16Vec3, size: u16) -> usize;
+    fn position(index: usize, size: u16) -> U16Vec3;
+}
+
+/// Row-major order with x varying fastest, then y, then z.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct LinearLayout;
+
+impl BlockLayout for LinearLayout {
+    fn capacity(size: u16) -> usize {
+        (size as usize).pow(3)
+    }
+
+    fn index(position: U16Vec3, size: u16) -> usize {
+        let size = size as usize;
+        position.x as usize + size * (position.y as usize + size * position.z as usize)
+    }
+
+    fn position(index: usize, size: u16) -> U16Vec3 {
+        let size = size as usize;
+        U16Vec3::new(
+            (index % size) as u16,
+            (index / size % size) as u16,
+            (index / (size * size)) as u16,
+        )
+    }
+}
+
+/// Z-order curve, interleaving the bits of x, y and z so each 2×2×2 cube of blocks is
+/// contiguous, as is each 4×4×4 cube and so on. Sizes that aren't a power of two are padded up
+/// to the next one.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct MortonLayout;
+
+/// Spreads the low 16 bits of `value` out so there are two zero bits between each.
+fn spread_bits(value: u16) -> u64 {
+    let mut x = value as u64;
+    x = (x | (x << 32)) & 0x001f_0000_0000_ffff;
+    x = (x | (x << 16)) & 0x001f_0000_ff00_00ff;
+    x = (x | (x << 8)) & 0x100f_00f0_0f00_f00f;
+    x = (x | (x << 4)) & 0x10c3_0c30_c30c_30c3;
+    x = (x | (x << 2)) & 0x1249_2492_4924_9249;
+    x
+}
+
+/// Inverse of `spread_bits`, gathering every third bit back together.
+fn compact_bits(value: u64) -> u16 {
+    let mut x = value & 0x1249_2492_4924_9249;
+    x = (x | (x >> 2)) & 0x10c3_0c30_c30c_30c3;
+    x = (x | (x >> 4)) & 0x100f_00f0_0f00_f00f;
+    x = (x | (x >> 8)) & 0x001f_0000_ff00_00ff;
+    x = (x | (x >> 16)) & 0x001f_0000_0000_ffff;
+    x = (x | (x >> 32)) & 0xffff;
+    x as u16
+}
+
+impl BlockLayout for MortonLayout {
+    fn capacity(size: u16) -> usize {
+        (size.next_power_of_two() as usize).pow(3)
+    }
+
+    fn index(position: U16Vec3, _size: u16) -> usize {
+        (spread_bits(position.x) | spread_bits(position.y) << 1 | spread_bits(position.z) << 2)
+            as usize
+    }
+
+    fn position(index: usize, _size: u16) -> U16Vec3 {
+        let index = index as u64;
+        U16Vec3::new(
+            compact_bits(index),
+            compact_bits(index >> 1),
+            compact_bits(index >> 2),
+        )
+    }
+}
+
+/// A dense cube of blocks stored in the order given by `L`.
+#[derive(Debug, Clone)]
+pub struct BlockStorage<L: BlockLayout> {
+    blocks: Vec<BlockType>,
+    size: u16,
+    /// Number of blocks that aren't air.
+    count: usize,
+    layout: PhantomData<L>,
+}
+
+impl<L: BlockLayout> BlockStorage<L> {
+    pub fn new(size: u16) -> Self {
+        Self {
+            blocks: vec![BlockType::Air; L::capacity(size)],
+            size,
+            count: 0,
+            layout: PhantomData,
+        }
+    }
+
+    pub fn get(&self, position: U16Vec3) -> BlockType {
+        self.blocks[L::index(position, self.size)]
+    }
+
+    pub fn set(&mut self, position: U16Vec3, block: BlockType) {
+        let slot = &mut self.blocks[L::index(position, self.size)];
+        match (*slot == BlockType::Air, block == BlockType::Air) {
+            (true, false) => self.count += 1,
+            (false, true) => self.count -= 1,
+            _ => (),
+        }
+        *slot = block;
+    }
+
+    /// Number of blocks that aren't air.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Every block that isn't air, in storage order.
+    pub fn iter(&self) -> impl Iterator<Item = (U16Vec3, BlockType)> + '_ {
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, block)| **block != BlockType::Air)
+            .map(|(index, block)| (L::position(index, self.size), *block))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::U16Vec3;
+
+    use super::{BlockLayout, BlockStorage, LinearLayout, MortonLayout};
+    use crate::block::BlockType;
+
+    fn assert_round_trip<L: BlockLayout>(size: u16) {
+        let mut seen = vec![false; L::capacity(size)];
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    let position = U16Vec3::new(x, y, z);
+                    let index = L::index(position, size);
+                    assert!(!seen[index], "{position:?} shares slot {index}");
+                    seen[index] = true;
+                    assert_eq!(position, L::position(index, size));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_layouts_round_trip() {
+        assert_round_trip::<LinearLayout>(16);
+        assert_round_trip::<LinearLayout>(5);
+        assert_round_trip::<MortonLayout>(16);
+        assert_round_trip::<MortonLayout>(32);
+        assert_round_trip::<MortonLayout>(5);
+    }
+
+    #[test]
+    fn test_morton_index_interleaves_bits() {
+        assert_eq!(0b111, MortonLayout::index(U16Vec3::new(1, 1, 1), 16));
+        assert_eq!(0b001_000, MortonLayout::index(U16Vec3::new(2, 0, 0), 16));
+        assert_eq!(
+            0b100_000_000,
+            MortonLayout::index(U16Vec3::new(0, 0, 4), 16)
+        );
+    }
+
+    #[test]
+    fn test_storage_counts_blocks() {
+        let mut storage = BlockStorage::<MortonLayout>::new(16);
+        storage.set(U16Vec3::new(3, 4, 5), BlockType::Stone);
+        storage.set(U16Vec3::new(3, 4, 5), BlockType::Sand);
+        storage.set(U16Vec3::new(15, 0, 0), BlockType::Water);
+        assert_eq!(2, storage.count());
+
+        storage.set(U16Vec3::new(15, 0, 0), BlockType::Air);
+        assert_eq!(
+            vec![(U16Vec3::new(3, 4, 5), BlockType::Sand)],
+            storage.iter().collect::<Vec<_>>()
+        );
+    }
+}
@@ -1,41 +1,366 @@
 use bevy::{
     asset::Asset,
+    image::{ImageFilterMode, ImageSampler, ImageSamplerDescriptor},
     pbr::{MaterialPipeline, MaterialPipelineKey},
     prelude::*,
     reflect::TypePath,
     render::{
         mesh::MeshVertexBufferLayoutRef,
+        render_asset::RenderAssetUsages,
         render_resource::{
-            AsBindGroup, Face, RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError,
+            AsBindGroup, Extent3d, Face, RenderPipelineDescriptor, ShaderRef, ShaderType,
+            SpecializedMeshPipelineError, TextureDimension, TextureViewDescriptor,
+            TextureViewDimension,
         },
     },
 };
 
+use super::vertex::ATTRIBUTE_PACKED_VERTEX;
+use crate::block::{BlockLayer, BlockType, ALL_BLOCKS};
+
+const BLOCK_TEXTURES: &str = "textures/blocks.png";
+/// Layers a vertex's 8 bit texture index can refer to.
+const TEXTURE_LAYERS: usize = 256;
+/// Opacity of translucent blocks drawn in flat colours, which have no texture alpha to use.
+const FLAT_TRANSLUCENT_ALPHA: f32 = 0.6;
+
+/// Draws chunks with `ChunkMaterial`, turning the block texture atlas into a texture array.
+pub struct ChunkMaterialPlugin;
+
+impl Plugin for ChunkMaterialPlugin {
+    fn build(&self, app: &mut App) {
+        let atlas = app.world().resource::<AssetServer>().load(BLOCK_TEXTURES);
+        app.add_plugins(MaterialPlugin::<ChunkMaterial>::default())
+            .insert_resource(BlockTextures { atlas, array: None })
+            .add_systems(Update, prepare_block_textures);
+    }
+}
+
+/// The block texture atlas as authored, a row of square textures, and the texture array built
+/// from it.
+#[derive(Resource, Debug)]
+pub struct BlockTextures {
+    atlas: Handle<Image>,
+    array: Option<Handle<Image>>,
+}
+
+impl BlockTextures {
+    pub fn atlas(&self) -> &Handle<Image> {
+        &self.atlas
+    }
+}
+
+/// Draws chunk meshes made of packed vertices, see `ATTRIBUTE_PACKED_VERTEX`.
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+#[bind_group_data(ChunkMaterialKey)]
 pub struct ChunkMaterial {
     #[uniform(0)]
     pub color: LinearRgba,
-    #[texture(1)]
+    /// Block textures, one layer per block after air. Filled in by `prepare_block_textures` once
+    /// they have loaded.
+    #[texture(1, dimension = "2d_array")]
     #[sampler(2)]
     pub texture: Option<Handle<Image>>,
+    #[uniform(3)]
+    pub lighting: ChunkLighting,
+    /// Opaque for solid and cutout blocks, which discard their transparent texels, or blended for
+    /// translucent blocks.
+    pub alpha_mode: AlphaMode,
+    #[uniform(4)]
+    pub flat_colors: FlatColors,
+    /// Draws each block in its colour from `flat_colors`, lit per vertex and unshadowed, instead
+    /// of sampling its texture. The texture array is never built for flat materials.
+    pub flat: bool,
+    #[uniform(5)]
+    pub fog: ChunkFog,
+}
+
+#[derive(Eq, PartialEq, Hash, Clone)]
+pub struct ChunkMaterialKey {
+    flat: bool,
+}
+
+impl From<&ChunkMaterial> for ChunkMaterialKey {
+    fn from(material: &ChunkMaterial) -> Self {
+        Self {
+            flat: material.flat,
+        }
+    }
+}
+
+/// The colour of each layer of the block texture array, taken from `BlockType::color` of the
+/// first block drawn with it.
+#[derive(ShaderType, Debug, Clone)]
+pub struct FlatColors {
+    colors: [Vec4; TEXTURE_LAYERS],
+}
+
+impl FlatColors {
+    pub fn get(&self, texture: u8) -> Vec4 {
+        self.colors[texture as usize]
+    }
+}
+
+impl Default for FlatColors {
+    fn default() -> Self {
+        let mut colors = [Vec4::ZERO; TEXTURE_LAYERS];
+        // in reverse, so the first block with a texture sets its colour
+        for block in ALL_BLOCKS.into_iter().rev() {
+            if block == BlockType::Air {
+                continue;
+            }
+            let alpha = match block.layer() {
+                BlockLayer::Translucent => FLAT_TRANSLUCENT_ALPHA,
+                _ => 1.0,
+            };
+            colors[block.texture() as usize] =
+                LinearRgba::from(block.color()).with_alpha(alpha).to_vec4();
+        }
+        Self { colors }
+    }
+}
+
+#[derive(ShaderType, Debug, Clone, Copy)]
+pub struct ChunkLighting {
+    /// Strength of sunlight from `0.0` at night to `1.0` at noon.
+    pub daylight: f32,
+    /// Non-zero tints terrain by the shadow cascade each fragment falls in.
+    pub debug_cascades: u32,
+    /// Direction towards the sun, which flat materials light their vertices by.
+    pub sun_direction: Vec3,
+}
+
+impl Default for ChunkLighting {
+    fn default() -> Self {
+        Self {
+            daylight: 1.0,
+            debug_cascades: 0,
+            sun_direction: Vec3::Y,
+        }
+    }
+}
+
+/// Distance fog distant terrain fades into, set by `fog::update_fog` from the camera's biome.
+#[derive(ShaderType, Debug, Clone, Copy, PartialEq)]
+pub struct ChunkFog {
+    /// Linear colour of the fog at noon, darkened by the shader at night.
+    pub color: Vec4,
+    /// Distance from the camera in blocks at which fog begins.
+    pub start: f32,
+    /// Distance at which terrain is hidden entirely by fog.
+    pub end: f32,
+}
+
+impl Default for ChunkFog {
+    fn default() -> Self {
+        Self {
+            color: LinearRgba::from(Color::srgb_u8(135, 206, 235)).to_vec4(),
+            start: 160.0,
+            end: 320.0,
+        }
+    }
+}
+
+impl ChunkFog {
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        Self {
+            color: self.color.lerp(other.color, t),
+            start: self.start + (other.start - self.start) * t,
+            end: self.end + (other.end - self.end) * t,
+        }
+    }
 }
 
 impl Material for ChunkMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/world.wgsl".into()
+    }
+
     fn fragment_shader() -> ShaderRef {
         "shaders/world.wgsl".into()
     }
 
+    fn prepass_vertex_shader() -> ShaderRef {
+        "shaders/world_prepass.wgsl".into()
+    }
+
     fn alpha_mode(&self) -> AlphaMode {
-        AlphaMode::Mask(0.5)
+        self.alpha_mode
     }
 
     fn specialize(
         _pipeline: &MaterialPipeline<Self>,
         descriptor: &mut RenderPipelineDescriptor,
-        _layout: &MeshVertexBufferLayoutRef,
-        _key: MaterialPipelineKey<Self>,
+        layout: &MeshVertexBufferLayoutRef,
+        key: MaterialPipelineKey<Self>,
     ) -> Result<(), SpecializedMeshPipelineError> {
+        descriptor.vertex.buffers = vec![layout
+            .0
+            .get_layout(&[ATTRIBUTE_PACKED_VERTEX.at_shader_location(0)])?];
         descriptor.primitive.cull_mode = Some(Face::Front);
+        if key.bind_group_data.flat {
+            descriptor.vertex.shader_defs.push("FLAT_COLOR".into());
+            // prepasses such as shadow maps have no fragment stage
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment.shader_defs.push("FLAT_COLOR".into());
+            }
+        }
         Ok(())
     }
 }
+
+/// Builds the block texture array once the atlas has loaded and hands it to chunk materials still
+/// waiting for it.
+pub fn prepare_block_textures(
+    mut textures: ResMut<BlockTextures>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<ChunkMaterial>>,
+) {
+    if textures.array.is_none() {
+        let Some(array) = images.get(&textures.atlas).and_then(block_texture_array) else {
+            return;
+        };
+        textures.array = Some(images.add(array));
+    }
+
+    let waiting: Vec<_> = materials
+        .iter()
+        .filter(|(_, material)| material.texture.is_none() && !material.flat)
+        .map(|(id, _)| id)
+        .collect();
+    for id in waiting {
+        if let Some(material) = materials.get_mut(id) {
+            material.texture = textures.array.clone();
+        }
+    }
+}
+
+/// Splits a row of square 8-bit RGBA textures into the layers of a texture array, each with a full
+/// mip chain. Unlike sampling an atlas, lower mips never blend in the neighbouring textures.
+pub fn block_texture_array(atlas: &Image) -> Option<Image> {
+    let size = atlas.size();
+    let (tile, layers) = (size.y, size.x / size.y.max(1));
+    let format = atlas.texture_descriptor.format;
+    if layers == 0 || !tile.is_power_of_two() || format.block_copy_size(None) != Some(4) {
+        return None;
+    }
+
+    // layers are stored one after another, each with its mip levels from largest to smallest
+    let mut data = Vec::new();
+    for layer in 0..layers {
+        let mut level: Vec<[u8; 4]> = (0..tile * tile)
+            .map(|i| {
+                let at = ((i / tile * size.x + layer * tile + i % tile) * 4) as usize;
+                atlas.data[at..at + 4].try_into().unwrap()
+            })
+            .collect();
+        let mut level_size = tile;
+        data.extend(level.iter().flatten());
+        while level_size > 1 {
+            level = downsample(&level, level_size);
+            level_size /= 2;
+            data.extend(level.iter().flatten());
+        }
+    }
+
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: tile,
+            height: tile,
+            depth_or_array_layers: layers,
+        },
+        TextureDimension::D2,
+        &[0; 4],
+        format,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.data = data;
+    image.texture_descriptor.mip_level_count = tile.ilog2() + 1;
+    image.texture_view_descriptor = Some(TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::D2Array),
+        ..default()
+    });
+    // pixels stay crisp up close, and distant blocks blend between mips rather than shimmering
+    image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+        mipmap_filter: ImageFilterMode::Linear,
+        ..ImageSamplerDescriptor::nearest()
+    });
+    Some(image)
+}
+
+/// Halves a square texture by averaging each 2x2 block of pixels.
+fn downsample(pixels: &[[u8; 4]], size: u32) -> Vec<[u8; 4]> {
+    let half = size / 2;
+    (0..half * half)
+        .map(|i| {
+            let (x, y) = (i % half * 2, i / half * 2);
+            let corners = [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)]
+                .map(|(x, y)| pixels[(y * size + x) as usize]);
+            std::array::from_fn(|channel| {
+                let sum: u32 = corners.iter().map(|pixel| pixel[channel] as u32).sum();
+                ((sum + 2) / 4) as u8
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        color::{ColorToComponents, LinearRgba},
+        image::Image,
+        render::{
+            render_asset::RenderAssetUsages,
+            render_resource::{Extent3d, TextureDimension, TextureFormat},
+        },
+    };
+
+    use super::{block_texture_array, FlatColors};
+    use crate::block::BlockType;
+
+    #[test]
+    fn test_block_texture_array() {
+        // two 4x4 textures side by side, one red and one blue
+        let data = (0..4 * 8)
+            .flat_map(|i| match i % 8 < 4 {
+                true => [255, 0, 0, 255],
+                false => [0, 0, 255, 255],
+            })
+            .collect();
+        let atlas = Image::new(
+            Extent3d {
+                width: 8,
+                height: 4,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::MAIN_WORLD,
+        );
+
+        let array = block_texture_array(&atlas).unwrap();
+        assert_eq!(2, array.texture_descriptor.size.depth_or_array_layers);
+        assert_eq!(3, array.texture_descriptor.mip_level_count);
+
+        // each layer holds 4x4, 2x2 and 1x1 mips, none blending in the other texture
+        let layer_bytes = (16 + 4 + 1) * 4;
+        assert_eq!(2 * layer_bytes, array.data.len());
+        assert!(array.data[..layer_bytes]
+            .chunks(4)
+            .all(|pixel| pixel == [255, 0, 0, 255]));
+        assert!(array.data[layer_bytes..]
+            .chunks(4)
+            .all(|pixel| pixel == [0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn test_flat_colors() {
+        let colors = FlatColors::default();
+        let stone = colors.get(BlockType::Stone.texture());
+        assert_eq!(LinearRgba::from(BlockType::Stone.color()).to_vec4(), stone);
+        assert_eq!(stone, colors.get(BlockType::StoneSlab.texture()));
+        assert!(colors.get(BlockType::Glass.texture()).w < 1.0);
+        assert_eq!(1.0, colors.get(BlockType::Leaves.texture()).w);
+    }
+}
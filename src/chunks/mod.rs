@@ -1,4 +1,9 @@
 pub mod chunk;
 pub mod chunk_loader;
+pub mod codec;
+pub mod foliage;
 pub mod generate;
+pub mod layout;
 pub mod material;
+pub mod position;
+pub mod vertex;
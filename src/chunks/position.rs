@@ -0,0 +1,138 @@
+//! Typed positions of blocks, in the world and within a chunk, with the conversions between them
+//! and `ChunkCoordinate`. Block positions are split with euclidean division, so a block at -1
+//! is in chunk -1 rather than chunk 0.
+
+use bevy::math::{I64Vec3, U16Vec3, Vec3};
+
+use super::chunk::ChunkCoordinate;
+
+/// A block's position in the world. Blocks are centred on their position, so block `b` spans
+/// `b - 0.5` to `b + 0.5`.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct BlockPos(pub I64Vec3);
+
+/// A block's position within a chunk, each component less than the chunk's size.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct LocalPos(U16Vec3);
+
+impl BlockPos {
+    /// The block containing a point in the world.
+    pub fn from_world(position: Vec3) -> Self {
+        Self((position + 0.5).floor().as_i64vec3())
+    }
+
+    pub fn chunk(self, chunk_size: u16) -> ChunkCoordinate {
+        ChunkCoordinate(self.0.div_euclid(I64Vec3::splat(chunk_size as i64)))
+    }
+
+    pub fn local(self, chunk_size: u16) -> LocalPos {
+        LocalPos(
+            self.0
+                .rem_euclid(I64Vec3::splat(chunk_size as i64))
+                .as_u16vec3(),
+        )
+    }
+
+    /// The chunk this block is in and its position within that chunk.
+    pub fn split(self, chunk_size: u16) -> (ChunkCoordinate, LocalPos) {
+        (self.chunk(chunk_size), self.local(chunk_size))
+    }
+}
+
+impl From<I64Vec3> for BlockPos {
+    fn from(value: I64Vec3) -> Self {
+        Self(value)
+    }
+}
+
+impl From<BlockPos> for I64Vec3 {
+    fn from(value: BlockPos) -> Self {
+        value.0
+    }
+}
+
+impl LocalPos {
+    /// `position` within a chunk of `chunk_size`, or `None` if it lies outside the chunk.
+    pub fn new(position: U16Vec3, chunk_size: u16) -> Option<Self> {
+        position
+            .cmplt(U16Vec3::splat(chunk_size))
+            .all()
+            .then_some(Self(position))
+    }
+
+    /// A block `offset` from a chunk's origin, or `None` if it lies outside the chunk.
+    pub fn from_offset(offset: I64Vec3, chunk_size: u16) -> Option<Self> {
+        let inside = offset.cmpge(I64Vec3::ZERO).all()
+            && offset.cmplt(I64Vec3::splat(chunk_size as i64)).all();
+        inside.then(|| Self(offset.as_u16vec3()))
+    }
+
+    pub fn get(self) -> U16Vec3 {
+        self.0
+    }
+}
+
+impl From<LocalPos> for U16Vec3 {
+    fn from(value: LocalPos) -> Self {
+        value.0
+    }
+}
+
+impl ChunkCoordinate {
+    /// The chunk's block with the smallest coordinates.
+    pub fn origin(self, chunk_size: u16) -> BlockPos {
+        BlockPos(self.0 * chunk_size as i64)
+    }
+
+    pub fn block(self, local: LocalPos, chunk_size: u16) -> BlockPos {
+        BlockPos(self.origin(chunk_size).0 + local.0.as_i64vec3())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec3, U16Vec3, Vec3};
+
+    use super::{BlockPos, LocalPos};
+    use crate::chunks::chunk::ChunkCoordinate;
+
+    #[test]
+    fn test_negative_blocks_split_downwards() {
+        let (chunk, local) = BlockPos(I64Vec3::new(-1, 0, -16)).split(16);
+        assert_eq!(ChunkCoordinate(I64Vec3::new(-1, 0, -1)), chunk);
+        assert_eq!(U16Vec3::new(15, 0, 0), local.get());
+
+        let (chunk, local) = BlockPos(I64Vec3::new(-33, 31, 32)).split(32);
+        assert_eq!(ChunkCoordinate(I64Vec3::new(-2, 0, 1)), chunk);
+        assert_eq!(U16Vec3::new(31, 31, 0), local.get());
+    }
+
+    #[test]
+    fn test_split_round_trips() {
+        for chunk_size in [16, 32] {
+            for x in -40..40 {
+                let block = BlockPos(I64Vec3::new(x, -x * 3, 7));
+                let (chunk, local) = block.split(chunk_size);
+                assert_eq!(block, chunk.block(local, chunk_size));
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_world_rounds_to_the_nearest_block() {
+        assert_eq!(I64Vec3::ZERO, BlockPos::from_world(Vec3::splat(0.4)).0);
+        assert_eq!(I64Vec3::ZERO, BlockPos::from_world(Vec3::splat(-0.4)).0);
+        assert_eq!(
+            I64Vec3::new(-1, 1, -2),
+            BlockPos::from_world(Vec3::new(-0.6, 0.5, -1.9)).0
+        );
+    }
+
+    #[test]
+    fn test_local_positions_are_checked() {
+        assert!(LocalPos::new(U16Vec3::new(15, 0, 15), 16).is_some());
+        assert!(LocalPos::new(U16Vec3::new(16, 0, 0), 16).is_none());
+        assert!(LocalPos::from_offset(I64Vec3::new(0, -1, 0), 16).is_none());
+        assert!(LocalPos::from_offset(I64Vec3::new(31, 0, 0), 32).is_some());
+    }
+}
@@ -0,0 +1,142 @@
+use bevy::{
+    math::Vec3,
+    render::{
+        mesh::{Mesh, MeshVertexAttribute, VertexAttributeValues},
+        render_resource::VertexFormat,
+    },
+};
+
+/// Chunk vertices packed into two words, unpacked by `shaders/chunk_vertex.wgsl`:
+///
+/// - first word: x, y and z of the vertex's corner within the chunk in `POSITION_STEPS` of a
+///   block, 10 bits each
+/// - second word: the face's index into `FACE_NORMALS` in 3 bits, the texture corner in 2 bits,
+///   the texture's layer in the block texture array in 8 bits, the emissive multiplier in
+///   `EMISSIVE_STEPS` in 8 bits, the light level in 4 bits and the ambient occlusion in 2 bits
+///
+/// It shares the id of `Mesh::ATTRIBUTE_POSITION` so that Bevy's shadow and prepass pipelines,
+/// which require a position, accept chunk meshes and hand the words to the chunk vertex shaders.
+pub const ATTRIBUTE_PACKED_VERTEX: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_Position", 0, VertexFormat::Uint32x2);
+
+/// Positions are stored in sixteenths of a block, fine enough for every fluid level and block
+/// shape.
+pub const POSITION_STEPS: f32 = 16.0;
+/// The emissive multiplier is stored in eighths.
+pub const EMISSIVE_STEPS: f32 = 8.0;
+/// Brightest light level a vertex can be lit at.
+pub const MAX_LIGHT: u8 = 15;
+/// Most corners of a vertex that can be occluded by neighbouring blocks.
+pub const MAX_OCCLUSION: u8 = 3;
+
+/// Normal of each face of a block, in the order faces are meshed.
+pub const FACE_NORMALS: [Vec3; 6] = [
+    Vec3::Z,
+    Vec3::X,
+    Vec3::NEG_X,
+    Vec3::NEG_Z,
+    Vec3::Y,
+    Vec3::NEG_Y,
+];
+
+/// A chunk vertex before packing. Block centres sit on whole numbers, so a vertex at `position`
+/// is stored as its corner `position + 0.5`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ChunkVertex {
+    pub position: Vec3,
+    pub face: u8,
+    /// Corner of the texture, each either `0.0` or `1.0`.
+    pub uv: [f32; 2],
+    pub texture: u8,
+    pub emissive: f32,
+    /// Light reaching the vertex, up to `MAX_LIGHT`.
+    pub light: u8,
+    /// Number of blocks crowding the vertex's corner, up to `MAX_OCCLUSION`.
+    pub occlusion: u8,
+}
+
+impl ChunkVertex {
+    pub fn pack(&self) -> [u32; 2] {
+        let corner = ((self.position + 0.5) * POSITION_STEPS).round().as_uvec3() & 0x3ff;
+        let u = (self.uv[0] > 0.5) as u32;
+        let v = (self.uv[1] > 0.5) as u32;
+        let emissive = (self.emissive * EMISSIVE_STEPS).round().clamp(0.0, 255.0) as u32;
+        let light = self.light.min(MAX_LIGHT) as u32;
+        let occlusion = self.occlusion.min(MAX_OCCLUSION) as u32;
+        [
+            corner.x | corner.y << 10 | corner.z << 20,
+            self.face as u32 & 0x7
+                | u << 3
+                | v << 4
+                | (self.texture as u32) << 5
+                | emissive << 13
+                | light << 21
+                | occlusion << 25,
+        ]
+    }
+
+    pub fn unpack([position, data]: [u32; 2]) -> Self {
+        let corner = Vec3::new(
+            (position & 0x3ff) as f32,
+            (position >> 10 & 0x3ff) as f32,
+            (position >> 20 & 0x3ff) as f32,
+        ) / POSITION_STEPS;
+        Self {
+            position: corner - 0.5,
+            face: (data & 0x7) as u8,
+            uv: [(data >> 3 & 1) as f32, (data >> 4 & 1) as f32],
+            texture: (data >> 5 & 0xff) as u8,
+            emissive: (data >> 13 & 0xff) as f32 / EMISSIVE_STEPS,
+            light: (data >> 21 & 0xf) as u8,
+            occlusion: (data >> 25 & 0x3) as u8,
+        }
+    }
+}
+
+/// Overrides the emissive multiplier of every vertex of a packed chunk mesh.
+pub fn set_emissive(mesh: &mut Mesh, emissive: f32) {
+    let Some(VertexAttributeValues::Uint32x2(vertices)) =
+        mesh.attribute_mut(ATTRIBUTE_PACKED_VERTEX)
+    else {
+        return;
+    };
+    for vertex in vertices {
+        *vertex = ChunkVertex {
+            emissive,
+            ..ChunkVertex::unpack(*vertex)
+        }
+        .pack();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec3;
+
+    use super::ChunkVertex;
+
+    #[test]
+    fn test_pack_round_trip() {
+        let vertex = ChunkVertex {
+            position: Vec3::new(31.5, 12.125, 4.0),
+            face: 5,
+            uv: [1.0, 0.0],
+            texture: 10,
+            emissive: 6.0,
+            light: 7,
+            occlusion: 1,
+        };
+        assert_eq!(vertex, ChunkVertex::unpack(vertex.pack()));
+
+        let full = ChunkVertex {
+            position: Vec3::splat(31.5),
+            face: 3,
+            uv: [0.0, 1.0],
+            texture: 255,
+            emissive: 16.0,
+            light: 15,
+            occlusion: 3,
+        };
+        assert_eq!(full, ChunkVertex::unpack(full.pack()));
+    }
+}
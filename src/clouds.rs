@@ -0,0 +1,249 @@
+//! A layer of blocky clouds high over the terrain, laid out by noise from the world seed and
+//! drifting slowly with the wind. Only the clouds around the camera are meshed, rebuilt as the
+//! camera crosses into another cloud cell.
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::Assets,
+    color::{Color, LinearRgba},
+    core_pipeline::core_3d::Camera3d,
+    ecs::{
+        change_detection::DetectChangesMut,
+        component::Component,
+        query::With,
+        schedule::IntoSystemConfigs,
+        system::{Commands, Local, Query, Res, ResMut},
+    },
+    math::{IVec2, Vec2, Vec3},
+    pbr::{MeshMaterial3d, NotShadowCaster, StandardMaterial},
+    prelude::{AlphaMode, Mesh3d},
+    render::{
+        mesh::{Indices, Mesh, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+        view::Visibility,
+    },
+    state::condition::in_state,
+    time::Time,
+    transform::components::{GlobalTransform, Transform},
+    utils::HashSet,
+};
+use noise::{NoiseFn, Perlin};
+
+use crate::{daylight::TimeOfDay, settings::Settings, state::GameState, world::World};
+
+/// Height of the bottom of the cloud layer.
+const CLOUD_HEIGHT: f32 = 192.0;
+const CLOUD_THICKNESS: f32 = 4.0;
+/// Width in blocks of one cloud cell.
+const CLOUD_CELL: f32 = 12.0;
+/// Cells from the camera's cell within which clouds are meshed.
+const CLOUD_RADIUS: i32 = 32;
+/// Blocks per second the clouds drift along x.
+const CLOUD_SPEED: f32 = 1.0;
+/// Noise above which a cell is cloud, higher gives clearer skies.
+const CLOUD_COVERAGE: f64 = 0.15;
+const CLOUD_NOISE_SCALE: f64 = 0.11;
+const CLOUD_ALPHA: f32 = 0.8;
+/// Brightness of clouds at night, when they only catch the moonlight.
+const NIGHT_BRIGHTNESS: f32 = 0.12;
+/// Shading of the tops, sides and bottoms of clouds, which are unlit.
+const TOP_SHADE: f32 = 1.0;
+const SIDE_SHADE: f32 = 0.85;
+const BOTTOM_SHADE: f32 = 0.7;
+
+/// The faces of a cloud cell's slab, as the cell a side faces, corners of the unit cube and shade.
+#[rustfmt::skip]
+const CLOUD_FACES: [(Option<IVec2>, [[f32; 3]; 4], f32); 6] = [
+    (None, [[0., 1., 0.], [0., 1., 1.], [1., 1., 1.], [1., 1., 0.]], TOP_SHADE),
+    (None, [[0., 0., 0.], [1., 0., 0.], [1., 0., 1.], [0., 0., 1.]], BOTTOM_SHADE),
+    (Some(IVec2::NEG_X), [[0., 0., 0.], [0., 0., 1.], [0., 1., 1.], [0., 1., 0.]], SIDE_SHADE),
+    (Some(IVec2::X), [[1., 0., 1.], [1., 0., 0.], [1., 1., 0.], [1., 1., 1.]], SIDE_SHADE),
+    (Some(IVec2::NEG_Y), [[1., 0., 0.], [0., 0., 0.], [0., 1., 0.], [1., 1., 0.]], SIDE_SHADE),
+    (Some(IVec2::Y), [[0., 0., 1.], [1., 0., 1.], [1., 1., 1.], [0., 1., 1.]], SIDE_SHADE),
+];
+
+pub struct CloudPlugin;
+
+impl Plugin for CloudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_clouds.run_if(in_state(GameState::InGame)));
+    }
+}
+
+#[derive(Component)]
+struct Clouds;
+
+/// Whether the cloud cell at `cell` is cloud.
+pub fn is_cloud(noise: &Perlin, cell: IVec2) -> bool {
+    let point = cell.as_dvec2() * CLOUD_NOISE_SCALE;
+    noise.get(point.to_array()) > CLOUD_COVERAGE
+}
+
+/// The cloud cells within `CLOUD_RADIUS` of `centre`.
+pub fn cloud_cells(noise: &Perlin, centre: IVec2) -> HashSet<IVec2> {
+    let mut cells = HashSet::new();
+    for x in -CLOUD_RADIUS..=CLOUD_RADIUS {
+        for z in -CLOUD_RADIUS..=CLOUD_RADIUS {
+            let offset = IVec2::new(x, z);
+            let cell = centre + offset;
+            if offset.length_squared() <= CLOUD_RADIUS * CLOUD_RADIUS && is_cloud(noise, cell) {
+                cells.insert(cell);
+            }
+        }
+    }
+    cells
+}
+
+/// A slab for every cloud cell, with sides only where a cell meets clear sky so neighbouring
+/// cells join into one cloud.
+pub fn cloud_mesh(cells: &HashSet<IVec2>) -> Mesh {
+    let mut positions: Vec<Vec3> = vec![];
+    let mut colors: Vec<[f32; 4]> = vec![];
+    let mut indices: Vec<u32> = vec![];
+    let mut quad = |corners: [Vec3; 4], shade: f32| {
+        let first = positions.len() as u32;
+        positions.extend(corners);
+        colors.extend([[shade, shade, shade, 1.0]; 4]);
+        indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    };
+
+    let scale = Vec3::new(CLOUD_CELL, CLOUD_THICKNESS, CLOUD_CELL);
+    for cell in cells {
+        let min = Vec3::new(cell.x as f32, 0.0, cell.y as f32) * CLOUD_CELL;
+        for (neighbour, corners, shade) in CLOUD_FACES {
+            // sides facing another cloud cell are inside the cloud
+            if neighbour.is_some_and(|neighbour| cells.contains(&(*cell + neighbour))) {
+                continue;
+            }
+            quad(
+                corners.map(|corner| min + Vec3::from(corner) * scale),
+                shade,
+            );
+        }
+    }
+
+    let normals = vec![Vec3::Y; positions.len()];
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    mesh.insert_indices(Indices::U32(indices));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh
+}
+
+/// Drifts the clouds, remeshes them around the camera when it moves into another cell and dims
+/// them at night. Clouds are hidden while `graphics.clouds` is off.
+#[allow(clippy::too_many_arguments)]
+fn update_clouds(
+    mut commands: Commands,
+    time: Res<Time>,
+    world: Res<World>,
+    time_of_day: Res<TimeOfDay>,
+    mut drift: Local<f32>,
+    mut built: Local<Option<(u32, IVec2)>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    settings_query: Query<&Settings>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    mut cloud_query: Query<
+        (
+            &mut Transform,
+            &mut Visibility,
+            &Mesh3d,
+            &MeshMaterial3d<StandardMaterial>,
+        ),
+        With<Clouds>,
+    >,
+) {
+    let enabled = settings_query
+        .get_single()
+        .map_or(true, |settings| settings.graphics.clouds);
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    *drift += CLOUD_SPEED * time.delta_secs();
+    // cells are fixed to the clouds, so the camera is found among them against the drift
+    let camera = camera.translation();
+    let centre = (Vec2::new(camera.x - *drift, camera.z) / CLOUD_CELL)
+        .floor()
+        .as_ivec2();
+    let key = (world.seed(), centre);
+    let noise = || Perlin::new(world.seed());
+
+    let Ok((mut transform, mut visibility, mesh, material)) = cloud_query.get_single_mut() else {
+        if enabled {
+            *built = Some(key);
+            commands.spawn((
+                Mesh3d(meshes.add(cloud_mesh(&cloud_cells(&noise(), centre)))),
+                MeshMaterial3d(materials.add(StandardMaterial {
+                    base_color: Color::WHITE,
+                    unlit: true,
+                    alpha_mode: AlphaMode::Blend,
+                    cull_mode: None,
+                    ..Default::default()
+                })),
+                Transform::from_xyz(*drift, CLOUD_HEIGHT, 0.0),
+                Visibility::Inherited,
+                NotShadowCaster,
+                Clouds,
+            ));
+        }
+        return;
+    };
+    visibility.set_if_neq(if enabled {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    });
+    if !enabled {
+        return;
+    }
+
+    transform.translation = Vec3::new(*drift, CLOUD_HEIGHT, 0.0);
+    if *built != Some(key) {
+        meshes.insert(&mesh.0, cloud_mesh(&cloud_cells(&noise(), centre)));
+        *built = Some(key);
+    }
+
+    let brightness = NIGHT_BRIGHTNESS + (1.0 - NIGHT_BRIGHTNESS) * time_of_day.daylight();
+    if let Some(material) = materials.get_mut(&material.0) {
+        let color = LinearRgba::new(brightness, brightness, brightness, CLOUD_ALPHA);
+        if material.base_color.to_linear() != color {
+            material.base_color = color.into();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{math::IVec2, utils::HashSet};
+    use noise::Perlin;
+
+    use super::{cloud_cells, cloud_mesh, is_cloud, CLOUD_RADIUS};
+
+    #[test]
+    fn test_cloud_cells_follow_noise_around_centre() {
+        let noise = Perlin::new(7);
+        let centre = IVec2::new(100, -40);
+        let cells = cloud_cells(&noise, centre);
+        assert!(!cells.is_empty());
+        for cell in &cells {
+            assert!(is_cloud(&noise, *cell));
+            assert!((*cell - centre).length_squared() <= CLOUD_RADIUS * CLOUD_RADIUS);
+        }
+        // some of the sky is clear
+        assert!(cells.len() < (CLOUD_RADIUS * CLOUD_RADIUS * 3) as usize);
+    }
+
+    #[test]
+    fn test_neighbouring_cloud_cells_share_no_sides() {
+        let one = cloud_mesh(&HashSet::from([IVec2::ZERO]));
+        assert_eq!(6 * 4, one.count_vertices());
+
+        let two = cloud_mesh(&HashSet::from([IVec2::ZERO, IVec2::X]));
+        assert_eq!(10 * 4, two.count_vertices());
+    }
+}
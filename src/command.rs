@@ -0,0 +1,542 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        event::{Event, Events},
+        query::With,
+        system::{In, IntoSystem, Res, Resource, SystemId},
+        world::World,
+    },
+    hierarchy::Parent,
+    log::{info, warn},
+    math::{I64Vec3, Vec3},
+    render::camera::Camera,
+    transform::components::Transform,
+};
+
+use crate::{
+    block::{BlockType, ALL_BLOCKS},
+    item::{Held, ALL_ITEMS},
+    player::Player,
+};
+
+/// What a command prints back, a message on success or the reason it failed.
+pub type CommandResult = Result<String, String>;
+pub type CommandSystemId = SystemId<In<CommandArgs>, CommandResult>;
+
+/// Runs commands entered in the in-game console or a dedicated server's terminal. Commands are
+/// systems registered with [`CommandAppExt::add_console_command`], taking the command's
+/// [`CommandArgs`] as input.
+pub struct CommandPlugin;
+
+impl Plugin for CommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CommandRegistry>()
+            .add_event::<ConsoleCommand>()
+            .add_event::<CommandOutput>()
+            .add_systems(Update, run_console_commands)
+            .add_console_command("help", "help", "lists every command", help_command);
+    }
+}
+
+/// A command entered in the console, e.g. `/benchmark quick` has the name `benchmark` and the
+/// single argument `quick`.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ConsoleCommand {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+impl ConsoleCommand {
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.trim().trim_start_matches('/').split_whitespace();
+        let name = words.next()?.to_lowercase();
+        Some(Self {
+            name,
+            args: words.map(str::to_string).collect(),
+        })
+    }
+}
+
+/// A line printed in response to a command.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct CommandOutput {
+    pub line: String,
+    pub is_error: bool,
+}
+
+/// The arguments of a command, read in order with typed parsers that report what was expected
+/// when an argument is missing or malformed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandArgs {
+    words: Vec<String>,
+    next: usize,
+    /// Where relative coordinates such as `~ ~1 ~` are measured from and which way `^ ^ ^1`
+    /// faces, the player's position and view when there is one.
+    origin: Option<Transform>,
+}
+
+impl CommandArgs {
+    pub fn new(words: Vec<String>) -> Self {
+        Self {
+            words,
+            next: 0,
+            origin: None,
+        }
+    }
+
+    pub fn with_origin(mut self, origin: Transform) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    pub fn origin(&self) -> Option<Transform> {
+        self.origin
+    }
+
+    fn require_origin(&self) -> Result<Transform, String> {
+        self.origin
+            .ok_or_else(|| "relative coordinates need a player".to_string())
+    }
+
+    /// Whether every argument has been read.
+    pub fn is_empty(&self) -> bool {
+        self.next >= self.words.len()
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.words.len().saturating_sub(self.next)
+    }
+
+    /// The next argument without reading it.
+    pub fn peek(&self) -> Option<&str> {
+        self.words.get(self.next).map(String::as_str)
+    }
+
+    /// The next argument, if there is one.
+    pub fn optional_word(&mut self) -> Option<String> {
+        let word = self.words.get(self.next).cloned();
+        if word.is_some() {
+            self.next += 1;
+        }
+        word
+    }
+
+    /// The next argument, `name` describing it in the error when it's missing.
+    pub fn word(&mut self, name: &str) -> Result<String, String> {
+        self.optional_word()
+            .ok_or_else(|| format!("expected {name}"))
+    }
+
+    pub fn number<T: FromStr>(&mut self, name: &str) -> Result<T, String> {
+        let word = self.word(name)?;
+        word.parse()
+            .map_err(|_| format!("'{word}' is not a valid {name}"))
+    }
+
+    /// Reads the three numbers of a position, e.g. the `x y z` of `/tp x y z`. Each may be
+    /// relative to the origin along a world axis, `~` being the origin itself and `~2` two blocks
+    /// past it. Alternatively all three may be `^left ^up ^forward` offsets from the way the
+    /// player is looking, so `^ ^ ^3` is three blocks in front of them.
+    pub fn position(&mut self) -> Result<Vec3, String> {
+        if self.remaining() < 3 {
+            return Err("expected a position x y z".to_string());
+        }
+        let words = [
+            self.word("a coordinate")?,
+            self.word("a coordinate")?,
+            self.word("a coordinate")?,
+        ];
+
+        match words.iter().filter(|word| word.starts_with('^')).count() {
+            0 => {
+                let mut position = Vec3::ZERO;
+                for (axis, word) in words.iter().enumerate() {
+                    position[axis] = match word.strip_prefix('~') {
+                        Some(offset) => {
+                            self.require_origin()?.translation[axis] + offset_of(offset)?
+                        }
+                        None => parse_coordinate(word)?,
+                    };
+                }
+                Ok(position)
+            }
+            3 => {
+                let origin = self.require_origin()?;
+                let [left, up, forward] = [0, 1, 2].map(|i| offset_of(&words[i][1..]));
+                Ok(origin.translation
+                    + origin.left() * left?
+                    + origin.up() * up?
+                    + origin.forward() * forward?)
+            }
+            _ => Err("^ coordinates can't be mixed with other coordinates".to_string()),
+        }
+    }
+
+    /// Reads a position and rounds it to the block it falls in.
+    pub fn block_position(&mut self) -> Result<I64Vec3, String> {
+        self.position()
+            .map(|position| position.round().as_i64vec3())
+    }
+
+    pub fn block(&mut self) -> Result<BlockType, String> {
+        parse_block(&self.word("a block")?)
+    }
+
+    pub fn held(&mut self) -> Result<Held, String> {
+        parse_held(&self.word("a block or item")?)
+    }
+
+    /// Fails if any arguments are left unread, so mistyped commands aren't half run.
+    pub fn finish(&self) -> Result<(), String> {
+        match self.words.get(self.next) {
+            Some(extra) => Err(format!("unexpected argument '{extra}'")),
+            None => Ok(()),
+        }
+    }
+}
+
+struct RegisteredCommand {
+    usage: &'static str,
+    description: &'static str,
+    system: CommandSystemId,
+}
+
+#[derive(Resource, Default)]
+pub struct CommandRegistry {
+    commands: BTreeMap<String, RegisteredCommand>,
+}
+
+impl CommandRegistry {
+    /// Completes the last word of a partly typed command as far as every candidate agrees, the
+    /// command's name first and then each argument from the placeholders in its usage.
+    pub fn complete(&self, line: &str) -> String {
+        let start = line.len() - line.trim_start_matches(['/', ' ']).len();
+        let words: Vec<&str> = line[start..].split_whitespace().collect();
+        let (position, partial) = if line.ends_with(' ') || words.is_empty() {
+            (words.len(), "")
+        } else {
+            (words.len() - 1, words[words.len() - 1])
+        };
+
+        let candidates: Vec<String> = if position == 0 {
+            self.commands.keys().cloned().collect()
+        } else {
+            let Some(command) = self.commands.get(&words[0].to_lowercase()) else {
+                return line.to_string();
+            };
+            usage_candidates(command.usage, position)
+        };
+        let partial_lower = partial.to_lowercase();
+        let matching: Vec<&String> = candidates
+            .iter()
+            .filter(|candidate| candidate.starts_with(&partial_lower))
+            .collect();
+        let Some(first) = matching.first() else {
+            return line.to_string();
+        };
+
+        let mut completed = first.as_str();
+        for candidate in &matching[1..] {
+            let common: usize = completed
+                .chars()
+                .zip(candidate.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a.len_utf8())
+                .sum();
+            completed = &completed[..common];
+        }
+        let mut line = format!("{}{}", &line[..line.len() - partial.len()], completed);
+        if matching.len() == 1 {
+            line.push(' ');
+        }
+        line
+    }
+}
+
+/// Values the argument at `position` of `usage` can take, from placeholders such as `<a|b>` or
+/// `[block]` and literal words such as the `set` of `time set <time>`. Numbers and other
+/// free-form arguments have none.
+fn usage_candidates(usage: &str, position: usize) -> Vec<String> {
+    let mut in_placeholder = false;
+    for (i, token) in usage.split_whitespace().enumerate() {
+        let opens = token.starts_with(['<', '[']);
+        let literal = !in_placeholder && !opens;
+        in_placeholder = (in_placeholder || opens) && !token.ends_with(['>', ']']);
+        if i != position {
+            continue;
+        }
+
+        let name = token.trim_matches(['<', '>', '[', ']']);
+        return if literal {
+            vec![name.to_string()]
+        } else if name == "block" {
+            ALL_BLOCKS
+                .iter()
+                .map(|block| block.name().to_lowercase())
+                .collect()
+        } else if name == "item" {
+            ALL_BLOCKS
+                .iter()
+                .map(|block| block.name().to_lowercase())
+                .chain(ALL_ITEMS.iter().map(|item| item.name().to_lowercase()))
+                .collect()
+        } else if name.contains('|') {
+            name.split('|')
+                .filter(|value| !value.contains('-'))
+                .map(str::to_string)
+                .collect()
+        } else {
+            vec![]
+        };
+    }
+    vec![]
+}
+
+pub trait CommandAppExt {
+    /// Registers `system` to run with the arguments of `/name ...` whenever it is entered.
+    fn add_console_command<M>(
+        &mut self,
+        name: &str,
+        usage: &'static str,
+        description: &'static str,
+        system: impl IntoSystem<In<CommandArgs>, CommandResult, M> + 'static,
+    ) -> &mut Self;
+}
+
+impl CommandAppExt for App {
+    fn add_console_command<M>(
+        &mut self,
+        name: &str,
+        usage: &'static str,
+        description: &'static str,
+        system: impl IntoSystem<In<CommandArgs>, CommandResult, M> + 'static,
+    ) -> &mut Self {
+        self.init_resource::<CommandRegistry>();
+        let system = self.world_mut().register_system(system);
+        self.world_mut()
+            .resource_mut::<CommandRegistry>()
+            .commands
+            .insert(
+                name.to_lowercase(),
+                RegisteredCommand {
+                    usage,
+                    description,
+                    system,
+                },
+            );
+        self
+    }
+}
+
+pub fn run_console_commands(world: &mut World) {
+    let commands: Vec<ConsoleCommand> = world
+        .resource_mut::<Events<ConsoleCommand>>()
+        .drain()
+        .collect();
+
+    let origin = command_origin(world);
+
+    for command in commands {
+        let mut args = CommandArgs::new(command.args);
+        if let Some(origin) = origin {
+            args = args.with_origin(origin);
+        }
+        let system = world
+            .resource::<CommandRegistry>()
+            .commands
+            .get(&command.name)
+            .map(|registered| registered.system);
+        let result = match system {
+            Some(system) => world
+                .run_system_with_input(system, args)
+                .unwrap_or_else(|_| Err(format!("/{} could not be run", command.name))),
+            None => Err(format!("unknown command '{}', try /help", command.name)),
+        };
+
+        let output = match result {
+            Ok(line) => {
+                info!("{}", line);
+                CommandOutput {
+                    line,
+                    is_error: false,
+                }
+            }
+            Err(line) => {
+                warn!("{}", line);
+                CommandOutput {
+                    line,
+                    is_error: true,
+                }
+            }
+        };
+        if !output.line.is_empty() {
+            world.send_event(output);
+        }
+    }
+}
+
+/// The player's position, facing the way their camera looks, or `None` when there is no player
+/// as on a dedicated server.
+fn command_origin(world: &mut World) -> Option<Transform> {
+    let mut origin = *world
+        .query_filtered::<&Transform, With<Player>>()
+        .iter(world)
+        .next()?;
+    // the camera is a child of the player pitched up or down, the player only turns around y
+    if let Some(camera) = world
+        .query_filtered::<&Transform, (With<Camera>, With<Parent>)>()
+        .iter(world)
+        .next()
+    {
+        origin.rotation *= camera.rotation;
+    }
+    Some(origin)
+}
+
+fn help_command(In(args): In<CommandArgs>, registry: Res<CommandRegistry>) -> CommandResult {
+    args.finish()?;
+    Ok(registry
+        .commands
+        .values()
+        .map(|command| format!("/{} - {}", command.usage, command.description))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn parse_coordinate(word: &str) -> Result<f32, String> {
+    word.parse::<f32>()
+        .map_err(|_| format!("'{word}' is not a number"))
+}
+
+/// The offset after a `~` or `^`, nothing meaning no offset.
+fn offset_of(offset: &str) -> Result<f32, String> {
+    if offset.is_empty() {
+        Ok(0.0)
+    } else {
+        parse_coordinate(offset)
+    }
+}
+
+pub fn parse_block(name: &str) -> Result<BlockType, String> {
+    BlockType::from_name(name).ok_or_else(|| format!("unknown block '{name}'"))
+}
+
+pub fn parse_held(name: &str) -> Result<Held, String> {
+    Held::from_name(name).ok_or_else(|| format!("unknown block or item '{name}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        app::App,
+        ecs::system::In,
+        math::{I64Vec3, Vec3},
+        transform::components::Transform,
+    };
+
+    use super::{CommandAppExt, CommandArgs, CommandRegistry, CommandResult, ConsoleCommand};
+    use crate::block::BlockType;
+
+    fn args(line: &str) -> CommandArgs {
+        CommandArgs::new(line.split_whitespace().map(str::to_string).collect())
+    }
+
+    #[test]
+    fn test_parse_console_command() {
+        assert_eq!(
+            Some(ConsoleCommand {
+                name: "benchmark".to_string(),
+                args: vec!["quick".to_string()],
+            }),
+            ConsoleCommand::parse("/Benchmark  quick ")
+        );
+        assert_eq!(None, ConsoleCommand::parse("  "));
+    }
+
+    #[test]
+    fn test_parse_position() {
+        assert_eq!(Ok(Vec3::new(1.5, -2.0, 30.0)), args("1.5 -2 30").position());
+        assert_eq!(
+            Ok(I64Vec3::new(2, -2, 30)),
+            args("1.5 -2.4 30").block_position()
+        );
+        assert!(args("1 2").position().is_err());
+        assert!(args("1 two 3").position().is_err());
+    }
+
+    #[test]
+    fn test_parse_relative_position() {
+        let origin = Transform::from_xyz(10.0, 64.0, -5.0);
+        assert_eq!(
+            Ok(Vec3::new(10.0, 66.0, 0.0)),
+            args("~ ~2 0").with_origin(origin).position()
+        );
+        assert_eq!(
+            Ok(Vec3::new(9.5, 64.0, -5.0)),
+            args("~-0.5 ~ ~").with_origin(origin).position()
+        );
+        assert!(args("~ ~ ~").position().is_err());
+        assert!(args("~x 0 0").with_origin(origin).position().is_err());
+    }
+
+    #[test]
+    fn test_parse_facing_position() {
+        // looking along +z, so the player's left is +x
+        let origin = Transform::from_xyz(10.0, 64.0, -5.0).looking_to(Vec3::Z, Vec3::Y);
+        let position = args("^1 ^ ^2").with_origin(origin).position().unwrap();
+        assert!(position.abs_diff_eq(Vec3::new(11.0, 64.0, -3.0), 1e-5));
+
+        let origin = origin.looking_to(Vec3::Y, Vec3::Z);
+        let position = args("^ ^ ^3").with_origin(origin).position().unwrap();
+        assert!(position.abs_diff_eq(Vec3::new(10.0, 67.0, -5.0), 1e-5));
+
+        assert!(args("^ ~ ^").with_origin(origin).position().is_err());
+        assert!(args("^ ^ ^").position().is_err());
+    }
+
+    #[test]
+    fn test_read_args_in_order() {
+        let mut args = args("3 4 5 sand 2 extra");
+        assert_eq!(Ok(I64Vec3::new(3, 4, 5)), args.block_position());
+        assert_eq!(Ok(BlockType::Sand), args.block());
+        assert_eq!(Ok(2), args.number::<u32>("a count"));
+        assert!(args.finish().is_err());
+        assert_eq!(Some("extra".to_string()), args.optional_word());
+        assert!(args.is_empty());
+        assert_eq!(Ok(()), args.finish());
+        assert!(args.block().is_err());
+    }
+
+    #[test]
+    fn test_complete_from_usage() {
+        let mut app = App::new();
+        let noop = |In(_): In<CommandArgs>| -> CommandResult { Ok(String::new()) };
+        app.add_console_command("setblock", "setblock <x> <y> <z> <block>", "", noop)
+            .add_console_command("seed", "seed", "", noop)
+            .add_console_command("time", "time set <noon|night|0-1>", "", noop)
+            .add_console_command(
+                "summon",
+                "summon <item|falling_block> <block> [x y z]",
+                "",
+                noop,
+            );
+        let registry = app.world().resource::<CommandRegistry>();
+
+        assert_eq!("s", registry.complete("s"));
+        assert_eq!("seed ", registry.complete("see"));
+        assert_eq!("/setblock ", registry.complete("/setb"));
+        assert_eq!(
+            "setblock 1 2 3 glowstone ",
+            registry.complete("setblock 1 2 3 Glo")
+        );
+        assert_eq!("time set ", registry.complete("time s"));
+        assert_eq!("time set n", registry.complete("time set "));
+        assert_eq!("time set noon ", registry.complete("time set noo"));
+        assert_eq!("summon item sand ", registry.complete("summon item sa"));
+        // coordinates can't be completed
+        assert_eq!("summon item sand ", registry.complete("summon item sand "));
+        assert_eq!("unknown ", registry.complete("unknown "));
+    }
+}
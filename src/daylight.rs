@@ -0,0 +1,327 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use bevy::{
+    asset::Assets,
+    diagnostic::{DiagnosticPath, Diagnostics},
+    ecs::{
+        component::Component,
+        query::With,
+        system::{In, Local, Query, Res, ResMut, Resource},
+    },
+    math::Vec3,
+    pbr::{light_consts::lux, CascadeShadowConfig, CascadeShadowConfigBuilder, DirectionalLight},
+    time::Time,
+    transform::components::Transform,
+};
+use serde::Deserialize;
+
+use crate::{
+    chunks::{chunk_loader::ChunkLoader, material::ChunkMaterial},
+    command::{CommandArgs, CommandResult},
+    settings::Settings,
+    weather::Weather,
+    world::World,
+};
+
+/// Sun elevation in radians below which the sun stops casting shadows.
+const MIN_SHADOW_ELEVATION: f32 = 0.05;
+/// Shadow tuning is quantised to this many steps so cascades aren't rebuilt every frame.
+const TUNING_STEPS: f32 = 32.0;
+
+/// How sharp the sun's shadows are and how far from the camera they reach.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShadowQuality {
+    /// Nothing casts shadows.
+    Off,
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl ShadowQuality {
+    /// Width in texels of the shadow map each cascade is rendered to.
+    pub fn map_size(&self) -> usize {
+        match self {
+            Self::Off | Self::Low => 1024,
+            Self::Medium => 2048,
+            Self::High => 4096,
+        }
+    }
+
+    pub fn cascades(&self) -> usize {
+        match self {
+            Self::Off | Self::Low => 2,
+            Self::Medium => 3,
+            Self::High => 4,
+        }
+    }
+
+    /// Furthest from the camera shadows are drawn, however far terrain is rendered.
+    pub fn max_distance(&self) -> f32 {
+        match self {
+            Self::Off => 0.0,
+            Self::Low => 96.0,
+            Self::Medium => 200.0,
+            Self::High => 400.0,
+        }
+    }
+}
+
+/// Distance from the camera shadows reach, as far as terrain is rendered up to the most `quality`
+/// allows, since cascades stretched past the terrain waste their resolution on empty space.
+pub fn shadow_distance(quality: ShadowQuality, render_distance: u32, chunk_size: u16) -> f32 {
+    (render_distance as f32 * chunk_size as f32).min(quality.max_distance())
+}
+
+/// Sky light chunks are currently lit with, from 0 at night to 1 at noon in clear weather.
+pub const DAYLIGHT: DiagnosticPath = DiagnosticPath::const_new("lighting/daylight");
+
+#[derive(Resource)]
+pub struct TimeOfDay {
+    /// Fraction of the day, `0.0` is midnight, `0.25` sunrise and `0.5` noon.
+    pub time: f32,
+    /// Length of a full day in seconds.
+    pub day_length: f32,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self {
+            time: 0.3,
+            day_length: 600.0,
+        }
+    }
+}
+
+impl TimeOfDay {
+    /// Unit vector pointing from the world towards the sun.
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = (self.time - 0.25) * TAU;
+        Vec3::new(angle.cos(), angle.sin(), 0.3).normalize()
+    }
+
+    /// Angle of the sun above the horizon in radians, negative at night.
+    pub fn sun_elevation(&self) -> f32 {
+        self.sun_direction().y.asin()
+    }
+
+    /// Strength of sunlight from `0.0` at night to `1.0` once the sun is well above the horizon.
+    pub fn daylight(&self) -> f32 {
+        let t = ((self.sun_direction().y + 0.1) / 0.35).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+/// `/time` prints the time of day, `/time set <time>` changes it to a named time or a fraction of
+/// the day.
+pub fn time_command(
+    In(mut args): In<CommandArgs>,
+    mut time_of_day: ResMut<TimeOfDay>,
+) -> CommandResult {
+    match args.optional_word().as_deref() {
+        None => Ok(format!("time is {:.3}", time_of_day.time)),
+        Some("set") => {
+            let time = args.word("a time")?;
+            args.finish()?;
+            let time = match time.as_str() {
+                "midnight" => 0.0,
+                "sunrise" => 0.25,
+                "day" => 0.3,
+                "noon" => 0.5,
+                "sunset" => 0.75,
+                "night" => 0.85,
+                fraction => fraction
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|fraction| (0.0..=1.0).contains(fraction))
+                    .ok_or_else(|| {
+                        format!("'{fraction}' is not a named time or a number from 0 to 1")
+                    })?,
+            };
+            time_of_day.time = time.fract();
+            Ok(format!("set time to {:.3}", time_of_day.time))
+        }
+        Some(other) => Err(format!("unknown argument '{other}', try /time set <time>")),
+    }
+}
+
+#[derive(Component)]
+pub struct Sun;
+
+pub fn advance_time_of_day(time: Res<Time>, mut time_of_day: ResMut<TimeOfDay>) {
+    time_of_day.time = (time_of_day.time + time.delta_secs() / time_of_day.day_length).fract();
+}
+
+pub fn update_sun(
+    time_of_day: Res<TimeOfDay>,
+    weather: Res<Weather>,
+    mut sun_query: Query<(&mut Transform, &mut DirectionalLight), With<Sun>>,
+) {
+    let direction = time_of_day.sun_direction();
+    for (mut transform, mut light) in sun_query.iter_mut() {
+        *transform = Transform::default().looking_to(-direction, Vec3::Y);
+        light.illuminance = lux::AMBIENT_DAYLIGHT * time_of_day.daylight() * weather.light();
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShadowTuning {
+    pub enabled: bool,
+    pub depth_bias: f32,
+    pub normal_bias: f32,
+    pub maximum_distance: f32,
+    pub first_cascade_far_bound: f32,
+    pub cascades: usize,
+}
+
+/// Low sun angles stretch shadow texels across voxel faces, so biases grow to avoid acne and
+/// cascades are pulled in to spend resolution closer to the camera. High sun keeps biases small
+/// so shadows don't detach from block edges.
+pub fn shadow_tuning(elevation: f32, maximum_distance: f32) -> ShadowTuning {
+    let grazing = 1.0 - elevation.clamp(0.0, FRAC_PI_2) / FRAC_PI_2;
+    let grazing = (grazing * TUNING_STEPS).round() / TUNING_STEPS;
+    let lerp = |from: f32, to: f32| from + (to - from) * grazing;
+
+    let maximum_distance = maximum_distance * lerp(1.0, 0.5);
+    ShadowTuning {
+        enabled: elevation > MIN_SHADOW_ELEVATION,
+        depth_bias: lerp(0.02, 0.06),
+        normal_bias: lerp(1.0, 3.0),
+        maximum_distance,
+        // short render distances still leave room for the later cascades
+        first_cascade_far_bound: lerp(24.0, 12.0).min(maximum_distance * 0.5),
+        cascades: 4,
+    }
+}
+
+pub fn tune_shadows(
+    time_of_day: Res<TimeOfDay>,
+    world: Res<World>,
+    chunk_loader: Res<ChunkLoader>,
+    mut applied: Local<Option<ShadowTuning>>,
+    mut sun_query: Query<(&mut DirectionalLight, &mut CascadeShadowConfig), With<Sun>>,
+    settings_query: Query<&Settings>,
+) {
+    let (quality, low_spec) = settings_query
+        .get_single()
+        .map_or((ShadowQuality::default(), false), |settings| {
+            (settings.graphics.shadows, settings.graphics.low_spec)
+        });
+    let distance = shadow_distance(quality, chunk_loader.render_distance(), world.chunk_size());
+    let mut tuning = ShadowTuning {
+        cascades: quality.cascades(),
+        ..shadow_tuning(time_of_day.sun_elevation(), distance)
+    };
+    // low spec chunks can't receive shadows, so none are rendered
+    tuning.enabled &= quality != ShadowQuality::Off && !low_spec;
+    if *applied == Some(tuning) {
+        return;
+    }
+
+    for (mut light, mut cascades) in sun_query.iter_mut() {
+        light.shadows_enabled = tuning.enabled;
+        light.shadow_depth_bias = tuning.depth_bias;
+        light.shadow_normal_bias = tuning.normal_bias;
+        *cascades = CascadeShadowConfigBuilder {
+            maximum_distance: tuning.maximum_distance,
+            first_cascade_far_bound: tuning.first_cascade_far_bound,
+            num_cascades: tuning.cascades,
+            ..Default::default()
+        }
+        .build();
+    }
+    *applied = Some(tuning);
+}
+
+/// Sky light is a single uniform on the material every chunk shares, so crossing dawn or dusk
+/// relights the whole loaded area with one write and there is no per-chunk work to spread out.
+/// With no relighting to track the progress of, the `DAYLIGHT` chunks are lit with is reported
+/// instead.
+pub fn update_chunk_lighting(
+    mut diagnostics: Diagnostics,
+    time_of_day: Res<TimeOfDay>,
+    weather: Res<Weather>,
+    chunk_loader: Res<ChunkLoader>,
+    settings_query: Query<&Settings>,
+    mut materials: ResMut<Assets<ChunkMaterial>>,
+) {
+    let debug_cascades = settings_query
+        .get_single()
+        .map(|settings| settings.debug.shadow_cascades as u32)
+        .unwrap_or_default();
+    let daylight = time_of_day.daylight() * weather.light();
+    let sun_direction = time_of_day.sun_direction();
+
+    let Some(material) = materials.get(&chunk_loader.material()) else {
+        return;
+    };
+    diagnostics.add_measurement(&DAYLIGHT, || material.lighting.daylight as f64);
+    // only flat materials light by the sun's direction, so others needn't follow it every frame
+    if (material.lighting.daylight - daylight).abs() < 0.005
+        && material.lighting.debug_cascades == debug_cascades
+        && (!material.flat || material.lighting.sun_direction.dot(sun_direction) > 0.9999)
+    {
+        return;
+    }
+
+    for handle in [chunk_loader.material(), chunk_loader.translucent_material()] {
+        if let Some(material) = materials.get_mut(&handle) {
+            material.lighting.daylight = daylight;
+            material.lighting.debug_cascades = debug_cascades;
+            material.lighting.sun_direction = sun_direction;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::{shadow_distance, shadow_tuning, ShadowQuality, TimeOfDay};
+
+    #[test]
+    fn test_sun_is_highest_at_noon() {
+        let noon = TimeOfDay {
+            time: 0.5,
+            ..Default::default()
+        };
+        let midnight = TimeOfDay {
+            time: 0.0,
+            ..Default::default()
+        };
+
+        assert!(noon.sun_elevation() > 1.0);
+        assert!(midnight.sun_elevation() < -1.0);
+        assert_eq!(1.0, noon.daylight());
+        assert_eq!(0.0, midnight.daylight());
+    }
+
+    #[test]
+    fn test_shadow_tuning_low_sun_increases_bias() {
+        let high = shadow_tuning(FRAC_PI_2, 200.0);
+        let low = shadow_tuning(0.1, 200.0);
+
+        assert!(high.enabled && low.enabled);
+        assert!(low.depth_bias > high.depth_bias);
+        assert!(low.normal_bias > high.normal_bias);
+        assert!(low.maximum_distance < high.maximum_distance);
+        assert_eq!(200.0, high.maximum_distance);
+    }
+
+    #[test]
+    fn test_shadow_tuning_disabled_below_horizon() {
+        assert!(!shadow_tuning(-0.2, 200.0).enabled);
+    }
+
+    #[test]
+    fn test_shadow_distance_follows_render_distance() {
+        assert_eq!(64.0, shadow_distance(ShadowQuality::High, 4, 16));
+        assert_eq!(200.0, shadow_distance(ShadowQuality::Medium, 64, 16));
+        assert!(shadow_distance(ShadowQuality::Low, 64, 16) < 200.0);
+
+        let tuning = shadow_tuning(0.1, shadow_distance(ShadowQuality::High, 1, 16));
+        assert!(tuning.first_cascade_far_bound < tuning.maximum_distance);
+    }
+}
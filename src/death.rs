@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        event::{EventReader, EventWriter},
+        query::With,
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res, ResMut, Resource},
+    },
+    log::{info, warn},
+    math::{I64Vec3, Vec3},
+    state::{
+        condition::in_state,
+        state::{OnEnter, OnExit},
+    },
+    transform::components::Transform,
+};
+
+use crate::{
+    block::BlockType,
+    chunks::chunk_loader::ChunkLoader,
+    interaction::{edit_block, BlockEdited, EditSource},
+    item::{BlockBroken, Held, Inventory, ItemDrop, ITEM_SCALE},
+    player::Player,
+    save::{SavedGravestone, WorldInfo},
+    state::GameState,
+    world::World,
+};
+
+/// Blocks above where a player died searched for room to put their gravestone.
+const GRAVESTONE_SEARCH_HEIGHT: i64 = 8;
+/// Horizontal speed a dead player's belongings are scattered at.
+const SCATTER_SPEED: f32 = 2.0;
+const SCATTER_POP_SPEED: f32 = 5.0;
+
+pub struct DeathPlugin;
+
+impl Plugin for DeathPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Gravestones>()
+            .add_systems(OnExit(GameState::Loading), load_gravestones)
+            .add_systems(OnEnter(GameState::Dead), drop_inventory)
+            .add_systems(
+                Update,
+                open_gravestones
+                    .after(edit_block)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// What each gravestone block in the world holds.
+#[derive(Resource, Debug, Default)]
+pub struct Gravestones(HashMap<I64Vec3, Vec<(Held, u32)>>);
+
+impl Gravestones {
+    fn save(&self, world_info: Option<&WorldInfo>) {
+        let Some(world_info) = world_info else {
+            return;
+        };
+        let saved: Vec<_> = self
+            .0
+            .iter()
+            .map(|(position, items)| SavedGravestone {
+                position: position.to_array(),
+                items: items.clone(),
+            })
+            .collect();
+        if let Err(e) = world_info.save_gravestones(&saved) {
+            warn!("failed to save gravestones: {}", e);
+        }
+    }
+}
+
+fn load_gravestones(world_info: Option<Res<WorldInfo>>, mut gravestones: ResMut<Gravestones>) {
+    let Some(world_info) = world_info else {
+        return;
+    };
+    match world_info.load_gravestones() {
+        Ok(saved) => {
+            gravestones.0 = saved
+                .into_iter()
+                .map(|gravestone| (I64Vec3::from_array(gravestone.position), gravestone.items))
+                .collect();
+        }
+        Err(e) => warn!("failed to load gravestones: {}", e),
+    }
+}
+
+/// Where to put the gravestone of a player who died at `position`: the block they died in, or
+/// the first one above it that isn't solid.
+pub fn gravestone_position(world: &mut World, position: Vec3) -> Option<I64Vec3> {
+    let start = position.round().as_i64vec3();
+    (0..GRAVESTONE_SEARCH_HEIGHT)
+        .map(|dy| start + I64Vec3::Y * dy)
+        .find(|block| !world.get_block(*block).is_solid())
+}
+
+/// Throws a stack of each kind of item out around `position`.
+fn scatter_items(commands: &mut Commands, position: Vec3, items: &[(Held, u32)]) {
+    for (i, (item, count)) in items.iter().enumerate() {
+        let angle = i as f32 / items.len() as f32 * std::f32::consts::TAU;
+        let velocity = Vec3::new(
+            angle.cos() * SCATTER_SPEED,
+            SCATTER_POP_SPEED,
+            angle.sin() * SCATTER_SPEED,
+        );
+        commands.spawn((
+            ItemDrop::new(*item, velocity).with_count(*count),
+            Transform::from_translation(position).with_scale(Vec3::splat(ITEM_SCALE)),
+        ));
+    }
+}
+
+/// Empties a dead player's inventory where they died, into a gravestone if the world's rules
+/// ask for one and there's room, otherwise onto the ground.
+#[allow(clippy::too_many_arguments)]
+fn drop_inventory(
+    mut commands: Commands,
+    world_info: Option<Res<WorldInfo>>,
+    mut world: ResMut<World>,
+    chunk_loader: Res<ChunkLoader>,
+    mut inventory: ResMut<Inventory>,
+    mut gravestones: ResMut<Gravestones>,
+    mut edited: EventWriter<BlockEdited>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    let items: Vec<_> = inventory.iter().collect();
+    if items.is_empty() {
+        return;
+    }
+    *inventory = Inventory::default();
+
+    let use_gravestone = world_info
+        .as_ref()
+        .is_some_and(|info| info.rules.gravestone);
+    let position = use_gravestone
+        .then(|| gravestone_position(&mut world, player.translation))
+        .flatten();
+    let Some(position) = position.filter(|block| world.set_block(*block, BlockType::Gravestone))
+    else {
+        scatter_items(&mut commands, player.translation, &items);
+        return;
+    };
+
+    chunk_loader.remesh_block(&mut commands, &world, position);
+    edited.send(BlockEdited {
+        position,
+        block: BlockType::Gravestone,
+        source: EditSource::Gravestone,
+    });
+    info!(
+        "left a gravestone at {}, {}, {}",
+        position.x, position.y, position.z
+    );
+    gravestones.0.insert(position, items);
+    gravestones.save(world_info.as_deref());
+}
+
+/// Breaking a gravestone spills out what it holds.
+fn open_gravestones(
+    mut commands: Commands,
+    world_info: Option<Res<WorldInfo>>,
+    mut gravestones: ResMut<Gravestones>,
+    mut broken: EventReader<BlockBroken>,
+) {
+    let mut opened = false;
+    for event in broken.read() {
+        if event.block != BlockType::Gravestone {
+            continue;
+        }
+        if let Some(items) = gravestones.0.remove(&event.position) {
+            scatter_items(&mut commands, event.position.as_vec3(), &items);
+            opened = true;
+        }
+    }
+    if opened {
+        gravestones.save(world_info.as_deref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec3, Vec3};
+
+    use super::gravestone_position;
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        world::World,
+    };
+
+    #[test]
+    fn test_gravestone_above_solid_blocks() {
+        let mut world = World::new(1);
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), ChunkData::default());
+        let position = Vec3::new(4.0, 2.6, 4.0);
+        assert_eq!(
+            Some(I64Vec3::new(4, 3, 4)),
+            gravestone_position(&mut world, position)
+        );
+
+        // dying inside blocks, say while flying through them, moves the gravestone up to air
+        world.set_blocks((3..5).map(|y| (I64Vec3::new(4, y, 4), BlockType::Stone)));
+        assert_eq!(
+            Some(I64Vec3::new(4, 5, 4)),
+            gravestone_position(&mut world, position)
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use bevy::{
+    asset::Assets,
+    ecs::system::{Commands, Query, Res, ResMut},
+    math::Vec3,
+    pbr::MeshMaterial3d,
+    prelude::Mesh3d,
+    render::mesh::Mesh,
+    transform::components::Transform,
+};
+
+use crate::{
+    block::BlockType,
+    chunks::{
+        chunk_loader::ChunkLoader, generate::generator::generate_block_mesh, vertex::set_emissive,
+    },
+    settings::Settings,
+};
+
+const CALIBRATION_INTENSITIES: [f32; 7] = [0.0, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
+/// Spawns glowstone cubes at increasing emissive intensities so bloom can be tuned against a
+/// known reference.
+pub fn spawn_emissive_calibration(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    chunk_loader: Res<ChunkLoader>,
+    settings_query: Query<&Settings>,
+) {
+    let Ok(settings) = settings_query.get_single() else {
+        return;
+    };
+    if !settings.debug.emissive_calibration {
+        return;
+    }
+
+    let block_mesh = generate_block_mesh(BlockType::Glowstone);
+
+    let origin = Vec3::new(-6.0, 24.0, 8.0);
+    for (i, intensity) in CALIBRATION_INTENSITIES.into_iter().enumerate() {
+        let mut mesh = block_mesh.clone();
+        set_emissive(&mut mesh, intensity);
+
+        commands.spawn((
+            Mesh3d(meshes.add(mesh)),
+            MeshMaterial3d(chunk_loader.material()),
+            Transform::from_translation(origin + Vec3::new(i as f32 * 2.0, 0.0, 0.0)),
+        ));
+    }
+}
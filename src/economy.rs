@@ -0,0 +1,159 @@
+//! Currency and trades, layered on the inventory so villager trades, shops and scripts can all
+//! read and change what a player can spend through `Wallet`.
+
+use std::fmt;
+
+use bevy::ecs::system::{In, ResMut};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    command::{CommandArgs, CommandResult},
+    item::{Held, Inventory, ItemType},
+};
+
+/// The item money is counted in.
+pub const CURRENCY: ItemType = ItemType::Emerald;
+
+/// Something holding currency.
+pub trait Wallet {
+    fn balance(&self) -> u32;
+
+    fn deposit(&mut self, amount: u32);
+
+    /// Removes `amount` if there is that much, returning whether there was.
+    fn withdraw(&mut self, amount: u32) -> bool;
+
+    fn can_afford(&self, amount: u32) -> bool {
+        self.balance() >= amount
+    }
+}
+
+impl Wallet for Inventory {
+    fn balance(&self) -> u32 {
+        self.count(CURRENCY)
+    }
+
+    fn deposit(&mut self, amount: u32) {
+        self.add(CURRENCY, amount);
+    }
+
+    fn withdraw(&mut self, amount: u32) -> bool {
+        self.take(CURRENCY, amount)
+    }
+}
+
+/// An offer of `count` of `goods` for `price` in currency, which can be taken either way.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trade {
+    pub goods: Held,
+    pub count: u32,
+    pub price: u32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TradeError {
+    CannotAfford { price: u32, balance: u32 },
+    NotEnoughGoods { goods: Held, count: u32, held: u32 },
+}
+
+impl fmt::Display for TradeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CannotAfford { price, balance } => {
+                write!(f, "costs {price} {}, have {balance}", CURRENCY.name())
+            }
+            Self::NotEnoughGoods { goods, count, held } => {
+                write!(f, "needs {count} {}, have {held}", goods.name())
+            }
+        }
+    }
+}
+
+impl Trade {
+    /// Pays the price out of `inventory` for the goods.
+    pub fn buy(&self, inventory: &mut Inventory) -> Result<(), TradeError> {
+        if !inventory.withdraw(self.price) {
+            return Err(TradeError::CannotAfford {
+                price: self.price,
+                balance: inventory.balance(),
+            });
+        }
+        inventory.add(self.goods, self.count);
+        Ok(())
+    }
+
+    /// Gives up the goods from `inventory` for the price.
+    pub fn sell(&self, inventory: &mut Inventory) -> Result<(), TradeError> {
+        if !inventory.take(self.goods, self.count) {
+            return Err(TradeError::NotEnoughGoods {
+                goods: self.goods,
+                count: self.count,
+                held: inventory.count(self.goods),
+            });
+        }
+        inventory.deposit(self.price);
+        Ok(())
+    }
+}
+
+pub fn balance_command(
+    In(mut args): In<CommandArgs>,
+    mut inventory: ResMut<Inventory>,
+) -> CommandResult {
+    let Some(action) = args.optional_word() else {
+        return Ok(format!("{} {}", inventory.balance(), CURRENCY.name()));
+    };
+    let amount: u32 = args.number("amount")?;
+    args.finish()?;
+
+    match action.to_lowercase().as_str() {
+        "add" => inventory.deposit(amount),
+        "take" => {
+            if !inventory.withdraw(amount) {
+                return Err(format!(
+                    "can't take {amount}, only have {}",
+                    inventory.balance()
+                ));
+            }
+        }
+        _ => return Err(format!("unknown action '{action}', expected add or take")),
+    }
+    Ok(format!(
+        "balance is {} {}",
+        inventory.balance(),
+        CURRENCY.name()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Trade, TradeError, Wallet};
+    use crate::{block::BlockType, item::Inventory};
+
+    #[test]
+    fn test_trades() {
+        let mut inventory = Inventory::default();
+        inventory.deposit(5);
+        let trade = Trade {
+            goods: BlockType::Glass.into(),
+            count: 4,
+            price: 3,
+        };
+
+        trade.buy(&mut inventory).unwrap();
+        assert_eq!(2, inventory.balance());
+        assert_eq!(4, inventory.count(BlockType::Glass));
+        assert_eq!(
+            Err(TradeError::CannotAfford {
+                price: 3,
+                balance: 2
+            }),
+            trade.buy(&mut inventory)
+        );
+
+        trade.sell(&mut inventory).unwrap();
+        assert_eq!(5, inventory.balance());
+        assert!(trade.sell(&mut inventory).is_err());
+        assert!(!inventory.withdraw(6));
+    }
+}
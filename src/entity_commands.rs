@@ -0,0 +1,334 @@
+use bevy::{
+    asset::Assets,
+    ecs::{
+        entity::Entity,
+        query::{With, Without},
+        system::{Commands, In, Query, Res, ResMut},
+    },
+    hierarchy::DespawnRecursiveExt,
+    math::Vec3,
+    render::mesh::Mesh,
+    transform::components::Transform,
+};
+
+use crate::{
+    block::BlockType,
+    chunks::chunk_loader::ChunkLoader,
+    command::{CommandArgs, CommandResult},
+    falling_block::{falling_block_bundle, FallingBlock},
+    item::{BlockMeshes, Held, Inventory, ItemDrop, ITEM_SCALE},
+    mob::{mob_bundle, registry::MobRegistry, Mob},
+    net::client::Client,
+    player::Player,
+};
+
+/// Distance in front of the player entities are summoned when no position is given.
+const SUMMON_DISTANCE: f32 = 2.0;
+
+/// Entities that can be created on demand with `/summon`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SummonKind {
+    Item,
+    FallingBlock,
+    /// Any mob. Mobs are summoned by the name of their kind instead, as in `/summon pig`.
+    Mob,
+}
+
+impl SummonKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "item" => Some(Self::Item),
+            "falling_block" => Some(Self::FallingBlock),
+            "mob" => Some(Self::Mob),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Item => "item",
+            Self::FallingBlock => "falling_block",
+            Self::Mob => "mob",
+        }
+    }
+}
+
+/// Which entities `/kill` applies to: `@e` for every entity or `@nearest` for the one closest to
+/// the player, optionally filtered by type as in `@e[type=item]`. A bare type such as `item`
+/// selects every entity of that type.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Selector {
+    pub kind: Option<SummonKind>,
+    pub nearest: bool,
+}
+
+impl Selector {
+    pub fn parse(selector: &str) -> Result<Self, String> {
+        if let Some(kind) = SummonKind::from_name(selector) {
+            return Ok(Self {
+                kind: Some(kind),
+                nearest: false,
+            });
+        }
+
+        let (target, filter) = match selector.split_once('[') {
+            Some((target, filter)) => {
+                let filter = filter
+                    .strip_suffix(']')
+                    .ok_or_else(|| format!("missing ']' in '{selector}'"))?;
+                (target, Some(filter))
+            }
+            None => (selector, None),
+        };
+        let nearest = match target {
+            "@e" => false,
+            "@nearest" => true,
+            _ => return Err(format!("unknown selector '{selector}', try @e or @nearest")),
+        };
+        let kind = match filter.map(|filter| filter.split_once('=')) {
+            None => None,
+            Some(Some(("type", kind))) => Some(
+                SummonKind::from_name(kind).ok_or_else(|| format!("unknown entity '{kind}'"))?,
+            ),
+            Some(_) => return Err(format!("unsupported filter in '{selector}'")),
+        };
+        Ok(Self { kind, nearest })
+    }
+
+    fn matches(&self, kind: SummonKind) -> bool {
+        self.kind.is_none_or(|selected| selected == kind)
+    }
+}
+
+/// Parses `<entity> <block> [x y z]`, the position being `None` when left out.
+pub fn parse_summon(
+    args: &mut CommandArgs,
+) -> Result<(SummonKind, BlockType, Option<Vec3>), String> {
+    let kind = args.word("an entity")?;
+    let kind = SummonKind::from_name(&kind).ok_or_else(|| format!("unknown entity '{kind}'"))?;
+    if kind == SummonKind::Mob {
+        return Err("mobs are summoned by name, e.g. /summon pig".to_string());
+    }
+    let block = args.block()?;
+    let position = if args.is_empty() {
+        None
+    } else {
+        Some(args.position()?)
+    };
+    args.finish()?;
+    Ok((kind, block, position))
+}
+
+pub fn summon_command(
+    In(mut args): In<CommandArgs>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut block_meshes: ResMut<BlockMeshes>,
+    chunk_loader: Res<ChunkLoader>,
+    client: Option<Res<Client>>,
+    mobs: Res<MobRegistry>,
+    player_query: Query<&Transform, With<Player>>,
+) -> CommandResult {
+    let summon_position = |position: Option<Vec3>| match position {
+        Some(position) => Ok(position),
+        None => {
+            let player = player_query
+                .get_single()
+                .map_err(|_| "there is no player to summon next to".to_string())?;
+            Ok::<_, String>(player.translation + player.forward() * SUMMON_DISTANCE)
+        }
+    };
+
+    if let Some((id, definition)) = args.peek().and_then(|name| mobs.by_name(name)) {
+        args.optional_word();
+        let position = if args.is_empty() {
+            None
+        } else {
+            Some(args.position()?)
+        };
+        args.finish()?;
+        let position = summon_position(position)?;
+        commands.spawn(mob_bundle(id, &mobs, position));
+        return Ok(format!(
+            "summoned {} at {:.1}, {:.1}, {:.1}",
+            definition.name, position.x, position.y, position.z
+        ));
+    }
+
+    let (kind, block, position) = parse_summon(&mut args)?;
+    let position = summon_position(position)?;
+
+    match kind {
+        SummonKind::Item => {
+            commands.spawn((
+                ItemDrop::new(block, Vec3::ZERO),
+                Transform::from_translation(position).with_scale(Vec3::splat(ITEM_SCALE)),
+            ));
+        }
+        SummonKind::FallingBlock => {
+            // falling blocks are only simulated by the host
+            if client.is_some() {
+                return Err(
+                    "falling blocks can't be summoned while connected to a server".to_string(),
+                );
+            }
+            commands.spawn(falling_block_bundle(
+                block,
+                position,
+                block_meshes.get(&mut meshes, block),
+                chunk_loader.material_for(block),
+            ));
+        }
+        SummonKind::Mob => unreachable!("parse_summon rejects mob"),
+    }
+    Ok(format!(
+        "summoned {} {} at {:.1}, {:.1}, {:.1}",
+        block.name(),
+        kind.name(),
+        position.x,
+        position.y,
+        position.z
+    ))
+}
+
+/// `/kill <selector>` despawns the selected entities. Players can't be killed.
+pub fn kill_command(
+    In(mut args): In<CommandArgs>,
+    mut commands: Commands,
+    player_query: Query<&Transform, With<Player>>,
+    item_query: Query<(Entity, &Transform), (With<ItemDrop>, Without<Player>)>,
+    falling_query: Query<(Entity, &Transform), (With<FallingBlock>, Without<Player>)>,
+    mob_query: Query<(Entity, &Transform), (With<Mob>, Without<Player>)>,
+) -> CommandResult {
+    let selector = Selector::parse(&args.word("a selector such as @e")?)?;
+    args.finish()?;
+
+    let mut selected: Vec<(Entity, Vec3)> = item_query
+        .iter()
+        .filter(|_| selector.matches(SummonKind::Item))
+        .chain(
+            falling_query
+                .iter()
+                .filter(|_| selector.matches(SummonKind::FallingBlock)),
+        )
+        .chain(
+            mob_query
+                .iter()
+                .filter(|_| selector.matches(SummonKind::Mob)),
+        )
+        .map(|(entity, transform)| (entity, transform.translation))
+        .collect();
+
+    if selector.nearest {
+        let origin = player_query
+            .get_single()
+            .map(|player| player.translation)
+            .map_err(|_| "there is no player to be nearest to".to_string())?;
+        selected = selected
+            .into_iter()
+            .min_by(|(_, a), (_, b)| {
+                a.distance_squared(origin)
+                    .total_cmp(&b.distance_squared(origin))
+            })
+            .into_iter()
+            .collect();
+    }
+
+    for (entity, _) in &selected {
+        commands.entity(*entity).despawn_recursive();
+    }
+    Ok(format!("killed {} entities", selected.len()))
+}
+
+/// `/clear [item]` empties the player's inventory, or just one block or item type from it.
+pub fn clear_command(
+    In(mut args): In<CommandArgs>,
+    mut inventory: ResMut<Inventory>,
+) -> CommandResult {
+    if args.is_empty() {
+        let held: Vec<Held> = inventory.iter().map(|(held, _)| held).collect();
+        let count: u32 = held.into_iter().map(|held| inventory.clear(held)).sum();
+        return Ok(format!("cleared {count} items"));
+    }
+
+    let held = args.held()?;
+    args.finish()?;
+    let count = inventory.clear(held);
+    Ok(format!("cleared {count} {}", held.name()))
+}
+
+/// `/give <item> [count]` adds blocks or items to the player's inventory.
+pub fn give_command(
+    In(mut args): In<CommandArgs>,
+    mut inventory: ResMut<Inventory>,
+) -> CommandResult {
+    let held = args.held()?;
+    let count = if args.is_empty() {
+        1
+    } else {
+        args.number("a count")?
+    };
+    args.finish()?;
+    inventory.add(held, count);
+    Ok(format!("gave {count} {}", held.name()))
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec3;
+
+    use super::{parse_summon, Selector, SummonKind};
+    use crate::{block::BlockType, command::CommandArgs};
+
+    fn args(line: &str) -> CommandArgs {
+        CommandArgs::new(line.split_whitespace().map(str::to_string).collect())
+    }
+
+    #[test]
+    fn test_parse_summon() {
+        assert_eq!(
+            Ok((SummonKind::Item, BlockType::Sand, None)),
+            parse_summon(&mut args("item sand"))
+        );
+        assert_eq!(
+            Ok((
+                SummonKind::FallingBlock,
+                BlockType::Stone,
+                Some(Vec3::new(1.0, 2.5, -3.0))
+            )),
+            parse_summon(&mut args("Falling_Block stone 1 2.5 -3"))
+        );
+        assert!(parse_summon(&mut args("zombie sand")).is_err());
+        assert!(parse_summon(&mut args("item sand 1 2")).is_err());
+        assert!(parse_summon(&mut args("item")).is_err());
+        assert!(parse_summon(&mut args("mob sand")).is_err());
+    }
+
+    #[test]
+    fn test_parse_selector() {
+        let selector = |kind, nearest| Ok(Selector { kind, nearest });
+
+        assert_eq!(selector(None, false), Selector::parse("@e"));
+        assert_eq!(selector(None, true), Selector::parse("@nearest"));
+        assert_eq!(
+            selector(Some(SummonKind::Item), false),
+            Selector::parse("@e[type=item]")
+        );
+        assert_eq!(
+            selector(Some(SummonKind::FallingBlock), true),
+            Selector::parse("@nearest[type=falling_block]")
+        );
+        assert_eq!(
+            selector(Some(SummonKind::Item), false),
+            Selector::parse("item")
+        );
+        assert!(Selector::parse("@p").is_err());
+        assert_eq!(
+            selector(Some(SummonKind::Mob), false),
+            Selector::parse("mob")
+        );
+        assert!(Selector::parse("@e[type=zombie]").is_err());
+        assert!(Selector::parse("@e[type=item").is_err());
+        assert!(Selector::parse("@e[name=item]").is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::{Assets, Handle},
+    color::{Color, LinearRgba},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::EventReader,
+        query::{Added, With, Without},
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res, ResMut, Resource},
+    },
+    log::warn,
+    math::{primitives::Sphere, Vec3},
+    pbr::{MeshMaterial3d, StandardMaterial},
+    prelude::Mesh3d,
+    render::mesh::Mesh,
+    state::{
+        condition::in_state,
+        state::{OnEnter, OnExit},
+    },
+    time::Time,
+    transform::components::Transform,
+};
+
+use crate::{
+    block::BlockType,
+    item::{spawn_item_drops, BlockBroken},
+    physics::{move_and_collide, Collider},
+    player::{Player, Spectator, PLAYER_COLLIDER},
+    save::{SavedPlayer, WorldInfo},
+    state::GameState,
+    world::World,
+};
+
+/// Values orbs are split into, largest first, so big rewards don't spill hundreds of orbs.
+const ORB_VALUES: [u32; 4] = [17, 7, 3, 1];
+const ORB_COLLIDER: Collider = Collider::new(Vec3::splat(-0.1), Vec3::splat(0.1));
+const ORB_GRAVITY: f32 = 10.0;
+const MAX_FALL_SPEED: f32 = 20.0;
+const POP_SPEED: f32 = 3.0;
+/// Seconds before a fresh orb is drawn to the player.
+const ATTRACT_DELAY: f32 = 0.5;
+/// Distance within which orbs fly towards the player, ignoring terrain.
+const ATTRACT_RANGE: f32 = 6.0;
+const ATTRACT_SPEED: f32 = 10.0;
+/// How quickly an attracted orb turns towards the player, per second.
+const ATTRACT_STEERING: f32 = 6.0;
+/// Distance from the middle of the player at which orbs are collected.
+const COLLECT_DISTANCE: f32 = 0.8;
+/// Seconds an orb lies in the world before it despawns.
+const DESPAWN_AGE: f32 = 300.0;
+
+pub struct ExperiencePlugin;
+
+impl Plugin for ExperiencePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Experience>()
+            .init_resource::<OrbAssets>()
+            .add_systems(OnExit(GameState::Loading), load_experience)
+            .add_systems(OnEnter(GameState::Paused), save_experience)
+            .add_systems(
+                Update,
+                (
+                    drop_block_experience.after(spawn_item_drops),
+                    add_orb_meshes,
+                    update_orbs,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Experience points needed to reach `level` from nothing. Each level takes two more points than
+/// the one before.
+pub fn points_for_level(level: u32) -> u32 {
+    level * level + 6 * level
+}
+
+/// The local player's experience, collected from orbs and spent in whole levels.
+#[derive(Resource, Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Experience {
+    points: u32,
+}
+
+impl Experience {
+    pub fn new(points: u32) -> Self {
+        Self { points }
+    }
+
+    pub fn points(&self) -> u32 {
+        self.points
+    }
+
+    pub fn level(&self) -> u32 {
+        let mut level = 0;
+        while points_for_level(level + 1) <= self.points {
+            level += 1;
+        }
+        level
+    }
+
+    /// How far through the current level the player is, from `0.0` to `1.0`.
+    pub fn progress(&self) -> f32 {
+        let level = self.level();
+        let start = points_for_level(level);
+        (self.points - start) as f32 / (points_for_level(level + 1) - start) as f32
+    }
+
+    pub fn add(&mut self, points: u32) {
+        self.points = self.points.saturating_add(points);
+    }
+
+    /// Takes `levels` off the player's level if they have that many, for anything bought with
+    /// levels. Progress towards the next level is kept as far as the lower level allows.
+    pub fn spend_levels(&mut self, levels: u32) -> bool {
+        let level = self.level();
+        if levels > level {
+            return false;
+        }
+        let progress = self.points - points_for_level(level);
+        let lowered = level - levels;
+        let room = points_for_level(lowered + 1) - points_for_level(lowered) - 1;
+        self.points = points_for_level(lowered) + progress.min(room);
+        true
+    }
+}
+
+/// Experience points for breaking a block, for the ores.
+fn block_experience(block: BlockType) -> u32 {
+    match block {
+        BlockType::Coal => 1,
+        BlockType::Iron => 2,
+        BlockType::Gold => 4,
+        _ => 0,
+    }
+}
+
+/// Splits `points` into orb values, largest first.
+fn orb_values(mut points: u32) -> Vec<u32> {
+    let mut values = vec![];
+    while points > 0 {
+        let value = ORB_VALUES
+            .into_iter()
+            .find(|value| *value <= points)
+            .unwrap_or(1);
+        values.push(value);
+        points -= value;
+    }
+    values
+}
+
+/// An orb of experience lying in the world, collected by walking near it.
+#[derive(Component, Debug)]
+pub struct ExperienceOrb {
+    pub value: u32,
+    velocity: Vec3,
+    age: f32,
+}
+
+/// Scatters orbs worth `points` in total from `position`.
+pub fn spawn_orbs(commands: &mut Commands, position: Vec3, points: u32) {
+    let values = orb_values(points);
+    let count = values.len();
+    for (i, value) in values.into_iter().enumerate() {
+        let angle = i as f32 / count as f32 * std::f32::consts::TAU;
+        commands.spawn((
+            ExperienceOrb {
+                value,
+                velocity: Vec3::new(angle.cos(), POP_SPEED, angle.sin()),
+                age: 0.0,
+            },
+            Transform::from_translation(position)
+                .with_scale(Vec3::splat(0.15 + 0.03 * (value as f32).sqrt())),
+        ));
+    }
+}
+
+fn drop_block_experience(mut commands: Commands, mut broken: EventReader<BlockBroken>) {
+    for event in broken.read() {
+        let points = block_experience(event.block);
+        if points > 0 {
+            spawn_orbs(&mut commands, event.position.as_vec3(), points);
+        }
+    }
+}
+
+/// Mesh and material shared by every orb.
+#[derive(Resource, Default)]
+struct OrbAssets {
+    mesh: Option<Handle<Mesh>>,
+    material: Option<Handle<StandardMaterial>>,
+}
+
+fn add_orb_meshes(
+    mut commands: Commands,
+    mut assets: ResMut<OrbAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    orb_query: Query<Entity, Added<ExperienceOrb>>,
+) {
+    for entity in orb_query.iter() {
+        let mesh = assets
+            .mesh
+            .get_or_insert_with(|| meshes.add(Sphere::new(0.5)))
+            .clone();
+        let material = assets
+            .material
+            .get_or_insert_with(|| {
+                materials.add(StandardMaterial {
+                    base_color: Color::srgb(0.6, 1.0, 0.2),
+                    emissive: LinearRgba::rgb(0.8, 2.0, 0.3),
+                    ..Default::default()
+                })
+            })
+            .clone();
+        commands
+            .entity(entity)
+            .insert((Mesh3d(mesh), MeshMaterial3d(material)));
+    }
+}
+
+/// Drops orbs to the ground, or draws them to a nearby player who collects them.
+fn update_orbs(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut world: ResMut<World>,
+    mut experience: ResMut<Experience>,
+    player_query: Query<&Transform, (With<Player>, Without<Spectator>)>,
+    mut orb_query: Query<(Entity, &mut ExperienceOrb, &mut Transform), Without<Player>>,
+) {
+    let delta_secs = time.delta_secs();
+    let player_centre = player_query
+        .get_single()
+        .ok()
+        .map(|player| player.translation + Vec3::Y * PLAYER_COLLIDER.max.y / 2.0);
+
+    for (entity, mut orb, mut transform) in orb_query.iter_mut() {
+        orb.age += delta_secs;
+        if orb.age > DESPAWN_AGE {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        let to_player = player_centre
+            .filter(|_| orb.age >= ATTRACT_DELAY)
+            .map(|centre| centre - transform.translation)
+            .filter(|to_player| to_player.length() < ATTRACT_RANGE);
+        let Some(to_player) = to_player else {
+            orb.velocity.y = (orb.velocity.y - ORB_GRAVITY * delta_secs).max(-MAX_FALL_SPEED);
+            let (position, blocked) = move_and_collide(
+                &mut world,
+                transform.translation,
+                ORB_COLLIDER,
+                orb.velocity * delta_secs,
+            );
+            transform.translation = position;
+            orb.velocity = Vec3::select(blocked, Vec3::ZERO, orb.velocity);
+            continue;
+        };
+
+        if to_player.length() < COLLECT_DISTANCE {
+            experience.add(orb.value);
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        // closer orbs fly faster, so they snap in rather than orbit
+        let speed = ATTRACT_SPEED * (1.0 - to_player.length() / ATTRACT_RANGE).max(0.2);
+        let steering = (ATTRACT_STEERING * delta_secs).min(1.0);
+        orb.velocity = orb.velocity.lerp(to_player.normalize() * speed, steering);
+        transform.translation += orb.velocity * delta_secs;
+    }
+}
+
+fn load_experience(world_info: Option<Res<WorldInfo>>, mut experience: ResMut<Experience>) {
+    let Some(world_info) = world_info else {
+        *experience = Experience::default();
+        return;
+    };
+    match world_info.load_player() {
+        Ok(saved) => *experience = Experience::new(saved.experience),
+        Err(e) => {
+            warn!("failed to load player: {}", e);
+            *experience = Experience::default();
+        }
+    }
+}
+
+/// Writes the player's experience to the world's save, whenever the game is paused.
+fn save_experience(world_info: Option<Res<WorldInfo>>, experience: Res<Experience>) {
+    let Some(world_info) = world_info else {
+        return;
+    };
+    let saved = SavedPlayer {
+        experience: experience.points(),
+    };
+    if let Err(e) = world_info.save_player(&saved) {
+        warn!("failed to save player: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{orb_values, points_for_level, Experience};
+
+    #[test]
+    fn test_levels() {
+        assert_eq!(0, points_for_level(0));
+        assert_eq!(7, points_for_level(1));
+        assert_eq!(16, points_for_level(2));
+
+        let mut experience = Experience::default();
+        assert_eq!(0, experience.level());
+        experience.add(20);
+        assert_eq!(2, experience.level());
+        assert_eq!(4.0 / 11.0, experience.progress());
+
+        assert!(!experience.spend_levels(3));
+        assert!(experience.spend_levels(2));
+        assert_eq!(4, experience.points());
+        assert_eq!(0, experience.level());
+    }
+
+    #[test]
+    fn test_orb_values() {
+        assert_eq!(vec![17, 7, 3, 1, 1], orb_values(29));
+        assert_eq!(vec![1], orb_values(1));
+        assert!(orb_values(0).is_empty());
+    }
+}
@@ -0,0 +1,423 @@
+//! Writes regions of the world to OBJ or glTF files, textured with the block atlas, for use in
+//! other 3D tools.
+
+use std::{
+    error::Error,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bevy::{
+    ecs::system::{In, ResMut},
+    math::{I64Vec3, Vec3},
+    render::mesh::{Indices, Mesh, VertexAttributeValues},
+};
+use serde_json::json;
+
+use crate::{
+    block::BLOCK_COUNT,
+    chunks::{
+        chunk::{ChunkCoordinate, ChunkData},
+        generate::generator::{generate_chunk_mesh, Lighting},
+        vertex::{ChunkVertex, ATTRIBUTE_PACKED_VERTEX, FACE_NORMALS},
+    },
+    command::{CommandArgs, CommandResult},
+    world::World,
+};
+
+pub const EXPORTS_DIR: &str = "exports";
+const BLOCK_TEXTURES: &str = "assets/textures/blocks.png";
+/// Name the block atlas is copied to beside each export.
+const TEXTURE_FILE: &str = "blocks.png";
+/// Longest side of a region that can be exported at once.
+const MAX_EXPORT_SIZE: i64 = 512;
+
+/// Triangles of part of a region, with texture coordinates into the block atlas.
+#[derive(Debug, Default)]
+pub struct ExportMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    /// Wound counter-clockwise, as both formats expect.
+    pub indices: Vec<u32>,
+}
+
+impl ExportMesh {
+    /// Appends a packed chunk mesh whose origin is at `origin`.
+    fn append(&mut self, mesh: &Mesh, origin: Vec3) {
+        let (Some(VertexAttributeValues::Uint32x2(vertices)), Some(Indices::U32(indices))) =
+            (mesh.attribute(ATTRIBUTE_PACKED_VERTEX), mesh.indices())
+        else {
+            return;
+        };
+
+        let start = self.positions.len() as u32;
+        let layers = (BLOCK_COUNT - 1) as f32;
+        for vertex in vertices.iter().map(|packed| ChunkVertex::unpack(*packed)) {
+            self.positions.push((origin + vertex.position).to_array());
+            self.normals
+                .push(FACE_NORMALS[vertex.face as usize % FACE_NORMALS.len()].to_array());
+            self.uvs.push([
+                (vertex.texture as f32 + vertex.uv[0]) / layers,
+                vertex.uv[1],
+            ]);
+        }
+        // chunk meshes are drawn with front faces culled, so they wind the other way
+        for triangle in indices.chunks_exact(3) {
+            self.indices
+                .extend([triangle[0], triangle[2], triangle[1]].map(|i| start + i));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+}
+
+/// A region's opaque and cut out blocks, and its translucent blocks.
+#[derive(Debug, Default)]
+pub struct ExportMeshes {
+    pub solid: ExportMesh,
+    pub translucent: ExportMesh,
+}
+
+/// The blocks of `chunk` at `coord` that lie between `min` and `max` inclusive.
+fn clip_chunk(
+    chunk: &ChunkData,
+    coord: ChunkCoordinate,
+    min: I64Vec3,
+    max: I64Vec3,
+) -> Arc<ChunkData> {
+    let origin = coord.0 * chunk.size as i64;
+    let inside = |position: bevy::math::U16Vec3| {
+        let position = origin + position.as_i64vec3();
+        position.cmpge(min).all() && position.cmple(max).all()
+    };
+
+    let mut clipped = ChunkData::with_size(chunk.size);
+    for (position, block) in chunk.blocks().filter(|(position, _)| inside(*position)) {
+        clipped.set_block_at(position, block);
+    }
+    for (position, level) in chunk
+        .fluid_levels()
+        .filter(|(position, _)| inside(*position))
+    {
+        clipped.set_fluid_at(position, chunk.get_block_at(position), level);
+    }
+    Arc::new(clipped)
+}
+
+/// Meshes the blocks between `min` and `max` inclusive, as the game would draw them with
+/// everything outside the region removed. Chunks that aren't loaded are left out.
+pub fn mesh_region(world: &mut World, min: I64Vec3, max: I64Vec3) -> ExportMeshes {
+    let (min, max) = (min.min(max), min.max(max));
+    let chunk_size = world.chunk_size() as i64;
+    let (min_chunk, max_chunk) = (
+        min.div_euclid(I64Vec3::splat(chunk_size)),
+        max.div_euclid(I64Vec3::splat(chunk_size)),
+    );
+
+    let clipped = |world: &mut World, coord: ChunkCoordinate| {
+        world
+            .get_chunk_data(coord)
+            .map(|chunk| clip_chunk(&chunk, coord, min, max))
+    };
+
+    let mut meshes = ExportMeshes::default();
+    for x in min_chunk.x..=max_chunk.x {
+        for y in min_chunk.y..=max_chunk.y {
+            for z in min_chunk.z..=max_chunk.z {
+                let coord = ChunkCoordinate(I64Vec3::new(x, y, z));
+                let Some(chunk) = clipped(world, coord) else {
+                    continue;
+                };
+                if chunk.empty() {
+                    continue;
+                }
+                let adjacent = coord
+                    .adjacent()
+                    .into_iter()
+                    .map(|coord| clipped(world, coord))
+                    .collect();
+
+                // exported files have no lighting, so corners aren't shaded
+                let chunk_meshes = generate_chunk_mesh(chunk, adjacent, Lighting::Flat);
+                let origin = (coord.0 * chunk_size).as_vec3();
+                meshes.solid.append(&chunk_meshes.solid, origin);
+                if let Some(translucent) = &chunk_meshes.translucent {
+                    meshes.translucent.append(translucent, origin);
+                }
+            }
+        }
+    }
+    meshes
+}
+
+/// OBJ source for `meshes`, using the material library `mtl_file`.
+pub fn to_obj(meshes: &ExportMeshes, mtl_file: &str) -> String {
+    let mut obj = format!("# exported from rustcraft\nmtllib {mtl_file}\nusemtl blocks\n");
+    let mut offset = 1;
+    for (name, mesh) in [
+        ("solid", &meshes.solid),
+        ("translucent", &meshes.translucent),
+    ] {
+        if mesh.is_empty() {
+            continue;
+        }
+        let _ = writeln!(obj, "o {name}");
+        for [x, y, z] in &mesh.positions {
+            let _ = writeln!(obj, "v {x} {y} {z}");
+        }
+        for [x, y, z] in &mesh.normals {
+            let _ = writeln!(obj, "vn {x} {y} {z}");
+        }
+        // OBJ texture coordinates start at the bottom of the image
+        for [u, v] in &mesh.uvs {
+            let _ = writeln!(obj, "vt {u} {}", 1.0 - v);
+        }
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| triangle[i] + offset);
+            let _ = writeln!(obj, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}");
+        }
+        offset += mesh.positions.len() as u32;
+    }
+    obj
+}
+
+fn to_mtl() -> String {
+    format!("newmtl blocks\nKd 1 1 1\nmap_Kd {TEXTURE_FILE}\nmap_d {TEXTURE_FILE}\n")
+}
+
+fn float_bytes<'a>(values: impl Iterator<Item = &'a f32>) -> Vec<u8> {
+    values.flat_map(|value| value.to_le_bytes()).collect()
+}
+
+/// glTF document for `meshes` and the binary buffer it refers to as `bin_file`.
+pub fn to_gltf(meshes: &ExportMeshes, bin_file: &str) -> (serde_json::Value, Vec<u8>) {
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_INT: u32 = 5125;
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+    const NEAREST: u32 = 9728;
+    const CLAMP_TO_EDGE: u32 = 33071;
+
+    let mut buffer: Vec<u8> = vec![];
+    let mut views = vec![];
+    let mut accessors = vec![];
+    let mut primitives = vec![];
+
+    let mut push_view = |buffer: &mut Vec<u8>, bytes: Vec<u8>, target: u32| {
+        views.push(json!({
+            "buffer": 0,
+            "byteOffset": buffer.len(),
+            "byteLength": bytes.len(),
+            "target": target,
+        }));
+        buffer.extend(bytes);
+        views.len() - 1
+    };
+
+    for (material, mesh) in [&meshes.solid, &meshes.translucent].into_iter().enumerate() {
+        if mesh.is_empty() {
+            continue;
+        }
+        let (min, max) = mesh.positions.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), position| {
+                let position = Vec3::from(*position);
+                (min.min(position), max.max(position))
+            },
+        );
+        let count = mesh.positions.len();
+        let positions = push_view(
+            &mut buffer,
+            float_bytes(mesh.positions.iter().flatten()),
+            ARRAY_BUFFER,
+        );
+        let normals = push_view(
+            &mut buffer,
+            float_bytes(mesh.normals.iter().flatten()),
+            ARRAY_BUFFER,
+        );
+        let uvs = push_view(
+            &mut buffer,
+            float_bytes(mesh.uvs.iter().flatten()),
+            ARRAY_BUFFER,
+        );
+        let indices = push_view(
+            &mut buffer,
+            mesh.indices.iter().flat_map(|i| i.to_le_bytes()).collect(),
+            ELEMENT_ARRAY_BUFFER,
+        );
+
+        let first = accessors.len();
+        accessors.extend([
+            json!({"bufferView": positions, "componentType": FLOAT, "count": count, "type": "VEC3",
+                "min": min.to_array(), "max": max.to_array()}),
+            json!({"bufferView": normals, "componentType": FLOAT, "count": count, "type": "VEC3"}),
+            json!({"bufferView": uvs, "componentType": FLOAT, "count": count, "type": "VEC2"}),
+            json!({"bufferView": indices, "componentType": UNSIGNED_INT,
+                "count": mesh.indices.len(), "type": "SCALAR"}),
+        ]);
+        primitives.push(json!({
+            "attributes": {"POSITION": first, "NORMAL": first + 1, "TEXCOORD_0": first + 2},
+            "indices": first + 3,
+            "material": material,
+        }));
+    }
+
+    let material = |alpha_mode: &str| {
+        json!({
+            "name": format!("blocks_{}", alpha_mode.to_lowercase()),
+            "pbrMetallicRoughness": {
+                "baseColorTexture": {"index": 0},
+                "metallicFactor": 0.0,
+                "roughnessFactor": 1.0,
+            },
+            "alphaMode": alpha_mode,
+        })
+    };
+    let document = json!({
+        "asset": {"version": "2.0", "generator": "rustcraft"},
+        "scene": 0,
+        "scenes": [{"nodes": [0]}],
+        "nodes": [{"name": "region", "mesh": 0}],
+        "meshes": [{"primitives": primitives}],
+        "materials": [material("MASK"), material("BLEND")],
+        "textures": [{"source": 0, "sampler": 0}],
+        "images": [{"uri": TEXTURE_FILE}],
+        "samplers": [{"magFilter": NEAREST, "minFilter": NEAREST,
+            "wrapS": CLAMP_TO_EDGE, "wrapT": CLAMP_TO_EDGE}],
+        "buffers": [{"uri": bin_file, "byteLength": buffer.len()}],
+        "bufferViews": views,
+        "accessors": accessors,
+    });
+    (document, buffer)
+}
+
+/// Writes `meshes` to `path`, as glTF if it ends in `.gltf` and otherwise as OBJ, with the files
+/// it refers to beside it.
+pub fn write_export(meshes: &ExportMeshes, path: &Path) -> Result<(), Box<dyn Error>> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or("export needs a file name")?;
+
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "gltf")
+    {
+        let bin_file = format!("{stem}.bin");
+        let (document, buffer) = to_gltf(meshes, &bin_file);
+        fs::write(path, serde_json::to_string_pretty(&document)?)?;
+        fs::write(dir.join(bin_file), buffer)?;
+    } else {
+        let mtl_file = format!("{stem}.mtl");
+        fs::write(path, to_obj(meshes, &mtl_file))?;
+        fs::write(dir.join(mtl_file), to_mtl())?;
+    }
+    fs::copy(BLOCK_TEXTURES, dir.join(TEXTURE_FILE))?;
+    Ok(())
+}
+
+pub fn export_command(In(mut args): In<CommandArgs>, mut world: ResMut<World>) -> CommandResult {
+    let from = args.block_position()?;
+    let to = args.block_position()?;
+    let name = args.word("a file name")?;
+    args.finish()?;
+
+    if (to - from).abs().max_element() >= MAX_EXPORT_SIZE {
+        return Err(format!(
+            "regions can be at most {MAX_EXPORT_SIZE} blocks across"
+        ));
+    }
+    let file_name = Path::new(&name)
+        .file_name()
+        .ok_or_else(|| format!("'{name}' is not a file name"))?;
+    let mut path = PathBuf::from(EXPORTS_DIR).join(file_name);
+    if !path
+        .extension()
+        .is_some_and(|extension| extension == "gltf" || extension == "obj")
+    {
+        path.set_extension("obj");
+    }
+
+    let meshes = mesh_region(&mut world, from, to);
+    if meshes.solid.is_empty() && meshes.translucent.is_empty() {
+        return Err("no loaded blocks in that region".to_string());
+    }
+    write_export(&meshes, &path).map_err(|e| format!("failed to export: {e}"))?;
+    let triangles = (meshes.solid.indices.len() + meshes.translucent.indices.len()) / 3;
+    Ok(format!(
+        "exported {triangles} triangles to {}",
+        path.display()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec3, U16Vec3};
+
+    use super::{mesh_region, to_gltf, to_obj};
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        world::World,
+    };
+
+    fn world() -> World {
+        let mut world = World::new(0);
+        let mut chunk = ChunkData::default();
+        for x in 0..4 {
+            chunk.set_block_at(U16Vec3::new(x, 0, 0), BlockType::Stone);
+        }
+        chunk.set_block_at(U16Vec3::new(0, 1, 0), BlockType::Glass);
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), chunk);
+        world
+    }
+
+    #[test]
+    fn test_mesh_region_clips_blocks() {
+        let mut world = world();
+        let meshes = mesh_region(&mut world, I64Vec3::new(0, 0, 0), I64Vec3::new(1, 0, 0));
+        // two stone blocks in a row, sharing a hidden face, with the glass outside the region
+        assert_eq!(10 * 4, meshes.solid.positions.len());
+        assert!(meshes.translucent.is_empty());
+        assert!(meshes
+            .solid
+            .positions
+            .iter()
+            .all(|[x, _, _]| (-0.5..=1.5).contains(x)));
+
+        let meshes = mesh_region(&mut world, I64Vec3::new(0, 1, 0), I64Vec3::new(0, 0, 0));
+        // the glass face against the stone is hidden
+        assert_eq!(5 * 4, meshes.translucent.positions.len());
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let mut world = world();
+        let meshes = mesh_region(&mut world, I64Vec3::new(-8, -8, -8), I64Vec3::new(8, 8, 8));
+        let faces = (meshes.solid.indices.len() + meshes.translucent.indices.len()) / 3;
+
+        let obj = to_obj(&meshes, "region.mtl");
+        assert_eq!(
+            faces,
+            obj.lines().filter(|line| line.starts_with("f ")).count()
+        );
+
+        let (document, buffer) = to_gltf(&meshes, "region.bin");
+        assert_eq!(buffer.len() as u64, document["buffers"][0]["byteLength"]);
+        assert_eq!(
+            2,
+            document["meshes"][0]["primitives"]
+                .as_array()
+                .unwrap()
+                .len()
+        );
+    }
+}
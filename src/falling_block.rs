@@ -0,0 +1,226 @@
+use bevy::{
+    app::{App, FixedUpdate, Plugin},
+    asset::{Assets, Handle},
+    ecs::{
+        bundle::Bundle,
+        component::Component,
+        entity::Entity,
+        event::{EventReader, EventWriter},
+        schedule::{
+            common_conditions::{not, resource_exists},
+            IntoSystemConfigs,
+        },
+        system::{Commands, ParamSet, Query, Res, ResMut},
+    },
+    math::{I64Vec3, Vec3},
+    pbr::MeshMaterial3d,
+    prelude::Mesh3d,
+    render::mesh::Mesh,
+    state::condition::in_state,
+    time::Time,
+    transform::components::Transform,
+};
+
+use crate::{
+    block::BlockType,
+    chunks::{chunk_loader::ChunkLoader, material::ChunkMaterial},
+    interaction::{BlockEdited, EditSource},
+    item::{BlockMeshes, ItemDrop, ITEM_SCALE},
+    net::client::Client,
+    physics::{move_and_collide, Collider},
+    state::GameState,
+    tick::TickPosition,
+    world::World,
+};
+
+const FALL_GRAVITY: f32 = 20.0;
+const MAX_FALL_SPEED: f32 = 40.0;
+/// Seconds a block may fall before it is removed, so blocks falling into ungenerated chunks
+/// don't fall forever.
+const MAX_FALL_TIME: f32 = 30.0;
+/// Slightly narrower than a block so a falling block slides down a one block wide shaft.
+const FALLING_COLLIDER: Collider = Collider::new(Vec3::splat(-0.49), Vec3::splat(0.49));
+
+/// Turns unsupported sand into falling entities that land and become blocks again, simulated on
+/// the fixed tick. Only the host simulates falling blocks, clients receive the resulting edits
+/// from the server.
+pub struct FallingBlockPlugin;
+
+impl Plugin for FallingBlockPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (start_falling_blocks, update_falling_blocks)
+                .chain()
+                .run_if(in_state(GameState::InGame))
+                .run_if(not(resource_exists::<Client>)),
+        );
+    }
+}
+
+/// A block falling through the air, to be placed back into the world where it lands.
+#[derive(Component, Debug)]
+pub struct FallingBlock {
+    pub block: BlockType,
+    velocity: f32,
+    time: f32,
+}
+
+/// Components of `block` starting to fall from `position`.
+pub fn falling_block_bundle(
+    block: BlockType,
+    position: Vec3,
+    mesh: Handle<Mesh>,
+    material: Handle<ChunkMaterial>,
+) -> impl Bundle {
+    (
+        FallingBlock {
+            block,
+            velocity: 0.0,
+            time: 0.0,
+        },
+        TickPosition::new(position),
+        Transform::from_translation(position),
+        Mesh3d(mesh),
+        MeshMaterial3d(material),
+    )
+}
+
+/// Blocks from `start` upwards that are left unsupported, bottom first. Gravity blocks stacked
+/// on each other fall together.
+pub fn unsupported_blocks(world: &mut World, start: I64Vec3) -> Vec<I64Vec3> {
+    if world.get_block(start - I64Vec3::Y).is_solid() {
+        return vec![];
+    }
+
+    (0..)
+        .map(|y| start + I64Vec3::Y * y)
+        .take_while(|position| world.get_block(*position).has_gravity())
+        .collect()
+}
+
+pub fn start_falling_blocks(
+    mut commands: Commands,
+    mut world: ResMut<World>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut block_meshes: ResMut<BlockMeshes>,
+    chunk_loader: Res<ChunkLoader>,
+    mut events: ParamSet<(EventReader<BlockEdited>, EventWriter<BlockEdited>)>,
+) {
+    // an edit can leave the block itself unsupported, or take away the support of the one above
+    let starts: Vec<I64Vec3> = events
+        .p0()
+        .read()
+        .flat_map(|edit| [edit.position, edit.position + I64Vec3::Y])
+        .collect();
+
+    let mut edits = vec![];
+    for start in starts {
+        for position in unsupported_blocks(&mut world, start) {
+            let block = world.get_block(position);
+            if !world.set_block(position, BlockType::Air) {
+                continue;
+            }
+            chunk_loader.remesh_block(&mut commands, &world, position);
+            edits.push(BlockEdited {
+                position,
+                block: BlockType::Air,
+                source: EditSource::FallingBlock,
+            });
+
+            commands.spawn(falling_block_bundle(
+                block,
+                position.as_vec3(),
+                block_meshes.get(&mut meshes, block),
+                chunk_loader.material_for(block),
+            ));
+        }
+    }
+    events.p1().send_batch(edits);
+}
+
+pub fn update_falling_blocks(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut world: ResMut<World>,
+    chunk_loader: Res<ChunkLoader>,
+    mut falling_query: Query<(Entity, &mut FallingBlock, &mut TickPosition)>,
+    mut edited: EventWriter<BlockEdited>,
+) {
+    let delta_secs = time.delta_secs();
+    for (entity, mut falling, mut tick_position) in falling_query.iter_mut() {
+        falling.time += delta_secs;
+        if falling.time > MAX_FALL_TIME {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        falling.velocity = (falling.velocity - FALL_GRAVITY * delta_secs).max(-MAX_FALL_SPEED);
+        let (position, blocked) = move_and_collide(
+            &mut world,
+            tick_position.current,
+            FALLING_COLLIDER,
+            Vec3::Y * falling.velocity * delta_secs,
+        );
+        tick_position.current = position;
+        if !blocked.y {
+            continue;
+        }
+
+        commands.entity(entity).despawn();
+        let landed = position.round().as_i64vec3();
+        let block = falling.block;
+        if !world.get_block(landed).is_solid() && world.set_block(landed, block) {
+            chunk_loader.remesh_block(&mut commands, &world, landed);
+            edited.send(BlockEdited {
+                position: landed,
+                block,
+                source: EditSource::FallingBlock,
+            });
+        } else {
+            // nowhere to settle, so leave it to be picked up instead
+            commands.spawn((
+                ItemDrop::new(block, Vec3::ZERO),
+                Transform::from_translation(position).with_scale(Vec3::splat(ITEM_SCALE)),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec3, U16Vec3};
+
+    use super::unsupported_blocks;
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        world::World,
+    };
+
+    #[test]
+    fn test_unsupported_blocks_fall_as_a_column() {
+        let mut world = World::new(0);
+        let mut chunk_data = ChunkData::default();
+        chunk_data.set_block_at(U16Vec3::new(4, 0, 4), BlockType::Stone);
+        for y in 2..5 {
+            chunk_data.set_block_at(U16Vec3::new(4, y, 4), BlockType::Sand);
+        }
+        chunk_data.set_block_at(U16Vec3::new(4, 5, 4), BlockType::Grass);
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), chunk_data);
+
+        assert_eq!(
+            vec![
+                I64Vec3::new(4, 2, 4),
+                I64Vec3::new(4, 3, 4),
+                I64Vec3::new(4, 4, 4)
+            ],
+            unsupported_blocks(&mut world, I64Vec3::new(4, 2, 4))
+        );
+        // the column is only unsupported from its base
+        assert!(unsupported_blocks(&mut world, I64Vec3::new(4, 3, 4)).is_empty());
+
+        world.set_block(I64Vec3::new(4, 1, 4), BlockType::Stone);
+        assert!(unsupported_blocks(&mut world, I64Vec3::new(4, 2, 4)).is_empty());
+    }
+}
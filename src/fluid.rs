@@ -0,0 +1,232 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::{
+    app::{App, FixedUpdate, Plugin},
+    ecs::{
+        event::{EventReader, EventWriter},
+        schedule::{
+            common_conditions::{not, resource_exists},
+            IntoSystemConfigs,
+        },
+        system::{Commands, ParamSet, Res, ResMut, Resource},
+    },
+    math::I64Vec3,
+    state::condition::in_state,
+};
+
+use crate::{
+    block::BlockType,
+    chunks::{chunk::FULL_FLUID_LEVEL, chunk_loader::ChunkLoader},
+    interaction::{BlockEdited, EditSource},
+    net::client::Client,
+    state::GameState,
+    world::World,
+};
+
+/// Ticks between flow steps, so spreading water is visible.
+const FLOW_TICKS: u32 = 5;
+/// Cells examined per step, so breaking into a flooded cave doesn't stall a frame.
+const MAX_ACTIVE_PER_STEP: usize = 4096;
+const HORIZONTAL: [I64Vec3; 4] = [I64Vec3::X, I64Vec3::NEG_X, I64Vec3::Z, I64Vec3::NEG_Z];
+
+/// Spreads water into neighbouring air every few ticks. Each step water falls into air
+/// below it, or failing that spreads sideways one level lower than itself, so it runs out after
+/// a few blocks. Only the host simulates flow, clients receive the resulting edits.
+pub struct FluidPlugin;
+
+impl Plugin for FluidPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FluidSimulation>().add_systems(
+            FixedUpdate,
+            flow_fluids
+                .run_if(in_state(GameState::InGame))
+                .run_if(not(resource_exists::<Client>)),
+        );
+    }
+}
+
+/// Cells that may flow on the next step: edited blocks and their neighbours.
+#[derive(Resource, Default)]
+pub struct FluidSimulation {
+    active: HashSet<I64Vec3>,
+    ticks: u32,
+}
+
+/// Changes the next flow step makes from the `active` cells, as the fluid and level each cell
+/// becomes, ordered by position.
+pub fn flow_step(
+    world: &mut World,
+    active: impl IntoIterator<Item = I64Vec3>,
+) -> Vec<(I64Vec3, BlockType, u8)> {
+    let mut updates: HashMap<I64Vec3, (BlockType, u8)> = HashMap::new();
+    let mut offer = |position: I64Vec3, block: BlockType, level: u8| {
+        let entry = updates.entry(position).or_insert((block, level));
+        entry.1 = entry.1.max(level);
+    };
+
+    for position in active {
+        let block = world.get_block(position);
+        if !block.flows() {
+            continue;
+        }
+        let level = world.get_fluid_level(position);
+
+        let below = world.get_block(position - I64Vec3::Y);
+        if below == BlockType::Air {
+            // falling water only slowly loses its level, so it can still spread once it lands
+            offer(position - I64Vec3::Y, block, FULL_FLUID_LEVEL - 1);
+            continue;
+        }
+        // water resting on thinner water keeps merging downwards rather than spreading
+        if below == block && world.get_fluid_level(position - I64Vec3::Y) < FULL_FLUID_LEVEL {
+            continue;
+        }
+        if level <= 1 {
+            continue;
+        }
+
+        for direction in HORIZONTAL {
+            let neighbour = position + direction;
+            let neighbour_block = world.get_block(neighbour);
+            if neighbour_block == BlockType::Air
+                || (neighbour_block == block && world.get_fluid_level(neighbour) < level - 1)
+            {
+                offer(neighbour, block, level - 1);
+            }
+        }
+    }
+
+    let mut updates: Vec<(I64Vec3, BlockType, u8)> = updates
+        .into_iter()
+        .map(|(position, (block, level))| (position, block, level))
+        .collect();
+    updates.sort_by_key(|(position, ..)| position.to_array());
+    updates
+}
+
+pub fn flow_fluids(
+    mut commands: Commands,
+    mut simulation: ResMut<FluidSimulation>,
+    mut world: ResMut<World>,
+    chunk_loader: Res<ChunkLoader>,
+    mut events: ParamSet<(EventReader<BlockEdited>, EventWriter<BlockEdited>)>,
+) {
+    for edit in events.p0().read() {
+        simulation.active.insert(edit.position);
+        simulation.active.extend(
+            HORIZONTAL
+                .into_iter()
+                .chain([I64Vec3::Y, I64Vec3::NEG_Y])
+                .map(|direction| edit.position + direction),
+        );
+    }
+
+    simulation.ticks += 1;
+    if simulation.ticks < FLOW_TICKS {
+        return;
+    }
+    simulation.ticks = 0;
+
+    let mut active = std::mem::take(&mut simulation.active);
+    let step: Vec<I64Vec3> = active.iter().copied().take(MAX_ACTIVE_PER_STEP).collect();
+    for position in &step {
+        active.remove(position);
+    }
+    simulation.active = active;
+
+    let mut edits = vec![];
+    for (position, block, level) in flow_step(&mut world, step) {
+        if !world.set_fluid(position, block, level) {
+            continue;
+        }
+        chunk_loader.remesh_block(&mut commands, &world, position);
+        // flowing into a cell is an edit like any other, which keeps the flow going next step
+        edits.push(BlockEdited {
+            position,
+            block,
+            source: EditSource::Fluid,
+        });
+    }
+    events.p1().send_batch(edits);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec3, U16Vec3};
+
+    use super::flow_step;
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData, FULL_FLUID_LEVEL},
+        world::World,
+    };
+
+    /// A world with a single chunk whose bottom layer is stone.
+    fn floored_world() -> World {
+        let mut world = World::new(0);
+        let mut chunk_data = ChunkData::default();
+        for x in 0..chunk_data.size {
+            for z in 0..chunk_data.size {
+                chunk_data.set_block_at(U16Vec3::new(x, 0, z), BlockType::Stone);
+            }
+        }
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), chunk_data);
+        world
+    }
+
+    fn run_step(world: &mut World, active: &[I64Vec3]) -> Vec<I64Vec3> {
+        let updates = flow_step(world, active.iter().copied());
+        for (position, block, level) in &updates {
+            world.set_fluid(*position, *block, *level);
+        }
+        updates.into_iter().map(|(position, ..)| position).collect()
+    }
+
+    #[test]
+    fn test_water_spreads_and_runs_out() {
+        let mut world = floored_world();
+        let source = I64Vec3::new(8, 1, 8);
+        world.set_block(source, BlockType::Water);
+
+        let mut active = vec![source];
+        for _ in 0..FULL_FLUID_LEVEL {
+            active = run_step(&mut world, &active);
+        }
+        assert!(
+            active.is_empty(),
+            "flow should stop, still active {active:?}"
+        );
+
+        assert_eq!(FULL_FLUID_LEVEL, world.get_fluid_level(source));
+        assert_eq!(
+            FULL_FLUID_LEVEL - 1,
+            world.get_fluid_level(source + I64Vec3::X)
+        );
+        assert_eq!(1, world.get_fluid_level(source + I64Vec3::X * 7));
+        assert_eq!(BlockType::Water, world.get_block(source + I64Vec3::X * 7));
+        assert_eq!(BlockType::Air, world.get_block(source + I64Vec3::X * 8));
+        assert_eq!(BlockType::Air, world.get_block(source + I64Vec3::Y));
+    }
+
+    #[test]
+    fn test_water_falls_before_spreading() {
+        let mut world = floored_world();
+        let source = I64Vec3::new(8, 3, 8);
+        world.set_block(source, BlockType::Water);
+
+        assert_eq!(vec![source - I64Vec3::Y], run_step(&mut world, &[source]));
+        assert_eq!(
+            FULL_FLUID_LEVEL - 1,
+            world.get_fluid_level(source - I64Vec3::Y)
+        );
+
+        assert_eq!(
+            vec![source - I64Vec3::Y * 2],
+            run_step(&mut world, &[source - I64Vec3::Y])
+        );
+
+        // landed on the floor, so the fallen water spreads
+        let spread = run_step(&mut world, &[source - I64Vec3::Y * 2]);
+        assert_eq!(4, spread.len());
+    }
+}
@@ -0,0 +1,219 @@
+//! Distance fog on chunks, coloured and spaced by the biome the camera is in, or drawn in close
+//! while the camera is under water or lava.
+
+use std::{collections::HashMap, error::Error, fs};
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::Assets,
+    color::{Color, ColorToComponents, LinearRgba},
+    core_pipeline::core_3d::Camera3d,
+    ecs::{
+        query::With,
+        schedule::IntoSystemConfigs,
+        system::{Local, Query, Res, ResMut, Resource},
+    },
+    log::warn,
+    math::I64Vec2,
+    state::condition::in_state,
+    time::Time,
+    transform::components::GlobalTransform,
+};
+use serde::Deserialize;
+
+use crate::{
+    block::BlockType,
+    chunks::{
+        chunk_loader::ChunkLoader,
+        generate::biome::Biome,
+        material::{ChunkFog, ChunkMaterial},
+    },
+    state::GameState,
+    ui::underwater::fluid_surface,
+    world::World,
+};
+
+pub const FOG_FILE: &str = "assets/fog.ron";
+/// The fog built into the game, used when `FOG_FILE` can't be read.
+const BUNDLED_FOG: &str = include_str!("../assets/fog.ron");
+const TRANSITION_RATE: f32 = 0.5;
+const WATER_FOG: BiomeFog = BiomeFog {
+    color: [30, 60, 130],
+    start: 2.0,
+    end: 28.0,
+};
+const LAVA_FOG: BiomeFog = BiomeFog {
+    color: [200, 70, 10],
+    start: 0.0,
+    end: 3.0,
+};
+
+/// Loads `BiomeFogs` from `FOG_FILE` and fades chunk fog towards the camera's biome.
+pub struct FogPlugin;
+
+impl Plugin for FogPlugin {
+    fn build(&self, app: &mut App) {
+        let fogs = fs::read_to_string(FOG_FILE)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|source| BiomeFogs::parse(&source))
+            .unwrap_or_else(|e| {
+                warn!("failed to load {}, using built in fog: {}", FOG_FILE, e);
+                BiomeFogs::default()
+            });
+        app.insert_resource(fogs)
+            .add_systems(Update, update_fog.run_if(in_state(GameState::InGame)));
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct BiomeFog {
+    /// sRGB colour of the fog at noon.
+    pub color: [u8; 3],
+    /// Distance from the camera in blocks at which fog begins.
+    pub start: f32,
+    /// Distance at which terrain is hidden entirely.
+    pub end: f32,
+}
+
+impl BiomeFog {
+    /// The fog to draw with chunks loaded `max_distance` blocks around the camera, pulled in
+    /// so terrain is hidden before the edge of the loaded chunks.
+    pub fn chunk_fog(&self, max_distance: f32) -> ChunkFog {
+        let scale = (max_distance / self.end).min(1.0);
+        let [r, g, b] = self.color;
+        ChunkFog {
+            color: LinearRgba::from(Color::srgb_u8(r, g, b)).to_vec4(),
+            start: self.start * scale,
+            end: self.end * scale,
+        }
+    }
+}
+
+/// The fog of each biome, read from `FOG_FILE`.
+#[derive(Resource, Debug, Clone)]
+pub struct BiomeFogs {
+    biomes: HashMap<Biome, BiomeFog>,
+}
+
+impl Default for BiomeFogs {
+    fn default() -> Self {
+        Self::parse(BUNDLED_FOG).expect("built in fog is valid")
+    }
+}
+
+impl BiomeFogs {
+    /// Reads a map from biome to its fog, which must cover every biome.
+    pub fn parse(source: &str) -> Result<Self, Box<dyn Error>> {
+        let biomes: HashMap<Biome, BiomeFog> = ron::from_str(source)?;
+        for biome in [
+            Biome::Ocean,
+            Biome::Desert,
+            Biome::Plains,
+            Biome::Mountains,
+            Biome::Snow,
+        ] {
+            let Some(fog) = biomes.get(&biome) else {
+                return Err(format!("no fog for {:?}", biome).into());
+            };
+            if !(0.0..fog.end).contains(&fog.start) {
+                return Err(format!("fog for {:?} must start before it ends", biome).into());
+            }
+        }
+        Ok(Self { biomes })
+    }
+
+    pub fn get(&self, biome: Biome) -> BiomeFog {
+        self.biomes[&biome]
+    }
+}
+
+/// The fog seen from inside `fluid`, if it's one.
+fn fluid_fog(fluid: BlockType) -> Option<BiomeFog> {
+    match fluid {
+        BlockType::Water => Some(WATER_FOG),
+        BlockType::Lava => Some(LAVA_FOG),
+        _ => None,
+    }
+}
+
+/// Fades the fog of both chunk materials towards that of the biome the camera is over. Columns
+/// without ground, such as in the void, have the fog of plains. Under water or lava the fog of
+/// the fluid is switched to at once.
+#[allow(clippy::too_many_arguments)]
+pub fn update_fog(
+    time: Res<Time>,
+    mut world: ResMut<World>,
+    fogs: Res<BiomeFogs>,
+    chunk_loader: Res<ChunkLoader>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    mut current: Local<Option<ChunkFog>>,
+    mut was_submerged: Local<bool>,
+    mut materials: ResMut<Assets<ChunkMaterial>>,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let position = camera.translation();
+    let column = I64Vec2::new(position.x.floor() as i64, position.z.floor() as i64);
+    let biome = world
+        .surface(column)
+        .map_or(Biome::Plains, Biome::from_surface);
+    let max_distance = (chunk_loader.render_distance() * world.chunk_size() as u32) as f32;
+    let submerged = fluid_surface(&mut world, position)
+        .filter(|(_, surface)| *surface > position.y)
+        .and_then(|(fluid, _)| fluid_fog(fluid));
+    let target = submerged.unwrap_or(fogs.get(biome)).chunk_fog(max_distance);
+
+    let t = 1.0 - (-TRANSITION_RATE * time.delta_secs()).exp();
+    let fog = match *current {
+        Some(current) if *was_submerged == submerged.is_some() => current.lerp(target, t),
+        _ => target,
+    };
+    *current = Some(fog);
+    *was_submerged = submerged.is_some();
+
+    let Some(material) = materials.get(&chunk_loader.material()) else {
+        return;
+    };
+    // fog close enough to the target isn't worth uploading again every frame
+    if material.fog.color.distance(fog.color) < 0.002
+        && (material.fog.start - fog.start).abs() < 0.5
+        && (material.fog.end - fog.end).abs() < 0.5
+    {
+        return;
+    }
+    for handle in [chunk_loader.material(), chunk_loader.translucent_material()] {
+        if let Some(material) = materials.get_mut(&handle) {
+            material.fog = fog;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BiomeFogs, BUNDLED_FOG};
+    use crate::chunks::generate::biome::Biome;
+
+    #[test]
+    fn test_parse_biome_fogs() {
+        let fogs = BiomeFogs::parse(BUNDLED_FOG).unwrap();
+        assert!(fogs.get(Biome::Snow).end < fogs.get(Biome::Plains).end);
+
+        let missing = "{ Plains: (color: (0, 0, 0), start: 10.0, end: 20.0) }";
+        assert!(BiomeFogs::parse(missing).is_err());
+        let backwards = BUNDLED_FOG.replace("start: 60.0, end: 180.0", "start: 180.0, end: 60.0");
+        assert!(BiomeFogs::parse(&backwards).is_err());
+    }
+
+    #[test]
+    fn test_fog_is_pulled_in_to_render_distance() {
+        let fog = BiomeFogs::default().get(Biome::Plains);
+        let far = fog.chunk_fog(1000.0);
+        assert_eq!((fog.start, fog.end), (far.start, far.end));
+
+        let near = fog.chunk_fog(fog.end / 2.0);
+        assert_eq!(fog.end / 2.0, near.end);
+        assert_eq!(fog.start / 2.0, near.start);
+        assert_eq!(far.color, near.color);
+    }
+}
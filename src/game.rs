@@ -0,0 +1,405 @@
+use crate::{
+    ai::AiPlugin,
+    ambience::{update_color_grading, AmbienceGrading},
+    audio::{
+        ambient::AmbientSoundPlugin,
+        apply_master_volume,
+        blocks::{play_block_sounds, play_footsteps, BlockSounds},
+        fluid::{update_fluid_emitters, FluidSounds},
+        music::MusicPlugin,
+    },
+    benchmark::{benchmark_command, run_benchmark},
+    block_registry::BlockRegistryPlugin,
+    breaking::{show_break_progress, spawn_crack_overlay, BreakProgress},
+    breath::{Breath, BreathPlugin},
+    chunk_entities::ChunkEntityPlugin,
+    chunks::{
+        chunk_loader::{
+            evict_chunk_data, gather_chunks, lighting_command, mark_chunks,
+            measure_resident_chunks, receive_chunk_results, save_resident_chunks, unload_chunks,
+            ChunkLoader, RESIDENT_CHUNKS, RESIDENT_CHUNK_MEGABYTES,
+        },
+        foliage::{update_foliage, FoliageAssets},
+        generate::{
+            structure::{structures_command, StructurePlugin},
+            terrain::GeneratorRegistry,
+        },
+        material::{ChunkFog, ChunkLighting, ChunkMaterial, ChunkMaterialPlugin, FlatColors},
+    },
+    clouds::CloudPlugin,
+    command::{CommandAppExt, CommandPlugin},
+    daylight::{
+        advance_time_of_day, time_command, tune_shadows, update_chunk_lighting, update_sun, Sun,
+        TimeOfDay, DAYLIGHT,
+    },
+    death::DeathPlugin,
+    debug::spawn_emissive_calibration,
+    economy::balance_command,
+    entity_commands::{clear_command, give_command, kill_command, summon_command},
+    experience::ExperiencePlugin,
+    export::export_command,
+    falling_block::FallingBlockPlugin,
+    fluid::FluidPlugin,
+    fog::FogPlugin,
+    interaction::{
+        edit_block, fill_command, highlight_target_block, paste_command, select_item,
+        setblock_command, target_block, BlockEdited, SelectedItem, TargetBlock,
+    },
+    item::ItemPlugin,
+    loading::{place_player_at_spawn, reset_loading_progress, update_loading, LoadingProgress},
+    mob::{Health, MobPlugin},
+    modding::mods_command,
+    net::NetworkPlugin,
+    particles::ParticlePlugin,
+    player::{
+        player_look, player_move, tp_command, update_sprint_fov, PlayerBundle, PLAYER_EYE_HEIGHT,
+        PLAYER_MAX_HEALTH,
+    },
+    rules::gamerule_command,
+    save::WorldInfo,
+    settings::{read_settings, SETTINGS_FILE},
+    sky::SkyPlugin,
+    state::{grab_cursor, release_cursor, toggle_pause, GameState},
+    third_person::ThirdPersonPlugin,
+    tick::TickPlugin,
+    ui::{
+        console::{console_closed, ConsolePlugin},
+        death::DeathScreenPlugin,
+        hud::HudPlugin,
+        loading::LoadingScreenPlugin,
+        main_menu::MainMenuPlugin,
+        pause::PauseMenuPlugin,
+        toast::ToastPlugin,
+        underwater::UnderwaterPlugin,
+        watch::{watch_command, WatchPlugin},
+    },
+    weather::{weather_command, WeatherPlugin},
+    world::{history::rollback_command, import::import_command, seed_command},
+};
+use bevy::{
+    core_pipeline::{bloom::Bloom, tonemapping::Tonemapping},
+    diagnostic::{Diagnostic, RegisterDiagnostic},
+    pbr::DirectionalLightShadowMap,
+    prelude::*,
+    render::view::ColorGrading,
+};
+
+fn setup_scene(mut commands: Commands, mut chunk_materials: ResMut<Assets<ChunkMaterial>>) {
+    let settings = read_settings(SETTINGS_FILE).expect("Failed to read settings.toml");
+
+    let spawn = Vec3::new(0.0, 20.0, 0.0);
+
+    let player = commands
+        .spawn((
+            PlayerBundle {
+                transform: Transform::from_xyz(spawn.x, spawn.y, spawn.z)
+                    .looking_to(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 1.0, 0.0)),
+                ..default()
+            },
+            Health::new(PLAYER_MAX_HEALTH),
+            Breath::default(),
+        ))
+        .id();
+
+    let render_distance = 64;
+    let camera = commands
+        .spawn((
+            Transform::from_xyz(0.0, PLAYER_EYE_HEIGHT, 0.0),
+            Camera3d { ..default() },
+            Camera {
+                hdr: true,
+                ..default()
+            },
+            Tonemapping::None,
+            ColorGrading::default(),
+            AmbienceGrading::default(),
+            SpatialListener::new(0.3),
+            Msaa::Off,
+        ))
+        .id();
+    commands.entity(player).add_children(&[camera]);
+    if settings.graphics.bloom {
+        commands.entity(camera).insert(Bloom::NATURAL);
+    }
+
+    let chunk_material_handle = chunk_materials.add(ChunkMaterial {
+        color: LinearRgba::WHITE,
+        texture: None,
+        lighting: ChunkLighting::default(),
+        alpha_mode: AlphaMode::Opaque,
+        flat_colors: FlatColors::default(),
+        flat: settings.graphics.low_spec,
+        fog: ChunkFog::default(),
+    });
+    let translucent_material_handle = chunk_materials.add(ChunkMaterial {
+        color: LinearRgba::WHITE,
+        texture: None,
+        lighting: ChunkLighting::default(),
+        alpha_mode: AlphaMode::Blend,
+        flat_colors: FlatColors::default(),
+        flat: settings.graphics.low_spec,
+        fog: ChunkFog::default(),
+    });
+    let chunk_loader = ChunkLoader::new(render_distance as u32, chunk_material_handle)
+        .with_translucent_material(translucent_material_handle)
+        .with_unloading(settings.renderer.unloading)
+        .with_memory(settings.renderer.memory)
+        .with_lighting(settings.graphics.lighting);
+    commands.insert_resource(chunk_loader);
+
+    commands.insert_resource(DirectionalLightShadowMap {
+        size: settings.graphics.shadows.map_size(),
+    });
+    commands.spawn((
+        DirectionalLight {
+            shadows_enabled: true,
+            ..default()
+        },
+        Transform::default(),
+        Sun,
+    ));
+
+    commands.spawn(settings);
+}
+
+/// The whole game, less Bevy's `DefaultPlugins`, which the app adds first. Crates building on
+/// rustcraft add this to their own app, followed by their mods.
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            ChunkMaterialPlugin,
+            HudPlugin,
+            PauseMenuPlugin,
+            DeathScreenPlugin,
+            MainMenuPlugin,
+            CommandPlugin,
+            ConsolePlugin,
+            LoadingScreenPlugin,
+            NetworkPlugin,
+            ItemPlugin,
+            FallingBlockPlugin,
+            FluidPlugin,
+            TickPlugin,
+            MobPlugin,
+        ))
+        .add_plugins((
+            BlockRegistryPlugin,
+            AiPlugin,
+            DeathPlugin,
+            ExperiencePlugin,
+            ToastPlugin,
+            FogPlugin,
+            UnderwaterPlugin,
+            StructurePlugin,
+            WatchPlugin,
+            BreathPlugin,
+            ThirdPersonPlugin,
+            SkyPlugin,
+            CloudPlugin,
+            WeatherPlugin,
+            ParticlePlugin,
+        ))
+        .add_plugins((MusicPlugin, AmbientSoundPlugin, ChunkEntityPlugin))
+        .register_diagnostic(Diagnostic::new(RESIDENT_CHUNKS))
+        .register_diagnostic(Diagnostic::new(RESIDENT_CHUNK_MEGABYTES).with_suffix(" MB"))
+        .init_state::<GameState>()
+        .enable_state_scoped_entities::<GameState>()
+        .init_resource::<GeneratorRegistry>()
+        .init_resource::<TargetBlock>()
+        .init_resource::<BreakProgress>()
+        .init_resource::<SelectedItem>()
+        .init_resource::<FluidSounds>()
+        .init_resource::<BlockSounds>()
+        .init_resource::<FoliageAssets>()
+        .init_resource::<TimeOfDay>()
+        .init_resource::<LoadingProgress>()
+        .register_diagnostic(Diagnostic::new(DAYLIGHT))
+        .add_event::<BlockEdited>()
+        .add_console_command("tp", "tp <x> <y> <z>", "teleports the player", tp_command)
+        .add_console_command(
+            "setblock",
+            "setblock <x> <y> <z> <block>",
+            "replaces a single block",
+            setblock_command,
+        )
+        .add_console_command(
+            "fill",
+            "fill <x1> <y1> <z1> <x2> <y2> <z2> <block>",
+            "fills a box with a block",
+            fill_command,
+        )
+        .add_console_command(
+            "rollback",
+            "rollback <x1> <y1> <z1> <x2> <y2> <z2> <duration>",
+            "undoes block edits inside a box made within e.g. 30m or 2h",
+            rollback_command,
+        )
+        .add_console_command(
+            "paste",
+            "paste <file.vox> [x y z]",
+            "places a MagicaVoxel model from the structures folder, at the player by default",
+            paste_command,
+        )
+        .add_console_command(
+            "time",
+            "time set <midnight|sunrise|day|noon|sunset|night|0-1>",
+            "shows or sets the time of day",
+            time_command,
+        )
+        .add_console_command(
+            "weather",
+            "weather set <clear|rain|snow>",
+            "shows or sets the weather",
+            weather_command,
+        )
+        .add_console_command("seed", "seed", "shows the world seed", seed_command)
+        .add_console_command(
+            "structures",
+            "structures [radius]",
+            "explains where structures are and aren't generated near the player",
+            structures_command,
+        )
+        .add_console_command(
+            "lighting",
+            "lighting [smooth|flat]",
+            "shows or sets whether block corners are shaded, remeshing loaded chunks",
+            lighting_command,
+        )
+        .add_console_command(
+            "summon",
+            "summon <item|falling_block> <block> [x y z]",
+            "spawns an entity or a mob by name as in /summon pig, in front of the player by default",
+            summon_command,
+        )
+        .add_console_command(
+            "kill",
+            "kill <@e|@nearest|item|falling_block|mob>",
+            "despawns entities, filter with e.g. @e[type=item]",
+            kill_command,
+        )
+        .add_console_command(
+            "clear",
+            "clear [item]",
+            "empties the inventory or removes one block or item type from it",
+            clear_command,
+        )
+        .add_console_command(
+            "give",
+            "give <item> [count]",
+            "adds blocks or items to the inventory",
+            give_command,
+        )
+        .add_console_command(
+            "balance",
+            "balance [add|take <amount>]",
+            "shows or changes how many emeralds the player has",
+            balance_command,
+        )
+        .add_console_command(
+            "gamerule",
+            "gamerule <gravestone> [true|false]",
+            "shows or changes one of the world's rules",
+            gamerule_command,
+        )
+        .add_console_command(
+            "export",
+            "export <x y z> <x y z> <file.obj|file.gltf>",
+            "writes the blocks between two corners to a model file in the exports folder",
+            export_command,
+        )
+        .add_console_command(
+            "import",
+            "import <region folder|file.mca>",
+            "converts a Minecraft map into this world, see assets/import/anvil.ron",
+            import_command,
+        )
+        .add_console_command("mods", "mods", "lists the loaded mods", mods_command)
+        .add_console_command(
+            "watch",
+            "watch <chunk [x z]|off>",
+            "shows live loading, meshing and edit details of a chunk column",
+            watch_command,
+        )
+        .add_console_command(
+            "benchmark",
+            "benchmark [quick|standard|long]",
+            "flies a fixed route and writes a performance report",
+            benchmark_command,
+        )
+        .add_systems(
+            Startup,
+            (
+                setup_scene,
+                spawn_emissive_calibration.after(setup_scene),
+                spawn_crack_overlay,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                player_move.run_if(console_closed),
+                player_look,
+                update_sprint_fov.after(player_move),
+                update_color_grading,
+                (
+                    target_block,
+                    highlight_target_block,
+                    edit_block.run_if(console_closed),
+                    show_break_progress,
+                )
+                    .chain()
+                    .after(player_move)
+                    .after(player_look),
+                update_fluid_emitters,
+                play_footsteps.after(player_move),
+                play_block_sounds.after(edit_block),
+                select_item.run_if(console_closed),
+                update_foliage,
+                (update_sun, tune_shadows, update_chunk_lighting).chain(),
+                run_benchmark,
+            )
+                .run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(
+            FixedUpdate,
+            advance_time_of_day.run_if(in_state(GameState::InGame)),
+        )
+        .add_systems(
+            Update,
+            (
+                (gather_chunks, receive_chunk_results, mark_chunks).before(unload_chunks),
+                (unload_chunks, evict_chunk_data, measure_resident_chunks).chain(),
+            )
+                .run_if(in_state(GameState::InGame).or(in_state(GameState::Loading))),
+        )
+        .add_systems(
+            OnEnter(GameState::Paused),
+            save_resident_chunks.run_if(resource_exists::<WorldInfo>),
+        )
+        .add_systems(
+            Last,
+            save_resident_chunks
+                .run_if(on_event::<AppExit>)
+                .run_if(resource_exists::<WorldInfo>),
+        )
+        .add_systems(
+            OnEnter(GameState::Loading),
+            (place_player_at_spawn, reset_loading_progress),
+        )
+        .add_systems(
+            Update,
+            update_loading
+                .after(receive_chunk_results)
+                .run_if(in_state(GameState::Loading)),
+        )
+        .add_systems(Update, toggle_pause.run_if(console_closed))
+        .add_systems(Update, apply_master_volume)
+        .add_systems(OnEnter(GameState::InGame), grab_cursor)
+        .add_systems(OnExit(GameState::InGame), release_cursor);
+
+        #[cfg(feature = "scripting")]
+        app.add_plugins(crate::scripting::ScriptingPlugin);
+    }
+}
@@ -0,0 +1,182 @@
+//! Numeric ids blocks and items are saved under. Each world keeps its own `IdMap` from these ids
+//! to namespaced keys such as `rustcraft:stone`, so reordering `ALL_BLOCKS` or `ALL_ITEMS`
+//! between versions doesn't change what its saved chunks contain.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block::{BlockType, ALL_BLOCKS},
+    item::{ItemType, ALL_ITEMS},
+};
+
+pub const NAMESPACE: &str = "rustcraft";
+
+/// Keys in the order blocks were numbered before worlds saved their own `IdMap`.
+const LEGACY_BLOCKS: [&str; 19] = [
+    "rustcraft:air",
+    "rustcraft:stone",
+    "rustcraft:grass",
+    "rustcraft:sand",
+    "rustcraft:water",
+    "rustcraft:snow",
+    "rustcraft:lava",
+    "rustcraft:glowstone",
+    "rustcraft:coal",
+    "rustcraft:iron",
+    "rustcraft:gold",
+    "rustcraft:fence",
+    "rustcraft:gravestone",
+    "rustcraft:glass",
+    "rustcraft:leaves",
+    "rustcraft:stone_slab",
+    "rustcraft:stone_stairs",
+    "rustcraft:tall_grass",
+    "rustcraft:flower",
+];
+const LEGACY_ITEMS: [&str; 3] = ["rustcraft:lead", "rustcraft:saddle", "rustcraft:emerald"];
+
+/// A world's saved ids, each the index of its key. Keys are only ever appended, so an id keeps
+/// meaning the same block or item for the life of the world.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdMap {
+    blocks: Vec<String>,
+    items: Vec<String>,
+}
+
+impl Default for IdMap {
+    /// Ids matching the running version's `BlockType::id` and `ItemType::id`, for new worlds and
+    /// for chunks sent between a client and server of the same version.
+    fn default() -> Self {
+        Self {
+            blocks: ALL_BLOCKS.map(|block| block.key().to_string()).to_vec(),
+            items: ALL_ITEMS.map(|item| item.key().to_string()).to_vec(),
+        }
+    }
+}
+
+impl IdMap {
+    /// The ids of worlds saved before they kept a map.
+    pub fn legacy() -> Self {
+        Self {
+            blocks: LEGACY_BLOCKS.map(str::to_string).to_vec(),
+            items: LEGACY_ITEMS.map(str::to_string).to_vec(),
+        }
+    }
+
+    /// Gives ids to any blocks and items added since the map was saved, returning whether there
+    /// were any.
+    pub fn register_missing(&mut self) -> bool {
+        let mut changed = false;
+        for key in ALL_BLOCKS.map(|block| block.key()) {
+            if !self.blocks.iter().any(|entry| entry == key) {
+                self.blocks.push(key.to_string());
+                changed = true;
+            }
+        }
+        for key in ALL_ITEMS.map(|item| item.key()) {
+            if !self.items.iter().any(|entry| entry == key) {
+                self.items.push(key.to_string());
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// The id `block` is saved under, if it has been registered. Chunks store ids in a byte, so
+    /// only the first 256 keys can be saved.
+    pub fn block_id(&self, block: BlockType) -> Option<u8> {
+        let index = self.blocks.iter().position(|key| key == block.key())?;
+        u8::try_from(index).ok()
+    }
+
+    /// The block saved as `id`, or `None` if the id is unknown or its block no longer exists.
+    pub fn block(&self, id: u8) -> Option<BlockType> {
+        BlockType::from_key(self.blocks.get(id as usize)?)
+    }
+
+    /// The key saved as `id`, whether or not its block still exists.
+    pub fn block_key(&self, id: u8) -> Option<&str> {
+        self.blocks.get(id as usize).map(String::as_str)
+    }
+
+    pub fn item_id(&self, item: ItemType) -> Option<u8> {
+        let index = self.items.iter().position(|key| key == item.key())?;
+        u8::try_from(index).ok()
+    }
+
+    pub fn item(&self, id: u8) -> Option<ItemType> {
+        ItemType::from_key(self.items.get(id as usize)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IdMap, NAMESPACE};
+    use crate::{
+        block::{BlockType, ALL_BLOCKS},
+        item::{ItemType, ALL_ITEMS},
+    };
+
+    #[test]
+    fn test_keys_are_unique_and_namespaced() {
+        for (i, block) in ALL_BLOCKS.iter().enumerate() {
+            assert!(block.key().starts_with(&format!("{NAMESPACE}:")));
+            assert_eq!(Some(*block), BlockType::from_key(block.key()));
+            assert!(ALL_BLOCKS[..i]
+                .iter()
+                .all(|other| other.key() != block.key()));
+        }
+        for item in ALL_ITEMS {
+            assert!(item.key().starts_with(&format!("{NAMESPACE}:")));
+            assert_eq!(Some(item), ItemType::from_key(item.key()));
+        }
+        assert_eq!(
+            Some(BlockType::StoneSlab),
+            BlockType::from_name("rustcraft:stone_slab")
+        );
+    }
+
+    #[test]
+    fn test_default_ids_match_runtime_ids() {
+        let ids = IdMap::default();
+        for block in ALL_BLOCKS {
+            assert_eq!(Some(block.id()), ids.block_id(block));
+            assert_eq!(Some(block), ids.block(block.id()));
+        }
+        for item in ALL_ITEMS {
+            assert_eq!(Some(item.id()), ids.item_id(item));
+        }
+        // blocks added since ids were saved come after the legacy ones
+        assert!(ids.blocks.starts_with(&IdMap::legacy().blocks));
+        assert_eq!(IdMap::legacy().items, ids.items);
+    }
+
+    #[test]
+    fn test_saved_ids_survive_reordering() {
+        // a world saved by a version that numbered blocks differently, and lacked flowers
+        let mut ids = IdMap {
+            blocks: vec![
+                "rustcraft:air".to_string(),
+                "rustcraft:glass".to_string(),
+                "rustcraft:removed".to_string(),
+                "rustcraft:stone".to_string(),
+            ],
+            items: vec![],
+        };
+        assert_eq!(Some(BlockType::Glass), ids.block(1));
+        assert_eq!(None, ids.block(2));
+        assert_eq!(Some(3), ids.block_id(BlockType::Stone));
+        assert_eq!(None, ids.block_id(BlockType::Flower));
+
+        assert!(ids.register_missing());
+        assert!(!ids.register_missing());
+        assert_eq!(Some(3), ids.block_id(BlockType::Stone));
+        let flower = ids.block_id(BlockType::Flower).unwrap();
+        assert!(flower > 3);
+        assert_eq!(Some(BlockType::Flower), ids.block(flower));
+        assert_eq!(
+            Some(ItemType::Saddle),
+            ids.item(ids.item_id(ItemType::Saddle).unwrap())
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use bevy::{
+    ecs::system::{Query, Res, SystemParam},
+    input::{keyboard::KeyCode, mouse::MouseButton, ButtonInput},
+};
+use serde::Deserialize;
+
+use crate::settings::Settings;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    /// Flies down, or sneaks while walking.
+    Descend,
+    Sprint,
+    Break,
+    Place,
+    ToggleFly,
+    ToggleNoclip,
+    /// Switches between first and third person.
+    ToggleView,
+    Pause,
+}
+
+/// A physical input an action can be bound to, written in `settings.toml` as a key name
+/// (`"W"`, `"Space"`, `"ShiftLeft"`) or a mouse button (`"MouseLeft"`).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl TryFrom<String> for Binding {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Binding::parse(&value).ok_or_else(|| format!("unknown key binding '{value}'"))
+    }
+}
+
+impl Binding {
+    pub fn parse(name: &str) -> Option<Self> {
+        let mouse = match name {
+            "MouseLeft" => Some(MouseButton::Left),
+            "MouseRight" => Some(MouseButton::Right),
+            "MouseMiddle" => Some(MouseButton::Middle),
+            "MouseBack" => Some(MouseButton::Back),
+            "MouseForward" => Some(MouseButton::Forward),
+            _ => None,
+        };
+        if let Some(button) = mouse {
+            return Some(Self::Mouse(button));
+        }
+
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Self::parse_char(c.to_ascii_uppercase()).map(Self::Key);
+        }
+
+        let key = match name {
+            "Space" => KeyCode::Space,
+            "Enter" => KeyCode::Enter,
+            "Tab" => KeyCode::Tab,
+            "Escape" => KeyCode::Escape,
+            "Backspace" => KeyCode::Backspace,
+            "ShiftLeft" => KeyCode::ShiftLeft,
+            "ShiftRight" => KeyCode::ShiftRight,
+            "ControlLeft" => KeyCode::ControlLeft,
+            "ControlRight" => KeyCode::ControlRight,
+            "AltLeft" => KeyCode::AltLeft,
+            "AltRight" => KeyCode::AltRight,
+            "ArrowUp" => KeyCode::ArrowUp,
+            "ArrowDown" => KeyCode::ArrowDown,
+            "ArrowLeft" => KeyCode::ArrowLeft,
+            "ArrowRight" => KeyCode::ArrowRight,
+            "F1" => KeyCode::F1,
+            "F2" => KeyCode::F2,
+            "F3" => KeyCode::F3,
+            "F4" => KeyCode::F4,
+            "F5" => KeyCode::F5,
+            "F6" => KeyCode::F6,
+            "F7" => KeyCode::F7,
+            "F8" => KeyCode::F8,
+            "F9" => KeyCode::F9,
+            "F10" => KeyCode::F10,
+            "F11" => KeyCode::F11,
+            "F12" => KeyCode::F12,
+            _ => return None,
+        };
+        Some(Self::Key(key))
+    }
+
+    fn parse_char(c: char) -> Option<KeyCode> {
+        let key = match c {
+            'A' => KeyCode::KeyA,
+            'B' => KeyCode::KeyB,
+            'C' => KeyCode::KeyC,
+            'D' => KeyCode::KeyD,
+            'E' => KeyCode::KeyE,
+            'F' => KeyCode::KeyF,
+            'G' => KeyCode::KeyG,
+            'H' => KeyCode::KeyH,
+            'I' => KeyCode::KeyI,
+            'J' => KeyCode::KeyJ,
+            'K' => KeyCode::KeyK,
+            'L' => KeyCode::KeyL,
+            'M' => KeyCode::KeyM,
+            'N' => KeyCode::KeyN,
+            'O' => KeyCode::KeyO,
+            'P' => KeyCode::KeyP,
+            'Q' => KeyCode::KeyQ,
+            'R' => KeyCode::KeyR,
+            'S' => KeyCode::KeyS,
+            'T' => KeyCode::KeyT,
+            'U' => KeyCode::KeyU,
+            'V' => KeyCode::KeyV,
+            'W' => KeyCode::KeyW,
+            'X' => KeyCode::KeyX,
+            'Y' => KeyCode::KeyY,
+            'Z' => KeyCode::KeyZ,
+            '0' => KeyCode::Digit0,
+            '1' => KeyCode::Digit1,
+            '2' => KeyCode::Digit2,
+            '3' => KeyCode::Digit3,
+            '4' => KeyCode::Digit4,
+            '5' => KeyCode::Digit5,
+            '6' => KeyCode::Digit6,
+            '7' => KeyCode::Digit7,
+            '8' => KeyCode::Digit8,
+            '9' => KeyCode::Digit9,
+            _ => return None,
+        };
+        Some(key)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub move_forward: Binding,
+    pub move_backward: Binding,
+    pub move_left: Binding,
+    pub move_right: Binding,
+    pub jump: Binding,
+    pub descend: Binding,
+    pub sprint: Binding,
+    #[serde(rename = "break")]
+    pub break_block: Binding,
+    #[serde(rename = "place")]
+    pub place_block: Binding,
+    pub toggle_fly: Binding,
+    pub toggle_noclip: Binding,
+    pub toggle_view: Binding,
+    pub pause: Binding,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            move_forward: Binding::Key(KeyCode::KeyW),
+            move_backward: Binding::Key(KeyCode::KeyS),
+            move_left: Binding::Key(KeyCode::KeyA),
+            move_right: Binding::Key(KeyCode::KeyD),
+            jump: Binding::Key(KeyCode::Space),
+            descend: Binding::Key(KeyCode::ShiftLeft),
+            sprint: Binding::Key(KeyCode::ControlLeft),
+            break_block: Binding::Mouse(MouseButton::Left),
+            place_block: Binding::Mouse(MouseButton::Right),
+            toggle_fly: Binding::Key(KeyCode::KeyF),
+            toggle_noclip: Binding::Key(KeyCode::KeyN),
+            toggle_view: Binding::Key(KeyCode::F5),
+            pause: Binding::Key(KeyCode::Escape),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn binding(&self, action: Action) -> Binding {
+        match action {
+            Action::MoveForward => self.move_forward,
+            Action::MoveBackward => self.move_backward,
+            Action::MoveLeft => self.move_left,
+            Action::MoveRight => self.move_right,
+            Action::Jump => self.jump,
+            Action::Descend => self.descend,
+            Action::Sprint => self.sprint,
+            Action::Break => self.break_block,
+            Action::Place => self.place_block,
+            Action::ToggleFly => self.toggle_fly,
+            Action::ToggleNoclip => self.toggle_noclip,
+            Action::ToggleView => self.toggle_view,
+            Action::Pause => self.pause,
+        }
+    }
+}
+
+/// Reads the state of actions through the key bindings in the current settings.
+#[derive(SystemParam)]
+pub struct ActionInput<'w, 's> {
+    keys: Res<'w, ButtonInput<KeyCode>>,
+    mouse: Res<'w, ButtonInput<MouseButton>>,
+    settings: Query<'w, 's, &'static Settings>,
+}
+
+impl ActionInput<'_, '_> {
+    fn bindings(&self) -> KeyBindings {
+        self.settings
+            .get_single()
+            .map(|settings| settings.bindings)
+            .unwrap_or_default()
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        match self.bindings().binding(action) {
+            Binding::Key(key) => self.keys.pressed(key),
+            Binding::Mouse(button) => self.mouse.pressed(button),
+        }
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        match self.bindings().binding(action) {
+            Binding::Key(key) => self.keys.just_pressed(key),
+            Binding::Mouse(button) => self.mouse.just_pressed(button),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::{keyboard::KeyCode, mouse::MouseButton};
+
+    use super::{Binding, KeyBindings};
+
+    #[test]
+    fn test_parse_binding() {
+        assert_eq!(Some(Binding::Key(KeyCode::KeyW)), Binding::parse("W"));
+        assert_eq!(Some(Binding::Key(KeyCode::KeyQ)), Binding::parse("q"));
+        assert_eq!(Some(Binding::Key(KeyCode::Digit3)), Binding::parse("3"));
+        assert_eq!(
+            Some(Binding::Key(KeyCode::ShiftLeft)),
+            Binding::parse("ShiftLeft")
+        );
+        assert_eq!(
+            Some(Binding::Mouse(MouseButton::Right)),
+            Binding::parse("MouseRight")
+        );
+        assert_eq!(None, Binding::parse("Hyper"));
+    }
+
+    #[test]
+    fn test_bindings_from_toml() {
+        let bindings: KeyBindings = toml::from_str(
+            r#"
+            jump = "E"
+            break = "MouseMiddle"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(Binding::Key(KeyCode::KeyE), bindings.jump);
+        assert_eq!(Binding::Mouse(MouseButton::Middle), bindings.break_block);
+        assert_eq!(Binding::Key(KeyCode::KeyW), bindings.move_forward);
+    }
+
+    #[test]
+    fn test_bindings_reject_unknown_key() {
+        assert!(toml::from_str::<KeyBindings>(r#"jump = "Hyper""#).is_err());
+    }
+}
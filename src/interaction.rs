@@ -0,0 +1,376 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{
+    color::{Alpha, Color},
+    ecs::{
+        event::{Event, EventReader, EventWriter},
+        query::With,
+        system::{Commands, In, Query, Res, ResMut, Resource},
+    },
+    gizmos::gizmos::Gizmos,
+    input::mouse::MouseWheel,
+    math::{I64Vec3, Vec3},
+    render::camera::Camera,
+    time::Time,
+    transform::components::{GlobalTransform, Transform},
+};
+
+use crate::{
+    block::{BlockType, PLACEABLE_BLOCKS},
+    breaking::BreakProgress,
+    chunks::{chunk_loader::ChunkLoader, generate::vox::load_vox},
+    command::{CommandArgs, CommandResult},
+    input::bindings::{Action, ActionInput},
+    item::{BlockBroken, Held, ALL_ITEMS},
+    physics::{raycast, RaycastHit},
+    player::{Player, Spectator, PLAYER_COLLIDER},
+    settings::Settings,
+    third_person::CameraView,
+    world::World,
+};
+
+pub const DEFAULT_REACH: f32 = 5.0;
+/// Opacity of the target block's outline at the very edge of the player's reach, fading from
+/// opaque over the last block.
+const EDGE_OUTLINE_ALPHA: f32 = 0.3;
+
+/// The block the player is currently looking at, if any is within reach.
+#[derive(Resource, Default)]
+pub struct TargetBlock(pub Option<RaycastHit>);
+
+/// Sent when a block is changed on this machine, by the local player or anything else, after the
+/// world has been updated. Edits received over the network aren't sent again.
+#[derive(Event, Debug, Copy, Clone, PartialEq)]
+pub struct BlockEdited {
+    pub position: I64Vec3,
+    pub block: BlockType,
+    pub source: EditSource,
+}
+
+/// What made a `BlockEdited` edit.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EditSource {
+    Player,
+    Command,
+    Script,
+    Fluid,
+    FallingBlock,
+    Mob,
+    Gravestone,
+    Weather,
+}
+
+impl EditSource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Player => "player",
+            Self::Command => "command",
+            Self::Script => "script",
+            Self::Fluid => "fluid",
+            Self::FallingBlock => "falling block",
+            Self::Mob => "mob",
+            Self::Gravestone => "gravestone",
+            Self::Weather => "weather",
+        }
+    }
+}
+
+/// What the player is holding, a block to place or an item to use, cycled with the mouse wheel.
+#[derive(Resource, Default)]
+pub struct SelectedItem {
+    index: usize,
+}
+
+impl SelectedItem {
+    pub fn held(&self) -> Held {
+        match PLACEABLE_BLOCKS.get(self.index) {
+            Some(block) => Held::Block(*block),
+            None => Held::Item(ALL_ITEMS[self.index - PLACEABLE_BLOCKS.len()]),
+        }
+    }
+
+    /// The block placed next, if a block is held.
+    pub fn block(&self) -> Option<BlockType> {
+        self.held().block()
+    }
+}
+
+pub fn select_item(mut selected: ResMut<SelectedItem>, mut wheel_evr: EventReader<MouseWheel>) {
+    for ev in wheel_evr.read() {
+        let count = PLACEABLE_BLOCKS.len() + ALL_ITEMS.len();
+        if ev.y < 0.0 {
+            selected.index = (selected.index + 1) % count;
+        } else if ev.y > 0.0 {
+            selected.index = (selected.index + count - 1) % count;
+        }
+    }
+}
+
+pub fn target_block(
+    mut world: ResMut<World>,
+    mut target: ResMut<TargetBlock>,
+    camera_query: Query<&GlobalTransform, With<Camera>>,
+    spectator_query: Query<(), (With<Player>, With<Spectator>)>,
+    settings_query: Query<&Settings>,
+    view: Res<CameraView>,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    // spectators can't reach anything, so they never break or place blocks
+    if !spectator_query.is_empty() {
+        target.0 = None;
+        return;
+    }
+
+    target.0 = raycast(
+        &mut world,
+        view.eye(camera),
+        camera.forward().as_vec3(),
+        reach(&settings_query),
+    );
+}
+
+/// How far the player can reach, from their settings.
+pub fn reach(settings_query: &Query<&Settings>) -> f32 {
+    settings_query
+        .get_single()
+        .map_or(DEFAULT_REACH, |settings| settings.player.reach)
+}
+
+/// Opacity of the outline around a block `distance` away, fading as it nears `reach`.
+fn outline_alpha(distance: f32, reach: f32) -> f32 {
+    let edge = (reach - distance).clamp(0.0, 1.0);
+    EDGE_OUTLINE_ALPHA + (1.0 - EDGE_OUTLINE_ALPHA) * edge
+}
+
+pub fn highlight_target_block(
+    target: Res<TargetBlock>,
+    settings_query: Query<&Settings>,
+    mut gizmos: Gizmos,
+) {
+    if let Some(hit) = target.0 {
+        let alpha = outline_alpha(hit.distance, reach(&settings_query));
+        // scale up slightly so the outline is not hidden by the block's own faces
+        gizmos.cuboid(
+            Transform::from_translation(hit.block.as_vec3()).with_scale(Vec3::splat(1.005)),
+            Color::BLACK.with_alpha(alpha),
+        );
+    }
+}
+
+/// Breaks the target block once break has been held on it for as long as its hardness, or
+/// places the held block against it.
+#[allow(clippy::too_many_arguments)]
+pub fn edit_block(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut world: ResMut<World>,
+    chunk_loader: Res<ChunkLoader>,
+    target: Res<TargetBlock>,
+    mut progress: ResMut<BreakProgress>,
+    selected: Res<SelectedItem>,
+    input: ActionInput,
+    player_query: Query<&Transform, With<Player>>,
+    mut edited: EventWriter<BlockEdited>,
+    mut broken: EventWriter<BlockBroken>,
+) {
+    let hardness = target
+        .0
+        .map_or(0.0, |hit| world.get_block(hit.block).hardness());
+    let broke = progress.advance(
+        target.0.map(|hit| hit.block),
+        hardness,
+        input.pressed(Action::Break),
+        time.delta_secs(),
+    );
+    let Some(hit) = target.0 else {
+        return;
+    };
+
+    if broke {
+        let previous = world.get_block(hit.block);
+        if world.set_block(hit.block, BlockType::Air) {
+            broken.send(BlockBroken {
+                position: hit.block,
+                block: previous,
+            });
+            chunk_loader.remesh_block(&mut commands, &world, hit.block);
+            edited.send(BlockEdited {
+                position: hit.block,
+                block: BlockType::Air,
+                source: EditSource::Player,
+            });
+        }
+    } else if input.just_pressed(Action::Place) {
+        let Some(placed) = selected.block() else {
+            return;
+        };
+        if hit.normal == I64Vec3::ZERO {
+            return;
+        }
+
+        let block = hit.block + hit.normal;
+        let block_min = block.as_vec3() - 0.5;
+        let block_max = block.as_vec3() + 0.5;
+        let overlaps_player = player_query.iter().any(|player| {
+            let min = player.translation + PLAYER_COLLIDER.min;
+            let max = player.translation + PLAYER_COLLIDER.max;
+            min.cmplt(block_max).all() && max.cmpgt(block_min).all()
+        });
+
+        if !overlaps_player && world.set_block(block, placed) {
+            chunk_loader.remesh_block(&mut commands, &world, block);
+            edited.send(BlockEdited {
+                position: block,
+                block: placed,
+                source: EditSource::Player,
+            });
+        }
+    }
+}
+
+/// Largest number of blocks a single `/fill` may change.
+const MAX_FILL_VOLUME: i64 = 32 * 32 * 32;
+/// Folder `paste` reads models from.
+const STRUCTURES_DIR: &str = "structures";
+
+pub fn setblock_command(
+    In(mut args): In<CommandArgs>,
+    mut commands: Commands,
+    mut world: ResMut<World>,
+    chunk_loader: Res<ChunkLoader>,
+    mut edited: EventWriter<BlockEdited>,
+) -> CommandResult {
+    let position = args.block_position()?;
+    let block = args.block()?;
+    args.finish()?;
+
+    if !world.set_block(position, block) {
+        return Err(format!("{position} is not loaded"));
+    }
+    chunk_loader.remesh_block(&mut commands, &world, position);
+    edited.send(BlockEdited {
+        position,
+        block,
+        source: EditSource::Command,
+    });
+    Ok(format!("set {} to {}", position, block.name()))
+}
+
+pub fn fill_command(
+    In(mut args): In<CommandArgs>,
+    mut commands: Commands,
+    mut world: ResMut<World>,
+    chunk_loader: Res<ChunkLoader>,
+    mut edited: EventWriter<BlockEdited>,
+) -> CommandResult {
+    let a = args.block_position()?;
+    let b = args.block_position()?;
+    let block = args.block()?;
+    args.finish()?;
+
+    let (min, max) = (a.min(b), a.max(b));
+    let volume = (max - min + 1).element_product();
+    if volume > MAX_FILL_VOLUME {
+        return Err(format!(
+            "cannot fill {volume} blocks, the limit is {MAX_FILL_VOLUME}"
+        ));
+    }
+
+    let mut positions = Vec::with_capacity(volume as usize);
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                let position = I64Vec3::new(x, y, z);
+                if world.get_block(position) != block {
+                    positions.push(position);
+                }
+            }
+        }
+    }
+
+    let count = world.set_blocks(positions.iter().map(|position| (*position, block)));
+    // blocks in chunks that aren't loaded were skipped, so only report the ones that changed
+    edited.send_batch(
+        positions
+            .into_iter()
+            .filter(|position| world.get_block(*position) == block)
+            .map(|position| BlockEdited {
+                position,
+                block,
+                source: EditSource::Command,
+            }),
+    );
+    chunk_loader.remesh_region(&mut commands, &world, min, max);
+    Ok(format!("filled {} blocks with {}", count, block.name()))
+}
+
+/// Places a MagicaVoxel model from `STRUCTURES_DIR` with its minimum corner at the given
+/// position, or at the player.
+pub fn paste_command(
+    In(mut args): In<CommandArgs>,
+    mut commands: Commands,
+    mut world: ResMut<World>,
+    chunk_loader: Res<ChunkLoader>,
+    mut edited: EventWriter<BlockEdited>,
+) -> CommandResult {
+    let name = args.word("a file name")?;
+    let origin = if args.is_empty() {
+        args.origin()
+            .ok_or_else(|| "there is no player to paste at".to_string())?
+            .translation
+            .round()
+            .as_i64vec3()
+    } else {
+        args.block_position()?
+    };
+    args.finish()?;
+
+    let file_name = Path::new(&name)
+        .file_name()
+        .ok_or_else(|| format!("'{name}' is not a file name"))?;
+    let path = PathBuf::from(STRUCTURES_DIR)
+        .join(file_name)
+        .with_extension("vox");
+    let blueprint = load_vox(&path)?;
+
+    let blocks: Vec<_> = blueprint
+        .blocks()
+        .map(|(local, block)| (origin + local, block))
+        .collect();
+    if blocks.len() as i64 > MAX_FILL_VOLUME {
+        return Err(format!(
+            "cannot paste {} blocks, the limit is {MAX_FILL_VOLUME}",
+            blocks.len()
+        ));
+    }
+
+    let count = world.set_blocks(blocks.iter().copied());
+    edited.send_batch(
+        blocks
+            .into_iter()
+            .filter(|(position, block)| world.get_block(*position) == *block)
+            .map(|(position, block)| BlockEdited {
+                position,
+                block,
+                source: EditSource::Command,
+            }),
+    );
+    chunk_loader.remesh_region(&mut commands, &world, origin, origin + blueprint.size - 1);
+    Ok(format!("pasted {count} blocks from {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{outline_alpha, EDGE_OUTLINE_ALPHA};
+
+    #[test]
+    fn test_outline_fades_at_the_edge_of_reach() {
+        assert_eq!(1.0, outline_alpha(2.0, 5.0));
+        assert_eq!(1.0, outline_alpha(4.0, 5.0));
+        assert!((outline_alpha(4.5, 5.0) - 0.65).abs() < 1e-6);
+        assert_eq!(EDGE_OUTLINE_ALPHA, outline_alpha(5.0, 5.0));
+        assert_eq!(1.0, outline_alpha(9.0, 12.0));
+    }
+}
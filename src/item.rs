@@ -0,0 +1,488 @@
+use std::collections::HashMap;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::{Assets, Handle},
+    color::Color,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::{Event, EventReader},
+        query::{Added, With},
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res, ResMut, Resource},
+    },
+    math::{primitives::Cuboid, I64Vec3, Quat, Vec3},
+    pbr::{MeshMaterial3d, StandardMaterial},
+    prelude::Mesh3d,
+    render::mesh::Mesh,
+    state::condition::in_state,
+    time::Time,
+    transform::components::Transform,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block::{BlockType, BLOCK_COUNT},
+    chunks::{chunk_loader::ChunkLoader, generate::generator::generate_block_mesh},
+    interaction::edit_block,
+    physics::{move_and_collide, Collider},
+    player::{Player, PLAYER_COLLIDER},
+    state::GameState,
+    world::World,
+};
+
+/// Width of a dropped item relative to a block.
+pub const ITEM_SCALE: f32 = 0.25;
+pub const ITEM_COLLIDER: Collider = Collider::new(
+    Vec3::splat(-ITEM_SCALE / 2.0),
+    Vec3::splat(ITEM_SCALE / 2.0),
+);
+const ITEM_GRAVITY: f32 = 20.0;
+const MAX_FALL_SPEED: f32 = 30.0;
+/// Speed at which a dropped item pops up out of the broken block.
+const POP_SPEED: f32 = 4.0;
+/// Horizontal speed lost per second while resting on the ground.
+const GROUND_FRICTION: f32 = 8.0;
+/// Radians per second dropped items turn.
+const SPIN_SPEED: f32 = 1.5;
+/// Seconds before a fresh drop can be picked up, so it is visible before it disappears.
+const PICKUP_DELAY: f32 = 0.4;
+/// Distance beyond the player's collider within which items are collected.
+const PICKUP_REACH: f32 = 0.5;
+/// Seconds a drop lies in the world before it despawns.
+const DESPAWN_AGE: f32 = 300.0;
+
+pub struct ItemPlugin;
+
+impl Plugin for ItemPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Inventory>()
+            .init_resource::<BlockMeshes>()
+            .init_resource::<ItemMeshes>()
+            .add_event::<BlockBroken>()
+            .add_systems(
+                Update,
+                (
+                    spawn_item_drops.after(edit_block),
+                    add_item_meshes,
+                    update_item_drops,
+                    pick_up_items,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Sent when the local player breaks a block, with the block that was there.
+#[derive(Event, Debug, Copy, Clone, PartialEq)]
+pub struct BlockBroken {
+    pub position: I64Vec3,
+    pub block: BlockType,
+}
+
+/// Things that can be carried but not placed as blocks.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum ItemType {
+    /// Ties a mob to the player, or to a fence.
+    Lead,
+    /// Lets the player ride a mob that can be ridden.
+    Saddle,
+    /// Currency traded for goods, see `economy`.
+    Emerald,
+}
+
+pub const ITEM_COUNT: usize = 3;
+
+/// Every item type, indexed by its numeric id.
+pub const ALL_ITEMS: [ItemType; ITEM_COUNT] = [ItemType::Lead, ItemType::Saddle, ItemType::Emerald];
+
+impl ItemType {
+    pub fn id(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        ALL_ITEMS.get(id as usize).copied()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Lead => "Lead",
+            Self::Saddle => "Saddle",
+            Self::Emerald => "Emerald",
+        }
+    }
+
+    /// Colour the item is drawn in when dropped.
+    pub fn color(&self) -> Color {
+        match self {
+            Self::Lead => Color::srgb(0.55, 0.4, 0.25),
+            Self::Saddle => Color::srgb(0.45, 0.25, 0.12),
+            Self::Emerald => Color::srgb(0.1, 0.8, 0.35),
+        }
+    }
+
+    /// Namespaced id the item is saved under, which never changes between versions.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::Lead => "rustcraft:lead",
+            Self::Saddle => "rustcraft:saddle",
+            Self::Emerald => "rustcraft:emerald",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        ALL_ITEMS.into_iter().find(|item| item.key() == key)
+    }
+
+    /// Looks an item up by name or key, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        ALL_ITEMS.into_iter().find(|item| {
+            item.name().eq_ignore_ascii_case(name) || item.key().eq_ignore_ascii_case(name)
+        })
+    }
+}
+
+/// Anything the player can carry: a block or an item.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum Held {
+    Block(BlockType),
+    Item(ItemType),
+}
+
+impl Held {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Block(block) => block.name(),
+            Self::Item(item) => item.name(),
+        }
+    }
+
+    /// Looks a block or item up by name, ignoring case.
+    pub fn from_name(name: &str) -> Option<Self> {
+        BlockType::from_name(name)
+            .map(Self::Block)
+            .or_else(|| ItemType::from_name(name).map(Self::Item))
+    }
+
+    pub fn block(&self) -> Option<BlockType> {
+        match self {
+            Self::Block(block) => Some(*block),
+            Self::Item(_) => None,
+        }
+    }
+
+    /// Position in the inventory, blocks first.
+    fn index(&self) -> usize {
+        match self {
+            Self::Block(block) => block.id() as usize,
+            Self::Item(item) => BLOCK_COUNT + item.id() as usize,
+        }
+    }
+
+    fn from_index(index: usize) -> Option<Self> {
+        match index.checked_sub(BLOCK_COUNT) {
+            None => BlockType::from_id(index as u8).map(Self::Block),
+            Some(id) => ItemType::from_id(id as u8).map(Self::Item),
+        }
+    }
+}
+
+impl From<BlockType> for Held {
+    fn from(block: BlockType) -> Self {
+        Self::Block(block)
+    }
+}
+
+impl From<ItemType> for Held {
+    fn from(item: ItemType) -> Self {
+        Self::Item(item)
+    }
+}
+
+/// Blocks and items the player has collected, counted by type.
+#[derive(Resource, Debug)]
+pub struct Inventory {
+    counts: [u32; BLOCK_COUNT + ITEM_COUNT],
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            counts: [0; BLOCK_COUNT + ITEM_COUNT],
+        }
+    }
+}
+
+impl Inventory {
+    pub fn count(&self, held: impl Into<Held>) -> u32 {
+        self.counts[held.into().index()]
+    }
+
+    pub fn add(&mut self, held: impl Into<Held>, count: u32) {
+        self.counts[held.into().index()] += count;
+    }
+
+    /// Removes `count` of a block or item if there are that many, returning whether there were.
+    pub fn take(&mut self, held: impl Into<Held>, count: u32) -> bool {
+        let held = &mut self.counts[held.into().index()];
+        if *held < count {
+            return false;
+        }
+        *held -= count;
+        true
+    }
+
+    /// Removes every one of a block or item, returning how many there were.
+    pub fn clear(&mut self, held: impl Into<Held>) -> u32 {
+        std::mem::take(&mut self.counts[held.into().index()])
+    }
+
+    /// Every block and item held, with how many.
+    pub fn iter(&self) -> impl Iterator<Item = (Held, u32)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .filter_map(|(index, count)| Some((Held::from_index(index)?, *count)))
+    }
+}
+
+/// A stack of blocks or items dropped into the world, waiting to be picked up.
+#[derive(Component, Debug)]
+pub struct ItemDrop {
+    pub item: Held,
+    pub count: u32,
+    velocity: Vec3,
+    grounded: bool,
+    age: f32,
+}
+
+impl ItemDrop {
+    pub fn new(item: impl Into<Held>, velocity: Vec3) -> Self {
+        Self {
+            item: item.into(),
+            count: 1,
+            velocity,
+            grounded: false,
+            age: 0.0,
+        }
+    }
+
+    pub fn with_count(mut self, count: u32) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Carries over how long a saved drop had been on the ground.
+    pub fn with_age(mut self, age: f32) -> Self {
+        self.age = age;
+        self
+    }
+
+    /// Seconds the drop has been on the ground.
+    pub fn age(&self) -> f32 {
+        self.age
+    }
+}
+
+/// Whether breaking `block` leaves something to pick up.
+fn drops_item(block: BlockType) -> bool {
+    // a gravestone spills what it holds instead, see `death::open_gravestones`, and spawners
+    // can't be collected
+    block.is_selectable() && !matches!(block, BlockType::Gravestone | BlockType::Spawner)
+}
+
+pub fn spawn_item_drops(mut commands: Commands, mut broken: EventReader<BlockBroken>) {
+    for event in broken.read() {
+        if !drops_item(event.block) {
+            continue;
+        }
+
+        // scatter drops a little so several from the same spot don't stack exactly
+        let scatter = (event.position.x ^ event.position.z).rem_euclid(8) as f32 / 8.0;
+        let angle = scatter * std::f32::consts::TAU;
+        let velocity = Vec3::new(angle.cos(), POP_SPEED, angle.sin());
+        commands.spawn((
+            ItemDrop::new(event.block, velocity),
+            Transform::from_translation(event.position.as_vec3())
+                .with_scale(Vec3::splat(ITEM_SCALE)),
+        ));
+    }
+}
+
+/// Meshes of a single block for entities drawn as blocks, such as dropped items, shared between
+/// every entity of a block type.
+#[derive(Resource, Default)]
+pub struct BlockMeshes {
+    meshes: HashMap<u8, Handle<Mesh>>,
+}
+
+impl BlockMeshes {
+    pub fn get(&mut self, meshes: &mut Assets<Mesh>, block: BlockType) -> Handle<Mesh> {
+        self.meshes
+            .entry(block.id())
+            .or_insert_with(|| meshes.add(generate_block_mesh(block)))
+            .clone()
+    }
+}
+
+/// Meshes of dropped items that aren't blocks, flat tiles in each item's colour.
+#[derive(Resource, Default)]
+pub struct ItemMeshes {
+    tile: Option<Handle<Mesh>>,
+    materials: HashMap<u8, Handle<StandardMaterial>>,
+}
+
+impl ItemMeshes {
+    pub fn get(
+        &mut self,
+        meshes: &mut Assets<Mesh>,
+        materials: &mut Assets<StandardMaterial>,
+        item: ItemType,
+    ) -> (Handle<Mesh>, Handle<StandardMaterial>) {
+        let tile = self
+            .tile
+            .get_or_insert_with(|| meshes.add(Cuboid::new(1.0, 0.25, 1.0)))
+            .clone();
+        let material = self
+            .materials
+            .entry(item.id())
+            .or_insert_with(|| materials.add(StandardMaterial::from(item.color())))
+            .clone();
+        (tile, material)
+    }
+}
+
+fn add_item_meshes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut block_meshes: ResMut<BlockMeshes>,
+    mut item_meshes: ResMut<ItemMeshes>,
+    chunk_loader: Res<ChunkLoader>,
+    item_query: Query<(Entity, &ItemDrop), Added<ItemDrop>>,
+) {
+    for (entity, drop) in item_query.iter() {
+        match drop.item {
+            Held::Block(block) => {
+                let mesh = block_meshes.get(&mut meshes, block);
+                commands.entity(entity).insert((
+                    Mesh3d(mesh),
+                    MeshMaterial3d(chunk_loader.material_for(block)),
+                ));
+            }
+            Held::Item(item) => {
+                let (mesh, material) = item_meshes.get(&mut meshes, &mut materials, item);
+                commands
+                    .entity(entity)
+                    .insert((Mesh3d(mesh), MeshMaterial3d(material)));
+            }
+        }
+    }
+}
+
+pub fn update_item_drops(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut world: ResMut<World>,
+    mut item_query: Query<(Entity, &mut ItemDrop, &mut Transform)>,
+) {
+    let delta_secs = time.delta_secs();
+    for (entity, mut item, mut transform) in item_query.iter_mut() {
+        item.age += delta_secs;
+        if item.age > DESPAWN_AGE {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        transform.rotation *= Quat::from_rotation_y(SPIN_SPEED * delta_secs);
+
+        let mut velocity = item.velocity;
+        velocity.y = (velocity.y - ITEM_GRAVITY * delta_secs).max(-MAX_FALL_SPEED);
+        if item.grounded {
+            let horizontal = Vec3::new(velocity.x, 0.0, velocity.z);
+            let slowed = horizontal.length() - GROUND_FRICTION * delta_secs;
+            let horizontal = horizontal.normalize_or_zero() * slowed.max(0.0);
+            velocity = Vec3::new(horizontal.x, velocity.y, horizontal.z);
+        }
+
+        let (position, blocked) = move_and_collide(
+            &mut world,
+            transform.translation,
+            ITEM_COLLIDER,
+            velocity * delta_secs,
+        );
+        transform.translation = position;
+
+        item.grounded = blocked.y && velocity.y < 0.0;
+        if blocked.x {
+            velocity.x = 0.0;
+        }
+        if blocked.y {
+            velocity.y = 0.0;
+        }
+        if blocked.z {
+            velocity.z = 0.0;
+        }
+        item.velocity = velocity;
+    }
+}
+
+pub fn pick_up_items(
+    mut commands: Commands,
+    mut inventory: ResMut<Inventory>,
+    player_query: Query<&Transform, With<Player>>,
+    item_query: Query<(Entity, &ItemDrop, &Transform)>,
+) {
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    let min = player.translation + PLAYER_COLLIDER.min - PICKUP_REACH;
+    let max = player.translation + PLAYER_COLLIDER.max + PICKUP_REACH;
+
+    for (entity, item, transform) in item_query.iter() {
+        let position = transform.translation;
+        if item.age >= PICKUP_DELAY
+            && (position + ITEM_COLLIDER.max).cmpgt(min).all()
+            && (position + ITEM_COLLIDER.min).cmplt(max).all()
+        {
+            inventory.add(item.item, item.count);
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Held, Inventory, ItemType};
+    use crate::block::BlockType;
+
+    #[test]
+    fn test_inventory_counts_blocks() {
+        let mut inventory = Inventory::default();
+        inventory.add(BlockType::Stone, 2);
+        inventory.add(BlockType::Sand, 1);
+        inventory.add(BlockType::Stone, 1);
+        inventory.add(ItemType::Lead, 2);
+
+        assert_eq!(3, inventory.count(BlockType::Stone));
+        assert_eq!(0, inventory.count(BlockType::Grass));
+        assert_eq!(2, inventory.count(ItemType::Lead));
+        assert_eq!(
+            vec![
+                (Held::Block(BlockType::Stone), 3),
+                (Held::Block(BlockType::Sand), 1),
+                (Held::Item(ItemType::Lead), 2)
+            ],
+            inventory.iter().collect::<Vec<_>>()
+        );
+
+        assert!(!inventory.take(BlockType::Sand, 2));
+        assert!(inventory.take(BlockType::Stone, 2));
+        assert_eq!(1, inventory.count(BlockType::Stone));
+    }
+}
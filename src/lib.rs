@@ -0,0 +1,50 @@
+pub mod ai;
+pub mod ambience;
+pub mod audio;
+pub mod benchmark;
+pub mod block;
+pub mod block_registry;
+pub mod breaking;
+pub mod breath;
+pub mod chunk_entities;
+pub mod chunks;
+pub mod clouds;
+pub mod command;
+pub mod daylight;
+pub mod death;
+pub mod debug;
+pub mod economy;
+pub mod entity_commands;
+pub mod experience;
+pub mod export;
+pub mod falling_block;
+pub mod fluid;
+pub mod fog;
+pub mod game;
+pub mod ids;
+pub mod input;
+pub mod interaction;
+pub mod item;
+pub mod loading;
+pub mod mob;
+pub mod modding;
+pub mod net;
+pub mod particles;
+pub mod physics;
+pub mod player;
+pub mod rules;
+pub mod save;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod settings;
+pub mod sky;
+pub mod snow;
+pub mod state;
+#[cfg(test)]
+mod testing;
+pub mod third_person;
+pub mod tick;
+pub mod ui;
+pub mod util;
+pub mod weather;
+pub mod world;
@@ -0,0 +1,138 @@
+use bevy::{
+    ecs::{
+        query::With,
+        system::{Query, Res, ResMut, Resource},
+    },
+    log::info,
+    math::{Dir3, I64Vec2, I64Vec3},
+    state::state::NextState,
+    transform::components::Transform,
+};
+
+use crate::{
+    chunks::{
+        chunk::ChunkCoordinate,
+        chunk_loader::{is_in_view, GenerateChunkData},
+    },
+    player::Player,
+    state::GameState,
+    world::World,
+};
+
+/// Height just above the terrain at the world origin, where players spawn.
+pub fn spawn_height(world: &World) -> f32 {
+    let height = world
+        .surface(I64Vec2::ZERO)
+        .map_or(0, |surface| surface.height);
+    height as f32 + 2.0
+}
+
+/// Places the player just above the terrain at the world origin.
+pub fn place_player_at_spawn(
+    world: Res<World>,
+    mut player_query: Query<&mut Transform, With<Player>>,
+) {
+    info!("world seed is {}", world.seed());
+    for mut transform in player_query.iter_mut() {
+        transform.translation.y = spawn_height(&world);
+        let spawn = transform.translation;
+        info!("spawned at {:?}, {:?}, {:?}", spawn.x, spawn.y, spawn.z);
+    }
+}
+
+/// Horizontal radius in chunks of the region generated before entering the game.
+const SPAWN_RADIUS: i64 = 1;
+
+#[derive(Resource, Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct LoadingProgress {
+    pub generated: usize,
+    pub required: usize,
+    /// Chunks currently waiting on terrain generation.
+    pub queued: usize,
+}
+
+impl LoadingProgress {
+    pub fn fraction(&self) -> f32 {
+        if self.required == 0 {
+            return 0.0;
+        }
+        self.generated as f32 / self.required as f32
+    }
+}
+
+/// Chunks that must be generated before the player can enter the game: the columns around the
+/// player from their chunk down to the bottom of the world. Only chunks the chunk loader gathers
+/// are included, those near the player and those in the cone in front of them, as the rest would
+/// wait until the player turns.
+pub fn spawn_region(player_chunk: ChunkCoordinate, forward: Dir3) -> Vec<ChunkCoordinate> {
+    let mut region = Vec::new();
+    for x in -SPAWN_RADIUS..=SPAWN_RADIUS {
+        for z in -SPAWN_RADIUS..=SPAWN_RADIUS {
+            for y in 0..=player_chunk.0.y {
+                let chunk =
+                    ChunkCoordinate(I64Vec3::new(player_chunk.0.x + x, y, player_chunk.0.z + z));
+                if is_in_view(chunk, player_chunk, forward) {
+                    region.push(chunk);
+                }
+            }
+        }
+    }
+    region
+}
+
+pub fn reset_loading_progress(mut progress: ResMut<LoadingProgress>) {
+    *progress = LoadingProgress::default();
+}
+
+/// Tracks generation of the spawn region and enters the game once all of it has generated, so
+/// the player never starts above empty space.
+pub fn update_loading(
+    mut world: ResMut<World>,
+    mut progress: ResMut<LoadingProgress>,
+    player_query: Query<&Transform, With<Player>>,
+    generating_query: Query<(), With<GenerateChunkData>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Ok(transform) = player_query.get_single() else {
+        return;
+    };
+
+    let player_chunk = world.chunk_at(transform.translation);
+    let region = spawn_region(player_chunk, transform.forward());
+    *progress = LoadingProgress {
+        generated: region
+            .iter()
+            .filter(|coord| world.is_chunk_generated(**coord))
+            .count(),
+        required: region.len(),
+        queued: generating_query.iter().count(),
+    };
+
+    if progress.generated == progress.required {
+        next_state.set(GameState::InGame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use bevy::math::{Dir3, I64Vec3};
+
+    use super::spawn_region;
+    use crate::chunks::chunk::ChunkCoordinate;
+
+    #[test]
+    fn test_spawn_region_follows_chunk_loader() {
+        let region = spawn_region(ChunkCoordinate(I64Vec3::new(0, 4, 0)), Dir3::Z);
+
+        // every column near the player, down to two chunks below them
+        assert!(region.contains(&ChunkCoordinate(I64Vec3::new(0, 2, -1))));
+        assert!(region.contains(&ChunkCoordinate(I64Vec3::new(1, 4, 1))));
+        // and further down only in front of them
+        assert!(region.contains(&ChunkCoordinate(I64Vec3::new(1, 1, 1))));
+        assert!(!region.contains(&ChunkCoordinate(I64Vec3::new(0, 1, -1))));
+        assert!(!region.contains(&ChunkCoordinate(I64Vec3::new(0, 0, 0))));
+        assert_eq!(region.len(), region.iter().collect::<HashSet<_>>().len());
+    }
+}
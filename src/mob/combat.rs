@@ -0,0 +1,43 @@
+use bevy::ecs::{
+    component::Component,
+    entity::Entity,
+    event::{Event, EventReader},
+    query::Without,
+    system::{Commands, Query},
+};
+
+use super::Health;
+use crate::player::Spectator;
+
+/// Sent to hurt anything with `Health`.
+#[derive(Event, Debug, Copy, Clone, PartialEq)]
+pub struct Damage {
+    pub target: Entity,
+    pub attacker: Option<Entity>,
+    pub amount: f32,
+}
+
+/// Whatever most recently hurt this entity, which pets defend their owner against.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LastAttacker(pub Entity);
+
+pub fn apply_damage(
+    mut commands: Commands,
+    mut damage: EventReader<Damage>,
+    mut health_query: Query<&mut Health, Without<Spectator>>,
+) {
+    for Damage {
+        target,
+        attacker,
+        amount,
+    } in damage.read().copied()
+    {
+        let Ok(mut health) = health_query.get_mut(target) else {
+            continue;
+        };
+        health.current = (health.current - amount).max(0.0);
+        if let Some(attacker) = attacker.filter(|attacker| *attacker != target) {
+            commands.entity(target).insert(LastAttacker(attacker));
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::HashSet;
+
+use bevy::{
+    asset::{Assets, Handle},
+    color::Color,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{With, Without},
+        system::{Commands, Local, Query, Res, ResMut},
+    },
+    math::{I64Vec3, Vec3, Vec3Swizzles},
+    pbr::{MeshMaterial3d, StandardMaterial},
+    prelude::Mesh3d,
+    render::{
+        mesh::{Mesh, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+        view::{NoFrustumCulling, Visibility},
+    },
+    transform::components::Transform,
+};
+
+use super::{registry::MobRegistry, Mob};
+use crate::{
+    input::bindings::{Action, ActionInput},
+    interaction::TargetBlock,
+    item::{Inventory, ItemType},
+    player::Player,
+    tick::TickPosition,
+    world::World,
+};
+
+/// Length of a lead, which a leashed mob can wander within.
+pub const LEAD_LENGTH: f32 = 5.0;
+/// Distance at which a lead breaks and springs back to the player's inventory.
+const SNAP_LENGTH: f32 = 12.0;
+/// Speed a stretched lead pulls its mob back at, per block it's stretched by.
+const PULL_STRENGTH: f32 = 4.0;
+/// Height above a holder's feet their end of the lead is held at.
+const HAND_HEIGHT: f32 = 1.2;
+/// Fraction of a mob's height its lead is tied at.
+const COLLAR_HEIGHT: f32 = 0.75;
+const ROPE_SEGMENTS: usize = 16;
+const ROPE_COLOR: Color = Color::srgb(0.55, 0.4, 0.25);
+
+/// What the other end of a lead is attached to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LeadAnchor {
+    /// Held by an entity, usually the player.
+    Holder(Entity),
+    /// Tied to the fence block at this position.
+    Fence(I64Vec3),
+}
+
+/// A mob on a lead, kept within `LEAD_LENGTH` of its anchor.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Leashed {
+    pub anchor: LeadAnchor,
+}
+
+/// The rope drawn between a leashed mob and its anchor.
+#[derive(Component, Debug)]
+pub struct LeadRope {
+    mob: Entity,
+}
+
+/// Where a lead is knotted around a fence, at the top of the block.
+fn fence_knot(block: I64Vec3) -> Vec3 {
+    block.as_vec3() + Vec3::Y * 0.5
+}
+
+/// Points along a rope of `length` hanging from `start` to `end`. A slack rope sags as a
+/// catenary, and a taut one is straight.
+pub fn catenary(start: Vec3, end: Vec3, length: f32, segments: usize) -> Vec<Vec3> {
+    let offset = end - start;
+    let span = offset.xz().length();
+    if length <= offset.length() || span < 1e-3 {
+        return (0..=segments)
+            .map(|i| start.lerp(end, i as f32 / segments as f32))
+            .collect();
+    }
+
+    // the curve a·cosh(x / a) spans the rope's length when 2a·sinh(span / 2a) is the length
+    // along the chord, which shrinks as `a` grows, so `a` is found by bisection
+    let chord = (length * length - offset.y * offset.y).sqrt();
+    let arc = |a: f32| 2.0 * a * (span / (2.0 * a)).sinh();
+    let (mut low, mut high) = (1e-3_f32, 1e4_f32);
+    for _ in 0..64 {
+        let middle = (low * high).sqrt();
+        if arc(middle) > chord {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    let a = (low * high).sqrt();
+
+    // shift the lowest point along so the curve rises by `offset.y` across the span
+    let lowest = span / 2.0 - a * (offset.y / arc(a)).asinh();
+    let height = |x: f32| a * ((x - lowest) / a).cosh();
+    (0..=segments)
+        .map(|i| {
+            let t = i as f32 / segments as f32;
+            let y = height(span * t) - height(0.0);
+            Vec3::new(start.x + offset.x * t, start.y + y, start.z + offset.z * t)
+        })
+        .collect()
+}
+
+/// Using a lead on a mob ties it to the player, or takes it back off a fence. Using one on a mob
+/// the player is already leading unties it and returns the lead.
+pub fn use_lead(
+    commands: &mut Commands,
+    inventory: &mut Inventory,
+    player: Entity,
+    mob: Entity,
+    leashed: Option<&Leashed>,
+) {
+    match leashed.map(|leashed| leashed.anchor) {
+        Some(LeadAnchor::Holder(holder)) if holder == player => {
+            commands.entity(mob).remove::<Leashed>();
+            inventory.add(ItemType::Lead, 1);
+        }
+        Some(_) => {
+            commands.entity(mob).insert(Leashed {
+                anchor: LeadAnchor::Holder(player),
+            });
+        }
+        None => {
+            if inventory.take(ItemType::Lead, 1) {
+                commands.entity(mob).insert(Leashed {
+                    anchor: LeadAnchor::Holder(player),
+                });
+            }
+        }
+    }
+}
+
+/// Placing onto a fence while leading mobs ties them all to it instead of placing a block.
+pub fn tie_leads_to_fences(
+    input: ActionInput,
+    mut world: ResMut<World>,
+    mut target: ResMut<TargetBlock>,
+    player_query: Query<Entity, With<Player>>,
+    mut leashed_query: Query<&mut Leashed>,
+) {
+    if !input.just_pressed(Action::Place) {
+        return;
+    }
+    let (Some(hit), Ok(player)) = (target.0, player_query.get_single()) else {
+        return;
+    };
+    if !world.get_block(hit.block).is_fence() {
+        return;
+    }
+
+    let mut tied = false;
+    for mut leashed in leashed_query.iter_mut() {
+        if leashed.anchor == LeadAnchor::Holder(player) {
+            leashed.anchor = LeadAnchor::Fence(hit.block);
+            tied = true;
+        }
+    }
+    if tied {
+        target.0 = None;
+    }
+}
+
+/// Pulls leashed mobs back towards their anchor when their lead is stretched. Leads break when
+/// stretched too far or when their fence is gone, returning to the player's inventory.
+pub fn pull_leashed_mobs(
+    mut commands: Commands,
+    mut world: ResMut<World>,
+    mut inventory: ResMut<Inventory>,
+    holder_query: Query<&Transform, Without<Mob>>,
+    mut mob_query: Query<(Entity, &mut Mob, &TickPosition, &Leashed)>,
+) {
+    for (entity, mut mob, position, leashed) in mob_query.iter_mut() {
+        let anchor = match leashed.anchor {
+            LeadAnchor::Holder(holder) => holder_query.get(holder).ok().map(|t| t.translation),
+            LeadAnchor::Fence(block) => {
+                world.get_block(block).is_fence().then(|| fence_knot(block))
+            }
+        };
+        let Some(offset) = anchor
+            .map(|anchor| anchor - position.current)
+            .filter(|offset| offset.length() <= SNAP_LENGTH)
+        else {
+            commands.entity(entity).remove::<Leashed>();
+            inventory.add(ItemType::Lead, 1);
+            continue;
+        };
+
+        let stretch = offset.length() - LEAD_LENGTH;
+        if stretch <= 0.0 {
+            continue;
+        }
+        // cancel any movement away from the anchor, then pull harder the further it's stretched
+        let toward = Vec3::new(offset.x, 0.0, offset.z).normalize_or_zero();
+        let away = mob.velocity.dot(toward).min(0.0);
+        mob.velocity += toward * (PULL_STRENGTH * stretch - away);
+    }
+}
+
+/// Where a leashed mob's anchor is, for its wander radius and its rope.
+pub fn anchor_position(
+    anchor: LeadAnchor,
+    holder_position: impl Fn(Entity) -> Option<Vec3>,
+) -> Option<Vec3> {
+    match anchor {
+        LeadAnchor::Holder(holder) => holder_position(holder),
+        LeadAnchor::Fence(block) => Some(fence_knot(block)),
+    }
+}
+
+/// Draws a rope from each leashed mob to its anchor, removing ropes whose lead is gone.
+#[allow(clippy::too_many_arguments)]
+pub fn update_lead_ropes(
+    mut commands: Commands,
+    registry: Res<MobRegistry>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut rope_material: Local<Option<Handle<StandardMaterial>>>,
+    holder_query: Query<&Transform, Without<Mob>>,
+    mob_query: Query<(Entity, &Mob, &Transform, &Leashed)>,
+    rope_query: Query<(Entity, &LeadRope, &Mesh3d)>,
+) {
+    let rope_points = |mob: Entity| {
+        let (_, mob, transform, leashed) = mob_query.get(mob).ok()?;
+        let height = registry.get(mob.id)?.size().y * COLLAR_HEIGHT;
+        let anchor = anchor_position(leashed.anchor, |holder| {
+            let holder = holder_query.get(holder).ok()?;
+            Some(holder.translation + Vec3::Y * HAND_HEIGHT)
+        })?;
+        let collar = transform.translation + Vec3::Y * height;
+        Some(catenary(anchor, collar, LEAD_LENGTH, ROPE_SEGMENTS))
+    };
+
+    let mut roped = HashSet::new();
+    for (entity, rope, mesh) in rope_query.iter() {
+        match rope_points(rope.mob) {
+            Some(points) => {
+                if let Some(mesh) = meshes.get_mut(&mesh.0) {
+                    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, points);
+                }
+                roped.insert(rope.mob);
+            }
+            None => commands.entity(entity).despawn(),
+        }
+    }
+
+    for (entity, ..) in mob_query.iter() {
+        if roped.contains(&entity) {
+            continue;
+        }
+        let Some(points) = rope_points(entity) else {
+            continue;
+        };
+
+        let material = rope_material
+            .get_or_insert_with(|| {
+                materials.add(StandardMaterial {
+                    base_color: ROPE_COLOR,
+                    unlit: true,
+                    ..Default::default()
+                })
+            })
+            .clone();
+        let mesh = Mesh::new(
+            PrimitiveTopology::LineStrip,
+            RenderAssetUsages::RENDER_WORLD | RenderAssetUsages::MAIN_WORLD,
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, points);
+        // the rope's points are in world space and change every frame, so its bounds go stale
+        commands.spawn((
+            LeadRope { mob: entity },
+            Mesh3d(meshes.add(mesh)),
+            MeshMaterial3d(material),
+            Transform::default(),
+            Visibility::default(),
+            NoFrustumCulling,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec3;
+
+    use super::catenary;
+
+    #[test]
+    fn test_catenary_sags_between_ends() {
+        let (start, end) = (Vec3::new(0.0, 2.0, 0.0), Vec3::new(3.0, 1.0, 0.0));
+        let points = catenary(start, end, 5.0, 16);
+
+        assert_eq!(17, points.len());
+        assert!(points[0].distance(start) < 1e-3);
+        assert!(points[16].distance(end) < 1e-3);
+        assert!(points[8].y < 1.0);
+
+        let length: f32 = points
+            .windows(2)
+            .map(|pair| pair[0].distance(pair[1]))
+            .sum();
+        assert!((length - 5.0).abs() < 0.05, "rope is {length} long");
+    }
+
+    #[test]
+    fn test_taut_catenary_is_straight() {
+        let (start, end) = (Vec3::ZERO, Vec3::new(6.0, 2.0, 3.0));
+        let points = catenary(start, end, 5.0, 4);
+        assert_eq!(start.lerp(end, 0.5), points[2]);
+
+        let hanging = catenary(start, Vec3::new(0.0, -2.0, 0.0), 5.0, 4);
+        assert_eq!(Vec3::new(0.0, -1.0, 0.0), hanging[2]);
+    }
+}
@@ -0,0 +1,256 @@
+use std::{collections::HashMap, path::Path};
+
+use bevy::{
+    app::{App, FixedUpdate, Plugin, Update},
+    asset::{AssetServer, Assets, Handle},
+    color::Color,
+    ecs::{
+        bundle::Bundle,
+        component::Component,
+        entity::Entity,
+        query::{Added, Has},
+        schedule::IntoSystemConfigs,
+        system::{Commands, Local, Query, Res, ResMut},
+    },
+    gltf::GltfAssetLabel,
+    hierarchy::{BuildChildren, DespawnRecursiveExt},
+    log::warn,
+    math::{primitives::Cuboid, Vec3},
+    pbr::{MeshMaterial3d, StandardMaterial},
+    prelude::Mesh3d,
+    render::{mesh::Mesh, view::Visibility},
+    scene::SceneRoot,
+    state::{
+        condition::in_state,
+        state::{OnEnter, OnExit},
+    },
+    time::Time,
+    transform::components::Transform,
+};
+
+use crate::{
+    experience::spawn_orbs,
+    interaction::{edit_block, target_block},
+    item::{ItemDrop, ITEM_SCALE},
+    physics::move_and_collide,
+    state::GameState,
+    tick::TickPosition,
+    world::World,
+};
+
+pub mod combat;
+pub mod lead;
+pub mod natural;
+pub mod pet;
+pub mod registry;
+pub mod ride;
+pub mod spawner;
+
+use combat::{apply_damage, Damage};
+use lead::{pull_leashed_mobs, tie_leads_to_fences, update_lead_ropes};
+use natural::{despawn_far_mobs, spawn_natural_mobs};
+use pet::{interact_with_mobs, load_pets, save_pets};
+use registry::{MobId, MobRegistry, MOBS_DIR};
+use ride::{dismount_riders, seat_riders, steer_mounts, Ridden};
+use spawner::run_spawners;
+
+const MOB_GRAVITY: f32 = 20.0;
+const MAX_FALL_SPEED: f32 = 40.0;
+/// Speed at which drops pop out of a mob that died.
+const DROP_POP_SPEED: f32 = 4.0;
+
+/// Loads the mobs defined in `MOBS_DIR` and simulates every mob in the world.
+pub struct MobPlugin;
+
+impl Plugin for MobPlugin {
+    fn build(&self, app: &mut App) {
+        let registry = MobRegistry::load(Path::new(MOBS_DIR)).unwrap_or_else(|e| {
+            warn!("failed to load mobs from {}: {}", MOBS_DIR, e);
+            MobRegistry::default()
+        });
+
+        app.insert_resource(registry)
+            .add_event::<Damage>()
+            .add_systems(
+                Update,
+                (
+                    add_mob_models,
+                    (apply_damage, kill_dead_mobs).chain(),
+                    (interact_with_mobs, tie_leads_to_fences)
+                        .chain()
+                        .after(target_block)
+                        .before(edit_block),
+                    update_lead_ropes,
+                    (dismount_riders, seat_riders).chain(),
+                    run_spawners,
+                    (spawn_natural_mobs, despawn_far_mobs),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                FixedUpdate,
+                (pull_leashed_mobs, steer_mounts, move_mobs)
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnExit(GameState::Loading), load_pets)
+            .add_systems(OnEnter(GameState::Paused), save_pets);
+    }
+}
+
+/// A creature defined in the `MobRegistry`.
+#[derive(Component, Debug)]
+pub struct Mob {
+    pub id: MobId,
+    pub velocity: Vec3,
+    pub grounded: bool,
+}
+
+#[derive(Component, Debug, Copy, Clone, PartialEq)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+}
+
+/// Components of a mob standing at `position`. Its model is added once it's spawned.
+pub fn mob_bundle(id: MobId, registry: &MobRegistry, position: Vec3) -> impl Bundle {
+    let health = registry.get(id).map_or(1.0, |definition| definition.health);
+    (
+        Mob {
+            id,
+            velocity: Vec3::ZERO,
+            grounded: false,
+        },
+        Health::new(health),
+        TickPosition::new(position),
+        Transform::from_translation(position),
+        Visibility::default(),
+    )
+}
+
+/// Gives new mobs their model, or a box of their size when they don't have one. Boxes are shared
+/// between every mob of a kind.
+fn add_mob_models(
+    mut commands: Commands,
+    registry: Res<MobRegistry>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut boxes: Local<HashMap<MobId, (Handle<Mesh>, Handle<StandardMaterial>)>>,
+    mob_query: Query<(Entity, &Mob), Added<Mob>>,
+) {
+    for (entity, mob) in mob_query.iter() {
+        let Some(definition) = registry.get(mob.id) else {
+            continue;
+        };
+
+        if let Some(model) = &definition.appearance.model {
+            let scene = asset_server.load(GltfAssetLabel::Scene(0).from_asset(model.clone()));
+            commands.entity(entity).with_child(SceneRoot(scene));
+            continue;
+        }
+
+        let (mesh, material) = boxes
+            .entry(mob.id)
+            .or_insert_with(|| {
+                let [r, g, b] = definition.appearance.color;
+                let material = StandardMaterial {
+                    base_color: Color::srgb(r, g, b),
+                    base_color_texture: definition
+                        .appearance
+                        .texture
+                        .clone()
+                        .map(|texture| asset_server.load(texture)),
+                    perceptual_roughness: 1.0,
+                    ..Default::default()
+                };
+                (
+                    meshes.add(Cuboid::from_size(definition.size())),
+                    materials.add(material),
+                )
+            })
+            .clone();
+        commands.entity(entity).with_child((
+            Mesh3d(mesh),
+            MeshMaterial3d(material),
+            Transform::from_xyz(0.0, definition.size().y / 2.0, 0.0),
+        ));
+    }
+}
+
+/// Applies gravity and each mob's velocity, stopping them against terrain.
+pub fn move_mobs(
+    time: Res<Time>,
+    mut world: ResMut<World>,
+    registry: Res<MobRegistry>,
+    mut mob_query: Query<(&mut Mob, &mut TickPosition, Has<Ridden>)>,
+) {
+    let delta_secs = time.delta_secs();
+    for (mut mob, mut tick_position, ridden) in mob_query.iter_mut() {
+        let Some(definition) = registry.get(mob.id) else {
+            continue;
+        };
+        // hold mobs still until the ground under them has loaded
+        let (chunk, _) = world.split_block_coordinate(tick_position.current.round().as_i64vec3());
+        if !world.is_chunk_generated(chunk) {
+            continue;
+        }
+
+        mob.velocity.y = (mob.velocity.y - MOB_GRAVITY * delta_secs).max(-MAX_FALL_SPEED);
+        // a ridden mob carries its rider, who mustn't be pushed into blocks overhead
+        let collider = if ridden {
+            definition.mounted_collider()
+        } else {
+            definition.collider()
+        };
+        let (position, blocked) = move_and_collide(
+            &mut world,
+            tick_position.current,
+            collider,
+            mob.velocity * delta_secs,
+        );
+        tick_position.current = position;
+        mob.grounded = blocked.y && mob.velocity.y < 0.0;
+        mob.velocity = Vec3::select(blocked, Vec3::ZERO, mob.velocity);
+    }
+}
+
+/// Despawns mobs whose health has run out, leaving their drops behind.
+fn kill_dead_mobs(
+    mut commands: Commands,
+    registry: Res<MobRegistry>,
+    mob_query: Query<(Entity, &Mob, &Health, &Transform)>,
+) {
+    let mut rng = rand::thread_rng();
+    for (entity, mob, health, transform) in mob_query.iter() {
+        if !health.is_dead() {
+            continue;
+        }
+
+        commands.entity(entity).despawn_recursive();
+        let Some(definition) = registry.get(mob.id) else {
+            continue;
+        };
+        let centre = transform.translation + Vec3::Y * definition.size().y / 2.0;
+        spawn_orbs(&mut commands, centre, definition.experience);
+        for (block, count) in definition.roll_drops(&mut rng) {
+            for i in 0..count {
+                let angle = i as f32 / count as f32 * std::f32::consts::TAU;
+                commands.spawn((
+                    ItemDrop::new(block, Vec3::new(angle.cos(), DROP_POP_SPEED, angle.sin())),
+                    Transform::from_translation(centre).with_scale(Vec3::splat(ITEM_SCALE)),
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Creatures that appear on their own on open ground around players, as their `SpawnRules` allow,
+//! and are despawned again once every player has left them far behind. Only so many mobs are let
+//! into any one area.
+
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Has, With},
+        system::{Commands, Local, Query, Res, ResMut},
+    },
+    hierarchy::DespawnRecursiveExt,
+    math::{I64Vec2, I64Vec3, Vec3},
+    time::Time,
+    transform::components::Transform,
+};
+use rand::Rng;
+
+use super::{
+    lead::Leashed,
+    mob_bundle,
+    pet::Pet,
+    registry::MobRegistry,
+    ride::{Ridden, Saddled},
+    Mob,
+};
+use crate::{
+    block::BlockType, block_registry::BlockRegistry, daylight::TimeOfDay, player::Player,
+    world::World,
+};
+
+/// Seconds between attempts to spawn around each player.
+const SPAWN_INTERVAL: f32 = 2.0;
+/// Horizontal distances from a player that mobs spawn between, out of sight but not too far.
+const MIN_SPAWN_DISTANCE: f32 = 24.0;
+const MAX_SPAWN_DISTANCE: f32 = 48.0;
+/// Blocks above and below a player the ground is looked for in.
+const SCAN_HEIGHT: i64 = 32;
+/// Mobs within `CAP_RADIUS` blocks of a spot that stop more spawning there.
+const AREA_CAP: usize = 8;
+const CAP_RADIUS: f32 = 32.0;
+/// Distance from every player beyond which naturally spawned mobs are despawned.
+const DESPAWN_DISTANCE: f32 = 96.0;
+/// Blocks either side of a group's first mob the rest spawn within.
+const GROUP_SPREAD: i64 = 3;
+/// Daylight above which it counts as day for `SpawnTime`.
+const DAY_THRESHOLD: f32 = 0.5;
+
+/// A mob that spawned on its own, and so can be despawned when nobody is around.
+#[derive(Component, Debug, Default)]
+pub struct NaturalSpawn;
+
+/// Where a mob could stand on the open ground of `column`, searching down from `top` to `bottom`,
+/// and the block it would stand on. Plants are passed through, but the first solid block down
+/// has to have room for a mob over it and fluids are no place to spawn.
+pub fn surface_spot(
+    world: &mut World,
+    column: I64Vec2,
+    top: i64,
+    bottom: i64,
+) -> Option<(Vec3, BlockType)> {
+    let ground = (bottom..=top)
+        .rev()
+        .map(|y| I64Vec3::new(column.x, y, column.y))
+        .find(|&position| {
+            let block = world.get_block(position);
+            block.is_solid() || block.is_fluid()
+        })?;
+    let block = world.get_block(ground);
+    let headroom = (1..=2).all(|dy| !world.get_block(ground + I64Vec3::Y * dy).is_solid());
+    if block.is_fluid() || !headroom {
+        return None;
+    }
+    Some((ground.as_vec3() + Vec3::Y * 0.5, block))
+}
+
+/// Spawns groups of mobs on the ground around players, every so often and while their area isn't
+/// already full.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_natural_mobs(
+    mut commands: Commands,
+    time: Res<Time>,
+    time_of_day: Res<TimeOfDay>,
+    mut world: ResMut<World>,
+    registry: Res<MobRegistry>,
+    blocks: Res<BlockRegistry>,
+    mut timer: Local<f32>,
+    player_query: Query<&Transform, With<Player>>,
+    mob_query: Query<&Transform, With<Mob>>,
+) {
+    *timer -= time.delta_secs();
+    if *timer > 0.0 {
+        return;
+    }
+    *timer = SPAWN_INTERVAL;
+
+    let mut rng = rand::thread_rng();
+    let day = time_of_day.daylight() > DAY_THRESHOLD;
+    let mut spawned: Vec<Vec3> = vec![];
+    for transform in player_query.iter() {
+        let player = transform.translation;
+        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+        let distance = rng.gen_range(MIN_SPAWN_DISTANCE..MAX_SPAWN_DISTANCE);
+        let column = (Vec3::new(angle.cos(), 0.0, angle.sin()) * distance + player)
+            .round()
+            .as_i64vec3();
+        let column = I64Vec2::new(column.x, column.z);
+        let top = (player.y as i64 + SCAN_HEIGHT).min(world.height as i64);
+        let bottom = player.y as i64 - SCAN_HEIGHT;
+        // terrain that hasn't loaded yet would look like open sky
+        let (top_chunk, _) = world.split_block_coordinate(I64Vec3::new(column.x, top, column.y));
+        if !world.is_chunk_generated(top_chunk) {
+            continue;
+        }
+        let Some((spot, block)) = surface_spot(&mut world, column, top, bottom) else {
+            continue;
+        };
+
+        let around = mob_query
+            .iter()
+            .map(|transform| transform.translation)
+            .chain(spawned.iter().copied())
+            .filter(|position| position.distance(spot) < CAP_RADIUS)
+            .count();
+        if around >= AREA_CAP {
+            continue;
+        }
+        let Some(id) = registry.natural_mob(block, day, &blocks, rng.gen()) else {
+            continue;
+        };
+        let Some(definition) = registry.get(id) else {
+            continue;
+        };
+
+        let (min, max) = definition.spawn.group;
+        let count = (rng.gen_range(min..=max.max(min)) as usize).min(AREA_CAP - around);
+        let mut spots = vec![spot];
+        for _ in 1..count {
+            let offset = I64Vec2::new(
+                rng.gen_range(-GROUP_SPREAD..=GROUP_SPREAD),
+                rng.gen_range(-GROUP_SPREAD..=GROUP_SPREAD),
+            );
+            // the rest of the group keeps to the same kind of ground
+            if let Some((spot, _)) = surface_spot(&mut world, column + offset, top, bottom)
+                .filter(|(_, under)| definition.spawn.allows(*under, &blocks))
+            {
+                spots.push(spot);
+            }
+        }
+        for spot in spots {
+            commands.spawn((mob_bundle(id, &registry, spot), NaturalSpawn));
+            spawned.push(spot);
+        }
+    }
+}
+
+/// Despawns naturally spawned mobs far from every player, keeping any a player has taken on as a
+/// pet, lead or mount.
+#[allow(clippy::type_complexity)]
+pub fn despawn_far_mobs(
+    mut commands: Commands,
+    player_query: Query<&Transform, With<Player>>,
+    mob_query: Query<
+        (
+            Entity,
+            &Transform,
+            Has<Pet>,
+            Has<Leashed>,
+            Has<Saddled>,
+            Has<Ridden>,
+        ),
+        (With<Mob>, With<NaturalSpawn>),
+    >,
+) {
+    let players: Vec<Vec3> = player_query
+        .iter()
+        .map(|transform| transform.translation)
+        .collect();
+    // without a player everything would be out of range
+    if players.is_empty() {
+        return;
+    }
+    for (entity, transform, pet, leashed, saddled, ridden) in mob_query.iter() {
+        if pet || leashed || saddled || ridden {
+            continue;
+        }
+        if players
+            .iter()
+            .all(|player| player.distance(transform.translation) > DESPAWN_DISTANCE)
+        {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec2, I64Vec3, Vec3};
+
+    use super::surface_spot;
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        world::World,
+    };
+
+    #[test]
+    fn test_surface_spot_needs_open_ground() {
+        let mut world = World::new(0);
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), ChunkData::default());
+        let column = I64Vec2::new(4, 4);
+        assert_eq!(None, surface_spot(&mut world, column, 15, 0));
+
+        world.set_block(I64Vec3::new(4, 3, 4), BlockType::Grass);
+        assert_eq!(
+            Some((Vec3::new(4.0, 3.5, 4.0), BlockType::Grass)),
+            surface_spot(&mut world, column, 15, 0)
+        );
+
+        // plants are stood in, but water is no place to spawn
+        world.set_block(I64Vec3::new(4, 4, 4), BlockType::TallGrass);
+        assert_eq!(
+            Some((Vec3::new(4.0, 3.5, 4.0), BlockType::Grass)),
+            surface_spot(&mut world, column, 15, 0)
+        );
+        world.set_block(I64Vec3::new(4, 5, 4), BlockType::Water);
+        assert_eq!(None, surface_spot(&mut world, column, 15, 0));
+
+        // nor is ground with no room over it above the search
+        world.set_block(I64Vec3::new(4, 5, 4), BlockType::Stone);
+        world.set_block(I64Vec3::new(4, 7, 4), BlockType::Stone);
+        assert_eq!(None, surface_spot(&mut world, column, 5, 0));
+        assert_eq!(
+            Some((Vec3::new(4.0, 7.5, 4.0), BlockType::Stone)),
+            surface_spot(&mut world, column, 15, 0)
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Has, With, Without},
+        system::{Commands, Query, Res, ResMut},
+    },
+    log::warn,
+    math::Vec3,
+    render::camera::Camera,
+    transform::components::GlobalTransform,
+};
+use rand::Rng;
+
+use super::{
+    lead::{use_lead, Leashed},
+    mob_bundle,
+    registry::MobRegistry,
+    ride::{mount, Ridden, Riding, Saddled},
+    Health, Mob,
+};
+use crate::{
+    input::bindings::{Action, ActionInput},
+    interaction::{reach, SelectedItem, TargetBlock},
+    item::{Held, Inventory, ItemType},
+    player::{Player, Spectator},
+    save::{SavedEntities, SavedPet, WorldInfo},
+    settings::Settings,
+    third_person::CameraView,
+    tick::TickPosition,
+};
+
+/// A mob tamed by a player, which follows them around unless told to stay.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Pet {
+    pub owner: Entity,
+    pub staying: bool,
+}
+
+/// Using the place action on a mob puts it on a lead when holding one, saddles or climbs onto it
+/// if it can be ridden, tames it when holding the item it's tamed with, or tells a pet to stay or
+/// follow. Placing a block is skipped when a mob is in the way.
+#[allow(clippy::too_many_arguments)]
+pub fn interact_with_mobs(
+    mut commands: Commands,
+    input: ActionInput,
+    registry: Res<MobRegistry>,
+    selected: Res<SelectedItem>,
+    mut inventory: ResMut<Inventory>,
+    mut target: ResMut<TargetBlock>,
+    camera_query: Query<&GlobalTransform, With<Camera>>,
+    view: Res<CameraView>,
+    settings_query: Query<&Settings>,
+    player_query: Query<(Entity, Has<Riding>), (With<Player>, Without<Spectator>)>,
+    mut mob_query: Query<(
+        Entity,
+        &Mob,
+        &TickPosition,
+        Option<&mut Pet>,
+        Option<&Leashed>,
+        Has<Saddled>,
+        Has<Ridden>,
+    )>,
+) {
+    if !input.just_pressed(Action::Place) {
+        return;
+    }
+    let (Ok(camera), Ok((player, riding))) = (camera_query.get_single(), player_query.get_single())
+    else {
+        return;
+    };
+
+    let origin = view.eye(camera);
+    let direction = camera.forward().as_vec3();
+    let reach = target.0.map_or(reach(&settings_query), |hit| hit.distance);
+    let nearest = mob_query
+        .iter()
+        .filter(|(.., ridden)| !ridden)
+        .filter_map(|(entity, mob, position, ..)| {
+            let collider = registry.get(mob.id)?.collider();
+            let distance = collider.raycast(position.current, origin, direction, reach)?;
+            Some((entity, distance))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+    let Some((entity, _)) = nearest else {
+        return;
+    };
+    target.0 = None;
+
+    let Ok((_, mob, _, pet, leashed, saddled, _)) = mob_query.get_mut(entity) else {
+        return;
+    };
+    if selected.held() == Held::Item(ItemType::Lead) {
+        use_lead(&mut commands, &mut inventory, player, entity, leashed);
+        return;
+    }
+    if registry
+        .get(mob.id)
+        .is_some_and(|definition| definition.ride.is_some())
+    {
+        if saddled && !riding {
+            mount(&mut commands, player, entity);
+            return;
+        }
+        if !saddled && selected.held() == Held::Item(ItemType::Saddle) {
+            if inventory.take(ItemType::Saddle, 1) {
+                commands.entity(entity).insert(Saddled);
+            }
+            return;
+        }
+    }
+    match pet {
+        Some(mut pet) if pet.owner == player => pet.staying = !pet.staying,
+        Some(_) => (),
+        None => {
+            let Some(tame) = registry.get(mob.id).and_then(|definition| definition.tame) else {
+                return;
+            };
+            if selected.block() != Some(tame.item) || !inventory.take(tame.item, 1) {
+                return;
+            }
+            if rand::thread_rng().gen::<f32>() < tame.chance {
+                commands.entity(entity).insert(Pet {
+                    owner: player,
+                    staying: false,
+                });
+            }
+        }
+    }
+}
+
+/// Writes every pet to the world's save. Runs whenever the game is paused, which quitting always
+/// goes through.
+pub fn save_pets(
+    world_info: Option<Res<WorldInfo>>,
+    registry: Res<MobRegistry>,
+    pet_query: Query<(&Mob, &Pet, &TickPosition, &Health)>,
+) {
+    let Some(world_info) = world_info else {
+        return;
+    };
+
+    let pets = pet_query
+        .iter()
+        .filter_map(|(mob, pet, position, health)| {
+            Some(SavedPet {
+                mob: registry.get(mob.id)?.name.clone(),
+                position: position.current.to_array(),
+                health: health.current,
+                staying: pet.staying,
+            })
+        })
+        .collect();
+    if let Err(e) = world_info.save_entities(&SavedEntities { pets }) {
+        warn!("failed to save pets: {}", e);
+    }
+}
+
+/// Brings back the pets saved with the world, owned by the local player.
+pub fn load_pets(
+    mut commands: Commands,
+    world_info: Option<Res<WorldInfo>>,
+    registry: Res<MobRegistry>,
+    player_query: Query<Entity, With<Player>>,
+) {
+    let (Some(world_info), Ok(player)) = (world_info, player_query.get_single()) else {
+        return;
+    };
+    let entities = match world_info.load_entities() {
+        Ok(entities) => entities,
+        Err(e) => {
+            warn!("failed to load pets: {}", e);
+            return;
+        }
+    };
+
+    for saved in entities.pets {
+        let Some((id, definition)) = registry.by_name(&saved.mob) else {
+            warn!("skipping saved pet of unknown mob '{}'", saved.mob);
+            continue;
+        };
+        let health = Health {
+            current: saved.health.min(definition.health),
+            max: definition.health,
+        };
+        commands
+            .spawn(mob_bundle(id, &registry, Vec3::from_array(saved.position)))
+            .insert((
+                health,
+                Pet {
+                    owner: player,
+                    staying: saved.staying,
+                },
+            ));
+    }
+}
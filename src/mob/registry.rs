@@ -0,0 +1,531 @@
+use std::{error::Error, fs, path::Path};
+
+use bevy::{ecs::system::Resource, math::Vec3};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    block::BlockType, block_registry::BlockRegistry, physics::Collider, player::PLAYER_COLLIDER,
+};
+
+/// Directory mob definitions are loaded from, one `.ron` file per mob.
+pub const MOBS_DIR: &str = "assets/mobs";
+
+/// Index of a mob in the `MobRegistry`, stable for as long as the game runs.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct MobId(pub u16);
+
+/// Everything that sets one kind of mob apart, read from its file in `MOBS_DIR`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct MobDefinition {
+    /// Name used in commands, e.g. `/summon pig`.
+    pub name: String,
+    pub health: f32,
+    /// Walking speed in blocks per second.
+    pub speed: f32,
+    /// Width, height and depth of the mob's collider in blocks.
+    pub size: [f32; 3],
+    #[serde(default)]
+    pub appearance: MobAppearance,
+    #[serde(default)]
+    pub drops: Vec<MobDrop>,
+    #[serde(default)]
+    pub spawn: SpawnRules,
+    /// Name of a behavior tree in the `BehaviorLibrary`, `"passive"` when left out.
+    #[serde(default)]
+    pub behavior: Option<String>,
+    /// Melee attack used by behaviors with an `Attack` node, mobs without one can't hurt anything.
+    #[serde(default)]
+    pub attack: Option<MobAttack>,
+    /// How the player can tame this mob into a pet, mobs without this can't be tamed.
+    #[serde(default)]
+    pub tame: Option<TameRules>,
+    /// How the mob moves with a rider in its saddle, mobs without this can't be ridden.
+    #[serde(default)]
+    pub ride: Option<RideRules>,
+    /// Experience points dropped as orbs when the mob is killed.
+    #[serde(default)]
+    pub experience: u32,
+}
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct MobAttack {
+    pub damage: f32,
+    /// Seconds between hits.
+    pub cooldown: f32,
+}
+
+/// Using `item` on the mob tames it with probability `chance`, using up one either way.
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct TameRules {
+    pub item: BlockType,
+    pub chance: f32,
+}
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct RideRules {
+    /// Speed in blocks per second while ridden.
+    pub speed: f32,
+    pub jump_speed: f32,
+    /// Height above the mob's feet the rider's feet are at.
+    pub seat_height: f32,
+}
+
+/// How a mob is drawn. Mobs without a model are drawn as a box of their size.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct MobAppearance {
+    /// glTF file whose first scene is the mob's model, relative to the assets directory.
+    pub model: Option<String>,
+    /// Image wrapped onto the box of mobs without a model.
+    pub texture: Option<String>,
+    /// Tint of the box, multiplied with the texture if there is one.
+    pub color: [f32; 3],
+}
+
+impl Default for MobAppearance {
+    fn default() -> Self {
+        Self {
+            model: None,
+            texture: None,
+            color: [1.0, 1.0, 1.0],
+        }
+    }
+}
+
+/// Items dropped when a mob dies.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct MobDrop {
+    pub block: BlockType,
+    pub min: u32,
+    pub max: u32,
+    /// Chance from `0.0` to `1.0` that anything is dropped at all.
+    #[serde(default = "always")]
+    pub chance: f32,
+}
+
+fn always() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum SpawnTime {
+    #[default]
+    Any,
+    Day,
+    Night,
+}
+
+/// Where and when a mob appears on its own.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct SpawnRules {
+    /// Blocks the mob may stand on when it spawns. With no blocks or `tags` it never spawns
+    /// naturally.
+    pub blocks: Vec<BlockType>,
+    /// Block tags from the `BlockRegistry` whose blocks the mob may also stand on.
+    pub tags: Vec<String>,
+    pub time: SpawnTime,
+    /// Smallest and largest number spawned together.
+    pub group: (u32, u32),
+    /// How often this mob is picked relative to others that could spawn in the same place.
+    pub weight: u32,
+    /// How often a dungeon's spawner holds this mob relative to others, `0` for never.
+    pub spawner_weight: u32,
+}
+
+impl Default for SpawnRules {
+    fn default() -> Self {
+        Self {
+            blocks: vec![],
+            tags: vec![],
+            time: SpawnTime::Any,
+            group: (1, 1),
+            weight: 1,
+            spawner_weight: 0,
+        }
+    }
+}
+
+impl SpawnTime {
+    /// Whether mobs spawning at this time may spawn now, during the day or not.
+    pub fn allows(&self, day: bool) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Day => day,
+            Self::Night => !day,
+        }
+    }
+}
+
+impl SpawnRules {
+    /// Whether the mob may spawn standing on `block`.
+    pub fn allows(&self, block: BlockType, registry: &BlockRegistry) -> bool {
+        self.blocks.contains(&block) || self.tags.iter().any(|tag| registry.is(block, tag))
+    }
+}
+
+impl MobDefinition {
+    pub fn parse(source: &str) -> Result<Self, Box<dyn Error>> {
+        let definition: Self = ron::from_str(source)?;
+        definition.validate()?;
+        Ok(definition)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() || self.name.contains(char::is_whitespace) {
+            return Err(format!("mob name '{}' must be a single word", self.name));
+        }
+        if self.health <= 0.0 || self.size.iter().any(|size| *size <= 0.0) {
+            return Err(format!("{} needs positive health and size", self.name));
+        }
+        if self
+            .attack
+            .is_some_and(|attack| attack.damage <= 0.0 || attack.cooldown <= 0.0)
+        {
+            return Err(format!(
+                "{} needs a positive attack damage and cooldown",
+                self.name
+            ));
+        }
+        if self
+            .ride
+            .is_some_and(|ride| ride.speed <= 0.0 || ride.seat_height <= 0.0)
+        {
+            return Err(format!(
+                "{} needs a positive ride speed and seat height",
+                self.name
+            ));
+        }
+        if let Some(drop) = self.drops.iter().find(|drop| drop.min > drop.max) {
+            return Err(format!(
+                "{} drops between {} and {} {}",
+                self.name,
+                drop.min,
+                drop.max,
+                drop.block.name()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn size(&self) -> Vec3 {
+        Vec3::from_array(self.size)
+    }
+
+    /// Box around the mob's feet, which its translation is at.
+    pub fn collider(&self) -> Collider {
+        let size = self.size();
+        Collider::new(
+            Vec3::new(-size.x / 2.0, 0.0, -size.z / 2.0),
+            Vec3::new(size.x / 2.0, size.y, size.z / 2.0),
+        )
+    }
+
+    /// Box around the mob and a rider sitting in its saddle, which a ridden mob collides with.
+    pub fn mounted_collider(&self) -> Collider {
+        let seat_height = self.ride.map_or(self.size().y, |ride| ride.seat_height);
+        self.collider()
+            .union(PLAYER_COLLIDER.offset(Vec3::Y * seat_height))
+    }
+
+    /// Mobs that attack players on sight, which only come from spawners.
+    pub fn is_hostile(&self) -> bool {
+        self.behavior
+            .as_deref()
+            .is_some_and(|behavior| behavior.eq_ignore_ascii_case("hostile"))
+    }
+
+    /// What the mob leaves behind when it dies this time.
+    pub fn roll_drops(&self, rng: &mut impl Rng) -> Vec<(BlockType, u32)> {
+        let mut drops = Vec::new();
+        for drop in &self.drops {
+            if rng.gen::<f32>() >= drop.chance {
+                continue;
+            }
+            let count = rng.gen_range(drop.min..=drop.max);
+            if count > 0 {
+                drops.push((drop.block, count));
+            }
+        }
+        drops
+    }
+}
+
+/// Every kind of mob, loaded from `MOBS_DIR` when the game starts so new mobs need no code.
+#[derive(Resource, Debug, Default)]
+pub struct MobRegistry {
+    definitions: Vec<MobDefinition>,
+}
+
+impl MobRegistry {
+    /// Reads every `.ron` file in `dir`, in name order so ids don't depend on the file system.
+    pub fn load(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut paths: Vec<_> = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        paths.retain(|path| path.extension().is_some_and(|extension| extension == "ron"));
+        paths.sort();
+
+        let mut registry = Self::default();
+        for path in paths {
+            let definition = MobDefinition::parse(&fs::read_to_string(&path)?)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            registry.register(definition);
+        }
+        Ok(registry)
+    }
+
+    /// Adds a mob, replacing any with the same name.
+    pub fn register(&mut self, definition: MobDefinition) -> MobId {
+        if let Some((id, _)) = self.by_name(&definition.name) {
+            self.definitions[id.0 as usize] = definition;
+            return id;
+        }
+        self.definitions.push(definition);
+        MobId(self.definitions.len() as u16 - 1)
+    }
+
+    pub fn get(&self, id: MobId) -> Option<&MobDefinition> {
+        self.definitions.get(id.0 as usize)
+    }
+
+    /// Looks a mob up by name, ignoring case.
+    pub fn by_name(&self, name: &str) -> Option<(MobId, &MobDefinition)> {
+        self.iter()
+            .find(|(_, definition)| definition.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (MobId, &MobDefinition)> {
+        self.definitions
+            .iter()
+            .enumerate()
+            .map(|(i, definition)| (MobId(i as u16), definition))
+    }
+
+    /// The mob a spawner holds, picked by `roll` between the mobs with a `spawner_weight`.
+    pub fn spawner_mob(&self, roll: u64) -> Option<MobId> {
+        let total: u64 = self
+            .iter()
+            .map(|(_, definition)| definition.spawn.spawner_weight as u64)
+            .sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = roll % total;
+        for (id, definition) in self.iter() {
+            let weight = definition.spawn.spawner_weight as u64;
+            if roll < weight {
+                return Some(id);
+            }
+            roll -= weight;
+        }
+        None
+    }
+
+    /// The mob to spawn on its own standing on `block`, picked by `roll` between the mobs that may
+    /// by their `weight`. Hostile mobs are left to spawners.
+    pub fn natural_mob(
+        &self,
+        block: BlockType,
+        day: bool,
+        blocks: &BlockRegistry,
+        roll: u64,
+    ) -> Option<MobId> {
+        let candidates: Vec<(MobId, u64)> = self
+            .iter()
+            .filter(|(_, definition)| {
+                !definition.is_hostile()
+                    && definition.spawn.time.allows(day)
+                    && definition.spawn.allows(block, blocks)
+            })
+            .map(|(id, definition)| (id, definition.spawn.weight as u64))
+            .collect();
+        let total: u64 = candidates.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = roll % total;
+        for (id, weight) in candidates {
+            if roll < weight {
+                return Some(id);
+            }
+            roll -= weight;
+        }
+        None
+    }
+
+    pub fn len(&self) -> usize {
+        self.definitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{MobDefinition, MobRegistry, SpawnTime, MOBS_DIR};
+    use crate::{block::BlockType, block_registry::BlockRegistry, player::PLAYER_COLLIDER};
+
+    const COW: &str = r#"(
+        name: "cow",
+        health: 10.0,
+        speed: 1.5,
+        size: (0.9, 1.4, 1.4),
+        drops: [(block: Grass, min: 0, max: 2)],
+        spawn: (blocks: [Grass], time: Day, group: (2, 4), weight: 8),
+    )"#;
+
+    #[test]
+    fn test_parse_mob_definition() {
+        let cow = MobDefinition::parse(COW).unwrap();
+        assert_eq!("cow", cow.name);
+        assert_eq!(vec![BlockType::Grass], cow.spawn.blocks);
+        assert_eq!(SpawnTime::Day, cow.spawn.time);
+        assert_eq!(1.0, cow.drops[0].chance);
+        assert_eq!(None, cow.appearance.model);
+
+        assert!(MobDefinition::parse(&COW.replace("10.0", "0.0")).is_err());
+        assert!(MobDefinition::parse(&COW.replace("min: 0", "min: 3")).is_err());
+        assert!(MobDefinition::parse(&COW.replace("Grass", "Dirt")).is_err());
+    }
+
+    #[test]
+    fn test_spawn_rules_allow_tagged_blocks() {
+        let registry = BlockRegistry::parse_tags(r#"{"soil": ["Sand", "Snow"]}"#).unwrap();
+        let mut cow = MobDefinition::parse(COW).unwrap();
+        assert!(cow.spawn.allows(BlockType::Grass, &registry));
+        assert!(!cow.spawn.allows(BlockType::Sand, &registry));
+
+        cow.spawn.tags.push("#soil".to_string());
+        assert!(cow.spawn.allows(BlockType::Sand, &registry));
+        assert!(!cow.spawn.allows(BlockType::Stone, &registry));
+    }
+
+    #[test]
+    fn test_roll_drops_within_range() {
+        let cow = MobDefinition::parse(COW).unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..32 {
+            for (block, count) in cow.roll_drops(&mut rng) {
+                assert_eq!(BlockType::Grass, block);
+                assert!((1..=2).contains(&count));
+            }
+        }
+    }
+
+    #[test]
+    fn test_register_replaces_by_name() {
+        let mut registry = MobRegistry::default();
+        let cow = MobDefinition::parse(COW).unwrap();
+        let id = registry.register(cow.clone());
+
+        let faster = MobDefinition { speed: 3.0, ..cow };
+        assert_eq!(id, registry.register(faster));
+        assert_eq!(1, registry.len());
+        assert_eq!(Some(3.0), registry.by_name("COW").map(|(_, cow)| cow.speed));
+    }
+
+    #[test]
+    fn test_parse_attack_and_taming() {
+        let wolf = |damage: f32| {
+            COW.replace(
+                "weight: 8),",
+                &format!(
+                    "weight: 8),
+                    attack: Some((damage: {damage:?}, cooldown: 1.0)),
+                    tame: Some((item: Coal, chance: 0.3)),"
+                ),
+            )
+        };
+        let tameable = MobDefinition::parse(&wolf(4.0)).unwrap();
+        assert_eq!(Some(4.0), tameable.attack.map(|attack| attack.damage));
+        assert_eq!(Some(BlockType::Coal), tameable.tame.map(|tame| tame.item));
+        assert!(MobDefinition::parse(COW).unwrap().tame.is_none());
+        assert!(MobDefinition::parse(&wolf(0.0)).is_err());
+    }
+
+    #[test]
+    fn test_spawner_mob_follows_weights() {
+        let mut registry = MobRegistry::default();
+        registry.register(MobDefinition::parse(COW).unwrap());
+        assert_eq!(None, registry.spawner_mob(0));
+
+        let spawned = |name: &str, weight: u32| {
+            let cow = COW.replace(
+                "weight: 8)",
+                &format!("weight: 8, spawner_weight: {weight})"),
+            );
+            MobDefinition {
+                name: name.to_string(),
+                ..MobDefinition::parse(&cow).unwrap()
+            }
+        };
+        let skeleton = registry.register(spawned("skeleton", 1));
+        let spider = registry.register(spawned("spider", 3));
+        assert_eq!(Some(skeleton), registry.spawner_mob(0));
+        assert_eq!(Some(spider), registry.spawner_mob(1));
+        assert_eq!(Some(spider), registry.spawner_mob(3));
+        assert_eq!(Some(skeleton), registry.spawner_mob(4));
+    }
+
+    #[test]
+    fn test_natural_mob_follows_rules() {
+        let blocks = BlockRegistry::default();
+        let mut registry = MobRegistry::default();
+        let cow = registry.register(MobDefinition::parse(COW).unwrap());
+        let zombie = MobDefinition {
+            name: "zombie".to_string(),
+            behavior: Some("hostile".to_string()),
+            ..MobDefinition::parse(&COW.replace("Day", "Any")).unwrap()
+        };
+        registry.register(zombie);
+        let bat = MobDefinition::parse(&COW.replace("Day", "Night").replace("cow", "bat")).unwrap();
+        let bat = registry.register(bat);
+
+        assert_eq!(
+            Some(cow),
+            registry.natural_mob(BlockType::Grass, true, &blocks, 5)
+        );
+        assert_eq!(
+            Some(bat),
+            registry.natural_mob(BlockType::Grass, false, &blocks, 5)
+        );
+        assert_eq!(
+            None,
+            registry.natural_mob(BlockType::Stone, true, &blocks, 5)
+        );
+    }
+
+    #[test]
+    fn test_mounted_collider_holds_rider() {
+        let horse = COW.replace(
+            "weight: 8),",
+            "weight: 8),
+            ride: Some((speed: 8.0, jump_speed: 8.0, seat_height: 1.2)),",
+        );
+        let horse = MobDefinition::parse(&horse).unwrap();
+        let collider = horse.mounted_collider();
+        assert_eq!(horse.collider().min, collider.min);
+        assert_eq!(1.2 + PLAYER_COLLIDER.max.y, collider.max.y);
+        assert_eq!(0.7, collider.max.z);
+    }
+
+    #[test]
+    fn test_bundled_mobs_load() {
+        let registry = MobRegistry::load(Path::new(MOBS_DIR)).unwrap();
+        assert!(registry.by_name("pig").is_some());
+        assert!(registry
+            .by_name("wolf")
+            .is_some_and(|(_, wolf)| wolf.tame.is_some()));
+        assert!(registry
+            .by_name("horse")
+            .is_some_and(|(_, horse)| horse.ride.is_some()));
+    }
+}
@@ -0,0 +1,161 @@
+use bevy::{
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{With, Without},
+        system::{Commands, Query, Res, ResMut},
+    },
+    math::Vec3,
+    transform::components::Transform,
+};
+
+use super::{registry::MobRegistry, Mob};
+use crate::{
+    input::bindings::{Action, ActionInput},
+    physics::intersects_solid,
+    player::{movement_input, PlayerMovement, PLAYER_COLLIDER},
+    tick::TickPosition,
+    world::World,
+};
+
+/// Gap left between a mount and where its rider lands when they get off.
+const DISMOUNT_GAP: f32 = 0.1;
+
+/// A mob wearing a saddle, which the player can climb on.
+#[derive(Component, Debug, Default)]
+pub struct Saddled;
+
+/// A mob being steered by `rider`.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Ridden {
+    pub rider: Entity,
+}
+
+/// A player sitting on `mount`, whose own movement is switched off until they get off.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Riding {
+    pub mount: Entity,
+}
+
+pub fn mount(commands: &mut Commands, rider: Entity, mount: Entity) {
+    commands.entity(rider).insert(Riding { mount });
+    commands.entity(mount).insert(Ridden { rider });
+}
+
+/// Moves ridden mobs the way their rider's movement keys point, relative to where the rider is
+/// looking.
+pub fn steer_mounts(
+    input: ActionInput,
+    registry: Res<MobRegistry>,
+    rider_query: Query<&Transform, (With<Riding>, Without<Mob>)>,
+    mut mount_query: Query<(&mut Mob, &Ridden)>,
+) {
+    let steering = movement_input(&input);
+    for (mut mob, ridden) in mount_query.iter_mut() {
+        let ride = registry.get(mob.id).and_then(|definition| definition.ride);
+        let (Some(ride), Ok(rider)) = (ride, rider_query.get(ridden.rider)) else {
+            continue;
+        };
+
+        let velocity = rider.rotation * steering * ride.speed;
+        mob.velocity.x = velocity.x;
+        mob.velocity.z = velocity.z;
+        if mob.grounded && input.pressed(Action::Jump) {
+            mob.velocity.y = ride.jump_speed;
+        }
+    }
+}
+
+/// Keeps riders in their mount's saddle and turns mounts to face where their rider looks.
+pub fn seat_riders(
+    registry: Res<MobRegistry>,
+    mut rider_query: Query<(&Riding, &mut Transform), Without<Mob>>,
+    mut mount_query: Query<(&Mob, &mut Transform)>,
+) {
+    for (riding, mut rider) in rider_query.iter_mut() {
+        let Ok((mob, mut mount)) = mount_query.get_mut(riding.mount) else {
+            continue;
+        };
+        let Some(ride) = registry.get(mob.id).and_then(|definition| definition.ride) else {
+            continue;
+        };
+        rider.translation = mount.translation + Vec3::Y * ride.seat_height;
+        mount.rotation = rider.rotation;
+    }
+}
+
+/// Gets riders off when they press descend or their mount is gone, setting them down beside it.
+pub fn dismount_riders(
+    mut commands: Commands,
+    input: ActionInput,
+    mut world: ResMut<World>,
+    registry: Res<MobRegistry>,
+    mut rider_query: Query<(Entity, &Riding, &mut Transform, &mut PlayerMovement), Without<Mob>>,
+    mount_query: Query<(&Mob, &TickPosition), With<Ridden>>,
+) {
+    for (rider, riding, mut transform, mut movement) in rider_query.iter_mut() {
+        let mount = mount_query.get(riding.mount).ok();
+        if mount.is_some() && !input.just_pressed(Action::Descend) {
+            continue;
+        }
+
+        commands.entity(rider).remove::<Riding>();
+        movement.stop();
+        let Some((mob, position)) = mount else {
+            continue;
+        };
+        commands.entity(riding.mount).remove::<Ridden>();
+        if let Some(definition) = registry.get(mob.id) {
+            transform.translation =
+                dismount_position(&mut world, position.current, definition.size());
+        }
+    }
+}
+
+/// Somewhere clear beside a mount of `size` standing at `position` for its rider to get off at,
+/// or on top of it when every side is blocked.
+pub fn dismount_position(world: &mut World, position: Vec3, size: Vec3) -> Vec3 {
+    let reach = size.x.max(size.z) / 2.0 + PLAYER_COLLIDER.max.x + DISMOUNT_GAP;
+    [Vec3::X, Vec3::NEG_X, Vec3::Z, Vec3::NEG_Z]
+        .into_iter()
+        .map(|side| position + side * reach)
+        .find(|spot| {
+            !intersects_solid(
+                world,
+                *spot + PLAYER_COLLIDER.min,
+                *spot + PLAYER_COLLIDER.max,
+            )
+        })
+        .unwrap_or(position + Vec3::Y * size.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec3, Vec3};
+
+    use super::dismount_position;
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        world::World,
+    };
+
+    #[test]
+    fn test_dismount_beside_mount() {
+        let mut world = World::new(1);
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), ChunkData::default());
+        let position = Vec3::new(8.0, 0.5, 8.0);
+        let size = Vec3::new(1.2, 1.6, 2.0);
+
+        let spot = dismount_position(&mut world, position, size);
+        assert_eq!(position.y, spot.y);
+        assert!(spot.x > position.x + 1.0);
+
+        // a wall on the right sends the rider out the other side
+        let wall =
+            (0..4).flat_map(|y| (6..11).map(move |z| (I64Vec3::new(10, y, z), BlockType::Stone)));
+        world.set_blocks(wall);
+        let spot = dismount_position(&mut world, position, size);
+        assert!(spot.x < position.x - 1.0);
+    }
+}
@@ -0,0 +1,173 @@
+//! Spawner blocks in dungeons, which spawn their mob while a player is nearby. Spawners are found
+//! from the dungeons the world generates rather than by searching chunks, and one stops for good
+//! once its block is broken.
+
+use std::collections::HashMap;
+
+use bevy::{
+    ecs::{
+        query::With,
+        system::{Commands, Local, Query, Res, ResMut},
+    },
+    math::{I64Vec3, Vec3},
+    time::Time,
+    transform::components::Transform,
+};
+use rand::Rng;
+
+use super::{mob_bundle, registry::MobRegistry, Mob};
+use crate::{
+    block::BlockType, chunks::generate::structure::spawners_in, player::Player, world::World,
+};
+
+/// Distance in blocks a player must be within for a spawner to run.
+const SPAWNER_RANGE: i64 = 16;
+/// Mobs of a spawner's kind within `CAP_RADIUS` blocks that stop it spawning more.
+const SPAWNER_CAP: usize = 4;
+const CAP_RADIUS: f32 = 8.0;
+/// Blocks either side of a spawner its mobs appear within.
+const SPAWN_SPREAD: i64 = 2;
+/// Seconds between looking for spawners near the player.
+const SEARCH_INTERVAL: f32 = 1.0;
+/// Seconds a spawner waits between spawns, picked at random within this range.
+const SPAWN_DELAY: std::ops::Range<f32> = 10.0..20.0;
+/// Most mobs a spawner spawns at once.
+const MAX_GROUP: usize = 2;
+
+/// The roll a spawner's mob is picked with, fixed by its position so it never changes.
+pub fn spawner_roll(position: I64Vec3) -> u64 {
+    let [x, y, z] = position.to_array().map(|c| c as u64);
+    x.wrapping_mul(73_856_093) ^ y.wrapping_mul(19_349_663) ^ z.wrapping_mul(83_492_791)
+}
+
+/// Feet positions around a spawner a mob could stand at, with two blocks of air over solid ground.
+pub fn spawn_spots(world: &mut World, spawner: I64Vec3) -> Vec<Vec3> {
+    let mut spots = vec![];
+    for x in -SPAWN_SPREAD..=SPAWN_SPREAD {
+        for z in -SPAWN_SPREAD..=SPAWN_SPREAD {
+            for y in -1..=1 {
+                let cell = spawner + I64Vec3::new(x, y, z);
+                if world.get_block(cell) == BlockType::Air
+                    && world.get_block(cell + I64Vec3::Y) == BlockType::Air
+                    && world.get_block(cell - I64Vec3::Y).is_solid()
+                {
+                    spots.push(cell.as_vec3() - Vec3::Y * 0.5);
+                }
+            }
+        }
+    }
+    spots
+}
+
+/// Spawns mobs from the spawners near each player, waiting a while between spawns and stopping
+/// while enough of a spawner's mobs are still around it.
+pub fn run_spawners(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut world: ResMut<World>,
+    registry: Res<MobRegistry>,
+    mut search_timer: Local<f32>,
+    mut cooldowns: Local<HashMap<I64Vec3, f32>>,
+    player_query: Query<&Transform, With<Player>>,
+    mob_query: Query<(&Mob, &Transform)>,
+) {
+    *search_timer -= time.delta_secs();
+    if *search_timer > 0.0 {
+        return;
+    }
+    let elapsed = SEARCH_INTERVAL - *search_timer;
+    *search_timer = SEARCH_INTERVAL;
+
+    let mut rng = rand::thread_rng();
+    let mut noise = world.noise_generator();
+    let (seed, height) = (world.seed(), world.height);
+    let mut nearby = vec![];
+    for transform in player_query.iter() {
+        let player = transform.translation.round().as_i64vec3();
+        let range = I64Vec3::splat(SPAWNER_RANGE);
+        nearby.extend(spawners_in(
+            player - range,
+            player + range,
+            seed,
+            &mut noise,
+            height,
+        ));
+    }
+    nearby.sort_by_key(|position| position.to_array());
+    nearby.dedup();
+    // spawners out of range keep no cooldown, so they start over when a player returns
+    cooldowns.retain(|position, _| nearby.contains(position));
+
+    for spawner in nearby {
+        let cooldown = cooldowns
+            .entry(spawner)
+            .or_insert_with(|| rng.gen_range(SPAWN_DELAY));
+        *cooldown -= elapsed;
+        if *cooldown > 0.0 {
+            continue;
+        }
+        *cooldown = rng.gen_range(SPAWN_DELAY);
+
+        if world.get_block(spawner) != BlockType::Spawner {
+            continue;
+        }
+        let Some(id) = registry.spawner_mob(spawner_roll(spawner)) else {
+            continue;
+        };
+        let centre = spawner.as_vec3();
+        let around = mob_query
+            .iter()
+            .filter(|(mob, transform)| {
+                mob.id == id && transform.translation.distance(centre) < CAP_RADIUS
+            })
+            .count();
+        let mut spots = spawn_spots(&mut world, spawner);
+        let count = rng
+            .gen_range(1..=MAX_GROUP)
+            .min(SPAWNER_CAP.saturating_sub(around));
+        for _ in 0..count {
+            if spots.is_empty() {
+                break;
+            }
+            let spot = spots.swap_remove(rng.gen_range(0..spots.len()));
+            commands.spawn(mob_bundle(id, &registry, spot));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec3, Vec3};
+
+    use super::{spawn_spots, spawner_roll};
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        world::World,
+    };
+
+    #[test]
+    fn test_spawn_spots_stand_on_ground() {
+        let mut world = World::new(0);
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), ChunkData::default());
+        let spawner = I64Vec3::new(4, 2, 4);
+        assert!(spawn_spots(&mut world, spawner).is_empty());
+
+        world.set_block(I64Vec3::new(5, 1, 4), BlockType::Stone);
+        world.set_block(I64Vec3::new(3, 0, 3), BlockType::Stone);
+        // no headroom above this one
+        world.set_block(I64Vec3::new(2, 1, 2), BlockType::Stone);
+        world.set_block(I64Vec3::new(2, 3, 2), BlockType::Stone);
+        assert_eq!(
+            vec![Vec3::new(3.0, 0.5, 3.0), Vec3::new(5.0, 1.5, 4.0)],
+            spawn_spots(&mut world, spawner)
+        );
+    }
+
+    #[test]
+    fn test_spawner_roll_is_fixed_by_position() {
+        let position = I64Vec3::new(-40, 12, 96);
+        assert_eq!(spawner_roll(position), spawner_roll(position));
+        assert_ne!(spawner_roll(position), spawner_roll(position + I64Vec3::X));
+    }
+}
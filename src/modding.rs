@@ -0,0 +1,253 @@
+//! The API for crates extending rustcraft. A mod implements `GameMod` and is added to an app
+//! after `GamePlugin` with `App::add_mod`:
+//!
+//! ```ignore
+//! App::new()
+//!     .add_plugins((DefaultPlugins, GamePlugin))
+//!     .add_mod(MyMod)
+//!     .run();
+//! ```
+//!
+//! Mods can add block tags, mobs, world generators, console commands and systems. They can't add
+//! blocks yet, as every block is compiled into `BlockType`, so a mod's generators build terrain
+//! from the built in blocks.
+
+use std::sync::Arc;
+
+use bevy::{
+    app::App,
+    ecs::{
+        schedule::{IntoSystemConfigs, ScheduleLabel},
+        system::{In, IntoSystem, Res, Resource},
+    },
+    log::{info, warn},
+};
+
+use crate::{
+    block_registry::BlockRegistry,
+    chunks::generate::{
+        noise::SharedNoise,
+        terrain::{ChunkGenerator, GeneratorRegistry},
+    },
+    command::{CommandAppExt, CommandArgs, CommandResult},
+    mob::registry::{MobDefinition, MobRegistry},
+};
+
+pub trait GameMod: Send + Sync + 'static {
+    /// Unique name shown in logs and by `/mods`.
+    fn name(&self) -> &str;
+
+    /// Registers the mod's content. Runs once, when the mod is added to the app.
+    fn build(&self, context: &mut ModContext);
+}
+
+/// What a mod can register into, handed to `GameMod::build`.
+pub struct ModContext<'a> {
+    app: &'a mut App,
+    name: String,
+}
+
+impl ModContext<'_> {
+    /// The app, for anything without a method here, such as resources, events or plugins.
+    pub fn app(&mut self) -> &mut App {
+        self.app
+    }
+
+    /// Adds block names or `#`-prefixed tags to a block tag, creating it if needed.
+    pub fn add_to_tag(&mut self, tag: &str, members: &[&str]) -> &mut Self {
+        let mut registry = self
+            .app
+            .world_mut()
+            .get_resource_mut::<BlockRegistry>()
+            .expect("mods are added after GamePlugin");
+        if let Err(e) = registry.add_to_tag(tag, members) {
+            warn!("mod {} failed to add to tag '{}': {}", self.name, tag, e);
+        }
+        self
+    }
+
+    /// Adds a mob, replacing any built in mob with the same name.
+    pub fn add_mob(&mut self, definition: MobDefinition) -> &mut Self {
+        self.app
+            .world_mut()
+            .get_resource_mut::<MobRegistry>()
+            .expect("mods are added after GamePlugin")
+            .register(definition);
+        self
+    }
+
+    /// Adds a generator new worlds can be created with, picked in the main menu and saved as
+    /// `name`. `factory` builds it for each world from the world's noise, height and chunk size.
+    /// Worlds made with it can't be loaded without the mod.
+    pub fn add_generator(
+        &mut self,
+        name: &str,
+        factory: impl Fn(SharedNoise, u64, u16) -> Arc<dyn ChunkGenerator> + Send + Sync + 'static,
+    ) -> &mut Self {
+        let mut registry = self
+            .app
+            .world_mut()
+            .get_resource_mut::<GeneratorRegistry>()
+            .expect("mods are added after GamePlugin");
+        if let Err(e) = registry.register(name, factory) {
+            warn!(
+                "mod {} failed to add generator '{}': {}",
+                self.name, name, e
+            );
+        }
+        self
+    }
+
+    pub fn add_console_command<M>(
+        &mut self,
+        name: &str,
+        usage: &'static str,
+        description: &'static str,
+        system: impl IntoSystem<In<CommandArgs>, CommandResult, M> + 'static,
+    ) -> &mut Self {
+        self.app
+            .add_console_command(name, usage, description, system);
+        self
+    }
+
+    pub fn add_systems<M>(
+        &mut self,
+        schedule: impl ScheduleLabel,
+        systems: impl IntoSystemConfigs<M>,
+    ) -> &mut Self {
+        self.app.add_systems(schedule, systems);
+        self
+    }
+}
+
+/// Names of the mods added to the app, in the order they were added.
+#[derive(Resource, Default)]
+pub struct LoadedMods(Vec<String>);
+
+pub trait ModAppExt {
+    /// Builds `game_mod` into the app. A second mod with the same name is skipped.
+    fn add_mod(&mut self, game_mod: impl GameMod) -> &mut Self;
+}
+
+impl ModAppExt for App {
+    fn add_mod(&mut self, game_mod: impl GameMod) -> &mut Self {
+        let name = game_mod.name().to_string();
+        let mut loaded = self.world_mut().get_resource_or_init::<LoadedMods>();
+        if loaded.0.contains(&name) {
+            warn!("mod {} was added twice", name);
+            return self;
+        }
+        loaded.0.push(name.clone());
+
+        info!("loading mod {}", name);
+        game_mod.build(&mut ModContext { app: self, name });
+        self
+    }
+}
+
+pub fn mods_command(In(args): In<CommandArgs>, loaded: Option<Res<LoadedMods>>) -> CommandResult {
+    args.finish()?;
+    let names = loaded.map_or(vec![], |loaded| loaded.0.clone());
+    if names.is_empty() {
+        return Ok("no mods are loaded".to_string());
+    }
+    Ok(format!("{} mods: {}", names.len(), names.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bevy::{
+        app::App,
+        ecs::system::In,
+        math::{I64Vec2, I64Vec3},
+    };
+
+    use super::{GameMod, LoadedMods, ModAppExt, ModContext};
+    use crate::{
+        block::BlockType,
+        block_registry::BlockRegistry,
+        chunks::{
+            chunk::{ChunkCoordinate, ChunkData},
+            generate::{
+                biome::ColumnSurface,
+                noise::SharedNoise,
+                terrain::{ChunkGenerator, GeneratorKind, GeneratorRegistry},
+            },
+        },
+        command::{CommandArgs, CommandRegistry, CommandResult},
+        mob::registry::{MobDefinition, MobRegistry},
+    };
+
+    struct TestMod;
+
+    /// Nothing but air, everywhere.
+    struct Empty {
+        chunk_size: u16,
+    }
+
+    impl ChunkGenerator for Empty {
+        fn generate(&self, _coord: ChunkCoordinate) -> ChunkData {
+            ChunkData::with_size(self.chunk_size)
+        }
+
+        fn surface(&self, _column: I64Vec2) -> Option<ColumnSurface> {
+            None
+        }
+    }
+
+    impl GameMod for TestMod {
+        fn name(&self) -> &str {
+            "test"
+        }
+
+        fn build(&self, context: &mut ModContext) {
+            let golem = r#"(name: "golem", health: 50.0, speed: 1.0, size: (1.0, 2.5, 1.0))"#;
+            context
+                .add_to_tag("#ores", &["Glowstone"])
+                .add_to_tag("ores", &["Unobtainium"])
+                .add_mob(MobDefinition::parse(golem).unwrap())
+                .add_generator("empty", |_, _, chunk_size| Arc::new(Empty { chunk_size }))
+                .add_console_command(
+                    "golem",
+                    "golem",
+                    "does nothing",
+                    |In(_): In<CommandArgs>| -> CommandResult { Ok(String::new()) },
+                );
+        }
+    }
+
+    #[test]
+    fn test_add_mod() {
+        let mut app = App::new();
+        app.insert_resource(BlockRegistry::default())
+            .insert_resource(MobRegistry::default())
+            .insert_resource(GeneratorRegistry::default())
+            .add_mod(TestMod)
+            .add_mod(TestMod);
+
+        let world = app.world();
+        assert_eq!(vec!["test".to_string()], world.resource::<LoadedMods>().0);
+        assert!(world
+            .resource::<BlockRegistry>()
+            .is(BlockType::Glowstone, "ores"));
+        let mobs = world.resource::<MobRegistry>();
+        assert_eq!(1, mobs.len());
+        assert_eq!(50.0, mobs.by_name("golem").unwrap().1.health);
+        let empty = GeneratorKind::Mod("empty".to_string())
+            .build(
+                SharedNoise::new(0),
+                256,
+                32,
+                world.resource::<GeneratorRegistry>(),
+            )
+            .unwrap();
+        let chunk = empty.generate(ChunkCoordinate(I64Vec3::new(0, -1, 0)));
+        assert_eq!((32, 0), (chunk.size, chunk.block_count()));
+        assert!(world
+            .resource::<CommandRegistry>()
+            .complete("gol")
+            .starts_with("golem"));
+    }
+}
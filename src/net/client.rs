@@ -0,0 +1,263 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    time::Duration,
+};
+
+use bevy::{
+    ecs::{
+        event::{EventReader, EventWriter},
+        query::With,
+        removal_detection::RemovedComponents,
+        system::{Commands, Local, Query, Res, ResMut, Resource},
+    },
+    log::{info, warn},
+    math::{I64Vec3, Vec3},
+    transform::components::Transform,
+};
+
+use super::{
+    connection::Connection,
+    protocol::{encode_frame, ClientMessage, FrameReader, ServerMessage, PROTOCOL_VERSION},
+    RemotePlayers,
+};
+use crate::{
+    chunks::{
+        chunk::{is_valid_chunk_size, ChunkCoordinate, ChunkData},
+        chunk_loader::{Chunk, ChunkLoader, GenerateChunkData},
+        codec::decode_chunk,
+    },
+    ids::IdMap,
+    interaction::BlockEdited,
+    player::Player,
+    save::WorldInfo,
+    ui::toast::Toast,
+    world::World,
+};
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How far the player must move before their position is sent again.
+const POSITION_THRESHOLD: f32 = 0.05;
+
+/// Connection to a remote server. Terrain is generated locally from the server's seed and
+/// edits are applied immediately as a prediction, with the server's copy of edited chunks and
+/// its block changes overriding local state.
+#[derive(Resource)]
+pub struct Client {
+    connection: Connection,
+    player_id: u32,
+    /// The server's copy of edited chunks, applied once local generation of each has finished.
+    streamed: HashMap<ChunkCoordinate, ChunkData>,
+}
+
+impl Client {
+    /// Connects and completes the handshake, returning the client and the server's world.
+    pub fn connect(addr: SocketAddr) -> Result<(Self, WorldInfo), Box<dyn Error>> {
+        let mut stream = TcpStream::connect_timeout(&addr, HANDSHAKE_TIMEOUT)?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        stream.write_all(&encode_frame(&ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+        }))?;
+
+        let mut reader = FrameReader::default();
+        let mut buffer = [0; 4096];
+        loop {
+            match reader.next_frame::<ServerMessage>()? {
+                Some(ServerMessage::Welcome {
+                    player_id,
+                    seed,
+                    chunk_size,
+                    generator,
+                }) => {
+                    if !is_valid_chunk_size(chunk_size) {
+                        return Err(
+                            format!("server uses unsupported chunk size {chunk_size}").into()
+                        );
+                    }
+                    stream.set_read_timeout(None)?;
+                    let connection = Connection::with_reader(stream, reader)?;
+                    return Ok((
+                        Self {
+                            connection,
+                            player_id,
+                            streamed: HashMap::new(),
+                        },
+                        WorldInfo::new(&addr.to_string(), seed)
+                            .with_chunk_size(chunk_size)
+                            .with_generator(generator),
+                    ));
+                }
+                Some(ServerMessage::Rejected { reason }) => return Err(reason.into()),
+                Some(_) => continue,
+                None => (),
+            }
+
+            let read = stream.read(&mut buffer)?;
+            if read == 0 {
+                return Err("server closed the connection".into());
+            }
+            reader.push(&buffer[..read]);
+        }
+    }
+}
+
+pub fn receive_server_messages(
+    mut commands: Commands,
+    mut client: ResMut<Client>,
+    mut world: ResMut<World>,
+    mut remote_players: ResMut<RemotePlayers>,
+    chunk_loader: Res<ChunkLoader>,
+    mut toasts: EventWriter<Toast>,
+) {
+    let messages = match client.connection.receive::<ServerMessage>() {
+        Ok(messages) => messages,
+        Err(e) => {
+            warn!("lost connection to server, continuing offline: {}", e);
+            commands.remove_resource::<Client>();
+            remote_players.0.clear();
+            return;
+        }
+    };
+
+    for message in messages {
+        match message {
+            ServerMessage::ChunkData { coord, data } => {
+                let coord = ChunkCoordinate(I64Vec3::from_array(coord));
+                let chunk_data = match decode_chunk(&data, &IdMap::default()) {
+                    Ok(chunk_data) if chunk_data.size == world.chunk_size() => chunk_data,
+                    Ok(chunk_data) => {
+                        warn!(
+                            "discarding chunk {:?} from server of size {}",
+                            coord.0, chunk_data.size
+                        );
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("discarding chunk {:?} from server: {}", coord.0, e);
+                        continue;
+                    }
+                };
+
+                // chunks still generating locally would overwrite it, so apply once they finish
+                if world.is_chunk_generated(coord) {
+                    apply_chunk(&mut commands, &mut world, &chunk_loader, coord, chunk_data);
+                } else {
+                    client.streamed.insert(coord, chunk_data);
+                }
+            }
+            ServerMessage::BlockChanged { position, block } => {
+                let block_coord = I64Vec3::from_array(position);
+                if world.get_block(block_coord) != block && world.set_block(block_coord, block) {
+                    chunk_loader.remesh_block(&mut commands, &world, block_coord);
+                }
+            }
+            ServerMessage::PlayerMoved {
+                player_id,
+                position,
+            } => {
+                if player_id != client.player_id {
+                    remote_players
+                        .0
+                        .insert(player_id, Vec3::from_array(position));
+                }
+            }
+            ServerMessage::PlayerLeft { player_id } => {
+                remote_players.0.remove(&player_id);
+            }
+            ServerMessage::Announcement { text } => {
+                info!("server: {}", text);
+                toasts.send(Toast(text));
+            }
+            ServerMessage::Rejected { reason } => {
+                warn!("disconnected by server: {}", reason);
+                commands.remove_resource::<Client>();
+                remote_players.0.clear();
+                return;
+            }
+            ServerMessage::Welcome { .. } => (),
+        }
+    }
+}
+
+fn apply_chunk(
+    commands: &mut Commands,
+    world: &mut World,
+    chunk_loader: &ChunkLoader,
+    coord: ChunkCoordinate,
+    chunk_data: ChunkData,
+) {
+    world.insert_chunk(coord, chunk_data);
+    chunk_loader.remesh_chunk(commands, coord);
+    for adjacent in coord.adjacent() {
+        chunk_loader.remesh_chunk(commands, adjacent);
+    }
+}
+
+/// Replaces locally generated chunks with the server's copy when one was streamed before
+/// generation finished.
+pub fn apply_streamed_chunks(
+    mut commands: Commands,
+    mut client: ResMut<Client>,
+    mut world: ResMut<World>,
+    chunk_loader: Res<ChunkLoader>,
+    mut generated: RemovedComponents<GenerateChunkData>,
+    chunk_query: Query<&Chunk>,
+) {
+    for entity in generated.read() {
+        let Ok(chunk) = chunk_query.get(entity) else {
+            continue;
+        };
+        if let Some(chunk_data) = client.streamed.remove(&chunk.coord()) {
+            apply_chunk(
+                &mut commands,
+                &mut world,
+                &chunk_loader,
+                chunk.coord(),
+                chunk_data,
+            );
+        }
+    }
+}
+
+pub fn send_block_edits(mut client: ResMut<Client>, mut edited: EventReader<BlockEdited>) {
+    for edit in edited.read() {
+        client.connection.send(&ClientMessage::SetBlock {
+            position: edit.position.to_array(),
+            block: edit.block,
+        });
+    }
+}
+
+pub fn send_player_position(
+    mut client: ResMut<Client>,
+    mut last_position: Local<Option<Vec3>>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    let Ok(transform) = player_query.get_single() else {
+        return;
+    };
+
+    let position = transform.translation;
+    let moved = last_position.map_or(true, |last| last.distance(position) > POSITION_THRESHOLD);
+    if moved {
+        *last_position = Some(position);
+        client.connection.send(&ClientMessage::PlayerMoved {
+            position: position.to_array(),
+        });
+    }
+}
+
+pub fn flush_server(mut commands: Commands, mut client: ResMut<Client>) {
+    if let Err(e) = client.connection.flush() {
+        warn!("lost connection to server, continuing offline: {}", e);
+        commands.remove_resource::<Client>();
+    }
+}
+
+pub fn log_connected(client: Res<Client>) {
+    if let Some(addr) = client.connection.peer_addr() {
+        info!("joined {} as player {}", addr, client.player_id);
+    }
+}
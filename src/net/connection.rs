@@ -0,0 +1,125 @@
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpStream},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::protocol::{encode_frame, FrameReader};
+
+/// Bytes a connection may have waiting to be written before its peer is treated as gone, so a
+/// peer that stops reading can't make the other side buffer without limit.
+const MAX_QUEUED_BYTES: usize = 8 * 1024 * 1024;
+
+/// A non-blocking, framed TCP connection. Outgoing frames are buffered and written by `flush`
+/// so a slow peer never stalls the frame.
+pub struct Connection {
+    stream: TcpStream,
+    reader: FrameReader,
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        Self::with_reader(stream, FrameReader::default())
+    }
+
+    /// Wraps a stream that has already been read from, keeping any bytes left over in `reader`.
+    pub fn with_reader(stream: TcpStream, reader: FrameReader) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            reader,
+            outgoing: Vec::new(),
+        })
+    }
+
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.stream.peer_addr().ok()
+    }
+
+    /// Bytes sent but not yet written to the socket.
+    pub fn queued_bytes(&self) -> usize {
+        self.outgoing.len()
+    }
+
+    pub fn send<T: Serialize>(&mut self, message: &T) {
+        self.outgoing.extend(encode_frame(message));
+    }
+
+    /// Writes as much buffered output as the socket will take without blocking, failing if more
+    /// than `MAX_QUEUED_BYTES` is left over.
+    pub fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        if self.outgoing.len() > MAX_QUEUED_BYTES {
+            return Err(io::Error::new(
+                ErrorKind::Other,
+                "peer isn't reading what it's sent",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reads every complete message currently available, failing if the peer disconnected or
+    /// sent something malformed.
+    pub fn receive<T: DeserializeOwned>(&mut self) -> io::Result<Vec<T>> {
+        let mut buffer = [0; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(ErrorKind::ConnectionAborted.into()),
+                Ok(read) => self.reader.push(&buffer[..read]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut messages = Vec::new();
+        while let Some(message) = self
+            .reader
+            .next_frame()
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?
+        {
+            messages.push(message);
+        }
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, TcpListener, TcpStream};
+
+    use super::{Connection, MAX_QUEUED_BYTES};
+
+    #[test]
+    fn test_flush_fails_once_peer_stops_reading() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        // accepted but never read from
+        let _peer = listener.accept().unwrap();
+        let mut connection = Connection::new(stream).unwrap();
+
+        let message = vec![0u8; 1024 * 1024];
+        let mut sent = 0;
+        let flushed = loop {
+            connection.send(&message);
+            sent += message.len();
+            match connection.flush() {
+                Ok(()) if sent < 16 * MAX_QUEUED_BYTES => (),
+                flushed => break flushed,
+            }
+        };
+        assert!(flushed.is_err());
+        assert!(connection.queued_bytes() > MAX_QUEUED_BYTES);
+    }
+}
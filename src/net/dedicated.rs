@@ -0,0 +1,387 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, BufRead},
+    sync::{
+        mpsc::{self, Receiver},
+        Mutex,
+    },
+    thread,
+};
+
+use bevy::{
+    app::{App, AppExit, Plugin, Update},
+    ecs::{
+        event::EventWriter,
+        schedule::{common_conditions::resource_exists, IntoSystemConfigs},
+        system::{In, Res, ResMut, Resource},
+    },
+    log::{error, info, warn},
+    math::I64Vec3,
+    tasks::{AsyncComputeTaskPool, Task},
+    time::Time,
+    utils::futures,
+};
+
+use super::{
+    metrics::{serve_metrics, MetricsEndpoint},
+    schedule::{describe_seconds, RestartCountdown, RestartStep, SchedulePlugin},
+    server::{
+        accept_clients, broadcast_host_edits, flush_clients, receive_client_messages,
+        stream_chunks, Server,
+    },
+    RemotePlayers,
+};
+use crate::{
+    chunks::{
+        chunk::ChunkCoordinate,
+        chunk_loader::{chunk_distance, load_chunk_data, LoadedChunk},
+        generate::structure::StructurePlugin,
+    },
+    command::{
+        run_console_commands, CommandAppExt, CommandArgs, CommandPlugin, CommandResult,
+        ConsoleCommand,
+    },
+    interaction::BlockEdited,
+    save::WorldInfo,
+    world::{history::rollback_command, seed_command, World},
+};
+
+/// Distance in chunks generated around each player, enough to validate every edit within reach.
+const GENERATE_DISTANCE: u32 = 2;
+/// Chunks further than this from every player are unloaded, edited ones being saved first.
+const UNLOAD_DISTANCE: u32 = GENERATE_DISTANCE + 1;
+/// Exit code the server stops with when it should be started again.
+pub const RESTART_EXIT_CODE: u8 = 75;
+/// Seconds before trying again when the world couldn't be saved for a restart.
+const RESTART_RETRY_SECONDS: f32 = 60.0;
+
+/// Runs a `Server` without a local player or any rendering. Expects `Server`, `World` and
+/// `WorldInfo` resources to be inserted by the caller, and serves metrics if a `MetricsEndpoint`
+/// is too. Scheduled tasks are run from a `Schedule` and the server restarts, exiting with
+/// `RESTART_EXIT_CODE`, when a `RestartCountdown` runs out.
+pub struct DedicatedServerPlugin;
+
+impl Plugin for DedicatedServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((CommandPlugin, SchedulePlugin, StructurePlugin))
+            .init_resource::<RemotePlayers>()
+            .init_resource::<ServerChunks>()
+            .add_event::<BlockEdited>()
+            .insert_resource(TtyConsole::spawn())
+            .add_console_command("list", "list", "lists connected players", list_command)
+            .add_console_command(
+                "kick",
+                "kick <player>",
+                "disconnects a player",
+                kick_command,
+            )
+            .add_console_command("save", "save", "saves the world", save_command)
+            .add_console_command("say", "say <message>", "messages every player", say_command)
+            .add_console_command("seed", "seed", "shows the world seed", seed_command)
+            .add_console_command("stop", "stop", "saves and shuts down", stop_command)
+            .add_console_command(
+                "rollback",
+                "rollback <x1> <y1> <z1> <x2> <y2> <z2> <duration>",
+                "undoes block edits inside a box made within e.g. 30m or 2h",
+                rollback_command,
+            )
+            .add_systems(
+                Update,
+                (
+                    (
+                        accept_clients,
+                        receive_client_messages,
+                        generate_around_players,
+                        broadcast_host_edits,
+                        stream_chunks,
+                        flush_clients,
+                    )
+                        .chain(),
+                    read_tty_commands.before(run_console_commands),
+                    serve_metrics
+                        .after(generate_around_players)
+                        .run_if(resource_exists::<MetricsEndpoint>),
+                    count_down_restart
+                        .before(flush_clients)
+                        .run_if(resource_exists::<RestartCountdown>),
+                ),
+            );
+    }
+}
+
+/// Chunks the server has loaded or is loading. Without a camera the chunk loader has nothing to
+/// follow, so terrain is loaded around each connected player instead.
+#[derive(Resource, Default)]
+pub struct ServerChunks {
+    generating: HashMap<ChunkCoordinate, Task<LoadedChunk>>,
+    generated: HashSet<ChunkCoordinate>,
+}
+
+impl ServerChunks {
+    pub fn generating(&self) -> usize {
+        self.generating.len()
+    }
+}
+
+fn generate_around_players(
+    mut chunks: ResMut<ServerChunks>,
+    mut world: ResMut<World>,
+    mut server: ResMut<Server>,
+    world_info: Res<WorldInfo>,
+) {
+    let player_chunks: Vec<ChunkCoordinate> = server
+        .players()
+        .map(|(_, position)| world.chunk_at(position))
+        .collect();
+    let ServerChunks {
+        generating,
+        generated,
+    } = chunks.as_mut();
+
+    let task_pool = AsyncComputeTaskPool::get();
+    let distance = GENERATE_DISTANCE as i64;
+    for centre in &player_chunks {
+        for x in -distance..=distance {
+            for y in -distance..=distance {
+                for z in -distance..=distance {
+                    let coord = ChunkCoordinate(centre.0 + I64Vec3::new(x, y, z));
+                    if generated.contains(&coord) || generating.contains_key(&coord) {
+                        continue;
+                    }
+
+                    let generator = world.generator();
+                    let world_info = world_info.clone();
+                    generating.insert(
+                        coord,
+                        task_pool.spawn(async move {
+                            load_chunk_data(Some(&world_info), &*generator, coord)
+                        }),
+                    );
+                }
+            }
+        }
+    }
+
+    generating.retain(|coord, task| match futures::check_ready(task) {
+        Some(loaded) => {
+            match loaded {
+                LoadedChunk::Saved(chunk_data) => {
+                    world.insert_chunk(*coord, chunk_data);
+                    world.mark_edited(*coord);
+                    server.add_saved_chunk(*coord);
+                }
+                // the corruption has already been logged
+                LoadedChunk::Generated(chunk_data) | LoadedChunk::Recovered(chunk_data, _) => {
+                    world.insert_chunk(*coord, chunk_data);
+                }
+            }
+            generated.insert(*coord);
+            false
+        }
+        None => true,
+    });
+
+    generated.retain(|coord| {
+        let nearby = player_chunks
+            .iter()
+            .any(|centre| chunk_distance(*coord, *centre) <= UNLOAD_DISTANCE);
+        if nearby {
+            return true;
+        }
+        if world.is_edited(*coord) {
+            let saved = world.get_chunk_data(*coord).map_or(Ok(()), |chunk_data| {
+                world_info.save_chunk(*coord, &chunk_data)
+            });
+            if let Err(e) = saved {
+                // kept in memory rather than losing the edits
+                warn!("failed to save chunk {:?}, keeping it loaded: {}", coord, e);
+                return true;
+            }
+        }
+        world.clear_chunk(*coord);
+        false
+    });
+}
+
+/// Lines typed into the server's terminal, read on a background thread so the tick loop never
+/// waits on stdin.
+#[derive(Resource)]
+pub struct TtyConsole(Mutex<Receiver<String>>);
+
+impl TtyConsole {
+    pub fn spawn() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Self(Mutex::new(receiver))
+    }
+}
+
+fn read_tty_commands(console: Res<TtyConsole>, mut commands: EventWriter<ConsoleCommand>) {
+    let receiver = console.0.lock().unwrap();
+    for line in receiver.try_iter() {
+        if let Some(command) = ConsoleCommand::parse(&line) {
+            commands.send(command);
+        }
+    }
+}
+
+fn list_command(In(args): In<CommandArgs>, server: Res<Server>) -> CommandResult {
+    args.finish()?;
+    let mut lines = vec![format!("{} players online", server.players().count())];
+    lines.extend(server.players().map(|(id, position)| {
+        format!(
+            "  player {} at {:.1}, {:.1}, {:.1}",
+            id, position.x, position.y, position.z
+        )
+    }));
+    Ok(lines.join("\n"))
+}
+
+fn kick_command(
+    In(mut args): In<CommandArgs>,
+    mut server: ResMut<Server>,
+    mut remote_players: ResMut<RemotePlayers>,
+) -> CommandResult {
+    let id: u32 = args.number("player id")?;
+    args.finish()?;
+    if server.kick(&mut remote_players, id, "kicked by the server") {
+        Ok(format!("kicked player {}", id))
+    } else {
+        Err(format!("no player {} is connected", id))
+    }
+}
+
+fn save_command(
+    In(args): In<CommandArgs>,
+    world_info: Res<WorldInfo>,
+    mut world: ResMut<World>,
+) -> CommandResult {
+    args.finish()?;
+    save_world(&world_info, &mut world)
+}
+
+fn stop_command(
+    In(args): In<CommandArgs>,
+    mut server: ResMut<Server>,
+    mut remote_players: ResMut<RemotePlayers>,
+    world_info: Res<WorldInfo>,
+    mut world: ResMut<World>,
+    mut exit: EventWriter<AppExit>,
+) -> CommandResult {
+    args.finish()?;
+    kick_everyone(&mut server, &mut remote_players, "server stopped");
+    exit.send(AppExit::Success);
+    save_world(&world_info, &mut world)
+}
+
+fn say_command(In(mut args): In<CommandArgs>, mut server: ResMut<Server>) -> CommandResult {
+    let mut words = vec![args.word("message")?];
+    while let Some(word) = args.optional_word() {
+        words.push(word);
+    }
+    let text = words.join(" ");
+    server.announce(&text);
+    Ok(format!("said '{}'", text))
+}
+
+fn count_down_restart(
+    time: Res<Time>,
+    mut countdown: ResMut<RestartCountdown>,
+    mut server: ResMut<Server>,
+    mut remote_players: ResMut<RemotePlayers>,
+    world_info: Res<WorldInfo>,
+    mut world: ResMut<World>,
+    mut exit: EventWriter<AppExit>,
+) {
+    match countdown.tick(time.delta()) {
+        Some(RestartStep::Warn(seconds)) => {
+            let text = format!("server restarting in {}", describe_seconds(seconds));
+            info!("{}", text);
+            server.announce(&text);
+        }
+        Some(RestartStep::Restart) => match save_world(&world_info, &mut world) {
+            Ok(line) => {
+                info!("{}", line);
+                kick_everyone(&mut server, &mut remote_players, "server restarting");
+                info!("restarting");
+                exit.send(AppExit::from_code(RESTART_EXIT_CODE));
+            }
+            // restarting now would lose every edit made since the last save
+            Err(line) => {
+                error!(
+                    "{}, restarting in {:.0} seconds instead",
+                    line, RESTART_RETRY_SECONDS
+                );
+                countdown.postpone(RESTART_RETRY_SECONDS);
+            }
+        },
+        None => (),
+    }
+}
+
+fn kick_everyone(server: &mut Server, remote_players: &mut RemotePlayers, reason: &str) {
+    let ids: Vec<u32> = server.players().map(|(id, _)| id).collect();
+    for id in ids {
+        server.kick(remote_players, id, reason);
+    }
+}
+
+/// Saves the world's metadata and every edited chunk still loaded, those already unloaded having
+/// been saved as they went.
+fn save_world(world_info: &WorldInfo, world: &mut World) -> CommandResult {
+    let saved = world_info
+        .save()
+        .and_then(|()| world.save_edited_chunks(world_info));
+    match saved {
+        Ok(chunks) => Ok(format!(
+            "saved {} and {} edited chunks",
+            world_info.name, chunks
+        )),
+        Err(e) => Err(format!("failed to save {}: {}", world_info.name, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec3, U16Vec3};
+
+    use super::save_world;
+    use crate::{
+        block::BlockType,
+        chunks::{
+            chunk::ChunkCoordinate,
+            chunk_loader::{load_chunk_data, LoadedChunk},
+        },
+        save::{TestSaves, WorldInfo},
+        world::World,
+    };
+
+    #[test]
+    fn test_edits_survive_save_world() {
+        let _saves = TestSaves::new();
+        let info = WorldInfo::new("dedicated save test", 1);
+        let coord = ChunkCoordinate(I64Vec3::ZERO);
+        let mut world = World::new(1);
+        let generator = world.generator();
+        world.insert_chunk(coord, generator.generate(coord));
+        assert!(world.set_block(I64Vec3::new(1, 2, 3), BlockType::Glowstone));
+        save_world(&info, &mut world).unwrap();
+
+        let reloaded = WorldInfo::load(&info.dir()).unwrap();
+        let LoadedChunk::Saved(chunk_data) =
+            load_chunk_data(Some(&reloaded), &*World::new(1).generator(), coord)
+        else {
+            panic!("the edited chunk wasn't saved");
+        };
+        assert_eq!(
+            BlockType::Glowstone,
+            chunk_data.get_block_at(U16Vec3::new(1, 2, 3))
+        );
+    }
+}
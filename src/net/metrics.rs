@@ -0,0 +1,288 @@
+//! An optional HTTP endpoint on the dedicated server serving Prometheus metrics, so instances
+//! can be monitored with standard tooling. Enabled by `metrics_port` in `[network]` settings.
+
+use std::{
+    fmt::Write as _,
+    io::{self, ErrorKind, Read, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    ecs::system::{Res, ResMut, Resource},
+    log::warn,
+    time::Time,
+};
+
+use super::{dedicated::ServerChunks, server::Server};
+use crate::world::World;
+
+/// Largest request read before the connection is dropped.
+const MAX_REQUEST_LENGTH: usize = 8192;
+/// Scrapes waited on at once, more are closed as soon as they're accepted.
+const MAX_PENDING_REQUESTS: usize = 16;
+/// How long a scrape has to send its request before it is dropped, so idle or slow connections
+/// can't hold on to the pending slots.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a scrape may take to read its response before it is dropped, stalling the tick.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+/// Weight of the newest tick in the averaged tick rate.
+const TICK_SMOOTHING: f32 = 0.05;
+
+/// Listens for scrapes without blocking the tick loop. Requests are read a little each tick and
+/// answered once complete.
+#[derive(Resource)]
+pub struct MetricsEndpoint {
+    listener: TcpListener,
+    requests: Vec<PendingRequest>,
+    /// Seconds per tick, averaged over recent ticks.
+    tick_time: f32,
+}
+
+/// A scrape whose request is still being read.
+struct PendingRequest {
+    stream: TcpStream,
+    request: Vec<u8>,
+    accepted: Instant,
+}
+
+impl MetricsEndpoint {
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            requests: Vec::new(),
+            tick_time: 0.0,
+        })
+    }
+
+    /// Drops scrapes that have taken longer than `REQUEST_TIMEOUT`, then accepts new ones up to
+    /// `MAX_PENDING_REQUESTS`.
+    fn accept_requests(&mut self, now: Instant) {
+        self.requests
+            .retain(|pending| now.duration_since(pending.accepted) < REQUEST_TIMEOUT);
+        loop {
+            match self.listener.accept() {
+                // closed rather than left waiting to be accepted
+                Ok(_) if self.requests.len() >= MAX_PENDING_REQUESTS => (),
+                Ok((stream, _)) => {
+                    if stream.set_nonblocking(true).is_ok() {
+                        self.requests.push(PendingRequest {
+                            stream,
+                            request: Vec::new(),
+                            accepted: now,
+                        });
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    warn!("failed to accept metrics connection: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// A gauge as shown to Prometheus.
+struct Metric {
+    name: &'static str,
+    help: &'static str,
+    value: f64,
+}
+
+pub fn serve_metrics(
+    time: Res<Time>,
+    mut endpoint: ResMut<MetricsEndpoint>,
+    server: Res<Server>,
+    world: Res<World>,
+    chunks: Res<ServerChunks>,
+) {
+    let delta = time.delta_secs();
+    endpoint.tick_time = if endpoint.tick_time == 0.0 {
+        delta
+    } else {
+        endpoint.tick_time + (delta - endpoint.tick_time) * TICK_SMOOTHING
+    };
+
+    endpoint.accept_requests(Instant::now());
+    if endpoint.requests.is_empty() {
+        return;
+    }
+
+    let metrics = [
+        Metric {
+            name: "rustcraft_ticks_per_second",
+            help: "Server ticks per second, averaged over recent ticks.",
+            value: if endpoint.tick_time > 0.0 {
+                1.0 / endpoint.tick_time as f64
+            } else {
+                0.0
+            },
+        },
+        Metric {
+            name: "rustcraft_players",
+            help: "Players who have joined.",
+            value: server.players().count() as f64,
+        },
+        Metric {
+            name: "rustcraft_connections",
+            help: "Open connections, including players still joining.",
+            value: server.connections() as f64,
+        },
+        Metric {
+            name: "rustcraft_loaded_chunks",
+            help: "Chunks held in memory.",
+            value: world.resident_chunks() as f64,
+        },
+        Metric {
+            name: "rustcraft_edited_chunks",
+            help: "Chunks edited since the server started.",
+            value: server.edited_chunks() as f64,
+        },
+        Metric {
+            name: "rustcraft_generating_chunks",
+            help: "Chunks queued for generation.",
+            value: chunks.generating() as f64,
+        },
+        Metric {
+            name: "rustcraft_outgoing_bytes",
+            help: "Bytes queued to be sent to clients.",
+            value: server.queued_bytes() as f64,
+        },
+        Metric {
+            name: "rustcraft_chunk_memory_bytes",
+            help: "Memory used by loaded chunk data.",
+            value: world.resident_bytes() as f64,
+        },
+    ];
+
+    endpoint.requests.retain_mut(
+        |PendingRequest {
+             stream, request, ..
+         }| {
+            let mut buffer = [0; 1024];
+            loop {
+                match stream.read(&mut buffer) {
+                    Ok(0) => return false,
+                    Ok(read) => request.extend_from_slice(&buffer[..read]),
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(_) => return false,
+                }
+            }
+            if request.len() > MAX_REQUEST_LENGTH {
+                return false;
+            }
+            let Some(end) = request.windows(4).position(|window| window == b"\r\n\r\n") else {
+                return true;
+            };
+
+            let response = respond(&request[..end], &metrics);
+            // the response fits in the socket's buffer, so this only waits on a stalled client
+            let _ = stream
+                .set_nonblocking(false)
+                .and_then(|()| stream.set_write_timeout(Some(WRITE_TIMEOUT)))
+                .and_then(|()| stream.write_all(response.as_bytes()));
+            false
+        },
+    );
+}
+
+/// The HTTP response to a request, given its head.
+fn respond(head: &[u8], metrics: &[Metric]) -> String {
+    let request_line = head.split(|byte| *byte == b'\r').next().unwrap_or_default();
+    let mut parts = request_line.split(|byte| *byte == b' ');
+    let (method, path) = (parts.next(), parts.next());
+    let (status, body) = match (method, path) {
+        (Some(b"GET"), Some(b"/metrics")) => ("200 OK", render_metrics(metrics)),
+        (Some(b"GET"), _) => ("404 Not Found", "not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "only GET is supported\n".to_string(),
+        ),
+    };
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Metrics in Prometheus's text exposition format.
+fn render_metrics(metrics: &[Metric]) -> String {
+    let mut text = String::new();
+    for metric in metrics {
+        let _ = writeln!(text, "# HELP {} {}", metric.name, metric.help);
+        let _ = writeln!(text, "# TYPE {} gauge", metric.name);
+        let _ = writeln!(text, "{} {}", metric.name, metric.value);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, TcpStream},
+        time::Instant,
+    };
+
+    use super::{
+        render_metrics, respond, Metric, MetricsEndpoint, MAX_PENDING_REQUESTS, REQUEST_TIMEOUT,
+    };
+
+    fn metrics() -> [Metric; 2] {
+        [
+            Metric {
+                name: "rustcraft_players",
+                help: "Players who have joined.",
+                value: 3.0,
+            },
+            Metric {
+                name: "rustcraft_ticks_per_second",
+                help: "Server ticks per second.",
+                value: 29.5,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_render_metrics() {
+        assert_eq!(
+            "# HELP rustcraft_players Players who have joined.\n\
+             # TYPE rustcraft_players gauge\n\
+             rustcraft_players 3\n\
+             # HELP rustcraft_ticks_per_second Server ticks per second.\n\
+             # TYPE rustcraft_ticks_per_second gauge\n\
+             rustcraft_ticks_per_second 29.5\n",
+            render_metrics(&metrics())
+        );
+    }
+
+    #[test]
+    fn test_respond() {
+        let response = respond(b"GET /metrics HTTP/1.1\r\nHost: localhost", &metrics());
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("rustcraft_ticks_per_second 29.5\n"));
+        let body = render_metrics(&metrics());
+        assert!(response.contains(&format!("Content-Length: {}\r\n", body.len())));
+
+        assert!(respond(b"GET / HTTP/1.1", &metrics()).starts_with("HTTP/1.1 404"));
+        assert!(respond(b"POST /metrics HTTP/1.1", &metrics()).starts_with("HTTP/1.1 405"));
+        assert!(respond(b"", &metrics()).starts_with("HTTP/1.1 405"));
+    }
+
+    #[test]
+    fn test_pending_requests_are_capped_and_time_out() {
+        let mut endpoint = MetricsEndpoint::bind(0).unwrap();
+        let port = endpoint.listener.local_addr().unwrap().port();
+        let _clients: Vec<TcpStream> = (0..MAX_PENDING_REQUESTS + 2)
+            .map(|_| TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap())
+            .collect();
+
+        let now = Instant::now();
+        endpoint.accept_requests(now);
+        assert_eq!(MAX_PENDING_REQUESTS, endpoint.requests.len());
+        endpoint.accept_requests(now + REQUEST_TIMEOUT);
+        assert!(endpoint.requests.is_empty());
+    }
+}
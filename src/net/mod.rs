@@ -0,0 +1,88 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    chunks::chunk_loader::receive_chunk_results,
+    interaction::edit_block,
+    player::PLAYER_COLLIDER,
+    state::{in_world, GameState},
+};
+
+pub mod client;
+pub mod connection;
+pub mod dedicated;
+pub mod metrics;
+pub mod protocol;
+pub mod schedule;
+pub mod server;
+
+use client::{
+    apply_streamed_chunks, flush_server, log_connected, receive_server_messages, send_block_edits,
+    send_player_position, Client,
+};
+use schedule::SchedulePlugin;
+use server::{
+    accept_clients, broadcast_host_edits, broadcast_host_position, flush_clients,
+    receive_client_messages, start_server, stream_chunks, Server,
+};
+
+/// Last known position of every other player in the session, keyed by player id.
+#[derive(Resource, Default)]
+pub struct RemotePlayers(pub HashMap<u32, Vec3>);
+
+pub struct NetworkPlugin;
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(SchedulePlugin)
+            .init_resource::<RemotePlayers>()
+            .add_systems(
+                OnEnter(GameState::Loading),
+                (
+                    start_server,
+                    log_connected.run_if(resource_exists::<Client>),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    accept_clients,
+                    receive_client_messages,
+                    broadcast_host_edits.after(edit_block),
+                    broadcast_host_position,
+                    stream_chunks,
+                    flush_clients,
+                )
+                    .chain()
+                    .run_if(resource_exists::<Server>)
+                    .run_if(in_world),
+            )
+            .add_systems(
+                Update,
+                (
+                    receive_server_messages,
+                    apply_streamed_chunks.after(receive_chunk_results),
+                    send_block_edits.after(edit_block),
+                    send_player_position,
+                    flush_server,
+                )
+                    .chain()
+                    .run_if(resource_exists::<Client>)
+                    .run_if(in_world),
+            )
+            .add_systems(
+                Update,
+                draw_remote_players.run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+fn draw_remote_players(remote_players: Res<RemotePlayers>, mut gizmos: Gizmos) {
+    let size = PLAYER_COLLIDER.max - PLAYER_COLLIDER.min;
+    for position in remote_players.0.values() {
+        let centre = *position + (PLAYER_COLLIDER.min + PLAYER_COLLIDER.max) / 2.0;
+        gizmos.cuboid(
+            Transform::from_translation(centre).with_scale(size),
+            Color::srgb(1.0, 0.8, 0.2),
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt::Display;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{block::BlockType, chunks::generate::terrain::GeneratorKind};
+
+/// Bumped whenever a message changes shape, clients and servers must match exactly.
+pub const PROTOCOL_VERSION: u32 = 6;
+pub const DEFAULT_PORT: u16 = 25565;
+/// Largest frame either side will accept, guarding against corrupt or hostile length prefixes.
+const MAX_FRAME_SIZE: usize = 4 * 1024 * 1024;
+const LENGTH_PREFIX_SIZE: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ClientMessage {
+    Hello {
+        version: u32,
+    },
+    PlayerMoved {
+        position: [f32; 3],
+    },
+    SetBlock {
+        position: [i64; 3],
+        block: BlockType,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ServerMessage {
+    Welcome {
+        player_id: u32,
+        seed: u32,
+        chunk_size: u16,
+        generator: GeneratorKind,
+    },
+    Rejected {
+        reason: String,
+    },
+    /// A chunk encoded with `chunks::codec`, sent for chunks that differ from what the seed
+    /// generates.
+    ChunkData {
+        coord: [i64; 3],
+        data: Vec<u8>,
+    },
+    BlockChanged {
+        position: [i64; 3],
+        block: BlockType,
+    },
+    PlayerMoved {
+        player_id: u32,
+        position: [f32; 3],
+    },
+    PlayerLeft {
+        player_id: u32,
+    },
+    /// Text from the server shown to every player, such as a restart warning.
+    Announcement {
+        text: String,
+    },
+}
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    FrameTooLarge(usize),
+    Decode(bincode::Error),
+}
+
+impl Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FrameTooLarge(size) => write!(f, "frame of {size} bytes exceeds limit"),
+            Self::Decode(e) => write!(f, "malformed message: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+/// Encodes a message as a little-endian `u32` length prefix followed by its bincode body.
+pub fn encode_frame<T: Serialize>(message: &T) -> Vec<u8> {
+    let body = bincode::serialize(message).expect("protocol messages are always serializable");
+    let mut frame = Vec::with_capacity(LENGTH_PREFIX_SIZE + body.len());
+    frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&body);
+    frame
+}
+
+/// Reassembles frames from a byte stream that may split or join them arbitrarily.
+#[derive(Default)]
+pub struct FrameReader {
+    buffer: Vec<u8>,
+}
+
+impl FrameReader {
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    pub fn next_frame<T: DeserializeOwned>(&mut self) -> Result<Option<T>, ProtocolError> {
+        if self.buffer.len() < LENGTH_PREFIX_SIZE {
+            return Ok(None);
+        }
+
+        let mut prefix = [0; LENGTH_PREFIX_SIZE];
+        prefix.copy_from_slice(&self.buffer[..LENGTH_PREFIX_SIZE]);
+        let length = u32::from_le_bytes(prefix) as usize;
+        if length > MAX_FRAME_SIZE {
+            return Err(ProtocolError::FrameTooLarge(length));
+        }
+        if self.buffer.len() < LENGTH_PREFIX_SIZE + length {
+            return Ok(None);
+        }
+
+        let frame: Vec<u8> = self
+            .buffer
+            .drain(..LENGTH_PREFIX_SIZE + length)
+            .skip(LENGTH_PREFIX_SIZE)
+            .collect();
+        bincode::deserialize(&frame)
+            .map(Some)
+            .map_err(ProtocolError::Decode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode_frame, ClientMessage, FrameReader, ProtocolError, ServerMessage};
+    use crate::block::BlockType;
+
+    #[test]
+    fn test_frame_round_trip() {
+        let message = ServerMessage::BlockChanged {
+            position: [1, -2, 3],
+            block: BlockType::Sand,
+        };
+
+        let mut reader = FrameReader::default();
+        reader.push(&encode_frame(&message));
+        assert_eq!(Some(message), reader.next_frame().unwrap());
+        assert_eq!(None, reader.next_frame::<ServerMessage>().unwrap());
+    }
+
+    #[test]
+    fn test_frame_reader_handles_split_and_joined_frames() {
+        let first = ClientMessage::Hello { version: 1 };
+        let second = ClientMessage::PlayerMoved {
+            position: [0.0, 1.0, 2.0],
+        };
+        let mut bytes = encode_frame(&first);
+        bytes.extend(encode_frame(&second));
+
+        let mut reader = FrameReader::default();
+        let (head, tail) = bytes.split_at(3);
+        reader.push(head);
+        assert_eq!(None, reader.next_frame::<ClientMessage>().unwrap());
+
+        reader.push(tail);
+        assert_eq!(Some(first), reader.next_frame().unwrap());
+        assert_eq!(Some(second), reader.next_frame().unwrap());
+    }
+
+    #[test]
+    fn test_frame_reader_rejects_oversized_frame() {
+        let mut reader = FrameReader::default();
+        reader.push(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            reader.next_frame::<ClientMessage>(),
+            Err(ProtocolError::FrameTooLarge(_))
+        ));
+    }
+}
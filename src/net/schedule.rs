@@ -0,0 +1,254 @@
+//! Timed events configured in the `[server]` section of the settings: console commands run over
+//! and over, and the dedicated server's restart countdown.
+
+use std::time::Duration;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        event::EventWriter,
+        schedule::{common_conditions::resource_exists, IntoSystemConfigs},
+        system::{Res, ResMut, Resource},
+    },
+    log::{info, warn},
+    time::{Time, Timer, TimerMode},
+};
+
+use super::server::Server;
+use crate::{
+    command::{run_console_commands, ConsoleCommand},
+    settings::ServerSettings,
+};
+
+/// Runs a `Schedule`'s tasks while a `Server` is running. Expects the `Schedule` to be inserted
+/// by the caller.
+pub struct SchedulePlugin;
+
+impl Plugin for SchedulePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            run_scheduled_tasks
+                .before(run_console_commands)
+                .run_if(resource_exists::<Schedule>)
+                .run_if(resource_exists::<Server>),
+        );
+    }
+}
+
+/// Console commands run at fixed intervals.
+#[derive(Resource, Default)]
+pub struct Schedule {
+    tasks: Vec<(String, Timer)>,
+}
+
+impl Schedule {
+    pub fn new(settings: &ServerSettings) -> Self {
+        let tasks = settings
+            .tasks
+            .iter()
+            .filter(|task| {
+                let valid = task.every_minutes > 0.0;
+                if !valid {
+                    warn!(
+                        "ignoring task '{}' run every {} minutes",
+                        task.command, task.every_minutes
+                    );
+                }
+                valid
+            })
+            .map(|task| {
+                let timer = Timer::from_seconds(task.every_minutes * 60.0, TimerMode::Repeating);
+                (task.command.clone(), timer)
+            })
+            .collect();
+        Self { tasks }
+    }
+
+    /// Advances every task's timer, returning the commands that are due.
+    pub fn tick(&mut self, delta: Duration) -> Vec<&str> {
+        self.tasks
+            .iter_mut()
+            .filter_map(|(command, timer)| {
+                // a long stall runs a task once rather than once per missed interval
+                let due = timer.tick(delta).times_finished_this_tick() > 0;
+                due.then_some(command.as_str())
+            })
+            .collect()
+    }
+}
+
+fn run_scheduled_tasks(
+    time: Res<Time>,
+    mut schedule: ResMut<Schedule>,
+    mut commands: EventWriter<ConsoleCommand>,
+) {
+    for line in schedule.tick(time.delta()) {
+        info!("running scheduled task '{}'", line);
+        if let Some(command) = ConsoleCommand::parse(line) {
+            commands.send(command);
+        }
+    }
+}
+
+/// Time left until the dedicated server restarts, and the warnings still to be given.
+#[derive(Resource, Debug)]
+pub struct RestartCountdown {
+    remaining: f32,
+    /// Seconds before the restart, largest first.
+    warnings: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RestartStep {
+    /// Players should be told the server restarts in this many seconds.
+    Warn(u32),
+    Restart,
+}
+
+impl RestartCountdown {
+    /// The countdown configured by `settings`, if restarts are enabled.
+    pub fn new(settings: &ServerSettings) -> Option<Self> {
+        let hours = settings.restart_hours.filter(|hours| *hours > 0.0)?;
+        let remaining = hours * 60.0 * 60.0;
+        let mut warnings: Vec<u32> = settings
+            .restart_warnings
+            .iter()
+            .copied()
+            .filter(|warning| (*warning as f32) < remaining)
+            .collect();
+        warnings.sort_unstable_by(|a, b| b.cmp(a));
+        warnings.dedup();
+        Some(Self {
+            remaining,
+            warnings,
+        })
+    }
+
+    pub fn remaining(&self) -> f32 {
+        self.remaining
+    }
+
+    /// Puts the restart off for `seconds` more, without warning players again.
+    pub fn postpone(&mut self, seconds: f32) {
+        self.remaining = seconds;
+        self.warnings.clear();
+    }
+
+    /// Counts down by `delta`. When several warnings pass at once only the last is given.
+    pub fn tick(&mut self, delta: Duration) -> Option<RestartStep> {
+        self.remaining -= delta.as_secs_f32();
+        if self.remaining <= 0.0 {
+            return Some(RestartStep::Restart);
+        }
+
+        let passed = self
+            .warnings
+            .iter()
+            .take_while(|warning| **warning as f32 >= self.remaining)
+            .count();
+        let warning = self.warnings.drain(..passed).last()?;
+        Some(RestartStep::Warn(warning))
+    }
+}
+
+/// How long until a restart, e.g. "5 minutes" or "10 seconds".
+pub fn describe_seconds(seconds: u32) -> String {
+    let (count, unit) = if seconds >= 60 && seconds % 60 == 0 {
+        (seconds / 60, "minute")
+    } else {
+        (seconds, "second")
+    };
+    if count == 1 {
+        format!("1 {}", unit)
+    } else {
+        format!("{} {}s", count, unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{describe_seconds, RestartCountdown, RestartStep, Schedule};
+    use crate::settings::{ScheduledTask, ServerSettings};
+
+    fn minutes(minutes: f32) -> Duration {
+        Duration::from_secs_f32(minutes * 60.0)
+    }
+
+    #[test]
+    fn test_schedule_runs_due_tasks() {
+        let settings = ServerSettings {
+            tasks: vec![
+                ScheduledTask {
+                    every_minutes: 5.0,
+                    command: "save".to_string(),
+                },
+                ScheduledTask {
+                    every_minutes: 2.0,
+                    command: "kill item".to_string(),
+                },
+                ScheduledTask {
+                    every_minutes: 0.0,
+                    command: "stop".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+        let mut schedule = Schedule::new(&settings);
+
+        assert!(schedule.tick(minutes(1.0)).is_empty());
+        assert_eq!(vec!["kill item"], schedule.tick(minutes(1.0)));
+        assert!(schedule.tick(minutes(1.0)).is_empty());
+        assert_eq!(vec!["kill item"], schedule.tick(minutes(1.0)));
+        assert_eq!(vec!["save"], schedule.tick(minutes(1.0)));
+        // missed intervals run once
+        assert_eq!(vec!["save", "kill item"], schedule.tick(minutes(20.0)));
+    }
+
+    #[test]
+    fn test_restart_countdown() {
+        let settings = ServerSettings {
+            restart_hours: Some(0.1),
+            restart_warnings: vec![10, 60, 300, 3600],
+            ..Default::default()
+        };
+        let mut countdown = RestartCountdown::new(&settings).unwrap();
+        assert_eq!(360.0, countdown.remaining());
+
+        assert_eq!(None, countdown.tick(Duration::from_secs(50)));
+        assert_eq!(
+            Some(RestartStep::Warn(300)),
+            countdown.tick(Duration::from_secs(10))
+        );
+        assert_eq!(None, countdown.tick(Duration::from_secs(1)));
+        // both the one minute and ten second warnings pass
+        assert_eq!(
+            Some(RestartStep::Warn(10)),
+            countdown.tick(Duration::from_secs(290))
+        );
+        assert_eq!(
+            Some(RestartStep::Restart),
+            countdown.tick(Duration::from_secs(10))
+        );
+        // put off when the world couldn't be saved
+        countdown.postpone(60.0);
+        assert_eq!(None, countdown.tick(Duration::from_secs(59)));
+        assert_eq!(
+            Some(RestartStep::Restart),
+            countdown.tick(Duration::from_secs(1))
+        );
+
+        let disabled = ServerSettings::default();
+        assert!(RestartCountdown::new(&disabled).is_none());
+    }
+
+    #[test]
+    fn test_describe_seconds() {
+        assert_eq!("5 minutes", describe_seconds(300));
+        assert_eq!("1 minute", describe_seconds(60));
+        assert_eq!("90 seconds", describe_seconds(90));
+        assert_eq!("1 second", describe_seconds(1));
+    }
+}
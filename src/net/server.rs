@@ -0,0 +1,638 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, ErrorKind},
+    net::{Ipv4Addr, TcpListener},
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    ecs::{
+        event::EventReader,
+        query::With,
+        system::{Commands, Local, Query, Res, ResMut, Resource},
+    },
+    log::{info, warn},
+    math::{I64Vec3, Vec3},
+    transform::components::Transform,
+};
+
+use super::{
+    client::Client,
+    connection::Connection,
+    protocol::{ClientMessage, ServerMessage, PROTOCOL_VERSION},
+    schedule::Schedule,
+    RemotePlayers,
+};
+use crate::{
+    block::{BlockType, PLACEABLE_BLOCKS},
+    chunks::{
+        chunk::ChunkCoordinate,
+        chunk_loader::{chunk_distance, ChunkLoader},
+        codec::encode_chunk,
+    },
+    ids::IdMap,
+    interaction::{BlockEdited, DEFAULT_REACH},
+    loading::spawn_height,
+    player::{Player, PLAYER_EYE_HEIGHT},
+    settings::{read_server_settings, Settings, SETTINGS_FILE},
+    world::World,
+};
+
+/// Player id used for the hosting player in a listen server.
+pub const HOST_PLAYER_ID: u32 = 0;
+/// How far a player must move before their position is sent again.
+const POSITION_THRESHOLD: f32 = 0.05;
+/// Distance in chunks around each player within which edited chunks are streamed.
+const STREAM_DISTANCE: u32 = 8;
+const MAX_STREAMED_CHUNKS_PER_FRAME: usize = 8;
+/// Distance in blocks a client may edit beyond its reach, for movement the server hasn't been
+/// told about yet.
+const EDIT_LEEWAY: f32 = 1.0;
+/// Connections still joining at once, more are closed as soon as they're accepted.
+const MAX_PENDING_CONNECTIONS: usize = 16;
+/// How long a connection has to say hello before it is dropped, so idle connections can't hold
+/// on to the pending slots.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Fastest a client may say its player moves, in blocks per second: faster than sprinting in
+/// noclip and all but the longest falls. Moves beyond it are cut short.
+const MAX_PLAYER_SPEED: f32 = 100.0;
+/// Longest time between moves that counts towards how far the next may go, so standing still
+/// can't save up for a jump across the world.
+const MAX_MOVE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Authoritative side of a multiplayer session. The world it owns is the one every connected
+/// client's block edits are validated against.
+#[derive(Resource)]
+pub struct Server {
+    listener: TcpListener,
+    clients: HashMap<u32, RemoteClient>,
+    next_player_id: u32,
+    /// Chunks whose blocks differ from what the seed generates, which clients must be sent.
+    /// Unedited chunks are generated by clients themselves from the world seed.
+    edited_chunks: HashSet<ChunkCoordinate>,
+    /// How far from their eyes clients may edit blocks.
+    reach: f32,
+    max_players: usize,
+}
+
+struct RemoteClient {
+    connection: Connection,
+    joined: bool,
+    /// When the connection was accepted, to drop it if it never joins.
+    connected: Instant,
+    /// Where the player is as far as the server is concerned, which their edits are checked
+    /// against.
+    position: Vec3,
+    /// When `position` was last updated.
+    moved: Instant,
+    /// Edited chunks this client holds an up to date copy of.
+    sent_chunks: HashSet<ChunkCoordinate>,
+}
+
+impl Server {
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            clients: HashMap::new(),
+            next_player_id: HOST_PLAYER_ID + 1,
+            edited_chunks: HashSet::new(),
+            reach: DEFAULT_REACH,
+            max_players: 16,
+        })
+    }
+
+    pub fn with_reach(mut self, reach: f32) -> Self {
+        self.reach = reach;
+        self
+    }
+
+    pub fn with_max_players(mut self, max_players: u32) -> Self {
+        self.max_players = max_players as usize;
+        self
+    }
+
+    fn broadcast(&mut self, message: &ServerMessage, except: Option<u32>) {
+        for (id, client) in self.clients.iter_mut() {
+            if client.joined && Some(*id) != except {
+                client.connection.send(message);
+            }
+        }
+    }
+
+    /// Joined players and their last known positions.
+    pub fn players(&self) -> impl Iterator<Item = (u32, Vec3)> + '_ {
+        self.clients
+            .iter()
+            .filter(|(_, client)| client.joined)
+            .map(|(id, client)| (*id, client.position))
+    }
+
+    pub fn is_edited(&self, chunk_coord: ChunkCoordinate) -> bool {
+        self.edited_chunks.contains(&chunk_coord)
+    }
+
+    pub fn edited_chunks(&self) -> usize {
+        self.edited_chunks.len()
+    }
+
+    /// Records a chunk read back from the save, which clients can't generate for themselves, so
+    /// it is streamed to them like an edit.
+    pub fn add_saved_chunk(&mut self, chunk_coord: ChunkCoordinate) {
+        self.edited_chunks.insert(chunk_coord);
+    }
+
+    /// Open connections, including clients that haven't finished joining.
+    pub fn connections(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Bytes waiting to be written to every client.
+    pub fn queued_bytes(&self) -> usize {
+        self.clients
+            .values()
+            .map(|client| client.connection.queued_bytes())
+            .sum()
+    }
+
+    /// Shows every joined player a message.
+    pub fn announce(&mut self, text: &str) {
+        self.broadcast(
+            &ServerMessage::Announcement {
+                text: text.to_string(),
+            },
+            None,
+        );
+    }
+
+    /// Disconnects a player, telling them why. Returns `false` if no such player is connected.
+    pub fn kick(&mut self, remote_players: &mut RemotePlayers, id: u32, reason: &str) -> bool {
+        let Some(client) = self.clients.get_mut(&id) else {
+            return false;
+        };
+
+        client.connection.send(&ServerMessage::Rejected {
+            reason: reason.to_string(),
+        });
+        let _ = client.connection.flush();
+        disconnect(self, remote_players, id);
+        true
+    }
+
+    /// Records an edit, invalidating every client's copy of the chunk so it is streamed again.
+    fn mark_edited(&mut self, world: &World, position: I64Vec3) {
+        let chunk_coord = world.block_to_chunk_coordinate(position);
+        self.edited_chunks.insert(chunk_coord);
+        for client in self.clients.values_mut() {
+            client.sent_chunks.remove(&chunk_coord);
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.clients.values().filter(|client| client.joined).count() >= self.max_players
+    }
+
+    /// Drops connections that haven't joined within `HANDSHAKE_TIMEOUT`, then accepts new ones
+    /// while fewer than `MAX_PENDING_CONNECTIONS` are still joining.
+    fn accept(&mut self, now: Instant) {
+        self.clients.retain(|id, client| {
+            let expired =
+                !client.joined && now.duration_since(client.connected) >= HANDSHAKE_TIMEOUT;
+            if expired {
+                info!("player {} took too long to join", id);
+            }
+            !expired
+        });
+
+        loop {
+            let pending = self
+                .clients
+                .values()
+                .filter(|client| !client.joined)
+                .count();
+            match self.listener.accept() {
+                // closed rather than left waiting to be accepted
+                Ok(_) if pending >= MAX_PENDING_CONNECTIONS => (),
+                Ok((stream, addr)) => match Connection::new(stream) {
+                    Ok(connection) => {
+                        let id = self.next_player_id;
+                        self.next_player_id += 1;
+                        info!("player {} connecting from {}", id, addr);
+                        self.clients.insert(
+                            id,
+                            RemoteClient {
+                                connection,
+                                joined: false,
+                                connected: now,
+                                position: Vec3::ZERO,
+                                moved: now,
+                                sent_chunks: HashSet::new(),
+                            },
+                        );
+                    }
+                    Err(e) => warn!("failed to accept connection from {}: {}", addr, e),
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    warn!("failed to accept connection: {}", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Turns away a client that hasn't joined, telling them why.
+    fn reject(&mut self, id: u32, reason: String) {
+        if let Some(mut client) = self.clients.remove(&id) {
+            client.connection.send(&ServerMessage::Rejected { reason });
+            let _ = client.connection.flush();
+        }
+    }
+}
+
+/// Starts listening when entering a world with hosting enabled, unless already connected to
+/// another server.
+pub fn start_server(
+    mut commands: Commands,
+    settings_query: Query<&Settings>,
+    server: Option<Res<Server>>,
+    client: Option<Res<Client>>,
+) {
+    let Ok(settings) = settings_query.get_single() else {
+        return;
+    };
+    if !settings.network.host || server.is_some() || client.is_some() {
+        return;
+    }
+
+    match Server::bind(settings.network.port) {
+        Ok(server) => {
+            let server = server
+                .with_reach(settings.player.reach)
+                .with_max_players(settings.network.max_players);
+            info!("hosting on port {}", settings.network.port);
+            commands.insert_resource(server);
+            match read_server_settings(SETTINGS_FILE) {
+                Ok(server_settings) => commands.insert_resource(Schedule::new(&server_settings)),
+                Err(e) => warn!("failed to read the server's schedule, running none: {}", e),
+            }
+        }
+        Err(e) => warn!("failed to host on port {}: {}", settings.network.port, e),
+    }
+}
+
+pub fn accept_clients(mut server: ResMut<Server>) {
+    server.accept(Instant::now());
+}
+
+pub fn receive_client_messages(
+    mut commands: Commands,
+    mut server: ResMut<Server>,
+    mut world: ResMut<World>,
+    mut remote_players: ResMut<RemotePlayers>,
+    chunk_loader: Option<Res<ChunkLoader>>,
+) {
+    let now = Instant::now();
+    let ids: Vec<u32> = server.clients.keys().copied().collect();
+    for id in ids {
+        let received = match server.clients.get_mut(&id) {
+            Some(client) => client.connection.receive::<ClientMessage>(),
+            None => continue,
+        };
+        let messages = match received {
+            Ok(messages) => messages,
+            Err(e) => {
+                info!("player {} disconnected: {}", id, e);
+                disconnect(&mut server, &mut remote_players, id);
+                continue;
+            }
+        };
+
+        for message in messages {
+            // nothing but a hello is listened to until the client has joined
+            let joined = server.clients.get(&id).is_some_and(|client| client.joined);
+            if !joined && !matches!(message, ClientMessage::Hello { .. }) {
+                continue;
+            }
+            match message {
+                ClientMessage::Hello { .. } if joined => (),
+                ClientMessage::Hello { version } => {
+                    if version != PROTOCOL_VERSION {
+                        let reason = format!(
+                            "server uses protocol {}, client uses {}",
+                            PROTOCOL_VERSION, version
+                        );
+                        server.reject(id, reason);
+                        break;
+                    }
+                    if server.is_full() {
+                        server.reject(id, "server is full".to_string());
+                        break;
+                    }
+                    welcome(&mut server, &world, &remote_players, id, now);
+                }
+                ClientMessage::PlayerMoved { position } => {
+                    let Some(client) = server.clients.get_mut(&id) else {
+                        continue;
+                    };
+                    let position = limit_move(
+                        client.position,
+                        Vec3::from_array(position),
+                        now.duration_since(client.moved),
+                    );
+                    client.position = position;
+                    client.moved = now;
+                    remote_players.0.insert(id, position);
+                    server.broadcast(
+                        &ServerMessage::PlayerMoved {
+                            player_id: id,
+                            position: position.to_array(),
+                        },
+                        Some(id),
+                    );
+                }
+                ClientMessage::SetBlock { position, block } => {
+                    let block_coord = I64Vec3::from_array(position);
+                    let allowed = server.clients.get(&id).is_some_and(|client| {
+                        can_edit(client.position, block_coord, block, server.reach)
+                    });
+                    if allowed && world.set_block(block_coord, block) {
+                        server.mark_edited(&world, block_coord);
+                        // a dedicated server has no meshes to update
+                        if let Some(chunk_loader) = &chunk_loader {
+                            chunk_loader.remesh_block(&mut commands, &world, block_coord);
+                        }
+                        server.broadcast(&ServerMessage::BlockChanged { position, block }, None);
+                    } else if let Some(client) = server.clients.get_mut(&id) {
+                        // the edit was refused, so correct the client's prediction
+                        client.connection.send(&ServerMessage::BlockChanged {
+                            position,
+                            block: world.get_block(block_coord),
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Where a player moving from `from` towards `to` can have got to after `elapsed`, going no
+/// faster than `MAX_PLAYER_SPEED`. Positions that aren't finite are ignored.
+fn limit_move(from: Vec3, to: Vec3, elapsed: Duration) -> Vec3 {
+    if !to.is_finite() {
+        return from;
+    }
+    let max_distance = MAX_PLAYER_SPEED * elapsed.min(MAX_MOVE_INTERVAL).as_secs_f32();
+    from + (to - from).clamp_length_max(max_distance)
+}
+
+/// Whether a player standing at `position` may set the block at `block_coord` to `block`. They
+/// can only reach blocks near enough to their eyes, and only break blocks or place those they
+/// could select. Lava and glowstone are left to the host, as lava floods whatever is below it and
+/// glowstone relights everything around it.
+fn can_edit(position: Vec3, block_coord: I64Vec3, block: BlockType, reach: f32) -> bool {
+    let placeable = block == BlockType::Air
+        || (PLACEABLE_BLOCKS.contains(&block)
+            && !matches!(block, BlockType::Lava | BlockType::Glowstone));
+    let eye = position + Vec3::Y * PLAYER_EYE_HEIGHT;
+    // blocks are centred on their coordinate
+    let centre = block_coord.as_vec3();
+    let nearest = eye.clamp(centre - 0.5, centre + 0.5);
+    placeable && eye.distance(nearest) <= reach + EDIT_LEEWAY
+}
+
+fn welcome(
+    server: &mut Server,
+    world: &World,
+    remote_players: &RemotePlayers,
+    id: u32,
+    now: Instant,
+) {
+    let Some(client) = server.clients.get_mut(&id) else {
+        return;
+    };
+
+    client.joined = true;
+    // where the client places its player, which its moves are measured from
+    client.position = Vec3::new(0.0, spawn_height(world), 0.0);
+    client.moved = now;
+    client.connection.send(&ServerMessage::Welcome {
+        player_id: id,
+        seed: world.seed(),
+        chunk_size: world.chunk_size(),
+        generator: world.generator_kind().clone(),
+    });
+    for (other, position) in remote_players.0.iter() {
+        client.connection.send(&ServerMessage::PlayerMoved {
+            player_id: *other,
+            position: position.to_array(),
+        });
+    }
+    info!("player {} joined", id);
+}
+
+fn disconnect(server: &mut Server, remote_players: &mut RemotePlayers, id: u32) {
+    server.clients.remove(&id);
+    remote_players.0.remove(&id);
+    server.broadcast(&ServerMessage::PlayerLeft { player_id: id }, None);
+}
+
+/// Relays block edits made on the server itself, by the host or console commands, to every
+/// client.
+pub fn broadcast_host_edits(
+    mut server: ResMut<Server>,
+    world: Res<World>,
+    mut edited: EventReader<BlockEdited>,
+) {
+    for edit in edited.read() {
+        server.mark_edited(&world, edit.position);
+        server.broadcast(
+            &ServerMessage::BlockChanged {
+                position: edit.position.to_array(),
+                block: edit.block,
+            },
+            None,
+        );
+    }
+}
+
+pub fn broadcast_host_position(
+    mut server: ResMut<Server>,
+    mut last_position: Local<Option<Vec3>>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    let Ok(transform) = player_query.get_single() else {
+        return;
+    };
+
+    let position = transform.translation;
+    let moved = last_position.map_or(true, |last| last.distance(position) > POSITION_THRESHOLD);
+    if moved {
+        *last_position = Some(position);
+        server.broadcast(
+            &ServerMessage::PlayerMoved {
+                player_id: HOST_PLAYER_ID,
+                position: position.to_array(),
+            },
+            None,
+        );
+    }
+}
+
+/// Edited chunks a player near `centre` still needs, nearest first.
+fn chunks_to_stream(
+    edited_chunks: &HashSet<ChunkCoordinate>,
+    sent_chunks: &HashSet<ChunkCoordinate>,
+    centre: ChunkCoordinate,
+    max: usize,
+) -> Vec<ChunkCoordinate> {
+    let mut chunks: Vec<ChunkCoordinate> = edited_chunks
+        .iter()
+        .filter(|chunk| !sent_chunks.contains(chunk))
+        .filter(|chunk| chunk_distance(**chunk, centre) <= STREAM_DISTANCE)
+        .copied()
+        .collect();
+    chunks.sort_by_key(|chunk| {
+        let offset = chunk.0 - centre.0;
+        (chunk_distance(*chunk, centre), offset.length_squared())
+    });
+    chunks.truncate(max);
+    chunks
+}
+
+pub fn stream_chunks(mut server: ResMut<Server>, mut world: ResMut<World>) {
+    let server = server.as_mut();
+    for client in server.clients.values_mut().filter(|client| client.joined) {
+        let centre = world.chunk_at(client.position);
+        for chunk_coord in chunks_to_stream(
+            &server.edited_chunks,
+            &client.sent_chunks,
+            centre,
+            MAX_STREAMED_CHUNKS_PER_FRAME,
+        ) {
+            // chunks the server has unloaded are sent again once they are regenerated
+            let Some(chunk_data) = world.get_chunk_data(chunk_coord) else {
+                continue;
+            };
+            client.connection.send(&ServerMessage::ChunkData {
+                coord: chunk_coord.0.to_array(),
+                data: encode_chunk(&chunk_data, &IdMap::default()),
+            });
+            client.sent_chunks.insert(chunk_coord);
+        }
+    }
+}
+
+pub fn flush_clients(mut server: ResMut<Server>, mut remote_players: ResMut<RemotePlayers>) {
+    let failed: Vec<u32> = server
+        .clients
+        .iter_mut()
+        .filter_map(|(id, client)| client.connection.flush().err().map(|_| *id))
+        .collect();
+
+    for id in failed {
+        info!("player {} disconnected", id);
+        disconnect(&mut server, &mut remote_players, id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        net::{Ipv4Addr, TcpStream},
+        time::{Duration, Instant},
+    };
+
+    use bevy::math::{I64Vec3, Vec3};
+
+    use super::{
+        can_edit, chunks_to_stream, limit_move, Server, HANDSHAKE_TIMEOUT, MAX_PENDING_CONNECTIONS,
+        MAX_PLAYER_SPEED,
+    };
+    use crate::{block::BlockType, chunks::chunk::ChunkCoordinate};
+
+    fn coord(x: i64, y: i64, z: i64) -> ChunkCoordinate {
+        ChunkCoordinate(I64Vec3::new(x, y, z))
+    }
+
+    #[test]
+    fn test_can_edit_within_reach() {
+        let position = Vec3::new(0.6, 10.0, 0.0);
+        let (air, stone) = (BlockType::Air, BlockType::Stone);
+        assert!(can_edit(position, I64Vec3::new(0, 9, 0), air, 5.0));
+        // the block spans 6.5 to 7.5, within reach and leeway of the eye at 0.6
+        assert!(can_edit(position, I64Vec3::new(7, 12, 0), stone, 5.0));
+        assert!(!can_edit(position, I64Vec3::new(-6, 12, 0), stone, 5.0));
+        assert!(!can_edit(position, I64Vec3::new(0, -20, 0), air, 5.0));
+    }
+
+    #[test]
+    fn test_limit_move() {
+        let from = Vec3::new(0.0, 10.0, 0.0);
+        let step = Duration::from_millis(50);
+        let near = Vec3::new(3.0, 10.0, 0.0);
+        assert_eq!(near, limit_move(from, near, step));
+
+        let far = Vec3::new(1000.0, 10.0, 0.0);
+        let limited = limit_move(from, far, step);
+        assert!((limited.distance(from) - MAX_PLAYER_SPEED * 0.05).abs() < 1e-3);
+        assert!(limited.x > 0.0 && limited.y == 10.0);
+        // waiting doesn't let a player go any further in one move
+        assert!(limit_move(from, far, Duration::from_secs(60)).x < 100.0);
+        assert_eq!(from, limit_move(from, Vec3::NAN, step));
+    }
+
+    #[test]
+    fn test_pending_connections_are_capped_and_time_out() {
+        let mut server = Server::bind(0).unwrap().with_max_players(1);
+        let port = server.listener.local_addr().unwrap().port();
+        let _clients: Vec<TcpStream> = (0..MAX_PENDING_CONNECTIONS + 2)
+            .map(|_| TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap())
+            .collect();
+
+        let now = Instant::now();
+        server.accept(now);
+        assert_eq!(MAX_PENDING_CONNECTIONS, server.connections());
+        assert!(!server.is_full());
+
+        // a joined player stays, and fills the server
+        server.clients.values_mut().next().unwrap().joined = true;
+        assert!(server.is_full());
+        server.accept(now + HANDSHAKE_TIMEOUT);
+        assert_eq!(1, server.connections());
+    }
+
+    #[test]
+    fn test_can_edit_only_placeable_blocks() {
+        let position = Vec3::new(0.5, 10.0, 0.5);
+        let block = I64Vec3::new(1, 10, 0);
+        assert!(can_edit(position, block, BlockType::Sand, 5.0));
+        assert!(!can_edit(position, block, BlockType::Lava, 5.0));
+        assert!(!can_edit(position, block, BlockType::Glowstone, 5.0));
+        assert!(!can_edit(position, block, BlockType::Spawner, 5.0));
+    }
+
+    #[test]
+    fn test_chunks_to_stream_nearest_first() {
+        let edited = HashSet::from([
+            coord(5, 0, 0),
+            coord(1, 0, 0),
+            coord(1, 1, 1),
+            coord(3, 0, 0),
+        ]);
+        let chunks = chunks_to_stream(&edited, &HashSet::new(), coord(0, 0, 0), 3);
+
+        assert_eq!(vec![coord(1, 0, 0), coord(1, 1, 1), coord(3, 0, 0)], chunks);
+    }
+
+    #[test]
+    fn test_chunks_to_stream_skips_sent_and_distant_chunks() {
+        let edited = HashSet::from([coord(1, 0, 0), coord(2, 0, 0), coord(100, 0, 0)]);
+        let sent = HashSet::from([coord(1, 0, 0)]);
+        let chunks = chunks_to_stream(&edited, &sent, coord(0, 0, 0), 8);
+
+        assert_eq!(vec![coord(2, 0, 0)], chunks);
+    }
+}
@@ -0,0 +1,318 @@
+//! Short lived particles: fragments of a block's texture that burst out when it's broken, and a
+//! small puff when one is placed. Each particle is a tiny quad facing the camera that falls and
+//! settles on the ground before fading away.
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::{Assets, Handle},
+    core_pipeline::core_3d::Camera3d,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::EventReader,
+        query::{With, Without},
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res, ResMut, Resource},
+    },
+    image::Image,
+    math::{I64Vec3, Vec3},
+    pbr::{MeshMaterial3d, NotShadowCaster, StandardMaterial},
+    prelude::{AlphaMode, Mesh3d},
+    render::{
+        mesh::{Indices, Mesh, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+    state::condition::in_state,
+    time::Time,
+    transform::components::{GlobalTransform, Transform},
+    utils::HashMap,
+};
+use rand::Rng;
+
+use crate::{
+    block::BlockType,
+    chunks::{material::BlockTextures, position::BlockPos},
+    interaction::{BlockEdited, EditSource},
+    item::BlockBroken,
+    state::GameState,
+    world::World,
+};
+
+const BREAK_PARTICLES: usize = 24;
+const PLACE_PARTICLES: usize = 6;
+/// Most particles alive at once, beyond which no more are spawned.
+const MAX_PARTICLES: usize = 600;
+const PARTICLE_SIZE: f32 = 0.12;
+const PARTICLE_GRAVITY: f32 = 16.0;
+/// Seconds a particle lives, picked at random within this range.
+const PARTICLE_LIFETIME: std::ops::Range<f32> = 0.5..1.2;
+/// Fragments of a block's texture are this many to a side of it.
+const PIECES: u8 = 4;
+
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ParticleAssets>().add_systems(
+            Update,
+            (spawn_block_particles, update_particles)
+                .chain()
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+/// The material particles are drawn with and a mesh for each fragment of each block texture,
+/// made as they're first needed.
+#[derive(Resource, Default)]
+struct ParticleAssets {
+    material: Option<Handle<StandardMaterial>>,
+    meshes: HashMap<(u8, u8), Handle<Mesh>>,
+}
+
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Particle {
+    pub velocity: Vec3,
+    age: f32,
+    lifetime: f32,
+}
+
+impl Particle {
+    pub fn new(velocity: Vec3, lifetime: f32) -> Self {
+        Self {
+            velocity,
+            age: 0.0,
+            lifetime,
+        }
+    }
+
+    /// Moves a particle at `position` on by `delta_secs`, returning where it ends up. Particles
+    /// stop against solid blocks rather than passing into them.
+    pub fn step(&mut self, world: &mut World, position: Vec3, delta_secs: f32) -> Vec3 {
+        self.age += delta_secs;
+        self.velocity.y -= PARTICLE_GRAVITY * delta_secs;
+
+        let mut position = position;
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            let moved = position + axis * self.velocity.dot(axis) * delta_secs;
+            if world.get_block(BlockPos::from_world(moved).0).is_solid() {
+                // landing also stops a particle sliding along the ground
+                if axis == Vec3::Y {
+                    self.velocity = Vec3::ZERO;
+                } else {
+                    self.velocity -= axis * self.velocity.dot(axis);
+                }
+            } else {
+                position = moved;
+            }
+        }
+        position
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.age >= self.lifetime
+    }
+
+    /// Scale of the particle, shrinking away over the end of its life.
+    fn scale(&self) -> f32 {
+        ((self.lifetime - self.age) / (self.lifetime * 0.3)).clamp(0.0, 1.0)
+    }
+}
+
+/// A quad showing fragment `piece` of texture `texture` in an atlas of `layers` textures.
+fn fragment_mesh(texture: u8, piece: u8, layers: u32) -> Mesh {
+    let (column, row) = ((piece % PIECES) as f32, (piece / PIECES) as f32);
+    let width = 1.0 / layers as f32;
+    let u = |x: f32| (texture as f32 + x / PIECES as f32) * width;
+    let v = |y: f32| y / PIECES as f32;
+    let half = PARTICLE_SIZE / 2.0;
+
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    mesh.insert_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3]));
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        vec![
+            [-half, -half, 0.0],
+            [half, -half, 0.0],
+            [half, half, 0.0],
+            [-half, half, 0.0],
+        ],
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; 4]);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_UV_0,
+        vec![
+            [u(column), v(row + 1.0)],
+            [u(column + 1.0), v(row + 1.0)],
+            [u(column + 1.0), v(row)],
+            [u(column), v(row)],
+        ],
+    );
+    mesh
+}
+
+/// Starting positions and velocities of the particles for a block at `block`: a burst through
+/// the whole block when it's broken, or a few drifting out from its base when it's placed.
+fn burst(block: I64Vec3, broken: bool, rng: &mut impl Rng) -> Vec<(Vec3, Vec3)> {
+    let centre = block.as_vec3();
+    let count = if broken {
+        BREAK_PARTICLES
+    } else {
+        PLACE_PARTICLES
+    };
+    (0..count)
+        .map(|_| {
+            let offset = Vec3::new(
+                rng.gen_range(-0.4..0.4),
+                rng.gen_range(-0.4..0.4),
+                rng.gen_range(-0.4..0.4),
+            );
+            if broken {
+                (
+                    centre + offset,
+                    offset * 4.0 + Vec3::Y * rng.gen_range(1.0..3.0),
+                )
+            } else {
+                let outward = Vec3::new(offset.x, 0.0, offset.z).normalize_or_zero();
+                (
+                    centre + outward * 0.55 - Vec3::Y * 0.45,
+                    outward * rng.gen_range(0.5..1.5) + Vec3::Y * 1.5,
+                )
+            }
+        })
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_block_particles(
+    mut commands: Commands,
+    mut assets: ResMut<ParticleAssets>,
+    textures: Res<BlockTextures>,
+    images: Res<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut broken: EventReader<BlockBroken>,
+    mut edited: EventReader<BlockEdited>,
+    particle_query: Query<(), With<Particle>>,
+) {
+    let placed = edited
+        .read()
+        .filter(|edit| edit.source == EditSource::Player && edit.block != BlockType::Air)
+        .map(|edit| (edit.position, edit.block, false));
+    let bursts: Vec<(I64Vec3, BlockType, bool)> = broken
+        .read()
+        .map(|broken| (broken.position, broken.block, true))
+        .chain(placed)
+        .filter(|(_, block, _)| *block != BlockType::Air)
+        .collect();
+    // the atlas gives the number of textures, so nothing can be drawn until it has loaded
+    let Some(atlas) = images.get(textures.atlas()) else {
+        return;
+    };
+    let layers = atlas.size().x / atlas.size().y.max(1);
+
+    let material = assets
+        .material
+        .get_or_insert_with(|| {
+            materials.add(StandardMaterial {
+                base_color_texture: Some(textures.atlas().clone()),
+                alpha_mode: AlphaMode::Mask(0.5),
+                perceptual_roughness: 1.0,
+                cull_mode: None,
+                ..Default::default()
+            })
+        })
+        .clone();
+    let mut alive = particle_query.iter().count();
+    let mut rng = rand::thread_rng();
+    for (position, block, broken) in bursts {
+        for (start, velocity) in burst(position, broken, &mut rng) {
+            if alive >= MAX_PARTICLES {
+                return;
+            }
+            alive += 1;
+            let piece = rng.gen_range(0..PIECES * PIECES);
+            let mesh = assets
+                .meshes
+                .entry((block.texture(), piece))
+                .or_insert_with(|| meshes.add(fragment_mesh(block.texture(), piece, layers)))
+                .clone();
+            commands.spawn((
+                Mesh3d(mesh),
+                MeshMaterial3d(material.clone()),
+                Transform::from_translation(start),
+                NotShadowCaster,
+                Particle::new(velocity, rng.gen_range(PARTICLE_LIFETIME)),
+            ));
+        }
+    }
+}
+
+/// Moves particles, turns them to face the camera and despawns them once they've expired.
+fn update_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut world: ResMut<World>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    mut particle_query: Query<(Entity, &mut Particle, &mut Transform), Without<Camera3d>>,
+) {
+    let facing = camera_query
+        .get_single()
+        .map(|camera| camera.compute_transform().rotation)
+        .unwrap_or_default();
+    for (entity, mut particle, mut transform) in particle_query.iter_mut() {
+        transform.translation = particle.step(&mut world, transform.translation, time.delta_secs());
+        if particle.is_expired() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        transform.rotation = facing;
+        transform.scale = Vec3::splat(particle.scale());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec3, Vec3};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{burst, Particle, BREAK_PARTICLES, PLACE_PARTICLES};
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        world::World,
+    };
+
+    #[test]
+    fn test_particles_land_on_blocks() {
+        let mut world = World::new(0);
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), ChunkData::default());
+        world.set_block(I64Vec3::new(4, 2, 4), BlockType::Stone);
+
+        let mut particle = Particle::new(Vec3::new(0.5, 0.0, 0.0), 10.0);
+        let mut position = Vec3::new(4.0, 5.0, 4.0);
+        for _ in 0..120 {
+            position = particle.step(&mut world, position, 1.0 / 60.0);
+        }
+        // resting on the top face, which is at 2.5
+        assert!(position.y >= 2.5 && position.y < 2.8);
+        assert_eq!(Vec3::ZERO, particle.velocity);
+        assert!(!particle.is_expired());
+    }
+
+    #[test]
+    fn test_breaking_bursts_more_than_placing() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let block = I64Vec3::new(-3, 10, 7);
+        let broken = burst(block, true, &mut rng);
+        let placed = burst(block, false, &mut rng);
+        assert_eq!(BREAK_PARTICLES, broken.len());
+        assert_eq!(PLACE_PARTICLES, placed.len());
+        for (start, _) in broken {
+            assert!((start - block.as_vec3()).abs().max_element() < 0.5);
+        }
+    }
+}
@@ -0,0 +1,427 @@
+use bevy::math::{BVec3, I64Vec3, Vec3};
+
+use crate::{block::BlockType, world::World};
+
+/// Gap left between a collider and the block face it was stopped against.
+const SKIN: f32 = 0.001;
+/// Largest distance moved along an axis in a single collision step, so fast bodies cannot tunnel.
+const MAX_STEP: f32 = 0.5;
+/// Depth below a collider's base searched for ground to stand on.
+const GROUND_PROBE: f32 = 0.1;
+
+/// Axis-aligned box relative to an entity's translation.
+#[derive(Debug, Copy, Clone)]
+pub struct Collider {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Collider {
+    pub const fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// This collider moved by `offset`.
+    pub fn offset(&self, offset: Vec3) -> Self {
+        Self::new(self.min + offset, self.max + offset)
+    }
+
+    /// Smallest collider containing both this and `other`.
+    pub fn union(&self, other: Collider) -> Self {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    /// Distance along a ray to where it enters this collider placed at `position`, if it does
+    /// within `max_distance`. `direction` must be normalised.
+    pub fn raycast(
+        &self,
+        position: Vec3,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+    ) -> Option<f32> {
+        let inverse = direction.recip();
+        let a = (position + self.min - origin) * inverse;
+        let b = (position + self.max - origin) * inverse;
+        let enter = a.min(b).max_element().max(0.0);
+        let exit = a.max(b).min_element();
+        (enter <= exit && enter <= max_distance).then_some(enter)
+    }
+}
+
+/// Boxes of solid blocks overlapping `min..max`, as world space `(min, max)` pairs. Blocks are
+/// centred on their integer coordinate, so a full block `b` spans `b - 0.5..b + 0.5`.
+fn solid_boxes(world: &mut World, min: Vec3, max: Vec3) -> Vec<(Vec3, Vec3)> {
+    let lo = (min + 0.5).floor();
+    let hi = (max + 0.5).ceil() - 1.0;
+
+    let mut boxes = vec![];
+    for x in lo.x as i64..=hi.x as i64 {
+        for y in lo.y as i64..=hi.y as i64 {
+            for z in lo.z as i64..=hi.z as i64 {
+                let block = world.get_block(I64Vec3::new(x, y, z));
+                if !block.is_solid() {
+                    continue;
+                }
+                let centre = Vec3::new(x as f32, y as f32, z as f32);
+                boxes.extend(
+                    block
+                        .shape()
+                        .boxes()
+                        .iter()
+                        .map(|(box_min, box_max)| (centre + *box_min, centre + *box_max))
+                        .filter(|(box_min, box_max)| {
+                            box_min.cmplt(max).all() && box_max.cmpgt(min).all()
+                        }),
+                );
+            }
+        }
+    }
+    boxes
+}
+
+pub fn intersects_solid(world: &mut World, min: Vec3, max: Vec3) -> bool {
+    !solid_boxes(world, min, max).is_empty()
+}
+
+/// Moves `collider` at `position` by `delta` one axis at a time, stopping flush against solid blocks.
+/// Returns the new position and which axes were blocked.
+pub fn move_and_collide(
+    world: &mut World,
+    position: Vec3,
+    collider: Collider,
+    delta: Vec3,
+) -> (Vec3, BVec3) {
+    let mut position = position;
+    let mut blocked = [false; 3];
+
+    let steps = (delta.abs().max_element() / MAX_STEP).ceil().max(1.0) as u32;
+    let step = delta / steps as f32;
+
+    for _ in 0..steps {
+        for axis in 0..3 {
+            if blocked[axis] || step[axis] == 0.0 {
+                continue;
+            }
+
+            let mut candidate = position;
+            candidate[axis] += step[axis];
+
+            let boxes = solid_boxes(world, candidate + collider.min, candidate + collider.max);
+            // never trap a collider that is already overlapping terrain
+            if boxes.is_empty()
+                || intersects_solid(world, position + collider.min, position + collider.max)
+            {
+                position = candidate;
+                continue;
+            }
+
+            if step[axis] > 0.0 {
+                let face = boxes
+                    .iter()
+                    .map(|(min, _)| min[axis])
+                    .fold(f32::MAX, f32::min);
+                position[axis] = (face - collider.max[axis] - SKIN).max(position[axis]);
+            } else {
+                let face = boxes
+                    .iter()
+                    .map(|(_, max)| max[axis])
+                    .fold(f32::MIN, f32::max);
+                position[axis] = (face - collider.min[axis] + SKIN).min(position[axis]);
+            }
+            blocked[axis] = true;
+        }
+    }
+
+    (position, BVec3::new(blocked[0], blocked[1], blocked[2]))
+}
+
+/// Whether any `block` overlaps `collider` at `position`, such as water to swim in.
+pub fn touches_block(
+    world: &mut World,
+    position: Vec3,
+    collider: Collider,
+    block: BlockType,
+) -> bool {
+    let lo = (position + collider.min + 0.5).floor().as_i64vec3();
+    let hi = (position + collider.max + 0.5).ceil().as_i64vec3() - 1;
+    (lo.x..=hi.x).any(|x| {
+        (lo.y..=hi.y)
+            .any(|y| (lo.z..=hi.z).any(|z| world.get_block(I64Vec3::new(x, y, z)) == block))
+    })
+}
+
+/// Whether `collider` at `position` has solid ground just beneath any part of its base.
+pub fn has_ground(world: &mut World, position: Vec3, collider: Collider) -> bool {
+    let min = position + collider.min - Vec3::Y * GROUND_PROBE;
+    let max = position + collider.max.with_y(collider.min.y);
+    intersects_solid(world, min, max)
+}
+
+/// `delta` without the horizontal movement that would take a grounded collider off the edge of
+/// what it stands on, as when sneaking. Each axis is kept if moving along it alone stays on
+/// ground, then the diagonal is checked.
+pub fn stop_at_ledges(world: &mut World, position: Vec3, collider: Collider, delta: Vec3) -> Vec3 {
+    let mut delta = delta;
+    if !has_ground(world, position + delta * Vec3::X, collider) {
+        delta.x = 0.0;
+    }
+    if !has_ground(world, position + delta * Vec3::Z, collider) {
+        delta.z = 0.0;
+    }
+    if !has_ground(world, position + delta.with_y(0.0), collider) {
+        delta.z = 0.0;
+    }
+    delta
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RaycastHit {
+    pub block: I64Vec3,
+    /// Face of `block` that was hit, pointing back towards the ray origin.
+    pub normal: I64Vec3,
+    pub distance: f32,
+}
+
+/// Walks the block grid along a ray and returns the first selectable block within
+/// `max_distance`.
+pub fn raycast(
+    world: &mut World,
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+) -> Option<RaycastHit> {
+    let direction = direction.normalize_or_zero();
+    if direction == Vec3::ZERO {
+        return None;
+    }
+
+    // shift so that block `b` spans `b..b + 1` and cells can be found with floor
+    let start = origin + 0.5;
+    let mut block = start.floor().as_i64vec3();
+
+    let mut step = I64Vec3::ZERO;
+    let mut t_max = Vec3::splat(f32::INFINITY);
+    let t_delta = (1.0 / direction).abs();
+    for axis in 0..3 {
+        if direction[axis] > 0.0 {
+            step[axis] = 1;
+            t_max[axis] = (block[axis] as f32 + 1.0 - start[axis]) / direction[axis];
+        } else if direction[axis] < 0.0 {
+            step[axis] = -1;
+            t_max[axis] = (start[axis] - block[axis] as f32) / -direction[axis];
+        }
+    }
+
+    let mut normal = I64Vec3::ZERO;
+    let mut distance = 0.0;
+    while distance <= max_distance {
+        if world.get_block(block).is_selectable() {
+            return Some(RaycastHit {
+                block,
+                normal,
+                distance,
+            });
+        }
+
+        let axis = if t_max.x < t_max.y && t_max.x < t_max.z {
+            0
+        } else if t_max.y < t_max.z {
+            1
+        } else {
+            2
+        };
+
+        distance = t_max[axis];
+        block[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+        normal = I64Vec3::ZERO;
+        normal[axis] = -step[axis];
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec3, U16Vec3, Vec3};
+
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        world::World,
+    };
+
+    use super::{
+        has_ground, intersects_solid, move_and_collide, raycast, stop_at_ledges, touches_block,
+        Collider,
+    };
+
+    fn floor_world() -> World {
+        let mut world = World::new(0);
+        let mut chunk_data = ChunkData::default();
+        for x in 0..chunk_data.size {
+            for z in 0..chunk_data.size {
+                chunk_data.set_block_at(U16Vec3::new(x, 0, z), BlockType::Stone);
+            }
+        }
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), chunk_data);
+        world
+    }
+
+    const COLLIDER: Collider = Collider::new(Vec3::new(-0.3, 0.0, -0.3), Vec3::new(0.3, 1.8, 0.3));
+
+    #[test]
+    fn test_intersects_solid() {
+        let mut world = floor_world();
+        assert!(intersects_solid(
+            &mut world,
+            Vec3::new(4.0, 0.4, 4.0),
+            Vec3::new(5.0, 1.0, 5.0)
+        ));
+        assert!(!intersects_solid(
+            &mut world,
+            Vec3::new(4.0, 0.5, 4.0),
+            Vec3::new(5.0, 1.0, 5.0)
+        ));
+    }
+
+    #[test]
+    fn test_move_and_collide_lands_on_floor() {
+        let mut world = floor_world();
+        let (position, blocked) = move_and_collide(
+            &mut world,
+            Vec3::new(8.0, 3.0, 8.0),
+            COLLIDER,
+            Vec3::new(0.0, -5.0, 0.0),
+        );
+
+        assert!(blocked.y);
+        assert!((position.y - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_move_and_collide_with_block_shapes() {
+        let mut world = floor_world();
+        world.set_block(I64Vec3::new(8, 1, 8), BlockType::StoneSlab);
+        world.set_block(I64Vec3::new(4, 1, 4), BlockType::Flower);
+
+        let (position, blocked) = move_and_collide(
+            &mut world,
+            Vec3::new(8.0, 3.0, 8.0),
+            COLLIDER,
+            Vec3::new(0.0, -5.0, 0.0),
+        );
+        assert!(blocked.y);
+        assert!((position.y - 1.0).abs() < 0.01);
+
+        // plants are walked through but can still be aimed at
+        let (position, _) = move_and_collide(
+            &mut world,
+            Vec3::new(4.0, 3.0, 4.0),
+            COLLIDER,
+            Vec3::new(0.0, -5.0, 0.0),
+        );
+        assert!((position.y - 0.5).abs() < 0.01);
+        let hit = raycast(&mut world, Vec3::new(4.0, 3.0, 4.0), Vec3::NEG_Y, 5.0).unwrap();
+        assert_eq!(I64Vec3::new(4, 1, 4), hit.block);
+    }
+
+    #[test]
+    fn test_stop_at_ledges() {
+        let mut world = floor_world();
+        // standing on the last column of the floor, which ends at x = 15.5
+        let position = Vec3::new(15.0, 0.501, 8.0);
+        assert!(has_ground(&mut world, position, COLLIDER));
+        assert!(!has_ground(&mut world, position + Vec3::Y, COLLIDER));
+
+        let step = Vec3::new(0.4, -0.1, 0.0);
+        assert_eq!(step, stop_at_ledges(&mut world, position, COLLIDER, step));
+        assert_eq!(
+            Vec3::new(0.0, 0.0, 0.5),
+            stop_at_ledges(&mut world, position, COLLIDER, Vec3::new(1.0, 0.0, 0.5))
+        );
+        assert_eq!(
+            Vec3::new(0.0, 0.0, -1.0),
+            stop_at_ledges(&mut world, position, COLLIDER, Vec3::new(0.0, 0.0, -1.0))
+        );
+    }
+
+    #[test]
+    fn test_touches_block() {
+        let mut world = floor_world();
+        world.set_block(I64Vec3::new(8, 1, 8), BlockType::Water);
+        let standing = Vec3::new(8.0, 0.501, 8.0);
+        let water = |world: &mut World, position| {
+            touches_block(world, position, COLLIDER, BlockType::Water)
+        };
+        assert!(water(&mut world, standing));
+        assert!(water(&mut world, standing + Vec3::X * 0.7));
+        assert!(!water(&mut world, standing + Vec3::X));
+        assert!(!water(&mut world, standing + Vec3::Y));
+    }
+
+    #[test]
+    fn test_move_and_collide_free_movement() {
+        let mut world = floor_world();
+        let (position, blocked) = move_and_collide(
+            &mut world,
+            Vec3::new(8.0, 2.0, 8.0),
+            COLLIDER,
+            Vec3::new(2.0, 0.0, 1.0),
+        );
+
+        assert!(!blocked.any());
+        assert_eq!(Vec3::new(10.0, 2.0, 9.0), position);
+    }
+
+    #[test]
+    fn test_raycast_hits_floor() {
+        let mut world = floor_world();
+        let hit = raycast(
+            &mut world,
+            Vec3::new(8.2, 4.0, 8.7),
+            Vec3::new(0.0, -1.0, 0.0),
+            10.0,
+        )
+        .unwrap();
+
+        assert_eq!(I64Vec3::new(8, 0, 9), hit.block);
+        assert_eq!(I64Vec3::new(0, 1, 0), hit.normal);
+        assert!((hit.distance - 3.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_collider_raycast() {
+        let collider = Collider::new(Vec3::new(-0.5, 0.0, -0.5), Vec3::new(0.5, 2.0, 0.5));
+        let position = Vec3::new(0.0, 0.0, 5.0);
+        assert_eq!(
+            Some(4.5),
+            collider.raycast(position, Vec3::new(0.0, 1.0, 0.0), Vec3::Z, 10.0)
+        );
+        assert_eq!(
+            None,
+            collider.raycast(position, Vec3::new(0.0, 1.0, 0.0), Vec3::Z, 4.0)
+        );
+        assert_eq!(
+            None,
+            collider.raycast(position, Vec3::new(0.0, 3.0, 0.0), Vec3::Z, 10.0)
+        );
+        assert_eq!(
+            None,
+            collider.raycast(position, Vec3::new(0.0, 1.0, 0.0), Vec3::NEG_Z, 10.0)
+        );
+    }
+
+    #[test]
+    fn test_raycast_respects_max_distance() {
+        let mut world = floor_world();
+        assert!(raycast(
+            &mut world,
+            Vec3::new(8.0, 10.0, 8.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            5.0
+        )
+        .is_none());
+    }
+}
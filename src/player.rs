@@ -3,17 +3,27 @@ use bevy::{
         bundle::Bundle,
         component::Component,
         event::EventReader,
-        query::{With, Without},
-        system::{Query, Res},
+        query::{Has, With, Without},
+        system::{In, Query, Res, ResMut},
     },
     hierarchy::Parent,
-    input::{keyboard::KeyCode, mouse::MouseMotion, ButtonInput},
+    input::mouse::MouseMotion,
     math::{Dir3, Vec3},
-    prelude::Transform,
-    render::camera::Camera,
+    prelude::{Camera3d, Transform},
+    render::camera::{Camera, PerspectiveProjection, Projection},
     time::Time,
 };
 
+use crate::{
+    block::BlockType,
+    command::{CommandArgs, CommandResult},
+    input::bindings::{Action, ActionInput},
+    mob::ride::Riding,
+    physics::{move_and_collide, stop_at_ledges, touches_block, Collider},
+    settings::{PlayerSettings, Settings},
+    world::World,
+};
+
 #[derive(Bundle, Default)]
 pub struct PlayerBundle {
     pub marker: Player,
@@ -25,54 +35,392 @@ pub struct PlayerBundle {
 #[derive(Component, Default)]
 pub struct Player {}
 
+/// A player who can only look around, flying through blocks without touching anything, as after
+/// dying in a hardcore world.
+#[derive(Component, Debug, Default)]
+pub struct Spectator;
+
+pub const PLAYER_COLLIDER: Collider =
+    Collider::new(Vec3::new(-0.3, 0.0, -0.3), Vec3::new(0.3, 2.1, 0.3));
+/// Height of the player's eye above their feet, where the camera is in first person.
+pub const PLAYER_EYE_HEIGHT: f32 = 2.0;
+pub const PLAYER_MAX_HEALTH: f32 = 20.0;
+
+/// Maximum time between two jump presses for them to count as a double-tap.
+const DOUBLE_TAP_WINDOW: f32 = 0.3;
+/// Multiples of the normal speed sprinting and sneaking move at.
+const SPRINT_SPEED: f32 = 1.3;
+const SNEAK_SPEED: f32 = 0.3;
+/// How much wider the field of view is while sprinting, and how quickly it widens and narrows
+/// per second.
+const SPRINT_FOV: f32 = 1.15;
+const FOV_CHANGE_RATE: f32 = 8.0;
+/// Multiple of the normal speed swimming moves at.
+const SWIM_SPEED: f32 = 0.5;
+/// Fraction of gravity pulling a swimmer down, until they sink at `SINK_SPEED`.
+const SWIM_GRAVITY: f32 = 0.25;
+const SINK_SPEED: f32 = 2.0;
+/// Vertical speed swimming up or down, and how quickly water changes vertical speed otherwise.
+const SWIM_VERTICAL_SPEED: f32 = 4.0;
+const SWIM_ACCELERATION: f32 = 20.0;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum MovementMode {
+    Walk,
+    #[default]
+    Fly,
+    Noclip,
+}
+
+impl MovementMode {
+    fn has_gravity(&self) -> bool {
+        matches!(self, Self::Walk)
+    }
+
+    fn has_collision(&self) -> bool {
+        !matches!(self, Self::Noclip)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct MovementParams {
+    pub speed: f32,
+    pub acceleration: f32,
+    /// Rate the player slows down at when moving faster than the keys ask for.
+    pub friction: f32,
+}
+
 #[derive(Component)]
 pub struct PlayerMovement {
-    move_speed: f32,
+    pub mode: MovementMode,
+    walk: MovementParams,
+    fly: MovementParams,
+    noclip: MovementParams,
+    gravity: f32,
+    jump_speed: f32,
+    velocity: Vec3,
+    grounded: bool,
+    sprinting: bool,
+    sneaking: bool,
+    swimming: bool,
+    last_jump_press: f32,
 }
 
 impl Default for PlayerMovement {
     fn default() -> Self {
-        Self { move_speed: 20.0 }
+        Self {
+            mode: MovementMode::default(),
+            walk: MovementParams {
+                speed: 6.0,
+                acceleration: 60.0,
+                friction: 60.0,
+            },
+            fly: MovementParams {
+                speed: 20.0,
+                acceleration: 80.0,
+                friction: 80.0,
+            },
+            noclip: MovementParams {
+                speed: 40.0,
+                acceleration: 200.0,
+                friction: 200.0,
+            },
+            gravity: 32.0,
+            jump_speed: 9.0,
+            velocity: Vec3::ZERO,
+            grounded: false,
+            sprinting: false,
+            sneaking: false,
+            swimming: false,
+            last_jump_press: f32::NEG_INFINITY,
+        }
+    }
+}
+
+impl PlayerMovement {
+    pub fn stop(&mut self) {
+        self.velocity = Vec3::ZERO;
+    }
+
+    pub fn is_grounded(&self) -> bool {
+        self.grounded
+    }
+
+    pub fn is_sprinting(&self) -> bool {
+        self.sprinting
+    }
+
+    pub fn is_sneaking(&self) -> bool {
+        self.sneaking
+    }
+
+    /// Whether the player is walking in water, and so swims rather than walks.
+    pub fn is_swimming(&self) -> bool {
+        self.swimming
+    }
+
+    /// How the player moves in their current mode, walking as `settings` configure and faster
+    /// or slower while sprinting or sneaking.
+    fn params(&self, settings: &PlayerSettings) -> MovementParams {
+        let mut params = match self.mode {
+            MovementMode::Walk => MovementParams {
+                acceleration: settings.acceleration,
+                friction: settings.friction,
+                ..self.walk
+            },
+            MovementMode::Fly => self.fly,
+            MovementMode::Noclip => self.noclip,
+        };
+        if self.sprinting {
+            params.speed *= SPRINT_SPEED;
+        } else if self.sneaking {
+            params.speed *= SNEAK_SPEED;
+        }
+        if self.swimming {
+            params.speed *= SWIM_SPEED;
+        }
+        params
+    }
+
+    fn toggle_flying(&mut self) {
+        self.mode = match self.mode {
+            MovementMode::Walk => MovementMode::Fly,
+            MovementMode::Fly => MovementMode::Walk,
+            MovementMode::Noclip => MovementMode::Noclip,
+        };
+    }
+
+    fn toggle_noclip(&mut self) {
+        self.mode = match self.mode {
+            MovementMode::Noclip => MovementMode::Fly,
+            _ => MovementMode::Noclip,
+        };
     }
 }
 
+/// Moves `current` towards `target`, speeding up by at most `params.acceleration` and slowing
+/// down by at most `params.friction` over `delta_secs`.
+fn accelerate(current: Vec3, target: Vec3, params: MovementParams, delta_secs: f32) -> Vec3 {
+    let rate = if target.length_squared() < current.length_squared() {
+        params.friction
+    } else {
+        params.acceleration
+    };
+    approach(current, target, rate * delta_secs)
+}
+
+/// Vertical speed in water after `delta_secs`, swimming up or down while `direction` is `1` or
+/// `-1`, and otherwise sinking slowly under `gravity` weakened by the water.
+fn swim_vertical(current: f32, direction: f32, gravity: f32, delta_secs: f32) -> f32 {
+    let (target, rate) = if direction != 0.0 {
+        (direction * SWIM_VERTICAL_SPEED, SWIM_ACCELERATION)
+    } else if current > -SINK_SPEED {
+        (-SINK_SPEED, gravity * SWIM_GRAVITY)
+    } else {
+        // falling in, the water slows the player down
+        (-SINK_SPEED, SWIM_ACCELERATION)
+    };
+    current + (target - current).clamp(-rate * delta_secs, rate * delta_secs)
+}
+
+fn approach(current: Vec3, target: Vec3, max_delta: f32) -> Vec3 {
+    let delta = target - current;
+    let distance = delta.length();
+    if distance <= max_delta || distance == 0.0 {
+        target
+    } else {
+        current + delta / distance * max_delta
+    }
+}
+
+/// Direction the movement keys point in, relative to where the player faces, of length one or
+/// zero.
+pub fn movement_input(input: &ActionInput) -> Vec3 {
+    let mut movement_vector = Vec3::ZERO;
+    if input.pressed(Action::MoveLeft) {
+        movement_vector.x = -1.0;
+    } else if input.pressed(Action::MoveRight) {
+        movement_vector.x = 1.0;
+    }
+
+    if input.pressed(Action::MoveForward) {
+        movement_vector.z = -1.0;
+    } else if input.pressed(Action::MoveBackward) {
+        movement_vector.z = 1.0;
+    }
+    movement_vector.normalize_or_zero()
+}
+
 pub fn player_move(
     time: Res<Time>,
-    mut player_query: Query<(&PlayerMovement, &mut Transform)>,
+    mut world: ResMut<World>,
+    mut player_query: Query<(
+        &mut PlayerMovement,
+        &mut Transform,
+        Has<Riding>,
+        Has<Spectator>,
+    )>,
     camera_query: Query<(&Parent, &Transform), (With<Camera>, Without<PlayerMovement>)>,
-    keys: Res<ButtonInput<KeyCode>>,
+    settings_query: Query<&Settings>,
+    input: ActionInput,
 ) {
     let (parent, camera_transform) = camera_query.get_single().expect("camera does not exist");
-    let (player_movement, player_transform) = &mut player_query
+    let (mut movement, mut player_transform, riding, spectator) = player_query
         .get_mut(parent.get())
         .expect("player does not exist");
+    // a player in the saddle steers their mount instead, see `steer_mounts`
+    if riding {
+        return;
+    }
 
-    let move_speed = player_movement.move_speed;
+    if spectator {
+        movement.mode = MovementMode::Noclip;
+    } else if input.just_pressed(Action::ToggleNoclip) {
+        movement.toggle_noclip();
+    }
 
-    let mut movement_vector = Vec3::ZERO;
-    if keys.pressed(KeyCode::KeyA) {
-        movement_vector.x = -move_speed;
-    } else if keys.pressed(KeyCode::KeyD) {
-        movement_vector.x = move_speed;
+    if input.just_pressed(Action::ToggleFly) {
+        movement.toggle_flying();
     }
 
-    if keys.pressed(KeyCode::KeyW) {
-        movement_vector.z = -move_speed;
-    } else if keys.pressed(KeyCode::KeyS) {
-        movement_vector.z = move_speed;
+    movement.swimming = movement.mode == MovementMode::Walk
+        && touches_block(
+            &mut world,
+            player_transform.translation,
+            PLAYER_COLLIDER,
+            BlockType::Water,
+        );
+
+    // jump is pressed over and over to swim, which mustn't start flying
+    if input.just_pressed(Action::Jump) && !movement.swimming {
+        let now = time.elapsed_secs();
+        if now - movement.last_jump_press < DOUBLE_TAP_WINDOW {
+            movement.toggle_flying();
+            movement.last_jump_press = f32::NEG_INFINITY;
+        } else {
+            movement.last_jump_press = now;
+        }
     }
 
+    let movement_vector = movement_input(&input);
+    // sneaking shares the descend key, which only flies down when not walking
+    movement.sneaking =
+        movement.mode == MovementMode::Walk && !movement.swimming && input.pressed(Action::Descend);
+    // sprinting lasts while moving forwards, starting only when the key is held
+    movement.sprinting = movement_vector.z < 0.0
+        && !movement.sneaking
+        && (movement.sprinting || input.pressed(Action::Sprint));
+
     let mut vertical_movement = Vec3::ZERO;
-    if keys.pressed(KeyCode::Space) {
-        vertical_movement.y = move_speed;
-    } else if keys.pressed(KeyCode::ShiftLeft) {
-        vertical_movement.y = -move_speed;
+    if input.pressed(Action::Jump) {
+        vertical_movement.y = 1.0;
+    } else if input.pressed(Action::Descend) {
+        vertical_movement.y = -1.0;
     }
 
-    let final_movement =
-        player_transform.rotation * camera_transform.rotation * movement_vector * time.delta_secs()
-            + (vertical_movement * time.delta_secs());
-    player_transform.translation += final_movement;
+    let delta_secs = time.delta_secs();
+    let settings = settings_query
+        .get_single()
+        .map(|settings| settings.player)
+        .unwrap_or_default();
+    let params = movement.params(&settings);
+    let velocity = if movement.mode.has_gravity() {
+        let target = player_transform.rotation * movement_vector * params.speed;
+        let horizontal = Vec3::new(movement.velocity.x, 0.0, movement.velocity.z);
+        let mut velocity = accelerate(horizontal, target, params, delta_secs);
+
+        velocity.y = if movement.swimming {
+            swim_vertical(
+                movement.velocity.y,
+                vertical_movement.y,
+                movement.gravity,
+                delta_secs,
+            )
+        } else if movement.grounded && input.pressed(Action::Jump) {
+            movement.jump_speed
+        } else {
+            movement.velocity.y - movement.gravity * delta_secs
+        };
+        velocity
+    } else {
+        let target = (player_transform.rotation * camera_transform.rotation * movement_vector
+            + vertical_movement)
+            * params.speed;
+        accelerate(movement.velocity, target, params, delta_secs)
+    };
+
+    let mut delta = velocity * delta_secs;
+    if movement.sneaking && movement.grounded {
+        delta = stop_at_ledges(
+            &mut world,
+            player_transform.translation,
+            PLAYER_COLLIDER,
+            delta,
+        );
+    }
+    if movement.mode.has_collision() {
+        let (position, blocked) = move_and_collide(
+            &mut world,
+            player_transform.translation,
+            PLAYER_COLLIDER,
+            delta,
+        );
+        player_transform.translation = position;
+
+        movement.grounded = blocked.y && velocity.y < 0.0;
+        // movement held back at a ledge is lost, as if blocked
+        let held = (delta - velocity * delta_secs).cmpne(Vec3::ZERO);
+        movement.velocity = Vec3::select(blocked | held, Vec3::ZERO, velocity);
+    } else {
+        player_transform.translation += delta;
+        movement.grounded = false;
+        movement.velocity = velocity;
+    }
+}
+
+/// Widens the player's field of view while they sprint, easing between the two.
+pub fn update_sprint_fov(
+    time: Res<Time>,
+    player_query: Query<&PlayerMovement>,
+    mut camera_query: Query<(&Parent, &mut Projection), With<Camera3d>>,
+) {
+    let base = PerspectiveProjection::default().fov;
+    for (parent, mut projection) in camera_query.iter_mut() {
+        let (Ok(movement), Projection::Perspective(perspective)) =
+            (player_query.get(parent.get()), &*projection)
+        else {
+            continue;
+        };
+        let target = if movement.is_sprinting() {
+            base * SPRINT_FOV
+        } else {
+            base
+        };
+        if (target - perspective.fov).abs() < 1e-4 {
+            continue;
+        }
+        let fov = perspective.fov
+            + (target - perspective.fov) * (FOV_CHANGE_RATE * time.delta_secs()).min(1.0);
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.fov = fov;
+        }
+    }
+}
+
+pub fn tp_command(
+    In(mut args): In<CommandArgs>,
+    mut player_query: Query<(&mut Transform, &mut PlayerMovement), With<Player>>,
+) -> CommandResult {
+    let position = args.position()?;
+    args.finish()?;
+    for (mut transform, mut movement) in player_query.iter_mut() {
+        transform.translation = position;
+        movement.stop();
+    }
+    Ok(format!(
+        "teleported to {:.1}, {:.1}, {:.1}",
+        position.x, position.y, position.z
+    ))
 }
 
 #[derive(Component)]
@@ -110,3 +458,23 @@ pub fn player_look(
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{swim_vertical, SINK_SPEED, SWIM_VERTICAL_SPEED};
+
+    #[test]
+    fn test_swim_vertical() {
+        // sinking is gentler than falling, and limited
+        let sinking = swim_vertical(0.0, 0.0, 32.0, 0.1);
+        assert!(sinking < 0.0 && sinking > -3.2);
+        assert_eq!(-SINK_SPEED, swim_vertical(-SINK_SPEED, 0.0, 32.0, 0.1));
+
+        // diving in quickly is slowed down by the water
+        let diving = swim_vertical(-20.0, 0.0, 32.0, 0.1);
+        assert!(diving > -20.0 && diving < -SINK_SPEED);
+
+        assert_eq!(SWIM_VERTICAL_SPEED, swim_vertical(0.0, 1.0, 32.0, 1.0));
+        assert_eq!(-SWIM_VERTICAL_SPEED, swim_vertical(0.0, -1.0, 32.0, 1.0));
+    }
+}
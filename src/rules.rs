@@ -0,0 +1,84 @@
+use bevy::ecs::system::{In, ResMut};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    command::{CommandArgs, CommandResult},
+    save::WorldInfo,
+};
+
+/// Rules a world is played by, saved with it and changed in game with `/gamerule`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameRules {
+    /// Whether a player's belongings are kept in a gravestone where they die, instead of being
+    /// scattered on the ground.
+    pub gravestone: bool,
+}
+
+impl GameRules {
+    /// Names of every rule, as used by `/gamerule`.
+    pub const NAMES: [&'static str; 1] = ["gravestone"];
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "gravestone" => Some(self.gravestone),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: bool) -> Result<(), String> {
+        match name {
+            "gravestone" => self.gravestone = value,
+            _ => return Err(unknown_rule(name)),
+        }
+        Ok(())
+    }
+}
+
+fn unknown_rule(name: &str) -> String {
+    format!(
+        "unknown rule '{name}', rules are {}",
+        GameRules::NAMES.join(", ")
+    )
+}
+
+pub fn gamerule_command(
+    In(mut args): In<CommandArgs>,
+    world_info: Option<ResMut<WorldInfo>>,
+) -> CommandResult {
+    let mut world_info = world_info.ok_or_else(|| "no world is open".to_string())?;
+    let name = args.word("a rule")?.to_lowercase();
+    let Some(value) = args.optional_word() else {
+        let value = world_info
+            .rules
+            .get(&name)
+            .ok_or_else(|| unknown_rule(&name))?;
+        return Ok(format!("{name} is {value}"));
+    };
+    args.finish()?;
+
+    let value = value
+        .parse::<bool>()
+        .map_err(|_| format!("'{value}' is not true or false"))?;
+    world_info.rules.set(&name, value)?;
+    if let Err(e) = world_info.save() {
+        return Err(format!("set {name} to {value} but failed to save it: {e}"));
+    }
+    Ok(format!("set {name} to {value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GameRules;
+
+    #[test]
+    fn test_set_rules_by_name() {
+        let mut rules = GameRules::default();
+        assert_eq!(Some(false), rules.get("gravestone"));
+
+        rules.set("gravestone", true).unwrap();
+        assert!(rules.gravestone);
+        assert!(rules.set("keepinventory", true).is_err());
+        assert_eq!(None, rules.get("keepinventory"));
+    }
+}
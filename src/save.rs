@@ -0,0 +1,675 @@
+pub mod inspect;
+pub mod map;
+pub mod upgrade;
+
+use std::{
+    error::Error,
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+};
+
+use bevy::{ecs::system::Resource, math::I64Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block::BlockType,
+    chunks::{
+        chunk::{is_valid_chunk_size, ChunkCoordinate, ChunkData, CHUNK_SIZE},
+        codec::{decode_chunk, encode_chunk, ChunkCodecError},
+        generate::terrain::GeneratorKind,
+    },
+    ids::IdMap,
+    item::Held,
+    rules::GameRules,
+};
+
+pub const SAVES_DIR: &str = "saves";
+const WORLD_FILE: &str = "world.toml";
+/// Directory within a world's save holding its edited chunks, one file per chunk.
+const CHUNKS_DIR: &str = "chunks";
+/// Directory within a world's save holding chunk files that failed to load, kept for inspection.
+const QUARANTINE_DIR: &str = "quarantine";
+/// Directory within a world's save holding the entities left in unloaded chunks, one file per
+/// chunk.
+const CHUNK_ENTITIES_DIR: &str = "chunk_entities";
+const ENTITIES_FILE: &str = "entities.ron";
+const GRAVESTONES_FILE: &str = "gravestones.ron";
+const PLAYER_FILE: &str = "player.ron";
+
+#[cfg(test)]
+thread_local! {
+    static TEST_SAVES: std::cell::RefCell<Option<PathBuf>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Directory every world is saved in, `SAVES_DIR` unless a test has moved it with `TestSaves`.
+fn saves_dir() -> PathBuf {
+    #[cfg(test)]
+    if let Some(dir) = TEST_SAVES.with_borrow(Clone::clone) {
+        return dir;
+    }
+    PathBuf::from(SAVES_DIR)
+}
+
+/// Saves the worlds of the test creating it in a temporary directory of its own instead of
+/// `SAVES_DIR`, deleting them when dropped, so tests never leave worlds in the main menu.
+#[cfg(test)]
+pub(crate) struct TestSaves {
+    dir: PathBuf,
+}
+
+#[cfg(test)]
+impl TestSaves {
+    pub(crate) fn new() -> Self {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "rustcraft-saves-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        TEST_SAVES.set(Some(dir.clone()));
+        Self { dir }
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestSaves {
+    fn drop(&mut self) {
+        TEST_SAVES.set(None);
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Metadata describing a saved world, stored as `saves/<dir>/world.toml`.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorldInfo {
+    pub name: String,
+    pub seed: u32,
+    /// Width of the world's chunks in blocks, fixed when the world is created.
+    #[serde(default = "default_chunk_size")]
+    pub chunk_size: u16,
+    /// What the world's terrain is generated by, fixed when the world is created.
+    #[serde(default)]
+    pub generator: GeneratorKind,
+    /// Dying in a hardcore world ends the game for good, chosen when the world is created.
+    #[serde(default)]
+    pub hardcore: bool,
+    /// Set when the player dies in a hardcore world, which can then only be spectated.
+    #[serde(default)]
+    pub game_over: bool,
+    #[serde(default)]
+    pub rules: GameRules,
+    /// What the block and item ids in the world's chunks stand for.
+    #[serde(default = "IdMap::legacy")]
+    pub ids: IdMap,
+}
+
+fn default_chunk_size() -> u16 {
+    CHUNK_SIZE
+}
+
+impl WorldInfo {
+    pub fn new(name: &str, seed: u32) -> Self {
+        let name = name.trim();
+        Self {
+            name: if name.is_empty() {
+                "New World".to_string()
+            } else {
+                name.to_string()
+            },
+            seed,
+            chunk_size: CHUNK_SIZE,
+            generator: GeneratorKind::default(),
+            hardcore: false,
+            game_over: false,
+            rules: GameRules::default(),
+            ids: IdMap::default(),
+        }
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: u16) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn with_generator(mut self, generator: GeneratorKind) -> Self {
+        self.generator = generator;
+        self
+    }
+
+    pub fn with_hardcore(mut self, hardcore: bool) -> Self {
+        self.hardcore = hardcore;
+        self
+    }
+
+    /// Renames a new world, if needed, so it doesn't share a directory with a world already
+    /// saved: a second "New World" becomes "New World 2". Names differing only in characters
+    /// `dir` replaces, such as "a b" and "a_b", are told apart the same way.
+    pub fn with_unique_name(mut self) -> Self {
+        let name = self.name.clone();
+        let mut copy = 2;
+        while self.dir().exists() {
+            self.name = format!("{name} {copy}");
+            copy += 1;
+        }
+        self
+    }
+
+    /// Locks a hardcore world into spectating after its player dies, saving it straight away so
+    /// quitting can't undo it.
+    pub fn end_game(&mut self) -> Result<(), Box<dyn Error>> {
+        self.game_over = true;
+        self.save()
+    }
+
+    /// Deletes the world's save along with every chunk in it.
+    pub fn delete(&self) -> io::Result<()> {
+        fs::remove_dir_all(self.dir())
+    }
+
+    /// Directory the world is saved in, derived from its name.
+    pub fn dir(&self) -> PathBuf {
+        let dir_name: String = self
+            .name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        saves_dir().join(dir_name)
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let dir = self.dir();
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(WORLD_FILE), toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    fn chunks_dir(&self) -> PathBuf {
+        self.dir().join(CHUNKS_DIR)
+    }
+
+    /// File an edited chunk is saved to, named after its coordinate.
+    pub fn chunk_path(&self, coord: ChunkCoordinate) -> PathBuf {
+        let ChunkCoordinate(coord) = coord;
+        self.chunks_dir()
+            .join(format!("{}.{}.{}.chunk", coord.x, coord.y, coord.z))
+    }
+
+    pub fn save_chunk(
+        &self,
+        coord: ChunkCoordinate,
+        chunk: &ChunkData,
+    ) -> Result<(), Box<dyn Error>> {
+        let path = self.chunk_path(coord);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, encode_chunk(chunk, &self.ids))?;
+        Ok(())
+    }
+
+    /// Reads a chunk saved by `save_chunk`, or `None` if it was never saved. A file that can't be
+    /// decoded, or holds a chunk of the wrong size, gives a `ChunkCodecError`.
+    pub fn load_chunk(&self, coord: ChunkCoordinate) -> Result<Option<ChunkData>, Box<dyn Error>> {
+        let bytes = match fs::read(self.chunk_path(coord)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let chunk = decode_chunk(&bytes, &self.ids)?;
+        if chunk.size != self.chunk_size {
+            return Err(ChunkCodecError::Malformed("chunk size differs from the world's").into());
+        }
+        Ok(Some(chunk))
+    }
+
+    /// Moves a chunk's file into the world's quarantine directory, so a corrupt chunk is kept
+    /// but no longer loaded. Returns where it was moved to.
+    pub fn quarantine_chunk(&self, coord: ChunkCoordinate) -> io::Result<PathBuf> {
+        self.quarantine_file(&self.chunk_path(coord))
+    }
+
+    /// Moves any file in the save into its quarantine directory, returning where it was moved to.
+    pub fn quarantine_file(&self, path: &Path) -> io::Result<PathBuf> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?
+            .to_string_lossy()
+            .into_owned();
+        let dir = self.dir().join(QUARANTINE_DIR);
+        fs::create_dir_all(&dir)?;
+        // a chunk can be quarantined more than once, so earlier copies are numbered rather than
+        // replaced
+        let mut target = dir.join(&name);
+        let mut copy = 1;
+        while target.exists() {
+            target = dir.join(match name.rsplit_once('.') {
+                Some((stem, extension)) => format!("{stem}.{copy}.{extension}"),
+                None => format!("{name}.{copy}"),
+            });
+            copy += 1;
+        }
+        fs::rename(path, &target)?;
+        Ok(target)
+    }
+
+    /// Reads a world's metadata, giving ids to blocks and items added since it was last saved.
+    /// The world must be saved again before any of its chunks are, so the new ids are kept.
+    pub fn load(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let info_str = fs::read_to_string(dir.join(WORLD_FILE))?;
+        let mut info: Self = toml::from_str(&info_str)?;
+        if !is_valid_chunk_size(info.chunk_size) {
+            return Err(format!("unsupported chunk size {}", info.chunk_size).into());
+        }
+        info.ids.register_missing();
+        Ok(info)
+    }
+}
+
+/// Entities kept in a world's save. Players don't have lasting identities yet, so pets belong to
+/// whoever plays the world next.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedEntities {
+    pub pets: Vec<SavedPet>,
+}
+
+/// A tamed mob and the state its owner left it in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedPet {
+    /// Name of the mob in the `MobRegistry`.
+    pub mob: String,
+    pub position: [f32; 3],
+    pub health: f32,
+    pub staying: bool,
+}
+
+impl WorldInfo {
+    pub fn save_entities(&self, entities: &SavedEntities) -> Result<(), Box<dyn Error>> {
+        let dir = self.dir();
+        fs::create_dir_all(&dir)?;
+        let source = ron::ser::to_string_pretty(entities, ron::ser::PrettyConfig::default())?;
+        fs::write(dir.join(ENTITIES_FILE), source)?;
+        Ok(())
+    }
+
+    /// Reads the entities saved by `save_entities`, or none if they never were.
+    pub fn load_entities(&self) -> Result<SavedEntities, Box<dyn Error>> {
+        match fs::read_to_string(self.dir().join(ENTITIES_FILE)) {
+            Ok(source) => Ok(ron::from_str(&source)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(SavedEntities::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Items, mobs and falling blocks that were in a chunk when it was unloaded or the world was
+/// saved, brought back when the chunk loads again.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedChunkEntities {
+    /// Counts up each time the chunk's file is rewritten, so the entities of a file that are
+    /// already in the world can be told from a newer file's.
+    #[serde(default)]
+    pub generation: u64,
+    #[serde(default)]
+    pub items: Vec<SavedItem>,
+    #[serde(default)]
+    pub mobs: Vec<SavedMob>,
+    #[serde(default)]
+    pub falling_blocks: Vec<SavedFallingBlock>,
+}
+
+impl SavedChunkEntities {
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty() && self.mobs.is_empty() && self.falling_blocks.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedItem {
+    pub item: Held,
+    pub count: u32,
+    pub position: [f32; 3],
+    /// Seconds the item has been on the ground, so it still despawns in time.
+    pub age: f32,
+}
+
+/// A mob that isn't anyone's pet, which are kept in `SavedEntities` instead.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedMob {
+    /// Name of the mob in the `MobRegistry`.
+    pub mob: String,
+    pub position: [f32; 3],
+    pub health: f32,
+    #[serde(default)]
+    pub saddled: bool,
+    /// Whether it spawned on its own, and so may despawn again when nobody is near.
+    #[serde(default)]
+    pub natural: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedFallingBlock {
+    pub block: BlockType,
+    pub position: [f32; 3],
+}
+
+impl WorldInfo {
+    /// File the entities of a chunk are saved to, named after its coordinate like its blocks.
+    fn chunk_entities_path(&self, coord: ChunkCoordinate) -> PathBuf {
+        let ChunkCoordinate(coord) = coord;
+        self.dir()
+            .join(CHUNK_ENTITIES_DIR)
+            .join(format!("{}.{}.{}.ron", coord.x, coord.y, coord.z))
+    }
+
+    /// Saves the entities in the chunk at `coord`, replacing any saved before. The file is written
+    /// next to the old one and renamed over it, so it's never left half written. A chunk left
+    /// without entities has its file removed.
+    pub fn save_chunk_entities(
+        &self,
+        coord: ChunkCoordinate,
+        entities: &SavedChunkEntities,
+    ) -> Result<(), Box<dyn Error>> {
+        let path = self.chunk_entities_path(coord);
+        if entities.is_empty() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let source = ron::ser::to_string_pretty(entities, ron::ser::PrettyConfig::default())?;
+        let partial = path.with_extension("ron.tmp");
+        fs::write(&partial, source)?;
+        fs::rename(partial, path)?;
+        Ok(())
+    }
+
+    /// Reads the entities saved in the chunk at `coord`, or none if it was never saved. The file
+    /// is kept until the chunk is saved again, so whether they're already in the world is up to
+    /// the caller, see `SavedChunkEntities::generation`.
+    pub fn load_chunk_entities(
+        &self,
+        coord: ChunkCoordinate,
+    ) -> Result<SavedChunkEntities, Box<dyn Error>> {
+        match fs::read_to_string(self.chunk_entities_path(coord)) {
+            Ok(source) => Ok(ron::from_str(&source)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(SavedChunkEntities::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// What a player was carrying when they died, kept in the gravestone block at `position`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedGravestone {
+    pub position: [i64; 3],
+    pub items: Vec<(Held, u32)>,
+}
+
+impl WorldInfo {
+    pub fn save_gravestones(&self, gravestones: &[SavedGravestone]) -> Result<(), Box<dyn Error>> {
+        let dir = self.dir();
+        fs::create_dir_all(&dir)?;
+        let source = ron::ser::to_string_pretty(gravestones, ron::ser::PrettyConfig::default())?;
+        fs::write(dir.join(GRAVESTONES_FILE), source)?;
+        Ok(())
+    }
+
+    /// Reads the gravestones saved by `save_gravestones`, or none if there never were any.
+    pub fn load_gravestones(&self) -> Result<Vec<SavedGravestone>, Box<dyn Error>> {
+        match fs::read_to_string(self.dir().join(GRAVESTONES_FILE)) {
+            Ok(source) => Ok(ron::from_str(&source)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// What the local player keeps between sessions in a world.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedPlayer {
+    #[serde(default)]
+    pub experience: u32,
+}
+
+impl WorldInfo {
+    pub fn save_player(&self, player: &SavedPlayer) -> Result<(), Box<dyn Error>> {
+        let dir = self.dir();
+        fs::create_dir_all(&dir)?;
+        let source = ron::ser::to_string_pretty(player, ron::ser::PrettyConfig::default())?;
+        fs::write(dir.join(PLAYER_FILE), source)?;
+        Ok(())
+    }
+
+    /// Reads the player saved by `save_player`, or a fresh one if it never was.
+    pub fn load_player(&self) -> Result<SavedPlayer, Box<dyn Error>> {
+        match fs::read_to_string(self.dir().join(PLAYER_FILE)) {
+            Ok(source) => Ok(ron::from_str(&source)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(SavedPlayer::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// The chunk a file named by `WorldInfo::chunk_path` holds, or `None` for any other file.
+pub fn chunk_file_coordinate(path: &Path) -> Option<ChunkCoordinate> {
+    let name = path.file_name()?.to_str()?.strip_suffix(".chunk")?;
+    let mut parts = name.split('.').map(str::parse::<i64>);
+    let coord = I64Vec3::new(
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+    );
+    parts.next().is_none().then_some(ChunkCoordinate(coord))
+}
+
+/// Lists every world in the saves directory, skipping any whose metadata can't be read.
+pub fn list_worlds() -> Vec<WorldInfo> {
+    let Ok(entries) = fs::read_dir(saves_dir()) else {
+        return Vec::new();
+    };
+
+    let mut worlds: Vec<WorldInfo> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| WorldInfo::load(&entry.path()).ok())
+        .collect();
+    worlds.sort_by(|a, b| a.name.cmp(&b.name));
+    worlds
+}
+
+/// Numeric seeds are used as-is, any other text is hashed so words can be used as seeds. An
+/// empty seed picks one at random.
+pub fn parse_seed(seed: &str) -> u32 {
+    let seed = seed.trim();
+    if seed.is_empty() {
+        return rand::random();
+    }
+    if let Ok(seed) = seed.parse() {
+        return seed;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    hasher.finish() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use bevy::math::I64Vec3;
+
+    use super::{
+        chunk_file_coordinate, parse_seed, SavedChunkEntities, SavedEntities, SavedFallingBlock,
+        SavedItem, SavedMob, SavedPet, SavedPlayer, TestSaves, WorldInfo,
+    };
+    use crate::{
+        block::BlockType,
+        chunks::{
+            chunk::{ChunkCoordinate, ChunkData},
+            codec::ChunkCodecError,
+            generate::terrain::GeneratorKind,
+        },
+        ids::IdMap,
+        item::Held,
+    };
+
+    #[test]
+    fn test_parse_seed() {
+        assert_eq!(1234, parse_seed("1234"));
+        assert_eq!(1234, parse_seed(" 1234 "));
+        assert_eq!(parse_seed("rustcraft"), parse_seed("rustcraft"));
+        assert_ne!(parse_seed("rustcraft"), parse_seed("rustcrafts"));
+    }
+
+    #[test]
+    fn test_world_info_dir_sanitises_name() {
+        let info = WorldInfo::new("  My World/../x ", 1);
+        assert_eq!("My World/../x", info.name);
+        assert_eq!(Path::new("saves").join("My_World____x"), info.dir());
+        assert_eq!("New World", WorldInfo::new("", 1).name);
+    }
+
+    #[test]
+    fn test_new_worlds_get_unique_names() {
+        let _saves = TestSaves::new();
+        let first = WorldInfo::new("", 1).with_unique_name();
+        first.save().unwrap();
+        let second = WorldInfo::new("New World", 2).with_unique_name();
+        second.save().unwrap();
+        let third = WorldInfo::new("New_World", 3).with_unique_name();
+
+        assert_eq!("New World", first.name);
+        assert_eq!("New World 2", second.name);
+        assert_eq!("New_World 3", third.name);
+        assert_eq!(1, WorldInfo::load(&first.dir()).unwrap().seed);
+    }
+
+    #[test]
+    fn test_chunk_path_in_world_dir() {
+        let info = WorldInfo::new("Test", 1);
+        let coord = ChunkCoordinate(I64Vec3::new(-3, 0, 12));
+        assert_eq!(
+            Path::new("saves")
+                .join("Test")
+                .join("chunks")
+                .join("-3.0.12.chunk"),
+            info.chunk_path(coord)
+        );
+        assert_eq!(Some(coord), chunk_file_coordinate(&info.chunk_path(coord)));
+        assert_eq!(None, chunk_file_coordinate(Path::new("1.2.chunk")));
+        assert_eq!(None, chunk_file_coordinate(Path::new("1.2.3.4.chunk")));
+        assert_eq!(None, chunk_file_coordinate(Path::new("1.2.3.ron")));
+    }
+
+    #[test]
+    fn test_quarantine_corrupt_chunk() {
+        let _saves = TestSaves::new();
+        let info = WorldInfo::new("quarantine test", 1);
+        let coord = ChunkCoordinate(I64Vec3::new(2, -1, 0));
+        info.save_chunk(coord, &ChunkData::with_size(32)).unwrap();
+        let wrong_size = info.load_chunk(coord);
+        assert!(wrong_size.unwrap_err().is::<ChunkCodecError>());
+        let first = info.quarantine_chunk(coord).unwrap();
+        assert_eq!(
+            Some("2.-1.0.chunk"),
+            first.file_name().and_then(|f| f.to_str())
+        );
+
+        fs::write(info.chunk_path(coord), b"not a chunk").unwrap();
+        let garbage = info.load_chunk(coord);
+        assert!(garbage.unwrap_err().is::<ChunkCodecError>());
+        let second = info.quarantine_chunk(coord).unwrap();
+        assert_eq!(
+            Some("2.-1.0.1.chunk"),
+            second.file_name().and_then(|f| f.to_str())
+        );
+        assert!(info.load_chunk(coord).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_world_info_round_trip() {
+        let info = WorldInfo::new("Test", 42)
+            .with_chunk_size(32)
+            .with_generator(GeneratorKind::Amplified)
+            .with_hardcore(true);
+        let loaded: WorldInfo = toml::from_str(&toml::to_string(&info).unwrap()).unwrap();
+        assert_eq!(info, loaded);
+    }
+
+    #[test]
+    fn test_saved_entities_round_trip() {
+        let entities = SavedEntities {
+            pets: vec![SavedPet {
+                mob: "wolf".to_string(),
+                position: [1.0, 20.5, -3.0],
+                health: 12.0,
+                staying: true,
+            }],
+        };
+        let source =
+            ron::ser::to_string_pretty(&entities, ron::ser::PrettyConfig::default()).unwrap();
+        assert_eq!(entities, ron::from_str(&source).unwrap());
+    }
+
+    #[test]
+    fn test_chunk_entities_are_kept_until_saved_again() {
+        let _saves = TestSaves::new();
+        let info = WorldInfo::new("chunk entities test", 1);
+        let coord = ChunkCoordinate(I64Vec3::new(-1, 2, 3));
+        let entities = SavedChunkEntities {
+            generation: 4,
+            items: vec![SavedItem {
+                item: Held::Block(BlockType::Sand),
+                count: 3,
+                position: [-10.0, 40.2, 55.0],
+                age: 12.5,
+            }],
+            mobs: vec![SavedMob {
+                mob: "pig".to_string(),
+                position: [-12.0, 40.0, 50.0],
+                health: 6.0,
+                saddled: false,
+                natural: true,
+            }],
+            falling_blocks: vec![SavedFallingBlock {
+                block: BlockType::Sand,
+                position: [-9.0, 44.0, 53.0],
+            }],
+        };
+        assert!(info.load_chunk_entities(coord).unwrap().is_empty());
+        info.save_chunk_entities(coord, &entities).unwrap();
+        assert_eq!(entities, info.load_chunk_entities(coord).unwrap());
+        assert_eq!(entities, info.load_chunk_entities(coord).unwrap());
+        assert!(!info
+            .chunk_entities_path(coord)
+            .with_extension("ron.tmp")
+            .exists());
+
+        info.save_chunk_entities(coord, &SavedChunkEntities::default())
+            .unwrap();
+        assert!(info.load_chunk_entities(coord).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_saved_player_defaults() {
+        let loaded: SavedPlayer = ron::from_str("()").unwrap();
+        assert_eq!(SavedPlayer::default(), loaded);
+    }
+
+    #[test]
+    fn test_world_info_defaults_chunk_size() {
+        let loaded: WorldInfo = toml::from_str("name = \"Old\"\nseed = 7").unwrap();
+        assert_eq!(16, loaded.chunk_size);
+        assert_eq!(GeneratorKind::Noise, loaded.generator);
+        assert!(!loaded.hardcore);
+        assert!(!loaded.game_over);
+        assert_eq!(IdMap::legacy(), loaded.ids);
+    }
+}
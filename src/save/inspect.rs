@@ -0,0 +1,267 @@
+//! Statistics and integrity checks for a world's save, run offline by `rustcraft-save-tool`.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::{self, Display},
+    fs, io,
+    path::PathBuf,
+};
+
+use bevy::math::I64Vec3;
+
+use super::{chunk_file_coordinate, WorldInfo};
+use crate::{
+    block::BlockType,
+    chunks::{chunk::ChunkCoordinate, position::BlockPos},
+    mob::registry::MobRegistry,
+};
+
+/// Chunks along each side of a region, the unit chunks are counted in.
+pub const REGION_SIZE: i64 = 32;
+/// Chunks listed as the largest.
+const LARGEST_CHUNKS: usize = 10;
+
+#[derive(Debug, Default)]
+pub struct SaveReport {
+    /// Saved chunks in each region, by region coordinate.
+    pub regions: BTreeMap<[i64; 3], usize>,
+    /// Blocks of each type other than air across every readable chunk.
+    pub blocks: BTreeMap<&'static str, u64>,
+    /// The largest chunk files and their size in bytes, largest first.
+    pub largest: Vec<(ChunkCoordinate, u64)>,
+    pub problems: Vec<Problem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// A chunk file that can't be decoded, or holds a chunk of the wrong size.
+    CorruptChunk { path: PathBuf, error: String },
+    /// A file among the chunks that isn't named after one.
+    StrayFile(PathBuf),
+    /// A pet whose mob no longer exists, by its index in the saved entities.
+    UnknownPet { index: usize, mob: String },
+    /// A gravestone's items with no gravestone block where they were left.
+    OrphanedGravestone { index: usize, position: [i64; 3] },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CorruptChunk { path, error } => {
+                write!(f, "{} is corrupt: {}", path.display(), error)
+            }
+            Self::StrayFile(path) => write!(f, "{} is not a chunk", path.display()),
+            Self::UnknownPet { mob, .. } => write!(f, "a pet is an unknown mob '{}'", mob),
+            Self::OrphanedGravestone { position, .. } => write!(
+                f,
+                "the gravestone at {} {} {} is missing",
+                position[0], position[1], position[2]
+            ),
+        }
+    }
+}
+
+/// Reads every part of the world's save, gathering statistics and anything wrong with it.
+pub fn inspect_world(
+    world_info: &WorldInfo,
+    mobs: &MobRegistry,
+) -> Result<SaveReport, Box<dyn Error>> {
+    let mut report = SaveReport::default();
+    let entries = match fs::read_dir(world_info.chunks_dir()) {
+        Ok(entries) => entries.collect::<Result<Vec<_>, _>>()?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut sizes = vec![];
+    for entry in entries {
+        let path = entry.path();
+        let Some(coord) = chunk_file_coordinate(&path).filter(|_| path.is_file()) else {
+            report.problems.push(Problem::StrayFile(path));
+            continue;
+        };
+
+        let chunk = match world_info.load_chunk(coord) {
+            Ok(chunk) => chunk.ok_or("chunk was removed while reading")?,
+            Err(e) => {
+                let error = e.to_string();
+                report.problems.push(Problem::CorruptChunk { path, error });
+                continue;
+            }
+        };
+        for (_, block) in chunk.blocks() {
+            *report.blocks.entry(block.name()).or_default() += 1;
+        }
+        let region = coord.0.div_euclid(I64Vec3::splat(REGION_SIZE));
+        *report.regions.entry(region.to_array()).or_default() += 1;
+        sizes.push((coord, entry.metadata()?.len()));
+    }
+    sizes.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+    sizes.truncate(LARGEST_CHUNKS);
+    report.largest = sizes;
+
+    for (index, pet) in world_info.load_entities()?.pets.into_iter().enumerate() {
+        if mobs.by_name(&pet.mob).is_none() {
+            report.problems.push(Problem::UnknownPet {
+                index,
+                mob: pet.mob,
+            });
+        }
+    }
+
+    for (index, gravestone) in world_info.load_gravestones()?.into_iter().enumerate() {
+        let position = I64Vec3::from_array(gravestone.position);
+        let (coord, local) = BlockPos(position).split(world_info.chunk_size);
+        // a corrupt chunk is reported already, so its gravestones aren't
+        let block = match world_info.load_chunk(coord) {
+            Ok(Some(chunk)) => chunk.get_block_at(local.get()),
+            Ok(None) => BlockType::Air,
+            Err(_) => continue,
+        };
+        if block != BlockType::Gravestone {
+            report.problems.push(Problem::OrphanedGravestone {
+                index,
+                position: gravestone.position,
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// Fixes the problems `inspect_world` found, returning a line describing each fix. Corrupt
+/// chunks and stray files are quarantined, so the game regenerates those chunks, and pets and
+/// gravestones left without a mob or block are removed.
+pub fn repair_world(
+    world_info: &WorldInfo,
+    problems: &[Problem],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut fixes = vec![];
+    let mut entities = world_info.load_entities()?;
+    let mut gravestones = world_info.load_gravestones()?;
+    let (mut pets_removed, mut gravestones_removed) = (vec![], vec![]);
+    for problem in problems {
+        match problem {
+            Problem::CorruptChunk { path, .. } | Problem::StrayFile(path) => {
+                let moved = world_info.quarantine_file(path)?;
+                fixes.push(format!("moved {} to {}", path.display(), moved.display()));
+            }
+            Problem::UnknownPet { index, .. } => pets_removed.push(*index),
+            Problem::OrphanedGravestone { index, .. } => gravestones_removed.push(*index),
+        }
+    }
+
+    if !pets_removed.is_empty() {
+        entities.pets = (entities.pets.into_iter().enumerate())
+            .filter(|(i, _)| !pets_removed.contains(i))
+            .map(|(_, pet)| pet)
+            .collect();
+        world_info.save_entities(&entities)?;
+        fixes.push(format!("removed {} unknown pets", pets_removed.len()));
+    }
+    if !gravestones_removed.is_empty() {
+        gravestones = (gravestones.into_iter().enumerate())
+            .filter(|(i, _)| !gravestones_removed.contains(i))
+            .map(|(_, gravestone)| gravestone)
+            .collect();
+        world_info.save_gravestones(&gravestones)?;
+        fixes.push(format!(
+            "removed {} missing gravestones",
+            gravestones_removed.len()
+        ));
+    }
+    Ok(fixes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use bevy::math::{I64Vec3, U16Vec3};
+
+    use super::{inspect_world, repair_world, Problem};
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        mob::registry::{MobDefinition, MobRegistry},
+        save::{SavedEntities, SavedGravestone, SavedPet, TestSaves, WorldInfo},
+    };
+
+    #[test]
+    fn test_inspect_and_repair_world() {
+        let _saves = TestSaves::new();
+        let world_info = WorldInfo::new("inspect test", 1);
+        let mut chunk = ChunkData::default();
+        chunk.set_block_at(U16Vec3::new(1, 2, 3), BlockType::Gravestone);
+        chunk.set_block_at(U16Vec3::ZERO, BlockType::Stone);
+        for (x, y, z) in [(0, 0, 0), (40, 0, 0), (-1, 0, 0)] {
+            let coord = ChunkCoordinate(I64Vec3::new(x, y, z));
+            world_info.save_chunk(coord, &chunk).unwrap();
+        }
+        let corrupt = world_info.chunk_path(ChunkCoordinate(I64Vec3::new(5, 5, 5)));
+        fs::write(&corrupt, b"not a chunk").unwrap();
+        let stray = corrupt.with_file_name("notes.txt");
+        fs::write(&stray, b"").unwrap();
+
+        let pet = |mob: &str| SavedPet {
+            mob: mob.to_string(),
+            position: [0.0; 3],
+            health: 1.0,
+            staying: false,
+        };
+        let entities = SavedEntities {
+            pets: vec![pet("dragon"), pet("wolf")],
+        };
+        world_info.save_entities(&entities).unwrap();
+        let gravestone = |position| SavedGravestone {
+            position,
+            items: vec![],
+        };
+        world_info
+            .save_gravestones(&[gravestone([1, 2, 3]), gravestone([1, 2, -13])])
+            .unwrap();
+
+        let mut mobs = MobRegistry::default();
+        mobs.register(
+            MobDefinition::parse(
+                r#"(name: "wolf", health: 1.0, speed: 1.0, size: (1.0, 1.0, 1.0))"#,
+            )
+            .unwrap(),
+        );
+        let report = inspect_world(&world_info, &mobs).unwrap();
+        assert_eq!(
+            vec![([-1, 0, 0], 1), ([0, 0, 0], 1), ([1, 0, 0], 1)],
+            report.regions.into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(Some(&3), report.blocks.get(BlockType::Stone.name()));
+        assert_eq!(Some(&3), report.blocks.get(BlockType::Gravestone.name()));
+        assert_eq!(3, report.largest.len());
+        assert_eq!(4, report.problems.len());
+        assert!(report.problems.iter().any(
+            |problem| matches!(problem, Problem::CorruptChunk { path, .. } if *path == corrupt)
+        ));
+        assert!(report.problems.contains(&Problem::StrayFile(stray)));
+        assert!(report.problems.contains(&Problem::UnknownPet {
+            index: 0,
+            mob: "dragon".to_string()
+        }));
+        assert!(report.problems.contains(&Problem::OrphanedGravestone {
+            index: 1,
+            position: [1, 2, -13]
+        }));
+
+        assert_eq!(
+            4,
+            repair_world(&world_info, &report.problems).unwrap().len()
+        );
+        assert_eq!(
+            Vec::<Problem>::new(),
+            inspect_world(&world_info, &mobs).unwrap().problems
+        );
+        assert_eq!(vec![pet("wolf")], world_info.load_entities().unwrap().pets);
+        assert_eq!(
+            vec![gravestone([1, 2, 3])],
+            world_info.load_gravestones().unwrap()
+        );
+    }
+}
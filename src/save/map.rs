@@ -0,0 +1,351 @@
+//! Renders a world's explored chunk columns into a pyramid of map tiles for viewing in a
+//! browser, run offline by `rustcraft-save-tool map`.
+//!
+//! Saves only hold edited chunks, so explored columns are taken to be those with a saved chunk
+//! and those around the origin where players spawn. Chunks that weren't saved are generated
+//! again from the world's seed.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    error::Error,
+    fs, io,
+    path::Path,
+    sync::Arc,
+};
+
+use bevy::{
+    color::ColorToPacked,
+    image::Image,
+    math::{I64Vec2, I64Vec3, U16Vec3},
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+
+use super::{chunk_file_coordinate, WorldInfo};
+use crate::{
+    block::BlockType,
+    chunks::{
+        chunk::{ChunkCoordinate, ChunkData},
+        generate::terrain::{ChunkGenerator, GeneratorRegistry},
+    },
+    world::World,
+};
+
+/// Width of a tile in pixels.
+pub const TILE_SIZE: i64 = 256;
+/// Zoom levels beneath the most detailed, where a pixel is a block. Each halves the detail of the
+/// one above, so at zoom 0 a tile covers `TILE_SIZE << MAX_ZOOM` blocks.
+pub const MAX_ZOOM: u32 = 4;
+/// Chunks around the origin counted as explored, as players spawn there.
+const SPAWN_RADIUS: i64 = 8;
+/// Blocks above the generated surface searched for trees.
+const TREE_HEIGHT: i64 = 16;
+const VIEWER: &str = include_str!("map.html");
+
+type Pixels = Vec<[u8; 4]>;
+
+#[derive(Debug, Default)]
+pub struct MapSummary {
+    pub columns: usize,
+    pub tiles: usize,
+}
+
+/// The blocks of a world's columns, read from its save where a chunk was saved and generated
+/// otherwise. Chunks are kept once read, so columns should be visited tile by tile.
+struct MapSource<'a> {
+    world_info: &'a WorldInfo,
+    generator: Arc<dyn ChunkGenerator>,
+    /// Heights of the saved chunks in each chunk column.
+    saved: HashMap<I64Vec2, BTreeSet<i64>>,
+    chunks: HashMap<ChunkCoordinate, Arc<ChunkData>>,
+}
+
+impl MapSource<'_> {
+    fn chunk(&mut self, coord: ChunkCoordinate) -> Result<Arc<ChunkData>, Box<dyn Error>> {
+        if let Some(chunk) = self.chunks.get(&coord) {
+            return Ok(chunk.clone());
+        }
+        let saved = match self.saved.get(&I64Vec2::new(coord.0.x, coord.0.z)) {
+            Some(ys) if ys.contains(&coord.0.y) => self.world_info.load_chunk(coord)?,
+            _ => None,
+        };
+        let chunk = Arc::new(saved.unwrap_or_else(|| self.generator.generate(coord)));
+        self.chunks.insert(coord, chunk.clone());
+        Ok(chunk)
+    }
+
+    /// The highest block in a column and its height, or `None` if the column is empty.
+    fn top_block(&mut self, column: I64Vec2) -> Result<Option<(BlockType, i64)>, Box<dyn Error>> {
+        let size = self.world_info.chunk_size as i64;
+        let chunk_column = column.div_euclid(I64Vec2::splat(size));
+        let surface = self.generator.surface(column).map(|s| s.height as i64);
+        let saved = self.saved.get(&chunk_column).and_then(|ys| ys.last());
+        let top = match (surface, saved) {
+            (Some(surface), saved) => {
+                ((surface + TREE_HEIGHT).div_euclid(size)).max(*saved.unwrap_or(&0))
+            }
+            (None, Some(saved)) => *saved,
+            (None, None) => return Ok(None),
+        };
+
+        let local = column.rem_euclid(I64Vec2::splat(size));
+        for chunk_y in (0..=top).rev() {
+            let coord = ChunkCoordinate(I64Vec3::new(chunk_column.x, chunk_y, chunk_column.y));
+            let chunk = self.chunk(coord)?;
+            if chunk.empty() {
+                continue;
+            }
+            for y in (0..size).rev() {
+                let block =
+                    chunk.get_block_at(U16Vec3::new(local.x as u16, y as u16, local.y as u16));
+                if block != BlockType::Air {
+                    return Ok(Some((block, chunk_y * size + y)));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// The colour of a column's top block, lighter where it's higher than the column to its north
+/// and darker where it's lower, so hills stand out.
+pub fn shade(block: BlockType, height: i64, north_height: Option<i64>) -> [u8; 4] {
+    let factor = match north_height {
+        Some(north) if height > north => 1.15,
+        Some(north) if height < north => 0.8,
+        _ => 1.0,
+    };
+    let [r, g, b, _] = block.color().to_srgba().to_u8_array();
+    let [r, g, b] = [r, g, b].map(|channel| (channel as f32 * factor).min(255.0) as u8);
+    [r, g, b, 255]
+}
+
+/// Draws the most detailed tile at `tile`, leaving columns that weren't explored transparent.
+fn render_tile(
+    source: &mut MapSource,
+    explored: &HashSet<I64Vec2>,
+    tile: I64Vec2,
+) -> Result<(Pixels, usize), Box<dyn Error>> {
+    let size = source.world_info.chunk_size as i64;
+    let origin = tile * TILE_SIZE;
+    let mut pixels = vec![[0; 4]; (TILE_SIZE * TILE_SIZE) as usize];
+    let mut columns = 0;
+    let mut north = vec![None; TILE_SIZE as usize];
+    for z in 0..TILE_SIZE {
+        let mut row = Vec::with_capacity(TILE_SIZE as usize);
+        for x in 0..TILE_SIZE {
+            let column = origin + I64Vec2::new(x, z);
+            if !explored.contains(&column.div_euclid(I64Vec2::splat(size))) {
+                row.push(None);
+                continue;
+            }
+            let top = source.top_block(column)?;
+            if let Some((block, height)) = top {
+                // columns north of the explored area are only read to shade its edge
+                let north = match north[x as usize] {
+                    Some(top) => Some(top),
+                    None => source.top_block(column - I64Vec2::Y)?,
+                };
+                let north = north.map(|(_, height)| height);
+                pixels[(z * TILE_SIZE + x) as usize] = shade(block, height, north);
+                columns += 1;
+            }
+            row.push(top);
+        }
+        north = row;
+    }
+    Ok((pixels, columns))
+}
+
+/// Halves the detail of four tiles, ordered top left, top right, bottom left, bottom right, into
+/// one. Transparent pixels are left out of the average, so explored edges don't darken.
+fn merge_tiles(children: [Option<&Pixels>; 4]) -> Pixels {
+    let half = TILE_SIZE / 2;
+    let mut pixels = vec![[0; 4]; (TILE_SIZE * TILE_SIZE) as usize];
+    for (i, child) in children.into_iter().enumerate() {
+        let Some(child) = child else {
+            continue;
+        };
+        let (offset_x, offset_z) = ((i as i64 % 2) * half, (i as i64 / 2) * half);
+        for z in 0..half {
+            for x in 0..half {
+                let samples: Vec<[u8; 4]> = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .into_iter()
+                    .map(|(dx, dz)| child[((z * 2 + dz) * TILE_SIZE + x * 2 + dx) as usize])
+                    .filter(|pixel| pixel[3] != 0)
+                    .collect();
+                if samples.is_empty() {
+                    continue;
+                }
+                let count = samples.len() as u32;
+                let average: [u8; 4] = std::array::from_fn(|channel| {
+                    let sum: u32 = samples.iter().map(|pixel| pixel[channel] as u32).sum();
+                    ((sum + count / 2) / count) as u8
+                });
+                pixels[((offset_z + z) * TILE_SIZE + offset_x + x) as usize] = average;
+            }
+        }
+    }
+    pixels
+}
+
+fn write_tile(out: &Path, zoom: u32, tile: I64Vec2, pixels: &Pixels) -> Result<(), Box<dyn Error>> {
+    let dir = out.join(zoom.to_string()).join(tile.x.to_string());
+    fs::create_dir_all(&dir)?;
+    let image = Image::new(
+        Extent3d {
+            width: TILE_SIZE as u32,
+            height: TILE_SIZE as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        pixels.iter().flatten().copied().collect(),
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::MAIN_WORLD,
+    );
+    image
+        .try_into_dynamic()?
+        .save(dir.join(format!("{}.png", tile.y)))?;
+    Ok(())
+}
+
+/// Chunk columns holding saved chunks, and the heights of those chunks.
+fn saved_columns(world_info: &WorldInfo) -> io::Result<HashMap<I64Vec2, BTreeSet<i64>>> {
+    let entries = match fs::read_dir(world_info.chunks_dir()) {
+        Ok(entries) => entries.collect::<Result<Vec<_>, _>>()?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(e),
+    };
+    let mut saved: HashMap<I64Vec2, BTreeSet<i64>> = HashMap::new();
+    for coord in entries
+        .iter()
+        .filter_map(|entry| chunk_file_coordinate(&entry.path()))
+    {
+        saved
+            .entry(I64Vec2::new(coord.0.x, coord.0.z))
+            .or_default()
+            .insert(coord.0.y);
+    }
+    Ok(saved)
+}
+
+/// Writes the world's map to `out` as `{zoom}/{x}/{y}.png` tiles, zoom `MAX_ZOOM` drawing a
+/// block per pixel, with an `index.html` to view them.
+pub fn render_map(world_info: &WorldInfo, out: &Path) -> Result<MapSummary, Box<dyn Error>> {
+    let world = World::with_chunk_size(world_info.seed, world_info.chunk_size)
+        .with_generator(world_info.generator.clone(), &GeneratorRegistry::default())?;
+    let saved = saved_columns(world_info)?;
+    let mut explored: HashSet<I64Vec2> = saved.keys().copied().collect();
+    for x in -SPAWN_RADIUS..=SPAWN_RADIUS {
+        for z in -SPAWN_RADIUS..=SPAWN_RADIUS {
+            explored.insert(I64Vec2::new(x, z));
+        }
+    }
+
+    let chunk_size = world_info.chunk_size as i64;
+    let mut tiles: Vec<I64Vec2> = explored
+        .iter()
+        .map(|column| (column * chunk_size).div_euclid(I64Vec2::splat(TILE_SIZE)))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    tiles.sort_by_key(|tile| tile.to_array());
+
+    let mut source = MapSource {
+        world_info,
+        generator: world.generator(),
+        saved,
+        chunks: HashMap::new(),
+    };
+    let mut summary = MapSummary::default();
+    let mut level = HashMap::new();
+    for tile in tiles {
+        let (pixels, columns) = render_tile(&mut source, &explored, tile)?;
+        // tiles are rendered in order of x, so chunks left of this tile aren't needed again
+        source
+            .chunks
+            .retain(|coord, _| coord.0.x * chunk_size >= tile.x * TILE_SIZE);
+        write_tile(out, MAX_ZOOM, tile, &pixels)?;
+        summary.columns += columns;
+        level.insert(tile, pixels);
+    }
+    summary.tiles += level.len();
+
+    for zoom in (0..MAX_ZOOM).rev() {
+        let parents: HashSet<I64Vec2> = level
+            .keys()
+            .map(|tile| tile.div_euclid(I64Vec2::splat(2)))
+            .collect();
+        let mut next = HashMap::new();
+        for parent in parents {
+            let children = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                .map(|(x, z)| level.get(&(parent * 2 + I64Vec2::new(x, z))));
+            let pixels = merge_tiles(children);
+            write_tile(out, zoom, parent, &pixels)?;
+            next.insert(parent, pixels);
+        }
+        summary.tiles += next.len();
+        level = next;
+    }
+
+    fs::write(
+        out.join("index.html"),
+        VIEWER
+            .replace("{name}", &world_info.name)
+            .replace("{max_zoom}", &MAX_ZOOM.to_string()),
+    )?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use bevy::math::{I64Vec3, U16Vec3};
+
+    use super::{render_map, shade, MAX_ZOOM};
+    use crate::{
+        block::BlockType,
+        chunks::{
+            chunk::{ChunkCoordinate, ChunkData},
+            generate::terrain::GeneratorKind,
+        },
+        save::{TestSaves, WorldInfo},
+    };
+
+    #[test]
+    fn test_shade_by_north_neighbour() {
+        let grass = shade(BlockType::Grass, 40, Some(40));
+        assert_eq!(grass, shade(BlockType::Grass, 40, None));
+        assert_eq!(255, grass[3]);
+        assert!(shade(BlockType::Grass, 41, Some(40))[1] > grass[1]);
+        assert!(shade(BlockType::Grass, 39, Some(40))[1] < grass[1]);
+    }
+
+    #[test]
+    fn test_render_map() {
+        let _saves = TestSaves::new();
+        let world_info = WorldInfo::new("map test", 1).with_generator(GeneratorKind::Superflat);
+        // a pillar on a saved chunk far from spawn, in a tile of its own
+        let mut chunk = ChunkData::default();
+        chunk.set_block_at(U16Vec3::new(0, 15, 0), BlockType::Stone);
+        world_info
+            .save_chunk(ChunkCoordinate(I64Vec3::new(40, 2, 0)), &chunk)
+            .unwrap();
+        let out = world_info.dir().join("map");
+
+        let summary = render_map(&world_info, &out).unwrap();
+        // spawn's 17x17 chunks and the saved one
+        assert_eq!((17 * 17 + 1) * 16 * 16, summary.columns);
+        let written = |path: &str| out.join(path).is_file();
+        assert!(written("index.html"));
+        assert!(written(&format!("{MAX_ZOOM}/2/0.png")));
+        assert!(written(&format!("{MAX_ZOOM}/-1/-1.png")));
+        assert!(written("0/0/0.png"));
+        assert!(written("0/-1/-1.png"));
+        let viewer = fs::read_to_string(out.join("index.html")).unwrap();
+        assert!(viewer.contains(&world_info.name));
+    }
+}
@@ -0,0 +1,170 @@
+//! Rewrites a world's saved chunks with the block ids of the running version, so its `IdMap`
+//! can be reset and blocks that no longer exist are replaced. Run with `rustcraft-server
+//! --upgrade-world`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fs, io,
+};
+
+use super::{WorldInfo, CHUNKS_DIR};
+use crate::{
+    block::BlockType,
+    chunks::codec::{decode_chunk_mapped, encode_chunk},
+    ids::IdMap,
+};
+
+/// Where upgraded chunks are written before they replace the originals.
+const UPGRADED_DIR: &str = "chunks.upgraded";
+/// Where the original chunks are kept until the upgraded ones and the new ids are saved.
+const ORIGINAL_DIR: &str = "chunks.original";
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct UpgradeReport {
+    pub chunks: usize,
+    /// Keys of saved blocks this version doesn't have, with the number of chunks holding them.
+    pub unknown: BTreeMap<String, usize>,
+}
+
+/// Re-encodes every saved chunk of the world with `IdMap::default`, replacing blocks it no
+/// longer has with `fallback`, then saves the world with those ids.
+///
+/// Nothing is changed until every chunk has been converted. If the upgrade stops between
+/// swapping the chunk folders and saving the world, the originals are left in `ORIGINAL_DIR`.
+pub fn upgrade_world(
+    world_info: &mut WorldInfo,
+    fallback: BlockType,
+) -> Result<UpgradeReport, Box<dyn Error>> {
+    let dir = world_info.dir();
+    let chunks_dir = dir.join(CHUNKS_DIR);
+    let upgraded_dir = dir.join(UPGRADED_DIR);
+    let original_dir = dir.join(ORIGINAL_DIR);
+    if original_dir.exists() {
+        return Err(format!(
+            "{} is left from an interrupted upgrade, restore it as {} first",
+            original_dir.display(),
+            chunks_dir.display()
+        )
+        .into());
+    }
+
+    let ids = IdMap::default();
+    let mut report = UpgradeReport::default();
+    let entries = match fs::read_dir(&chunks_dir) {
+        Ok(entries) => entries.collect::<Result<Vec<_>, _>>()?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(e.into()),
+    };
+
+    if upgraded_dir.exists() {
+        fs::remove_dir_all(&upgraded_dir)?;
+    }
+    fs::create_dir_all(&upgraded_dir)?;
+    for entry in entries {
+        let path = entry.path();
+        if path
+            .extension()
+            .is_none_or(|extension| extension != "chunk")
+        {
+            continue;
+        }
+
+        let mut unknown = BTreeSet::new();
+        let chunk = decode_chunk_mapped(&fs::read(&path)?, |id| {
+            world_info.ids.block(id).or_else(|| {
+                let key = world_info.ids.block_key(id);
+                unknown.insert(key.map_or_else(|| format!("#{id}"), str::to_string));
+                Some(fallback)
+            })
+        })
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+        for key in unknown {
+            *report.unknown.entry(key).or_default() += 1;
+        }
+
+        fs::write(
+            upgraded_dir.join(entry.file_name()),
+            encode_chunk(&chunk, &ids),
+        )?;
+        report.chunks += 1;
+    }
+
+    if chunks_dir.exists() {
+        fs::rename(&chunks_dir, &original_dir)?;
+    }
+    fs::rename(&upgraded_dir, &chunks_dir)?;
+    world_info.ids = ids;
+    world_info.save()?;
+    if original_dir.exists() {
+        fs::remove_dir_all(&original_dir)?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, fs};
+
+    use bevy::math::{I64Vec3, U16Vec3};
+
+    use super::{upgrade_world, UpgradeReport};
+    use crate::{
+        block::BlockType,
+        chunks::{
+            chunk::{ChunkCoordinate, ChunkData},
+            codec::encode_chunk,
+        },
+        ids::IdMap,
+        save::{TestSaves, WorldInfo},
+    };
+
+    #[test]
+    fn test_upgrade_world() {
+        let _saves = TestSaves::new();
+        let mut world_info = WorldInfo::new("upgrade test", 1);
+        // an older version that numbered stone 2 and had a block since removed
+        world_info.ids = ron::from_str(
+            r#"(
+                blocks: ["rustcraft:air", "rustcraft:marble", "rustcraft:stone"],
+                items: [],
+            )"#,
+        )
+        .unwrap();
+        world_info.save().unwrap();
+
+        let mut chunk = ChunkData::default();
+        chunk.set_block_at(U16Vec3::ZERO, BlockType::Stone);
+        chunk.set_block_at(U16Vec3::X, BlockType::Grass);
+        let coord = ChunkCoordinate(I64Vec3::new(1, 2, 3));
+        fs::create_dir_all(world_info.chunk_path(coord).parent().unwrap()).unwrap();
+        // saved with grass where marble was
+        let mut old_ids = world_info.ids.clone();
+        old_ids.register_missing();
+        let mut encoded = zstd::decode_all(encode_chunk(&chunk, &old_ids).as_slice()).unwrap();
+        let grass = old_ids.block_id(BlockType::Grass).unwrap();
+        let palette_len = encoded[3] as usize;
+        let palette = &mut encoded[4..4 + palette_len];
+        *palette.iter_mut().find(|id| **id == grass).unwrap() = 1;
+        fs::write(
+            world_info.chunk_path(coord),
+            zstd::encode_all(encoded.as_slice(), 3).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            UpgradeReport {
+                chunks: 1,
+                unknown: BTreeMap::from([("rustcraft:marble".to_string(), 1)]),
+            },
+            upgrade_world(&mut world_info, BlockType::Sand).unwrap()
+        );
+        assert_eq!(
+            IdMap::default(),
+            WorldInfo::load(&world_info.dir()).unwrap().ids
+        );
+        let upgraded = world_info.load_chunk(coord).unwrap().unwrap();
+        assert_eq!(BlockType::Stone, upgraded.get_block_at(U16Vec3::ZERO));
+        assert_eq!(BlockType::Sand, upgraded.get_block_at(U16Vec3::X));
+    }
+}
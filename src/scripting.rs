@@ -0,0 +1,483 @@
+//! Rhai scripts loaded from `scripts/`, for prototyping gameplay without recompiling. Only built
+//! with the `scripting` feature.
+//!
+//! A script defines callbacks: `on_tick()`, run every gameplay tick, and
+//! `on_block_break(x, y, z, block)`, run when the player breaks a block. Callbacks can call
+//! `get_block(x, y, z)`, `set_block(x, y, z, block)`, `player_position()` and
+//! `raycast(origin, direction, max_distance)`. Blocks are given by name and positions as arrays
+//! of three numbers.
+
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    app::{App, FixedUpdate, Plugin, Startup, Update},
+    ecs::{
+        event::{EventReader, EventWriter},
+        query::{QueryState, With},
+        schedule::IntoSystemConfigs,
+        system::{Commands, In, Res, ResMut, Resource, SystemState},
+        world::Mut,
+    },
+    log::{info, warn},
+    math::{I64Vec3, Vec3},
+    state::condition::in_state,
+    transform::components::Transform,
+};
+use rhai::{Array, CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST, INT};
+
+use crate::{
+    block::BlockType,
+    chunks::chunk_loader::ChunkLoader,
+    command::{CommandAppExt, CommandArgs, CommandResult},
+    interaction::{BlockEdited, EditSource},
+    item::BlockBroken,
+    physics::raycast,
+    player::Player,
+    state::GameState,
+    world::World,
+};
+
+const SCRIPTS_DIR: &str = "scripts";
+/// Operations a single callback may run, so a runaway loop stops with an error rather than
+/// hanging the game.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+pub struct ScriptingPlugin;
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ScriptHost::new())
+            .add_console_command(
+                "scripts",
+                "scripts",
+                "reloads the scripts in scripts/",
+                scripts_command,
+            )
+            .add_systems(Startup, load_scripts)
+            .add_systems(
+                Update,
+                run_block_break_scripts.run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(
+                FixedUpdate,
+                run_tick_scripts.run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// A callback scripts can define.
+#[derive(Debug, Clone, Copy)]
+enum Callback {
+    Tick,
+    BlockBreak(BlockBroken),
+}
+
+impl Callback {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Tick => "on_tick",
+            Self::BlockBreak(_) => "on_block_break",
+        }
+    }
+
+    fn args(&self) -> Vec<Dynamic> {
+        match self {
+            Self::Tick => vec![],
+            Self::BlockBreak(BlockBroken { position, block }) => vec![
+                Dynamic::from(position.x as INT),
+                Dynamic::from(position.y as INT),
+                Dynamic::from(position.z as INT),
+                Dynamic::from(block.name().to_string()),
+            ],
+        }
+    }
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+}
+
+/// What the functions given to scripts can see while a callback runs. The world is moved in for
+/// the length of the callbacks and taken back afterwards.
+#[derive(Default)]
+struct ScriptContext {
+    world: Option<World>,
+    player: Option<Vec3>,
+    edited: Vec<BlockEdited>,
+}
+
+impl ScriptContext {
+    fn world(&mut self) -> Result<&mut World, Box<EvalAltResult>> {
+        self.world
+            .as_mut()
+            .ok_or_else(|| "the world can only be used from callbacks".into())
+    }
+}
+
+#[derive(Resource)]
+pub struct ScriptHost {
+    engine: Engine,
+    scripts: Vec<Script>,
+    context: Arc<Mutex<ScriptContext>>,
+}
+
+impl ScriptHost {
+    fn new() -> Self {
+        let context = Arc::new(Mutex::new(ScriptContext::default()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.on_print(|text| info!("script: {}", text));
+        register_functions(&mut engine, &context);
+        Self {
+            engine,
+            scripts: vec![],
+            context,
+        }
+    }
+
+    /// Compiles `source` and adds it to the scripts callbacks are run in.
+    fn add(&mut self, name: &str, source: &str) -> Result<(), String> {
+        let ast = self.engine.compile(source).map_err(|e| e.to_string())?;
+        self.scripts.push(Script {
+            name: name.to_string(),
+            ast,
+        });
+        Ok(())
+    }
+
+    /// Replaces the loaded scripts with every `.rhai` file in `dir`. Returns how many were
+    /// loaded, and an error for each that wasn't.
+    fn load(&mut self, dir: &Path) -> (usize, Vec<String>) {
+        self.scripts.clear();
+        let Ok(entries) = fs::read_dir(dir) else {
+            return (0, vec![]);
+        };
+
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "rhai")
+            })
+            .collect();
+        paths.sort();
+        let mut errors = vec![];
+        for path in paths {
+            let result = fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|source| self.add(&path.display().to_string(), &source));
+            if let Err(e) = result {
+                errors.push(format!("{}: {}", path.display(), e));
+            }
+        }
+        (self.scripts.len(), errors)
+    }
+
+    /// Runs `callbacks` in every script defining them, returning the world and the blocks the
+    /// scripts changed.
+    fn run(
+        &self,
+        world: World,
+        player: Option<Vec3>,
+        callbacks: &[Callback],
+    ) -> (World, Vec<BlockEdited>) {
+        {
+            let mut context = self.context.lock().unwrap();
+            context.world = Some(world);
+            context.player = player;
+        }
+
+        for callback in callbacks {
+            let (name, args) = (callback.name(), callback.args());
+            for script in &self.scripts {
+                let defined = script
+                    .ast
+                    .iter_functions()
+                    .any(|function| function.name == name && function.params.len() == args.len());
+                if !defined {
+                    continue;
+                }
+
+                let options = CallFnOptions::new().eval_ast(false);
+                let result = self.engine.call_fn_with_options::<Dynamic>(
+                    options,
+                    &mut Scope::new(),
+                    &script.ast,
+                    name,
+                    args.clone(),
+                );
+                if let Err(e) = result {
+                    warn!("{} failed in {}: {}", name, script.name, e);
+                }
+            }
+        }
+
+        let mut context = self.context.lock().unwrap();
+        let world = context
+            .world
+            .take()
+            .expect("callbacks can't remove the world");
+        (world, std::mem::take(&mut context.edited))
+    }
+}
+
+fn register_functions(engine: &mut Engine, context: &Arc<Mutex<ScriptContext>>) {
+    let shared = context.clone();
+    engine.register_fn(
+        "get_block",
+        move |x: INT, y: INT, z: INT| -> Result<String, Box<EvalAltResult>> {
+            let mut context = shared.lock().unwrap();
+            let block = context.world()?.get_block(I64Vec3::new(x, y, z));
+            Ok(block.name().to_string())
+        },
+    );
+
+    let shared = context.clone();
+    engine.register_fn(
+        "set_block",
+        move |x: INT, y: INT, z: INT, name: &str| -> Result<bool, Box<EvalAltResult>> {
+            let block =
+                BlockType::from_name(name).ok_or_else(|| format!("unknown block {name}"))?;
+            let position = I64Vec3::new(x, y, z);
+            let mut context = shared.lock().unwrap();
+            let changed = context.world()?.set_block(position, block);
+            if changed {
+                context.edited.push(BlockEdited {
+                    position,
+                    block,
+                    source: EditSource::Script,
+                });
+            }
+            Ok(changed)
+        },
+    );
+
+    let shared = context.clone();
+    engine.register_fn("player_position", move || -> Dynamic {
+        let player = shared.lock().unwrap().player;
+        player.map_or(Dynamic::UNIT, |position| {
+            Dynamic::from_array(
+                position
+                    .to_array()
+                    .map(|c| Dynamic::from(c as f64))
+                    .to_vec(),
+            )
+        })
+    });
+
+    let shared = context.clone();
+    engine.register_fn(
+        "raycast",
+        move |origin: Array,
+              direction: Array,
+              max_distance: Dynamic|
+              -> Result<Dynamic, Box<EvalAltResult>> {
+            let (origin, direction) = (to_vec3(&origin)?, to_vec3(&direction)?);
+            let max_distance = to_number(&max_distance)? as f32;
+            let mut context = shared.lock().unwrap();
+            let world = context.world()?;
+            let Some(hit) = raycast(world, origin, direction, max_distance) else {
+                return Ok(Dynamic::UNIT);
+            };
+
+            let to_array = |position: I64Vec3| {
+                Dynamic::from_array(position.to_array().map(Dynamic::from).to_vec())
+            };
+            let mut map = Map::new();
+            map.insert("position".into(), to_array(hit.block));
+            map.insert("normal".into(), to_array(hit.normal));
+            map.insert("distance".into(), Dynamic::from(hit.distance as f64));
+            let block = world.get_block(hit.block);
+            map.insert("block".into(), Dynamic::from(block.name().to_string()));
+            Ok(Dynamic::from_map(map))
+        },
+    );
+}
+
+fn to_number(value: &Dynamic) -> Result<f64, Box<EvalAltResult>> {
+    value
+        .as_float()
+        .or_else(|_| value.as_int().map(|value| value as f64))
+        .map_err(|_| "expected a number".into())
+}
+
+fn to_vec3(array: &Array) -> Result<Vec3, Box<EvalAltResult>> {
+    let components = array.iter().map(to_number).collect::<Result<Vec<_>, _>>()?;
+    match components[..] {
+        [x, y, z] => Ok(Vec3::new(x as f32, y as f32, z as f32)),
+        _ => Err("expected an array of three numbers".into()),
+    }
+}
+
+fn load_scripts(mut host: ResMut<ScriptHost>) {
+    let (loaded, errors) = host.load(Path::new(SCRIPTS_DIR));
+    for error in errors {
+        warn!("failed to load script {}", error);
+    }
+    if loaded > 0 {
+        info!("loaded {} scripts from {}", loaded, SCRIPTS_DIR);
+    }
+}
+
+fn scripts_command(In(args): In<CommandArgs>, mut host: ResMut<ScriptHost>) -> CommandResult {
+    args.finish()?;
+    let (loaded, errors) = host.load(Path::new(SCRIPTS_DIR));
+    let mut lines = vec![format!("loaded {} scripts from {}", loaded, SCRIPTS_DIR)];
+    lines.extend(errors);
+    Ok(lines.join("\n"))
+}
+
+type EditParams<'w, 's> = (
+    Commands<'w, 's>,
+    Res<'w, World>,
+    Option<Res<'w, ChunkLoader>>,
+    EventWriter<'w, BlockEdited>,
+);
+
+/// Runs callbacks with the world moved out of the ECS, then remeshes and announces whatever the
+/// scripts changed.
+fn run_callbacks(
+    ecs: &mut bevy::ecs::world::World,
+    player_query: &mut QueryState<&Transform, With<Player>>,
+    edit_params: &mut SystemState<EditParams<'static, 'static>>,
+    callbacks: &[Callback],
+) {
+    if ecs.resource::<ScriptHost>().scripts.is_empty() {
+        return;
+    }
+    let Some(world) = ecs.remove_resource::<World>() else {
+        return;
+    };
+
+    let player = player_query
+        .get_single(ecs)
+        .ok()
+        .map(|transform| transform.translation);
+    let (world, edited) =
+        ecs.resource_scope(|_, host: Mut<ScriptHost>| host.run(world, player, callbacks));
+    ecs.insert_resource(world);
+    if edited.is_empty() {
+        return;
+    }
+
+    let (mut commands, world, chunk_loader, mut events) = edit_params.get_mut(ecs);
+    if let Some(chunk_loader) = chunk_loader {
+        for edit in &edited {
+            chunk_loader.remesh_block(&mut commands, &world, edit.position);
+        }
+    }
+    events.send_batch(edited);
+    edit_params.apply(ecs);
+}
+
+fn run_tick_scripts(
+    ecs: &mut bevy::ecs::world::World,
+    player_query: &mut QueryState<&Transform, With<Player>>,
+    edit_params: &mut SystemState<EditParams<'static, 'static>>,
+) {
+    run_callbacks(ecs, player_query, edit_params, &[Callback::Tick]);
+}
+
+fn run_block_break_scripts(
+    ecs: &mut bevy::ecs::world::World,
+    broken: &mut SystemState<EventReader<'static, 'static, BlockBroken>>,
+    player_query: &mut QueryState<&Transform, With<Player>>,
+    edit_params: &mut SystemState<EditParams<'static, 'static>>,
+) {
+    let callbacks: Vec<Callback> = broken
+        .get_mut(ecs)
+        .read()
+        .map(|broken| Callback::BlockBreak(*broken))
+        .collect();
+    if !callbacks.is_empty() {
+        run_callbacks(ecs, player_query, edit_params, &callbacks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec3, U16Vec3, Vec3};
+
+    use super::{Callback, ScriptHost};
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        interaction::{BlockEdited, EditSource},
+        item::BlockBroken,
+        world::World,
+    };
+
+    fn world() -> World {
+        let mut world = World::new(1);
+        let mut chunk = ChunkData::default();
+        chunk.set_block_at(U16Vec3::new(2, 5, 2), BlockType::Stone);
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), chunk);
+        world
+    }
+
+    #[test]
+    fn test_block_break_callback() {
+        let mut host = ScriptHost::new();
+        host.add(
+            "test",
+            r#"
+                fn on_block_break(x, y, z, block) {
+                    if block == "sand" && get_block(x, y + 1, z) == "air" {
+                        set_block(x, y + 1, z, "glass");
+                    }
+                }
+            "#,
+        )
+        .unwrap();
+
+        let broken = BlockBroken {
+            position: I64Vec3::new(1, 1, 1),
+            block: BlockType::Sand,
+        };
+        let (mut world, edited) = host.run(world(), None, &[Callback::BlockBreak(broken)]);
+        assert_eq!(BlockType::Glass, world.get_block(I64Vec3::new(1, 2, 1)));
+        assert_eq!(
+            vec![BlockEdited {
+                position: I64Vec3::new(1, 2, 1),
+                block: BlockType::Glass,
+                source: EditSource::Script,
+            }],
+            edited
+        );
+
+        // callbacks without a definition, or failing, leave the world alone
+        let (mut world, edited) = host.run(world, None, &[Callback::Tick]);
+        assert!(edited.is_empty());
+        assert_eq!(BlockType::Glass, world.get_block(I64Vec3::new(1, 2, 1)));
+    }
+
+    #[test]
+    fn test_raycast_and_player_position() {
+        let mut host = ScriptHost::new();
+        host.add(
+            "test",
+            r#"
+                fn on_tick() {
+                    let hit = raycast(player_position(), [0, 1, 0], 10);
+                    if hit != () && hit.block == "stone" {
+                        let p = hit.position;
+                        set_block(p[0], p[1] + hit.normal[1] - 2, p[2], "gold");
+                    }
+                }
+            "#,
+        )
+        .unwrap();
+
+        let player = Some(Vec3::new(2.0, 0.0, 2.0));
+        let (mut world, edited) = host.run(world(), player, &[Callback::Tick]);
+        assert_eq!(1, edited.len());
+        assert_eq!(BlockType::Gold, world.get_block(I64Vec3::new(2, 2, 2)));
+
+        assert!(host.add("broken", "fn on_tick( {").is_err());
+    }
+}
@@ -1,18 +1,304 @@
-use bevy::ecs::component::Component;
+use std::{error::Error, net::SocketAddr};
+
+use bevy::{ecs::component::Component, log::warn};
 use serde::Deserialize;
 
-#[derive(Default, Deserialize, Clone, Copy, Component)]
+use crate::{
+    chunks::{
+        chunk::{is_valid_chunk_size, CHUNK_SIZE},
+        generate::{generator::Lighting, ore::OreSettings, terrain::GeneratorKind},
+    },
+    daylight::ShadowQuality,
+    input::bindings::KeyBindings,
+    interaction::DEFAULT_REACH,
+    net::protocol::DEFAULT_PORT,
+};
+
+pub const SETTINGS_FILE: &str = "assets/settings.toml";
+
+pub fn read_settings(file: &str) -> Result<Settings, Box<dyn Error>> {
+    let settings_str = std::fs::read_to_string(file)?;
+    let settings = toml::from_str(&settings_str)?;
+    Ok(settings)
+}
+
+/// Reads the `[server]` section of a settings file. It's kept out of `Settings`, as only the
+/// dedicated server runs its tasks.
+pub fn read_server_settings(file: &str) -> Result<ServerSettings, Box<dyn Error>> {
+    #[derive(Deserialize)]
+    struct ServerFile {
+        #[serde(default)]
+        server: ServerSettings,
+    }
+
+    let settings_str = std::fs::read_to_string(file)?;
+    let file: ServerFile = toml::from_str(&settings_str)?;
+    Ok(file.server)
+}
+
+#[derive(Default, Deserialize, Clone, Component)]
 pub struct Settings {
     pub renderer: RendererSettings,
+    #[serde(default)]
+    pub graphics: GraphicsSettings,
+    #[serde(default)]
+    pub audio: AudioSettings,
+    #[serde(default)]
+    pub bindings: KeyBindings,
+    #[serde(default)]
+    pub world: WorldSettings,
+    #[serde(default)]
+    pub player: PlayerSettings,
+    #[serde(default)]
+    pub network: NetworkSettings,
+    #[serde(default)]
+    pub debug: DebugSettings,
 }
 
 #[derive(Deserialize, Clone, Copy)]
 pub struct RendererSettings {
     pub render_distance: u32,
+    #[serde(default)]
+    pub unloading: UnloadSettings,
+    #[serde(default)]
+    pub memory: MemorySettings,
 }
 
 impl Default for RendererSettings {
     fn default() -> Self {
-        Self { render_distance: 8 }
+        Self {
+            render_distance: 8,
+            unloading: UnloadSettings::default(),
+            memory: MemorySettings::default(),
+        }
+    }
+}
+
+/// When chunks that the camera has left behind are unloaded. Both keep chunks around while the
+/// player moves back and forth over a chunk border, rather than regenerating them every time.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct UnloadSettings {
+    /// Distance in chunks beyond the render distance before a chunk can be unloaded.
+    pub extra_distance: u32,
+    /// Seconds a chunk must stay beyond the unload distance before it's unloaded.
+    pub grace_period: f32,
+}
+
+/// How much generated chunk data is kept in memory before far away chunks are evicted, least
+/// recently used first. Edited chunks are saved to disk before they are evicted.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct MemorySettings {
+    /// Megabytes of chunk data to keep in memory.
+    pub chunk_budget_mb: u32,
+    /// Distance in chunks from the camera within which chunks are never evicted.
+    pub keep_distance: u32,
+}
+
+impl Default for MemorySettings {
+    fn default() -> Self {
+        Self {
+            chunk_budget_mb: 512,
+            keep_distance: 8,
+        }
+    }
+}
+
+impl MemorySettings {
+    pub fn chunk_budget_bytes(&self) -> usize {
+        self.chunk_budget_mb as usize * 1024 * 1024
+    }
+}
+
+impl Default for UnloadSettings {
+    fn default() -> Self {
+        Self {
+            extra_distance: 2,
+            grace_period: 5.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub color_grading: bool,
+    pub color_grading_strength: f32,
+    pub bloom: bool,
+    /// Probability of a foliage tuft on fully grassy terrain, `0.0` disables foliage.
+    pub foliage_density: f32,
+    /// Distance in chunks from the camera within which foliage is drawn.
+    pub foliage_distance: u32,
+    /// Draws blocks in flat colours lit per vertex, without textures or shadows, for weak GPUs.
+    pub low_spec: bool,
+    /// Whether faces are shaded smoothly into corners or lit evenly. Can be changed in game with
+    /// `/lighting`.
+    pub lighting: Lighting,
+    /// Draws the drifting cloud layer.
+    pub clouds: bool,
+    /// How sharp and far reaching the sun's shadows are, `off` disables them.
+    pub shadows: ShadowQuality,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            color_grading: true,
+            color_grading_strength: 1.0,
+            bloom: true,
+            foliage_density: 0.4,
+            foliage_distance: 4,
+            low_spec: false,
+            lighting: Lighting::default(),
+            clouds: true,
+            shadows: ShadowQuality::default(),
+        }
+    }
+}
+
+/// Volumes from `0.0` to `1.0`, which can be changed in game from the pause menu.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct AudioSettings {
+    /// Scales every other volume.
+    pub master: f32,
+    pub music: f32,
+    /// Wind, cave drips and other background loops.
+    pub ambient: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            music: 0.5,
+            ambient: 0.8,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct WorldSettings {
+    /// Width of chunks in blocks for newly created worlds, `16` or `32`. Larger chunks mesh
+    /// more blocks per task but remesh more blocks for every edit.
+    pub chunk_size: u16,
+    /// What newly created worlds' terrain is generated by, until another is picked in the menu.
+    pub generator: GeneratorKind,
+    pub ores: OreSettings,
+    /// Block edits remembered for each chunk so they can be undone with `/rollback`, `0` keeps
+    /// none.
+    pub edit_history: u32,
+}
+
+impl Default for WorldSettings {
+    fn default() -> Self {
+        Self {
+            chunk_size: CHUNK_SIZE,
+            generator: GeneratorKind::default(),
+            ores: OreSettings::default(),
+            edit_history: 256,
+        }
+    }
+}
+
+impl WorldSettings {
+    /// The configured chunk size, or the default if it isn't supported.
+    pub fn chunk_size(&self) -> u16 {
+        if is_valid_chunk_size(self.chunk_size) {
+            self.chunk_size
+        } else {
+            warn!(
+                "unsupported chunk size {}, using {}",
+                self.chunk_size, CHUNK_SIZE
+            );
+            CHUNK_SIZE
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct PlayerSettings {
+    /// Distance in blocks from the camera the player can break, place and use blocks at. Game
+    /// modes that build from afar raise it on the `Settings` component.
+    pub reach: f32,
+    /// Blocks per second squared walking speeds up by towards the speed the keys ask for.
+    pub acceleration: f32,
+    /// Blocks per second squared walking slows down by when the keys ask for less speed.
+    pub friction: f32,
+}
+
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        Self {
+            reach: DEFAULT_REACH,
+            acceleration: 60.0,
+            friction: 60.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(default)]
+pub struct DebugSettings {
+    /// Spawns a row of glowstone cubes at increasing emissive strength in front of the spawn point.
+    pub emissive_calibration: bool,
+    /// Tints terrain by the shadow cascade each fragment is sampled from.
+    pub shadow_cascades: bool,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// Accepts connections from other players while in a world.
+    pub host: bool,
+    pub port: u16,
+    /// Server offered by the main menu's join button, e.g. `"192.168.0.2:25565"`.
+    pub server: Option<SocketAddr>,
+    /// Port the dedicated server serves Prometheus metrics on over HTTP, at `/metrics`.
+    pub metrics_port: Option<u16>,
+    /// Players a server lets join at once, not counting a listen server's host.
+    pub max_players: u32,
+}
+
+/// Timed events run by a server, read with `read_server_settings`.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ServerSettings {
+    /// Hours between automatic restarts of the dedicated server, which never restarts if unset.
+    pub restart_hours: Option<f32>,
+    /// Seconds before a restart at which players are warned of it.
+    pub restart_warnings: Vec<u32>,
+    pub tasks: Vec<ScheduledTask>,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            restart_hours: None,
+            restart_warnings: vec![300, 60, 10],
+            tasks: Vec::new(),
+        }
+    }
+}
+
+/// A console command run over and over while a world is hosted.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct ScheduledTask {
+    pub every_minutes: f32,
+    pub command: String,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            host: false,
+            port: DEFAULT_PORT,
+            server: None,
+            metrics_port: None,
+            max_players: 16,
+        }
     }
 }
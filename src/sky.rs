@@ -0,0 +1,292 @@
+//! The sky behind the world: its colour through the day, the sun and moon crossing it and the
+//! stars that come out at night. Everything in the sky follows the camera so it can't be reached.
+
+use std::f32::consts::TAU;
+
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    asset::Assets,
+    color::{Alpha, Color, Mix},
+    core_pipeline::core_3d::Camera3d,
+    ecs::{
+        component::Component,
+        query::{With, Without},
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res, ResMut},
+    },
+    hierarchy::{BuildChildren, ChildBuild},
+    math::{primitives::Rectangle, Quat, Vec3},
+    pbr::{MeshMaterial3d, NotShadowCaster, StandardMaterial},
+    prelude::{AlphaMode, Mesh3d},
+    render::{
+        camera::ClearColor,
+        mesh::{Indices, Mesh, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+        view::Visibility,
+    },
+    state::condition::in_state,
+    transform::components::{GlobalTransform, Transform},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    daylight::{update_sun, TimeOfDay},
+    state::GameState,
+    weather::Weather,
+};
+
+/// Distance from the camera things in the sky are drawn at, inside the camera's far plane but
+/// beyond most loaded terrain.
+const SKY_DISTANCE: f32 = 900.0;
+const SUN_SIZE: f32 = 90.0;
+const MOON_SIZE: f32 = 60.0;
+const STAR_COUNT: usize = 1500;
+const STAR_SIZE: std::ops::Range<f32> = 1.5..4.0;
+/// The same stars every night.
+const STAR_SEED: u64 = 0x5747;
+
+const DAY_SKY: Color = Color::srgb(0.53, 0.81, 0.92);
+const NIGHT_SKY: Color = Color::srgb(0.03, 0.04, 0.11);
+const SUNSET_SKY: Color = Color::srgb(0.95, 0.52, 0.3);
+/// Grey the sky turns in a storm at full daylight.
+const STORM_SKY: Color = Color::srgb(0.42, 0.45, 0.5);
+/// How much of the sky's colour a storm at its height greys out.
+const STORM_GREY: f32 = 0.75;
+const SUN_COLOR: Color = Color::srgb(1.0, 0.95, 0.7);
+const MOON_COLOR: Color = Color::srgb(0.85, 0.88, 0.95);
+/// Height of the sun above the horizon, as the y of its direction, within which the sky is
+/// tinted by sunrise or sunset.
+const SUNSET_HEIGHT: f32 = 0.25;
+
+pub struct SkyPlugin;
+
+impl Plugin for SkyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ClearColor(sky_color(&TimeOfDay::default())))
+            .add_systems(Startup, spawn_sky)
+            .add_systems(
+                Update,
+                update_sky
+                    .after(update_sun)
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// Follows the camera, carrying everything drawn in the sky.
+#[derive(Component)]
+struct Sky;
+
+#[derive(Component)]
+struct SunDisc;
+
+#[derive(Component)]
+struct MoonDisc;
+
+#[derive(Component)]
+struct Stars;
+
+/// Colour of the sky behind the world, from night through sunrise and sunset to day.
+pub fn sky_color(time_of_day: &TimeOfDay) -> Color {
+    let sky = NIGHT_SKY
+        .to_linear()
+        .mix(&DAY_SKY.to_linear(), time_of_day.daylight());
+    sky.mix(&SUNSET_SKY.to_linear(), sunset_tint(time_of_day))
+        .into()
+}
+
+/// The sky's colour greyed and darkened by a storm of `intensity`.
+pub fn stormy_sky_color(time_of_day: &TimeOfDay, intensity: f32) -> Color {
+    let storm = STORM_SKY.to_linear() * time_of_day.daylight().max(0.1);
+    sky_color(time_of_day)
+        .to_linear()
+        .mix(&storm.with_alpha(1.0), intensity * STORM_GREY)
+        .into()
+}
+
+/// How strongly sunrise or sunset tints the sky, strongest with the sun on the horizon.
+fn sunset_tint(time_of_day: &TimeOfDay) -> f32 {
+    let height = time_of_day.sun_direction().y.abs();
+    (1.0 - height / SUNSET_HEIGHT).clamp(0.0, 1.0) * 0.6
+}
+
+/// How visible the stars are, fading in as daylight goes.
+pub fn star_alpha(time_of_day: &TimeOfDay) -> f32 {
+    (1.0 - time_of_day.daylight()).powi(2)
+}
+
+/// A quad for each star, spread over the sky around the origin and facing it.
+fn star_mesh() -> Mesh {
+    let mut rng = StdRng::seed_from_u64(STAR_SEED);
+    let mut positions = Vec::with_capacity(STAR_COUNT * 4);
+    let mut indices = Vec::with_capacity(STAR_COUNT * 6);
+    for star in 0..STAR_COUNT as u32 {
+        // picking z and an angle uniformly spreads stars evenly over the sphere
+        let z: f32 = rng.gen_range(-1.0..1.0);
+        let angle = rng.gen_range(0.0..TAU);
+        let ring = (1.0 - z * z).sqrt();
+        let direction = Vec3::new(ring * angle.cos(), ring * angle.sin(), z);
+        let size = rng.gen_range(STAR_SIZE) / 2.0;
+
+        let right = direction.any_orthonormal_vector() * size;
+        let up = direction.cross(right);
+        let centre = direction * SKY_DISTANCE;
+        positions.extend([
+            centre - right - up,
+            centre + right - up,
+            centre + right + up,
+            centre - right + up,
+        ]);
+        let first = star * 4;
+        indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+    let normals: Vec<Vec3> = positions
+        .iter()
+        .map(|position| -position.normalize())
+        .collect();
+
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    mesh.insert_indices(Indices::U32(indices));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh
+}
+
+fn sky_material(color: Color) -> StandardMaterial {
+    StandardMaterial {
+        base_color: color,
+        unlit: true,
+        fog_enabled: false,
+        alpha_mode: AlphaMode::Blend,
+        cull_mode: None,
+        ..Default::default()
+    }
+}
+
+fn spawn_sky(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands
+        .spawn((Sky, Transform::default(), Visibility::default()))
+        .with_children(|sky| {
+            sky.spawn((
+                Mesh3d(meshes.add(star_mesh())),
+                MeshMaterial3d(materials.add(sky_material(Color::WHITE))),
+                Transform::default(),
+                NotShadowCaster,
+                Stars,
+            ));
+            sky.spawn((
+                Mesh3d(meshes.add(Rectangle::from_length(SUN_SIZE))),
+                MeshMaterial3d(materials.add(sky_material(SUN_COLOR))),
+                Transform::default(),
+                NotShadowCaster,
+                SunDisc,
+            ));
+            sky.spawn((
+                Mesh3d(meshes.add(Rectangle::from_length(MOON_SIZE))),
+                MeshMaterial3d(materials.add(sky_material(MOON_COLOR))),
+                Transform::default(),
+                NotShadowCaster,
+                MoonDisc,
+            ));
+        });
+}
+
+/// Keeps the sky centred on the camera, moves the sun and moon along the day and fades the stars.
+#[allow(clippy::type_complexity)]
+fn update_sky(
+    time_of_day: Res<TimeOfDay>,
+    weather: Res<Weather>,
+    mut clear_color: ResMut<ClearColor>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    mut sky_query: Query<
+        &mut Transform,
+        (
+            With<Sky>,
+            Without<SunDisc>,
+            Without<MoonDisc>,
+            Without<Stars>,
+        ),
+    >,
+    mut sun_query: Query<&mut Transform, (With<SunDisc>, Without<MoonDisc>, Without<Stars>)>,
+    mut moon_query: Query<&mut Transform, (With<MoonDisc>, Without<Stars>)>,
+    mut stars_query: Query<(&mut Transform, &MeshMaterial3d<StandardMaterial>), With<Stars>>,
+) {
+    clear_color.0 = stormy_sky_color(&time_of_day, weather.intensity);
+
+    // the camera's position from last frame is close enough this far away
+    if let (Ok(camera), Ok(mut sky)) = (camera_query.get_single(), sky_query.get_single_mut()) {
+        sky.translation = camera.translation();
+    }
+
+    // the sun and moon face the camera from opposite sides of the sky
+    let direction = time_of_day.sun_direction();
+    for mut sun in sun_query.iter_mut() {
+        *sun = Transform::from_translation(direction * SKY_DISTANCE).looking_to(direction, Vec3::Y);
+    }
+    for mut moon in moon_query.iter_mut() {
+        *moon =
+            Transform::from_translation(-direction * SKY_DISTANCE).looking_to(-direction, Vec3::Y);
+    }
+
+    let alpha = star_alpha(&time_of_day);
+    for (mut transform, material) in stars_query.iter_mut() {
+        // stars turn with the sun, about the same axis
+        transform.rotation = Quat::from_rotation_z((time_of_day.time - 0.25) * TAU);
+        if let Some(material) = materials.get_mut(&material.0) {
+            if (material.base_color.alpha() - alpha).abs() > 0.005 {
+                material.base_color.set_alpha(alpha);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::color::{Color, ColorToComponents};
+
+    use super::{sky_color, star_alpha, stormy_sky_color, DAY_SKY, NIGHT_SKY};
+    use crate::daylight::TimeOfDay;
+
+    fn at(time: f32) -> TimeOfDay {
+        TimeOfDay {
+            time,
+            ..Default::default()
+        }
+    }
+
+    fn close(a: Color, b: Color) -> bool {
+        a.to_linear().to_vec3().distance(b.to_linear().to_vec3()) < 1e-3
+    }
+
+    #[test]
+    fn test_sky_color_follows_the_day() {
+        assert!(close(DAY_SKY, sky_color(&at(0.5))));
+        assert!(close(NIGHT_SKY, sky_color(&at(0.0))));
+        // the horizon glows at sunrise, so it's neither
+        let sunrise = sky_color(&at(0.25));
+        assert!(!close(DAY_SKY, sunrise) && !close(NIGHT_SKY, sunrise));
+    }
+
+    #[test]
+    fn test_storms_grey_the_sky() {
+        let noon = at(0.5);
+        assert!(close(sky_color(&noon), stormy_sky_color(&noon, 0.0)));
+        let storm = stormy_sky_color(&noon, 1.0).to_linear();
+        assert!(storm.blue < DAY_SKY.to_linear().blue);
+    }
+
+    #[test]
+    fn test_stars_only_show_at_night() {
+        assert_eq!(0.0, star_alpha(&at(0.5)));
+        assert_eq!(1.0, star_alpha(&at(0.0)));
+        assert!(star_alpha(&at(0.23)) > star_alpha(&at(0.27)));
+    }
+}
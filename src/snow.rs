@@ -0,0 +1,156 @@
+//! Snow settling on the ground near players while it snows, and melting away again outside
+//! snowy biomes once it stops. Each change is an ordinary block edit, so it's remeshed, saved and
+//! sent to other players like any other.
+
+use bevy::{
+    ecs::{
+        event::EventWriter,
+        query::With,
+        system::{Commands, Local, Query, Res, ResMut},
+    },
+    math::{I64Vec2, I64Vec3},
+    time::Time,
+    transform::components::Transform,
+};
+use rand::Rng;
+
+use crate::{
+    block::{BlockShape, BlockType},
+    chunks::{chunk_loader::ChunkLoader, generate::biome::Biome},
+    interaction::{BlockEdited, EditSource},
+    player::Player,
+    weather::{Precipitation, Weather},
+    world::World,
+};
+
+/// Seconds between rounds of snow settling or melting.
+const SNOW_INTERVAL: f32 = 1.0;
+/// Columns picked around each player every round.
+const COLUMNS_PER_ROUND: usize = 12;
+/// Distance in blocks from a player columns are picked within.
+const SNOW_RADIUS: i64 = 32;
+/// Blocks above and below a player the ground is looked for in.
+const SNOW_SCAN_HEIGHT: i64 = 48;
+/// Weather intensity snow must reach before it settles.
+const SETTLING_INTENSITY: f32 = 0.5;
+
+/// Whether snow can settle on top of `block`.
+fn holds_snow(block: BlockType) -> bool {
+    block.is_solid() && block.shape() == BlockShape::Cube
+}
+
+/// The change to make to the top of `column`, between heights `top` and `bottom`: a layer of
+/// snow on open ground while `snowing`, or the snow there melted while `melting`.
+pub fn snow_change(
+    world: &mut World,
+    column: I64Vec2,
+    top: i64,
+    bottom: i64,
+    snowing: bool,
+    melting: bool,
+) -> Option<(I64Vec3, BlockType)> {
+    // the first block down from above is the one open to the sky
+    let ground = (bottom..=top)
+        .rev()
+        .map(|y| I64Vec3::new(column.x, y, column.y))
+        .find(|&position| world.get_block(position) != BlockType::Air)?;
+    let block = world.get_block(ground);
+    if snowing && holds_snow(block) {
+        Some((ground + I64Vec3::Y, BlockType::SnowLayer))
+    } else if melting && block == BlockType::SnowLayer {
+        Some((ground, BlockType::Air))
+    } else {
+        None
+    }
+}
+
+/// Settles snow around players while it snows over them, and melts it in warmer biomes otherwise.
+#[allow(clippy::too_many_arguments)]
+pub fn accumulate_snow(
+    mut commands: Commands,
+    time: Res<Time>,
+    weather: Res<Weather>,
+    mut world: ResMut<World>,
+    chunk_loader: Res<ChunkLoader>,
+    mut timer: Local<f32>,
+    mut edited: EventWriter<BlockEdited>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    *timer -= time.delta_secs();
+    if *timer > 0.0 {
+        return;
+    }
+    *timer = SNOW_INTERVAL;
+
+    let mut rng = rand::thread_rng();
+    for transform in player_query.iter() {
+        let player = transform.translation.round().as_i64vec3();
+        for _ in 0..COLUMNS_PER_ROUND {
+            let column = I64Vec2::new(
+                player.x + rng.gen_range(-SNOW_RADIUS..=SNOW_RADIUS),
+                player.z + rng.gen_range(-SNOW_RADIUS..=SNOW_RADIUS),
+            );
+            let Some(surface) = world.surface(column) else {
+                continue;
+            };
+            let biome = Biome::from_surface(surface);
+            let snowing = weather.intensity >= SETTLING_INTENSITY
+                && Precipitation::in_biome(weather.kind, biome) == Some(Precipitation::Snow);
+            let melting = biome != Biome::Snow
+                && Precipitation::in_biome(weather.kind, biome) != Some(Precipitation::Snow);
+
+            let (top, bottom) = (player.y + SNOW_SCAN_HEIGHT, player.y - SNOW_SCAN_HEIGHT);
+            let Some((position, block)) =
+                snow_change(&mut world, column, top, bottom, snowing, melting)
+            else {
+                continue;
+            };
+            if world.set_block(position, block) {
+                chunk_loader.remesh_block(&mut commands, &world, position);
+                edited.send(BlockEdited {
+                    position,
+                    block,
+                    source: EditSource::Weather,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec2, I64Vec3};
+
+    use super::snow_change;
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        world::World,
+    };
+
+    #[test]
+    fn test_snow_settles_on_open_ground_and_melts() {
+        let mut world = World::new(0);
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), ChunkData::default());
+        let column = I64Vec2::new(3, 3);
+        world.set_block(I64Vec3::new(3, 2, 3), BlockType::Grass);
+        assert_eq!(
+            Some((I64Vec3::new(3, 3, 3), BlockType::SnowLayer)),
+            snow_change(&mut world, column, 15, 0, true, false)
+        );
+
+        // a layer doesn't hold another, and only melts when it's warm
+        world.set_block(I64Vec3::new(3, 3, 3), BlockType::SnowLayer);
+        assert_eq!(None, snow_change(&mut world, column, 15, 0, true, false));
+        assert_eq!(
+            Some((I64Vec3::new(3, 3, 3), BlockType::Air)),
+            snow_change(&mut world, column, 15, 0, false, true)
+        );
+
+        // nothing settles under cover or on water
+        world.set_block(I64Vec3::new(3, 8, 3), BlockType::Water);
+        assert_eq!(None, snow_change(&mut world, column, 15, 0, true, false));
+        world.set_block(I64Vec3::new(3, 8, 3), BlockType::StoneSlab);
+        assert_eq!(None, snow_change(&mut world, column, 15, 0, true, false));
+    }
+}
@@ -0,0 +1,59 @@
+use bevy::{
+    ecs::{
+        query::With,
+        system::{Query, Res, ResMut},
+    },
+    state::state::{NextState, State, States},
+    window::{CursorGrabMode, PrimaryWindow, Window},
+};
+
+use crate::input::bindings::{Action, ActionInput};
+
+#[derive(States, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GameState {
+    #[default]
+    MainMenu,
+    Loading,
+    InGame,
+    Paused,
+    /// The player has died and the death screen is up.
+    Dead,
+}
+
+pub fn toggle_pause(
+    input: ActionInput,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !input.just_pressed(Action::Pause) {
+        return;
+    }
+
+    match state.get() {
+        GameState::InGame => next_state.set(GameState::Paused),
+        GameState::Paused => next_state.set(GameState::InGame),
+        _ => (),
+    }
+}
+
+/// Run condition for systems that keep running while a world is open, even when paused.
+pub fn in_world(state: Res<State<GameState>>) -> bool {
+    matches!(
+        state.get(),
+        GameState::Loading | GameState::InGame | GameState::Paused | GameState::Dead
+    )
+}
+
+pub fn grab_cursor(mut window_query: Query<&mut Window, With<PrimaryWindow>>) {
+    if let Ok(mut window) = window_query.get_single_mut() {
+        window.cursor_options.grab_mode = CursorGrabMode::Locked;
+        window.cursor_options.visible = false;
+    }
+}
+
+pub fn release_cursor(mut window_query: Query<&mut Window, With<PrimaryWindow>>) {
+    if let Ok(mut window) = window_query.get_single_mut() {
+        window.cursor_options.grab_mode = CursorGrabMode::None;
+        window.cursor_options.visible = true;
+    }
+}
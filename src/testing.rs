@@ -0,0 +1,325 @@
+use std::time::Duration;
+
+use bevy::{
+    app::{App, Update},
+    asset::Handle,
+    ecs::{entity::Entity, schedule::IntoSystemConfigs, world::Mut},
+    hierarchy::{BuildChildren, HierarchyPlugin},
+    input::{
+        keyboard::KeyCode,
+        mouse::{MouseButton, MouseMotion},
+        ButtonInput,
+    },
+    math::{I64Vec2, I64Vec3, Quat, Vec3},
+    render::camera::Camera,
+    time::TimeUpdateStrategy,
+    transform::{components::Transform, TransformPlugin},
+    MinimalPlugins,
+};
+
+use crate::{
+    breaking::BreakProgress,
+    chunks::{chunk::ChunkCoordinate, chunk_loader::ChunkLoader},
+    input::bindings::{Action, Binding, KeyBindings},
+    interaction::{edit_block, target_block, BlockEdited, SelectedItem, TargetBlock},
+    item::{pick_up_items, spawn_item_drops, update_item_drops, BlockBroken, Inventory},
+    physics::RaycastHit,
+    player::{player_look, player_move, PlayerBundle, PlayerMovement, PLAYER_EYE_HEIGHT},
+    third_person::CameraView,
+    world::World,
+};
+
+/// Simulated time advanced by every tick, so runs are identical regardless of the machine.
+pub const TICK: Duration = Duration::from_micros(16_667);
+/// Horizontal radius in chunks generated around the spawn column.
+const GENERATE_RADIUS: i64 = 1;
+
+/// A headless game for end-to-end tests: a seeded world generated up front around the spawn
+/// column, a player and camera, and the movement and interaction systems in the order the game
+/// runs them. Input is scripted through the default key bindings.
+pub struct TestGame {
+    app: App,
+    player: Entity,
+    camera: Entity,
+}
+
+impl TestGame {
+    /// Generates the world and stands the player on the surface at the centre of `column`.
+    pub fn new(seed: u32, column: I64Vec2) -> Self {
+        let mut world = World::new(seed);
+        let surface = surface_height(&world, column);
+        generate_around(&mut world, column, surface);
+
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, TransformPlugin, HierarchyPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(TICK))
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<ButtonInput<MouseButton>>()
+            .init_resource::<TargetBlock>()
+            .init_resource::<BreakProgress>()
+            .init_resource::<CameraView>()
+            .init_resource::<SelectedItem>()
+            .add_event::<MouseMotion>()
+            .add_event::<BlockEdited>()
+            .add_event::<BlockBroken>()
+            .init_resource::<Inventory>()
+            .insert_resource(world)
+            .insert_resource(ChunkLoader::new(0, Handle::default()))
+            .add_systems(
+                Update,
+                (
+                    player_look,
+                    player_move,
+                    target_block,
+                    edit_block,
+                    spawn_item_drops,
+                    update_item_drops,
+                    pick_up_items,
+                )
+                    .chain(),
+            );
+
+        let spawn = Vec3::new(column.x as f32, surface as f32 + 0.5, column.y as f32);
+        let player = app
+            .world_mut()
+            .spawn(PlayerBundle {
+                transform: Transform::from_translation(spawn),
+                ..Default::default()
+            })
+            .id();
+        let camera = app
+            .world_mut()
+            .spawn((
+                Transform::from_xyz(0.0, PLAYER_EYE_HEIGHT, 0.0),
+                Camera::default(),
+            ))
+            .id();
+        app.world_mut().entity_mut(player).add_children(&[camera]);
+
+        let mut game = Self {
+            app,
+            player,
+            camera,
+        };
+        // propagate transforms so the first scripted tick sees the camera in place
+        game.tick(1);
+        game
+    }
+
+    /// Runs `ticks` frames. Actions pressed before the call count as just pressed on the first.
+    pub fn tick(&mut self, ticks: u32) {
+        for _ in 0..ticks {
+            self.app.update();
+            let world = self.app.world_mut();
+            world.resource_mut::<ButtonInput<KeyCode>>().clear();
+            world.resource_mut::<ButtonInput<MouseButton>>().clear();
+        }
+    }
+
+    pub fn press(&mut self, action: Action) {
+        let world = self.app.world_mut();
+        match KeyBindings::default().binding(action) {
+            Binding::Key(key) => world.resource_mut::<ButtonInput<KeyCode>>().press(key),
+            Binding::Mouse(button) => world
+                .resource_mut::<ButtonInput<MouseButton>>()
+                .press(button),
+        }
+    }
+
+    pub fn release(&mut self, action: Action) {
+        let world = self.app.world_mut();
+        match KeyBindings::default().binding(action) {
+            Binding::Key(key) => world.resource_mut::<ButtonInput<KeyCode>>().release(key),
+            Binding::Mouse(button) => world
+                .resource_mut::<ButtonInput<MouseButton>>()
+                .release(button),
+        }
+    }
+
+    /// Presses and releases an action over a single tick.
+    pub fn tap(&mut self, action: Action) {
+        self.press(action);
+        self.tick(1);
+        self.release(action);
+    }
+
+    /// Holds break on the target block until it has had time to break.
+    pub fn break_target(&mut self) {
+        let hit = self.target().expect("a block is within reach");
+        let hardness = self.world().get_block(hit.block).hardness();
+        self.press(Action::Break);
+        self.tick((hardness / TICK.as_secs_f32()).ceil() as u32 + 1);
+        self.release(Action::Break);
+    }
+
+    /// Turns the player to `yaw` and tilts the camera to `pitch`, both in radians.
+    pub fn look(&mut self, yaw: f32, pitch: f32) {
+        let world = self.app.world_mut();
+        world.get_mut::<Transform>(self.player).unwrap().rotation = Quat::from_rotation_y(yaw);
+        world.get_mut::<Transform>(self.camera).unwrap().rotation = Quat::from_rotation_x(pitch);
+    }
+
+    pub fn player_position(&self) -> Vec3 {
+        self.app
+            .world()
+            .get::<Transform>(self.player)
+            .unwrap()
+            .translation
+    }
+
+    /// Moves the player to the centre of the block column they are standing in.
+    pub fn centre_on_column(&mut self) {
+        let world = self.app.world_mut();
+        let mut transform = world.get_mut::<Transform>(self.player).unwrap();
+        transform.translation.x = transform.translation.x.round();
+        transform.translation.z = transform.translation.z.round();
+    }
+
+    pub fn movement(&mut self) -> Mut<PlayerMovement> {
+        self.app
+            .world_mut()
+            .get_mut::<PlayerMovement>(self.player)
+            .unwrap()
+    }
+
+    pub fn world(&mut self) -> Mut<World> {
+        self.app.world_mut().resource_mut::<World>()
+    }
+
+    pub fn target(&self) -> Option<RaycastHit> {
+        self.app.world().resource::<TargetBlock>().0
+    }
+
+    pub fn inventory(&self) -> &Inventory {
+        self.app.world().resource::<Inventory>()
+    }
+}
+
+/// Height of the terrain at `column`, the top solid block being one below it.
+pub fn surface_height(world: &World, column: I64Vec2) -> u64 {
+    world.surface(column).map_or(0, |surface| surface.height)
+}
+
+fn generate_around(world: &mut World, column: I64Vec2, surface: u64) {
+    let chunk_size = world.chunk_size() as i64;
+    let centre = column.div_euclid(I64Vec2::splat(chunk_size));
+    let top = surface as i64 / chunk_size + 1;
+
+    for x in -GENERATE_RADIUS..=GENERATE_RADIUS {
+        for z in -GENERATE_RADIUS..=GENERATE_RADIUS {
+            for y in 0..=top {
+                let coord = ChunkCoordinate(I64Vec3::new(centre.x + x, y, centre.y + z));
+                let chunk_data = world.generator().generate(coord);
+                world.insert_chunk(coord, chunk_data);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        f32::consts::{FRAC_PI_2, FRAC_PI_4, PI},
+    };
+
+    use bevy::math::{I64Vec2, I64Vec3};
+
+    use super::{surface_height, TestGame};
+    use crate::{
+        block::BlockType,
+        chunks::codec::{decode_chunk, encode_chunk},
+        ids::IdMap,
+        input::bindings::Action,
+        physics::intersects_solid,
+        player::{MovementMode, PLAYER_COLLIDER},
+        world::World,
+    };
+
+    const SEED: u32 = 1288;
+    const DIG_DEPTH: i64 = 10;
+    /// Ticks allowed for the player to fall into a freshly dug hole and land.
+    const SETTLE_TICKS: u32 = 30;
+
+    /// First column along +x high enough above sea level to dig into dry ground.
+    fn dry_column(world: &World) -> I64Vec2 {
+        (0..)
+            .map(|x| I64Vec2::new(x, 0))
+            .find(|column| surface_height(world, *column) > 48)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_dig_down_ten_blocks_and_survive() {
+        let column = dry_column(&World::new(SEED));
+        let mut game = TestGame::new(SEED, column);
+        game.movement().mode = MovementMode::Walk;
+        game.tick(SETTLE_TICKS);
+        assert!(game.movement().is_grounded());
+
+        // walk forward along +z
+        let start = game.player_position();
+        game.look(PI, 0.0);
+        game.press(Action::MoveForward);
+        game.tick(20);
+        game.release(Action::MoveForward);
+        game.tick(SETTLE_TICKS);
+        assert!(game.player_position().z > start.z);
+
+        // place a block on the ground ahead
+        game.look(PI, -FRAC_PI_4);
+        // one tick to move the camera, another to aim with it
+        game.tick(2);
+        let hit = game.target().expect("ground ahead is within reach");
+        let placed = hit.block + hit.normal;
+        game.tap(Action::Place);
+        assert_eq!(BlockType::Stone, game.world().get_block(placed));
+
+        // dig straight down, falling into the hole after every block
+        game.centre_on_column();
+        game.look(PI, -FRAC_PI_2);
+        game.tick(SETTLE_TICKS);
+        let top = game.player_position();
+        let mut dug = Vec::new();
+        for depth in 1..=DIG_DEPTH {
+            let hit = game.target().expect("block below is within reach");
+            assert_eq!((top.y - 0.5).round() as i64 - depth + 1, hit.block.y);
+            game.break_target();
+            game.tick(SETTLE_TICKS);
+            dug.push(hit.block);
+        }
+
+        let position = game.player_position();
+        assert!((position.y - (top.y - DIG_DEPTH as f32)).abs() < 0.01);
+        assert!((position.x - top.x).abs() < 0.01 && (position.z - top.z).abs() < 0.01);
+        assert!(game.movement().is_grounded());
+        assert!(!intersects_solid(
+            &mut game.world(),
+            position + PLAYER_COLLIDER.min,
+            position + PLAYER_COLLIDER.max
+        ));
+        for block in &dug {
+            assert_eq!(BlockType::Air, game.world().get_block(*block));
+        }
+
+        // every dug block dropped into the hole and was picked up on the way down
+        let collected: u32 = game.inventory().iter().map(|(_, count)| count).sum();
+        assert_eq!(DIG_DEPTH as u32, collected);
+
+        // the edited chunks survive a save and reload
+        let mut world = game.world();
+        let edited: Vec<I64Vec3> = dug.iter().copied().chain([placed]).collect();
+        let chunks: HashSet<_> = edited
+            .iter()
+            .map(|block| world.block_to_chunk_coordinate(*block))
+            .collect();
+        let mut reloaded = World::new(SEED);
+        for chunk in chunks {
+            let encoded = encode_chunk(&world.get_chunk_data(chunk).unwrap(), &IdMap::default());
+            reloaded.insert_chunk(chunk, decode_chunk(&encoded, &IdMap::default()).unwrap());
+        }
+        for block in edited {
+            assert_eq!(world.get_block(block), reloaded.get_block(block));
+        }
+    }
+}
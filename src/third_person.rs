@@ -0,0 +1,192 @@
+//! Third person view, toggled from first person, which puts the camera behind the player and
+//! shows a stand-in model of them. The camera is pulled in front of blocks between it and the
+//! player's eye so terrain never hides them.
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::Assets,
+    color::Color,
+    core_pipeline::core_3d::Camera3d,
+    ecs::{
+        change_detection::DetectChangesMut,
+        component::Component,
+        entity::Entity,
+        query::{Added, With, Without},
+        schedule::IntoSystemConfigs,
+        system::{Commands, Query, Res, ResMut, Resource},
+    },
+    hierarchy::{BuildChildren, Parent},
+    math::{primitives::Cuboid, Vec3},
+    pbr::{MeshMaterial3d, StandardMaterial},
+    prelude::Mesh3d,
+    render::{mesh::Mesh, view::Visibility},
+    state::condition::in_state,
+    time::Time,
+    transform::components::{GlobalTransform, Transform},
+};
+
+use crate::{
+    input::bindings::{Action, ActionInput},
+    physics::raycast,
+    player::{player_look, player_move, Player, PLAYER_COLLIDER, PLAYER_EYE_HEIGHT},
+    state::GameState,
+    ui::console::console_closed,
+    world::World,
+};
+
+/// Distance behind the eye the camera sits at when nothing is in the way.
+const THIRD_PERSON_DISTANCE: f32 = 4.0;
+/// Gap kept between the camera and a block it's pulled in front of, so the near plane doesn't
+/// clip into it.
+const CAMERA_MARGIN: f32 = 0.2;
+/// Blocks per second the camera moves back out once a block is no longer in the way.
+const ZOOM_OUT_SPEED: f32 = 8.0;
+const PLAYER_MODEL_COLOR: Color = Color::srgb(0.25, 0.45, 0.75);
+
+pub struct ThirdPersonPlugin;
+
+impl Plugin for ThirdPersonPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraView>().add_systems(
+            Update,
+            (
+                add_player_model,
+                toggle_view.run_if(console_closed),
+                position_camera
+                    .after(toggle_view)
+                    .after(player_move)
+                    .after(player_look),
+            )
+                .run_if(in_state(GameState::InGame)),
+        );
+    }
+}
+
+/// Whether the camera is behind the player, and how far behind their eye it is.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct CameraView {
+    pub third_person: bool,
+    distance: f32,
+}
+
+impl CameraView {
+    /// Where the player sees from, which the camera is pulled back from in third person.
+    /// Blocks and mobs are aimed at from here.
+    pub fn eye(&self, camera: &GlobalTransform) -> Vec3 {
+        camera.translation() + camera.forward() * self.distance
+    }
+}
+
+/// The stand-in model drawn for the local player in third person.
+#[derive(Component)]
+struct PlayerModel;
+
+fn add_player_model(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    player_query: Query<Entity, Added<Player>>,
+) {
+    for player in player_query.iter() {
+        let size = PLAYER_COLLIDER.max - PLAYER_COLLIDER.min;
+        commands
+            .entity(player)
+            .insert(Visibility::default())
+            .with_child((
+                Mesh3d(meshes.add(Cuboid::from_size(size))),
+                MeshMaterial3d(materials.add(StandardMaterial {
+                    base_color: PLAYER_MODEL_COLOR,
+                    perceptual_roughness: 1.0,
+                    ..Default::default()
+                })),
+                Transform::from_translation(Vec3::Y * size.y / 2.0),
+                Visibility::Hidden,
+                PlayerModel,
+            ));
+    }
+}
+
+fn toggle_view(input: ActionInput, mut view: ResMut<CameraView>) {
+    if input.just_pressed(Action::ToggleView) {
+        view.third_person = !view.third_person;
+    }
+}
+
+/// How far behind `eye` the camera can go along `back`, up to `max_distance`, before a block is
+/// in the way.
+pub fn unobstructed_distance(world: &mut World, eye: Vec3, back: Vec3, max_distance: f32) -> f32 {
+    raycast(world, eye, back, max_distance + CAMERA_MARGIN)
+        .map_or(max_distance, |hit| (hit.distance - CAMERA_MARGIN).max(0.0))
+}
+
+/// Places the camera at the eye, or behind it in third person, and shows the player's model
+/// only when it can be seen.
+fn position_camera(
+    time: Res<Time>,
+    mut world: ResMut<World>,
+    mut view: ResMut<CameraView>,
+    player_query: Query<&Transform, (With<Player>, Without<Camera3d>)>,
+    mut camera_query: Query<(&Parent, &mut Transform), With<Camera3d>>,
+    mut model_query: Query<&mut Visibility, With<PlayerModel>>,
+) {
+    let Ok((parent, mut camera)) = camera_query.get_single_mut() else {
+        return;
+    };
+    let Ok(player) = player_query.get(parent.get()) else {
+        return;
+    };
+
+    let eye = Vec3::Y * PLAYER_EYE_HEIGHT;
+    let distance = if view.third_person {
+        let back = player.rotation * camera.rotation * Vec3::Z;
+        let room = unobstructed_distance(
+            &mut world,
+            player.translation + eye,
+            back,
+            THIRD_PERSON_DISTANCE,
+        );
+        // pull in at once so no block is ever in front of the player, but ease back out
+        room.min(view.distance + ZOOM_OUT_SPEED * time.delta_secs())
+    } else {
+        0.0
+    };
+    view.distance = distance;
+    camera.translation = eye + camera.rotation * Vec3::Z * distance;
+
+    for mut visibility in model_query.iter_mut() {
+        visibility.set_if_neq(if view.third_person {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec3, Vec3};
+
+    use super::{unobstructed_distance, CAMERA_MARGIN};
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        world::World,
+    };
+
+    #[test]
+    fn test_camera_is_pulled_in_front_of_blocks() {
+        let mut world = World::new(0);
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), ChunkData::default());
+        let eye = Vec3::new(4.0, 4.0, 4.0);
+        assert_eq!(4.0, unobstructed_distance(&mut world, eye, Vec3::Z, 4.0));
+
+        // the block's near face is 1.5 behind the eye
+        world.set_block(I64Vec3::new(4, 4, 6), BlockType::Stone);
+        let distance = unobstructed_distance(&mut world, eye, Vec3::Z, 4.0);
+        assert!((distance - (1.5 - CAMERA_MARGIN)).abs() < 1e-4);
+
+        world.set_block(I64Vec3::new(4, 4, 5), BlockType::Stone);
+        let distance = unobstructed_distance(&mut world, eye, Vec3::Z, 4.0);
+        assert!((distance - (0.5 - CAMERA_MARGIN)).abs() < 1e-4);
+    }
+}
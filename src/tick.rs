@@ -0,0 +1,86 @@
+use bevy::{
+    app::{App, FixedFirst, Plugin, RunFixedMainLoop, RunFixedMainLoopSystem},
+    ecs::{
+        component::Component,
+        schedule::IntoSystemConfigs,
+        system::{Query, Res},
+    },
+    math::Vec3,
+    time::{Fixed, Time},
+    transform::components::Transform,
+};
+
+/// Gameplay ticks per second. Systems added to `FixedUpdate` run at this rate whatever the frame
+/// rate, and see a `Time` whose delta is one tick.
+pub const TICKS_PER_SECOND: f64 = 20.0;
+
+/// Runs `FixedUpdate` at `TICKS_PER_SECOND` and smooths the movement of entities simulated there
+/// between ticks.
+pub struct TickPlugin;
+
+impl Plugin for TickPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Time::<Fixed>::from_hz(TICKS_PER_SECOND))
+            .add_systems(FixedFirst, record_previous_positions)
+            .add_systems(
+                RunFixedMainLoop,
+                interpolate_positions.in_set(RunFixedMainLoopSystem::AfterFixedMainLoop),
+            );
+    }
+}
+
+/// Position of an entity moved on the fixed tick. Tick systems move `current`, and the entity's
+/// `Transform` is drawn part way from `previous` so it moves smoothly between ticks.
+#[derive(Component, Debug, Copy, Clone, PartialEq)]
+pub struct TickPosition {
+    pub previous: Vec3,
+    pub current: Vec3,
+}
+
+impl TickPosition {
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            previous: position,
+            current: position,
+        }
+    }
+
+    /// Position `fraction` of the way through the tick after `previous`.
+    pub fn interpolate(&self, fraction: f32) -> Vec3 {
+        self.previous.lerp(self.current, fraction)
+    }
+}
+
+fn record_previous_positions(mut query: Query<&mut TickPosition>) {
+    for mut position in query.iter_mut() {
+        position.previous = position.current;
+    }
+}
+
+fn interpolate_positions(
+    fixed: Res<Time<Fixed>>,
+    mut query: Query<(&TickPosition, &mut Transform)>,
+) {
+    let fraction = fixed.overstep_fraction();
+    for (position, mut transform) in query.iter_mut() {
+        transform.translation = position.interpolate(fraction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec3;
+
+    use super::TickPosition;
+
+    #[test]
+    fn test_tick_position_interpolates_from_previous() {
+        let mut position = TickPosition::new(Vec3::ZERO);
+        assert_eq!(Vec3::ZERO, position.interpolate(0.5));
+
+        position.current = Vec3::new(0.0, -2.0, 0.0);
+        assert_eq!(Vec3::ZERO, position.interpolate(0.0));
+        assert_eq!(Vec3::new(0.0, -1.0, 0.0), position.interpolate(0.5));
+        assert_eq!(position.current, position.interpolate(1.0));
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    input::{
+        keyboard::{Key, KeyboardInput},
+        ButtonState,
+    },
+    prelude::*,
+};
+
+use super::widgets::edit_text;
+use crate::{
+    command::{CommandOutput, CommandRegistry, ConsoleCommand},
+    state::{toggle_pause, GameState},
+};
+
+const MAX_COMMAND_LENGTH: usize = 128;
+/// Lines of command output kept on screen.
+const HISTORY_LENGTH: usize = 8;
+/// Seconds output stays visible after the console is closed.
+const OUTPUT_DISPLAY_TIME: f32 = 5.0;
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .add_systems(Startup, spawn_console)
+            .add_systems(
+                Update,
+                (type_into_console, record_output, update_console_text)
+                    .chain()
+                    .after(toggle_pause)
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnExit(GameState::InGame), close_console);
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Console {
+    open: bool,
+    input: String,
+    history: VecDeque<String>,
+    /// Elapsed time at which the last output was printed.
+    last_output: f32,
+}
+
+/// Run condition for gameplay systems that should ignore input while the console is typed into.
+pub fn console_closed(console: Res<Console>) -> bool {
+    !console.open
+}
+
+#[derive(Component)]
+struct ConsoleText;
+
+fn spawn_console(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        TextFont {
+            font_size: 18.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(8.0),
+            right: Val::Px(8.0),
+            bottom: Val::Px(8.0),
+            padding: UiRect::all(Val::Px(4.0)),
+            ..default()
+        },
+        Visibility::Hidden,
+        ConsoleText,
+    ));
+}
+
+fn type_into_console(
+    mut console: ResMut<Console>,
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut commands: EventWriter<ConsoleCommand>,
+    registry: Res<CommandRegistry>,
+) {
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+
+        if !console.open {
+            if is_open_key(&event.logical_key) {
+                console.open = true;
+                console.input.clear();
+            }
+            continue;
+        }
+
+        match &event.logical_key {
+            Key::Escape => console.open = false,
+            Key::Enter => {
+                console.open = false;
+                if let Some(command) = ConsoleCommand::parse(&console.input) {
+                    commands.send(command);
+                }
+            }
+            Key::Tab => {
+                let mut completed = registry.complete(&console.input);
+                completed.truncate(MAX_COMMAND_LENGTH);
+                console.input = completed;
+            }
+            key => {
+                edit_text(&mut console.input, key, MAX_COMMAND_LENGTH);
+            }
+        }
+    }
+}
+
+/// The console opens with `/`, which is kept as the start of the command, or `T`.
+fn is_open_key(key: &Key) -> bool {
+    matches!(key, Key::Character(c) if c == "/" || c.eq_ignore_ascii_case("t"))
+}
+
+fn record_output(
+    time: Res<Time>,
+    mut console: ResMut<Console>,
+    mut output: EventReader<CommandOutput>,
+) {
+    for output in output.read() {
+        for line in output.line.lines() {
+            console.history.push_back(line.to_string());
+        }
+        while console.history.len() > HISTORY_LENGTH {
+            console.history.pop_front();
+        }
+        console.last_output = time.elapsed_secs();
+    }
+}
+
+fn update_console_text(
+    time: Res<Time>,
+    console: Res<Console>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<ConsoleText>>,
+) {
+    let showing_output = !console.history.is_empty()
+        && time.elapsed_secs() - console.last_output < OUTPUT_DISPLAY_TIME;
+
+    for (mut text, mut visibility) in text_query.iter_mut() {
+        let mut lines: Vec<String> = console.history.iter().cloned().collect();
+        if console.open {
+            lines.push(format!("/{}_", console.input));
+        }
+        let content = lines.join("\n");
+        if text.0 != content {
+            text.0 = content;
+        }
+
+        let visible = if console.open || showing_output {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        visibility.set_if_neq(visible);
+    }
+}
+
+fn close_console(mut console: ResMut<Console>) {
+    console.open = false;
+}
@@ -0,0 +1,203 @@
+use bevy::{app::AppExit, prelude::*};
+
+use super::widgets::{button_hover, menu_button};
+use crate::{
+    breath::Breath,
+    loading::spawn_height,
+    mob::Health,
+    player::{Player, PlayerMovement, Spectator},
+    save::WorldInfo,
+    state::GameState,
+    world::World,
+};
+
+pub struct DeathScreenPlugin;
+
+impl Plugin for DeathScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Loading), spectate_ended_games)
+            .add_systems(
+                Update,
+                detect_player_death.run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnEnter(GameState::Dead), spawn_death_screen)
+            .add_systems(
+                Update,
+                death_screen_buttons.run_if(in_state(GameState::Dead)),
+            );
+    }
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum DeathScreenButton {
+    Respawn,
+    Spectate,
+    DeleteWorld,
+    Quit,
+}
+
+/// A hardcore world that was lost can only be spectated when it's opened again.
+fn spectate_ended_games(
+    mut commands: Commands,
+    world_info: Option<Res<WorldInfo>>,
+    player_query: Query<Entity, With<Player>>,
+) {
+    if !world_info.is_some_and(|info| info.game_over) {
+        return;
+    }
+    for player in player_query.iter() {
+        commands.entity(player).insert(Spectator);
+    }
+}
+
+/// Shows the death screen when the player's health runs out. In a hardcore world the game is
+/// over as soon as they die.
+fn detect_player_death(
+    world_info: Option<ResMut<WorldInfo>>,
+    player_query: Query<&Health, (With<Player>, Without<Spectator>)>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !player_query.iter().any(Health::is_dead) {
+        return;
+    }
+
+    if let Some(mut info) = world_info.filter(|info| info.hardcore) {
+        if let Err(e) = info.end_game() {
+            warn!("failed to save the end of world '{}': {}", info.name, e);
+        }
+    }
+    next_state.set(GameState::Dead);
+}
+
+fn spawn_death_screen(
+    mut commands: Commands,
+    world_info: Option<Res<WorldInfo>>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    let game_over = world_info.is_some_and(|info| info.game_over);
+    let position = player_query
+        .get_single()
+        .map(|transform| transform.translation)
+        .unwrap_or_default();
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.4, 0.0, 0.0, 0.5)),
+            StateScoped(GameState::Dead),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new(if game_over { "Game over!" } else { "You died!" }),
+                TextFont {
+                    font_size: 36.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+            // so the player can find their way back to what they dropped
+            parent.spawn((
+                Text::new(format!(
+                    "Died at {:.0}, {:.0}, {:.0}",
+                    position.x, position.y, position.z
+                )),
+                TextFont {
+                    font_size: 18.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.8, 0.8, 0.8)),
+                Node {
+                    margin: UiRect::vertical(Val::Px(12.0)),
+                    ..default()
+                },
+            ));
+
+            if game_over {
+                menu_button(parent, "Spectate", DeathScreenButton::Spectate);
+                menu_button(parent, "Delete World", DeathScreenButton::DeleteWorld);
+            } else {
+                menu_button(parent, "Respawn", DeathScreenButton::Respawn);
+            }
+            menu_button(parent, "Quit", DeathScreenButton::Quit);
+        });
+}
+
+/// Deleting the world asks for a second click, shown on the button, before anything is removed.
+#[allow(clippy::too_many_arguments)]
+fn death_screen_buttons(
+    mut commands: Commands,
+    mut button_query: Query<
+        (
+            &Interaction,
+            &DeathScreenButton,
+            &Children,
+            &mut BackgroundColor,
+        ),
+        Changed<Interaction>,
+    >,
+    mut text_query: Query<&mut Text>,
+    world: Res<World>,
+    world_info: Option<Res<WorldInfo>>,
+    mut player_query: Query<
+        (Entity, &mut Health, &mut Transform, &mut PlayerMovement),
+        With<Player>,
+    >,
+    mut confirming_delete: Local<bool>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for (interaction, button, children, mut background) in button_query.iter_mut() {
+        button_hover(*interaction, &mut background);
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let Ok((player, mut health, mut transform, mut movement)) = player_query.get_single_mut()
+        else {
+            continue;
+        };
+        match button {
+            DeathScreenButton::Respawn => {
+                health.current = health.max;
+                commands.entity(player).insert(Breath::default());
+                movement.stop();
+                transform.translation = Vec3::new(0.0, spawn_height(&world), 0.0);
+                next_state.set(GameState::InGame);
+            }
+            DeathScreenButton::Spectate => {
+                // a spectator is out of harm's way, so their health just stops them dying again
+                health.current = health.max;
+                movement.stop();
+                commands.entity(player).insert(Spectator);
+                next_state.set(GameState::InGame);
+            }
+            DeathScreenButton::DeleteWorld if !*confirming_delete => {
+                *confirming_delete = true;
+                for child in children.iter() {
+                    if let Ok(mut text) = text_query.get_mut(*child) {
+                        text.0 = "Click again to delete".to_string();
+                    }
+                }
+            }
+            DeathScreenButton::DeleteWorld => {
+                if let Some(info) = &world_info {
+                    match info.delete() {
+                        Ok(()) => info!("deleted world '{}'", info.name),
+                        Err(e) => warn!("failed to delete world '{}': {}", info.name, e),
+                    }
+                }
+                exit.send(AppExit::Success);
+            }
+            DeathScreenButton::Quit => {
+                exit.send(AppExit::Success);
+            }
+        }
+    }
+}
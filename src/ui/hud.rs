@@ -0,0 +1,266 @@
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    breath::Breath, experience::Experience, interaction::SelectedItem, item::Inventory,
+    player::Player,
+};
+
+/// Window height the HUD is laid out for; larger windows scale it up proportionally.
+const REFERENCE_HEIGHT: f32 = 720.0;
+const CROSSHAIR_SIZE: f32 = 16.0;
+const CROSSHAIR_THICKNESS: f32 = 2.0;
+const EXPERIENCE_BAR_WIDTH: f32 = 360.0;
+const EXPERIENCE_BAR_HEIGHT: f32 = 6.0;
+const EXPERIENCE_COLOR: Color = Color::srgb(0.5, 0.9, 0.2);
+const BREATH_BAR_WIDTH: f32 = 120.0;
+const BREATH_COLOR: Color = Color::srgb(0.4, 0.7, 1.0);
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_hud).add_systems(
+            Update,
+            (
+                scale_hud,
+                update_coordinates_text,
+                update_selected_item_text,
+                update_experience_bar,
+                update_breath_bar,
+            ),
+        );
+    }
+}
+
+#[derive(Component)]
+struct CoordinatesText;
+
+#[derive(Component)]
+struct SelectedItemText;
+
+#[derive(Component)]
+struct ExperienceLevelText;
+
+#[derive(Component)]
+struct ExperienceBarFill;
+
+/// Shown only while the player is short of breath.
+#[derive(Component)]
+struct BreathBar;
+
+#[derive(Component)]
+struct BreathBarFill;
+
+fn spawn_hud(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            position_type: PositionType::Absolute,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn(Node {
+                    width: Val::Px(CROSSHAIR_SIZE),
+                    height: Val::Px(CROSSHAIR_SIZE),
+                    ..default()
+                })
+                .with_children(|crosshair| {
+                    let offset = (CROSSHAIR_SIZE - CROSSHAIR_THICKNESS) / 2.0;
+                    crosshair.spawn((
+                        Node {
+                            position_type: PositionType::Absolute,
+                            left: Val::Px(offset),
+                            width: Val::Px(CROSSHAIR_THICKNESS),
+                            height: Val::Px(CROSSHAIR_SIZE),
+                            ..default()
+                        },
+                        BackgroundColor(Color::WHITE),
+                    ));
+                    crosshair.spawn((
+                        Node {
+                            position_type: PositionType::Absolute,
+                            top: Val::Px(offset),
+                            width: Val::Px(CROSSHAIR_SIZE),
+                            height: Val::Px(CROSSHAIR_THICKNESS),
+                            ..default()
+                        },
+                        BackgroundColor(Color::WHITE),
+                    ));
+                });
+        });
+
+    commands
+        .spawn(Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+            flex_direction: FlexDirection::Column,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                Text::default(),
+                TextFont {
+                    font_size: 18.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                CoordinatesText,
+            ));
+            parent.spawn((
+                Text::default(),
+                TextFont {
+                    font_size: 18.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                SelectedItemText,
+            ));
+        });
+
+    commands
+        .spawn(Node {
+            width: Val::Percent(100.0),
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Node {
+                        width: Val::Px(BREATH_BAR_WIDTH),
+                        height: Val::Px(EXPERIENCE_BAR_HEIGHT),
+                        margin: UiRect::bottom(Val::Px(4.0)),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+                    Visibility::Hidden,
+                    BreathBar,
+                ))
+                .with_child((
+                    Node {
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    BackgroundColor(BREATH_COLOR),
+                    BreathBarFill,
+                ));
+            parent.spawn((
+                Text::default(),
+                TextFont {
+                    font_size: 16.0,
+                    ..default()
+                },
+                TextColor(EXPERIENCE_COLOR),
+                ExperienceLevelText,
+            ));
+            parent
+                .spawn((
+                    Node {
+                        width: Val::Px(EXPERIENCE_BAR_WIDTH),
+                        height: Val::Px(EXPERIENCE_BAR_HEIGHT),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+                ))
+                .with_child((
+                    Node {
+                        width: Val::Percent(0.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    BackgroundColor(EXPERIENCE_COLOR),
+                    ExperienceBarFill,
+                ));
+        });
+}
+
+fn scale_hud(mut ui_scale: ResMut<UiScale>, window_query: Query<&Window, With<PrimaryWindow>>) {
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+
+    let scale = (window.height() / REFERENCE_HEIGHT).max(0.5);
+    if ui_scale.0 != scale {
+        ui_scale.0 = scale;
+    }
+}
+
+fn update_coordinates_text(
+    player_query: Query<&Transform, With<Player>>,
+    mut text_query: Query<&mut Text, With<CoordinatesText>>,
+) {
+    let (Ok(player), Ok(mut text)) = (player_query.get_single(), text_query.get_single_mut())
+    else {
+        return;
+    };
+
+    let position = player.translation;
+    text.0 = format!("{:.1} / {:.1} / {:.1}", position.x, position.y, position.z);
+}
+
+fn update_selected_item_text(
+    selected: Res<SelectedItem>,
+    inventory: Res<Inventory>,
+    mut text_query: Query<&mut Text, With<SelectedItemText>>,
+) {
+    if !selected.is_changed() && !inventory.is_changed() {
+        return;
+    }
+
+    if let Ok(mut text) = text_query.get_single_mut() {
+        let held = selected.held();
+        text.0 = format!("{} ({})", held.name(), inventory.count(held));
+    }
+}
+
+fn update_experience_bar(
+    experience: Res<Experience>,
+    mut text_query: Query<&mut Text, With<ExperienceLevelText>>,
+    mut fill_query: Query<&mut Node, With<ExperienceBarFill>>,
+) {
+    if !experience.is_changed() {
+        return;
+    }
+
+    if let Ok(mut text) = text_query.get_single_mut() {
+        let level = experience.level();
+        text.0 = if level > 0 {
+            level.to_string()
+        } else {
+            String::new()
+        };
+    }
+    if let Ok(mut fill) = fill_query.get_single_mut() {
+        fill.width = Val::Percent(experience.progress() * 100.0);
+    }
+}
+
+fn update_breath_bar(
+    player_query: Query<&Breath, (With<Player>, Changed<Breath>)>,
+    mut bar_query: Query<&mut Visibility, With<BreathBar>>,
+    mut fill_query: Query<&mut Node, With<BreathBarFill>>,
+) {
+    let Ok(breath) = player_query.get_single() else {
+        return;
+    };
+
+    if let Ok(mut visibility) = bar_query.get_single_mut() {
+        visibility.set_if_neq(if breath.is_full() {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        });
+    }
+    if let Ok(mut fill) = fill_query.get_single_mut() {
+        fill.width = Val::Percent(breath.current / breath.max * 100.0);
+    }
+}
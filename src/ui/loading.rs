@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+
+use crate::{loading::LoadingProgress, state::GameState};
+
+const BAR_WIDTH: f32 = 320.0;
+const BAR_HEIGHT: f32 = 16.0;
+
+pub struct LoadingScreenPlugin;
+
+impl Plugin for LoadingScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Loading), spawn_loading_screen)
+            .add_systems(
+                Update,
+                update_loading_screen.run_if(in_state(GameState::Loading)),
+            );
+    }
+}
+
+#[derive(Component)]
+struct ProgressBar;
+
+#[derive(Component)]
+struct ProgressText;
+
+fn spawn_loading_screen(mut commands: Commands) {
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgb(0.1, 0.12, 0.15)),
+            GlobalZIndex(10),
+            StateScoped(GameState::Loading),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("Generating world"),
+                TextFont {
+                    font_size: 32.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                Node {
+                    margin: UiRect::bottom(Val::Px(16.0)),
+                    ..default()
+                },
+            ));
+
+            parent
+                .spawn((
+                    Node {
+                        width: Val::Px(BAR_WIDTH),
+                        height: Val::Px(BAR_HEIGHT),
+                        border: UiRect::all(Val::Px(2.0)),
+                        ..default()
+                    },
+                    BorderColor(Color::WHITE),
+                ))
+                .with_children(|bar| {
+                    bar.spawn((
+                        Node {
+                            width: Val::Percent(0.0),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgb(0.3, 0.7, 0.3)),
+                        ProgressBar,
+                    ));
+                });
+
+            parent.spawn((
+                Text::new(""),
+                TextFont {
+                    font_size: 16.0,
+                    ..default()
+                },
+                TextColor(Color::srgb(0.8, 0.8, 0.8)),
+                Node {
+                    margin: UiRect::top(Val::Px(8.0)),
+                    ..default()
+                },
+                ProgressText,
+            ));
+        });
+}
+
+fn update_loading_screen(
+    progress: Res<LoadingProgress>,
+    mut bar_query: Query<&mut Node, With<ProgressBar>>,
+    mut text_query: Query<&mut Text, With<ProgressText>>,
+) {
+    if !progress.is_changed() {
+        return;
+    }
+
+    for mut node in bar_query.iter_mut() {
+        node.width = Val::Percent(progress.fraction() * 100.0);
+    }
+    for mut text in text_query.iter_mut() {
+        text.0 = format!(
+            "{} / {} chunks ({} queued)",
+            progress.generated, progress.required, progress.queued
+        );
+    }
+}
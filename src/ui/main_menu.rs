@@ -0,0 +1,393 @@
+use bevy::{
+    app::AppExit,
+    input::{keyboard::KeyboardInput, ButtonState},
+    prelude::*,
+};
+
+use super::widgets::{button_hover, edit_text, menu_button};
+use crate::{
+    chunks::{
+        chunk::CHUNK_SIZE,
+        generate::terrain::{GeneratorKind, GeneratorRegistry},
+    },
+    net::client::Client,
+    save::{list_worlds, parse_seed, WorldInfo},
+    settings::Settings,
+    state::GameState,
+    world::World,
+};
+
+const MAX_FIELD_LENGTH: usize = 32;
+const FIELD_COLOR: Color = Color::srgba(0.05, 0.05, 0.05, 0.9);
+const FIELD_FOCUSED_COLOR: Color = Color::srgba(0.1, 0.1, 0.2, 0.9);
+
+pub struct MainMenuPlugin;
+
+impl Plugin for MainMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::MainMenu), spawn_main_menu)
+            .add_systems(
+                Update,
+                (
+                    focus_text_field,
+                    type_into_text_field,
+                    update_text_fields,
+                    main_menu_buttons,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::MainMenu)),
+            );
+    }
+}
+
+#[derive(Component, Clone)]
+enum MainMenuButton {
+    ToggleHardcore,
+    CycleGenerator,
+    CreateWorld,
+    LoadWorld(WorldInfo),
+    JoinServer,
+    Quit,
+}
+
+#[derive(Component, Default)]
+struct TextField {
+    value: String,
+    focused: bool,
+}
+
+#[derive(Component)]
+struct WorldNameField;
+
+#[derive(Component)]
+struct SeedField;
+
+/// Whether the world being created is hardcore, shown on the button that toggles it.
+#[derive(Component, Default)]
+struct HardcoreOption(bool);
+
+/// The generator the world being created will use, shown on the button that cycles it.
+#[derive(Component)]
+struct GeneratorOption(GeneratorKind);
+
+fn generator_label(generator: &GeneratorKind) -> String {
+    format!("World type: {}", generator.name())
+}
+
+fn hardcore_label(hardcore: bool) -> &'static str {
+    if hardcore {
+        "Hardcore: On"
+    } else {
+        "Hardcore: Off"
+    }
+}
+
+fn spawn_main_menu(mut commands: Commands, settings_query: Query<&Settings>) {
+    let worlds = list_worlds();
+    let generator = settings_query
+        .get_single()
+        .map(|settings| settings.world.generator.clone())
+        .unwrap_or_default();
+
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgb(0.1, 0.12, 0.15)),
+            GlobalZIndex(10),
+            StateScoped(GameState::MainMenu),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("Rustcraft"),
+                TextFont {
+                    font_size: 48.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                Node {
+                    margin: UiRect::bottom(Val::Px(24.0)),
+                    ..default()
+                },
+            ));
+
+            label(parent, "World name");
+            text_field(parent, WorldNameField);
+            label(parent, "Seed (leave blank for random)");
+            text_field(parent, SeedField);
+            menu_button(
+                parent,
+                &generator_label(&generator),
+                (MainMenuButton::CycleGenerator, GeneratorOption(generator)),
+            );
+            menu_button(
+                parent,
+                hardcore_label(false),
+                (MainMenuButton::ToggleHardcore, HardcoreOption::default()),
+            );
+            menu_button(parent, "Create World", MainMenuButton::CreateWorld);
+
+            if !worlds.is_empty() {
+                label(parent, "Load world");
+                for world in worlds {
+                    let name = match (world.hardcore, world.game_over) {
+                        (true, true) => format!("{} (game over)", world.name),
+                        (true, false) => format!("{} (hardcore)", world.name),
+                        _ => world.name.clone(),
+                    };
+                    menu_button(parent, &name, MainMenuButton::LoadWorld(world));
+                }
+            }
+
+            menu_button(parent, "Join Server", MainMenuButton::JoinServer);
+            menu_button(parent, "Quit", MainMenuButton::Quit);
+        });
+}
+
+fn label(parent: &mut ChildBuilder, text: &str) {
+    parent.spawn((
+        Text::new(text),
+        TextFont {
+            font_size: 18.0,
+            ..default()
+        },
+        TextColor(Color::srgb(0.8, 0.8, 0.8)),
+        Node {
+            margin: UiRect::top(Val::Px(12.0)),
+            ..default()
+        },
+    ));
+}
+
+fn text_field(parent: &mut ChildBuilder, marker: impl Component) {
+    parent
+        .spawn((
+            Button,
+            Node {
+                width: Val::Px(220.0),
+                height: Val::Px(36.0),
+                margin: UiRect::all(Val::Px(6.0)),
+                padding: UiRect::horizontal(Val::Px(8.0)),
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(FIELD_COLOR),
+            TextField::default(),
+            marker,
+        ))
+        .with_children(|field| {
+            field.spawn((
+                Text::new(""),
+                TextFont {
+                    font_size: 20.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+        });
+}
+
+fn focus_text_field(
+    interaction_query: Query<(Entity, &Interaction), (Changed<Interaction>, With<TextField>)>,
+    mut field_query: Query<(Entity, &mut TextField)>,
+) {
+    let Some((pressed, _)) = interaction_query
+        .iter()
+        .find(|(_, interaction)| **interaction == Interaction::Pressed)
+    else {
+        return;
+    };
+
+    for (entity, mut field) in field_query.iter_mut() {
+        field.focused = entity == pressed;
+    }
+}
+
+fn type_into_text_field(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    mut field_query: Query<&mut TextField>,
+) {
+    let Some(mut field) = field_query.iter_mut().find(|field| field.focused) else {
+        keyboard_events.clear();
+        return;
+    };
+
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+
+        edit_text(&mut field.value, &event.logical_key, MAX_FIELD_LENGTH);
+    }
+}
+
+fn update_text_fields(
+    mut field_query: Query<(&TextField, &Children, &mut BackgroundColor), Changed<TextField>>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (field, children, mut background) in field_query.iter_mut() {
+        background.0 = if field.focused {
+            FIELD_FOCUSED_COLOR
+        } else {
+            FIELD_COLOR
+        };
+
+        for child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                text.0 = if field.focused {
+                    format!("{}_", field.value)
+                } else {
+                    field.value.clone()
+                };
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn main_menu_buttons(
+    mut commands: Commands,
+    mut button_query: Query<
+        (&Interaction, &MainMenuButton, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+    mut hardcore_query: Query<(&mut HardcoreOption, &Children)>,
+    mut generator_query: Query<(&mut GeneratorOption, &Children)>,
+    mut text_query: Query<&mut Text>,
+    name_query: Query<&TextField, With<WorldNameField>>,
+    seed_query: Query<&TextField, With<SeedField>>,
+    settings_query: Query<&Settings>,
+    generators: Res<GeneratorRegistry>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for (interaction, button, mut background) in button_query.iter_mut() {
+        button_hover(*interaction, &mut background);
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        let world_info = match button {
+            MainMenuButton::ToggleHardcore => {
+                if let Ok((mut option, children)) = hardcore_query.get_single_mut() {
+                    option.0 = !option.0;
+                    for child in children.iter() {
+                        if let Ok(mut text) = text_query.get_mut(*child) {
+                            text.0 = hardcore_label(option.0).to_string();
+                        }
+                    }
+                }
+                continue;
+            }
+            MainMenuButton::CycleGenerator => {
+                if let Ok((mut option, children)) = generator_query.get_single_mut() {
+                    option.0 = generators.next(&option.0);
+                    for child in children.iter() {
+                        if let Ok(mut text) = text_query.get_mut(*child) {
+                            text.0 = generator_label(&option.0);
+                        }
+                    }
+                }
+                continue;
+            }
+            MainMenuButton::CreateWorld => {
+                let name = name_query
+                    .get_single()
+                    .map(|field| field.value.as_str())
+                    .unwrap_or_default();
+                let seed = seed_query
+                    .get_single()
+                    .map(|field| field.value.as_str())
+                    .unwrap_or_default();
+
+                let chunk_size = settings_query
+                    .get_single()
+                    .map(|settings| settings.world.chunk_size())
+                    .unwrap_or(CHUNK_SIZE);
+                let generator = generator_query
+                    .get_single()
+                    .map(|(option, _)| option.0.clone())
+                    .unwrap_or_default();
+                let hardcore = hardcore_query
+                    .get_single()
+                    .is_ok_and(|(option, _)| option.0);
+                let world_info = WorldInfo::new(name, parse_seed(seed))
+                    .with_chunk_size(chunk_size)
+                    .with_generator(generator)
+                    .with_hardcore(hardcore)
+                    .with_unique_name();
+                if let Err(e) = world_info.save() {
+                    warn!("failed to save world '{}': {}", world_info.name, e);
+                }
+                world_info
+            }
+            MainMenuButton::LoadWorld(world_info) => {
+                // keeps any ids given to new blocks when the world was loaded
+                if let Err(e) = world_info.save() {
+                    warn!("failed to save world '{}': {}", world_info.name, e);
+                }
+                world_info.clone()
+            }
+            MainMenuButton::JoinServer => {
+                let Some(addr) = settings_query
+                    .get_single()
+                    .ok()
+                    .and_then(|settings| settings.network.server)
+                else {
+                    warn!("no server configured, set network.server in settings.toml");
+                    continue;
+                };
+
+                match Client::connect(addr) {
+                    Ok((client, world_info)) => {
+                        commands.insert_resource(client);
+                        world_info
+                    }
+                    Err(e) => {
+                        warn!("failed to join {}: {}", addr, e);
+                        continue;
+                    }
+                }
+            }
+            MainMenuButton::Quit => {
+                exit.send(AppExit::Success);
+                continue;
+            }
+        };
+
+        let world_settings = settings_query
+            .get_single()
+            .map(|settings| settings.world.clone())
+            .unwrap_or_default();
+        let world = match World::with_chunk_size(world_info.seed, world_info.chunk_size)
+            .with_ores(world_settings.ores)
+            .with_generator(world_info.generator.clone(), &generators)
+        {
+            Ok(world) => world.with_history(world_settings.edit_history as usize),
+            Err(e) => {
+                warn!("failed to load world '{}': {}", world_info.name, e);
+                // a server's world can be made with a mod this game doesn't have either
+                commands.remove_resource::<Client>();
+                continue;
+            }
+        };
+
+        info!(
+            "entering {} world '{}' with seed {} and {}³ chunks",
+            world_info.generator.name(),
+            world_info.name,
+            world_info.seed,
+            world_info.chunk_size
+        );
+        commands.insert_resource(world);
+        commands.insert_resource(world_info);
+        next_state.set(GameState::Loading);
+    }
+}
@@ -0,0 +1,10 @@
+pub mod console;
+pub mod death;
+pub mod hud;
+pub mod loading;
+pub mod main_menu;
+pub mod pause;
+pub mod toast;
+pub mod underwater;
+pub mod watch;
+pub mod widgets;
@@ -0,0 +1,111 @@
+use bevy::{app::AppExit, prelude::*};
+
+use super::widgets::{button_hover, drag_sliders, menu_button, slider, Slider};
+use crate::{settings::Settings, state::GameState};
+
+pub struct PauseMenuPlugin;
+
+impl Plugin for PauseMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Paused), spawn_pause_menu)
+            .add_systems(
+                Update,
+                (pause_menu_buttons, (drag_sliders, volume_sliders).chain())
+                    .run_if(in_state(GameState::Paused)),
+            );
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum PauseMenuButton {
+    Resume,
+    Quit,
+}
+
+/// Sliders setting the volumes in `Settings::audio`.
+#[derive(Component, Clone, Copy)]
+enum VolumeSlider {
+    Master,
+    Music,
+    Ambient,
+}
+
+fn spawn_pause_menu(mut commands: Commands, settings_query: Query<&Settings>) {
+    let audio = settings_query
+        .get_single()
+        .map(|settings| settings.audio)
+        .unwrap_or_default();
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+            StateScoped(GameState::Paused),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                Text::new("Paused"),
+                TextFont {
+                    font_size: 36.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+                Node {
+                    margin: UiRect::bottom(Val::Px(24.0)),
+                    ..default()
+                },
+            ));
+            menu_button(parent, "Resume", PauseMenuButton::Resume);
+            menu_button(parent, "Quit", PauseMenuButton::Quit);
+            slider(parent, "Volume", audio.master, VolumeSlider::Master);
+            slider(parent, "Music", audio.music, VolumeSlider::Music);
+            slider(parent, "Ambient", audio.ambient, VolumeSlider::Ambient);
+        });
+}
+
+fn pause_menu_buttons(
+    mut button_query: Query<
+        (&Interaction, &PauseMenuButton, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for (interaction, button, mut background) in button_query.iter_mut() {
+        button_hover(*interaction, &mut background);
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match button {
+            PauseMenuButton::Resume => next_state.set(GameState::InGame),
+            PauseMenuButton::Quit => {
+                exit.send(AppExit::Success);
+            }
+        }
+    }
+}
+
+fn volume_sliders(
+    slider_query: Query<(&Slider, &VolumeSlider), Changed<Slider>>,
+    mut settings_query: Query<&mut Settings>,
+) {
+    let Ok(mut settings) = settings_query.get_single_mut() else {
+        return;
+    };
+    for (slider, volume) in slider_query.iter() {
+        let audio = &mut settings.audio;
+        match volume {
+            VolumeSlider::Master => audio.master = slider.0,
+            VolumeSlider::Music => audio.music = slider.0,
+            VolumeSlider::Ambient => audio.ambient = slider.0,
+        }
+    }
+}
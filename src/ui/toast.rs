@@ -0,0 +1,96 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+/// Toasts shown at once; older ones are dropped to make room.
+const MAX_TOASTS: usize = 4;
+/// Seconds each toast stays on screen.
+const TOAST_DISPLAY_TIME: f32 = 8.0;
+const TOAST_COLOR: Color = Color::srgb(1.0, 0.85, 0.3);
+
+/// Shows warnings the player should notice, such as a corrupt chunk being recovered, briefly at
+/// the top of the screen.
+pub struct ToastPlugin;
+
+impl Plugin for ToastPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Toast>()
+            .init_resource::<Toasts>()
+            .add_systems(Startup, spawn_toasts)
+            .add_systems(Update, (record_toasts, update_toast_text).chain());
+    }
+}
+
+/// A warning to show the player.
+#[derive(Event, Debug, Clone)]
+pub struct Toast(pub String);
+
+/// Toasts on screen, with the elapsed time each was shown at.
+#[derive(Resource, Default)]
+struct Toasts(VecDeque<(String, f32)>);
+
+#[derive(Component)]
+struct ToastText;
+
+fn spawn_toasts(mut commands: Commands) {
+    commands
+        .spawn(Node {
+            width: Val::Percent(100.0),
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        })
+        .with_child((
+            Text::default(),
+            TextFont {
+                font_size: 18.0,
+                ..default()
+            },
+            TextColor(TOAST_COLOR),
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+            Node {
+                padding: UiRect::all(Val::Px(4.0)),
+                ..default()
+            },
+            Visibility::Hidden,
+            ToastText,
+        ));
+}
+
+fn record_toasts(time: Res<Time>, mut toasts: ResMut<Toasts>, mut events: EventReader<Toast>) {
+    for Toast(message) in events.read() {
+        toasts.0.push_back((message.clone(), time.elapsed_secs()));
+        while toasts.0.len() > MAX_TOASTS {
+            toasts.0.pop_front();
+        }
+    }
+
+    let now = time.elapsed_secs();
+    while toasts
+        .0
+        .front()
+        .is_some_and(|(_, shown)| now - shown >= TOAST_DISPLAY_TIME)
+    {
+        toasts.0.pop_front();
+    }
+}
+
+fn update_toast_text(
+    toasts: Res<Toasts>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<ToastText>>,
+) {
+    if !toasts.is_changed() {
+        return;
+    }
+
+    for (mut text, mut visibility) in text_query.iter_mut() {
+        let lines: Vec<&str> = toasts.0.iter().map(|(line, _)| line.as_str()).collect();
+        text.0 = lines.join("\n");
+        visibility.set_if_neq(if lines.is_empty() {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        });
+    }
+}
@@ -0,0 +1,174 @@
+//! Tints the screen while the camera is in water or lava. Where the fluid's surface crosses the
+//! camera's near plane only the part of the screen below it is tinted, so the surface doesn't
+//! appear cut in half as the camera bobs through it.
+
+use bevy::{math::I64Vec3, prelude::*, render::camera::Projection};
+
+use crate::{block::BlockType, chunks::chunk::FULL_FLUID_LEVEL, state::GameState, world::World};
+
+const WATER_TINT: Color = Color::srgba(0.1, 0.25, 0.6, 0.4);
+const LAVA_TINT: Color = Color::srgba(0.9, 0.3, 0.05, 0.75);
+
+pub struct UnderwaterPlugin;
+
+impl Plugin for UnderwaterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_overlay)
+            .add_systems(Update, update_overlay.run_if(in_state(GameState::InGame)));
+    }
+}
+
+#[derive(Component)]
+struct FluidOverlay;
+
+fn spawn_overlay(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(0.0),
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(0.0),
+            display: Display::None,
+            ..default()
+        },
+        BackgroundColor(WATER_TINT),
+        FluidOverlay,
+    ));
+}
+
+/// The fluid whose surface is nearest the eye, and the height of that surface. A fluid with more
+/// of itself above has no surface nearby, so it's given one a block above the eye.
+pub fn fluid_surface(world: &mut World, eye: Vec3) -> Option<(BlockType, f32)> {
+    // blocks are centred on their coordinate
+    let block = (eye + 0.5).floor().as_i64vec3();
+    for block in [block, block - I64Vec3::Y] {
+        let fluid = world.get_block(block);
+        if !fluid.is_fluid() {
+            continue;
+        }
+        if world.get_block(block + I64Vec3::Y) == fluid {
+            return Some((fluid, eye.y + 1.0));
+        }
+        let level = world.get_fluid_level(block) as f32 / FULL_FLUID_LEVEL as f32;
+        return Some((fluid, block.y as f32 - 0.5 + level));
+    }
+    None
+}
+
+/// Fraction of the screen, from the bottom, below a fluid surface at height `surface`. The
+/// surface is found where it crosses the near plane, `near` in front of the eye, which is
+/// `2 * near * tan(fov / 2)` tall.
+pub fn submerged_fraction(
+    eye: Vec3,
+    forward: Vec3,
+    up: Vec3,
+    surface: f32,
+    near: f32,
+    fov: f32,
+) -> f32 {
+    let centre = eye.y + forward.y * near;
+    let half_height = near * (fov / 2.0).tan() * up.y;
+    if half_height <= f32::EPSILON {
+        // looking straight up or down, the surface is entirely on one side of the screen
+        return if surface > centre { 1.0 } else { 0.0 };
+    }
+    let crossing = (surface - centre) / half_height;
+    ((crossing + 1.0) / 2.0).clamp(0.0, 1.0)
+}
+
+fn update_overlay(
+    mut world: ResMut<World>,
+    camera_query: Query<(&GlobalTransform, &Projection), With<Camera3d>>,
+    mut overlay_query: Query<(&mut Node, &mut BackgroundColor), With<FluidOverlay>>,
+) {
+    let (Ok((camera, projection)), Ok((mut node, mut background))) =
+        (camera_query.get_single(), overlay_query.get_single_mut())
+    else {
+        return;
+    };
+
+    let eye = camera.translation();
+    let fraction = match (fluid_surface(&mut world, eye), projection) {
+        (Some((fluid, surface)), Projection::Perspective(perspective)) => {
+            background.0 = match fluid {
+                BlockType::Lava => LAVA_TINT,
+                _ => WATER_TINT,
+            };
+            submerged_fraction(
+                eye,
+                *camera.forward(),
+                *camera.up(),
+                surface,
+                perspective.near,
+                perspective.fov,
+            )
+        }
+        _ => 0.0,
+    };
+
+    node.display = if fraction > 0.0 {
+        Display::Flex
+    } else {
+        Display::None
+    };
+    node.height = Val::Percent(fraction * 100.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_4;
+
+    use bevy::math::{I64Vec3, U16Vec3, Vec3};
+
+    use super::{fluid_surface, submerged_fraction};
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        world::World,
+    };
+
+    const NEAR: f32 = 0.1;
+
+    #[test]
+    fn test_submerged_fraction() {
+        let level = |eye_y: f32, surface: f32| {
+            submerged_fraction(
+                Vec3::new(0.0, eye_y, 0.0),
+                Vec3::Z,
+                Vec3::Y,
+                surface,
+                NEAR,
+                FRAC_PI_4,
+            )
+        };
+        assert_eq!(0.0, level(10.0, 5.0));
+        assert_eq!(1.0, level(5.0, 10.0));
+        assert_eq!(0.5, level(5.0, 5.0));
+        // just below the surface most, but not all, of the screen is under it
+        let bobbing = level(4.99, 5.0);
+        assert!(bobbing > 0.5 && bobbing < 1.0);
+
+        let down = submerged_fraction(Vec3::splat(5.0), Vec3::NEG_Y, Vec3::Z, 5.05, NEAR, 1.0);
+        assert_eq!(1.0, down);
+    }
+
+    #[test]
+    fn test_fluid_surface() {
+        let mut world = World::new(0);
+        let mut chunk = ChunkData::default();
+        for y in 0..4 {
+            chunk.set_block_at(U16Vec3::new(0, y, 0), BlockType::Water);
+        }
+        chunk.set_fluid_at(U16Vec3::new(0, 3, 0), BlockType::Water, 4);
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), chunk);
+
+        // the top water block is half full, so its surface is level with its centre
+        let (fluid, surface) = fluid_surface(&mut world, Vec3::new(0.0, 3.2, 0.0)).unwrap();
+        assert_eq!((BlockType::Water, 3.0), (fluid, surface));
+        let (_, surface) = fluid_surface(&mut world, Vec3::new(0.0, 3.6, 0.0)).unwrap();
+        assert_eq!(3.0, surface);
+        let (_, deep) = fluid_surface(&mut world, Vec3::new(0.0, 1.0, 0.0)).unwrap();
+        assert!(deep > 1.0);
+        assert_eq!(None, fluid_surface(&mut world, Vec3::new(0.0, 5.0, 0.0)));
+    }
+}
@@ -0,0 +1,307 @@
+//! `/watch chunk <x> <z>` shows what the chunk pipeline is doing with a column of chunks as it
+//! happens, for debugging generation, meshing and edits.
+
+use bevy::{math::I64Vec2, prelude::*};
+
+use crate::{
+    chunks::{
+        chunk_loader::{
+            ChunkLoader, DirtyChunk, GenerateChunkData, GenerateChunkMesh, TranslucentPart,
+        },
+        position::BlockPos,
+    },
+    command::{CommandArgs, CommandResult},
+    falling_block::FallingBlock,
+    interaction::BlockEdited,
+    item::ItemDrop,
+    mob::Mob,
+    player::Player,
+    state::GameState,
+    world::World,
+};
+
+const WATCH_COLOR: Color = Color::srgb(0.6, 1.0, 0.8);
+
+/// Draws the panel for `/watch`.
+pub struct WatchPlugin;
+
+impl Plugin for WatchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Watch>()
+            .add_systems(Startup, spawn_watch_panel)
+            .add_systems(
+                Update,
+                (record_watched_edits, update_watch_panel)
+                    .chain()
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+/// The chunk column being watched, if any.
+#[derive(Resource, Default)]
+pub struct Watch(Option<WatchedColumn>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchedColumn {
+    /// The column's chunk x and z coordinates.
+    pub column: I64Vec2,
+    /// The last edit made inside the column since it was watched, and the elapsed time then.
+    pub last_edit: Option<(BlockEdited, f32)>,
+}
+
+impl WatchedColumn {
+    pub fn new(column: I64Vec2) -> Self {
+        Self {
+            column,
+            last_edit: None,
+        }
+    }
+
+    /// Remembers `edit` if it was made inside the column.
+    pub fn record(&mut self, edit: BlockEdited, chunk_size: u16, now: f32) {
+        let chunk = BlockPos(edit.position).chunk(chunk_size).0;
+        if I64Vec2::new(chunk.x, chunk.z) == self.column {
+            self.last_edit = Some((edit, now));
+        }
+    }
+
+    fn describe_last_edit(&self, now: f32) -> String {
+        let Some((edit, at)) = self.last_edit else {
+            return "no edits since watching".to_string();
+        };
+        format!(
+            "last edit: {} at {} by {}, {:.0}s ago",
+            edit.block.name(),
+            edit.position,
+            edit.source.name(),
+            now - at
+        )
+    }
+}
+
+/// What the panel shows of one loaded chunk.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct ChunkStatus {
+    y: i64,
+    generating: bool,
+    meshing: bool,
+    dirty: bool,
+    edited: bool,
+    vertices: usize,
+    entities: usize,
+}
+
+impl ChunkStatus {
+    fn describe(&self) -> String {
+        let state = if self.generating {
+            "generating"
+        } else if self.meshing {
+            "meshing"
+        } else {
+            "ready"
+        };
+        let mut line = format!(
+            "y {}: {}, {} vertices, {} entities",
+            self.y, state, self.vertices, self.entities
+        );
+        if self.dirty {
+            line.push_str(", dirty");
+        }
+        if self.edited {
+            line.push_str(", edited");
+        }
+        line
+    }
+}
+
+#[derive(Component)]
+struct WatchText;
+
+fn spawn_watch_panel(mut commands: Commands) {
+    commands.spawn((
+        Text::default(),
+        TextFont {
+            font_size: 14.0,
+            ..default()
+        },
+        TextColor(WATCH_COLOR),
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.6)),
+        Node {
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.0),
+            top: Val::Px(10.0),
+            padding: UiRect::all(Val::Px(4.0)),
+            ..default()
+        },
+        Visibility::Hidden,
+        WatchText,
+    ));
+}
+
+fn record_watched_edits(
+    time: Res<Time>,
+    world: Res<World>,
+    mut watch: ResMut<Watch>,
+    mut edited: EventReader<BlockEdited>,
+) {
+    let Some(watched) = watch.0.as_mut() else {
+        edited.clear();
+        return;
+    };
+    for edit in edited.read() {
+        watched.record(*edit, world.chunk_size(), time.elapsed_secs());
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_watch_panel(
+    time: Res<Time>,
+    watch: Res<Watch>,
+    world: Res<World>,
+    chunk_loader: Res<ChunkLoader>,
+    meshes: Res<Assets<Mesh>>,
+    chunks_query: Query<(
+        Has<GenerateChunkData>,
+        Has<GenerateChunkMesh>,
+        Has<DirtyChunk>,
+        Option<&Mesh3d>,
+        Option<&TranslucentPart>,
+    )>,
+    entity_query: Query<
+        &Transform,
+        Or<(With<Mob>, With<ItemDrop>, With<FallingBlock>, With<Player>)>,
+    >,
+    mut panel_query: Query<(&mut Text, &mut Visibility), With<WatchText>>,
+) {
+    let Ok((mut text, mut visibility)) = panel_query.get_single_mut() else {
+        return;
+    };
+    let Some(watched) = &watch.0 else {
+        visibility.set_if_neq(Visibility::Hidden);
+        return;
+    };
+    visibility.set_if_neq(Visibility::Inherited);
+
+    let chunk_size = world.chunk_size();
+    let vertices = |mesh: Option<&Mesh3d>| {
+        mesh.and_then(|mesh| meshes.get(&mesh.0))
+            .map_or(0, Mesh::count_vertices)
+    };
+    let mut statuses: Vec<ChunkStatus> = chunk_loader
+        .loaded_chunks()
+        .filter(|(coord, _)| I64Vec2::new(coord.0.x, coord.0.z) == watched.column)
+        .filter_map(|(coord, entity)| {
+            let (generating, meshing, dirty, mesh, part) = chunks_query.get(entity).ok()?;
+            let translucent = part
+                .and_then(|part| chunks_query.get(part.entity()).ok())
+                .map_or(0, |(.., mesh, _)| vertices(mesh));
+            let entities = entity_query
+                .iter()
+                .filter(|transform| {
+                    BlockPos::from_world(transform.translation).chunk(chunk_size) == coord
+                })
+                .count();
+            Some(ChunkStatus {
+                y: coord.0.y,
+                generating,
+                meshing,
+                dirty,
+                edited: world.is_edited(coord),
+                vertices: vertices(mesh) + translucent,
+                entities,
+            })
+        })
+        .collect();
+    statuses.sort_by_key(|status| status.y);
+
+    let mut lines = vec![format!(
+        "watching chunk {} {}",
+        watched.column.x, watched.column.y
+    )];
+    if statuses.is_empty() {
+        lines.push("not loaded".to_string());
+    }
+    lines.extend(statuses.iter().map(ChunkStatus::describe));
+    lines.push(watched.describe_last_edit(time.elapsed_secs()));
+    text.0 = lines.join("\n");
+}
+
+/// `/watch chunk [x z]` shows a panel following the column of chunks at chunk coordinates `x z`,
+/// or the player's, and `/watch off` hides it.
+pub fn watch_command(
+    In(mut args): In<CommandArgs>,
+    world: Res<World>,
+    mut watch: ResMut<Watch>,
+) -> CommandResult {
+    let target = args.word("chunk or off")?;
+    match target.as_str() {
+        "off" => {
+            args.finish()?;
+            watch.0 = None;
+            Ok("stopped watching".to_string())
+        }
+        "chunk" => {
+            let column = if args.is_empty() {
+                let origin = args
+                    .origin()
+                    .ok_or_else(|| "there is no player to watch around".to_string())?;
+                let chunk = BlockPos::from_world(origin.translation)
+                    .chunk(world.chunk_size())
+                    .0;
+                I64Vec2::new(chunk.x, chunk.z)
+            } else {
+                I64Vec2::new(args.number("a chunk x")?, args.number("a chunk z")?)
+            };
+            args.finish()?;
+            watch.0 = Some(WatchedColumn::new(column));
+            Ok(format!("watching chunk {} {}", column.x, column.y))
+        }
+        _ => Err(format!("can't watch '{target}', try chunk or off")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::{I64Vec2, I64Vec3};
+
+    use super::{ChunkStatus, WatchedColumn};
+    use crate::{
+        block::BlockType,
+        interaction::{BlockEdited, EditSource},
+    };
+
+    #[test]
+    fn test_watched_column_records_its_own_edits() {
+        let mut watched = WatchedColumn::new(I64Vec2::new(-1, 2));
+        let edit = |position| BlockEdited {
+            position,
+            block: BlockType::Stone,
+            source: EditSource::Fluid,
+        };
+        watched.record(edit(I64Vec3::new(0, 5, 40)), 16, 1.0);
+        assert_eq!(None, watched.last_edit);
+
+        watched.record(edit(I64Vec3::new(-1, 100, 32)), 16, 2.0);
+        assert_eq!(
+            "last edit: Stone at [-1, 100, 32] by fluid, 3s ago",
+            watched.describe_last_edit(5.0)
+        );
+    }
+
+    #[test]
+    fn test_describe_chunk_status() {
+        let status = ChunkStatus {
+            y: 3,
+            meshing: true,
+            dirty: true,
+            vertices: 1200,
+            entities: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            "y 3: meshing, 1200 vertices, 2 entities, dirty",
+            status.describe()
+        );
+    }
+}
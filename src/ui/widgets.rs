@@ -0,0 +1,147 @@
+use bevy::{input::keyboard::Key, prelude::*, ui::RelativeCursorPosition};
+
+const BUTTON_COLOR: Color = Color::srgba(0.15, 0.15, 0.15, 0.9);
+const BUTTON_HOVER_COLOR: Color = Color::srgba(0.3, 0.3, 0.3, 0.9);
+
+pub fn menu_button(parent: &mut ChildBuilder, label: &str, marker: impl Bundle) {
+    parent
+        .spawn((
+            Button,
+            Node {
+                width: Val::Px(220.0),
+                height: Val::Px(48.0),
+                margin: UiRect::all(Val::Px(6.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(BUTTON_COLOR),
+            marker,
+        ))
+        .with_children(|button| {
+            button.spawn((
+                Text::new(label),
+                TextFont {
+                    font_size: 22.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+        });
+}
+
+/// Highlights a menu button while it is hovered or pressed.
+pub fn button_hover(interaction: Interaction, background: &mut BackgroundColor) {
+    background.0 = match interaction {
+        Interaction::Hovered | Interaction::Pressed => BUTTON_HOVER_COLOR,
+        Interaction::None => BUTTON_COLOR,
+    };
+}
+
+/// Applies a typed key to a text input, returning `false` for keys that don't edit text.
+pub fn edit_text(value: &mut String, key: &Key, max_length: usize) -> bool {
+    match key {
+        Key::Backspace => {
+            value.pop();
+        }
+        Key::Space => {
+            if value.chars().count() < max_length {
+                value.push(' ');
+            }
+        }
+        Key::Character(characters) => {
+            for c in characters.chars().filter(|c| !c.is_control()) {
+                if value.chars().count() < max_length {
+                    value.push(c);
+                }
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+const SLIDER_TRACK_COLOR: Color = Color::srgba(0.1, 0.1, 0.1, 0.9);
+const SLIDER_FILL_COLOR: Color = Color::srgb(0.45, 0.6, 0.35);
+
+/// A value from `0.0` to `1.0` set by dragging along a slider.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Slider(pub f32);
+
+/// The part of a slider's track filled up to its value.
+#[derive(Component)]
+pub struct SliderFill;
+
+pub fn slider(parent: &mut ChildBuilder, label: &str, value: f32, marker: impl Bundle) {
+    parent
+        .spawn(Node {
+            width: Val::Px(220.0),
+            margin: UiRect::all(Val::Px(6.0)),
+            flex_direction: FlexDirection::Column,
+            ..default()
+        })
+        .with_children(|row| {
+            row.spawn((
+                Text::new(label),
+                TextFont {
+                    font_size: 18.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+            row.spawn((
+                Button,
+                RelativeCursorPosition::default(),
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Px(16.0),
+                    margin: UiRect::top(Val::Px(4.0)),
+                    ..default()
+                },
+                BackgroundColor(SLIDER_TRACK_COLOR),
+                Slider(value),
+                marker,
+            ))
+            .with_children(|track| {
+                track.spawn((
+                    Node {
+                        width: Val::Percent(value * 100.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    BackgroundColor(SLIDER_FILL_COLOR),
+                    SliderFill,
+                ));
+            });
+        });
+}
+
+/// Moves sliders held down with the mouse to the cursor.
+pub fn drag_sliders(
+    mut slider_query: Query<(
+        &Interaction,
+        &RelativeCursorPosition,
+        &mut Slider,
+        &Children,
+    )>,
+    mut fill_query: Query<&mut Node, With<SliderFill>>,
+) {
+    for (interaction, cursor, mut slider, children) in slider_query.iter_mut() {
+        let Some(cursor) = cursor
+            .normalized
+            .filter(|_| *interaction == Interaction::Pressed)
+        else {
+            continue;
+        };
+        let value = cursor.x.clamp(0.0, 1.0);
+        if slider.0 == value {
+            continue;
+        }
+        slider.0 = value;
+        for child in children.iter() {
+            if let Ok(mut fill) = fill_query.get_mut(*child) {
+                fill.width = Val::Percent(value * 100.0);
+            }
+        }
+    }
+}
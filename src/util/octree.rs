@@ -1,25 +1,54 @@
-use std::sync::{Arc, RwLock};
+use std::{
+    ops::AddAssign,
+    sync::{Arc, RwLock},
+};
 
 use bevy::{math::Vec3, utils::HashMap};
 
+/// How much data a branch of the octree holds, kept up to date on every insert and removal so
+/// regions can be summarised without visiting each leaf.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct OctreeCounts {
+    pub loaded: u32,
+    /// Loaded data that was inserted as occupied, e.g. chunks that aren't all air.
+    pub occupied: u32,
+}
+
+impl AddAssign for OctreeCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.loaded += other.loaded;
+        self.occupied += other.occupied;
+    }
+}
+
 pub struct OctreeNode<Data> {
     id: usize,
+    parent: Option<usize>,
     centre: Vec3,
     pub size: f32,
     depth: u8,
     children: Option<[usize; 8]>,
     data: Option<Arc<Data>>,
+    /// Point the data was inserted at, which may be anywhere within the node.
+    point: Vec3,
+    occupied: bool,
+    /// Totals for this node and everything below it.
+    counts: OctreeCounts,
 }
 
 impl<Data> OctreeNode<Data> {
-    fn new(id: usize, centre: Vec3, size: f32, depth: u8, data: Option<Arc<Data>>) -> Self {
+    fn new(id: usize, parent: Option<usize>, centre: Vec3, size: f32, depth: u8) -> Self {
         Self {
             id,
+            parent,
             children: None,
             centre,
             size,
             depth,
-            data,
+            data: None,
+            point: centre,
+            occupied: false,
+            counts: OctreeCounts::default(),
         }
     }
 
@@ -35,12 +64,24 @@ impl<Data> OctreeNode<Data> {
         self.data.clone()
     }
 
-    pub fn set_data(&mut self, data: Arc<Data>) {
-        self.data = Some(data);
+    pub fn has_data(&self) -> bool {
+        self.data.is_some()
     }
 
-    pub fn clear_data(&mut self) {
-        self.data = None;
+    /// Whether the node's data was inserted as occupied.
+    pub fn is_occupied(&self) -> bool {
+        self.data.is_some() && self.occupied
+    }
+
+    fn own_counts(&self) -> OctreeCounts {
+        OctreeCounts {
+            loaded: self.has_data() as u32,
+            occupied: self.is_occupied() as u32,
+        }
+    }
+
+    fn contains_point(&self, min: Vec3, max: Vec3) -> bool {
+        self.point.cmpge(min).all() && self.point.cmple(max).all()
     }
 }
 
@@ -56,10 +97,10 @@ impl<Data> Octree<Data> {
         let root_id = 0;
         let root = Arc::new(RwLock::new(OctreeNode::new(
             root_id,
+            None,
             Vec3::ZERO,
             size,
             0,
-            None,
         )));
 
         let mut arena = HashMap::new();
@@ -83,9 +124,9 @@ impl<Data> Octree<Data> {
         read.centre
     }
 
-    fn insert_node(&mut self, centre: Vec3, size: f32, depth: u8) -> usize {
+    fn insert_node(&mut self, parent: usize, centre: Vec3, size: f32, depth: u8) -> usize {
         let new_id = self.current_id;
-        let new_node = OctreeNode::new(new_id, centre, size, depth, None);
+        let new_node = OctreeNode::new(new_id, Some(parent), centre, size, depth);
         self.arena
             .insert(new_node.id, Arc::new(RwLock::new(new_node)));
         self.current_id += 1;
@@ -145,7 +186,7 @@ impl<Data> Octree<Data> {
         let mut ids = [0; 8];
         for (i, id) in ids.iter_mut().enumerate() {
             let centre = child_centres[i];
-            *id = self.insert_node(centre, child_size, octree_node.depth + 1);
+            *id = self.insert_node(octant, centre, child_size, octree_node.depth + 1);
         }
         octree_node.children = Some(ids);
     }
@@ -172,14 +213,90 @@ impl<Data> Octree<Data> {
     pub fn get_node_by_id(&self, id: usize) -> Arc<RwLock<OctreeNode<Data>>> {
         self.get_node(id)
     }
+
+    /// Stores `data` in the leaf containing `point`, replacing what was there. Occupied data is
+    /// counted separately so `count_in` can tell which regions hold anything of interest.
+    /// Returns the leaf's id.
+    pub fn insert(&mut self, point: Vec3, data: Arc<Data>, occupied: bool) -> usize {
+        let leaf_ref = self.query_octant(point);
+        let mut leaf = leaf_ref.write().unwrap();
+        let before = leaf.own_counts();
+        leaf.data = Some(data);
+        leaf.point = point;
+        leaf.occupied = occupied;
+        let (id, after) = (leaf.id, leaf.own_counts());
+        drop(leaf);
+
+        self.update_counts(id, before, after);
+        id
+    }
+
+    /// Clears the data in the leaf containing `point`.
+    pub fn remove(&mut self, point: Vec3) {
+        let leaf_ref = self.query_octant(point);
+        let mut leaf = leaf_ref.write().unwrap();
+        let before = leaf.own_counts();
+        leaf.data = None;
+        leaf.occupied = false;
+        let id = leaf.id;
+        drop(leaf);
+
+        self.update_counts(id, before, OctreeCounts::default());
+    }
+
+    /// Applies a change in a node's own counts to it and every ancestor.
+    fn update_counts(&self, id: usize, before: OctreeCounts, after: OctreeCounts) {
+        let mut current = Some(id);
+        while let Some(id) = current {
+            let node_ref = self.get_node(id);
+            let mut node = node_ref.write().unwrap();
+            node.counts.loaded = node.counts.loaded + after.loaded - before.loaded;
+            node.counts.occupied = node.counts.occupied + after.occupied - before.occupied;
+            current = node.parent;
+        }
+    }
+
+    /// Totals the data inserted at points within the box from `min` to `max`. Branches with
+    /// nothing loaded are skipped and branches entirely inside the box are taken whole, so large
+    /// regions cost little more than small ones.
+    pub fn count_in(&self, min: Vec3, max: Vec3) -> OctreeCounts {
+        let mut counts = OctreeCounts::default();
+        let mut stack = vec![self._root_id];
+        while let Some(id) = stack.pop() {
+            let node_ref = self.get_node(id);
+            let node = node_ref.read().unwrap();
+            if node.counts.loaded == 0 {
+                continue;
+            }
+
+            let (node_min, node_max) = (node.centre - node.size, node.centre + node.size);
+            if node_max.cmplt(min).any() || node_min.cmpgt(max).any() {
+                continue;
+            }
+            if node_min.cmpge(min).all() && node_max.cmple(max).all() {
+                counts += node.counts;
+                continue;
+            }
+
+            if node.contains_point(min, max) {
+                counts += node.own_counts();
+            }
+            if let Some(children) = node.children {
+                stack.extend(children);
+            }
+        }
+        counts
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
+    use std::sync::Arc;
+
     use bevy::{math::Vec3, utils::HashSet};
 
-    use super::Octree;
+    use super::{Octree, OctreeCounts};
 
     #[test]
     fn test_subdivide() {
@@ -263,4 +380,44 @@ mod tests {
         assert_eq!(8.0, octant.size);
         assert_eq!(Vec3::new(8.0, 8.0, 8.0), octant.centre);
     }
+
+    #[test]
+    fn test_count_in_tracks_inserts_and_removals() {
+        let mut octree = Octree::<u32>::new(16.0, 2);
+        octree.insert(Vec3::new(3.0, 3.0, 3.0), Arc::new(1), true);
+        octree.insert(Vec3::new(11.0, 3.0, 3.0), Arc::new(2), false);
+        octree.insert(Vec3::new(-9.0, 3.0, 3.0), Arc::new(3), true);
+
+        let everything = octree.count_in(Vec3::splat(-16.0), Vec3::splat(16.0));
+        assert_eq!(
+            OctreeCounts {
+                loaded: 3,
+                occupied: 2
+            },
+            everything
+        );
+        assert_eq!(
+            OctreeCounts {
+                loaded: 2,
+                occupied: 1
+            },
+            octree.count_in(Vec3::ZERO, Vec3::splat(16.0))
+        );
+        // part of a leaf that doesn't include its data
+        assert_eq!(
+            OctreeCounts::default(),
+            octree.count_in(Vec3::new(0.0, 0.0, 4.0), Vec3::new(2.0, 2.0, 8.0))
+        );
+
+        // replacing data updates the counts rather than adding to them
+        octree.insert(Vec3::new(11.0, 1.0, 3.0), Arc::new(4), true);
+        octree.remove(Vec3::new(-9.0, 3.0, 3.0));
+        assert_eq!(
+            OctreeCounts {
+                loaded: 2,
+                occupied: 2
+            },
+            octree.count_in(Vec3::splat(-16.0), Vec3::splat(16.0))
+        );
+    }
 }
@@ -135,3 +135,27 @@ pub fn cube() -> Vec<Vertex> {
         },
     ]
 }
+
+/// Two planes crossing along the diagonals of a cell, each facing both ways, in the same corner
+/// order as the faces of `cube`. Normals point up so plants are lit like the ground they grow on,
+/// and the top of the texture is at the top of the planes.
+pub fn cross() -> Vec<Vertex> {
+    let diagonals = [([-0.5, -0.5], [0.5, 0.5]), ([0.5, -0.5], [-0.5, 0.5])];
+    diagonals
+        .into_iter()
+        .flat_map(|(a, b)| [(a, b), (b, a)])
+        .flat_map(|(left, right)| {
+            [
+                ([left[0], 0.5, left[1]], [0.0, 0.0]),
+                ([left[0], -0.5, left[1]], [0.0, 1.0]),
+                ([right[0], 0.5, right[1]], [1.0, 0.0]),
+                ([right[0], -0.5, right[1]], [1.0, 1.0]),
+            ]
+        })
+        .map(|(position, uv)| Vertex {
+            position,
+            normal: [0.0, 1.0, 0.0],
+            uv,
+        })
+        .collect()
+}
@@ -0,0 +1,431 @@
+//! Weather, which passes from clear skies to rain or snow and back. Precipitation falls around
+//! the camera as it would in the biome the camera is over, so deserts stay dry and snowy biomes
+//! get snow whatever the weather, and storms darken the daylight while they last.
+
+use bevy::{
+    app::{App, Plugin, Startup, Update},
+    asset::Assets,
+    color::{Alpha, Color},
+    core_pipeline::core_3d::Camera3d,
+    ecs::{
+        change_detection::DetectChangesMut,
+        component::Component,
+        query::With,
+        schedule::{
+            common_conditions::{not, resource_exists},
+            IntoSystemConfigs,
+        },
+        system::{Commands, In, Local, Query, Res, ResMut, Resource},
+    },
+    math::{I64Vec2, Vec3},
+    pbr::{MeshMaterial3d, NotShadowCaster, StandardMaterial},
+    prelude::{AlphaMode, Mesh3d},
+    render::{
+        mesh::{Indices, Mesh, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+        view::Visibility,
+    },
+    state::condition::in_state,
+    time::Time,
+    transform::components::{GlobalTransform, Transform},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    chunks::generate::biome::Biome,
+    command::{CommandArgs, CommandResult},
+    net::client::Client,
+    snow::accumulate_snow,
+    state::GameState,
+    world::World,
+};
+
+/// Seconds each kind of weather lasts, picked at random within these ranges.
+const CLEAR_DURATION: std::ops::Range<f32> = 300.0..900.0;
+const STORM_DURATION: std::ops::Range<f32> = 120.0..300.0;
+/// Chance that clear weather gives way to snow rather than rain.
+const SNOW_CHANCE: f64 = 0.3;
+/// How quickly a storm builds up and dies down, in intensity per second.
+const INTENSITY_RATE: f32 = 0.1;
+/// Fraction of daylight a storm at full intensity takes away.
+const STORM_DIMMING: f32 = 0.45;
+
+/// Width of the block of drops repeated around the camera.
+const DROP_PERIOD: f32 = 24.0;
+const RAIN_DROPS: usize = 500;
+const SNOW_FLAKES: usize = 300;
+const RAIN_SPEED: f32 = 18.0;
+const SNOW_SPEED: f32 = 2.5;
+const RAIN_COLOR: Color = Color::srgba(0.6, 0.7, 0.9, 0.5);
+const SNOW_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.9);
+/// Blocks the camera must be below the terrain's surface for precipitation to stop, as it's
+/// then taken to be underground.
+const SHELTER_DEPTH: f32 = 3.0;
+
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Weather>()
+            .add_systems(Startup, spawn_precipitation)
+            .add_systems(
+                Update,
+                (
+                    (advance_weather, update_precipitation).chain(),
+                    // the server decides where snow settles
+                    accumulate_snow
+                        .after(advance_weather)
+                        .run_if(not(resource_exists::<Client>)),
+                )
+                    .run_if(in_state(GameState::InGame)),
+            );
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WeatherKind {
+    #[default]
+    Clear,
+    Rain,
+    Snow,
+}
+
+impl WeatherKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Clear => "clear",
+            Self::Rain => "rain",
+            Self::Snow => "snow",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        [Self::Clear, Self::Rain, Self::Snow]
+            .into_iter()
+            .find(|kind| kind.name() == name)
+    }
+}
+
+/// What falls from the sky over one biome.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precipitation {
+    Rain,
+    Snow,
+}
+
+impl Precipitation {
+    /// What `weather` brings to `biome`, if anything.
+    pub fn in_biome(weather: WeatherKind, biome: Biome) -> Option<Self> {
+        match (weather, biome) {
+            (WeatherKind::Clear, _) | (_, Biome::Desert) => None,
+            (_, Biome::Snow) | (WeatherKind::Snow, _) => Some(Self::Snow),
+            (WeatherKind::Rain, _) => Some(Self::Rain),
+        }
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct Weather {
+    pub kind: WeatherKind,
+    /// Seconds until the weather changes.
+    remaining: f32,
+    /// How heavy the weather is, from `0.0` for clear skies to `1.0` for a storm at its height.
+    pub intensity: f32,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self {
+            kind: WeatherKind::Clear,
+            remaining: CLEAR_DURATION.start,
+            intensity: 0.0,
+        }
+    }
+}
+
+impl Weather {
+    /// Changes the weather to `kind` for a while, picked with `rng`.
+    pub fn set(&mut self, kind: WeatherKind, rng: &mut impl Rng) {
+        self.kind = kind;
+        self.remaining = match kind {
+            WeatherKind::Clear => rng.gen_range(CLEAR_DURATION),
+            WeatherKind::Rain | WeatherKind::Snow => rng.gen_range(STORM_DURATION),
+        };
+    }
+
+    /// Builds up or dies down the current weather, moving on to the next once it's over.
+    pub fn tick(&mut self, delta_secs: f32, rng: &mut impl Rng) {
+        let target = if self.kind == WeatherKind::Clear {
+            0.0
+        } else {
+            1.0
+        };
+        let step = INTENSITY_RATE * delta_secs;
+        self.intensity += (target - self.intensity).clamp(-step, step);
+
+        self.remaining -= delta_secs;
+        if self.remaining > 0.0 {
+            return;
+        }
+        let next = match self.kind {
+            WeatherKind::Clear if rng.gen_bool(SNOW_CHANCE) => WeatherKind::Snow,
+            WeatherKind::Clear => WeatherKind::Rain,
+            WeatherKind::Rain | WeatherKind::Snow => WeatherKind::Clear,
+        };
+        self.set(next, rng);
+    }
+
+    /// Fraction of daylight that gets through the clouds.
+    pub fn light(&self) -> f32 {
+        1.0 - STORM_DIMMING * self.intensity
+    }
+}
+
+fn advance_weather(time: Res<Time>, mut weather: ResMut<Weather>) {
+    weather.tick(time.delta_secs(), &mut rand::thread_rng());
+}
+
+/// `/weather` prints the weather, `/weather set <clear|rain|snow>` changes it.
+pub fn weather_command(
+    In(mut args): In<CommandArgs>,
+    mut weather: ResMut<Weather>,
+) -> CommandResult {
+    match args.optional_word().as_deref() {
+        None => Ok(format!(
+            "weather is {}, at {:.0}% intensity",
+            weather.kind.name(),
+            weather.intensity * 100.0
+        )),
+        Some("set") => {
+            let name = args.word("a weather")?;
+            args.finish()?;
+            let kind = WeatherKind::parse(&name)
+                .ok_or_else(|| format!("unknown weather '{name}', try clear, rain or snow"))?;
+            weather.set(kind, &mut rand::thread_rng());
+            Ok(format!("set weather to {}", kind.name()))
+        }
+        Some(other) => Err(format!(
+            "unknown argument '{other}', try /weather set <weather>"
+        )),
+    }
+}
+
+/// `count` drops of `size` scattered through a cube `DROP_PERIOD` wide, repeated once along each
+/// axis so moving it by a period in any direction looks the same. Each drop is two crossed quads
+/// so it can be seen from any side.
+fn drop_mesh(count: usize, size: (f32, f32), seed: u64) -> Mesh {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let drops: Vec<Vec3> = (0..count)
+        .map(|_| Vec3::new(rng.gen(), rng.gen(), rng.gen()) * DROP_PERIOD)
+        .collect();
+    let (half_width, height) = (size.0 / 2.0, size.1);
+
+    let mut positions = vec![];
+    let mut indices = vec![];
+    for copy in 0..8 {
+        let offset = Vec3::new(
+            (copy & 1) as f32,
+            ((copy >> 1) & 1) as f32,
+            ((copy >> 2) & 1) as f32,
+        ) * DROP_PERIOD
+            - Vec3::splat(DROP_PERIOD);
+        for drop in &drops {
+            let base = *drop + offset;
+            for across in [Vec3::X * half_width, Vec3::Z * half_width] {
+                let first = positions.len() as u32;
+                positions.extend([
+                    base - across,
+                    base + across,
+                    base + across + Vec3::Y * height,
+                    base - across + Vec3::Y * height,
+                ]);
+                indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+            }
+        }
+    }
+    let normals = vec![Vec3::Y; positions.len()];
+
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    mesh.insert_indices(Indices::U32(indices));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh
+}
+
+/// Where to put a drop mesh so its drops are fixed in the world, other than falling `fallen`
+/// blocks, while always surrounding `camera`.
+pub fn drop_origin(camera: Vec3, fallen: f32) -> Vec3 {
+    let snap = |coordinate: f32| (coordinate / DROP_PERIOD).round() * DROP_PERIOD;
+    let fallen = fallen.rem_euclid(DROP_PERIOD);
+    Vec3::new(
+        snap(camera.x),
+        snap(camera.y + fallen) - fallen,
+        snap(camera.z),
+    )
+}
+
+fn spawn_precipitation(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (precipitation, mesh, color) in [
+        (
+            Precipitation::Rain,
+            drop_mesh(RAIN_DROPS, (0.04, 0.7), 1),
+            RAIN_COLOR,
+        ),
+        (
+            Precipitation::Snow,
+            drop_mesh(SNOW_FLAKES, (0.15, 0.15), 2),
+            SNOW_COLOR,
+        ),
+    ] {
+        commands.spawn((
+            Mesh3d(meshes.add(mesh)),
+            MeshMaterial3d(materials.add(StandardMaterial {
+                base_color: color,
+                unlit: true,
+                alpha_mode: AlphaMode::Blend,
+                cull_mode: None,
+                ..Default::default()
+            })),
+            Transform::default(),
+            Visibility::Hidden,
+            NotShadowCaster,
+            precipitation,
+        ));
+    }
+}
+
+/// Shows the precipitation of the biome the camera is over, falling around the camera and
+/// thickening with the weather's intensity.
+#[allow(clippy::type_complexity)]
+fn update_precipitation(
+    time: Res<Time>,
+    mut last: Local<Option<Precipitation>>,
+    world: Res<World>,
+    weather: Res<Weather>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    mut precipitation_query: Query<(
+        &Precipitation,
+        &mut Transform,
+        &mut Visibility,
+        &MeshMaterial3d<StandardMaterial>,
+    )>,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let position = camera.translation();
+    let column = I64Vec2::new(position.x.floor() as i64, position.z.floor() as i64);
+    let surface = world.surface(column);
+    let sheltered =
+        surface.is_some_and(|surface| position.y < surface.height as f32 - SHELTER_DEPTH);
+    let here = surface
+        .and_then(|surface| Precipitation::in_biome(weather.kind, Biome::from_surface(surface)));
+    if here.is_some() {
+        *last = here;
+    }
+    // the last of a storm dies down as whatever it was falling as
+    let falling = if weather.kind == WeatherKind::Clear {
+        *last
+    } else {
+        here
+    }
+    .filter(|_| !sheltered && weather.intensity > 0.0);
+
+    for (precipitation, mut transform, mut visibility, material) in precipitation_query.iter_mut() {
+        if falling != Some(*precipitation) {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        }
+        visibility.set_if_neq(Visibility::Inherited);
+        let speed = match precipitation {
+            Precipitation::Rain => RAIN_SPEED,
+            Precipitation::Snow => SNOW_SPEED,
+        };
+        transform.translation = drop_origin(position, time.elapsed_secs() * speed);
+        if let Some(material) = materials.get_mut(&material.0) {
+            let base = match precipitation {
+                Precipitation::Rain => RAIN_COLOR,
+                Precipitation::Snow => SNOW_COLOR,
+            };
+            material
+                .base_color
+                .set_alpha(base.alpha() * weather.intensity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec3;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::{drop_origin, Precipitation, Weather, WeatherKind, DROP_PERIOD};
+    use crate::chunks::generate::biome::Biome;
+
+    #[test]
+    fn test_precipitation_depends_on_biome() {
+        let rain = Some(Precipitation::Rain);
+        let snow = Some(Precipitation::Snow);
+        assert_eq!(
+            None,
+            Precipitation::in_biome(WeatherKind::Clear, Biome::Snow)
+        );
+        assert_eq!(
+            rain,
+            Precipitation::in_biome(WeatherKind::Rain, Biome::Plains)
+        );
+        assert_eq!(
+            snow,
+            Precipitation::in_biome(WeatherKind::Rain, Biome::Snow)
+        );
+        assert_eq!(
+            snow,
+            Precipitation::in_biome(WeatherKind::Snow, Biome::Ocean)
+        );
+        assert_eq!(
+            None,
+            Precipitation::in_biome(WeatherKind::Rain, Biome::Desert)
+        );
+    }
+
+    #[test]
+    fn test_weather_builds_up_and_passes() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut weather = Weather::default();
+        weather.set(WeatherKind::Rain, &mut rng);
+        weather.tick(5.0, &mut rng);
+        assert_eq!(0.5, weather.intensity);
+        assert!(weather.light() < 1.0);
+
+        // a storm never lasts longer than this
+        weather.tick(1000.0, &mut rng);
+        assert_eq!(WeatherKind::Clear, weather.kind);
+        weather.tick(20.0, &mut rng);
+        assert_eq!(0.0, weather.intensity);
+        assert_eq!(1.0, weather.light());
+    }
+
+    #[test]
+    fn test_drops_surround_the_camera() {
+        for (camera, fallen) in [
+            (Vec3::new(0.0, 70.0, 0.0), 0.0),
+            (Vec3::new(-13.0, 5.5, 250.0), 31.0),
+            (Vec3::new(11.9, -40.0, -12.1), 1000.5),
+        ] {
+            let origin = drop_origin(camera, fallen);
+            // the drop mesh spans a period either side of its origin
+            assert!((camera - origin).abs().max_element() <= DROP_PERIOD / 2.0 + 1e-3);
+            // and moves with the fall, not the camera, within a period
+            let periods = (origin.y + fallen.rem_euclid(DROP_PERIOD)) / DROP_PERIOD;
+            assert!((periods - periods.round()).abs() < 1e-4);
+        }
+    }
+}
@@ -1,54 +1,337 @@
-use std::{
-    fmt::Debug,
-    sync::{Arc, RwLock},
-};
+pub mod history;
+pub mod import;
+pub mod shared;
+
+use std::{collections::HashMap, error::Error, fmt::Debug, sync::Arc, time::Instant};
 
 use bevy::{
-    ecs::system::Resource,
-    math::{I64Vec3, Vec3},
+    ecs::system::{In, Res, Resource},
+    math::{I64Vec2, I64Vec3, U16Vec3, Vec3},
 };
 
-use crate::chunks::generate::noise::NoiseGenerator;
+use crate::{
+    block::BlockType,
+    chunks::generate::{
+        biome::ColumnSurface,
+        noise::{NoiseGenerator, SharedNoise},
+        ore::OreSettings,
+        terrain::{ChunkGenerator, GeneratorKind, GeneratorRegistry},
+    },
+    command::{CommandArgs, CommandResult},
+    save::WorldInfo,
+    util::octree::OctreeCounts,
+};
+use history::{Edit, EditHistory};
+use shared::SharedChunks;
 
-use super::chunks::chunk::{ChunkCoordinate, ChunkData, ChunkOctree};
+use super::chunks::{
+    chunk::{ChunkCoordinate, ChunkData, ChunkOctree, EvictedChunk, CHUNK_SIZE, FULL_FLUID_LEVEL},
+    position::BlockPos,
+};
 
 #[derive(Resource)]
 pub struct World {
     seed: u32,
     pub height: u64,
     chunks: ChunkOctree,
-    pub noise_generator: Arc<RwLock<NoiseGenerator>>,
+    /// The chunks' data again, for tasks to read off the main schedule.
+    shared: SharedChunks,
+    noise: SharedNoise,
+    generator_kind: GeneratorKind,
+    generator: Arc<dyn ChunkGenerator>,
+    /// Recent edits to put back with `/rollback`, if enabled.
+    history: Option<EditHistory>,
 }
 
 impl World {
-    pub fn new() -> Self {
-        let seed = rand::random();
+    pub fn new(seed: u32) -> Self {
+        Self::with_chunk_size(seed, CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(seed: u32, chunk_size: u16) -> Self {
+        let noise = SharedNoise::new(seed);
+        let height = 256;
         Self {
             seed,
-            height: 256,
-            chunks: ChunkOctree::default(),
-            noise_generator: Arc::new(RwLock::new(NoiseGenerator::new(seed))),
+            height,
+            chunks: ChunkOctree::with_chunk_size(chunk_size),
+            shared: SharedChunks::default(),
+            generator_kind: GeneratorKind::default(),
+            generator: GeneratorKind::default()
+                .build_builtin(noise.clone(), height, chunk_size)
+                .expect("the default generator is built in"),
+            noise,
+            history: None,
+        }
+    }
+
+    /// Remembers up to `per_chunk` block edits to each chunk so they can be rolled back. Zero
+    /// keeps none.
+    pub fn with_history(mut self, per_chunk: usize) -> Self {
+        self.history = (per_chunk > 0).then(|| EditHistory::new(per_chunk));
+        self
+    }
+
+    pub fn keeps_history(&self) -> bool {
+        self.history.is_some()
+    }
+
+    /// Rebuilds the world's noise to generate ores as common as `ores` says.
+    pub fn with_ores(mut self, ores: OreSettings) -> Self {
+        self.noise = SharedNoise::with_ores(self.seed, ores);
+        // generators added by mods need the registry, so are given the new noise by
+        // `with_generator`
+        if let Some(generator) =
+            self.generator_kind
+                .build_builtin(self.noise(), self.height, self.chunk_size())
+        {
+            self.generator = generator;
         }
+        self
+    }
+
+    /// Generates the world with `kind`, looking it up in `registry` if a mod added it. A world
+    /// made with a mod that isn't loaded can't be generated, see `GeneratorKind::build`.
+    pub fn with_generator(
+        mut self,
+        kind: GeneratorKind,
+        registry: &GeneratorRegistry,
+    ) -> Result<Self, String> {
+        self.generator = kind.build(self.noise(), self.height, self.chunk_size(), registry)?;
+        self.generator_kind = kind;
+        Ok(self)
     }
 
     pub fn seed(&self) -> u32 {
         self.seed
     }
 
+    /// The terrain noise, to hand to generation tasks.
+    pub fn noise(&self) -> SharedNoise {
+        self.noise.clone()
+    }
+
+    /// A fresh lookup cache over the terrain noise for sampling a handful of columns.
+    pub fn noise_generator(&self) -> NoiseGenerator {
+        NoiseGenerator::from_shared(self.noise())
+    }
+
+    /// What the world's chunks are generated by, to hand to generation tasks.
+    pub fn generator(&self) -> Arc<dyn ChunkGenerator> {
+        self.generator.clone()
+    }
+
+    pub fn generator_kind(&self) -> &GeneratorKind {
+        &self.generator_kind
+    }
+
+    /// The generated terrain's surface at a block column, `None` where there is no ground.
+    pub fn surface(&self, column: I64Vec2) -> Option<ColumnSurface> {
+        self.generator.surface(column)
+    }
+
+    pub fn chunk_size(&self) -> u16 {
+        self.chunks.chunk_size
+    }
+
     pub fn insert_chunk(
         &mut self,
         chunk_coord: ChunkCoordinate,
         chunk_data: ChunkData,
     ) -> Arc<ChunkData> {
-        self.chunks.set_chunk_data(chunk_coord, chunk_data)
+        let data = self.chunks.set_chunk_data(chunk_coord, chunk_data);
+        self.shared.insert(chunk_coord, data.clone());
+        data
+    }
+
+    /// A handle to the world's chunk data that tasks can read from other threads.
+    pub fn shared_chunks(&self) -> SharedChunks {
+        self.shared.clone()
     }
 
     pub fn get_chunk_data(&mut self, chunk_coord: ChunkCoordinate) -> Option<Arc<ChunkData>> {
         self.chunks.get_chunk_data(chunk_coord)
     }
 
+    /// The chunk a block is in and its position within that chunk.
+    pub fn split_block_coordinate(&self, block_coord: I64Vec3) -> (ChunkCoordinate, U16Vec3) {
+        let (chunk_coord, local) = BlockPos(block_coord).split(self.chunk_size());
+        (chunk_coord, local.get())
+    }
+
+    pub fn get_block(&mut self, block_coord: I64Vec3) -> BlockType {
+        let (chunk_coord, local) = self.split_block_coordinate(block_coord);
+        self.get_chunk_data(chunk_coord)
+            .map(|chunk_data| chunk_data.get_block_at(local))
+            .unwrap_or_default()
+    }
+
+    /// Replaces a single block, returning `false` if its chunk has not been generated.
+    pub fn set_block(&mut self, block_coord: I64Vec3, block_type: BlockType) -> bool {
+        let (chunk_coord, local) = self.split_block_coordinate(block_coord);
+        let Some(chunk_data) = self.get_chunk_data(chunk_coord) else {
+            return false;
+        };
+
+        let mut chunk_data = ChunkData::clone(&chunk_data);
+        self.record_edit(
+            chunk_coord,
+            block_coord,
+            chunk_data.get_block_at(local),
+            block_type,
+        );
+        chunk_data.set_block_at(local, block_type);
+        self.insert_chunk(chunk_coord, chunk_data);
+        self.chunks.mark_edited(chunk_coord);
+        true
+    }
+
+    pub fn get_fluid_level(&mut self, block_coord: I64Vec3) -> u8 {
+        let (chunk_coord, local) = self.split_block_coordinate(block_coord);
+        self.get_chunk_data(chunk_coord)
+            .map(|chunk_data| chunk_data.fluid_level_at(local))
+            .unwrap_or(FULL_FLUID_LEVEL)
+    }
+
+    /// Places a fluid filled to `level`, returning `false` if its chunk has not been generated.
+    pub fn set_fluid(&mut self, block_coord: I64Vec3, block_type: BlockType, level: u8) -> bool {
+        let (chunk_coord, local) = self.split_block_coordinate(block_coord);
+        let Some(chunk_data) = self.get_chunk_data(chunk_coord) else {
+            return false;
+        };
+
+        let mut chunk_data = ChunkData::clone(&chunk_data);
+        chunk_data.set_fluid_at(local, block_type, level);
+        self.insert_chunk(chunk_coord, chunk_data);
+        self.chunks.mark_edited(chunk_coord);
+        true
+    }
+
+    /// Replaces many blocks, copying each affected chunk once rather than once per block.
+    /// Blocks in chunks that have not been generated are skipped. Returns how many were set.
+    pub fn set_blocks(&mut self, blocks: impl IntoIterator<Item = (I64Vec3, BlockType)>) -> usize {
+        let mut by_chunk: HashMap<ChunkCoordinate, Vec<(I64Vec3, U16Vec3, BlockType)>> =
+            HashMap::new();
+        for (block_coord, block_type) in blocks {
+            let (chunk_coord, local) = self.split_block_coordinate(block_coord);
+            by_chunk
+                .entry(chunk_coord)
+                .or_default()
+                .push((block_coord, local, block_type));
+        }
+
+        let mut count = 0;
+        for (chunk_coord, blocks) in by_chunk {
+            let Some(chunk_data) = self.get_chunk_data(chunk_coord) else {
+                continue;
+            };
+
+            let mut chunk_data = ChunkData::clone(&chunk_data);
+            for (block_coord, local, block_type) in &blocks {
+                let previous = chunk_data.get_block_at(*local);
+                self.record_edit(chunk_coord, *block_coord, previous, *block_type);
+                chunk_data.set_block_at(*local, *block_type);
+            }
+            self.insert_chunk(chunk_coord, chunk_data);
+            self.chunks.mark_edited(chunk_coord);
+            count += blocks.len();
+        }
+        count
+    }
+
+    fn record_edit(
+        &mut self,
+        chunk_coord: ChunkCoordinate,
+        position: I64Vec3,
+        previous: BlockType,
+        block_type: BlockType,
+    ) {
+        if let Some(history) = self.history.as_mut().filter(|_| previous != block_type) {
+            history.record(
+                chunk_coord,
+                Edit {
+                    position,
+                    previous,
+                    at: Instant::now(),
+                },
+            );
+        }
+    }
+
+    /// Puts back the blocks edited within `min` and `max` inclusive since `since`, or ever if
+    /// it's `None`, through `set_blocks`. Edits in chunks that aren't loaded are kept for later.
+    /// Returns the blocks put back.
+    pub fn rollback(
+        &mut self,
+        min: I64Vec3,
+        max: I64Vec3,
+        since: Option<Instant>,
+    ) -> Vec<(I64Vec3, BlockType)> {
+        let Some(mut history) = self.history.take() else {
+            return Vec::new();
+        };
+        let chunks = &mut self.chunks;
+        let restored: Vec<_> = history
+            .take_since(min, max, since, |chunk_coord| {
+                chunks.get_chunk_data(chunk_coord).is_some()
+            })
+            .into_iter()
+            .collect();
+        // with the history taken, putting blocks back isn't recorded as more edits
+        self.set_blocks(restored.iter().copied());
+        self.history = Some(history);
+        restored
+    }
+
     pub fn clear_chunk(&mut self, chunk_coord: ChunkCoordinate) {
-        self.chunks.clear_chunk(chunk_coord)
+        self.chunks.clear_chunk(chunk_coord);
+        self.shared.remove(chunk_coord);
+    }
+
+    /// Records that a chunk differs from what generation would make, such as one read back from
+    /// disk, so it is saved rather than dropped when evicted.
+    pub fn mark_edited(&mut self, chunk_coord: ChunkCoordinate) {
+        self.chunks.mark_edited(chunk_coord)
+    }
+
+    pub fn is_edited(&self, chunk_coord: ChunkCoordinate) -> bool {
+        self.chunks.is_edited(chunk_coord)
+    }
+
+    /// Writes every edited chunk still in memory to the save, returning how many were written.
+    pub fn save_edited_chunks(&mut self, world_info: &WorldInfo) -> Result<usize, Box<dyn Error>> {
+        let edited: Vec<ChunkCoordinate> = self.chunks.edited_chunks().collect();
+        for &chunk_coord in &edited {
+            if let Some(chunk_data) = self.chunks.get_chunk_data(chunk_coord) {
+                world_info.save_chunk(chunk_coord, &chunk_data)?;
+            }
+        }
+        Ok(edited.len())
+    }
+
+    pub fn resident_chunks(&self) -> usize {
+        self.chunks.resident_chunks()
+    }
+
+    pub fn resident_bytes(&self) -> usize {
+        self.chunks.resident_bytes()
+    }
+
+    /// Evicts the least recently used chunks further than `keep_distance` chunks from `centre`
+    /// until at most `budget` bytes of chunk data are resident.
+    pub fn evict_chunks(
+        &mut self,
+        budget: usize,
+        centre: ChunkCoordinate,
+        keep_distance: u32,
+    ) -> Vec<EvictedChunk> {
+        let evicted = self.chunks.evict(budget, |chunk| {
+            (chunk.0 - centre.0).abs().max_element() as u32 <= keep_distance
+        });
+        for chunk in &evicted {
+            self.shared.remove(chunk.coord);
+        }
+        evicted
     }
 
     pub fn adjacent_chunk_data(
@@ -67,10 +350,16 @@ impl World {
     }
 
     pub fn is_chunk_empty(&mut self, chunk_coord: ChunkCoordinate) -> bool {
-        self.chunks
-            .get_chunk_data(chunk_coord)
-            .map(|chunk_data| chunk_data.empty())
-            .unwrap_or(false)
+        self.chunks.is_chunk_empty(chunk_coord)
+    }
+
+    /// Generated chunks between `min` and `max` inclusive, and how many of them have blocks.
+    pub fn count_chunks(&self, min: ChunkCoordinate, max: ChunkCoordinate) -> OctreeCounts {
+        self.chunks.count_chunks(min, max)
+    }
+
+    pub fn any_solid_chunks(&self, min: ChunkCoordinate, max: ChunkCoordinate) -> bool {
+        self.chunks.any_solid_chunks(min, max)
     }
 
     pub fn chunk_to_world(&self, chunk_coord: ChunkCoordinate) -> Vec3 {
@@ -78,21 +367,56 @@ impl World {
     }
 
     pub fn block_to_chunk_coordinate(&self, block_coord: I64Vec3) -> ChunkCoordinate {
-        (block_coord / self.chunks.chunk_size as i64).into()
+        BlockPos(block_coord).chunk(self.chunk_size())
+    }
+
+    /// The chunk containing a point in the world.
+    pub fn chunk_at(&self, position: Vec3) -> ChunkCoordinate {
+        BlockPos::from_world(position).chunk(self.chunk_size())
     }
 }
 
+pub fn seed_command(In(args): In<CommandArgs>, world: Res<World>) -> CommandResult {
+    args.finish()?;
+    Ok(format!("world seed is {}", world.seed()))
+}
+
 impl Debug for World {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("World").field("seed", &self.seed).finish()
+        f.debug_struct("World")
+            .field("seed", &self.seed)
+            .field("generator", &self.generator_kind)
+            .finish()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use bevy::math::{I64Vec3, Vec3};
+
+    use super::World;
+    use crate::chunks::chunk::ChunkCoordinate;
 
     #[test]
-    fn test_block_to_chunk_coordinate() {}
+    fn test_block_to_chunk_coordinate() {
+        let world = World::with_chunk_size(0, 32);
+        let chunk = |x, y, z| ChunkCoordinate(I64Vec3::new(x, y, z));
+        assert_eq!(
+            chunk(0, 0, 0),
+            world.block_to_chunk_coordinate(I64Vec3::new(31, 0, 5))
+        );
+        // negative blocks belong to the chunk below, not the one at the origin
+        assert_eq!(
+            chunk(-1, 0, -1),
+            world.block_to_chunk_coordinate(I64Vec3::new(-1, 0, -32))
+        );
+        assert_eq!(
+            chunk(-2, 1, 0),
+            world.block_to_chunk_coordinate(I64Vec3::new(-33, 32, 0))
+        );
+        assert_eq!(chunk(-1, 0, 0), world.chunk_at(Vec3::new(-0.6, 0.0, 0.4)));
+        assert_eq!(chunk(0, 0, 0), world.chunk_at(Vec3::new(-0.4, 0.0, 0.4)));
+    }
 
     #[test]
     fn test_is_chunk_generated() {}
@@ -0,0 +1,247 @@
+//! A bounded log of the block edits made to each chunk, so griefing can be undone with
+//! `/rollback`.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    ecs::{
+        event::EventWriter,
+        system::{Commands, In, Res, ResMut},
+    },
+    math::I64Vec3,
+};
+
+use super::World;
+use crate::{
+    block::BlockType,
+    chunks::{chunk::ChunkCoordinate, chunk_loader::ChunkLoader},
+    command::{CommandArgs, CommandResult},
+    interaction::{BlockEdited, EditSource},
+};
+
+/// A block replaced at `position`, and what it was before.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edit {
+    pub position: I64Vec3,
+    pub previous: BlockType,
+    pub at: Instant,
+}
+
+#[derive(Debug, Default)]
+pub struct EditHistory {
+    /// Edits kept for each chunk, the oldest are forgotten first.
+    per_chunk: usize,
+    chunks: HashMap<ChunkCoordinate, VecDeque<Edit>>,
+}
+
+impl EditHistory {
+    pub fn new(per_chunk: usize) -> Self {
+        Self {
+            per_chunk,
+            chunks: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, chunk_coord: ChunkCoordinate, edit: Edit) {
+        let edits = self.chunks.entry(chunk_coord).or_default();
+        if edits.len() == self.per_chunk {
+            edits.pop_front();
+        }
+        edits.push_back(edit);
+    }
+
+    /// Edits remembered across every chunk.
+    pub fn len(&self) -> usize {
+        self.chunks.values().map(VecDeque::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forgets the edits made within `min` and `max` inclusive since `since`, or ever if it's
+    /// `None`, in the chunks `include` accepts. Returns what each edited block was before the
+    /// first of them.
+    pub fn take_since(
+        &mut self,
+        min: I64Vec3,
+        max: I64Vec3,
+        since: Option<Instant>,
+        mut include: impl FnMut(ChunkCoordinate) -> bool,
+    ) -> HashMap<I64Vec3, BlockType> {
+        let mut restored = HashMap::new();
+        for (chunk_coord, edits) in self.chunks.iter_mut() {
+            if !include(*chunk_coord) {
+                continue;
+            }
+            edits.retain(|edit| {
+                let undone = since.map_or(true, |since| edit.at >= since)
+                    && edit.position.cmpge(min).all()
+                    && edit.position.cmple(max).all();
+                if undone {
+                    restored.entry(edit.position).or_insert(edit.previous);
+                }
+                !undone
+            });
+        }
+        self.chunks.retain(|_, edits| !edits.is_empty());
+        restored
+    }
+}
+
+/// Parses a length of time such as `90s`, `30m`, `2h` or `1d`.
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let unit = text.chars().last()?;
+    let count: f32 = text[..text.len() - unit.len_utf8()].parse().ok()?;
+    let seconds = match unit {
+        's' => 1.0,
+        'm' => 60.0,
+        'h' => 60.0 * 60.0,
+        'd' => 24.0 * 60.0 * 60.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f32(count * seconds).ok()
+}
+
+/// `/rollback <x1> <y1> <z1> <x2> <y2> <z2> <duration>` puts back the blocks edited inside a box
+/// within the given time.
+pub fn rollback_command(
+    In(mut args): In<CommandArgs>,
+    mut commands: Commands,
+    mut world: ResMut<World>,
+    chunk_loader: Option<Res<ChunkLoader>>,
+    mut edited: EventWriter<BlockEdited>,
+) -> CommandResult {
+    let a = args.block_position()?;
+    let b = args.block_position()?;
+    let text = args.word("a duration")?;
+    args.finish()?;
+
+    let duration = parse_duration(&text)
+        .ok_or_else(|| format!("'{text}' is not a duration such as 30m or 2h"))?;
+    if !world.keeps_history() {
+        return Err("edit history is turned off in settings.toml".to_string());
+    }
+
+    let (min, max) = (a.min(b), a.max(b));
+    let since = Instant::now().checked_sub(duration);
+    let restored = world.rollback(min, max, since);
+    edited.send_batch(restored.iter().map(|(position, block)| BlockEdited {
+        position: *position,
+        block: *block,
+        source: EditSource::Command,
+    }));
+    // a dedicated server has no meshes to update
+    if let Some(chunk_loader) = chunk_loader {
+        chunk_loader.remesh_region(&mut commands, &world, min, max);
+    }
+    Ok(format!("rolled back {} blocks", restored.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use bevy::math::I64Vec3;
+
+    use super::{parse_duration, Edit, EditHistory};
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        world::World,
+    };
+
+    #[test]
+    fn test_history_is_bounded_per_chunk() {
+        let mut history = EditHistory::new(2);
+        let start = Instant::now();
+        for x in 0..3 {
+            history.record(
+                ChunkCoordinate(I64Vec3::ZERO),
+                Edit {
+                    position: I64Vec3::new(x, 0, 0),
+                    previous: BlockType::Stone,
+                    at: start,
+                },
+            );
+        }
+        assert_eq!(2, history.len());
+
+        let restored = history.take_since(I64Vec3::ZERO, I64Vec3::splat(8), None, |_| true);
+        assert!(!restored.contains_key(&I64Vec3::ZERO));
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn test_take_since_restores_the_first_edit() {
+        let mut history = EditHistory::new(16);
+        let start = Instant::now();
+        let position = I64Vec3::new(1, 2, 3);
+        let chunk = ChunkCoordinate(I64Vec3::ZERO);
+        for (seconds, previous) in [(0, BlockType::Grass), (10, BlockType::Stone)] {
+            history.record(
+                chunk,
+                Edit {
+                    position,
+                    previous,
+                    at: start + Duration::from_secs(seconds),
+                },
+            );
+        }
+        history.record(
+            chunk,
+            Edit {
+                position: I64Vec3::new(20, 0, 0),
+                previous: BlockType::Sand,
+                at: start + Duration::from_secs(10),
+            },
+        );
+
+        // only the later edit inside the box is undone
+        let since = Some(start + Duration::from_secs(5));
+        let restored = history.take_since(I64Vec3::ZERO, I64Vec3::splat(8), since, |_| true);
+        assert_eq!(Some(&BlockType::Stone), restored.get(&position));
+        assert_eq!(1, restored.len());
+        assert_eq!(2, history.len());
+
+        let restored = history.take_since(I64Vec3::ZERO, I64Vec3::splat(8), None, |_| false);
+        assert!(restored.is_empty());
+    }
+
+    #[test]
+    fn test_world_rollback() {
+        let mut world = World::new(0).with_history(64);
+        world.insert_chunk(ChunkCoordinate(I64Vec3::ZERO), ChunkData::default());
+        let position = I64Vec3::new(1, 1, 1);
+        world.set_block(position, BlockType::Stone);
+        world.set_blocks([
+            (position, BlockType::Sand),
+            (I64Vec3::ONE * 2, BlockType::Glass),
+        ]);
+        assert_eq!(BlockType::Sand, world.get_block(position));
+
+        let restored = world.rollback(I64Vec3::ZERO, I64Vec3::splat(4), None);
+        assert_eq!(2, restored.len());
+        assert_eq!(BlockType::Air, world.get_block(position));
+        assert_eq!(BlockType::Air, world.get_block(I64Vec3::ONE * 2));
+        // putting blocks back isn't itself recorded
+        assert!(world
+            .rollback(I64Vec3::ZERO, I64Vec3::splat(4), None)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(Some(Duration::from_secs(90)), parse_duration("90s"));
+        assert_eq!(Some(Duration::from_secs(1800)), parse_duration("30m"));
+        assert_eq!(Some(Duration::from_secs(7200)), parse_duration("2h"));
+        assert_eq!(Some(Duration::from_secs(86400)), parse_duration("1d"));
+        assert_eq!(None, parse_duration("30"));
+        assert_eq!(None, parse_duration("m"));
+        assert_eq!(None, parse_duration(""));
+        assert_eq!(None, parse_duration("-1h"));
+    }
+}
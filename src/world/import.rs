@@ -0,0 +1,69 @@
+//! Converts maps from other games into a rustcraft world's save.
+
+pub mod anvil;
+mod nbt;
+
+use std::path::Path;
+
+use bevy::{
+    ecs::system::{Commands, In, Res, ResMut},
+    log::warn,
+    math::I64Vec3,
+};
+
+use self::anvil::{import_anvil, AnvilMapping, MAPPING_FILE, SECTION_SIZE};
+use super::World;
+use crate::{
+    chunks::{chunk::ChunkCoordinate, chunk_loader::ChunkLoader},
+    command::{CommandArgs, CommandResult},
+    save::WorldInfo,
+};
+
+/// Imports a Minecraft map into the current world, replacing whatever was at the same chunks.
+/// Chunks that are already loaded are swapped for the imported ones straight away.
+pub fn import_command(
+    In(mut args): In<CommandArgs>,
+    mut commands: Commands,
+    mut world: ResMut<World>,
+    world_info: Option<Res<WorldInfo>>,
+    chunk_loader: Res<ChunkLoader>,
+) -> CommandResult {
+    let path = args.word("a region folder or .mca file")?;
+    args.finish()?;
+
+    let world_info = world_info.ok_or("only saved worlds can import maps")?;
+    let mapping = AnvilMapping::load(Path::new(MAPPING_FILE))
+        .map_err(|e| format!("failed to load {MAPPING_FILE}: {e}"))?;
+    let import = import_anvil(Path::new(&path), &world_info, world.height, &mapping)
+        .map_err(|e| format!("failed to import {path}: {e}"))?;
+    for error in &import.errors {
+        warn!("skipped {}", error);
+    }
+
+    let sections = world.height.div_ceil(SECTION_SIZE as u64) as i64;
+    for (x, z) in &import.columns {
+        for y in 0..sections {
+            let coord = ChunkCoordinate(I64Vec3::new(*x, y, *z));
+            if world.get_chunk_data(coord).is_none() {
+                continue;
+            }
+            match world_info.load_chunk(coord) {
+                Ok(Some(chunk_data)) => {
+                    world.insert_chunk(coord, chunk_data);
+                    world.mark_edited(coord);
+                }
+                Ok(None) => {}
+                Err(e) => warn!("failed to reload imported chunk {:?}: {}", coord.0, e),
+            }
+        }
+        let min = I64Vec3::new(*x, 0, *z) * SECTION_SIZE as i64;
+        let max = min + I64Vec3::new(1, sections, 1) * SECTION_SIZE as i64 - 1;
+        chunk_loader.remesh_region(&mut commands, &world, min, max);
+    }
+
+    let mut message = format!("imported {} chunk columns", import.columns.len());
+    if !import.errors.is_empty() {
+        message += &format!(", skipping {} that couldn't be read", import.errors.len());
+    }
+    Ok(message)
+}
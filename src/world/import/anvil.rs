@@ -0,0 +1,485 @@
+//! Reads Minecraft Java Edition region files, saved in the Anvil format since 1.13, converting
+//! each 16³ section of their chunks into a `ChunkData`.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use bevy::math::{I64Vec3, U16Vec3};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use serde::Deserialize;
+
+use super::nbt::{self, Tag};
+use crate::{
+    block::BlockType,
+    chunks::chunk::{ChunkCoordinate, ChunkData},
+    save::WorldInfo,
+};
+
+pub const MAPPING_FILE: &str = "assets/import/anvil.ron";
+/// Width of a Minecraft section, so imported worlds need chunks of the same size.
+pub const SECTION_SIZE: u16 = 16;
+const SECTOR_SIZE: usize = 4096;
+/// The first sector of a region holds chunk locations, the second their timestamps.
+const HEADER_SIZE: usize = 2 * SECTOR_SIZE;
+/// Data version of Minecraft 1.16, from which block states no longer span two longs.
+const PADDED_STATES_VERSION: i64 = 2529;
+
+/// Which rustcraft block each Minecraft block becomes.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct AnvilMapping {
+    /// Used for blocks the mapping doesn't mention.
+    pub unknown: BlockType,
+    /// Sections added to every Minecraft section's y, to lift worlds that go below zero.
+    #[serde(default)]
+    pub section_offset: i64,
+    /// Blocks by their Minecraft name, e.g. `minecraft:stone`. Keys starting with `*` match any
+    /// name ending in the rest of the key.
+    pub blocks: HashMap<String, BlockType>,
+}
+
+impl AnvilMapping {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(ron::from_str(source)?)
+    }
+
+    /// The block `name` maps to, preferring an exact match and then the longest suffix.
+    pub fn block(&self, name: &str) -> BlockType {
+        if let Some(block) = self.blocks.get(name) {
+            return *block;
+        }
+        self.blocks
+            .iter()
+            .filter_map(|(key, block)| {
+                let suffix = key.strip_prefix('*')?;
+                name.ends_with(suffix).then_some((suffix.len(), *block))
+            })
+            .max_by_key(|(length, _)| *length)
+            .map_or(self.unknown, |(_, block)| block)
+    }
+}
+
+/// One chunk of a region, a column of sections each converted to a chunk of `SECTION_SIZE`.
+#[derive(Debug, Clone)]
+pub struct AnvilColumn {
+    pub x: i64,
+    pub z: i64,
+    /// Sections that hold any blocks, by their y after `AnvilMapping::section_offset`.
+    pub sections: Vec<(i64, ChunkData)>,
+}
+
+/// What could be read from a region file. A chunk that can't be read doesn't stop the rest.
+#[derive(Debug, Default)]
+pub struct AnvilRegion {
+    pub columns: Vec<AnvilColumn>,
+    pub errors: Vec<String>,
+}
+
+pub fn read_region(bytes: &[u8], mapping: &AnvilMapping) -> Result<AnvilRegion, String> {
+    if bytes.len() < HEADER_SIZE {
+        return Err("missing region header".to_string());
+    }
+
+    let mut region = AnvilRegion::default();
+    for (i, location) in bytes[..SECTOR_SIZE].chunks_exact(4).enumerate() {
+        let sector = u32::from_be_bytes([0, location[0], location[1], location[2]]) as usize;
+        if sector == 0 {
+            continue;
+        }
+        let column = bytes
+            .get(sector * SECTOR_SIZE..)
+            .ok_or_else(|| "chunk lies past the end of the region".to_string())
+            .and_then(decompress)
+            .and_then(|data| nbt::parse(&data))
+            .and_then(|tag| read_column(&tag, mapping));
+        match column {
+            Ok(column) => region.columns.push(column),
+            Err(e) => region
+                .errors
+                .push(format!("chunk {} {} of region: {}", i % 32, i / 32, e)),
+        }
+    }
+    Ok(region)
+}
+
+/// Unpacks a chunk stored as its length, compression and compressed data.
+fn decompress(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let header = bytes.get(..5).ok_or("chunk header runs past the region")?;
+    let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let data = bytes
+        .get(5..4 + length.max(1))
+        .ok_or("chunk runs past the end of the region")?;
+
+    let mut raw = vec![];
+    let result = match header[4] {
+        1 => GzDecoder::new(data).read_to_end(&mut raw),
+        2 => ZlibDecoder::new(data).read_to_end(&mut raw),
+        3 => {
+            raw.extend_from_slice(data);
+            Ok(raw.len())
+        }
+        compression if compression & 0x80 != 0 => {
+            return Err("stored in a separate .mcc file, which isn't supported".to_string())
+        }
+        compression => return Err(format!("unknown compression {compression}")),
+    };
+    result.map_err(|e| format!("failed to decompress: {e}"))?;
+    Ok(raw)
+}
+
+fn read_column(tag: &Tag, mapping: &AnvilMapping) -> Result<AnvilColumn, String> {
+    let data_version = tag.get("DataVersion").and_then(Tag::as_i64).unwrap_or(0);
+    // before 1.18 everything was kept under `Level`, with names in a different case
+    let (level, sections) = match tag.get("Level") {
+        Some(level) => (level, level.get("Sections")),
+        None => (tag, tag.get("sections")),
+    };
+    let position = |name| {
+        level
+            .get(name)
+            .and_then(Tag::as_i64)
+            .ok_or_else(|| format!("missing {name}"))
+    };
+    let (x, z) = (position("xPos")?, position("zPos")?);
+
+    let mut column = AnvilColumn {
+        x,
+        z,
+        sections: vec![],
+    };
+    for section in sections.and_then(Tag::as_list).unwrap_or_default() {
+        let y = section
+            .get("Y")
+            .and_then(Tag::as_i64)
+            .ok_or("section without a Y")?;
+        let (palette, states) = match section.get("block_states") {
+            Some(states) => (states.get("palette"), states.get("data")),
+            None => (section.get("Palette"), section.get("BlockStates")),
+        };
+        let Some(palette) = palette.and_then(Tag::as_list) else {
+            if section.get("Blocks").is_some() {
+                return Err("saved before Minecraft 1.13, which isn't supported".to_string());
+            }
+            // sections holding only light have no blocks
+            continue;
+        };
+
+        let blocks = palette
+            .iter()
+            .map(|state| {
+                state
+                    .get("Name")
+                    .and_then(Tag::as_str)
+                    .map(|name| mapping.block(name))
+                    .ok_or("block state without a name")
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let states = states.and_then(Tag::as_long_array);
+        let chunk = read_states(&blocks, states, data_version >= PADDED_STATES_VERSION)?;
+        if !chunk.empty() {
+            column.sections.push((y + mapping.section_offset, chunk));
+        }
+    }
+    Ok(column)
+}
+
+/// Unpacks a section's palette indices, stored in as few bits as fit the palette but at least
+/// four. From 1.16 an index never spans two longs, which are padded instead.
+fn read_states(
+    blocks: &[BlockType],
+    states: Option<&[i64]>,
+    padded: bool,
+) -> Result<ChunkData, String> {
+    let mut chunk = ChunkData::with_size(SECTION_SIZE);
+    let count = (SECTION_SIZE as usize).pow(3);
+    let position = |i: usize| {
+        let size = SECTION_SIZE as usize;
+        U16Vec3::new(
+            (i % size) as u16,
+            (i / (size * size)) as u16,
+            (i / size % size) as u16,
+        )
+    };
+
+    let Some(states) = states else {
+        // a palette of one block needs no indices
+        let block = match blocks {
+            [block] => *block,
+            _ => return Err("block states are missing".to_string()),
+        };
+        if block != BlockType::Air {
+            for i in 0..count {
+                chunk.set_block_at(position(i), block);
+            }
+        }
+        return Ok(chunk);
+    };
+
+    let bits = (usize::BITS - blocks.len().saturating_sub(1).leading_zeros()).max(4) as usize;
+    let mask = (1u64 << bits) - 1;
+    let per_long = 64 / bits;
+    for i in 0..count {
+        let index = if padded {
+            let long = *states
+                .get(i / per_long)
+                .ok_or("block states are too short")? as u64;
+            (long >> (i % per_long * bits)) & mask
+        } else {
+            let bit = i * bits;
+            let long = *states.get(bit / 64).ok_or("block states are too short")? as u64;
+            let mut index = long >> (bit % 64);
+            if bit % 64 + bits > 64 {
+                let next = *states
+                    .get(bit / 64 + 1)
+                    .ok_or("block states are too short")? as u64;
+                index |= next << (64 - bit % 64);
+            }
+            index & mask
+        };
+        let block = *blocks
+            .get(index as usize)
+            .ok_or("block state outside the palette")?;
+        if block != BlockType::Air {
+            chunk.set_block_at(position(i), block);
+        }
+    }
+    Ok(chunk)
+}
+
+/// Every region file at `path`, a Minecraft world's `region` folder or a single `.mca` file.
+pub fn region_files(path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files: Vec<PathBuf> = fs::read_dir(path)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "mca"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+#[derive(Debug, Default)]
+pub struct AnvilImport {
+    /// The x and z of every column written, in chunks.
+    pub columns: Vec<(i64, i64)>,
+    pub errors: Vec<String>,
+}
+
+/// Converts the regions at `path` into the world's save. Every chunk of an imported column up to
+/// `height` is written, empty ones included, so terrain generated from the world's seed doesn't
+/// poke through the map.
+pub fn import_anvil(
+    path: &Path,
+    world_info: &WorldInfo,
+    height: u64,
+    mapping: &AnvilMapping,
+) -> Result<AnvilImport, Box<dyn Error>> {
+    if world_info.chunk_size != SECTION_SIZE {
+        return Err(
+            format!("maps can only be imported into worlds with {SECTION_SIZE}³ chunks").into(),
+        );
+    }
+    let files = region_files(path)?;
+    if files.is_empty() {
+        return Err(format!("no region files in {}", path.display()).into());
+    }
+
+    let sections = height.div_ceil(SECTION_SIZE as u64) as i64;
+    let mut import = AnvilImport::default();
+    for file in files {
+        let region = match fs::read(&file)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| read_region(&bytes, mapping))
+        {
+            Ok(region) => region,
+            Err(e) => {
+                import.errors.push(format!("{}: {}", file.display(), e));
+                continue;
+            }
+        };
+        import.errors.extend(
+            region
+                .errors
+                .into_iter()
+                .map(|e| format!("{}: {}", file.display(), e)),
+        );
+
+        for column in region.columns {
+            let mut chunks: HashMap<i64, ChunkData> = column.sections.into_iter().collect();
+            for y in 0..sections {
+                let chunk = chunks
+                    .remove(&y)
+                    .unwrap_or_else(|| ChunkData::with_size(SECTION_SIZE));
+                let coord = ChunkCoordinate(I64Vec3::new(column.x, y, column.z));
+                world_info.save_chunk(coord, &chunk)?;
+            }
+            import.columns.push((column.x, column.z));
+        }
+    }
+    Ok(import)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, io::Write};
+
+    use bevy::math::U16Vec3;
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use super::{read_region, read_states, AnvilMapping, MAPPING_FILE, SECTOR_SIZE};
+    use crate::{
+        block::BlockType,
+        world::import::nbt::{write, Tag},
+    };
+
+    fn compound<const N: usize>(tags: [(&str, Tag); N]) -> Tag {
+        Tag::Compound(
+            tags.into_iter()
+                .map(|(name, tag)| (name.to_string(), tag))
+                .collect(),
+        )
+    }
+
+    fn state(name: &str) -> Tag {
+        compound([("Name", Tag::String(name.to_string()))])
+    }
+
+    fn mapping() -> AnvilMapping {
+        AnvilMapping {
+            unknown: BlockType::Sand,
+            section_offset: 4,
+            blocks: HashMap::from([
+                ("minecraft:air".to_string(), BlockType::Air),
+                ("minecraft:stone".to_string(), BlockType::Stone),
+                ("*_leaves".to_string(), BlockType::Leaves),
+                ("*dark_oak_leaves".to_string(), BlockType::Glass),
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_mapping_prefers_longest_match() {
+        let mapping = mapping();
+        assert_eq!(BlockType::Stone, mapping.block("minecraft:stone"));
+        assert_eq!(BlockType::Leaves, mapping.block("minecraft:oak_leaves"));
+        assert_eq!(BlockType::Glass, mapping.block("minecraft:dark_oak_leaves"));
+        assert_eq!(BlockType::Sand, mapping.block("minecraft:netherrack"));
+
+        let bundled = AnvilMapping::load(std::path::Path::new(MAPPING_FILE)).unwrap();
+        assert_eq!(
+            BlockType::Coal,
+            bundled.block("minecraft:deepslate_coal_ore")
+        );
+        assert_eq!(BlockType::Air, bundled.block("minecraft:wall_torch"));
+    }
+
+    #[test]
+    fn test_read_states() {
+        let blocks = [BlockType::Air, BlockType::Stone, BlockType::Grass];
+        // four bits each, the first long holding blocks 0 to 15
+        let mut states = vec![0i64; 256];
+        states[0] = 0x21;
+        let padded = read_states(&blocks, Some(&states), true).unwrap();
+        assert_eq!(BlockType::Stone, padded.get_block_at(U16Vec3::ZERO));
+        assert_eq!(BlockType::Grass, padded.get_block_at(U16Vec3::new(1, 0, 0)));
+        assert_eq!(2, padded.block_count());
+
+        assert!(read_states(&blocks, Some(&[-1; 256]), true).is_err());
+        assert!(read_states(&blocks, Some(&[0; 10]), true).is_err());
+
+        // five bits each, so the thirteenth index spans the first two longs
+        let mut blocks = [BlockType::Air; 17];
+        blocks[16] = BlockType::Stone;
+        let mut states = vec![0i64; 320];
+        states[1] = 1;
+        let spanning = read_states(&blocks, Some(&states), false).unwrap();
+        assert_eq!(1, spanning.block_count());
+        assert_eq!(
+            BlockType::Stone,
+            spanning.get_block_at(U16Vec3::new(12, 0, 0))
+        );
+
+        let filled = read_states(&[BlockType::Stone], None, true).unwrap();
+        assert_eq!(4096, filled.block_count());
+        assert!(read_states(&blocks, None, true).is_err());
+    }
+
+    #[test]
+    fn test_read_region() {
+        let mut states = vec![0i64; 256];
+        // block 16 is at x 0, y 0, z 1
+        states[1] = 1;
+        let chunk = compound([
+            ("DataVersion", Tag::Int(3465)),
+            ("xPos", Tag::Int(-3)),
+            ("zPos", Tag::Int(7)),
+            (
+                "sections",
+                Tag::List(vec![
+                    compound([
+                        ("Y", Tag::Byte(-4)),
+                        (
+                            "block_states",
+                            compound([
+                                (
+                                    "palette",
+                                    Tag::List(vec![
+                                        state("minecraft:air"),
+                                        state("minecraft:oak_leaves"),
+                                    ]),
+                                ),
+                                ("data", Tag::LongArray(states)),
+                            ]),
+                        ),
+                    ]),
+                    compound([
+                        ("Y", Tag::Byte(0)),
+                        (
+                            "block_states",
+                            compound([("palette", Tag::List(vec![state("minecraft:air")]))]),
+                        ),
+                    ]),
+                ]),
+            ),
+        ]);
+
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(&write("", &chunk)).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut region = vec![0u8; 3 * SECTOR_SIZE];
+        // the chunk in the second slot is in the third sector, the one in the first is missing
+        region[4..8].copy_from_slice(&[0, 0, 2, 1]);
+        region[8..12].copy_from_slice(&[0, 0, 9, 1]);
+        let start = 2 * SECTOR_SIZE;
+        region[start..start + 4].copy_from_slice(&(compressed.len() as u32 + 1).to_be_bytes());
+        region[start + 4] = 2;
+        region[start + 5..start + 5 + compressed.len()].copy_from_slice(&compressed);
+
+        let region = read_region(&region, &mapping()).unwrap();
+        assert_eq!(1, region.errors.len());
+        assert_eq!(1, region.columns.len());
+        let column = &region.columns[0];
+        assert_eq!((-3, 7), (column.x, column.z));
+        // the empty section is dropped
+        assert_eq!(1, column.sections.len());
+        let (y, section) = &column.sections[0];
+        assert_eq!(0, *y);
+        assert_eq!(1, section.block_count());
+        assert_eq!(
+            BlockType::Leaves,
+            section.get_block_at(U16Vec3::new(0, 0, 1))
+        );
+
+        assert!(read_region(&[0; 100], &mapping()).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+//! A reader for Minecraft's Named Binary Tag format, enough to walk the chunks of a region file.
+
+use std::collections::HashMap;
+
+/// Compounds and lists nested deeper than this are rejected rather than risk the stack.
+const MAX_DEPTH: usize = 512;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<u8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(HashMap<String, Tag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    /// The tag named `name` in a compound.
+    pub fn get(&self, name: &str) -> Option<&Tag> {
+        match self {
+            Self::Compound(tags) => tags.get(name),
+            _ => None,
+        }
+    }
+
+    /// Any integer tag, widened.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Byte(value) => Some(*value as i64),
+            Self::Short(value) => Some(*value as i64),
+            Self::Int(value) => Some(*value as i64),
+            Self::Long(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Tag]> {
+        match self {
+            Self::List(tags) => Some(tags),
+            _ => None,
+        }
+    }
+
+    pub fn as_long_array(&self) -> Option<&[i64]> {
+        match self {
+            Self::LongArray(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the root compound at the start of `bytes`, discarding its name.
+pub fn parse(bytes: &[u8]) -> Result<Tag, String> {
+    let mut reader = Reader { bytes, offset: 0 };
+    let id = reader.u8()?;
+    if id != 10 {
+        return Err(format!("root tag is type {id}, not a compound"));
+    }
+    reader.string()?;
+    reader.payload(id, 0)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset + N)
+            .ok_or("unexpected end of tag")?;
+        self.offset += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    /// A length prefix, which must fit in what is left to read so garbage can't allocate wildly.
+    fn length(&mut self, element_size: usize) -> Result<usize, String> {
+        let length = i32::from_be_bytes(self.take()?);
+        usize::try_from(length)
+            .ok()
+            .filter(|length| length * element_size <= self.bytes.len() - self.offset)
+            .ok_or_else(|| format!("bad length {length}"))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let length = u16::from_be_bytes(self.take()?) as usize;
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset + length)
+            .ok_or("unexpected end of string")?;
+        self.offset += length;
+        // Java's modified UTF-8 only differs from UTF-8 for characters no block name uses
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    fn payload(&mut self, id: u8, depth: usize) -> Result<Tag, String> {
+        if depth > MAX_DEPTH {
+            return Err("tags nested too deeply".to_string());
+        }
+        Ok(match id {
+            1 => Tag::Byte(i8::from_be_bytes(self.take()?)),
+            2 => Tag::Short(i16::from_be_bytes(self.take()?)),
+            3 => Tag::Int(i32::from_be_bytes(self.take()?)),
+            4 => Tag::Long(i64::from_be_bytes(self.take()?)),
+            5 => Tag::Float(f32::from_be_bytes(self.take()?)),
+            6 => Tag::Double(f64::from_be_bytes(self.take()?)),
+            7 => {
+                let length = self.length(1)?;
+                let bytes = self.bytes[self.offset..self.offset + length].to_vec();
+                self.offset += length;
+                Tag::ByteArray(bytes)
+            }
+            8 => Tag::String(self.string()?),
+            9 => {
+                let element = self.u8()?;
+                let length = self.length(1)?;
+                let mut tags = Vec::with_capacity(length);
+                for _ in 0..length {
+                    tags.push(self.payload(element, depth + 1)?);
+                }
+                Tag::List(tags)
+            }
+            10 => {
+                let mut tags = HashMap::new();
+                loop {
+                    let id = self.u8()?;
+                    if id == 0 {
+                        break;
+                    }
+                    let name = self.string()?;
+                    tags.insert(name, self.payload(id, depth + 1)?);
+                }
+                Tag::Compound(tags)
+            }
+            11 => {
+                let length = self.length(4)?;
+                let mut values = Vec::with_capacity(length);
+                for _ in 0..length {
+                    values.push(i32::from_be_bytes(self.take()?));
+                }
+                Tag::IntArray(values)
+            }
+            12 => {
+                let length = self.length(8)?;
+                let mut values = Vec::with_capacity(length);
+                for _ in 0..length {
+                    values.push(i64::from_be_bytes(self.take()?));
+                }
+                Tag::LongArray(values)
+            }
+            _ => return Err(format!("unknown tag type {id}")),
+        })
+    }
+}
+
+/// Writes tags, for building test chunks.
+#[cfg(test)]
+pub fn write(name: &str, tag: &Tag) -> Vec<u8> {
+    fn id(tag: &Tag) -> u8 {
+        match tag {
+            Tag::Byte(_) => 1,
+            Tag::Short(_) => 2,
+            Tag::Int(_) => 3,
+            Tag::Long(_) => 4,
+            Tag::Float(_) => 5,
+            Tag::Double(_) => 6,
+            Tag::ByteArray(_) => 7,
+            Tag::String(_) => 8,
+            Tag::List(_) => 9,
+            Tag::Compound(_) => 10,
+            Tag::IntArray(_) => 11,
+            Tag::LongArray(_) => 12,
+        }
+    }
+
+    fn string(bytes: &mut Vec<u8>, value: &str) {
+        bytes.extend((value.len() as u16).to_be_bytes());
+        bytes.extend(value.as_bytes());
+    }
+
+    fn payload(bytes: &mut Vec<u8>, tag: &Tag) {
+        match tag {
+            Tag::Byte(value) => bytes.extend(value.to_be_bytes()),
+            Tag::Short(value) => bytes.extend(value.to_be_bytes()),
+            Tag::Int(value) => bytes.extend(value.to_be_bytes()),
+            Tag::Long(value) => bytes.extend(value.to_be_bytes()),
+            Tag::Float(value) => bytes.extend(value.to_be_bytes()),
+            Tag::Double(value) => bytes.extend(value.to_be_bytes()),
+            Tag::ByteArray(values) => {
+                bytes.extend((values.len() as i32).to_be_bytes());
+                bytes.extend(values);
+            }
+            Tag::String(value) => string(bytes, value),
+            Tag::List(tags) => {
+                bytes.push(tags.first().map_or(0, id));
+                bytes.extend((tags.len() as i32).to_be_bytes());
+                for tag in tags {
+                    payload(bytes, tag);
+                }
+            }
+            Tag::Compound(tags) => {
+                for (name, tag) in tags {
+                    bytes.push(id(tag));
+                    string(bytes, name);
+                    payload(bytes, tag);
+                }
+                bytes.push(0);
+            }
+            Tag::IntArray(values) => {
+                bytes.extend((values.len() as i32).to_be_bytes());
+                for value in values {
+                    bytes.extend(value.to_be_bytes());
+                }
+            }
+            Tag::LongArray(values) => {
+                bytes.extend((values.len() as i32).to_be_bytes());
+                for value in values {
+                    bytes.extend(value.to_be_bytes());
+                }
+            }
+        }
+    }
+
+    let mut bytes = vec![id(tag)];
+    string(&mut bytes, name);
+    payload(&mut bytes, tag);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{parse, write, Tag};
+
+    #[test]
+    fn test_nbt_round_trip() {
+        let tag = Tag::Compound(HashMap::from([
+            ("Y".to_string(), Tag::Byte(-4)),
+            (
+                "Name".to_string(),
+                Tag::String("minecraft:stone".to_string()),
+            ),
+            (
+                "sections".to_string(),
+                Tag::List(vec![Tag::LongArray(vec![1, -1]), Tag::LongArray(vec![])]),
+            ),
+            ("empty".to_string(), Tag::List(vec![])),
+        ]));
+        let parsed = parse(&write("", &tag)).unwrap();
+        assert_eq!(tag, parsed);
+        assert_eq!(Some(-4), parsed.get("Y").and_then(Tag::as_i64));
+        assert_eq!(
+            Some("minecraft:stone"),
+            parsed.get("Name").and_then(Tag::as_str)
+        );
+    }
+
+    #[test]
+    fn test_nbt_rejects_garbage() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&[8, 0, 0]).is_err());
+        // a compound holding a byte array claiming to be far longer than the data
+        assert!(parse(&[10, 0, 0, 7, 0, 1, b'a', 0x7f, 0, 0, 0]).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+//! Chunk data background tasks can read without the `World` resource.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::chunks::chunk::{ChunkCoordinate, ChunkData};
+
+/// Locks chunk data is split between, so readers seldom wait on each other or on the world.
+const SHARDS: usize = 16;
+
+type Shard = RwLock<HashMap<ChunkCoordinate, Arc<ChunkData>>>;
+
+/// Every resident chunk's data, kept in step with the world's octree. Clones share the same
+/// chunks, so a task handed one sees edits and evictions made after it was spawned.
+#[derive(Clone, Default)]
+pub struct SharedChunks {
+    shards: Arc<[Shard; SHARDS]>,
+}
+
+impl SharedChunks {
+    fn shard(&self, coord: ChunkCoordinate) -> &Shard {
+        let hash = coord.0.x.wrapping_mul(73_856_093)
+            ^ coord.0.y.wrapping_mul(19_349_663)
+            ^ coord.0.z.wrapping_mul(83_492_791);
+        &self.shards[hash.rem_euclid(SHARDS as i64) as usize]
+    }
+
+    pub fn get(&self, coord: ChunkCoordinate) -> Option<Arc<ChunkData>> {
+        self.shard(coord).read().unwrap().get(&coord).cloned()
+    }
+
+    pub fn contains(&self, coord: ChunkCoordinate) -> bool {
+        self.shard(coord).read().unwrap().contains_key(&coord)
+    }
+
+    /// The data of each chunk next to `coord`, in the order of `ChunkCoordinate::adjacent`.
+    pub fn adjacent(&self, coord: ChunkCoordinate) -> Vec<Option<Arc<ChunkData>>> {
+        coord
+            .adjacent()
+            .into_iter()
+            .map(|adj| self.get(adj))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.read().unwrap().len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(super) fn insert(&self, coord: ChunkCoordinate, data: Arc<ChunkData>) {
+        self.shard(coord).write().unwrap().insert(coord, data);
+    }
+
+    pub(super) fn remove(&self, coord: ChunkCoordinate) {
+        self.shard(coord).write().unwrap().remove(&coord);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use bevy::math::{I64Vec3, U16Vec3};
+
+    use crate::{
+        block::BlockType,
+        chunks::chunk::{ChunkCoordinate, ChunkData},
+        world::World,
+    };
+
+    #[test]
+    fn test_shared_chunks_follow_world() {
+        let mut world = World::new(0);
+        let shared = world.shared_chunks();
+        let coord = ChunkCoordinate(I64Vec3::new(0, 0, 0));
+        world.insert_chunk(coord, ChunkData::default());
+        world.insert_chunk(ChunkCoordinate(I64Vec3::new(1, 0, 0)), ChunkData::default());
+        assert_eq!(2, shared.len());
+
+        world.set_block(I64Vec3::new(1, 2, 3), BlockType::Stone);
+        let data = shared.get(coord).unwrap();
+        assert_eq!(BlockType::Stone, data.get_block_at(U16Vec3::new(1, 2, 3)));
+        let adjacent = shared.adjacent(coord);
+        assert_eq!(1, adjacent.iter().flatten().count());
+
+        world.clear_chunk(coord);
+        assert!(!shared.contains(coord));
+    }
+
+    #[test]
+    fn test_shared_chunks_read_from_threads() {
+        let mut world = World::new(0);
+        for x in 0..8 {
+            world.insert_chunk(ChunkCoordinate(I64Vec3::new(x, 0, 0)), ChunkData::default());
+        }
+        let shared = world.shared_chunks();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    (0..8)
+                        .filter(|x| shared.contains(ChunkCoordinate(I64Vec3::new(*x, 0, 0))))
+                        .count()
+                })
+            })
+            .collect();
+        world.insert_chunk(ChunkCoordinate(I64Vec3::new(8, 0, 0)), ChunkData::default());
+        for reader in readers {
+            assert_eq!(8, reader.join().unwrap());
+        }
+        assert!(Arc::ptr_eq(
+            &world
+                .get_chunk_data(ChunkCoordinate(I64Vec3::ZERO))
+                .unwrap(),
+            &shared.get(ChunkCoordinate(I64Vec3::ZERO)).unwrap()
+        ));
+    }
+}